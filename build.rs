@@ -0,0 +1,34 @@
+//! Records the distro-spec version fsdbg is compiled against
+//! (`fsdbg spec-version`, the `spec` field of reports).
+
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    // Same path as the distro-spec dependency in Cargo.toml
+    let manifest = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap())
+        .join("../../distro-spec/Cargo.toml");
+    println!("cargo:rerun-if-changed={}", manifest.display());
+
+    let version = fs::read_to_string(&manifest)
+        .ok()
+        .and_then(|toml| package_version(&toml))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=DISTRO_SPEC_VERSION={}", version);
+}
+
+/// `version` from the `[package]` table.
+fn package_version(toml: &str) -> Option<String> {
+    let mut in_package = false;
+    for line in toml.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+        } else if in_package {
+            if let Some(value) = line.strip_prefix("version") {
+                let value = value.trim_start().strip_prefix('=')?.trim();
+                return Some(value.trim_matches('"').to_string());
+            }
+        }
+    }
+    None
+}
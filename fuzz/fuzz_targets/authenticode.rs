@@ -0,0 +1,11 @@
+//! Authenticode certificate tables and the PKCS#7 signers in them
+
+#![no_main]
+
+use fsdbg::authenticode;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = authenticode::certificate_table(data);
+    let _ = authenticode::signers(data);
+});
@@ -0,0 +1,33 @@
+//! CPIO parsing and reads of every entry, strict and lenient
+
+#![no_main]
+
+use fsdbg::cpio::{CpioReader, Limits, ParseOptions};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Small enough that a declared size can't make the fuzzer run out of memory
+    let limits = Limits {
+        max_entry: 1 << 20,
+        max_total: 16 << 20,
+        ..Default::default()
+    };
+    for lenient in [false, true] {
+        let options = ParseOptions {
+            lenient,
+            limits,
+            ..Default::default()
+        };
+        let Ok(reader) = CpioReader::from_reader(data, options) else {
+            continue;
+        };
+        let reader = reader.with_symlink_resolution();
+        let _ = reader.stats();
+        let _ = reader.duplicates();
+        let _ = reader.file_kinds();
+        for entry in reader.entries() {
+            let _ = reader.symlink_target_exists(entry);
+            let _ = reader.read_file(&entry.path);
+        }
+    }
+});
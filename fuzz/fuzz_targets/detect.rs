@@ -0,0 +1,11 @@
+//! Format detection from a file header
+
+#![no_main]
+
+use fsdbg::detect::probe_header;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = probe_header(data, None);
+    let _ = probe_header(data, Some("img"));
+});
@@ -0,0 +1,10 @@
+//! Device tree header and root node parsing
+
+#![no_main]
+
+use fsdbg::dtb::DeviceTree;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = DeviceTree::parse(data);
+});
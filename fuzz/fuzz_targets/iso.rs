@@ -0,0 +1,12 @@
+//! Native ISO 9660 listing: volume descriptors, directory records and
+//! Rock Ridge entries with their CE continuations
+
+#![no_main]
+
+use fsdbg::prelude::IsoReader;
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = IsoReader::list_dirs(Cursor::new(data), &["boot", "EFI"]);
+});
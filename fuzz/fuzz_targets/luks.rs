@@ -0,0 +1,10 @@
+//! LUKS1/LUKS2 header parsing
+
+#![no_main]
+
+use fsdbg::luks;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = luks::parse(data);
+});
@@ -0,0 +1,20 @@
+//! PE headers, section lookup (UKIs) and content identification
+
+#![no_main]
+
+use fsdbg::magic::FileKind;
+use fsdbg::pe::{self, PeImage};
+use fsdbg::uki;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = FileKind::identify(data);
+    if uki::is_pe_header(data) {
+        let _ = uki::section(data, uki::INITRD_SECTION);
+        let _ = uki::cmdline(data);
+    }
+    if let Ok(image) = PeImage::parse(data) {
+        let _ = image.problems(pe::MACHINE_AMD64, false);
+        let _ = image.problems(pe::MACHINE_ARM64, true);
+    }
+});
@@ -0,0 +1,207 @@
+//! Target architecture of an artifact
+//!
+//! Checklist paths are written for x86_64 (`usr/lib64`, `BOOTX64.EFI`,
+//! `boot/vmlinuz`). [`Arch::path`] maps them onto another architecture, and
+//! the `detect_*` functions pick the architecture from the artifact itself:
+//! ELF machine types for archives, EFI loader names for ISOs.
+
+use crate::cpio::CpioReader;
+use crate::iso::IsoReader;
+use std::fmt;
+
+/// Architecture an artifact is built for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Arch {
+    #[default]
+    X86_64,
+    Aarch64,
+}
+
+/// ELF `e_machine` values
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+/// Binaries that tell the architecture of a tree, most reliable first
+const PROBE_PATHS: &[&str] = &[
+    "usr/lib/systemd/systemd",
+    "usr/bin/bash",
+    "usr/bin/busybox",
+    "bin/busybox",
+    "init",
+];
+
+impl Arch {
+    pub const ALL: [Arch; 2] = [Arch::X86_64, Arch::Aarch64];
+
+    pub fn parse_name(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "x86_64" | "x86-64" | "amd64" | "x64" => Some(Arch::X86_64),
+            "aarch64" | "arm64" => Some(Arch::Aarch64),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x86_64",
+            Arch::Aarch64 => "aarch64",
+        }
+    }
+
+    /// Directory holding the system libraries
+    pub fn lib_dir(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "usr/lib64",
+            Arch::Aarch64 => "usr/lib",
+        }
+    }
+
+    /// Fallback EFI loader name in `EFI/BOOT/`
+    pub fn efi_bootloader(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "BOOTX64.EFI",
+            Arch::Aarch64 => "BOOTAA64.EFI",
+        }
+    }
+
+    /// Suffix of per-architecture EFI binary names (`mmx64.efi`, `grubaa64.efi`)
+    pub fn efi_suffix(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x64",
+            Arch::Aarch64 => "aa64",
+        }
+    }
+
+    /// Kernel image file name
+    pub fn kernel_name(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "vmlinuz",
+            Arch::Aarch64 => "Image",
+        }
+    }
+
+    /// False for x86_64 paths with no counterpart on this architecture: the
+    /// `lib64` directory and its merged-usr symlink.
+    pub fn has(self, path: &str) -> bool {
+        let path = path.trim_matches('/');
+        self == Arch::X86_64 || (path != "lib64" && path != "usr/lib64")
+    }
+
+    /// Map a path written for x86_64 onto this architecture.
+    pub fn path(self, path: &str) -> String {
+        let x86 = Arch::X86_64;
+        if self == x86 {
+            return path.to_string();
+        }
+        if let Some(rest) = path.strip_prefix(&format!("{}/", x86.lib_dir())) {
+            return format!("{}/{}", self.lib_dir(), rest);
+        }
+        if let Some(dir) = path.strip_suffix(x86.efi_bootloader()) {
+            return format!("{}{}", dir, self.efi_bootloader());
+        }
+        if let Some(dir) = path.strip_suffix(x86.kernel_name()) {
+            if dir.is_empty() || dir.ends_with('/') {
+                return format!("{}{}", dir, self.kernel_name());
+            }
+        }
+        path.to_string()
+    }
+
+    /// Architecture of an ELF `e_machine` value.
+    pub fn from_elf_machine(machine: u16) -> Option<Self> {
+        match machine {
+            EM_X86_64 => Some(Arch::X86_64),
+            EM_AARCH64 => Some(Arch::Aarch64),
+            _ => None,
+        }
+    }
+
+    /// Architecture of a 64-bit little-endian ELF image, from its header.
+    pub fn from_elf(data: &[u8]) -> Option<Self> {
+        if data.len() < 20 || &data[..4] != b"\x7fELF" {
+            return None;
+        }
+        Self::from_elf_machine(u16::from_le_bytes([data[18], data[19]]))
+    }
+
+    /// Architecture of a fallback EFI loader name (`BOOTAA64.EFI`).
+    pub fn from_efi_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|arch| name.eq_ignore_ascii_case(arch.efi_bootloader()))
+    }
+}
+
+impl fmt::Display for Arch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Architecture of the binaries in an archive, if any could be read.
+///
+/// Well-known binaries are tried first, then the first ELF in `usr/bin`.
+pub fn detect_cpio(reader: &CpioReader) -> Option<Arch> {
+    let fallback = reader
+        .files()
+        .filter(|e| e.path.starts_with("usr/bin/"))
+        .map(|e| e.path.as_str());
+    PROBE_PATHS
+        .iter()
+        .copied()
+        .filter(|path| reader.get(path).is_some_and(|e| e.is_file()))
+        .chain(fallback)
+        .find_map(|path| reader.read_file(path).ok().and_then(|d| Arch::from_elf(&d)))
+}
+
+/// Architecture of an ISO, from the EFI loaders it ships. An ISO with
+/// loaders for several architectures counts as x86_64.
+pub fn detect_iso(reader: &IsoReader) -> Option<Arch> {
+    let found: Vec<Arch> = reader
+        .entries()
+        .iter()
+        .filter_map(|e| e.path.strip_prefix("/EFI/BOOT/"))
+        .filter_map(Arch::from_efi_name)
+        .collect();
+    match found[..] {
+        [] => None,
+        [arch] => Some(arch),
+        _ => Some(Arch::X86_64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_mapping() {
+        let arm = Arch::Aarch64;
+        assert_eq!(
+            arm.path("usr/lib64/security/pam_unix.so"),
+            "usr/lib/security/pam_unix.so"
+        );
+        assert_eq!(arm.path("/EFI/BOOT/BOOTX64.EFI"), "/EFI/BOOT/BOOTAA64.EFI");
+        assert_eq!(arm.path("boot/vmlinuz"), "boot/Image");
+        assert_eq!(arm.path("usr/bin/bash"), "usr/bin/bash");
+        assert!(!arm.has("lib64"));
+        assert!(!arm.has("usr/lib64"));
+        assert!(Arch::X86_64.has("lib64"));
+        assert_eq!(
+            Arch::X86_64.path("usr/lib64/libc.so.6"),
+            "usr/lib64/libc.so.6"
+        );
+    }
+
+    #[test]
+    fn test_detect() {
+        let mut header = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x02\0".to_vec();
+        header.extend_from_slice(&EM_AARCH64.to_le_bytes());
+        assert_eq!(Arch::from_elf(&header), Some(Arch::Aarch64));
+        assert_eq!(Arch::from_elf(b"#!/bin/sh\n"), None);
+        assert_eq!(Arch::from_efi_name("bootaa64.efi"), Some(Arch::Aarch64));
+        assert_eq!(Arch::from_efi_name("grubx64.efi"), None);
+        assert_eq!(Arch::parse_name("arm64"), Some(Arch::Aarch64));
+    }
+}
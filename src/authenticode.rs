@@ -0,0 +1,311 @@
+//! Authenticode signatures of PE images
+//!
+//! Secure Boot firmware loads an EFI binary only if it carries a PKCS#7
+//! signature its db (or shim's MOK list) trusts. The signature sits in the
+//! PE certificate table, found through the security data directory (see
+//! [`crate::pe`]); its signers are read natively from the DER, without
+//! openssl or sbverify.
+//!
+//! The signature itself isn't verified against the image digest or any
+//! key: a signed binary here is one a signer put a signature on, which is
+//! what tells a signed build from one that skipped sbsign.
+
+use crate::error::FsdbgError;
+use crate::pe::PeImage;
+
+/// Index of the security (certificate table) data directory
+const SECURITY_DIRECTORY: usize = 4;
+
+/// `WIN_CERT_TYPE_PKCS_SIGNED_DATA`
+const PKCS_SIGNED_DATA: u16 = 0x0002;
+
+/// Header of a `WIN_CERTIFICATE` entry: length, revision, type
+const WIN_CERTIFICATE_HEADER: usize = 8;
+
+/// 1.2.840.113549.1.7.2 (PKCS#7 signedData)
+const SIGNED_DATA_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+
+/// 2.5.4.3 (commonName)
+const COMMON_NAME_OID: &[u8] = &[0x55, 0x04, 0x03];
+
+/// A signer of an Authenticode signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signer {
+    /// CN of the signing certificate's subject
+    pub subject: Option<String>,
+    /// CN of its issuer (the CA the firmware or shim has to trust)
+    pub issuer: Option<String>,
+}
+
+impl std::fmt::Display for Signer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.subject.as_deref().unwrap_or("(no CN)"))?;
+        if let Some(ref issuer) = self.issuer {
+            write!(f, " (issued by {})", issuer)?;
+        }
+        Ok(())
+    }
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// The certificate table of a PE image; None if it has none (unsigned).
+pub fn certificate_table(data: &[u8]) -> Result<Option<&[u8]>, FsdbgError> {
+    let Some((offset, size)) = PeImage::parse(data)?.data_directory(SECURITY_DIRECTORY) else {
+        return Ok(None);
+    };
+    // The security directory holds a file offset, not an RVA
+    let (offset, size) = (offset as usize, size as usize);
+    offset
+        .checked_add(size)
+        .and_then(|end| data.get(offset..end))
+        .map(Some)
+        .ok_or_else(|| {
+            FsdbgError::invalid_format(format!(
+                "Certificate table ({} bytes at {:#x}) is past the end of the image",
+                size, offset
+            ))
+        })
+}
+
+/// Signers of the Authenticode signatures of a PE image; empty if it is
+/// unsigned.
+pub fn signers(data: &[u8]) -> Result<Vec<Signer>, FsdbgError> {
+    let Some(mut table) = certificate_table(data)? else {
+        return Ok(Vec::new());
+    };
+    let mut signers = Vec::new();
+    while table.len() >= WIN_CERTIFICATE_HEADER {
+        let length = u32_at(table, 0).unwrap_or(0) as usize;
+        let kind = u16_at(table, 6).unwrap_or(0);
+        let Some(certificate) = table
+            .get(..length)
+            .filter(|_| length >= WIN_CERTIFICATE_HEADER)
+            .map(|entry| &entry[WIN_CERTIFICATE_HEADER..])
+        else {
+            return Err(FsdbgError::invalid_format(format!(
+                "Certificate entry of {} bytes overruns the certificate table",
+                length
+            )));
+        };
+        if kind == PKCS_SIGNED_DATA {
+            signers.extend(pkcs7_signers(certificate).ok_or_else(|| {
+                FsdbgError::invalid_format("Certificate table entry is not PKCS#7 signed data")
+            })?);
+        }
+        // Entries are 8-byte aligned
+        let next = length.next_multiple_of(8);
+        if next == 0 || next >= table.len() {
+            break;
+        }
+        table = &table[next..];
+    }
+    Ok(signers)
+}
+
+/// Split one DER element off `data`: its tag, contents and what follows.
+fn der(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let len = rest[..n].iter().fold(0, |len, b| len << 8 | *b as usize);
+        (len, &rest[n..])
+    };
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
+
+/// The elements of a constructed DER value's contents.
+fn children(mut data: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut children = Vec::new();
+    while let Some((tag, contents, rest)) = der(data) {
+        children.push((tag, contents));
+        data = rest;
+    }
+    children
+}
+
+/// The commonName in an X.501 Name.
+fn common_name(name: &[u8]) -> Option<String> {
+    children(name)
+        .into_iter()
+        .filter(|(tag, _)| *tag == 0x31)
+        .flat_map(|(_, set)| children(set))
+        .find_map(|(_, attribute)| match children(attribute)[..] {
+            [(0x06, oid), (_, value)] if oid == COMMON_NAME_OID => {
+                Some(String::from_utf8_lossy(value).into_owned())
+            }
+            _ => None,
+        })
+}
+
+/// Signers of a PKCS#7 SignedData ContentInfo, matched to the certificates
+/// it embeds by issuer and serial number.
+fn pkcs7_signers(data: &[u8]) -> Option<Vec<Signer>> {
+    let (0x30, content_info, _) = der(data)? else {
+        return None;
+    };
+    let [(0x06, oid), (0xa0, explicit), ..] = children(content_info)[..] else {
+        return None;
+    };
+    if oid != SIGNED_DATA_OID {
+        return None;
+    }
+    let (0x30, signed_data, _) = der(explicit)? else {
+        return None;
+    };
+    let elements = children(signed_data);
+    // (issuer, serial, subject) of each embedded certificate
+    let certificates: Vec<(&[u8], &[u8], &[u8])> = elements
+        .iter()
+        .filter(|(tag, _)| *tag == 0xa0)
+        .flat_map(|(_, set)| children(set))
+        .filter_map(|(_, certificate)| {
+            let (_, tbs) = *children(certificate).first()?;
+            let mut fields = children(tbs);
+            if fields.first()?.0 == 0xa0 {
+                fields.remove(0);
+            }
+            match fields[..] {
+                [(0x02, serial), _, (0x30, issuer), _, (0x30, subject), ..] => {
+                    Some((issuer, serial, subject))
+                }
+                _ => None,
+            }
+        })
+        .collect();
+    let (_, signer_infos) = elements.iter().rev().find(|(tag, _)| *tag == 0x31)?;
+
+    Some(
+        children(signer_infos)
+            .into_iter()
+            .map(|(_, signer_info)| {
+                let issuer_serial = match children(signer_info)[..] {
+                    [_, (0x30, sid), ..] => match children(sid)[..] {
+                        [(0x30, issuer), (0x02, serial)] => Some((issuer, serial)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let certificate = issuer_serial.and_then(|(issuer, serial)| {
+                    certificates
+                        .iter()
+                        .find(|(i, s, _)| *i == issuer && *s == serial)
+                });
+                Signer {
+                    subject: certificate.and_then(|(_, _, subject)| common_name(subject)),
+                    issuer: issuer_serial.and_then(|(issuer, _)| common_name(issuer)),
+                }
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// DER element with a short or long form length.
+    fn tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        if contents.len() < 0x80 {
+            out.push(contents.len() as u8);
+        } else {
+            out.push(0x82);
+            out.extend((contents.len() as u16).to_be_bytes());
+        }
+        out.extend(contents);
+        out
+    }
+
+    fn name(cn: &str) -> Vec<u8> {
+        let attribute = [tlv(0x06, COMMON_NAME_OID), tlv(0x0c, cn.as_bytes())].concat();
+        tlv(0x30, &tlv(0x31, &tlv(0x30, &attribute)))
+    }
+
+    /// Minimal PE32+ image with `table` as its certificate table.
+    fn pe(table: &[u8]) -> Vec<u8> {
+        let mut image = vec![0u8; 0x200];
+        image[..2].copy_from_slice(b"MZ");
+        image[0x3c] = 0x40;
+        image[0x40..0x44].copy_from_slice(b"PE\0\0");
+        let optional = 0x40 + 24;
+        image[optional..optional + 2].copy_from_slice(&0x20bu16.to_le_bytes());
+        image[optional + 108] = 16;
+        let entry = optional + 112 + SECURITY_DIRECTORY * 8;
+        image[entry..entry + 4].copy_from_slice(&0x200u32.to_le_bytes());
+        image[entry + 4..entry + 8].copy_from_slice(&(table.len() as u32).to_le_bytes());
+        image.extend(table);
+        image
+    }
+
+    #[test]
+    fn test_signers() {
+        let serial = tlv(0x02, &[0x01, 0x23]);
+        let issuer = name("LevitateOS Secure Boot CA");
+        let tbs = [
+            tlv(0xa0, &tlv(0x02, &[2])),
+            serial.clone(),
+            tlv(0x30, &[]),
+            issuer.clone(),
+            tlv(0x30, &[]),
+            name("LevitateOS Secure Boot Signing"),
+        ]
+        .concat();
+        let certificate = tlv(0x30, &tlv(0x30, &tbs));
+        let signer_info = tlv(
+            0x30,
+            &[tlv(0x02, &[1]), tlv(0x30, &[issuer, serial].concat())].concat(),
+        );
+        let signed_data = [
+            tlv(0x02, &[1]),
+            tlv(0x31, &[]),
+            tlv(0x30, &[]),
+            tlv(0xa0, &certificate),
+            tlv(0x31, &signer_info),
+        ]
+        .concat();
+        let pkcs7 = tlv(
+            0x30,
+            &[
+                tlv(0x06, SIGNED_DATA_OID),
+                tlv(0xa0, &tlv(0x30, &signed_data)),
+            ]
+            .concat(),
+        );
+        let mut table = Vec::new();
+        table.extend((pkcs7.len() as u32 + 8).to_le_bytes());
+        table.extend(0x0200u16.to_le_bytes());
+        table.extend(PKCS_SIGNED_DATA.to_le_bytes());
+        table.extend(&pkcs7);
+
+        let signers = signers(&pe(&table)).unwrap();
+        assert_eq!(
+            signers,
+            [Signer {
+                subject: Some("LevitateOS Secure Boot Signing".to_string()),
+                issuer: Some("LevitateOS Secure Boot CA".to_string()),
+            }]
+        );
+        assert_eq!(
+            signers[0].to_string(),
+            "LevitateOS Secure Boot Signing (issued by LevitateOS Secure Boot CA)"
+        );
+
+        assert!(super::signers(&pe(&[])).unwrap().is_empty());
+        assert!(super::signers(b"not a PE image").is_err());
+        table.truncate(table.len() - 4);
+        assert!(super::signers(&pe(&table)).is_err());
+    }
+}
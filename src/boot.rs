@@ -0,0 +1,443 @@
+//! Boot smoke test
+//!
+//! Boots an ISO or qcow2 image headless under QEMU (UEFI firmware, serial
+//! console on a pipe) and watches the console for a login prompt or a
+//! caller-supplied marker. Static checks can't prove an artifact boots; this
+//! catches the kernel panics, emergency shells and silent hangs they miss.
+//!
+//! qcow2 images are booted with `snapshot=on`, so the image is never written.
+
+use crate::arch::Arch;
+use crate::checklist::{CheckCategory, CheckResult};
+use crate::error::FsdbgError;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Console text of a getty waiting for a user
+pub const LOGIN_MARKER: &str = " login:";
+
+/// Console text that means the boot failed, and how to report it
+const FAILURE_MARKERS: &[(&str, &str)] = &[
+    ("Kernel panic - not syncing", "Kernel panic"),
+    (
+        "You are in emergency mode",
+        "Dropped to the emergency shell",
+    ),
+    ("Entering emergency mode", "Dropped to the emergency shell"),
+    (
+        "Give root password for maintenance",
+        "Dropped to the emergency shell",
+    ),
+    ("No bootable device", "Firmware found no bootable device"),
+];
+
+/// Console lines kept in a failure message
+const TAIL_LINES: usize = 15;
+
+/// UEFI firmware images, by architecture, in the places distributions put them
+const FIRMWARE: &[(Arch, &str)] = &[
+    (Arch::X86_64, "/usr/share/edk2/ovmf/OVMF_CODE.fd"),
+    (Arch::X86_64, "/usr/share/edk2/x64/OVMF_CODE.4m.fd"),
+    (Arch::X86_64, "/usr/share/OVMF/OVMF_CODE_4M.fd"),
+    (Arch::X86_64, "/usr/share/OVMF/OVMF_CODE.fd"),
+    (Arch::X86_64, "/usr/share/qemu/ovmf-x86_64.bin"),
+    (Arch::Aarch64, "/usr/share/edk2/aarch64/QEMU_EFI.fd"),
+    (Arch::Aarch64, "/usr/share/AAVMF/AAVMF_CODE.fd"),
+    (Arch::Aarch64, "/usr/share/qemu-efi-aarch64/QEMU_EFI.fd"),
+    (Arch::Aarch64, "/usr/share/qemu/aavmf-aarch64-code.bin"),
+];
+
+/// What is booted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Medium {
+    Iso,
+    Qcow2,
+}
+
+impl Medium {
+    /// Classify `path` by the format detection registry.
+    pub fn detect(path: &Path) -> Result<Self, FsdbgError> {
+        match crate::detect::probe(path)?.first().map(|m| m.detector.name) {
+            Some("ISO 9660") => Ok(Medium::Iso),
+            Some("qcow2") => Ok(Medium::Qcow2),
+            Some(name) => Err(FsdbgError::unsupported_format(format!(
+                "{} (boot-test boots ISO and qcow2 images)",
+                name
+            ))
+            .with_path(path)),
+            None => Err(FsdbgError::invalid_format("Could not detect format").with_path(path)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Medium::Iso => "iso",
+            Medium::Qcow2 => "qcow2",
+        }
+    }
+}
+
+/// How a boot test ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// The marker appeared on the console
+    Booted { marker: String, after: Duration },
+    /// A failure marker appeared first
+    Failed(String),
+    /// Nothing conclusive before the timeout
+    TimedOut(Duration),
+    /// QEMU exited (or failed to start) before the marker appeared
+    Exited(String),
+}
+
+/// Result of a boot test
+#[derive(Debug, Clone)]
+pub struct BootOutcome {
+    pub verdict: Verdict,
+    /// Everything written to the serial console
+    pub console: Vec<u8>,
+}
+
+impl BootOutcome {
+    /// The last lines of console output.
+    pub fn tail(&self, lines: usize) -> String {
+        let text = String::from_utf8_lossy(&self.console);
+        let all: Vec<&str> = text
+            .lines()
+            .map(|l| l.trim_end_matches('\r'))
+            .filter(|l| !l.trim().is_empty())
+            .collect();
+        all[all.len().saturating_sub(lines)..].join("\n")
+    }
+
+    /// Convert into a check result for embedding in a verification report.
+    pub fn to_check_result(&self, medium: Medium) -> CheckResult {
+        let item = format!("boot: {} under QEMU", medium.name());
+        let failure = match self.verdict {
+            Verdict::Booted { ref marker, after } => {
+                return CheckResult::pass(
+                    format!("{} ({:?} after {:.0}s)", item, marker, after.as_secs_f64()),
+                    CheckCategory::Other,
+                );
+            }
+            Verdict::Failed(ref reason) => reason.clone(),
+            Verdict::TimedOut(timeout) => format!("Not booted within {}s", timeout.as_secs()),
+            Verdict::Exited(ref reason) => reason.clone(),
+        };
+        let tail = self.tail(TAIL_LINES);
+        let message = if tail.is_empty() {
+            format!("{}; the serial console stayed silent", failure)
+        } else {
+            format!("{}; console ended with:\n{}", failure, tail)
+        };
+        let hint = if tail.is_empty() {
+            "Check that the kernel command line has console=ttyS0 (console=ttyAMA0 on aarch64)"
+        } else {
+            "Rerun with --serial-log to keep the whole console output"
+        };
+        CheckResult::fail(item, CheckCategory::Other, message).with_hint(hint)
+    }
+}
+
+/// Scans serial console output for the markers as it arrives.
+pub struct ConsoleWatch {
+    markers: Vec<String>,
+    console: Vec<u8>,
+    /// Console bytes already searched, less a marker's length of overlap
+    scanned: usize,
+}
+
+impl ConsoleWatch {
+    pub fn new(markers: &[String]) -> Self {
+        Self {
+            markers: markers.to_vec(),
+            console: Vec::new(),
+            scanned: 0,
+        }
+    }
+
+    /// Add console output. Some(Ok(marker)) once a marker has appeared,
+    /// Some(Err(reason)) once a failure marker has.
+    pub fn feed(&mut self, chunk: &[u8]) -> Option<Result<String, String>> {
+        self.console.extend_from_slice(chunk);
+        let longest = self
+            .markers
+            .iter()
+            .map(String::len)
+            .chain(FAILURE_MARKERS.iter().map(|(m, _)| m.len()))
+            .max()
+            .unwrap_or(0);
+        let start = self.scanned.saturating_sub(longest);
+        let window = &self.console[start..];
+        self.scanned = self.console.len();
+
+        let failure = FAILURE_MARKERS
+            .iter()
+            .filter_map(|(marker, reason)| Some((find(window, marker.as_bytes())?, *reason)))
+            .min();
+        let found = self
+            .markers
+            .iter()
+            .filter_map(|marker| Some((find(window, marker.as_bytes())?, marker)))
+            .min();
+        match (found, failure) {
+            (Some((at, _)), Some((failed_at, reason))) if failed_at < at => {
+                Some(Err(reason.to_string()))
+            }
+            (Some((_, marker)), _) => Some(Ok(marker.clone())),
+            (None, Some((_, reason))) => Some(Err(reason.to_string())),
+            (None, None) => None,
+        }
+    }
+
+    pub fn into_console(self) -> Vec<u8> {
+        self.console
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return None;
+    }
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// A QEMU boot of one image
+#[derive(Debug, Clone)]
+pub struct BootTest {
+    pub medium: Medium,
+    pub arch: Arch,
+    pub timeout: Duration,
+    /// Console text that counts as booted (any one of them)
+    pub markers: Vec<String>,
+    /// Guest memory in MiB
+    pub memory: u32,
+    /// UEFI firmware; found in the usual places if None
+    pub firmware: Option<PathBuf>,
+    /// Use KVM when /dev/kvm is usable (TCG otherwise)
+    pub kvm: bool,
+}
+
+impl BootTest {
+    pub fn new(medium: Medium, arch: Arch) -> Self {
+        Self {
+            medium,
+            arch,
+            timeout: Duration::from_secs(120),
+            markers: vec![LOGIN_MARKER.to_string()],
+            memory: 2048,
+            firmware: None,
+            kvm: true,
+        }
+    }
+
+    /// The firmware to boot with.
+    pub fn firmware(&self) -> Result<PathBuf, FsdbgError> {
+        if let Some(ref firmware) = self.firmware {
+            if !firmware.exists() {
+                return Err(FsdbgError::file_not_found(firmware));
+            }
+            return Ok(firmware.clone());
+        }
+        FIRMWARE
+            .iter()
+            .filter(|(arch, _)| *arch == self.arch)
+            .map(|(_, path)| PathBuf::from(path))
+            .find(|path| path.exists())
+            .ok_or_else(|| {
+                FsdbgError::tool_missing(
+                    format!("UEFI firmware for {}", self.arch.name()),
+                    match self.arch {
+                        Arch::X86_64 => "Install edk2-ovmf (ovmf on Debian), or pass --firmware.",
+                        Arch::Aarch64 => {
+                            "Install edk2-aarch64 (qemu-efi-aarch64 on Debian), or pass --firmware."
+                        }
+                    },
+                )
+            })
+    }
+
+    /// Whether the test would run under KVM.
+    pub fn uses_kvm(&self) -> bool {
+        self.kvm
+            && std::env::consts::ARCH == self.arch.name()
+            && fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open("/dev/kvm")
+                .is_ok()
+    }
+
+    /// The QEMU command line for booting `image`.
+    pub fn command(&self, image: &Path) -> Result<Command, FsdbgError> {
+        let firmware = self.firmware()?;
+        // Option values escape commas by doubling them
+        let escape = |path: &Path| path.to_string_lossy().replace(',', ",,");
+
+        let mut qemu = Command::new(format!("qemu-system-{}", self.arch.name()));
+        match self.arch {
+            Arch::X86_64 => qemu.args(["-machine", "q35"]).args([
+                "-drive".to_string(),
+                format!(
+                    "if=pflash,format=raw,unit=0,readonly=on,file={}",
+                    escape(&firmware)
+                ),
+            ]),
+            Arch::Aarch64 => qemu.args(["-machine", "virt"]).arg("-bios").arg(&firmware),
+        };
+        if self.uses_kvm() {
+            qemu.args(["-accel", "kvm", "-cpu", "host"]);
+        } else {
+            qemu.args(["-accel", "tcg", "-cpu", "max"]);
+        }
+        qemu.args(["-m", &self.memory.to_string(), "-smp", "2"])
+            .args(["-display", "none", "-monitor", "none", "-serial", "stdio"])
+            .args(["-no-reboot", "-nic", "user,model=virtio-net-pci"]);
+        match self.medium {
+            Medium::Iso => qemu
+                .args(["-device", "virtio-scsi-pci,id=scsi"])
+                .args(["-device", "scsi-cd,drive=cd,bootindex=0"])
+                .arg("-drive")
+                .arg(format!(
+                    "if=none,id=cd,media=cdrom,format=raw,readonly=on,file={}",
+                    escape(image)
+                )),
+            Medium::Qcow2 => qemu.arg("-drive").arg(format!(
+                "if=virtio,format=qcow2,snapshot=on,file={}",
+                escape(image)
+            )),
+        };
+        qemu.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        Ok(qemu)
+    }
+
+    /// Boot `image` and watch the console until a marker, a failure, the
+    /// timeout, or QEMU exiting.
+    pub fn run(&self, image: &Path) -> Result<BootOutcome, FsdbgError> {
+        let qemu_name = format!("qemu-system-{}", self.arch.name());
+        let mut qemu = self.command(image)?.spawn().map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FsdbgError::tool_missing(
+                &qemu_name,
+                match self.arch {
+                    Arch::X86_64 => "Install qemu-system-x86.",
+                    Arch::Aarch64 => "Install qemu-system-aarch64.",
+                },
+            ),
+            _ => FsdbgError::external_tool_failed(&qemu_name, e.to_string()),
+        })?;
+
+        // The console is read on a thread so the timeout holds while it's quiet
+        let (tx, rx) = mpsc::channel();
+        let mut stdout = qemu.stdout.take().expect("piped stdout");
+        let reader = std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = stdout.read(&mut buf) {
+                if n == 0 || tx.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut stderr = qemu.stderr.take().expect("piped stderr");
+        let errors = std::thread::spawn(move || {
+            let mut errors = String::new();
+            let _ = stderr.read_to_string(&mut errors);
+            errors
+        });
+
+        let started = Instant::now();
+        let mut watch = ConsoleWatch::new(&self.markers);
+        let verdict = loop {
+            let left = self.timeout.saturating_sub(started.elapsed());
+            match rx.recv_timeout(left) {
+                Ok(chunk) => match watch.feed(&chunk) {
+                    Some(Ok(marker)) => {
+                        break Verdict::Booted {
+                            marker,
+                            after: started.elapsed(),
+                        }
+                    }
+                    Some(Err(reason)) => break Verdict::Failed(reason),
+                    None => {}
+                },
+                Err(mpsc::RecvTimeoutError::Timeout) => break Verdict::TimedOut(self.timeout),
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    let status = qemu.wait()?;
+                    break Verdict::Exited(format!(
+                        "QEMU exited ({}) before a login prompt",
+                        status
+                    ));
+                }
+            }
+        };
+        let _ = qemu.kill();
+        let _ = qemu.wait();
+        let _ = reader.join();
+        let errors = errors.join().unwrap_or_default();
+        let verdict = match verdict {
+            Verdict::Exited(reason) if !errors.trim().is_empty() => {
+                Verdict::Exited(format!("{}: {}", reason, errors.trim()))
+            }
+            verdict => verdict,
+        };
+        // Output that arrived after the verdict still belongs in the log
+        for chunk in rx.try_iter() {
+            watch.feed(&chunk);
+        }
+
+        Ok(BootOutcome {
+            verdict,
+            console: watch.into_console(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_console_watch() {
+        let mut watch = ConsoleWatch::new(&[LOGIN_MARKER.to_string()]);
+        assert_eq!(watch.feed(b"[    0.000000] Linux version 6.12\r\n"), None);
+        // A marker split across reads is still found
+        assert_eq!(watch.feed(b"\r\nlevitate lo"), None);
+        assert_eq!(watch.feed(b"gin: "), Some(Ok(LOGIN_MARKER.to_string())));
+
+        let mut watch = ConsoleWatch::new(&[LOGIN_MARKER.to_string()]);
+        let panic = watch.feed(b"VFS: Unable to mount root fs\nKernel panic - not syncing: VFS\n");
+        assert_eq!(panic, Some(Err("Kernel panic".to_string())));
+
+        let outcome = BootOutcome {
+            verdict: Verdict::TimedOut(Duration::from_secs(5)),
+            console: watch.into_console(),
+        };
+        let result = outcome.to_check_result(Medium::Iso);
+        assert!(!result.passed);
+        assert!(result
+            .message
+            .unwrap()
+            .ends_with("VFS: Unable to mount root fs\nKernel panic - not syncing: VFS"));
+    }
+
+    #[test]
+    fn test_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let firmware = dir.path().join("OVMF_CODE.fd");
+        fs::write(&firmware, b"").unwrap();
+        let mut test = BootTest::new(Medium::Qcow2, Arch::X86_64);
+        test.firmware = Some(firmware);
+        test.kvm = false;
+
+        let command = test.command(Path::new("/images/a,b.qcow2")).unwrap();
+        assert_eq!(command.get_program(), "qemu-system-x86_64");
+        let args: Vec<_> = command.get_args().map(|a| a.to_string_lossy()).collect();
+        assert!(args.contains(&"if=virtio,format=qcow2,snapshot=on,file=/images/a,,b.qcow2".into()));
+        assert!(args.contains(&"tcg".into()));
+    }
+}
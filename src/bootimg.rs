@@ -0,0 +1,192 @@
+//! Android boot image (boot.img) inspection
+//!
+//! The embedded spin ships kernel and ramdisk concatenated behind an
+//! Android boot image header: the `ANDROID!` magic, the component sizes,
+//! and page-aligned components after it. Headers v0-v2 carry their page
+//! size; v3 and v4 fix it at 4096. The ramdisk is read in place like a
+//! UKI's initrd, so initramfs checklists run on the boot.img itself.
+//!
+//! Only gzip and uncompressed ramdisks can be read; lz4 ones (the other
+//! common choice) have to be extracted and decompressed first.
+
+use crate::error::FsdbgError;
+use crate::ByteRange;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+pub const MAGIC: &[u8; 8] = b"ANDROID!";
+
+/// Page size of v3+ headers
+const V3_PAGE_SIZE: u32 = 4096;
+
+/// Bytes of the header the fields below live in (v0-v2 is the longest)
+const HEADER_LEN: usize = 1660;
+
+/// Legacy and frame lz4 magics, little-endian
+const LZ4_MAGICS: [[u8; 4]; 2] = [[0x02, 0x21, 0x4c, 0x18], [0x04, 0x22, 0x4d, 0x18]];
+
+/// What an Android boot image holds, and where
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootImage {
+    pub header_version: u32,
+    pub page_size: u32,
+    pub kernel: ByteRange,
+    pub ramdisk: ByteRange,
+    /// Kernel command line, extra command line appended (v0-v2)
+    pub cmdline: String,
+}
+
+impl BootImage {
+    /// Parse a boot image header; `file_len` bounds the components.
+    pub fn parse(header: &[u8], file_len: u64) -> Result<Self, FsdbgError> {
+        if !is_boot_img_header(header) {
+            return Err(FsdbgError::invalid_format("Not an Android boot image"));
+        }
+        if header.len() < HEADER_LEN {
+            return Err(FsdbgError::invalid_format("Boot image header is truncated"));
+        }
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes(header[offset..offset + 4].try_into().expect("4 bytes"))
+        };
+        let header_version = u32_at(40);
+        let (page_size, cmdline) = match header_version {
+            0..=2 => {
+                let mut cmdline = header_string(&header[64..576]);
+                cmdline.push_str(&header_string(&header[608..1632]));
+                (u32_at(36), cmdline)
+            }
+            3 | 4 => (V3_PAGE_SIZE, header_string(&header[44..1580])),
+            version => {
+                return Err(FsdbgError::unsupported_format(format!(
+                    "Android boot image header version {}",
+                    version
+                )))
+            }
+        };
+        if !page_size.is_power_of_two() || !(2048..=65536).contains(&page_size) {
+            return Err(FsdbgError::invalid_format(format!(
+                "Boot image page size {} is not a power of two from 2048 to 65536",
+                page_size
+            )));
+        }
+
+        // Header page, kernel, ramdisk, each padded to a page
+        let page = page_size as u64;
+        let kernel = ByteRange::new(page, Some(u32_at(8) as u64));
+        let ramdisk_size = match header_version {
+            0..=2 => u32_at(16),
+            _ => u32_at(12),
+        } as u64;
+        let ramdisk = ByteRange::new(
+            page + (u32_at(8) as u64).next_multiple_of(page),
+            Some(ramdisk_size),
+        );
+        for (name, range) in [("kernel", kernel), ("ramdisk", ramdisk)] {
+            if range.offset + range.length.unwrap_or(0) > file_len {
+                return Err(FsdbgError::invalid_format(format!(
+                    "Boot image {} runs past the end of the file",
+                    name
+                )));
+            }
+        }
+        Ok(Self {
+            header_version,
+            page_size,
+            kernel,
+            ramdisk,
+            cmdline,
+        })
+    }
+
+    /// Read and parse the header of the boot image at `path`.
+    pub fn open(path: &Path) -> Result<Self, FsdbgError> {
+        let mut file = File::open(path).map_err(|_| FsdbgError::file_not_found(path))?;
+        let file_len = file.metadata()?.len();
+        let mut header = Vec::new();
+        (&mut file)
+            .take(HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+        Self::parse(&header, file_len).map_err(|e| e.with_path(path))
+    }
+}
+
+/// NUL-padded header string.
+fn header_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+}
+
+/// Check for the boot image magic.
+pub fn is_boot_img_header(header: &[u8]) -> bool {
+    header.starts_with(MAGIC)
+}
+
+/// True if the file at `path` is an Android boot image.
+pub fn is_boot_img(path: &Path) -> Result<bool, FsdbgError> {
+    let mut header = Vec::new();
+    File::open(path)
+        .map_err(|_| FsdbgError::file_not_found(path))?
+        .take(MAGIC.len() as u64)
+        .read_to_end(&mut header)?;
+    Ok(is_boot_img_header(&header))
+}
+
+/// Locate the ramdisk of a boot image, refusing compression fsdbg can't read.
+pub fn ramdisk_range(path: &Path) -> Result<ByteRange, FsdbgError> {
+    let image = BootImage::open(path)?;
+    if image.ramdisk.length == Some(0) {
+        return Err(FsdbgError::missing_required(
+            "ramdisk (a v3+ boot image may keep it in vendor_boot)",
+        )
+        .with_path(path));
+    }
+    let mut magic = [0u8; 4];
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(image.ramdisk.offset))?;
+    file.read_exact(&mut magic)?;
+    if LZ4_MAGICS.contains(&magic) {
+        return Err(FsdbgError::unsupported_format(
+            "lz4-compressed ramdisk; copy it out with extract-initrd, decompress it with lz4 -d and verify that",
+        )
+        .with_path(path));
+    }
+    Ok(image.ramdisk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A v0 header for a 5000-byte kernel and 300-byte ramdisk, 2048-byte pages.
+    fn header(version: u32) -> Vec<u8> {
+        let mut header = vec![0u8; HEADER_LEN];
+        header[..8].copy_from_slice(MAGIC);
+        header[8..12].copy_from_slice(&5000u32.to_le_bytes());
+        let ramdisk_at = if version < 3 { 16 } else { 12 };
+        header[ramdisk_at..ramdisk_at + 4].copy_from_slice(&300u32.to_le_bytes());
+        header[36..40].copy_from_slice(&2048u32.to_le_bytes());
+        header[40..44].copy_from_slice(&version.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut v0 = header(0);
+        v0[64..76].copy_from_slice(b"console=ttyS");
+        v0[608..610].copy_from_slice(b"0 ");
+        let image = BootImage::parse(&v0, 1 << 20).unwrap();
+        assert_eq!(image.kernel, ByteRange::new(2048, Some(5000)));
+        assert_eq!(image.ramdisk, ByteRange::new(2048 + 6144, Some(300)));
+        assert_eq!(image.cmdline, "console=ttyS0");
+
+        // v3 ignores the v0 page size field: pages are 4096
+        let image = BootImage::parse(&header(3), 1 << 20).unwrap();
+        assert_eq!(image.ramdisk, ByteRange::new(4096 + 8192, Some(300)));
+
+        assert!(BootImage::parse(&header(0), 8000).is_err());
+        assert!(BootImage::parse(&header(7), 1 << 20).is_err());
+        assert!(BootImage::parse(b"ANDROID!", 1 << 20).is_err());
+        assert!(!is_boot_img_header(b"MZ"));
+    }
+}
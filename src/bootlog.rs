@@ -0,0 +1,355 @@
+//! Serial console log analysis
+//!
+//! Classifies the failure signatures a boot leaves on the console (kernel
+//! panics, the emergency target, a root device that never appeared, PAM
+//! errors, ...) into findings, each naming the checklist rules that cover
+//! the artifact component it usually comes down to. Given the verification
+//! report of the same artifact, a finding also points at the rules that
+//! actually failed there.
+
+use crate::checklist::{CheckCategory, CheckResult, ChecklistType, VerificationReport};
+use crate::checklist::{CheckCategory as C, ChecklistType as T};
+use std::fmt;
+
+/// A checklist rule: results of one checklist, in one category, whose item
+/// starts with a prefix (empty for the whole category)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub checklist: ChecklistType,
+    pub category: CheckCategory,
+    pub item: &'static str,
+}
+
+impl Rule {
+    const fn new(checklist: ChecklistType, category: CheckCategory, item: &'static str) -> Self {
+        Self {
+            checklist,
+            category,
+            item,
+        }
+    }
+
+    /// Whether `result`, from a report of `checklist`, falls under this rule.
+    pub fn covers(&self, checklist: &str, result: &CheckResult) -> bool {
+        checklist == self.checklist.name()
+            && result.category == self.category
+            && result.item.starts_with(self.item)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}*",
+            self.checklist.key(),
+            self.category.key(),
+            self.item
+        )
+    }
+}
+
+/// A known failure signature
+#[derive(Debug)]
+pub struct Signature {
+    pub id: &'static str,
+    /// Console text that identifies it (any one of them)
+    pub patterns: &'static [&'static str],
+    /// What it usually means
+    pub summary: &'static str,
+    pub rules: &'static [Rule],
+    /// Signatures that explain this one better when both appear
+    pub unless: &'static [&'static str],
+}
+
+/// Failure signatures, most specific first
+pub const SIGNATURES: &[Signature] = &[
+    Signature {
+        id: "root-fs-unmountable",
+        patterns: &["VFS: Unable to mount root fs", "VFS: Cannot open root device"],
+        summary: "The kernel could not mount the root filesystem: its storage or filesystem driver is missing, or root= is wrong",
+        rules: &[
+            Rule::new(T::InstallInitramfs, C::KernelModule, ""),
+            Rule::new(T::LiveInitramfs, C::KernelModule, ""),
+            Rule::new(T::Iso, C::Other, "Volume ID"),
+            Rule::new(T::Qcow2, C::EtcFile, "boot entry"),
+        ],
+        unless: &[],
+    },
+    Signature {
+        id: "no-init",
+        patterns: &[
+            "No working init found",
+            "Requested init",
+            "Attempted to kill init",
+            "Failed to execute /init",
+        ],
+        summary: "The kernel found no init to run (or init died): /init or systemd is missing or can't load its libraries",
+        rules: &[
+            Rule::new(T::InstallInitramfs, C::Symlink, ""),
+            Rule::new(T::InstallInitramfs, C::Binary, "usr/lib/systemd/systemd"),
+            Rule::new(T::LiveInitramfs, C::Binary, ""),
+            Rule::new(T::Rootfs, C::Symlink, "usr/sbin/init"),
+            Rule::new(T::Rootfs, C::Library, ""),
+        ],
+        unless: &[],
+    },
+    Signature {
+        id: "kernel-panic",
+        patterns: &["Kernel panic - not syncing"],
+        summary: "The kernel panicked",
+        rules: &[
+            Rule::new(T::InstallInitramfs, C::KernelModule, ""),
+            Rule::new(T::InstallInitramfs, C::Binary, ""),
+        ],
+        unless: &["root-fs-unmountable", "no-init"],
+    },
+    Signature {
+        id: "root-device-missing",
+        patterns: &[
+            "Timed out waiting for device",
+            "Could not find the root block device",
+            "Gave up waiting for root file system device",
+        ],
+        summary: "The root device never appeared: udev rules or the disk driver are missing from the initramfs, or root=/fstab name a device the disk doesn't have",
+        rules: &[
+            Rule::new(T::InstallInitramfs, C::UdevRule, ""),
+            Rule::new(T::InstallInitramfs, C::KernelModule, ""),
+            Rule::new(T::Iso, C::Other, "Volume ID"),
+            Rule::new(T::Qcow2, C::EtcFile, "boot entry"),
+            Rule::new(T::Qcow2, C::EtcFile, "etc/fstab"),
+        ],
+        unless: &[],
+    },
+    Signature {
+        id: "switch-root-failed",
+        patterns: &["Failed to switch root", "Failed to start Switch Root", "initrd-switch-root.service: Failed"],
+        summary: "The initramfs could not switch to the root filesystem",
+        rules: &[
+            Rule::new(T::InstallInitramfs, C::Binary, "usr/sbin/switch_root"),
+            Rule::new(T::InstallInitramfs, C::Unit, "initrd-switch-root"),
+            Rule::new(T::Rootfs, C::Symlink, "usr/sbin/init"),
+        ],
+        unless: &[],
+    },
+    Signature {
+        id: "missing-module",
+        patterns: &["modprobe: FATAL: Module", "Failed to insert module", "Failed to find module"],
+        summary: "A kernel module could not be loaded",
+        rules: &[
+            Rule::new(T::InstallInitramfs, C::KernelModule, ""),
+            Rule::new(T::Rootfs, C::KernelModule, ""),
+        ],
+        unless: &[],
+    },
+    Signature {
+        id: "missing-library",
+        patterns: &["error while loading shared libraries"],
+        summary: "A binary could not load a shared library",
+        rules: &[
+            Rule::new(T::Rootfs, C::Library, ""),
+            Rule::new(T::InstallInitramfs, C::Library, ""),
+        ],
+        unless: &[],
+    },
+    Signature {
+        id: "emergency-target",
+        patterns: &[
+            "You are in emergency mode",
+            "Entering emergency mode",
+            "Reached target emergency.target",
+            "Give root password for maintenance",
+        ],
+        summary: "systemd fell back to the emergency target, usually because a mount from fstab failed",
+        rules: &[
+            Rule::new(T::Rootfs, C::Config, "etc/fstab"),
+            Rule::new(T::Qcow2, C::Config, "etc/fstab"),
+            Rule::new(T::Qcow2, C::EtcFile, "etc/fstab"),
+            Rule::new(T::InstallInitramfs, C::Unit, ""),
+        ],
+        unless: &[],
+    },
+    Signature {
+        id: "pam-failure",
+        patterns: &[
+            "PAM unable to dlopen",
+            "PAM adding faulty module",
+            "Module is unknown",
+            "PAM failure",
+            "Critical error - immediate abort",
+        ],
+        summary: "PAM could not load its configuration or modules, so nobody can log in",
+        rules: &[
+            Rule::new(T::Rootfs, C::EtcFile, "etc/pam.d/"),
+            Rule::new(T::Rootfs, C::Library, "usr/lib64/security/"),
+            Rule::new(T::AuthAudit, C::Library, ""),
+            Rule::new(T::AuthAudit, C::EtcFile, ""),
+        ],
+        unless: &[],
+    },
+    Signature {
+        id: "unit-failed",
+        patterns: &["Failed to start ", "Failed to mount "],
+        summary: "A systemd unit failed",
+        rules: &[
+            Rule::new(T::Rootfs, C::Unit, ""),
+            Rule::new(T::Rootfs, C::Binary, ""),
+        ],
+        unless: &["switch-root-failed"],
+    },
+];
+
+/// A signature found in a console log
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub signature: &'static Signature,
+    /// 1-based line of the first occurrence
+    pub line: usize,
+    /// That line, without terminal escapes
+    pub excerpt: String,
+}
+
+impl Finding {
+    /// Failed results of `report` under this finding's rules.
+    pub fn failed_rules<'a>(&self, report: &'a VerificationReport) -> Vec<&'a CheckResult> {
+        report
+            .results
+            .iter()
+            .filter(|r| r.is_failure())
+            .filter(|r| {
+                self.signature
+                    .rules
+                    .iter()
+                    .any(|rule| rule.covers(&report.artifact_type, r))
+            })
+            .collect()
+    }
+
+    /// Convert into a check result, naming the rules that failed in
+    /// `report` when there is one and the rules to look at otherwise.
+    pub fn to_check_result(&self, report: Option<&VerificationReport>) -> CheckResult {
+        let failed = report.map(|r| self.failed_rules(r)).unwrap_or_default();
+        let hint = if failed.is_empty() {
+            let rules: Vec<String> = self.signature.rules.iter().map(|r| r.to_string()).collect();
+            format!("Check rules {}", rules.join(", "))
+        } else {
+            let items: Vec<&str> = failed.iter().take(5).map(|r| r.item.as_str()).collect();
+            format!(
+                "Likely cause, failed in the verification report: {}{}",
+                items.join(", "),
+                if failed.len() > items.len() {
+                    ", ..."
+                } else {
+                    ""
+                }
+            )
+        };
+        CheckResult::fail(
+            format!("boot log: {}", self.signature.id),
+            CheckCategory::Other,
+            format!(
+                "{} (line {}: {})",
+                self.signature.summary, self.line, self.excerpt
+            ),
+        )
+        .with_hint(hint)
+    }
+}
+
+/// Remove terminal escape sequences (systemd colours its status lines).
+fn strip_escapes(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else if c != '\r' {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Every signature in `console`, at its first occurrence, in log order.
+pub fn analyze(console: &[u8]) -> Vec<Finding> {
+    let text = String::from_utf8_lossy(console);
+    let mut findings: Vec<Finding> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = strip_escapes(line);
+        for signature in SIGNATURES {
+            if findings.iter().any(|f| f.signature.id == signature.id) {
+                continue;
+            }
+            if signature.patterns.iter().any(|p| line.contains(p)) {
+                findings.push(Finding {
+                    signature,
+                    line: i + 1,
+                    excerpt: line.trim().to_string(),
+                });
+            }
+        }
+    }
+    let found: Vec<&str> = findings.iter().map(|f| f.signature.id).collect();
+    findings.retain(|f| !f.signature.unless.iter().any(|id| found.contains(id)));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PANIC_LOG: &str = "\
+[    1.234] VFS: Cannot open root device \"UUID=5f0c\" or unknown-block(0,0): error -6\r
+[    1.235] Please append a correct \"root=\" boot option\r
+[    1.236] Kernel panic - not syncing: VFS: Unable to mount root fs on unknown-block(0,0)\r
+";
+
+    #[test]
+    fn test_analyze_panic() {
+        let findings = analyze(PANIC_LOG.as_bytes());
+        // The generic panic is explained by the root fs one
+        let ids: Vec<_> = findings.iter().map(|f| f.signature.id).collect();
+        assert_eq!(ids, ["root-fs-unmountable"]);
+        assert_eq!(findings[0].line, 1);
+
+        let mut report = VerificationReport::new(ChecklistType::InstallInitramfs.name());
+        report.add(CheckResult::pass(
+            "module: ext4",
+            CheckCategory::KernelModule,
+        ));
+        report.add(CheckResult::fail(
+            "module: virtio_blk",
+            CheckCategory::KernelModule,
+            "Missing",
+        ));
+        let result = findings[0].to_check_result(Some(&report));
+        assert_eq!(result.item, "boot log: root-fs-unmountable");
+        assert_eq!(
+            result.hint.as_deref(),
+            Some("Likely cause, failed in the verification report: module: virtio_blk")
+        );
+        assert!(findings[0]
+            .to_check_result(None)
+            .hint
+            .unwrap()
+            .starts_with("Check rules install-initramfs:kernel-module:*"));
+    }
+
+    #[test]
+    fn test_analyze_systemd() {
+        let log = "[\x1b[0;1;31mFAILED\x1b[0m] Failed to mount /var.\n\
+                   You are in emergency mode. After logging in, type \"journalctl -xb\"\n\
+                   login: PAM adding faulty module: /usr/lib64/security/pam_systemd.so\n";
+        let findings = analyze(log.as_bytes());
+        let ids: Vec<_> = findings.iter().map(|f| f.signature.id).collect();
+        assert_eq!(ids, ["unit-failed", "emergency-target", "pam-failure"]);
+        assert_eq!(findings[0].excerpt, "[FAILED] Failed to mount /var.");
+        assert!(analyze(b"Welcome to LevitateOS!\nlevitate login: ").is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+//! Report cache (verify --cache)
+//!
+//! Pre-push hooks re-verify the same artifacts over and over. Each verify
+//! run is stored under the artifact's SHA256 plus a key describing the
+//! checklist and options, so an unchanged artifact replays its report
+//! without re-running any check.
+//!
+//! The run also records the artifact's entries, and the latest run per
+//! artifact path is remembered. When an artifact has changed, the caller
+//! can diff it against that previous run and decide whether any check can
+//! be affected (see `verify --incremental`).
+//!
+//! Layout: `<dir>/<hash>.json` per run, `<dir>/latest-<hash>` holding the
+//! artifact SHA256 of the most recent run for a path.
+
+use crate::diff::{compare, EntryMap};
+use crate::error::{ErrorCode, FsdbgError};
+use crate::report::SavedReport;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A cached verify run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedRun {
+    pub saved: SavedReport,
+    /// Entries of the verified archive, for diffing against the next run
+    pub entries: EntryMap,
+    /// Content SHA256 per regular file (CPIO only; empty otherwise)
+    #[serde(default)]
+    pub digests: BTreeMap<String, String>,
+}
+
+impl CachedRun {
+    /// Paths whose entry or content differs between two runs, sorted.
+    ///
+    /// Content changes are only seen when both runs have digests.
+    pub fn changed_paths(
+        &self,
+        entries: &EntryMap,
+        digests: &BTreeMap<String, String>,
+    ) -> Vec<String> {
+        let mut changed: BTreeSet<String> = compare(&self.entries, entries)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        for (path, digest) in digests {
+            if self.digests.get(path).is_some_and(|old| old != digest) {
+                changed.insert(path.clone());
+            }
+        }
+        changed.into_iter().collect()
+    }
+}
+
+/// Directory of cached runs
+pub struct ReportCache {
+    dir: PathBuf,
+}
+
+impl ReportCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `$XDG_CACHE_HOME/fsdbg`, or `~/.cache/fsdbg`.
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_CACHE_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".cache")))
+            .map(|d| d.join("fsdbg"))
+    }
+
+    /// Run recorded for exactly this artifact content and key.
+    pub fn get(&self, sha256: &str, key: &str) -> Option<CachedRun> {
+        let json = fs::read(self.run_path(sha256, key)).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    /// Most recent run recorded for this artifact path and key, whatever
+    /// the artifact's content was then.
+    pub fn latest(&self, artifact: &Path, key: &str) -> Option<CachedRun> {
+        let sha256 = fs::read_to_string(self.latest_path(artifact, key)).ok()?;
+        self.get(sha256.trim(), key)
+    }
+
+    /// Store a run and make it the latest for its artifact path.
+    pub fn put(
+        &self,
+        artifact: &Path,
+        sha256: &str,
+        key: &str,
+        run: &CachedRun,
+    ) -> Result<(), FsdbgError> {
+        fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_vec(run).map_err(|e| {
+            FsdbgError::new(ErrorCode::IoError, format!("Cannot serialize run: {}", e))
+        })?;
+        // Write then rename so a concurrent reader never sees a partial file
+        let path = self.run_path(sha256, key);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &path)?;
+        fs::write(self.latest_path(artifact, key), sha256)?;
+        Ok(())
+    }
+
+    fn run_path(&self, sha256: &str, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", hash(&[sha256, key])))
+    }
+
+    fn latest_path(&self, artifact: &Path, key: &str) -> PathBuf {
+        let artifact = fs::canonicalize(artifact).unwrap_or_else(|_| artifact.to_path_buf());
+        let name = hash(&[&artifact.to_string_lossy(), key]);
+        self.dir.join(format!("latest-{}", name))
+    }
+}
+
+/// Hex SHA256 of NUL-separated parts.
+fn hash(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::VerificationReport;
+    use std::time::{Duration, SystemTime};
+
+    fn run(checklist: &str) -> CachedRun {
+        CachedRun {
+            saved: SavedReport::new(
+                Path::new("rootfs.img"),
+                checklist,
+                SystemTime::now(),
+                Duration::ZERO,
+                VerificationReport::new("Rootfs"),
+            ),
+            entries: EntryMap::new(),
+            digests: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_get_and_latest() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ReportCache::new(dir.path());
+        let artifact = dir.path().join("rootfs.img");
+
+        assert!(cache.get("aa", "rootfs").is_none());
+        cache
+            .put(&artifact, "aa", "rootfs", &run("rootfs"))
+            .unwrap();
+        cache
+            .put(&artifact, "bb", "rootfs", &run("rootfs"))
+            .unwrap();
+
+        assert!(cache.get("aa", "rootfs").is_some());
+        assert!(cache.get("aa", "rootfs --strict").is_none());
+        let latest = cache.latest(&artifact, "rootfs").unwrap();
+        assert_eq!(latest.saved.checklist, "rootfs");
+        assert!(cache.latest(&artifact, "iso").is_none());
+    }
+
+    #[test]
+    fn test_changed_paths() {
+        let mut old = run("rootfs");
+        old.digests.insert("etc/os-release".into(), "aa".into());
+        old.digests.insert("etc/hostname".into(), "bb".into());
+
+        let mut digests = old.digests.clone();
+        digests.insert("etc/hostname".into(), "cc".into());
+        assert_eq!(
+            old.changed_paths(&EntryMap::new(), &digests),
+            ["etc/hostname"]
+        );
+    }
+}
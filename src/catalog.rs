@@ -0,0 +1,185 @@
+//! Message catalog for report rendering (--catalog)
+//!
+//! Report labels (PASS/FAIL, section headings), category names and hints
+//! are looked up in a catalog, so a downstream build can ship translations
+//! as a TOML file instead of patching the renderer:
+//!
+//! ```toml
+//! pass = "OK"
+//! fail = "FEHLER"
+//!
+//! [categories]
+//! binary = "Programme"
+//!
+//! [subsystems]
+//! networking = "Netzwerk"
+//!
+//! [hints]
+//! "Install the kbd package into the rootfs" = "kbd-Paket ins rootfs installieren"
+//! ```
+//!
+//! Labels are keyed by name ([`LABELS`]), categories and subsystems by their
+//! report key (`etc-file`, `networking`), and hints by their English text. Anything a catalog leaves
+//! out stays English. Porcelain output is never translated.
+
+use crate::checklist::{CheckCategory, Subsystem};
+use crate::error::{ErrorCode, FsdbgError};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Label keys and their English text
+pub const LABELS: &[(&str, &str)] = &[
+    ("pass", "PASS"),
+    ("fail", "FAIL"),
+    ("skip", "SKIP"),
+    ("hint", "hint"),
+    ("verification", "Verification"),
+    ("summary", "Summary"),
+    ("all-passed", "All passed"),
+    ("root-causes", "Root causes"),
+    ("top-failures", "Top failures"),
+    ("result", "Result"),
+    ("failed", "failed"),
+    ("passed", "passed"),
+    ("skipped", "skipped"),
+    ("checks-passed", "checks passed"),
+    ("prerequisite-failed", "prerequisite failed"),
+    (
+        "more-failures",
+        "more (run with --full for the complete list)",
+    ),
+];
+
+/// Translations for report rendering; empty means English.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    labels: HashMap<String, String>,
+    categories: HashMap<String, String>,
+    subsystems: HashMap<String, String>,
+    hints: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Load a catalog file.
+    pub fn load(path: &Path) -> Result<Self, FsdbgError> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| FsdbgError::from(e).with_path(path))?;
+        Self::parse(&content).map_err(|e| e.with_path(path))
+    }
+
+    /// Parse a catalog. Unknown label, category and subsystem keys are
+    /// errors, so a typo doesn't silently leave a label untranslated.
+    pub fn parse(content: &str) -> Result<Self, FsdbgError> {
+        let invalid = |message: String| FsdbgError::new(ErrorCode::ParseError, message);
+        let table: toml::Table = content
+            .parse()
+            .map_err(|e| invalid(format!("Invalid catalog: {}", e)))?;
+
+        let strings =
+            |value: &toml::Value, what: &str| -> Result<HashMap<String, String>, FsdbgError> {
+                let table = value
+                    .as_table()
+                    .ok_or_else(|| invalid(format!("[{}] must be a table", what)))?;
+                table
+                    .iter()
+                    .map(|(key, value)| match value.as_str() {
+                        Some(text) => Ok((key.clone(), text.to_string())),
+                        None => Err(invalid(format!("{}.{} must be a string", what, key))),
+                    })
+                    .collect()
+            };
+
+        let mut catalog = Catalog::default();
+        for (key, value) in &table {
+            match (key.as_str(), value) {
+                ("categories", value) => {
+                    catalog.categories = strings(value, "categories")?;
+                    if let Some(unknown) = catalog
+                        .categories
+                        .keys()
+                        .find(|k| CheckCategory::from_key(k).is_none())
+                    {
+                        return Err(invalid(format!("Unknown category '{}'", unknown)));
+                    }
+                }
+                ("subsystems", value) => {
+                    catalog.subsystems = strings(value, "subsystems")?;
+                    if let Some(unknown) = catalog
+                        .subsystems
+                        .keys()
+                        .find(|k| Subsystem::parse_name(k).is_none())
+                    {
+                        return Err(invalid(format!("Unknown subsystem '{}'", unknown)));
+                    }
+                }
+                ("hints", value) => catalog.hints = strings(value, "hints")?,
+                (label, toml::Value::String(text)) if LABELS.iter().any(|(k, _)| *k == label) => {
+                    catalog.labels.insert(label.to_string(), text.clone());
+                }
+                (label, _) => return Err(invalid(format!("Unknown label '{}'", label))),
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Text of a label from [`LABELS`].
+    pub fn label<'a>(&'a self, key: &'a str) -> &'a str {
+        self.labels
+            .get(key)
+            .map(String::as_str)
+            .or_else(|| LABELS.iter().find(|(k, _)| *k == key).map(|(_, v)| *v))
+            .unwrap_or(key)
+    }
+
+    /// Display name of a category.
+    pub fn category(&self, category: CheckCategory) -> String {
+        match self.categories.get(category.key()) {
+            Some(name) => name.clone(),
+            None => category.to_string(),
+        }
+    }
+
+    /// Display name of a subsystem.
+    pub fn subsystem(&self, subsystem: Subsystem) -> String {
+        match self.subsystems.get(subsystem.key()) {
+            Some(name) => name.clone(),
+            None => subsystem.to_string(),
+        }
+    }
+
+    /// A hint, translated if the catalog has it.
+    pub fn hint<'a>(&'a self, hint: &'a str) -> &'a str {
+        self.hints.get(hint).map_or(hint, String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let catalog = Catalog::parse(
+            "fail = \"FEHLER\"\n\
+             [categories]\n\
+             etc-file = \"/etc-Dateien\"\n\
+             [subsystems]\n\
+             networking = \"Netzwerk\"\n\
+             [hints]\n\
+             \"Install it\" = \"Installieren\"\n",
+        )
+        .unwrap();
+        assert_eq!(catalog.label("fail"), "FEHLER");
+        assert_eq!(catalog.label("pass"), "PASS");
+        assert_eq!(catalog.category(CheckCategory::EtcFile), "/etc-Dateien");
+        assert_eq!(catalog.category(CheckCategory::Binary), "Binaries");
+        assert_eq!(catalog.subsystem(Subsystem::Networking), "Netzwerk");
+        assert_eq!(catalog.subsystem(Subsystem::Auth), "Authentication");
+        assert_eq!(catalog.hint("Install it"), "Installieren");
+        assert_eq!(catalog.hint("Remove it"), "Remove it");
+
+        assert!(Catalog::parse("fial = \"x\"").is_err());
+        assert!(Catalog::parse("[categories]\nbinaries = \"x\"").is_err());
+        assert!(Catalog::parse("[subsystems]\nnetwork = \"x\"").is_err());
+    }
+}
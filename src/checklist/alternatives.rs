@@ -0,0 +1,147 @@
+//! Alternative-style links
+//!
+//! Commands like `vi`, `python` and `sh` are links to whichever package
+//! provides them, often through etc/alternatives. A link can resolve and
+//! still land on the wrong provider (an unsupported editor, a python2
+//! left over in the build root), which nothing else notices. Each link the
+//! spec lists must resolve to one of its approved providers; passes name the
+//! whole chain so `--verbose` shows who provides what.
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use crate::reader::ArchiveReader;
+use crate::spec::Spec;
+use glob::Pattern;
+use std::collections::BTreeMap;
+
+/// Same limit as the kernel's MAXSYMLINKS
+const MAX_HOPS: usize = 40;
+
+/// Paths a link passes through: the link itself, each symlink it reaches,
+/// and the final path (which may not exist).
+pub fn chain<R: ArchiveReader + ?Sized>(reader: &R, link: &str) -> Vec<String> {
+    let Some(mut current) = reader.locate(link) else {
+        return vec![link.to_string()];
+    };
+    let mut hops = vec![current.clone()];
+    while let Some(target) = reader.link_target(&current) {
+        if hops.len() > MAX_HOPS {
+            break;
+        }
+        let next = match target.strip_prefix('/') {
+            Some(absolute) => absolute.to_string(),
+            None => match current.rsplit_once('/') {
+                Some((parent, _)) => format!("{}/{}", parent, target),
+                None => target.to_string(),
+            },
+        };
+        // Resolve the directories on the way, not the last component
+        current = reader.locate(&next).unwrap_or_else(|| normalize(&next));
+        hops.push(current.clone());
+    }
+    hops
+}
+
+/// Collapse `.` and `..` components.
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Check the spec's alternative links that the archive ships.
+pub fn check(reader: &CpioReader, spec: &Spec) -> Vec<CheckResult> {
+    let mut approved: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (link, provider) in &spec.alternatives {
+        approved.entry(link).or_default().push(provider);
+    }
+
+    approved
+        .into_iter()
+        .filter(|(link, _)| reader.get(link).is_some())
+        .map(|(link, providers)| {
+            let hops = chain(reader, link);
+            let last = hops.last().expect("chain starts at the link");
+            let shown = hops.join(" -> ");
+            if reader.get(last).is_none_or(|e| !e.is_file()) {
+                return CheckResult::fail(
+                    link,
+                    CheckCategory::Symlink,
+                    format!("Does not resolve: {}", shown),
+                )
+                .with_hint("Install the provider, or fix the link (or its etc/alternatives entry)");
+            }
+            let ok = providers
+                .iter()
+                .any(|p| Pattern::new(p).is_ok_and(|p| p.matches(last)));
+            if ok {
+                CheckResult::pass(shown, CheckCategory::Symlink)
+            } else {
+                CheckResult::fail(
+                    link,
+                    CheckCategory::Symlink,
+                    format!(
+                        "Provided by {} ({}), approved: {}",
+                        last,
+                        shown,
+                        providers.join(", ")
+                    ),
+                )
+                .with_hint("Point the link at an approved provider, or add the provider to the spec's alternatives")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::EntryKind;
+
+    struct Links(&'static [(&'static str, Option<&'static str>)]);
+
+    impl ArchiveReader for Links {
+        fn entry_kind(&self, path: &str) -> Option<EntryKind> {
+            self.0.iter().find(|(p, _)| *p == path).map(|(_, t)| {
+                if t.is_some() {
+                    EntryKind::Symlink
+                } else {
+                    EntryKind::File
+                }
+            })
+        }
+
+        fn link_target(&self, path: &str) -> Option<&str> {
+            self.0
+                .iter()
+                .find(|(p, _)| *p == path)
+                .and_then(|(_, t)| *t)
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        let links = Links(&[
+            ("usr/bin/vi", Some("/etc/alternatives/vi")),
+            ("etc/alternatives/vi", Some("../../usr/bin/vim")),
+            ("usr/bin/vim", None),
+            ("usr/bin/python", Some("python2")),
+        ]);
+        assert_eq!(
+            chain(&links, "usr/bin/vi"),
+            ["usr/bin/vi", "etc/alternatives/vi", "usr/bin/vim"]
+        );
+        assert_eq!(
+            chain(&links, "usr/bin/python"),
+            ["usr/bin/python", "usr/bin/python2"]
+        );
+    }
+}
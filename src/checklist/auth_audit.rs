@@ -20,13 +20,15 @@
 //! fsdbg verify rootfs.erofs --type auth-audit --verbose
 //! ```
 
-use super::{CheckCategory, CheckResult, VerificationReport};
+use super::{
+    check_empty_files, CheckCategory, CheckResult, Hint, Scope, Subsystem, VerificationReport,
+};
+use crate::arch::Arch;
 use crate::cpio::CpioReader;
+use crate::spec::Spec;
 
-// Import from SINGLE SOURCE OF TRUTH (auth subsystem)
-use distro_spec::shared::auth::{
-    AUTH_BIN, AUTH_SBIN, PAM_CONFIGS, PAM_MODULES, SECURITY_FILES, SHADOW_SBIN, SUDO_LIBS,
-};
+// Auth component lists come from the distro-spec snapshot the checklist is
+// given (crate::spec)
 
 // =============================================================================
 // AUTHENTICATION AUDIT SPECIFIC CONSTANTS
@@ -128,10 +130,70 @@ const RECOMMENDED_PAM_MODULES: &[(&str, &str)] = &[
 // VERIFICATION
 // =============================================================================
 
-/// Verify authentication components in a CPIO/EROFS archive.
-///
-/// Returns a detailed report of authentication readiness.
-pub fn verify(reader: &CpioReader) -> VerificationReport {
+/// Where to fix failures, by category and path prefix.
+pub const HINTS: &[Hint] = &[
+    Hint::new(CheckCategory::Binary, "", "Add it to AUTH_BIN/AUTH_SBIN/SHADOW_SBIN in distro-spec/src/shared/auth.rs"),
+    Hint::new(CheckCategory::Binary, "usr/sbin/unix_chkpwd", "Add the pam package's sbin set in recinit/systemd.rs (AUTH_SBIN in distro-spec/src/shared/auth.rs); pam_unix.so execs this exact path"),
+    Hint::new(CheckCategory::Binary, "usr/lib/systemd/", "Add it to SYSTEMD_BINARIES in distro-spec/src/shared/components.rs"),
+    Hint::new(CheckCategory::Library, "", "Add it to PAM_MODULES in distro-spec/src/shared/auth.rs"),
+    Hint::new(CheckCategory::Library, "usr/libexec/sudo/", "Add it to SUDO_LIBS in distro-spec/src/shared/auth.rs"),
+    Hint::new(CheckCategory::EtcFile, "", "Add it to ETC_FILES in distro-spec/src/shared/components.rs"),
+    Hint::new(CheckCategory::EtcFile, "etc/pam.d/", "Add it to PAM_CONFIGS in distro-spec/src/shared/auth.rs"),
+    Hint::new(CheckCategory::EtcFile, "etc/security/", "Add it to SECURITY_FILES in distro-spec/src/shared/auth.rs"),
+    Hint::new(CheckCategory::Symlink, "etc/pam.d/", "password-auth must link to system-auth; see PAM_CONFIGS in distro-spec/src/shared/auth.rs"),
+    Hint::new(CheckCategory::Symlink, "usr/sbin/init", "usr/sbin/init must link to ../lib/systemd/systemd (FHS_SYMLINKS in distro-spec/src/shared/components.rs)"),
+    Hint::new(CheckCategory::Unit, "", "Add it to the systemd unit lists in distro-spec/src/shared/components.rs"),
+];
+
+/// Paths referenced by the authentication audit.
+pub fn scope(spec: &Spec) -> Scope {
+    let mut scope = Scope::new();
+    scope.extend(
+        CRITICAL_AUTH_BINARIES
+            .iter()
+            .map(|(path, _)| path.to_string()),
+        CheckCategory::Binary,
+    );
+    scope.extend(
+        spec.auth_bin.iter().map(|b| format!("usr/bin/{}", b)),
+        CheckCategory::Binary,
+    );
+    scope.extend(
+        spec.auth_sbin
+            .iter()
+            .chain(&spec.shadow_sbin)
+            .map(|b| format!("usr/sbin/{}", b)),
+        CheckCategory::Binary,
+    );
+    scope.add("usr/lib/systemd/systemd-logind", CheckCategory::Binary);
+    scope.add(
+        "usr/lib/systemd/system/systemd-logind.service",
+        CheckCategory::Unit,
+    );
+    for arch in Arch::ALL {
+        scope.add_prefix(
+            &format!("{}/security", arch.lib_dir()),
+            CheckCategory::Library,
+        );
+    }
+    scope.extend(
+        spec.sudo_libs
+            .iter()
+            .map(|l| format!("usr/libexec/sudo/{}", l)),
+        CheckCategory::Library,
+    );
+    scope.add_prefix("etc/pam.d", CheckCategory::EtcFile);
+    scope.add_prefix("etc/security", CheckCategory::EtcFile);
+    scope.extend(
+        CRITICAL_ETC_FILES.iter().map(|(path, _)| path.to_string()),
+        CheckCategory::EtcFile,
+    );
+    scope.add("usr/sbin/init", CheckCategory::Symlink);
+    scope
+}
+
+/// Verify authentication components in an archive built for `arch`.
+pub fn verify_for(reader: &CpioReader, arch: Arch, spec: &Spec) -> VerificationReport {
     let mut report = VerificationReport::new("Authentication Audit");
 
     // =========================================================================
@@ -150,7 +212,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     }
 
     // All AUTH_BIN from distro-spec
-    for bin in AUTH_BIN {
+    for bin in &spec.auth_bin {
         let path = format!("usr/bin/{}", bin);
         if !reader.exists(&path) {
             report.add(CheckResult::fail(
@@ -164,7 +226,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     }
 
     // All AUTH_SBIN from distro-spec
-    for sbin in AUTH_SBIN {
+    for sbin in &spec.auth_sbin {
         let path = format!("usr/sbin/{}", sbin);
         if !reader.exists(&path) {
             report.add(CheckResult::fail(
@@ -178,7 +240,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     }
 
     // All SHADOW_SBIN from distro-spec (faillock, chage, etc.)
-    for sbin in SHADOW_SBIN {
+    for sbin in &spec.shadow_sbin {
         let path = format!("usr/sbin/{}", sbin);
         if !reader.exists(&path) {
             report.add(CheckResult::fail(
@@ -195,7 +257,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // 2. Critical PAM modules
     // =========================================================================
     for (module, reason) in CRITICAL_PAM_MODULES {
-        let path = format!("usr/lib64/security/{}", module);
+        let path = format!("{}/security/{}", arch.lib_dir(), module);
         if reader.exists(&path) {
             report.add(CheckResult::pass(path, CheckCategory::Library));
         } else {
@@ -208,8 +270,8 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     }
 
     // All PAM_MODULES from distro-spec
-    for module in PAM_MODULES {
-        let path = format!("usr/lib64/security/{}", module);
+    for module in &spec.pam_modules {
+        let path = format!("{}/security/{}", arch.lib_dir(), module);
         // Skip if already checked as critical
         if CRITICAL_PAM_MODULES.iter().any(|(m, _)| *m == *module) {
             continue;
@@ -241,16 +303,16 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     }
 
     // All PAM_CONFIGS from distro-spec
-    for config in PAM_CONFIGS {
+    for config in &spec.pam_configs {
         // Skip if already checked as critical
         if CRITICAL_PAM_CONFIGS.iter().any(|(c, _)| *c == *config) {
             continue;
         }
         if reader.exists(config) {
-            report.add(CheckResult::pass(*config, CheckCategory::EtcFile));
+            report.add(CheckResult::pass(config, CheckCategory::EtcFile));
         } else {
             report.add(CheckResult::fail(
-                *config,
+                config,
                 CheckCategory::EtcFile,
                 "PAM config missing",
             ));
@@ -273,16 +335,16 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     }
 
     // All SECURITY_FILES from distro-spec
-    for sec_file in SECURITY_FILES {
+    for sec_file in &spec.security_files {
         // Skip if already checked
         if CRITICAL_SECURITY_FILES.iter().any(|(f, _)| *f == *sec_file) {
             continue;
         }
         if reader.exists(sec_file) {
-            report.add(CheckResult::pass(*sec_file, CheckCategory::EtcFile));
+            report.add(CheckResult::pass(sec_file, CheckCategory::EtcFile));
         } else {
             report.add(CheckResult::fail(
-                *sec_file,
+                sec_file,
                 CheckCategory::EtcFile,
                 "Security file missing",
             ));
@@ -307,7 +369,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 6. Sudo libraries
     // =========================================================================
-    for lib in SUDO_LIBS {
+    for lib in &spec.sudo_libs {
         let path = format!("usr/libexec/sudo/{}", lib);
         if reader.exists(&path) {
             report.add(CheckResult::pass(path, CheckCategory::Library));
@@ -337,7 +399,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     }
 
     for (module, _reason) in RECOMMENDED_PAM_MODULES {
-        let path = format!("usr/lib64/security/{}", module);
+        let path = format!("{}/security/{}", arch.lib_dir(), module);
         // Skip if already checked
         if CRITICAL_PAM_MODULES.iter().any(|(m, _)| *m == *module) {
             continue;
@@ -429,9 +491,178 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
         ));
     }
 
+    // =========================================================================
+    // 11. Check for empty files in critical locations
+    // =========================================================================
+    check_empty_files(&mut report, reader, arch);
+
+    // =========================================================================
+    // 12. Password hashing policy (login.defs vs pam_unix)
+    // =========================================================================
+    let read = |path| reader.read_file_to_string(path).ok();
+    for result in hash_policy(read(LOGIN_DEFS).as_deref(), read(SYSTEM_AUTH).as_deref()) {
+        report.add(result);
+    }
+
+    // Everything audited here decides who can log in, whatever the item's
+    // name suggests (`usr/bin/su`, `root account`)
+    for result in report.results.iter_mut() {
+        result.subsystem = Some(Subsystem::Auth);
+    }
+    report.apply_hints(HINTS);
     report
 }
 
+// =============================================================================
+// PASSWORD HASHING POLICY
+// =============================================================================
+
+const LOGIN_DEFS: &str = "etc/login.defs";
+const SYSTEM_AUTH: &str = "etc/pam.d/system-auth";
+
+/// crypt(3) methods too weak to protect /etc/shadow
+const WEAK_HASHES: &[&str] = &["DES", "MD5", "BIGCRYPT"];
+
+/// pam_unix hash options, with the login.defs ENCRYPT_METHOD they match
+const PAM_UNIX_HASHES: &[(&str, &str)] = &[
+    ("md5", "MD5"),
+    ("bigcrypt", "BIGCRYPT"),
+    ("sha256", "SHA256"),
+    ("sha512", "SHA512"),
+    ("blowfish", "BCRYPT"),
+    ("yescrypt", "YESCRYPT"),
+    ("gost_yescrypt", "GOST_YESCRYPT"),
+];
+
+/// Hash method useradd, chpasswd and newusers use, from login.defs.
+///
+/// Without ENCRYPT_METHOD shadow-utils falls back to MD5 if MD5_CRYPT_ENAB
+/// is set, and to DES otherwise.
+fn login_defs_method(content: &str) -> (String, bool) {
+    let mut method = None;
+    let mut md5 = false;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some("ENCRYPT_METHOD"), Some(value)) => method = Some(value.to_uppercase()),
+            (Some("MD5_CRYPT_ENAB"), Some(value)) => md5 = value.eq_ignore_ascii_case("yes"),
+            _ => {}
+        }
+    }
+    match method {
+        Some(method) => (method, true),
+        None if md5 => ("MD5".to_string(), false),
+        None => ("DES".to_string(), false),
+    }
+}
+
+/// Hash method passwd uses, from the pam_unix `password` line.
+///
+/// None if there is no such line; Some(None) if pam_unix names no method
+/// and so takes ENCRYPT_METHOD from login.defs.
+fn pam_unix_method(content: &str) -> Option<Option<&'static str>> {
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some(rest) = line
+            .strip_prefix("password")
+            .or_else(|| line.strip_prefix("-password"))
+        else {
+            continue;
+        };
+        let mut fields = rest.split_whitespace();
+        if fields
+            .by_ref()
+            .find(|f| f.ends_with("pam_unix.so"))
+            .is_none()
+        {
+            continue;
+        }
+        // The last method option wins
+        let method = fields.rev().find_map(|option| {
+            PAM_UNIX_HASHES
+                .iter()
+                .find(|(name, _)| *name == option)
+                .map(|(_, method)| *method)
+        });
+        return Some(method);
+    }
+    None
+}
+
+/// Check login.defs and the pam_unix password line agree on a strong hash.
+///
+/// Missing files are reported by the existence checks above.
+fn hash_policy(login_defs: Option<&str>, system_auth: Option<&str>) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    let hint = "Set ENCRYPT_METHOD YESCRYPT in etc/login.defs and drop the hash option from pam_unix (or make it match)";
+    let defs_method = login_defs.map(login_defs_method);
+
+    if let Some((method, explicit)) = &defs_method {
+        let item = format!("{} (ENCRYPT_METHOD)", LOGIN_DEFS);
+        if !explicit {
+            results.push(
+                CheckResult::fail(
+                    item,
+                    CheckCategory::Config,
+                    format!("ENCRYPT_METHOD not set: shadow falls back to {}", method),
+                )
+                .with_hint(hint),
+            );
+        } else if WEAK_HASHES.contains(&method.as_str()) {
+            results.push(
+                CheckResult::fail(
+                    item,
+                    CheckCategory::Config,
+                    format!("ENCRYPT_METHOD {}: weak hash for useradd/chpasswd", method),
+                )
+                .with_hint(hint),
+            );
+        } else {
+            results.push(CheckResult::pass(item, CheckCategory::Config));
+        }
+    }
+
+    let Some(system_auth) = system_auth else {
+        return results;
+    };
+    let item = format!("{} (pam_unix password)", SYSTEM_AUTH);
+    match pam_unix_method(system_auth) {
+        None => results.push(CheckResult::fail(
+            item,
+            CheckCategory::Config,
+            "No pam_unix.so password line: passwd can't set passwords",
+        )),
+        Some(Some(method)) if WEAK_HASHES.contains(&method) => results.push(
+            CheckResult::fail(
+                item,
+                CheckCategory::Config,
+                format!("pam_unix hashes with {}: weak hash for passwd", method),
+            )
+            .with_hint(hint),
+        ),
+        Some(Some(method)) => match &defs_method {
+            Some((defs, _)) if defs != method => results.push(
+                CheckResult::fail(
+                    item,
+                    CheckCategory::Config,
+                    format!(
+                        "pam_unix hashes with {} but login.defs uses {}: passwd and useradd disagree",
+                        method, defs
+                    ),
+                )
+                .with_hint(hint),
+            ),
+            _ => results.push(CheckResult::pass(item, CheckCategory::Config)),
+        },
+        // Inherits ENCRYPT_METHOD, judged above
+        Some(None) => results.push(CheckResult::pass(item, CheckCategory::Config)),
+    }
+    results
+}
+
 // =============================================================================
 // TESTS
 // =============================================================================
@@ -439,6 +670,9 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use distro_spec::shared::auth::{
+        AUTH_BIN, AUTH_SBIN, PAM_CONFIGS, PAM_MODULES, SECURITY_FILES, SHADOW_SBIN, SUDO_LIBS,
+    };
 
     // -------------------------------------------------------------------------
     // Constant coverage tests - verify our lists are complete
@@ -677,6 +911,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_hash_policy() {
+        let failures = |defs, auth| {
+            hash_policy(defs, auth)
+                .into_iter()
+                .filter(|r| !r.passed)
+                .map(|r| r.message.unwrap_or_default())
+                .collect::<Vec<_>>()
+        };
+        let auth = "password  sufficient  pam_unix.so yescrypt shadow use_authtok\n";
+
+        assert!(failures(Some("ENCRYPT_METHOD YESCRYPT\n"), Some(auth)).is_empty());
+        assert!(failures(
+            Some("ENCRYPT_METHOD SHA512\n"),
+            Some("password sufficient pam_unix.so shadow\n")
+        )
+        .is_empty());
+        assert_eq!(
+            failures(Some("# ENCRYPT_METHOD SHA512\n"), None),
+            ["ENCRYPT_METHOD not set: shadow falls back to DES"]
+        );
+        assert_eq!(
+            failures(Some("ENCRYPT_METHOD SHA512\n"), Some(auth)),
+            ["pam_unix hashes with YESCRYPT but login.defs uses SHA512: passwd and useradd disagree"]
+        );
+        assert_eq!(
+            failures(
+                Some("ENCRYPT_METHOD MD5\n"),
+                Some("password [success=1 default=ignore] pam_unix.so md5\n")
+            ),
+            [
+                "ENCRYPT_METHOD MD5: weak hash for useradd/chpasswd",
+                "pam_unix hashes with MD5: weak hash for passwd"
+            ]
+        );
+    }
+
     #[test]
     fn test_shells_file_is_critical() {
         // /etc/shells is required for valid shell checking
@@ -0,0 +1,177 @@
+//! Checklist selection from artifact contents (verify --type auto)
+//!
+//! Scripts verifying every artifact of a build shouldn't have to map file
+//! names to checklist types. [`select`] picks one from what the artifact is
+//! and holds, first match wins:
+//!
+//! | Found | Checklist |
+//! |-------|-----------|
+//! | ISO 9660 image | iso |
+//! | qcow2 image | qcow2 |
+//! | `init` and busybox | live-initramfs |
+//! | systemd and `sysroot/` | install-initramfs |
+//! | systemd and the core FHS directories | rootfs |
+//!
+//! CPIO archives, the initrd embedded in a UKI or Android boot image, and
+//! staged directory trees are told apart by their paths. An artifact matching none of these needs an explicit `--type`.
+
+use super::{live_initramfs, ChecklistType};
+use crate::cpio::CpioReader;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::ArchiveFormat;
+use std::fmt;
+use std::path::Path;
+
+/// `--type` value asking for selection
+pub const AUTO: &str = "auto";
+
+/// systemd's manager; initramfs and rootfs builds both have it
+const SYSTEMD: &str = "usr/lib/systemd/systemd";
+
+/// Where the install initramfs mounts the root before switch-root
+const SYSROOT: &str = "sysroot";
+
+/// Directories only a full root filesystem has all of
+const FHS_CORE: &[&str] = &["etc", "usr/bin", "usr/lib", "var", "root"];
+
+/// The checklist picked for an artifact, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub checklist: ChecklistType,
+    pub reason: String,
+}
+
+impl Selection {
+    pub fn new(checklist: ChecklistType, reason: impl Into<String>) -> Self {
+        Self {
+            checklist,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.checklist.key(), self.reason)
+    }
+}
+
+/// Pick the checklist for the artifact at `path`.
+pub fn select(path: &Path) -> Result<Selection, FsdbgError> {
+    // Images are published from the rootfs
+    let image = if path.is_dir() {
+        crate::oci::is_image(path)
+    } else {
+        crate::tar::is_tar(path)? && crate::oci::is_image_archive(&crate::tar::list(path)?)
+    };
+    if image {
+        return Ok(Selection::new(ChecklistType::Rootfs, "container image"));
+    }
+    if path.is_dir() {
+        let reader = CpioReader::from_dir(crate::dir::DirReader::open(path)?);
+        return select_cpio(&reader).map_err(|e| e.with_path(path));
+    }
+    if let Some(range) = crate::embedded_initrd(path)? {
+        let reader = CpioReader::open_range(path, range)?;
+        return select_cpio(&reader).map_err(|e| e.with_path(path));
+    }
+    let qcow2 = crate::detect::probe(path)?
+        .first()
+        .is_some_and(|best| best.detector.name == "qcow2");
+    if qcow2 {
+        return Ok(Selection::new(ChecklistType::Qcow2, "qcow2 image"));
+    }
+    match crate::detect_format(path)? {
+        ArchiveFormat::Iso => Ok(Selection::new(ChecklistType::Iso, "ISO 9660 image")),
+        ArchiveFormat::Cpio | ArchiveFormat::CpioGzip => {
+            select_cpio(&CpioReader::open(path)?).map_err(|e| e.with_path(path))
+        }
+        ArchiveFormat::Erofs => Err(FsdbgError::unsupported_format(
+            "EROFS images have no checklist; verify the CPIO or ISO they are built into",
+        )
+        .with_path(path)),
+    }
+}
+
+/// Pick the checklist for a CPIO archive from its paths.
+pub fn select_cpio(reader: &CpioReader) -> Result<Selection, FsdbgError> {
+    let busybox = live_initramfs::BUSYBOX_BINARY;
+    if reader.exists(live_initramfs::INIT_PATH) && reader.exists(busybox) {
+        return Ok(Selection::new(
+            ChecklistType::LiveInitramfs,
+            format!("init and {}", busybox),
+        ));
+    }
+    if reader.exists(SYSTEMD) {
+        if reader.get(SYSROOT).is_some_and(|e| e.is_dir()) {
+            return Ok(Selection::new(
+                ChecklistType::InstallInitramfs,
+                format!("{} and {}/", SYSTEMD, SYSROOT),
+            ));
+        }
+        if FHS_CORE.iter().all(|dir| reader.exists(dir)) {
+            return Ok(Selection::new(
+                ChecklistType::Rootfs,
+                format!("{} and {}", SYSTEMD, FHS_CORE.join(", ")),
+            ));
+        }
+    }
+    Err(FsdbgError::new(
+        ErrorCode::InvalidArgument,
+        format!(
+            "Can't tell the checklist from the contents (no init and {}, no {} with {}/ or {}); pass --type",
+            busybox,
+            SYSTEMD,
+            SYSROOT,
+            FHS_CORE.join(", ")
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpio::ParseOptions;
+
+    fn archive(paths: &[(&str, u32)]) -> CpioReader {
+        let mut out = Vec::new();
+        for (path, mode) in paths.iter().chain(&[("TRAILER!!!", 0)]) {
+            out.extend_from_slice(b"070701");
+            for field in [0, *mode, 0, 0, 1, 0, 0, 0, 0, 0, 0] {
+                out.extend_from_slice(format!("{:08x}", field).as_bytes());
+            }
+            out.extend_from_slice(format!("{:08x}{:08x}", path.len() + 1, 0).as_bytes());
+            out.extend_from_slice(path.as_bytes());
+            out.push(0);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        CpioReader::from_reader(&out[..], ParseOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_select_cpio() {
+        let live = archive(&[("init", 0o100755), ("bin/busybox", 0o100755)]);
+        assert_eq!(
+            select_cpio(&live).unwrap().checklist,
+            ChecklistType::LiveInitramfs
+        );
+
+        let install = archive(&[(SYSTEMD, 0o100755), ("sysroot", 0o40755)]);
+        assert_eq!(
+            select_cpio(&install).unwrap().to_string(),
+            "install-initramfs (usr/lib/systemd/systemd and sysroot/)"
+        );
+
+        let mut paths: Vec<(&str, u32)> = FHS_CORE.iter().map(|dir| (*dir, 0o40755)).collect();
+        paths.push((SYSTEMD, 0o100755));
+        let rootfs = archive(&paths);
+        assert_eq!(
+            select_cpio(&rootfs).unwrap().checklist,
+            ChecklistType::Rootfs
+        );
+
+        // A systemd tree missing part of the FHS core could be anything; don't guess
+        let partial = archive(&paths[2..]);
+        assert!(select_cpio(&partial).is_err());
+    }
+}
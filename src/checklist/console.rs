@@ -0,0 +1,163 @@
+//! Console keymap and font data
+//!
+//! The live environment drops users at a console, where
+//! systemd-vconsole-setup runs loadkeys and setfont with the KEYMAP and
+//! FONT from etc/vconsole.conf. A keymap or font missing from
+//! usr/lib/kbd leaves the keyboard on the kernel's US map (or the
+//! console unreadable) with only a journal message. The kbd tools, their
+//! data directories and every map and font vconsole.conf names must exist.
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use std::collections::HashSet;
+
+pub const VCONSOLE_CONF: &str = "etc/vconsole.conf";
+pub const KBD_DATA: &str = "usr/lib/kbd";
+
+/// Tools systemd-vconsole-setup runs
+pub const KBD_TOOLS: &[&str] = &["usr/bin/loadkeys", "usr/bin/setfont"];
+
+/// Keymap used when vconsole.conf names none
+const DEFAULT_KEYMAP: &str = "us";
+
+/// Data directories, with the vconsole.conf settings that name files in them
+/// and the suffixes kbd tries
+const DATA_DIRS: &[(&str, &[&str], &[&str])] = &[
+    (
+        "keymaps",
+        &["KEYMAP", "KEYMAP_TOGGLE"],
+        &[".map.gz", ".map", ".map.xz", ".map.zst"],
+    ),
+    (
+        "consolefonts",
+        &["FONT"],
+        &[".psfu.gz", ".psf.gz", ".psfu", ".psf", ".gz", ""],
+    ),
+    (
+        "consoletrans",
+        &["FONT_MAP"],
+        &[".trans", "_to_uni.trans", ""],
+    ),
+    ("unimaps", &["FONT_UNIMAP"], &[".uni", ""]),
+];
+
+/// KEY=value settings of vconsole.conf, quotes removed.
+pub fn parse_vconsole(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            (key.trim().to_string(), value.to_string())
+        })
+        .collect()
+}
+
+/// Check the kbd tools and data, and the maps and fonts vconsole.conf names.
+pub fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    for tool in KBD_TOOLS {
+        results.push(if reader.exists(tool) {
+            CheckResult::pass(*tool, CheckCategory::Binary)
+        } else {
+            CheckResult::fail(
+                *tool,
+                CheckCategory::Binary,
+                "Missing (systemd-vconsole-setup can't apply vconsole.conf)",
+            )
+            .with_hint("Install the kbd package into the rootfs")
+        });
+    }
+
+    // Paths under usr/lib/kbd, relative to it
+    let prefix = format!("{}/", KBD_DATA);
+    let data: HashSet<String> = reader
+        .entries()
+        .iter()
+        .filter(|e| !e.is_dir())
+        .filter_map(|e| {
+            CpioReader::normalize_path(&e.path)
+                .strip_prefix(&prefix)
+                .map(String::from)
+        })
+        .collect();
+
+    let settings = match reader.read_file_to_string(VCONSOLE_CONF) {
+        Ok(content) => parse_vconsole(&content),
+        Err(_) => Vec::new(),
+    };
+    let mut wanted: Vec<(&str, String)> = Vec::new();
+    if !settings.iter().any(|(key, _)| key == "KEYMAP") {
+        wanted.push(("KEYMAP", DEFAULT_KEYMAP.to_string()));
+    }
+    for (key, value) in &settings {
+        if !value.is_empty() {
+            wanted.push((key.as_str(), value.clone()));
+        }
+    }
+
+    for (dir, keys, suffixes) in DATA_DIRS {
+        let dir_prefix = format!("{}/", dir);
+        let names: HashSet<&str> = data
+            .iter()
+            .filter_map(|p| p.strip_prefix(&dir_prefix))
+            .map(|p| p.rsplit('/').next().unwrap_or(p))
+            .collect();
+        // Keymaps and fonts are needed even without a vconsole.conf naming them
+        let item = format!("{}/{}", KBD_DATA, dir);
+        if matches!(*dir, "keymaps" | "consolefonts") {
+            results.push(if names.is_empty() {
+                CheckResult::fail(
+                    &item,
+                    CheckCategory::Directory,
+                    "No data (kbd data not installed)",
+                )
+                .with_hint("Install the kbd package's usr/lib/kbd data into the rootfs")
+            } else {
+                CheckResult::pass(&item, CheckCategory::Directory)
+            });
+        }
+        for (key, value) in wanted.iter().filter(|(key, _)| keys.contains(key)) {
+            let item = format!("{} {}={}", VCONSOLE_CONF, key, value);
+            // A path (FONT=/usr/share/...) is used as is
+            let found = if value.contains('/') {
+                reader.exists(value.trim_start_matches('/'))
+            } else {
+                suffixes
+                    .iter()
+                    .any(|suffix| names.contains(format!("{}{}", value, suffix).as_str()))
+            };
+            results.push(if found {
+                CheckResult::pass(item, CheckCategory::Config)
+            } else {
+                CheckResult::fail(
+                    item,
+                    CheckCategory::Config,
+                    format!("Not found in {}/{}", KBD_DATA, dir),
+                )
+                .with_hint("Fix the name in vconsole.conf, or include that map/font when trimming kbd data")
+            });
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vconsole() {
+        let settings =
+            parse_vconsole("# written by recinit\nKEYMAP=\"de-latin1\"\nFONT=eurlatgr\n\n");
+        assert_eq!(
+            settings,
+            [
+                ("KEYMAP".to_string(), "de-latin1".to_string()),
+                ("FONT".to_string(), "eurlatgr".to_string())
+            ]
+        );
+    }
+}
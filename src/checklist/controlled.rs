@@ -0,0 +1,195 @@
+//! Controlled directories: only known entries allowed
+//!
+//! Most checks ask whether expected paths are present; nothing notices an
+//! addition. In a few directories an addition is the failure - a stray
+//! generator in usr/lib/systemd/system-generators has broken boot, and an
+//! unknown file in etc/pam.d changes who can log in. A [`ControlledDir`]
+//! names the entries such a directory may hold; every other entry directly
+//! under it is reported as unexpected.
+//!
+//! The rootfs checklist controls the generator directory and etc/pam.d
+//! (against the spec's PAM configs). Checklist extensions control more
+//! directories, or allow more entries in these, with `[[controlled]]`.
+
+use super::{CheckCategory, CheckResult, ChecklistType, Scope};
+use crate::cpio::CpioReader;
+use crate::spec::Spec;
+use glob::Pattern;
+use std::collections::BTreeSet;
+
+/// systemd's own generators; anything else in the directory came from a package
+pub const SYSTEM_GENERATORS: &str = "usr/lib/systemd/system-generators";
+
+/// PAM service configs
+pub const PAM_D: &str = "etc/pam.d";
+
+/// A directory whose entries must all be known
+#[derive(Debug, Clone)]
+pub struct ControlledDir {
+    pub dir: String,
+    /// File name globs of the entries allowed directly under `dir`
+    pub allow: Vec<String>,
+    pub category: CheckCategory,
+    /// Hint for unexpected entries
+    pub hint: Option<String>,
+}
+
+impl ControlledDir {
+    pub fn new(dir: &str, category: CheckCategory) -> Self {
+        Self {
+            dir: dir.trim_matches('/').to_string(),
+            allow: Vec::new(),
+            category,
+            hint: None,
+        }
+    }
+
+    /// Allow entries named (or matching) `names`.
+    pub fn allow<S: AsRef<str>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.allow
+            .extend(names.into_iter().map(|n| n.as_ref().to_string()));
+        self
+    }
+
+    fn allows(&self, name: &str) -> bool {
+        self.allow
+            .iter()
+            .any(|allowed| match Pattern::new(allowed) {
+                Ok(pattern) => pattern.matches(name),
+                Err(_) => allowed == name,
+            })
+    }
+}
+
+/// Directories a checklist controls itself.
+pub fn builtin(checklist: ChecklistType, spec: &Spec) -> Vec<ControlledDir> {
+    match checklist {
+        ChecklistType::Rootfs => vec![
+            ControlledDir::new(SYSTEM_GENERATORS, CheckCategory::Unit)
+                .allow(["systemd-*-generator"]),
+            ControlledDir::new(PAM_D, CheckCategory::EtcFile).allow(
+                spec.pam_configs
+                    .iter()
+                    .filter_map(|config| config.strip_prefix("etc/pam.d/")),
+            ),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// Merge entries for the same directory: their allow-lists add up.
+pub fn merge(dirs: impl IntoIterator<Item = ControlledDir>) -> Vec<ControlledDir> {
+    let mut merged: Vec<ControlledDir> = Vec::new();
+    for dir in dirs {
+        match merged.iter_mut().find(|d| d.dir == dir.dir) {
+            Some(existing) => {
+                existing.allow.extend(dir.allow);
+                existing.hint = dir.hint.or(existing.hint.take());
+            }
+            None => merged.push(dir),
+        }
+    }
+    merged
+}
+
+/// Add the controlled directories to a checklist scope.
+pub fn extend_scope(dirs: &[ControlledDir], scope: &mut Scope) {
+    for dir in dirs {
+        scope.add_prefix(&dir.dir, dir.category);
+    }
+}
+
+/// Report entries directly under each controlled directory that it doesn't
+/// allow, given every path of the artifact. Directories that don't exist
+/// pass silently; whether they must exist is up to other checks.
+pub fn check<'a>(
+    paths: impl IntoIterator<Item = &'a str>,
+    dirs: &[ControlledDir],
+) -> Vec<CheckResult> {
+    let mut seen: Vec<BTreeSet<&str>> = vec![BTreeSet::new(); dirs.len()];
+    for path in paths {
+        let path = path.trim_start_matches("./").trim_matches('/');
+        for (dir, names) in dirs.iter().zip(seen.iter_mut()) {
+            let name = path
+                .strip_prefix(dir.dir.as_str())
+                .and_then(|rest| rest.strip_prefix('/'))
+                .and_then(|rest| rest.split('/').next());
+            if let Some(name) = name.filter(|name| !name.is_empty()) {
+                names.insert(name);
+            }
+        }
+    }
+
+    let mut results = Vec::new();
+    for (dir, names) in dirs.iter().zip(seen) {
+        if names.is_empty() {
+            continue;
+        }
+        let unexpected: Vec<&str> = names.iter().copied().filter(|n| !dir.allows(n)).collect();
+        if unexpected.is_empty() {
+            results.push(CheckResult::pass(
+                format!("{}/ (only known entries, {})", dir.dir, names.len()),
+                dir.category,
+            ));
+        }
+        for name in unexpected {
+            let hint = dir.hint.clone().unwrap_or_else(|| {
+                format!(
+                    "Drop it from the build, or allow it with a [[controlled]] entry for {} in checks.d",
+                    dir.dir
+                )
+            });
+            results.push(
+                CheckResult::fail(
+                    format!("{}/{}", dir.dir, name),
+                    dir.category,
+                    format!("Unexpected: not a known entry of {}/", dir.dir),
+                )
+                .with_hint(hint),
+            );
+        }
+    }
+    results
+}
+
+/// Check the controlled directories of a CPIO archive.
+pub fn check_cpio(reader: &CpioReader, dirs: &[ControlledDir]) -> Vec<CheckResult> {
+    check(reader.entries().iter().map(|e| e.path.as_str()), dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_controlled_dirs() {
+        let mut dirs = builtin(ChecklistType::Rootfs, Spec::builtin());
+        dirs.push(
+            ControlledDir::new(SYSTEM_GENERATORS, CheckCategory::Unit).allow(["oem-generator"]),
+        );
+        let dirs = merge(dirs);
+        assert_eq!(dirs.len(), 2);
+
+        let paths = [
+            "usr/lib/systemd/system-generators",
+            "usr/lib/systemd/system-generators/systemd-fstab-generator",
+            "usr/lib/systemd/system-generators/oem-generator",
+            "usr/lib/systemd/system-generators/stray-generator",
+            "usr/lib/systemd/system-generatorsX/ignored",
+        ];
+        let results = check(paths, &dirs);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].item,
+            "usr/lib/systemd/system-generators/stray-generator"
+        );
+        assert!(!results[0].passed);
+
+        let results = check(paths[..3].iter().copied(), &dirs);
+        assert!(results[0].passed);
+        assert_eq!(
+            results[0].item,
+            "usr/lib/systemd/system-generators/ (only known entries, 2)"
+        );
+    }
+}
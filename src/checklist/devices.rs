@@ -0,0 +1,168 @@
+//! Device node policy
+//!
+//! The kernel opens `/dev/console` for init's stdin/stdout before init runs,
+//! and plenty of early scripts write to `/dev/null`. An initramfs either
+//! ships both nodes with the right numbers or has init mount devtmpfs over
+//! `/dev` before anything needs them.
+//!
+//! The rootfs is the opposite case: `/dev` is always devtmpfs at runtime, so a
+//! device node packed into it is build-host state that leaked in (a copied
+//! `/dev` tree, a disk node from a chroot).
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::{CpioEntry, CpioReader, FileType};
+
+/// Nodes every initramfs needs: (path, major, minor), all character devices.
+pub const REQUIRED_NODES: &[(&str, u32, u32)] = &[("dev/console", 5, 1), ("dev/null", 1, 3)];
+
+/// `c 5:1` / `b 8:0` style description of a device node.
+fn describe(entry: &CpioEntry) -> String {
+    let kind = match entry.file_type {
+        FileType::BlockDevice => 'b',
+        _ => 'c',
+    };
+    format!("{} {}:{}", kind, entry.rdev_major, entry.rdev_minor)
+}
+
+fn is_device(entry: &CpioEntry) -> bool {
+    matches!(
+        entry.file_type,
+        FileType::CharDevice | FileType::BlockDevice
+    )
+}
+
+/// True if init mounts devtmpfs itself: systemd always does, a shell init
+/// does if its script says so.
+pub fn init_mounts_devtmpfs(reader: &CpioReader) -> bool {
+    let Some(init) = reader.get("init") else {
+        return false;
+    };
+    if init.is_symlink() {
+        return init
+            .link_target
+            .as_deref()
+            .is_some_and(|target| target.ends_with("systemd/systemd"));
+    }
+    init.is_file()
+        && reader
+            .read_file_to_string("init")
+            .is_ok_and(|script| script.contains("devtmpfs"))
+}
+
+/// Check the required initramfs device nodes.
+///
+/// A missing node passes when init mounts devtmpfs; a node that is present
+/// must be a character device with the expected numbers either way.
+pub fn check_required(reader: &CpioReader) -> Vec<CheckResult> {
+    let devtmpfs = init_mounts_devtmpfs(reader);
+    REQUIRED_NODES
+        .iter()
+        .map(|&(path, major, minor)| {
+            let expected = format!("c {}:{}", major, minor);
+            match reader.get(path) {
+                Some(entry)
+                    if entry.file_type == FileType::CharDevice
+                        && (entry.rdev_major, entry.rdev_minor) == (major, minor) =>
+                {
+                    CheckResult::pass(format!("{} ({})", path, expected), CheckCategory::Device)
+                }
+                Some(entry) => {
+                    let actual = if is_device(entry) {
+                        describe(entry)
+                    } else {
+                        "not a device node".to_string()
+                    };
+                    CheckResult::fail(
+                        path,
+                        CheckCategory::Device,
+                        format!("Expected {}, found {}", expected, actual),
+                    )
+                }
+                None if devtmpfs => CheckResult::pass(
+                    format!("{} (provided by devtmpfs)", path),
+                    CheckCategory::Device,
+                ),
+                None => CheckResult::fail(
+                    path,
+                    CheckCategory::Device,
+                    format!("Missing ({}) and init doesn't mount devtmpfs", expected),
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Flag device nodes other than the required ones as forbidden.
+///
+/// Only failures are returned - a clean archive adds nothing to the report.
+pub fn check_unexpected(reader: &CpioReader) -> Vec<CheckResult> {
+    reader
+        .entries()
+        .iter()
+        .filter(|e| is_device(e))
+        .filter(|e| {
+            let path = CpioReader::normalize_path(&e.path);
+            !REQUIRED_NODES.iter().any(|&(required, major, minor)| {
+                path == required
+                    && e.file_type == FileType::CharDevice
+                    && (e.rdev_major, e.rdev_minor) == (major, minor)
+            })
+        })
+        .map(|e| {
+            if e.is_whiteout() {
+                return CheckResult::fail(
+                    &e.path,
+                    CheckCategory::Forbidden,
+                    "Unexpected overlayfs whiteout (c 0:0)",
+                )
+                .with_hint("Whiteouts only mean something in an overlay layer - the tree was staged from an overlay mount's upper dir");
+            }
+            CheckResult::fail(
+                &e.path,
+                CheckCategory::Forbidden,
+                format!("Unexpected device node ({})", describe(e)),
+            )
+            .with_hint("/dev is devtmpfs at runtime - don't copy device nodes from the build host")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(path: &str, file_type: FileType, major: u32, minor: u32) -> CpioEntry {
+        CpioEntry {
+            path: path.to_string(),
+            raw_path: path.as_bytes().to_vec(),
+            size: 0,
+            mode: 0o600,
+            file_type,
+            link_target: None,
+            uid: 0,
+            gid: 0,
+            nlink: 1,
+            mtime: 0,
+            dev_major: 0,
+            dev_minor: 0,
+            rdev_major: major,
+            rdev_minor: minor,
+            digest: None,
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe(&node("dev/null", FileType::CharDevice, 1, 3)),
+            "c 1:3"
+        );
+        assert_eq!(
+            describe(&node("dev/sda", FileType::BlockDevice, 8, 0)),
+            "b 8:0"
+        );
+        assert!(!is_device(&node("dev/fifo", FileType::Fifo, 0, 0)));
+        assert!(node("etc/motd", FileType::CharDevice, 0, 0).is_whiteout());
+        assert!(!node("dev/null", FileType::CharDevice, 1, 3).is_whiteout());
+    }
+}
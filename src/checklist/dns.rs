@@ -0,0 +1,157 @@
+//! DNS resolution stack
+//!
+//! LevitateOS resolves through NetworkManager and glibc's DNS module;
+//! systemd-resolved is filtered out of the build. The expectations only
+//! skip resolved, so a rootfs that ships it anyway (or a resolv.conf that
+//! still points at its stub) passes while DNS is dead on first boot.
+
+use super::{CheckCategory, CheckResult, Subsystem};
+use crate::arch::Arch;
+use crate::cpio::CpioReader;
+
+pub const RESOLV_CONF: &str = "etc/resolv.conf";
+
+/// Where NetworkManager writes resolv.conf (rc-manager=symlink)
+const NM_RESOLV_CONF: &str = "run/NetworkManager/resolv.conf";
+
+/// systemd-resolved's stub and upstream resolv.conf files
+const RESOLVED_RESOLV_CONFS: &[&str] = &[
+    "run/systemd/resolve/stub-resolv.conf",
+    "run/systemd/resolve/resolv.conf",
+    "usr/lib/systemd/resolv.conf",
+];
+
+/// systemd-resolved enablement that must not ship (besides its NSS module).
+/// The daemon, unit and resolvectl are on the spec's deny-list, checked by
+/// the rootfs checklist.
+pub const RESOLVED_PATHS: &[&str] = &[
+    "etc/systemd/system/dbus-org.freedesktop.resolve1.service",
+    "etc/systemd/system/sysinit.target.wants/systemd-resolved.service",
+    "etc/systemd/system/multi-user.target.wants/systemd-resolved.service",
+];
+
+/// NSS modules for DNS: glibc's (required) and resolved's (forbidden)
+pub fn nss_modules(arch: Arch) -> [String; 2] {
+    [
+        format!("{}/libnss_dns.so.2", arch.lib_dir()),
+        format!("{}/libnss_resolve.so.2", arch.lib_dir()),
+    ]
+}
+
+/// Check resolv.conf, the DNS NSS module and the absence of systemd-resolved.
+pub fn check(reader: &CpioReader, arch: Arch) -> Vec<CheckResult> {
+    let resolved_hint = "systemd-resolved is filtered out of LevitateOS; drop it from the package set and its symlinks from the rootfs";
+    let mut results = vec![resolv_conf(reader)];
+
+    let [dns, resolve] = nss_modules(arch);
+    results.push(if reader.exists(&dns) {
+        CheckResult::pass(&dns, CheckCategory::Library)
+    } else {
+        CheckResult::fail(
+            &dns,
+            CheckCategory::Library,
+            "Missing (glibc can't resolve hostnames through DNS)",
+        )
+        .with_hint("libnss_dns.so.2 comes from glibc; include it when trimming libraries")
+    });
+
+    for path in RESOLVED_PATHS.iter().copied().chain([resolve.as_str()]) {
+        results.push(if reader.get(path).is_some() {
+            CheckResult::fail(
+                path,
+                CheckCategory::Forbidden,
+                "systemd-resolved shipped, but LevitateOS resolves through NetworkManager",
+            )
+            .with_hint(resolved_hint)
+        } else {
+            CheckResult::pass(
+                format!("{} (correctly absent)", path),
+                CheckCategory::Forbidden,
+            )
+        });
+    }
+    results
+        .into_iter()
+        .map(|result| result.with_subsystem(Subsystem::Networking))
+        .collect()
+}
+
+fn resolv_conf(reader: &CpioReader) -> CheckResult {
+    let hint = "Leave etc/resolv.conf out (NetworkManager creates it) or link it to ../run/NetworkManager/resolv.conf";
+    let Some(entry) = reader.get(RESOLV_CONF) else {
+        return CheckResult::pass(
+            format!("{} (absent, NetworkManager creates it)", RESOLV_CONF),
+            CheckCategory::Config,
+        );
+    };
+    if let Some(target) = entry.link_target.as_deref().filter(|_| entry.is_symlink()) {
+        return match link_problem(target) {
+            None => CheckResult::pass(
+                format!("{} -> {}", RESOLV_CONF, target),
+                CheckCategory::Config,
+            ),
+            Some(problem) => {
+                CheckResult::fail(RESOLV_CONF, CheckCategory::Config, problem).with_hint(hint)
+            }
+        };
+    }
+    // NetworkManager rewrites a regular file, but until it does the build
+    // host's resolvers are used
+    let content = reader.read_file_to_string(RESOLV_CONF).unwrap_or_default();
+    match content
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("nameserver"))
+    {
+        Some(server) => CheckResult::fail(
+            RESOLV_CONF,
+            CheckCategory::Config,
+            format!(
+                "Static file with nameserver {} (the build host's resolver?)",
+                server.trim()
+            ),
+        )
+        .with_hint(hint),
+        None => CheckResult::pass(
+            format!("{} (no static nameservers)", RESOLV_CONF),
+            CheckCategory::Config,
+        ),
+    }
+}
+
+/// Why a resolv.conf symlink target is wrong, if it is.
+fn link_problem(target: &str) -> Option<String> {
+    // Targets are relative to etc/ or absolute
+    let resolved = match target.strip_prefix('/') {
+        Some(absolute) => absolute.to_string(),
+        None => target.trim_start_matches("../").to_string(),
+    };
+    if resolved == NM_RESOLV_CONF {
+        None
+    } else if RESOLVED_RESOLV_CONFS.contains(&resolved.as_str()) {
+        Some(format!(
+            "Points to {} (systemd-resolved, which isn't shipped)",
+            target
+        ))
+    } else {
+        Some(format!(
+            "Points to {}, not NetworkManager's resolv.conf",
+            target
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_problem() {
+        assert_eq!(link_problem("../run/NetworkManager/resolv.conf"), None);
+        assert_eq!(link_problem("/run/NetworkManager/resolv.conf"), None);
+        assert_eq!(
+            link_problem("../run/systemd/resolve/stub-resolv.conf").as_deref(),
+            Some("Points to ../run/systemd/resolve/stub-resolv.conf (systemd-resolved, which isn't shipped)")
+        );
+        assert!(link_problem("/tmp/resolv.conf").is_some());
+    }
+}
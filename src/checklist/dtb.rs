@@ -0,0 +1,233 @@
+//! Device tree blobs for aarch64 artifacts
+//!
+//! A board without UEFI+ACPI firmware boots only with the device tree for
+//! it, and the blobs are built with the kernel: a tree from another kernel
+//! version may describe hardware the kernel's drivers bind differently.
+//! The kernel packages install them per version,
+//!
+//! | Layout | Directory |
+//! |--------|-----------|
+//! | Fedora/Rocky kernel-core | `boot/dtb-<version>/` (and `boot/dtb` linking to it) |
+//! | modules tree | `usr/lib/modules/<version>/dtb/` |
+//! | live ISO | `boot/dtb/` or `boot/dtb-<version>/` |
+//!
+//! and each kernel the artifact ships needs a set. Versioned sets for a
+//! kernel it doesn't ship are stale; an unversioned `boot/dtb/` serves an
+//! artifact with one kernel.
+
+use super::{CheckCategory, CheckResult, Subsystem};
+use crate::cpio::CpioReader;
+use flate2::read::GzDecoder;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+
+/// Where the unversioned set and the versioned `dtb-<version>` sets live
+pub const BOOT_DIR: &str = "boot";
+
+const MODULES_DIR: &str = "usr/lib/modules";
+
+/// Marker the kernel's banner starts with (`Linux version 6.12.0-1 (...)`)
+const BANNER: &[u8] = b"Linux version ";
+
+/// The set of device trees a `.dtb` file at `path` belongs to: its
+/// directory and the kernel version the directory is for, if it says.
+fn set_of(path: &str) -> Option<(String, Option<&str>)> {
+    if !path.ends_with(".dtb") {
+        return None;
+    }
+    if let Some(rest) = path
+        .strip_prefix(MODULES_DIR)
+        .and_then(|rest| rest.strip_prefix('/'))
+    {
+        let (version, rest) = rest.split_once('/')?;
+        return rest
+            .starts_with("dtb/")
+            .then(|| (format!("{}/{}/dtb", MODULES_DIR, version), Some(version)));
+    }
+    let rest = path.strip_prefix(BOOT_DIR)?.strip_prefix('/')?;
+    let (dir, _) = rest.split_once('/')?;
+    match dir.strip_prefix("dtb-") {
+        Some(version) => Some((format!("{}/{}", BOOT_DIR, dir), Some(version))),
+        None if dir == "dtb" => Some((format!("{}/dtb", BOOT_DIR), None)),
+        None => None,
+    }
+}
+
+/// Check the device tree sets among `files` (paths without a leading `/`)
+/// against the `kernels` versions the artifact ships. With no known kernel
+/// version, any set will do.
+pub fn check<'a>(files: impl IntoIterator<Item = &'a str>, kernels: &[String]) -> Vec<CheckResult> {
+    let mut sets: BTreeMap<String, (Option<&str>, usize)> = BTreeMap::new();
+    for path in files {
+        if let Some((dir, version)) = set_of(path) {
+            sets.entry(dir).or_insert((version, 0)).1 += 1;
+        }
+    }
+    let found = |dir: &str, count: usize| {
+        CheckResult::pass(
+            format!("{} ({} device trees)", dir, count),
+            CheckCategory::Other,
+        )
+    };
+
+    let mut results = Vec::new();
+    if sets.is_empty() {
+        results.push(
+            CheckResult::fail(
+                format!("{}/dtb", BOOT_DIR),
+                CheckCategory::Other,
+                "No device tree blobs (CRITICAL: boards without ACPI firmware won't boot)",
+            )
+            .with_hint("Install the kernel's device trees (kernel-core ships them in usr/lib/modules/<version>/dtb)"),
+        );
+    } else if kernels.is_empty() {
+        results.extend(sets.iter().map(|(dir, (_, count))| found(dir, *count)));
+    } else {
+        let unversioned = sets.iter().find(|(_, (version, _))| version.is_none());
+        for kernel in kernels {
+            let matching: Vec<_> = sets
+                .iter()
+                .filter(|(_, (version, _))| *version == Some(kernel.as_str()))
+                .collect();
+            match (matching.is_empty(), unversioned) {
+                (false, _) => {
+                    results.extend(matching.iter().map(|(dir, (_, count))| found(dir, *count)))
+                }
+                (true, Some((dir, (_, count)))) if kernels.len() == 1 => {
+                    results.push(found(dir, *count))
+                }
+                _ => results.push(
+                    CheckResult::fail(
+                        format!("{}/dtb-{}", BOOT_DIR, kernel),
+                        CheckCategory::Other,
+                        format!("No device tree blobs for kernel {}", kernel),
+                    )
+                    .with_hint("Install the device trees built with this kernel; blobs from another version may not match its drivers"),
+                ),
+            }
+        }
+        let shipped: BTreeSet<&str> = kernels.iter().map(String::as_str).collect();
+        for (dir, (version, _)) in &sets {
+            if let Some(version) = version.filter(|v| !shipped.contains(v)) {
+                results.push(CheckResult::fail(
+                    dir,
+                    CheckCategory::Other,
+                    format!(
+                        "Device trees for kernel {}, which isn't shipped ({})",
+                        version,
+                        kernels.join(", ")
+                    ),
+                ));
+            }
+        }
+    }
+    results
+        .into_iter()
+        .map(|r| r.with_subsystem(Subsystem::Boot))
+        .collect()
+}
+
+/// Check a root filesystem's device trees against its kernels, the
+/// directories in `usr/lib/modules`.
+pub fn check_cpio(reader: &CpioReader) -> Vec<CheckResult> {
+    let kernels: Vec<String> = reader
+        .entries()
+        .iter()
+        .filter(|e| e.is_dir())
+        .filter_map(|e| e.path.strip_prefix(MODULES_DIR)?.strip_prefix('/'))
+        .filter(|version| !version.contains('/'))
+        .map(str::to_string)
+        .collect();
+    check(reader.entries().iter().map(|e| e.path.as_str()), &kernels)
+}
+
+/// Kernel release from the banner in a kernel image: an arm64 `Image`, or
+/// one gzip-compressed whole (`Image.gz`) or in an EFI zboot wrapper.
+pub fn kernel_version(image: &[u8]) -> Option<String> {
+    let gzip = if image.get(4..8) == Some(b"zimg") {
+        // zboot: payload offset and size at 8 and 12, compression at 0x18
+        let word = |at: usize| {
+            let bytes = image.get(at..at + 4)?;
+            Some(u32::from_le_bytes(bytes.try_into().ok()?))
+        };
+        let (offset, size) = (word(8)? as usize, word(12)? as usize);
+        let payload = image.get(offset..offset.checked_add(size)?)?;
+        image
+            .get(0x18..0x1c)
+            .filter(|c| *c == b"gzip")
+            .map(|_| payload)
+    } else {
+        image.starts_with(&[0x1f, 0x8b]).then_some(image)
+    };
+    let decompressed;
+    let image = match gzip {
+        Some(payload) => {
+            let mut out = Vec::new();
+            // A truncated stream still holds the banner, early in .rodata
+            let _ = GzDecoder::new(payload).read_to_end(&mut out);
+            decompressed = out;
+            &decompressed[..]
+        }
+        None => image,
+    };
+    let at = image.windows(BANNER.len()).position(|w| w == BANNER)? + BANNER.len();
+    let version = image[at..]
+        .split(|b| b.is_ascii_whitespace() || *b == 0)
+        .next()?;
+    (!version.is_empty()).then(|| String::from_utf8_lossy(version).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let kernels = vec!["6.12.0".to_string()];
+        let files = [
+            "usr/lib/modules/6.12.0/dtb/broadcom/bcm2711-rpi-4-b.dtb",
+            "usr/lib/modules/6.12.0/dtb/rockchip/rk3588-rock-5b.dtb",
+            "boot/dtb-6.6.1/broadcom/bcm2711-rpi-4-b.dtb",
+            "boot/dtb-6.6.1/README",
+        ];
+        let results = check(files, &kernels);
+        let items: Vec<_> = results
+            .iter()
+            .map(|r| (r.item.as_str(), r.passed))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                ("usr/lib/modules/6.12.0/dtb (2 device trees)", true),
+                ("boot/dtb-6.6.1", false)
+            ]
+        );
+
+        // One kernel: the unversioned set is its set; with two, it's neither's
+        assert!(check(["boot/dtb/a.dtb"], &kernels).iter().all(|r| r.passed));
+        let two = ["6.12.0".to_string(), "6.13.0".to_string()];
+        assert_eq!(
+            check(["boot/dtb/a.dtb"], &two)
+                .iter()
+                .filter(|r| !r.passed)
+                .count(),
+            2
+        );
+        assert!(!check(["boot/vmlinuz"], &[])[0].passed);
+
+        let mut image =
+            b"\0\0ARM\x64 text Linux version 6.12.0-1.el10.aarch64 (mockbuild@)".to_vec();
+        assert_eq!(
+            kernel_version(&image).as_deref(),
+            Some("6.12.0-1.el10.aarch64")
+        );
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut gz, &image).unwrap();
+        assert_eq!(
+            kernel_version(&gz.finish().unwrap()).as_deref(),
+            Some("6.12.0-1.el10.aarch64")
+        );
+        image.truncate(16);
+        assert_eq!(kernel_version(&image), None);
+    }
+}
@@ -0,0 +1,294 @@
+//! EFI binaries of the live ISO
+//!
+//! Firmware loads them from two places: the ISO tree, and `efiboot.img`,
+//! the FAT image El Torito hands UEFI when booting from optical media
+//! (systemd-boot then only sees what that image carries). Existence checks
+//! pass a truncated copy, so every `*.efi` in either is parsed
+//! ([`crate::pe`]): it must be a complete PE image for the ISO's machine
+//! (or the one its name says, as in `BOOTIA32.EFI`), an EFI application
+//! (a driver under a `drivers` directory), with valid alignment. The ISO's
+//! binaries under `EFI/` that efiboot.img doesn't carry have to fit in its
+//! free space, and the copies it does carry have to be the same size.
+
+use super::{CheckCategory, CheckResult, Subsystem};
+use crate::arch::Arch;
+use crate::error::FsdbgError;
+use crate::fat;
+use crate::iso::IsoReader;
+use crate::pe::{self, PeImage};
+use crate::ScratchDir;
+use distro_spec::shared::EFIBOOT_FILENAME;
+use humansize::{format_size, BINARY};
+use std::path::Path;
+
+fn is_efi(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".efi")
+}
+
+/// Where a tree's files are read from
+enum Root<'a> {
+    Iso(&'a IsoReader),
+    /// Files copied out of a FAT image
+    Dir(ScratchDir),
+}
+
+/// Files of one place firmware boots from
+pub struct EfiTree<'a> {
+    /// Prefix of the items reported for it
+    pub label: String,
+    /// Paths relative to its root, and sizes
+    pub files: Vec<(String, u64)>,
+    /// Free space, for a FAT image
+    pub free: Option<u64>,
+    root: Root<'a>,
+}
+
+impl EfiTree<'_> {
+    /// The file at `path`, ignoring case as FAT does.
+    pub fn find(&self, path: &str) -> Option<&(String, u64)> {
+        self.files
+            .iter()
+            .find(|(file, _)| file.eq_ignore_ascii_case(path))
+    }
+
+    /// Read a file of the tree.
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, FsdbgError> {
+        match self.root {
+            Root::Iso(reader) => reader.read_file(&format!("/{}", path)),
+            Root::Dir(ref dir) => Ok(std::fs::read(dir.path().join(path))?),
+        }
+    }
+
+    /// Paths of its EFI binaries.
+    pub fn binaries(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .filter(|path| is_efi(path))
+    }
+
+    /// The files of the FAT image at `image`.
+    fn fat(label: String, image: &Path, free: u64) -> Result<Self, FsdbgError> {
+        let dir = ScratchDir::new("fsdbg-esp-")?;
+        fat::extract(image, dir.path())?;
+        Self::dir(label, dir, Some(free))
+    }
+
+    /// The files copied into `dir`, which the tree keeps until dropped.
+    pub(super) fn dir(
+        label: String,
+        dir: ScratchDir,
+        free: Option<u64>,
+    ) -> Result<Self, FsdbgError> {
+        let files = fat::files(dir.path())?
+            .into_iter()
+            .map(|file| -> Result<(String, u64), FsdbgError> {
+                let size = std::fs::metadata(dir.path().join(&file))?.len();
+                Ok((file.to_string_lossy().into_owned(), size))
+            })
+            .collect::<Result<_, FsdbgError>>()?;
+        Ok(Self {
+            label,
+            files,
+            free,
+            root: Root::Dir(dir),
+        })
+    }
+}
+
+/// The ISO tree and efiboot.img's files
+pub struct EfiTrees<'a> {
+    pub iso: EfiTree<'a>,
+    /// None without an efiboot.img; an error if it couldn't be read (mtools
+    /// missing)
+    pub esp: Option<Result<EfiTree<'a>, FsdbgError>>,
+}
+
+impl<'a> EfiTrees<'a> {
+    pub fn open(reader: &'a IsoReader) -> Self {
+        let iso = EfiTree {
+            label: String::new(),
+            files: reader
+                .entries()
+                .iter()
+                .filter(|entry| !entry.is_dir && !entry.is_symlink)
+                .map(|entry| (entry.path.trim_start_matches('/').to_string(), entry.size))
+                .collect(),
+            free: None,
+            root: Root::Iso(reader),
+        };
+        let efiboot = format!("/{}", EFIBOOT_FILENAME);
+        let esp = reader
+            .exists(&efiboot)
+            .then(|| -> Result<EfiTree<'a>, FsdbgError> {
+                let image = reader.read_file(&efiboot)?;
+                let free = fat::free_bytes(&image)?;
+                let spool = ScratchDir::new("fsdbg-efiboot-")?;
+                let spooled = spool.path().join("efiboot.img");
+                std::fs::write(&spooled, &image)?;
+                EfiTree::fat(format!("{}:", EFIBOOT_FILENAME), &spooled, free)
+            });
+        Self { iso, esp }
+    }
+
+    /// The trees that could be read.
+    pub fn trees(&self) -> impl Iterator<Item = &EfiTree<'a>> {
+        std::iter::once(&self.iso).chain(self.esp.as_ref().and_then(|esp| esp.as_ref().ok()))
+    }
+}
+
+/// Machine an EFI binary has to be built for: the one its name says
+/// (`BOOTIA32.EFI`, `grubaa64.efi`), else the ISO's.
+fn expected_machine(path: &str, arch: Arch) -> u16 {
+    let name = path.to_ascii_lowercase();
+    if name.ends_with("ia32.efi") {
+        pe::MACHINE_I386
+    } else if name.ends_with("aa64.efi") {
+        pe::MACHINE_ARM64
+    } else if name.ends_with("x64.efi") {
+        pe::MACHINE_AMD64
+    } else {
+        pe::machine_of(arch)
+    }
+}
+
+/// Validate the PE headers of every EFI binary, and the room efiboot.img
+/// has for the ISO's.
+pub fn check(trees: &EfiTrees, arch: Arch) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    for tree in trees.trees() {
+        for path in tree.binaries() {
+            let item = format!("{}/{} PE headers", tree.label, path);
+            let driver = path.to_ascii_lowercase().contains("/drivers/");
+            let problems = tree
+                .read(path)
+                .and_then(|data| PeImage::parse(&data))
+                .map(|image| image.problems(expected_machine(path, arch), driver));
+            results.push(match problems {
+                Ok(problems) if problems.is_empty() => {
+                    CheckResult::pass(item, CheckCategory::Binary)
+                }
+                Ok(problems) => CheckResult::fail(item, CheckCategory::Binary, problems.join("; ")),
+                Err(e) => CheckResult::fail(item, CheckCategory::Binary, e.to_string()),
+            });
+        }
+    }
+
+    if let Some(Ok(ref esp)) = trees.esp {
+        results.extend(check_room(&trees.iso, esp));
+    }
+    results
+        .into_iter()
+        .map(|r| r.with_subsystem(Subsystem::Boot))
+        .collect()
+}
+
+/// Compare the ISO's EFI binaries under `EFI/` with the FAT image's.
+fn check_room(iso: &EfiTree, esp: &EfiTree) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    let mut missing = Vec::new();
+    for (path, size) in &iso.files {
+        if !path.starts_with("EFI/") || !is_efi(path) {
+            continue;
+        }
+        match esp.find(path) {
+            None => missing.push((path.as_str(), *size)),
+            Some((_, copy)) if copy != size => results.push(
+                CheckResult::fail(
+                    format!("{}/{}", esp.label, path),
+                    CheckCategory::Binary,
+                    format!(
+                        "{} bytes, the ISO's copy has {} (truncated or stale)",
+                        copy, size
+                    ),
+                )
+                .with_hint("Rebuild efiboot.img from the ISO's EFI tree in leviso"),
+            ),
+            Some(_) => {}
+        }
+    }
+
+    let free = esp.free.unwrap_or_default();
+    let needed: u64 = missing.iter().map(|(_, size)| size).sum();
+    let item = format!("{} free space", EFIBOOT_FILENAME);
+    results.push(if needed > free {
+        let names: Vec<&str> = missing.iter().map(|(path, _)| *path).collect();
+        CheckResult::fail(
+            item,
+            CheckCategory::Other,
+            format!(
+                "{} free, too little for the EFI binaries it doesn't carry ({}: {})",
+                format_size(free, BINARY),
+                names.join(", "),
+                format_size(needed, BINARY)
+            ),
+        )
+        .with_hint("Size efiboot.img for every EFI binary it has to carry in leviso")
+    } else {
+        CheckResult::pass(
+            format!("{} ({} free)", item, format_size(free, BINARY)),
+            CheckCategory::Other,
+        )
+    });
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(label: &str, files: &[(&str, u64)], free: Option<u64>) -> EfiTree<'static> {
+        EfiTree {
+            label: label.to_string(),
+            files: files.iter().map(|(p, s)| (p.to_string(), *s)).collect(),
+            free,
+            root: Root::Dir(ScratchDir::new("fsdbg-test-").unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_check_room() {
+        let iso = tree(
+            "",
+            &[
+                ("EFI/BOOT/BOOTX64.EFI", 100_000),
+                ("EFI/Linux/live.efi", 60_000),
+                ("EFI/Linux/debug.efi", 60_000),
+                ("boot/vmlinuz", 10_000_000),
+            ],
+            None,
+        );
+        let esp = tree(
+            "efiboot.img:",
+            &[
+                ("EFI/BOOT/bootx64.efi", 90_000),
+                ("EFI/Linux/live.efi", 60_000),
+            ],
+            Some(50_000),
+        );
+        let results = check_room(&iso, &esp);
+        let items: Vec<_> = results
+            .iter()
+            .map(|r| (r.item.as_str(), r.passed))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                ("efiboot.img:/EFI/BOOT/BOOTX64.EFI", false),
+                ("efiboot.img free space", false)
+            ]
+        );
+
+        let roomy = tree("efiboot.img:", &[], Some(1 << 20));
+        assert!(check_room(&tree("", &[], None), &roomy)[0].passed);
+
+        assert_eq!(
+            expected_machine("EFI/BOOT/BOOTIA32.EFI", Arch::X86_64),
+            pe::MACHINE_I386
+        );
+        assert_eq!(
+            expected_machine("EFI/Linux/live.efi", Arch::Aarch64),
+            pe::MACHINE_ARM64
+        );
+    }
+}
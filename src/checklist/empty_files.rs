@@ -0,0 +1,192 @@
+//! Empty and placeholder file detection
+//!
+//! Existence checks pass a zero-byte file just as happily as a real one. A
+//! truncated unit, binary, or PAM config is always a broken build, so every
+//! CPIO checklist runs this scan over the locations where an empty regular
+//! file can never be legitimate.
+//!
+//! Locations are glob patterns (see [`DEFAULT_PATTERNS`] and
+//! [`LIB_PATTERNS`]); callers can pass extra patterns to cover
+//! artifact-specific paths.
+
+use super::{CheckCategory, CheckResult};
+use crate::arch::Arch;
+use crate::cpio::CpioReader;
+use crate::error::FsdbgError;
+use glob::{MatchOptions, Pattern};
+
+/// Locations where a zero-byte regular file indicates a broken build.
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    // Binaries
+    "bin/*",
+    "sbin/*",
+    "usr/bin/*",
+    "usr/sbin/*",
+    "usr/lib/systemd/systemd*",
+    "usr/lib/udev/*_id",
+    "init",
+    // Systemd units
+    "usr/lib/systemd/system/*.service",
+    "usr/lib/systemd/system/*.socket",
+    "usr/lib/systemd/system/*.target",
+    "usr/lib/systemd/system/*.mount",
+    "usr/lib/systemd/system/*.timer",
+    "usr/lib/systemd/system/*.path",
+    "etc/systemd/system/*.service",
+    // PAM
+    "etc/pam.d/*",
+];
+
+/// Library locations, relative to the architecture's [`Arch::lib_dir`].
+pub const LIB_PATTERNS: &[&str] = &[
+    // PAM modules
+    "security/pam_*.so",
+    // Libraries
+    "*.so*",
+];
+
+/// `*` must not cross directory boundaries, so `usr/bin/*` doesn't match
+/// everything under `usr/bin/` recursively.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Compile the default location patterns for an archive built for `arch`.
+pub fn default_patterns(arch: Arch) -> Vec<Pattern> {
+    let libs = LIB_PATTERNS
+        .iter()
+        .map(|p| format!("{}/{}", arch.lib_dir(), p));
+    DEFAULT_PATTERNS
+        .iter()
+        .map(|p| p.to_string())
+        .chain(libs)
+        .map(|p| Pattern::new(&p).expect("default empty-file pattern is valid"))
+        .collect()
+}
+
+/// Compile user-supplied location patterns.
+pub fn parse_patterns<S: AsRef<str>>(patterns: &[S]) -> Result<Vec<Pattern>, FsdbgError> {
+    patterns
+        .iter()
+        .map(|p| {
+            Pattern::new(p.as_ref()).map_err(|e| {
+                FsdbgError::invalid_format(format!("Invalid glob '{}': {}", p.as_ref(), e))
+            })
+        })
+        .collect()
+}
+
+/// Flag zero-byte regular files matching any of the patterns.
+///
+/// Only failures are returned - a clean archive adds nothing to the report.
+pub fn check(reader: &CpioReader, patterns: &[Pattern]) -> Vec<CheckResult> {
+    reader
+        .files()
+        .filter(|e| e.size == 0)
+        .filter(|e| {
+            let path = CpioReader::normalize_path(&e.path);
+            patterns
+                .iter()
+                .any(|p| p.matches_with(&path, MATCH_OPTIONS))
+        })
+        .map(|e| {
+            CheckResult::fail(
+                &e.path,
+                category_for(&e.path),
+                "Empty file (truncated or placeholder)",
+            )
+            .with_hint("Check the copy step that staged it - the source was missing or the write was cut short")
+        })
+        .collect()
+}
+
+/// Pick the report category for an empty file from its location.
+fn category_for(path: &str) -> CheckCategory {
+    let path = CpioReader::normalize_path(path);
+    if path.starts_with("usr/lib/systemd/system/") || path.starts_with("etc/systemd/system/") {
+        CheckCategory::Unit
+    } else if path.starts_with("etc/") {
+        CheckCategory::EtcFile
+    } else if path
+        .rsplit('/')
+        .next()
+        .is_some_and(|name| name.contains(".so"))
+    {
+        // Shared libraries and PAM modules, in whichever lib directory
+        CheckCategory::Library
+    } else {
+        CheckCategory::Binary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_patterns_compile() {
+        for arch in Arch::ALL {
+            assert_eq!(
+                default_patterns(arch).len(),
+                DEFAULT_PATTERNS.len() + LIB_PATTERNS.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_lib_patterns_follow_arch() {
+        let matches = |arch, path: &str| {
+            default_patterns(arch)
+                .iter()
+                .any(|p| p.matches_with(path, MATCH_OPTIONS))
+        };
+        assert!(matches(Arch::X86_64, "usr/lib64/libc.so.6"));
+        assert!(matches(Arch::X86_64, "usr/lib64/security/pam_unix.so"));
+        assert!(!matches(Arch::X86_64, "usr/lib/libc.so.6"));
+        assert!(matches(Arch::Aarch64, "usr/lib/libc.so.6"));
+        assert!(matches(Arch::Aarch64, "usr/lib/security/pam_unix.so"));
+        assert!(!matches(Arch::Aarch64, "usr/lib64/libc.so.6"));
+    }
+
+    #[test]
+    fn test_star_does_not_cross_directories() {
+        let patterns = default_patterns(Arch::X86_64);
+        let matches = |path: &str| patterns.iter().any(|p| p.matches_with(path, MATCH_OPTIONS));
+
+        assert!(matches("usr/bin/bash"));
+        assert!(matches("usr/lib/systemd/system/sshd.service"));
+        assert!(matches("etc/pam.d/system-auth"));
+        // Drop-in directories may legitimately hold empty files
+        assert!(!matches(
+            "usr/lib/systemd/system/getty@.service.d/override.conf"
+        ));
+        assert!(!matches("usr/share/doc/bash/README"));
+    }
+
+    #[test]
+    fn test_parse_patterns_rejects_invalid() {
+        assert!(parse_patterns(&["etc/*.conf"]).is_ok());
+        assert!(parse_patterns(&["etc/[*.conf"]).is_err());
+    }
+
+    #[test]
+    fn test_category_for() {
+        assert_eq!(
+            category_for("usr/lib/systemd/system/foo.service"),
+            CheckCategory::Unit
+        );
+        assert_eq!(category_for("etc/pam.d/login"), CheckCategory::EtcFile);
+        assert_eq!(category_for("usr/bin/ls"), CheckCategory::Binary);
+        assert_eq!(category_for("usr/lib64/libc.so.6"), CheckCategory::Library);
+        assert_eq!(
+            category_for("usr/lib/security/pam_unix.so"),
+            CheckCategory::Library
+        );
+        assert_eq!(
+            category_for("usr/lib/systemd/systemd-logind"),
+            CheckCategory::Binary
+        );
+    }
+}
@@ -0,0 +1,739 @@
+//! Drop-in checklist extensions (checks.d)
+//!
+//! OEM builds add their own agents, configs and branding on top of
+//! LevitateOS. Rather than forking fsdbg, they describe the extra checks in
+//! TOML files that get merged into the selected checklist:
+//!
+//! - `/etc/fsdbg/checks.d/*.toml`, in name order
+//! - `<artifact>.checks.toml` next to the artifact (e.g. `rootfs.img.checks.toml`)
+//!
+//! ```toml
+//! # Checklists this file applies to (names as for --type); all if omitted
+//! checklists = ["rootfs"]
+//!
+//! [[check]]
+//! path = "opt/oem/bin/agent"
+//! kind = "file"            # any (default), file, dir, symlink, absent
+//! category = "binary"      # report category key (default: other; absent: forbidden)
+//! subsystem = "networking" # auth, boot, networking, storage, audio (default: from the path)
+//! executable = true        # CPIO only; ISO entries carry no mode
+//! magic = "elf"             # content type (see crate::magic): elf, pe, script, text, ...
+//! hint = "Shipped by the oem-agent package"
+//!
+//! [[check]]
+//! path = "usr/bin/telnet"
+//! kind = "absent"
+//!
+//! # A glob counts matching paths: at least `min` (default 1), at most `max`
+//! [[check]]
+//! path = "opt/oem/plugins/*.so"
+//! min = 2
+//! ```
+//!
+//! Glob checks take kind `any` or `absent` (no match allowed), and no
+//! `executable` or `magic`.
+//!
+//! `[[controlled]]` entries report anything directly under a directory that
+//! isn't allowed (see [`super::controlled`]); for a directory the checklist
+//! already controls, `allow` adds to its list:
+//!
+//! ```toml
+//! [[controlled]]
+//! dir = "usr/lib/systemd/system-generators"
+//! allow = ["oem-generator"]   # file name globs
+//! category = "unit"           # default: other
+//! ```
+
+use super::controlled::ControlledDir;
+use super::patterns::{is_glob, GlobCheck};
+use super::{CheckCategory, CheckResult, ChecklistType, Scope, Subsystem};
+use crate::cpio::{CpioReader, FileType};
+use crate::error::{ErrorCode, FsdbgError};
+use crate::fixit::Fix;
+use crate::iso::IsoReader;
+use crate::magic::{self, FileKind};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// System-wide extension directory
+pub const SYSTEM_DIR: &str = "/etc/fsdbg/checks.d";
+
+/// What an extension check expects at its path
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpectedKind {
+    /// Present, whatever its type
+    #[default]
+    Any,
+    File,
+    Dir,
+    Symlink,
+    /// Must not be present
+    Absent,
+}
+
+/// One check from an extension file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtraCheck {
+    pub path: String,
+    #[serde(default)]
+    pub kind: ExpectedKind,
+    /// Category key (see [`CheckCategory::key`])
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Subsystem key (see [`Subsystem::key`])
+    #[serde(default)]
+    pub subsystem: Option<String>,
+    #[serde(default)]
+    pub executable: bool,
+    /// Content type (see [`FileKind::name`])
+    #[serde(default)]
+    pub magic: Option<String>,
+    /// Fewest paths a glob must match
+    #[serde(default)]
+    pub min: Option<usize>,
+    /// Most paths a glob may match
+    #[serde(default)]
+    pub max: Option<usize>,
+    #[serde(default)]
+    pub hint: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ControlledEntry {
+    dir: String,
+    #[serde(default)]
+    allow: Vec<String>,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    hint: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExtensionFile {
+    #[serde(default)]
+    checklists: Vec<String>,
+    #[serde(default, rename = "check")]
+    checks: Vec<ExtraCheck>,
+    #[serde(default)]
+    controlled: Vec<ControlledEntry>,
+}
+
+/// A loaded extension file
+#[derive(Debug, Clone)]
+pub struct Extension {
+    pub source: PathBuf,
+    /// Checklists it applies to; empty means all
+    pub checklists: Vec<ChecklistType>,
+    pub checks: Vec<ExtraCheck>,
+    /// Directories to control, or allow-list additions for controlled ones
+    pub controlled: Vec<ControlledDir>,
+}
+
+/// What a reader knows about an entry
+struct Node {
+    file_type: FileType,
+    /// Permission bits, if the format records them
+    mode: Option<u32>,
+}
+
+/// Sidecar extension file for an artifact: `<artifact>.checks.toml`.
+pub fn sidecar_path(artifact: &Path) -> PathBuf {
+    let mut name = artifact.as_os_str().to_owned();
+    name.push(".checks.toml");
+    PathBuf::from(name)
+}
+
+/// Extension files that apply to an artifact: [`SYSTEM_DIR`] in name order,
+/// then the artifact's sidecar. An artifact read from stdin (None) has no
+/// sidecar.
+pub fn discover(artifact: Option<&Path>) -> Vec<PathBuf> {
+    discover_in(Path::new(SYSTEM_DIR), artifact)
+}
+
+fn discover_in(system_dir: &Path, artifact: Option<&Path>) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(system_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    files.sort();
+    if let Some(sidecar) = artifact.map(sidecar_path).filter(|p| p.is_file()) {
+        files.push(sidecar);
+    }
+    files
+}
+
+/// Load and validate an extension file.
+pub fn load(path: &Path) -> Result<Extension, FsdbgError> {
+    let text = fs::read_to_string(path)?;
+    let parse_error = |message: String| {
+        FsdbgError::new(
+            ErrorCode::ParseError,
+            format!("{}: {}", path.display(), message),
+        )
+        .with_path(path)
+    };
+    let file: ExtensionFile = toml::from_str(&text).map_err(|e| parse_error(e.to_string()))?;
+
+    let checklists = file
+        .checklists
+        .iter()
+        .map(|name| {
+            ChecklistType::parse_name(name)
+                .ok_or_else(|| parse_error(format!("Unknown checklist: {}", name)))
+        })
+        .collect::<Result<_, _>>()?;
+    for check in &file.checks {
+        if let Some(key) = &check.category {
+            CheckCategory::from_key(key)
+                .ok_or_else(|| parse_error(format!("Unknown category: {}", key)))?;
+        }
+        if let Some(key) = &check.subsystem {
+            Subsystem::parse_name(key)
+                .ok_or_else(|| parse_error(format!("Unknown subsystem: {}", key)))?;
+        }
+        if check.path.trim_matches('/').is_empty() {
+            return Err(parse_error("Check with an empty path".to_string()));
+        }
+        if let Some(name) = &check.magic {
+            FileKind::parse_name(name)
+                .ok_or_else(|| parse_error(format!("Unknown magic: {}", name)))?;
+        }
+        if is_glob(&check.path) {
+            let single = !matches!(check.kind, ExpectedKind::Any | ExpectedKind::Absent);
+            if single || check.executable || check.magic.is_some() {
+                return Err(parse_error(format!(
+                    "{}: kind, executable and magic apply to single paths, not globs",
+                    check.path
+                )));
+            }
+            check.glob().map_err(|e| parse_error(e.to_string()))?;
+        } else if check.min.is_some() || check.max.is_some() {
+            return Err(parse_error(format!(
+                "{}: min and max apply to globs",
+                check.path
+            )));
+        }
+    }
+
+    let mut controlled = Vec::new();
+    for entry in file.controlled {
+        if entry.dir.trim_matches('/').is_empty() {
+            return Err(parse_error(
+                "Controlled directory with an empty path".to_string(),
+            ));
+        }
+        let category = match entry.category {
+            Some(ref key) => CheckCategory::from_key(key)
+                .ok_or_else(|| parse_error(format!("Unknown category: {}", key)))?,
+            None => CheckCategory::Other,
+        };
+        if let Some(bad) = entry
+            .allow
+            .iter()
+            .find(|glob| glob::Pattern::new(glob).is_err())
+        {
+            return Err(parse_error(format!("Invalid glob: {}", bad)));
+        }
+        let mut dir = ControlledDir::new(&entry.dir, category).allow(&entry.allow);
+        dir.hint = entry.hint;
+        controlled.push(dir);
+    }
+
+    Ok(Extension {
+        source: path.to_path_buf(),
+        checklists,
+        checks: file.checks,
+        controlled,
+    })
+}
+
+impl Extension {
+    pub fn applies_to(&self, checklist: ChecklistType) -> bool {
+        self.checklists.is_empty() || self.checklists.contains(&checklist)
+    }
+
+    /// Add the extension's paths to a checklist scope.
+    pub fn extend_scope(&self, scope: &mut Scope) {
+        for check in &self.checks {
+            match check.glob() {
+                Ok(Some(glob)) => glob.extend_scope(scope),
+                _ => scope.add(check.path.as_str(), check.category()),
+            }
+        }
+        super::controlled::extend_scope(&self.controlled, scope);
+    }
+
+    fn has_globs(&self) -> bool {
+        self.checks.iter().any(|check| is_glob(&check.path))
+    }
+
+    /// Run the extension's checks against a CPIO archive.
+    pub fn check_cpio(&self, reader: &CpioReader) -> Vec<CheckResult> {
+        let paths: Vec<&str> = match self.has_globs() {
+            true => reader.entries().iter().map(|e| e.path.as_str()).collect(),
+            false => Vec::new(),
+        };
+        self.check(
+            &paths,
+            |path| {
+                reader.get(path).map(|e| Node {
+                    file_type: e.file_type,
+                    mode: Some(e.mode),
+                })
+            },
+            |path| reader.read_file(path),
+        )
+    }
+
+    /// Run the extension's checks against an ISO image.
+    pub fn check_iso(&self, reader: &IsoReader) -> Vec<CheckResult> {
+        let paths: Vec<&str> = match self.has_globs() {
+            true => reader.entries().iter().map(|e| e.path.as_str()).collect(),
+            false => Vec::new(),
+        };
+        let lookup = |path: &str| {
+            let path = format!("/{}", path);
+            reader
+                .entries()
+                .iter()
+                .find(|e| e.path == path)
+                .map(|e| Node {
+                    file_type: if e.is_dir {
+                        FileType::Directory
+                    } else if e.is_symlink {
+                        FileType::Symlink
+                    } else {
+                        FileType::Regular
+                    },
+                    mode: None,
+                })
+        };
+        self.check(&paths, lookup, |path| {
+            reader.read_head(path, magic::HEAD_LEN)
+        })
+    }
+
+    /// `paths` lists the artifact for glob checks; `lookup` finds single
+    /// paths, and `read` their content for magic checks.
+    fn check(
+        &self,
+        paths: &[&str],
+        lookup: impl Fn(&str) -> Option<Node>,
+        read: impl Fn(&str) -> Result<Vec<u8>, FsdbgError>,
+    ) -> Vec<CheckResult> {
+        self.checks
+            .iter()
+            .map(|check| {
+                let path = check.path.trim_matches('/');
+                let category = check.category();
+                let result = if let Ok(Some(glob)) = check.glob() {
+                    glob.check(paths.iter().copied())
+                } else {
+                    match (lookup(path), check.kind) {
+                        (None, ExpectedKind::Absent) => CheckResult::pass(path, category),
+                        (Some(_), ExpectedKind::Absent) => {
+                            CheckResult::fail(path, category, "Present")
+                        }
+                        (None, _) => CheckResult::fail(path, category, "Missing"),
+                        (Some(node), kind) => match problem(&node, kind, check.executable) {
+                            Some((message, None)) => CheckResult::fail(path, category, message),
+                            Some((message, Some(mode))) => {
+                                CheckResult::fail(path, category, message)
+                                    .with_fix(Fix::chmod(path, mode))
+                            }
+                            None => match check.magic.as_deref().and_then(FileKind::parse_name) {
+                                None => CheckResult::pass(path, category),
+                                Some(expected) => match read(path) {
+                                    Ok(content) => {
+                                        magic::check(path, category, &content, &[expected])
+                                    }
+                                    Err(e) => CheckResult::fail(
+                                        path,
+                                        category,
+                                        format!("Cannot read: {}", e),
+                                    ),
+                                },
+                            },
+                        },
+                    }
+                };
+                let result = match check.subsystem.as_deref().and_then(Subsystem::parse_name) {
+                    Some(subsystem) => result.with_subsystem(subsystem),
+                    None => result,
+                };
+                if result.passed {
+                    return result;
+                }
+                let hint = match &check.hint {
+                    Some(hint) => hint.clone(),
+                    None => format!("Check defined in {}", self.source.display()),
+                };
+                result.with_hint(hint)
+            })
+            .collect()
+    }
+}
+
+impl ExtraCheck {
+    /// The check as a glob expectation, if its path is a glob.
+    fn glob(&self) -> Result<Option<GlobCheck>, FsdbgError> {
+        if !is_glob(&self.path) {
+            return Ok(None);
+        }
+        let mut glob = GlobCheck::parse(&self.path, self.category())?;
+        let (min, max) = match self.kind {
+            ExpectedKind::Absent => (0, Some(0)),
+            _ => (self.min.unwrap_or(1), self.max),
+        };
+        glob = glob.at_least(min);
+        if let Some(max) = max {
+            glob = glob.at_most(max);
+        }
+        Ok(Some(glob))
+    }
+
+    /// Reported category; absent checks are always forbidden items.
+    fn category(&self) -> CheckCategory {
+        if self.kind == ExpectedKind::Absent {
+            return CheckCategory::Forbidden;
+        }
+        self.category
+            .as_deref()
+            .and_then(CheckCategory::from_key)
+            .unwrap_or(CheckCategory::Other)
+    }
+}
+
+/// Why a present entry doesn't meet a check, if it doesn't, and the mode
+/// fixing it if only the mode is wrong.
+fn problem(node: &Node, kind: ExpectedKind, executable: bool) -> Option<(String, Option<u32>)> {
+    let expected = match kind {
+        ExpectedKind::File => Some((FileType::Regular, "regular file")),
+        ExpectedKind::Dir => Some((FileType::Directory, "directory")),
+        ExpectedKind::Symlink => Some((FileType::Symlink, "symlink")),
+        ExpectedKind::Any | ExpectedKind::Absent => None,
+    };
+    if let Some((file_type, name)) = expected {
+        if node.file_type != file_type {
+            return Some((format!("Not a {}", name), None));
+        }
+    }
+    match node.mode {
+        Some(mode) if executable && mode & 0o111 == 0 => Some((
+            format!("Not executable (mode {:o})", mode & 0o7777),
+            Some(mode | 0o111),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_and_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("oem.toml");
+        fs::write(
+            &path,
+            r#"
+checklists = ["rootfs"]
+
+[[check]]
+path = "/opt/oem/bin/agent"
+kind = "file"
+category = "binary"
+subsystem = "networking"
+executable = true
+
+[[check]]
+path = "usr/lib/oem/boot.efi"
+magic = "pe"
+
+[[check]]
+path = "usr/bin/telnet"
+kind = "absent"
+hint = "OEM images must not ship telnet"
+"#,
+        )
+        .unwrap();
+
+        let extension = load(&path).unwrap();
+        assert!(extension.applies_to(ChecklistType::Rootfs));
+        assert!(!extension.applies_to(ChecklistType::Iso));
+
+        let results = extension.check(
+            &[],
+            |p| match p {
+                "opt/oem/bin/agent" => Some(Node {
+                    file_type: FileType::Regular,
+                    mode: Some(0o100644),
+                }),
+                "usr/bin/telnet" => Some(Node {
+                    file_type: FileType::Regular,
+                    mode: Some(0o100755),
+                }),
+                "usr/lib/oem/boot.efi" => Some(Node {
+                    file_type: FileType::Regular,
+                    mode: Some(0o100644),
+                }),
+                _ => None,
+            },
+            |_| Ok(b"#!/bin/sh\n".to_vec()),
+        );
+        assert_eq!(results[0].category, CheckCategory::Binary);
+        assert_eq!(results[0].subsystem, Some(Subsystem::Networking));
+        assert_eq!(
+            results[0].message.as_deref(),
+            Some("Not executable (mode 644)")
+        );
+        assert_eq!(results[0].fix, Some(Fix::chmod("opt/oem/bin/agent", 0o755)));
+        assert_eq!(
+            results[1].message.as_deref(),
+            Some("Content is script, expected pe")
+        );
+        assert_eq!(results[2].category, CheckCategory::Forbidden);
+        assert_eq!(
+            results[2].hint.as_deref(),
+            Some("OEM images must not ship telnet")
+        );
+
+        fs::write(
+            &path,
+            "[[check]]\npath = \"opt/oem/plugins/*.so\"\nmin = 2\n[[check]]\npath = \"etc/oem/*.bak\"\nkind = \"absent\"\n",
+        )
+        .unwrap();
+        let extension = load(&path).unwrap();
+        let results = extension.check(
+            &[
+                "opt/oem/plugins/a.so",
+                "etc/oem/app.conf",
+                "etc/oem/app.conf.bak",
+            ],
+            |_| None,
+            |_| Ok(Vec::new()),
+        );
+        assert_eq!(results[0].item, "opt/oem/plugins/*.so");
+        assert_eq!(
+            results[0].message.as_deref(),
+            Some("1 match(es), expected at least 2")
+        );
+        assert_eq!(results[1].category, CheckCategory::Forbidden);
+        assert_eq!(
+            results[1].message.as_deref(),
+            Some("1 match(es), expected none")
+        );
+
+        fs::write(&path, "[[check]]\npath = \"x/*\"\nkind = \"file\"\n").unwrap();
+        assert!(load(&path).is_err());
+        fs::write(&path, "[[check]]\npath = \"x\"\nmagic = \"elf64\"\n").unwrap();
+        assert!(load(&path).is_err());
+        fs::write(&path, "[[check]]\npath = \"x\"\nmin = 2\n").unwrap();
+        assert!(load(&path).is_err());
+        fs::write(&path, "[[check]]\npath = \"x\"\ncategory = \"nope\"\n").unwrap();
+        assert!(load(&path).is_err());
+        fs::write(&path, "[[check]]\npath = \"x\"\nsubsystem = \"gpu\"\n").unwrap();
+        assert!(load(&path).is_err());
+        assert_eq!(
+            sidecar_path(Path::new("out/rootfs.img")),
+            Path::new("out/rootfs.img.checks.toml")
+        );
+    }
+
+    #[test]
+    fn test_discover_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let checks_d = dir.path().join("checks.d");
+        fs::create_dir(&checks_d).unwrap();
+        for name in ["20-site.toml", "10-oem.toml", "README.md"] {
+            fs::write(checks_d.join(name), "").unwrap();
+        }
+        let artifact = dir.path().join("rootfs.img");
+        assert_eq!(
+            discover_in(&checks_d, Some(&artifact)),
+            [checks_d.join("10-oem.toml"), checks_d.join("20-site.toml")]
+        );
+
+        // The sidecar comes last, so it reads as the most specific
+        fs::write(sidecar_path(&artifact), "").unwrap();
+        assert_eq!(
+            discover_in(&checks_d, Some(&artifact)),
+            [
+                checks_d.join("10-oem.toml"),
+                checks_d.join("20-site.toml"),
+                dir.path().join("rootfs.img.checks.toml"),
+            ]
+        );
+        assert_eq!(
+            discover_in(&dir.path().join("missing"), Some(&artifact)),
+            [dir.path().join("rootfs.img.checks.toml")]
+        );
+        // Nothing sits next to stdin
+        assert_eq!(
+            discover_in(&checks_d, None),
+            [checks_d.join("10-oem.toml"), checks_d.join("20-site.toml")]
+        );
+    }
+
+    #[test]
+    fn test_merge_into_checklist() {
+        let dir = tempfile::tempdir().unwrap();
+        let checks_d = dir.path().join("checks.d");
+        fs::create_dir(&checks_d).unwrap();
+        fs::write(
+            checks_d.join("10-oem.toml"),
+            r#"
+checklists = ["rootfs"]
+
+[[check]]
+path = "opt/oem/bin/agent"
+
+[[controlled]]
+dir = "usr/lib/systemd/system-generators"
+allow = ["oem-generator"]
+hint = "Generators ship with the OEM image"
+"#,
+        )
+        .unwrap();
+        fs::write(
+            checks_d.join("20-site.toml"),
+            r#"
+[[check]]
+path = "usr/bin/telnet"
+kind = "absent"
+
+[[controlled]]
+dir = "usr/lib/systemd/system-generators"
+allow = ["site-*"]
+hint = "Ask the site team"
+"#,
+        )
+        .unwrap();
+        let artifact = dir.path().join("rootfs.img");
+        fs::write(
+            sidecar_path(&artifact),
+            "checklists = [\"iso\"]\n[[check]]\npath = \"boot/oem.cfg\"\n",
+        )
+        .unwrap();
+
+        let extensions: Vec<Extension> = discover_in(&checks_d, Some(&artifact))
+            .iter()
+            .map(|path| load(path).unwrap())
+            .collect();
+        let merged: Vec<&Extension> = extensions
+            .iter()
+            .filter(|e| e.applies_to(ChecklistType::Rootfs))
+            .collect();
+        assert_eq!(merged.len(), 2);
+
+        let results: Vec<CheckResult> = merged
+            .iter()
+            .flat_map(|e| {
+                e.check(
+                    &[],
+                    |p| {
+                        (p == "usr/bin/telnet").then_some(Node {
+                            file_type: FileType::Regular,
+                            mode: Some(0o100755),
+                        })
+                    },
+                    |_| Ok(Vec::new()),
+                )
+            })
+            .collect();
+        let items: Vec<(&str, bool)> = results
+            .iter()
+            .map(|r| (r.item.as_str(), r.passed))
+            .collect();
+        assert_eq!(
+            items,
+            [("opt/oem/bin/agent", false), ("usr/bin/telnet", false)]
+        );
+        assert_eq!(
+            results[0].hint,
+            Some(format!(
+                "Check defined in {}",
+                checks_d.join("10-oem.toml").display()
+            ))
+        );
+
+        // Allow lists add up; the last file to set a hint overrides the others
+        let controlled = crate::checklist::controlled::merge(
+            crate::checklist::controlled::builtin(
+                ChecklistType::Rootfs,
+                crate::spec::Spec::builtin(),
+            )
+            .into_iter()
+            .chain(merged.iter().flat_map(|e| e.controlled.iter().cloned())),
+        );
+        let generators = controlled
+            .iter()
+            .find(|d| d.dir == crate::checklist::controlled::SYSTEM_GENERATORS)
+            .unwrap();
+        assert_eq!(
+            generators.allow,
+            ["systemd-*-generator", "oem-generator", "site-*"]
+        );
+        let results = crate::checklist::controlled::check(
+            [
+                "usr/lib/systemd/system-generators/oem-generator",
+                "usr/lib/systemd/system-generators/site-mounts",
+                "usr/lib/systemd/system-generators/stray",
+            ],
+            &controlled,
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].hint.as_deref(), Some("Ask the site team"));
+    }
+
+    #[test]
+    fn test_malformed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("oem.toml");
+        let error = |text: &str| {
+            fs::write(&path, text).unwrap();
+            let e = load(&path).unwrap_err();
+            assert_eq!(e.code(), ErrorCode::ParseError);
+            e.message()
+                .strip_prefix(&format!("{}: ", path.display()))
+                .unwrap()
+                .to_string()
+        };
+
+        assert!(error("[[check]\npath = \"x\"\n").contains("invalid table header"));
+        assert!(error("[[check]]\npath = 5\n").contains("invalid type"));
+        assert!(error("[[check]]\npath = \"x\"\nmode = \"0755\"\n").contains("unknown field"));
+        assert!(error("[[checks]]\npath = \"x\"\n").contains("unknown field"));
+        assert_eq!(
+            error("checklists = [\"desktop\"]\n"),
+            "Unknown checklist: desktop"
+        );
+        assert_eq!(
+            error("[[check]]\npath = \"/\"\n"),
+            "Check with an empty path"
+        );
+        assert_eq!(
+            error("[[controlled]]\ndir = \"etc/pam.d\"\nallow = [\"[oem\"]\n"),
+            "Invalid glob: [oem"
+        );
+        assert_eq!(
+            error("[[controlled]]\ndir = \"\"\n"),
+            "Controlled directory with an empty path"
+        );
+
+        // An empty file is an extension with no checks; a missing one is an error
+        fs::write(&path, "").unwrap();
+        assert!(load(&path).unwrap().checks.is_empty());
+        assert!(load(&dir.path().join("missing.toml")).is_err());
+    }
+}
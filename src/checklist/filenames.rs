@@ -0,0 +1,132 @@
+//! Filename policy lint
+//!
+//! Names that a Linux filesystem takes without complaint can still break an
+//! artifact later: a trailing space or control character survives packing
+//! but not a shell script, and media formats are stricter still. FAT drops
+//! trailing dots and rejects `"*:<>?\|`, and ISO 9660 path records stop at
+//! 255 bytes.
+
+use super::{CheckCategory, CheckResult};
+
+/// Longest name component Linux, Rock Ridge and VFAT all accept, in bytes
+pub const NAME_MAX: usize = 255;
+
+/// Longest path an ISO 9660 directory hierarchy records, in bytes
+pub const ISO_PATH_MAX: usize = 255;
+
+/// Characters FAT rejects in names
+const FAT_RESERVED: &[char] = &['"', '*', ':', '<', '>', '?', '\\', '|'];
+
+/// Which rules apply to an artifact
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Any archive: control characters, trailing spaces, overlong names
+    Archive,
+    /// Also what gets lost copying to optical/USB media: FAT-reserved
+    /// characters, trailing dots, paths over the ISO 9660 limit
+    Media,
+}
+
+/// Flag entry paths that break the policy, one result per path.
+///
+/// Only failures are returned - a clean archive adds nothing to the report.
+pub fn check<'a>(paths: impl Iterator<Item = &'a String>, policy: Policy) -> Vec<CheckResult> {
+    paths
+        .filter_map(|path| {
+            let path = path.trim_matches('/');
+            let (message, hint) = problem(path, policy)?;
+            Some(CheckResult::fail(printable(path), CheckCategory::Other, message).with_hint(hint))
+        })
+        .collect()
+}
+
+/// What's wrong with a path under `policy`, and what to do about it.
+fn problem(path: &str, policy: Policy) -> Option<(String, &'static str)> {
+    const RENAME: &str = "Rename it in the build";
+    if let Some(c) = path.chars().find(|c| c.is_control()) {
+        return Some((
+            format!("Contains control character 0x{:02x}", c as u32),
+            RENAME,
+        ));
+    }
+    for name in path.split('/') {
+        if name.ends_with(' ') {
+            return Some((
+                format!("Name '{}' ends with a space", name),
+                "Usually a quoting slip in a build script; FAT strips trailing spaces",
+            ));
+        }
+        if name.len() > NAME_MAX {
+            return Some((
+                format!("Name is {} bytes (limit {})", name.len(), NAME_MAX),
+                RENAME,
+            ));
+        }
+        if policy == Policy::Media {
+            if let Some(c) = name.chars().find(|c| FAT_RESERVED.contains(c)) {
+                return Some((
+                    format!("Name '{}' contains '{}', which FAT rejects", name, c),
+                    "Copying the image onto FAT USB media fails on it; rename it in the build",
+                ));
+            }
+            if name.ends_with('.') && name != "." && name != ".." {
+                return Some((
+                    format!("Name '{}' ends with a dot, which FAT drops", name),
+                    RENAME,
+                ));
+            }
+        }
+    }
+    if policy == Policy::Media && path.len() > ISO_PATH_MAX {
+        return Some((
+            format!(
+                "Path is {} bytes (ISO 9660 limit {})",
+                path.len(),
+                ISO_PATH_MAX
+            ),
+            "Shorten the directory structure; mastering tools truncate or relocate it",
+        ));
+    }
+    None
+}
+
+/// The path with control characters written as `\xNN`, so reports stay
+/// readable.
+fn printable(path: &str) -> String {
+    path.chars()
+        .map(|c| {
+            if c.is_control() {
+                format!("\\x{:02x}", c as u32)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problems() {
+        let message = |path: &str, policy| problem(path, policy).map(|(m, _)| m);
+        assert_eq!(message("usr/bin/ls", Policy::Media), None);
+        assert_eq!(
+            message("etc/motd\x1b", Policy::Archive).as_deref(),
+            Some("Contains control character 0x1b")
+        );
+        assert_eq!(
+            message("etc/issue /x", Policy::Archive).as_deref(),
+            Some("Name 'issue ' ends with a space")
+        );
+        // Perl man pages are fine in a rootfs, not on FAT media
+        let perl = "usr/share/man/man3/File::Spec.3pm.gz";
+        assert_eq!(message(perl, Policy::Archive), None);
+        assert!(message(perl, Policy::Media).unwrap().contains("':'"));
+        assert!(message(&"a/".repeat(130), Policy::Media)
+            .unwrap()
+            .starts_with("Path is"));
+        assert_eq!(printable("a\tb"), "a\\x09b");
+    }
+}
@@ -0,0 +1,199 @@
+//! First-boot hygiene
+//!
+//! State that must be unique per installation is created on first boot:
+//! systemd writes `etc/machine-id` when it is empty or missing, systemd-
+//! random-seed saves a fresh seed, and sshd-keygen generates host keys. An
+//! artifact that ships any of them (because it was booted, or built from a
+//! booted tree) gives every installation the same ID, the same initial
+//! entropy and the same SSH identity.
+//!
+//! The checks run on whatever the artifact offers: file contents for CPIO
+//! archives and mounted images, sizes alone for the EROFS rootfs an ISO
+//! embeds.
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use crate::erofs::ErofsReader;
+use std::path::Path;
+
+pub const MACHINE_ID: &str = "etc/machine-id";
+pub const RANDOM_SEED: &str = "var/lib/systemd/random-seed";
+
+/// SSH host keys that must be generated on first boot
+pub const SSH_HOST_KEYS: &[&str] = &[
+    "etc/ssh/ssh_host_rsa_key",
+    "etc/ssh/ssh_host_ecdsa_key",
+    "etc/ssh/ssh_host_ed25519_key",
+];
+
+/// What an artifact can tell about a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileState {
+    Absent,
+    Content(String),
+    /// Present, contents not readable from this artifact type
+    Size(u64),
+    /// Present but unreadable
+    Error(String),
+}
+
+/// Check the first-boot state in an archive.
+pub fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    results(|path| match reader.get(path) {
+        None => FileState::Absent,
+        Some(_) => match reader.read_file(path) {
+            Ok(data) => FileState::Content(String::from_utf8_lossy(&data).to_string()),
+            Err(e) => FileState::Error(e.to_string()),
+        },
+    })
+}
+
+/// Check the first-boot state in an EROFS image, by file size.
+pub fn check_erofs(reader: &ErofsReader) -> Vec<CheckResult> {
+    results(|path| {
+        reader
+            .entries()
+            .iter()
+            .find(|e| e.path.trim_start_matches('/') == path)
+            .map_or(FileState::Absent, |e| FileState::Size(e.size))
+    })
+}
+
+/// Check the first-boot state in a mounted image.
+pub fn check_dir(root: &Path) -> Vec<CheckResult> {
+    results(|path| {
+        let path = root.join(path);
+        if !path.exists() {
+            return FileState::Absent;
+        }
+        match std::fs::read_to_string(&path) {
+            Ok(content) => FileState::Content(content),
+            Err(e) => FileState::Error(e.to_string()),
+        }
+    })
+}
+
+fn results(state: impl Fn(&str) -> FileState) -> Vec<CheckResult> {
+    let mut results = vec![machine_id(state(MACHINE_ID))];
+
+    let seed_hint = "Delete var/lib/systemd/random-seed from the image; systemd-random-seed creates one on first boot";
+    results.push(match state(RANDOM_SEED) {
+        FileState::Absent => CheckResult::pass(
+            format!("{} (absent, created on first boot)", RANDOM_SEED),
+            CheckCategory::Other,
+        ),
+        FileState::Content(c) if c.is_empty() => {
+            CheckResult::pass(format!("{} (empty)", RANDOM_SEED), CheckCategory::Other)
+        }
+        FileState::Size(0) => {
+            CheckResult::pass(format!("{} (empty)", RANDOM_SEED), CheckCategory::Other)
+        }
+        FileState::Error(e) => CheckResult::fail(
+            RANDOM_SEED,
+            CheckCategory::Other,
+            format!("Cannot read: {}", e),
+        ),
+        _ => CheckResult::fail(
+            RANDOM_SEED,
+            CheckCategory::Other,
+            "Shipped seed - every installation starts from the same entropy",
+        )
+        .with_hint(seed_hint),
+    });
+
+    for key in SSH_HOST_KEYS {
+        results.push(if state(key) == FileState::Absent {
+            CheckResult::pass(format!("{} (absent, will regenerate)", key), CheckCategory::Other)
+        } else {
+            CheckResult::fail(
+                *key,
+                CheckCategory::Other,
+                "Should not exist (unique keys should be generated on first boot)",
+            )
+            .with_hint("Delete etc/ssh/ssh_host_* from the image; sshd-keygen generates them on first boot")
+        });
+    }
+    results
+}
+
+fn machine_id(state: FileState) -> CheckResult {
+    let hint = "Truncate it to empty so systemd generates a unique ID on first boot";
+    let uninitialized = "uninitialized\n".len() as u64;
+    match state {
+        // Missing is OK - systemd will create it on first boot
+        FileState::Absent => CheckResult::pass(
+            format!("{} (missing, will be created on first boot)", MACHINE_ID),
+            CheckCategory::EtcFile,
+        ),
+        FileState::Content(c) if c.trim().is_empty() => CheckResult::pass(
+            format!("{} (empty for first-boot regeneration)", MACHINE_ID),
+            CheckCategory::EtcFile,
+        ),
+        FileState::Content(c) if c.trim() == "uninitialized" => CheckResult::pass(
+            format!("{} (uninitialized for first-boot regeneration)", MACHINE_ID),
+            CheckCategory::EtcFile,
+        ),
+        // Non-empty machine ID - the tree has been booted
+        FileState::Content(c) => CheckResult::fail(
+            MACHINE_ID,
+            CheckCategory::EtcFile,
+            format!(
+                "Contains value '{}' - should be empty for unique ID generation",
+                c.trim()
+            ),
+        )
+        .with_hint(hint),
+        FileState::Size(0) => CheckResult::pass(
+            format!("{} (empty for first-boot regeneration)", MACHINE_ID),
+            CheckCategory::EtcFile,
+        ),
+        FileState::Size(size) if size == uninitialized => CheckResult::pass(
+            format!(
+                "{} ({} bytes, the size of 'uninitialized')",
+                MACHINE_ID, size
+            ),
+            CheckCategory::EtcFile,
+        ),
+        FileState::Size(size) => CheckResult::fail(
+            MACHINE_ID,
+            CheckCategory::EtcFile,
+            format!("{} bytes - should be empty for unique ID generation", size),
+        )
+        .with_hint(hint),
+        FileState::Error(e) => CheckResult::fail(
+            MACHINE_ID,
+            CheckCategory::EtcFile,
+            format!("Cannot read: {}", e),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_results() {
+        let clean = results(|path| match path {
+            MACHINE_ID => FileState::Content("uninitialized\n".to_string()),
+            _ => FileState::Absent,
+        });
+        assert!(clean.iter().all(|r| r.passed));
+
+        let booted = results(|path| match path {
+            MACHINE_ID => FileState::Size(33),
+            RANDOM_SEED => FileState::Size(512),
+            "etc/ssh/ssh_host_ed25519_key" => FileState::Size(411),
+            _ => FileState::Absent,
+        });
+        let failed: Vec<&str> = booted
+            .iter()
+            .filter(|r| !r.passed)
+            .map(|r| r.item.as_str())
+            .collect();
+        assert_eq!(
+            failed,
+            [MACHINE_ID, RANDOM_SEED, "etc/ssh/ssh_host_ed25519_key"]
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! fstab semantic validation
+//!
+//! "Has / and /boot" says nothing about whether the lines work. systemd's
+//! fstab generator turns each one into a mount unit, and a line with a
+//! filesystem the kernel can't mount, contradictory options or a kernel
+//! device name that changes between boots fails at boot time instead. Each
+//! line is parsed and checked for:
+//! - Field count, and numeric dump/pass fields in range
+//! - Option syntax (empty options, `=value` without a name, `ro` with `rw`)
+//! - A filesystem type the image has a kernel module (or builtin) for
+//! - Kernel device names (`/dev/sda2`, `/dev/vda1`) where policy requires
+//!   `UUID=`/`PARTUUID=`/`LABEL=` identifiers
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Path of the file, relative to the root
+pub const FSTAB: &str = "etc/fstab";
+
+/// Filesystems the kernel always has, or that need no module to mount
+const VIRTUAL_FS: &[&str] = &[
+    "auto",
+    "swap",
+    "none",
+    "proc",
+    "sysfs",
+    "tmpfs",
+    "devtmpfs",
+    "devpts",
+    "ramfs",
+    "cgroup",
+    "cgroup2",
+    "securityfs",
+    "debugfs",
+    "tracefs",
+    "efivarfs",
+    "bpf",
+    "configfs",
+    "mqueue",
+    "hugetlbfs",
+    "pstore",
+    "fusectl",
+    "rootfs",
+];
+
+/// Filesystem types whose module is named differently
+const FS_MODULES: &[(&str, &str)] = &[
+    ("iso9660", "isofs"),
+    ("nfs4", "nfsv4"),
+    ("cifs", "cifs"),
+    ("smb3", "cifs"),
+    ("ntfs", "ntfs3"),
+];
+
+/// Kernel device names that depend on probe order
+const UNSTABLE_DEVICES: &[&str] = &["sd", "vd", "hd", "xvd", "nvme", "mmcblk"];
+
+/// Options that contradict each other when both are given
+const CONFLICTS: &[(&str, &str)] = &[
+    ("ro", "rw"),
+    ("auto", "noauto"),
+    ("exec", "noexec"),
+    ("suid", "nosuid"),
+    ("dev", "nodev"),
+    ("atime", "noatime"),
+];
+
+/// Kernel modules in an image, loadable or builtin, by module name
+#[derive(Debug, Default)]
+pub struct KernelModules {
+    names: HashSet<String>,
+}
+
+impl KernelModules {
+    /// Modules from `usr/lib/modules/<version>/` paths (`.ko`, `.ko.xz`,
+    /// ...) and `modules.builtin` lines.
+    pub fn from_paths<'a>(paths: impl Iterator<Item = &'a str>) -> Self {
+        let names = paths
+            .filter_map(|p| p.split_once("usr/lib/modules/").map(|(_, rest)| rest))
+            .filter_map(|p| p.rsplit('/').next())
+            .filter_map(|name| name.split_once(".ko").map(|(name, _)| name))
+            .map(|name| name.replace('-', "_"))
+            .collect();
+        Self { names }
+    }
+
+    /// Modules of an archive, including those listed in `modules.builtin`.
+    pub fn from_cpio(reader: &CpioReader) -> Self {
+        let mut modules = Self::from_paths(reader.entries().iter().map(|e| e.path.as_str()));
+        let builtin: Vec<String> = reader
+            .files()
+            .map(|e| CpioReader::normalize_path(&e.path))
+            .filter(|p| p.starts_with("usr/lib/modules/") && p.ends_with("/modules.builtin"))
+            .collect();
+        for path in builtin {
+            if let Ok(list) = reader.read_file_to_string(&path) {
+                modules.add_builtin(&list);
+            }
+        }
+        modules
+    }
+
+    /// Modules of a mounted image.
+    pub fn from_dir(root: &Path) -> Self {
+        let mut paths = Vec::new();
+        let mut builtin = Vec::new();
+        let mut pending = vec![root.join("usr/lib/modules")];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    pending.push(path);
+                } else if path.ends_with("modules.builtin") {
+                    builtin.push(path);
+                } else {
+                    paths.push(path.to_string_lossy().to_string());
+                }
+            }
+        }
+        let mut modules = Self::from_paths(paths.iter().map(String::as_str));
+        for path in builtin {
+            if let Ok(list) = std::fs::read_to_string(path) {
+                modules.add_builtin(&list);
+            }
+        }
+        modules
+    }
+
+    fn add_builtin(&mut self, list: &str) {
+        let paths = list.lines().map(|l| format!("usr/lib/modules/{}", l));
+        let builtin = Self::from_paths(paths.collect::<Vec<_>>().iter().map(String::as_str));
+        self.names.extend(builtin.names);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Whether the kernel can mount `fs_type`. Always true for virtual
+    /// filesystems and when no modules are known at all.
+    pub fn supports(&self, fs_type: &str) -> bool {
+        if self.is_empty() || VIRTUAL_FS.contains(&fs_type) || fs_type.starts_with("fuse") {
+            return true;
+        }
+        let module = FS_MODULES
+            .iter()
+            .find(|(fs, _)| *fs == fs_type)
+            .map_or(fs_type, |(_, module)| module);
+        self.names.contains(&module.replace('-', "_"))
+    }
+}
+
+/// Validate `etc/fstab` in an archive, if it has one.
+pub fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    if !reader.get(FSTAB).is_some_and(|e| e.is_file()) {
+        return Vec::new();
+    }
+    match reader.read_file_to_string(FSTAB) {
+        Ok(content) => results(&content, &KernelModules::from_cpio(reader)),
+        Err(e) => vec![CheckResult::fail(
+            FSTAB,
+            CheckCategory::Config,
+            format!("Cannot read: {}", e),
+        )],
+    }
+}
+
+/// Results for one fstab: a pass if it's clean, a failure per problem.
+pub fn results(content: &str, modules: &KernelModules) -> Vec<CheckResult> {
+    let issues = lint_fstab(content, modules);
+    if issues.is_empty() {
+        return vec![CheckResult::pass(
+            format!("{} (syntax)", FSTAB),
+            CheckCategory::Config,
+        )];
+    }
+    issues
+        .into_iter()
+        .map(|(line, message)| {
+            CheckResult::fail(
+                format!("{}:{}", FSTAB, line),
+                CheckCategory::Config,
+                message,
+            )
+            .with_hint("Fix the generator (recfstab) or the line; systemd fails this mount at boot")
+        })
+        .collect()
+}
+
+/// Lint one fstab, returning (line number, message) for each problem.
+fn lint_fstab(content: &str, modules: &KernelModules) -> Vec<(usize, String)> {
+    let mut issues = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let mut issue = |message: String| issues.push((i + 1, message));
+        if !(3..=6).contains(&fields.len()) {
+            issue(format!(
+                "Expected 3-6 fields (source, target, type, options, dump, pass), found {}",
+                fields.len()
+            ));
+            continue;
+        }
+        let (source, target, fs_type) = (fields[0], fields[1], fields[2]);
+
+        if is_kernel_device(source) {
+            issue(format!(
+                "{} is a kernel device name that can change between boots; use UUID= or PARTUUID=",
+                source
+            ));
+        }
+        if !target.starts_with('/') && target != "none" && fs_type != "swap" {
+            issue(format!("Mount point '{}' is not an absolute path", target));
+        }
+        if !modules.supports(fs_type) {
+            issue(format!(
+                "No kernel module for filesystem type '{}' in the image",
+                fs_type
+            ));
+        }
+        if let Some(options) = fields.get(3) {
+            if let Some(message) = option_problem(options) {
+                issue(message);
+            }
+        }
+        if let Some(dump) = fields.get(4) {
+            if !matches!(*dump, "0" | "1") {
+                issue(format!("Dump field must be 0 or 1, found '{}'", dump));
+            }
+        }
+        if let Some(pass) = fields.get(5) {
+            match (*pass, target) {
+                ("0", _) | ("1", "/") | ("2", _) => {}
+                ("1", _) => issue(format!(
+                    "Pass 1 is for the root filesystem; use 2 for {}",
+                    target
+                )),
+                (pass, _) => issue(format!("Pass field must be 0, 1 or 2, found '{}'", pass)),
+            }
+        }
+    }
+    issues
+}
+
+/// What's wrong with a comma-separated options field, if anything.
+fn option_problem(options: &str) -> Option<String> {
+    let options: Vec<&str> = options.split(',').collect();
+    if options.iter().any(|o| o.is_empty()) {
+        return Some("Empty mount option (stray comma)".to_string());
+    }
+    if let Some(o) = options.iter().find(|o| o.starts_with('=')) {
+        return Some(format!("Mount option '{}' has no name", o));
+    }
+    CONFLICTS
+        .iter()
+        .find(|(a, b)| options.contains(a) && options.contains(b))
+        .map(|(a, b)| format!("Conflicting mount options '{}' and '{}'", a, b))
+}
+
+/// `/dev/sda2`, `/dev/vda1`, `/dev/nvme0n1p2`: names assigned in probe order.
+fn is_kernel_device(source: &str) -> bool {
+    source.strip_prefix("/dev/").is_some_and(|name| {
+        UNSTABLE_DEVICES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_fstab() {
+        let modules = KernelModules::from_paths(
+            [
+                "usr/lib/modules/6.12.0/kernel/fs/ext4/ext4.ko.xz",
+                "usr/lib/modules/6.12.0/kernel/fs/fat/vfat.ko.zst",
+            ]
+            .into_iter(),
+        );
+        let fstab = "# generated\n\
+                     UUID=5f0c / ext4 defaults 0 1\n\
+                     UUID=ABCD /boot vfat umask=0077 0 2\n\
+                     tmpfs /tmp tmpfs nosuid,nodev 0 0\n\
+                     /dev/vda3 /data xfs defaults 0 2\n\
+                     UUID=1111 /srv ext4 ro,,rw 0 1\n\
+                     UUID=2222 /home ext4 defaults 3 x\n\
+                     UUID=3333\n";
+        let issues = lint_fstab(fstab, &modules);
+        let lines: Vec<usize> = issues.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [5, 5, 6, 6, 7, 7, 8]);
+        assert!(issues[1].1.contains("'xfs'"));
+        assert_eq!(issues[2].1, "Empty mount option (stray comma)");
+        assert_eq!(
+            option_problem("ro,noatime,rw").unwrap(),
+            "Conflicting mount options 'ro' and 'rw'"
+        );
+
+        // Builtin filesystems count, and nothing is flagged without any modules
+        let mut modules = KernelModules::default();
+        assert!(modules.supports("xfs"));
+        modules.add_builtin("kernel/fs/ext4/ext4.ko\nkernel/fs/isofs/isofs.ko\n");
+        assert!(modules.supports("iso9660"));
+        assert!(!modules.supports("xfs"));
+    }
+}
@@ -0,0 +1,218 @@
+//! Getty/console configuration audit for live images
+//!
+//! A live ISO with broken autologin or no getty on its serial console looks
+//! dead - the kernel boots, then nothing. These checks run against the live
+//! overlay and the consoles named in the UKI command lines:
+//! - /etc/issue exists
+//! - Autologin drop-ins reference a user defined in the overlay
+//! - serial-getty@ is enabled for every serial console in a UKI cmdline
+//!
+//! systemd-getty-generator only starts a getty on the *last* `console=`
+//! argument (the one backing /dev/console); every other serial console must
+//! be enabled explicitly.
+
+use super::{CheckCategory, CheckResult};
+use crate::fixit::Fix;
+use crate::overlay::OverlayTree;
+
+const ISSUE: &str = "etc/issue";
+const PASSWD: &str = "etc/passwd";
+const SYSTEM_DIR: &str = "etc/systemd/system/";
+const GETTY_WANTS_DIR: &str = "etc/systemd/system/getty.target.wants/";
+
+/// Consoles named by one UKI's `console=` arguments, in cmdline order.
+#[derive(Debug, Clone)]
+pub struct UkiConsoles {
+    pub uki: String,
+    pub consoles: Vec<String>,
+}
+
+/// Audit getty/console configuration in a live overlay.
+pub fn check(overlay: &dyn OverlayTree, ukis: &[UkiConsoles]) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    let paths = overlay.paths();
+
+    // /etc/issue
+    if paths.iter().any(|p| p == ISSUE) {
+        results.push(CheckResult::pass(ISSUE, CheckCategory::EtcFile));
+    } else {
+        results.push(
+            CheckResult::fail(
+                ISSUE,
+                CheckCategory::EtcFile,
+                "Missing (blank pre-login banner on live console)",
+            )
+            .with_hint("Stage etc/issue in the leviso live overlay"),
+        );
+    }
+
+    // Autologin drop-ins
+    let dropins: Vec<&String> = paths
+        .iter()
+        .filter(|p| is_getty_dropin(p) && p.ends_with(".conf"))
+        .collect();
+    let users = overlay
+        .read_to_string(PASSWD)
+        .map(|passwd| {
+            passwd
+                .lines()
+                .filter_map(|l| l.split(':').next())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut found_autologin = false;
+    for dropin in dropins {
+        let content = match overlay.read_to_string(dropin) {
+            Ok(content) => content,
+            Err(e) => {
+                results.push(CheckResult::fail(
+                    dropin.as_str(),
+                    CheckCategory::Unit,
+                    format!("Cannot read: {}", e),
+                ));
+                continue;
+            }
+        };
+        let Some(user) = autologin_user(&content) else {
+            continue;
+        };
+        found_autologin = true;
+
+        // Without an overlay passwd the user has to come from the rootfs,
+        // which only reliably has root
+        if users.iter().any(|u| u == &user) || (users.is_empty() && user == "root") {
+            results.push(CheckResult::pass(
+                format!("{} (autologin {})", dropin, user),
+                CheckCategory::Unit,
+            ));
+        } else {
+            results.push(CheckResult::fail(
+                dropin.as_str(),
+                CheckCategory::Unit,
+                format!("Autologin user '{}' not in overlay {}", user, PASSWD),
+            )
+            .with_hint("Create the live user in the overlay's etc/passwd, or fix the drop-in's --autologin"));
+        }
+    }
+    if !found_autologin {
+        results.push(
+            CheckResult::fail(
+                format!("{}getty@.service.d/", SYSTEM_DIR),
+                CheckCategory::Unit,
+                "No autologin drop-in (live ISO stops at a login prompt)",
+            )
+            .with_hint("Stage getty@tty1.service.d/autologin.conf in the leviso live overlay"),
+        );
+    }
+
+    // serial-getty@ for UKI consoles
+    for uki in ukis {
+        for (i, console) in uki.consoles.iter().enumerate() {
+            if !is_serial_console(console) {
+                continue;
+            }
+            let unit = format!("serial-getty@{}.service", console);
+            let wants = format!("{}{}", GETTY_WANTS_DIR, unit);
+            let is_last = i + 1 == uki.consoles.len();
+
+            if paths.iter().any(|p| p == &wants) {
+                results.push(CheckResult::pass(
+                    format!("{} ({})", unit, uki.uki),
+                    CheckCategory::Unit,
+                ));
+            } else if is_last {
+                results.push(CheckResult::pass(
+                    format!("{} ({}, via systemd-getty-generator)", unit, uki.uki),
+                    CheckCategory::Unit,
+                ));
+            } else {
+                results.push(
+                    CheckResult::fail(
+                        format!("{} ({})", unit, uki.uki),
+                        CheckCategory::Unit,
+                        format!("console={} in cmdline but {} is not enabled", console, unit),
+                    )
+                    .with_hint("Add a getty.target.wants symlink for it in the live overlay")
+                    .with_fix(Fix::wants(&wants)),
+                );
+            }
+        }
+    }
+
+    results
+}
+
+/// Drop-in files for getty@/serial-getty@/console-getty units.
+fn is_getty_dropin(path: &str) -> bool {
+    path.strip_prefix(SYSTEM_DIR)
+        .and_then(|rest| rest.split_once(".service.d/"))
+        .is_some_and(|(unit, _)| unit.contains("getty"))
+}
+
+/// Extract the `--autologin`/`-a` user from an agetty ExecStart line.
+fn autologin_user(content: &str) -> Option<String> {
+    for line in content.lines() {
+        let Some(cmd) = line.trim().strip_prefix("ExecStart=") else {
+            continue;
+        };
+        let mut args = cmd.split_whitespace();
+        while let Some(arg) = args.next() {
+            if let Some(user) = arg.strip_prefix("--autologin=") {
+                return Some(user.to_string());
+            }
+            if arg == "--autologin" || arg == "-a" {
+                return args.next().map(str::to_string);
+            }
+        }
+    }
+    None
+}
+
+/// Virtual terminals (tty0, tty1, ...) are handled by getty@/autovt@.
+fn is_serial_console(console: &str) -> bool {
+    !console
+        .strip_prefix("tty")
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autologin_user() {
+        let dropin = "[Service]\n\
+                      ExecStart=\n\
+                      ExecStart=-/sbin/agetty -o '-p -f -- \\\\u' --noclear --autologin live %I $TERM\n";
+        assert_eq!(autologin_user(dropin).as_deref(), Some("live"));
+        assert_eq!(
+            autologin_user("ExecStart=-/sbin/agetty -a root --keep-baud 115200 %I").as_deref(),
+            Some("root")
+        );
+        assert_eq!(autologin_user("ExecStart=-/sbin/agetty %I"), None);
+    }
+
+    #[test]
+    fn test_is_getty_dropin() {
+        assert!(is_getty_dropin(
+            "etc/systemd/system/getty@tty1.service.d/autologin.conf"
+        ));
+        assert!(is_getty_dropin(
+            "etc/systemd/system/serial-getty@.service.d/autologin.conf"
+        ));
+        assert!(!is_getty_dropin(
+            "etc/systemd/system/sshd.service.d/override.conf"
+        ));
+    }
+
+    #[test]
+    fn test_is_serial_console() {
+        assert!(!is_serial_console("tty0"));
+        assert!(!is_serial_console("tty1"));
+        assert!(is_serial_console("ttyS0"));
+        assert!(is_serial_console("ttyAMA0"));
+        assert!(is_serial_console("hvc0"));
+    }
+}
@@ -0,0 +1,274 @@
+//! GRUB legacy BIOS boot for dual-bootloader ISOs
+//!
+//! The live ISO boots UEFI through systemd-boot; spins that also boot
+//! legacy BIOS add GRUB, whose El Torito boot image (`eltorito.img`, or
+//! isolinux's) loads `boot/grub/grub.cfg`. These checks run when the ISO
+//! has that config: every menuentry's `linux` and `initrd` must be on the
+//! ISO, and the boot catalog must list a bootable BIOS image that is a
+//! file on it.
+//!
+//! Paths through GRUB variables (`${isofile}`) can't be resolved without
+//! running GRUB and are not checked; a device prefix such as `($root)` is
+//! dropped.
+
+use super::{CheckCategory, CheckResult, Subsystem};
+use crate::iso::{self, IsoReader};
+
+/// GRUB config on the ISO
+pub const CONFIG: &str = "boot/grub/grub.cfg";
+
+/// Directory holding the config and GRUB's modules
+pub const DIR: &str = "boot/grub";
+
+/// A `menuentry` block of a GRUB config
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MenuEntry {
+    pub title: String,
+    /// Kernel (`linux`, `linux16`, `linuxefi`)
+    pub linux: Option<String>,
+    /// Initrds (`initrd`, `initrd16`, `initrdefi`), in load order
+    pub initrd: Vec<String>,
+}
+
+/// Split a config line into words, honouring GRUB's quoting.
+fn words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote = None;
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            // Single quotes take everything literally, double quotes allow escapes
+            (Some('"') | None, '\\') => {
+                word.extend(chars.next());
+                in_word = true;
+            }
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// Parse the menuentries of a GRUB config, including those in submenus.
+pub fn parse(config: &str) -> Vec<MenuEntry> {
+    let mut entries = Vec::new();
+    // Open blocks; Some(index) for a menuentry
+    let mut blocks: Vec<Option<usize>> = Vec::new();
+    for line in config.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words = words(line);
+        let Some(command) = words.first() else {
+            continue;
+        };
+        let current = blocks.iter().rev().find_map(|b| *b);
+        match command.as_str() {
+            "menuentry" => {
+                entries.push(MenuEntry {
+                    title: words.get(1).cloned().unwrap_or_default(),
+                    ..MenuEntry::default()
+                });
+                blocks.push(Some(entries.len() - 1));
+            }
+            "linux" | "linux16" | "linuxefi" => {
+                if let (Some(i), Some(path)) = (current, words.get(1)) {
+                    entries[i].linux = Some(path.clone());
+                }
+            }
+            "initrd" | "initrd16" | "initrdefi" => {
+                if let Some(i) = current {
+                    entries[i].initrd.extend(words[1..].iter().cloned());
+                }
+            }
+            "}" => {
+                blocks.pop();
+            }
+            _ if line.ends_with('{') => blocks.push(None),
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// The ISO path a GRUB file argument names, without its device prefix;
+/// None when it goes through a variable.
+fn iso_path(arg: &str) -> Option<String> {
+    let path = match arg.strip_prefix('(') {
+        Some(rest) => &rest[rest.find(')')? + 1..],
+        None => arg,
+    };
+    (!path.contains('$')).then(|| format!("/{}", path.trim_start_matches('/')))
+}
+
+/// Check the GRUB config on the ISO and the BIOS boot image in its El
+/// Torito catalog.
+pub fn check(reader: &IsoReader) -> Vec<CheckResult> {
+    let config_path = format!("/{}", CONFIG);
+    let mut results = Vec::new();
+
+    match reader.read_file_to_string(&config_path) {
+        Ok(config) => {
+            let entries = parse(&config);
+            if entries.is_empty() {
+                results.push(CheckResult::fail(
+                    &config_path,
+                    CheckCategory::EtcFile,
+                    "No menuentry (BIOS boot has nothing to boot)",
+                ));
+            } else {
+                results.push(CheckResult::pass(&config_path, CheckCategory::EtcFile));
+            }
+            for entry in &entries {
+                let files = entry
+                    .linux
+                    .iter()
+                    .map(|path| ("linux", path))
+                    .chain(entry.initrd.iter().map(|path| ("initrd", path)));
+                for (command, arg) in files {
+                    let Some(path) = iso_path(arg) else {
+                        continue;
+                    };
+                    let item = format!("grub.cfg '{}': {} {}", entry.title, command, path);
+                    if reader.exists(&path) {
+                        results.push(CheckResult::pass(item, CheckCategory::Binary));
+                    } else {
+                        results.push(
+                            CheckResult::fail(
+                                item,
+                                CheckCategory::Binary,
+                                "Missing (CRITICAL: menu entry won't boot)",
+                            )
+                            .with_hint("Point the menuentry at the kernel and initramfs paths from distro-spec/src/shared"),
+                        );
+                    }
+                }
+            }
+        }
+        Err(e) => results.push(CheckResult::fail(
+            &config_path,
+            CheckCategory::EtcFile,
+            format!("Cannot read: {}", e),
+        )),
+    }
+
+    results.push(bios_image(reader));
+    results
+        .into_iter()
+        .map(|r| r.with_subsystem(Subsystem::Boot))
+        .collect()
+}
+
+/// Check the El Torito catalog lists a bootable BIOS image that is a file
+/// on the ISO.
+fn bios_image(reader: &IsoReader) -> CheckResult {
+    const ITEM: &str = "El Torito BIOS boot image";
+    let catalog = match iso::boot_catalog(reader.source_path()) {
+        Ok(catalog) => catalog,
+        Err(e) => {
+            return CheckResult::fail(
+                ITEM,
+                CheckCategory::Other,
+                format!("Cannot read boot catalog: {}", e),
+            )
+        }
+    };
+    let Some(image) = catalog
+        .iter()
+        .find(|image| image.platform == iso::PLATFORM_BIOS)
+    else {
+        return CheckResult::fail(
+            ITEM,
+            CheckCategory::Other,
+            "No BIOS entry in the boot catalog (CRITICAL: legacy BIOS won't boot)",
+        )
+        .with_hint("Pass the GRUB El Torito image to xorriso with -b (e.g. boot/grub/i386-pc/eltorito.img)");
+    };
+    if !image.bootable {
+        return CheckResult::fail(
+            ITEM,
+            CheckCategory::Other,
+            format!(
+                "BIOS entry at block {} is not marked bootable",
+                image.extent
+            ),
+        );
+    }
+    match reader.file_at(image.extent) {
+        Some(file) => CheckResult::pass(format!("{}: {}", ITEM, file.path), CheckCategory::Other),
+        None => CheckResult::fail(
+            ITEM,
+            CheckCategory::Other,
+            format!(
+                "Boot catalog points at block {}, which is no file on the ISO",
+                image.extent
+            ),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = r#"
+set default=0
+# menuentry 'commented out' {
+menuentry 'Live (BIOS)' --class linux {
+    linux16 ($root)/boot/vmlinuz root=live:LABEL=LEVITATE quiet
+    initrd16 /boot/intel-ucode.img /boot/initramfs-live.img
+}
+submenu "Troubleshooting" {
+    menuentry "Emergency \"shell\"" {
+        if [ -f /boot/vmlinuz ]; then
+            linux /boot/vmlinuz emergency
+        fi
+        initrd ${isofile}/boot/initramfs-live.img
+    }
+}
+menuentry 'Boot from first hard disk' {
+    chainloader (hd0)+1
+}
+"#;
+        let entries = parse(config);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].title, "Live (BIOS)");
+        assert_eq!(entries[0].linux.as_deref(), Some("($root)/boot/vmlinuz"));
+        assert_eq!(
+            entries[0].initrd,
+            ["/boot/intel-ucode.img", "/boot/initramfs-live.img"]
+        );
+        assert_eq!(entries[1].title, "Emergency \"shell\"");
+        assert_eq!(entries[1].linux.as_deref(), Some("/boot/vmlinuz"));
+        assert_eq!(entries[2].linux, None);
+
+        assert_eq!(
+            iso_path("($root)/boot/vmlinuz").as_deref(),
+            Some("/boot/vmlinuz")
+        );
+        assert_eq!(iso_path("boot/vmlinuz").as_deref(), Some("/boot/vmlinuz"));
+        assert_eq!(iso_path("${isofile}/boot/initramfs-live.img"), None);
+    }
+}
@@ -0,0 +1,175 @@
+//! Home directory and skel checks
+//!
+//! A user whose home is missing, owned by someone else or group-writable
+//! can't log in (or sshd refuses their keys), and one created without the
+//! skel dotfiles starts with no shell profile. Root's home, etc/skel and the
+//! home of every login account in etc/passwd are checked.
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use crate::fixit::Fix;
+
+pub const PASSWD: &str = "etc/passwd";
+pub const SKEL: &str = "etc/skel";
+
+/// Dotfiles useradd copies into a new home (from the bash package)
+pub const SKEL_FILES: &[&str] = &[".bashrc", ".bash_profile", ".bash_logout"];
+
+/// UIDs useradd assigns to regular accounts (login.defs UID_MIN..UID_MAX)
+const LOGIN_UIDS: std::ops::RangeInclusive<u32> = 1000..=60000;
+
+/// Shells that mean the account can't log in
+const NO_LOGIN_SHELLS: &[&str] = &[
+    "/sbin/nologin",
+    "/usr/sbin/nologin",
+    "/bin/false",
+    "/usr/bin/false",
+];
+
+/// An etc/passwd account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+    pub shell: String,
+}
+
+/// Parse etc/passwd, skipping malformed lines.
+pub fn parse_passwd(content: &str) -> Vec<Account> {
+    content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let [name, _, uid, gid, _, home, shell] = fields[..] else {
+                return None;
+            };
+            Some(Account {
+                name: name.to_string(),
+                uid: uid.parse().ok()?,
+                gid: gid.parse().ok()?,
+                home: home.to_string(),
+                shell: shell.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Accounts whose home has to be usable: root and regular login accounts.
+fn needs_home(account: &Account) -> bool {
+    account.uid == 0
+        || (LOGIN_UIDS.contains(&account.uid) && !NO_LOGIN_SHELLS.contains(&account.shell.as_str()))
+}
+
+/// Check root's home, etc/skel and the homes of login accounts.
+pub fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+
+    let accounts = match reader.read_file_to_string(PASSWD) {
+        Ok(content) => parse_passwd(&content),
+        // The etc file checks report a missing passwd
+        Err(_) => Vec::new(),
+    };
+    let root = accounts
+        .iter()
+        .find(|a| a.uid == 0)
+        .cloned()
+        .unwrap_or(Account {
+            name: "root".to_string(),
+            uid: 0,
+            gid: 0,
+            home: "/root".to_string(),
+            shell: String::new(),
+        });
+    let others = accounts.iter().filter(|a| a.uid != 0 && needs_home(a));
+    for account in std::iter::once(&root).chain(others) {
+        results.push(home_result(reader, account));
+    }
+
+    for name in SKEL_FILES {
+        let path = format!("{}/{}", SKEL, name);
+        if reader.get(&path).is_some_and(|e| e.is_file()) {
+            results.push(CheckResult::pass(path, CheckCategory::EtcFile));
+        } else {
+            results.push(
+                CheckResult::fail(
+                    path,
+                    CheckCategory::EtcFile,
+                    "Missing (new users get no shell profile)",
+                )
+                .with_hint("Install bash's etc/skel dotfiles into the rootfs"),
+            );
+        }
+    }
+    results
+}
+
+fn home_result(reader: &CpioReader, account: &Account) -> CheckResult {
+    let path = CpioReader::normalize_path(&account.home);
+    let item = format!("{} (home of {})", path, account.name);
+    let Some((problem, mode)) = home_problem(reader, account) else {
+        return CheckResult::pass(item, CheckCategory::Directory);
+    };
+    let result =
+        CheckResult::fail(item, CheckCategory::Directory, problem).with_hint(if account.uid == 0 {
+            "Create root/ as 0700 root:root when building the rootfs"
+        } else {
+            "Create pre-seeded users with useradd -m (home from etc/skel, owned by the user)"
+        });
+    match mode {
+        Some(mode) => result.with_fix(Fix::chmod(path, mode)),
+        None => result,
+    }
+}
+
+/// What's wrong with the home, and the mode fixing it if only the mode is.
+fn home_problem(reader: &CpioReader, account: &Account) -> Option<(String, Option<u32>)> {
+    let Some(entry) = reader.get(&account.home) else {
+        return Some(("Missing (login starts in / or fails)".to_string(), None));
+    };
+    if !entry.is_dir() {
+        return Some(("Not a directory".to_string(), None));
+    }
+    if (entry.uid, entry.gid) != (account.uid, account.gid) {
+        let problem = format!(
+            "Owned by {}:{}, expected {}:{}",
+            entry.uid, entry.gid, account.uid, account.gid
+        );
+        return Some((problem, None));
+    }
+    let mode = entry.permissions() & 0o7777;
+    if account.uid == 0 && mode != 0o700 {
+        return Some((format!("Mode {:04o}, expected 0700", mode), Some(0o700)));
+    }
+    if mode & 0o022 != 0 {
+        // sshd's StrictModes rejects keys under a group/world-writable home
+        let problem = format!("Mode {:04o} is group/world-writable", mode);
+        return Some((problem, Some(mode & !0o022)));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_passwd() {
+        let accounts = parse_passwd(
+            "root:x:0:0:root:/root:/bin/bash\n\
+             sshd:x:74:74::/usr/share/empty.sshd:/usr/sbin/nologin\n\
+             live:x:1000:1000:Live User:/home/live:/bin/bash\n\
+             svc:x:1001:1001::/var/lib/svc:/usr/sbin/nologin\n\
+             broken:x:1002\n",
+        );
+        assert_eq!(accounts.len(), 4);
+        let homes: Vec<&str> = accounts
+            .iter()
+            .filter(|a| needs_home(a))
+            .map(|a| a.home.as_str())
+            .collect();
+        assert_eq!(homes, ["/root", "/home/live"]);
+    }
+}
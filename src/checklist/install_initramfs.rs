@@ -9,8 +9,14 @@
 //! - Systemd files: `tools/recinit/src/systemd.rs`
 //! - Install structure: `tools/recinit/src/install.rs`
 
-use super::{CheckCategory, CheckResult, VerificationReport};
+use super::{
+    check_empty_files, devices, has_path, udev, CheckCategory, CheckResult, Hint, Prerequisite,
+    Scope, VerificationReport,
+};
+use crate::arch::Arch;
 use crate::cpio::CpioReader;
+use crate::fixit::Fix;
+use crate::spec::Spec;
 
 // =============================================================================
 // BINARIES - from recinit/src/systemd.rs SYSTEMD_FILES
@@ -256,19 +262,73 @@ pub const DIRS: &[&str] = &[
 ];
 
 // =============================================================================
-// KERNEL MODULES - from distro-spec (SINGLE SOURCE OF TRUTH)
+// VERIFICATION
 // =============================================================================
 
-// Re-export for consumers that expect MODULES here
-pub use distro_spec::shared::INSTALL_MODULES as MODULES;
-pub use distro_spec::shared::INSTALL_MODULES_BUILTIN as TYPICALLY_BUILTIN;
+/// Where to fix failures, by category and path prefix.
+pub const HINTS: &[Hint] = &[
+    Hint::new(CheckCategory::Binary, "", "Add it to SYSTEMD_FILES in tools/recinit/src/systemd.rs"),
+    Hint::new(CheckCategory::Binary, "init", "tools/recinit/src/install.rs must link init to usr/lib/systemd/systemd"),
+    Hint::new(CheckCategory::Binary, "usr/lib/udev/", "Add it to UDEV_HELPERS in tools/recinit/src/systemd.rs"),
+    Hint::new(CheckCategory::Binary, "usr/lib/systemd/system-generators/", "Copy it in copy_initrd_units (tools/recinit/src/systemd.rs)"),
+    Hint::new(CheckCategory::Unit, "", "Add it to INITRD_UNITS in tools/recinit/src/systemd.rs"),
+    Hint::new(CheckCategory::Symlink, "", "Create it in create_install_directory_structure (tools/recinit/src/install.rs)"),
+    Hint::new(CheckCategory::Symlink, "usr/lib/systemd/system/", "Add it to INITRD_WANTS_SYMLINKS in tools/recinit/src/systemd.rs"),
+    Hint::new(CheckCategory::Symlink, "usr/lib/systemd/systemd-sysroot-fstab-check", "Link it to system-generators/systemd-fstab-generator in copy_initrd_units (tools/recinit/src/systemd.rs)"),
+    Hint::new(CheckCategory::EtcFile, "", "Create it in build_install_initramfs (tools/recinit/src/install.rs)"),
+    Hint::new(CheckCategory::EtcFile, "usr/lib/tmpfiles.d/", "Copy it in copy_initrd_units (tools/recinit/src/systemd.rs)"),
+    Hint::new(CheckCategory::UdevRule, "", "Copy the rule in copy_initrd_units (tools/recinit/src/systemd.rs)"),
+    Hint::new(CheckCategory::Directory, "", "Add it to INSTALL_DIRS in tools/recinit/src/install.rs"),
+    Hint::new(CheckCategory::KernelModule, "", "Add it to distro-spec/src/shared/modules.rs, or build it into the kernel"),
+    Hint::new(CheckCategory::Library, "", "Copy the symlink target alongside the link in tools/recinit/src/systemd.rs"),
+    Hint::new(CheckCategory::Device, "", "Link init to systemd (it mounts devtmpfs), or mount devtmpfs in the wrapper script"),
+];
 
-// =============================================================================
-// VERIFICATION
-// =============================================================================
+/// Directories whose absence explains whole groups of failures.
+pub const PREREQUISITES: &[Prerequisite] = &[Prerequisite::new(
+    "usr/lib/systemd",
+    &[
+        (CheckCategory::Binary, "usr/lib/systemd/"),
+        (CheckCategory::Unit, ""),
+        (CheckCategory::Symlink, "usr/lib/systemd/"),
+    ],
+)];
+
+/// Paths referenced by the install initramfs checklist.
+pub fn scope() -> Scope {
+    let mut scope = Scope::new();
+    scope.extend(
+        BINARIES.iter().chain(UDEV_HELPERS).chain(GENERATORS),
+        CheckCategory::Binary,
+    );
+    scope.add("init", CheckCategory::Binary);
+    scope.extend(
+        UNITS
+            .iter()
+            .map(|u| format!("usr/lib/systemd/system/{}", u)),
+        CheckCategory::Unit,
+    );
+    scope.extend(
+        SYMLINKS
+            .iter()
+            .map(|(link, _)| *link)
+            .chain(CRITICAL_SYMLINKS.iter().copied()),
+        CheckCategory::Symlink,
+    );
+    scope.extend(WANTS_SYMLINKS, CheckCategory::Unit);
+    scope.extend(ETC_FILES.iter().chain(TMPFILES), CheckCategory::EtcFile);
+    scope.add_prefix("usr/lib/udev/rules.d", CheckCategory::UdevRule);
+    scope.extend(DIRS, CheckCategory::Directory);
+    scope.add_prefix("usr/lib/modules", CheckCategory::KernelModule);
+    scope.extend(
+        devices::REQUIRED_NODES.iter().map(|(path, _, _)| *path),
+        CheckCategory::Device,
+    );
+    scope
+}
 
-/// Verify a CPIO archive against the install initramfs checklist.
-pub fn verify(reader: &CpioReader) -> VerificationReport {
+/// Verify an initramfs built for `arch` against the install initramfs checklist.
+pub fn verify_for(reader: &CpioReader, arch: Arch, spec: &Spec) -> VerificationReport {
     let mut report = VerificationReport::new("Install Initramfs");
 
     // Check binaries
@@ -301,11 +361,14 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
                         CheckCategory::Symlink,
                     ));
                 } else {
-                    report.add(CheckResult::fail(
-                        "init",
-                        CheckCategory::Symlink,
-                        format!("Symlink points to '{}' instead of systemd", target),
-                    ));
+                    report.add(
+                        CheckResult::fail(
+                            "init",
+                            CheckCategory::Symlink,
+                            format!("Symlink points to '{}' instead of systemd", target),
+                        )
+                        .with_fix(Fix::symlink("init", "/usr/lib/systemd/systemd")),
+                    );
                 }
             } else {
                 report.add(CheckResult::fail(
@@ -333,7 +396,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     }
 
     // Check symlinks (merged-usr symlinks)
-    for (link, target) in SYMLINKS {
+    for (link, target) in SYMLINKS.iter().filter(|(l, _)| arch.has(l)) {
         if let Some(entry) = reader.get(link) {
             if entry.is_symlink() {
                 if let Some(ref actual_target) = entry.link_target {
@@ -343,11 +406,14 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
                             CheckCategory::Symlink,
                         ));
                     } else {
-                        report.add(CheckResult::fail(
-                            format!("{} -> {}", link, target),
-                            CheckCategory::Symlink,
-                            format!("Points to '{}' instead", actual_target),
-                        ));
+                        report.add(
+                            CheckResult::fail(
+                                format!("{} -> {}", link, target),
+                                CheckCategory::Symlink,
+                                format!("Points to '{}' instead", actual_target),
+                            )
+                            .with_fix(Fix::symlink(*link, *target)),
+                        );
                     }
                 } else {
                     report.add(CheckResult::fail(
@@ -364,7 +430,10 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
                 ));
             }
         } else {
-            report.add(CheckResult::fail(*link, CheckCategory::Symlink, "Missing"));
+            report.add(
+                CheckResult::fail(*link, CheckCategory::Symlink, "Missing")
+                    .with_fix(Fix::symlink(*link, *target)),
+            );
         }
     }
 
@@ -439,11 +508,14 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
         if reader.exists(wants) {
             report.add(CheckResult::pass(*wants, CheckCategory::Symlink));
         } else {
-            report.add(CheckResult::fail(
-                *wants,
-                CheckCategory::Symlink,
-                "Missing (service not enabled)",
-            ));
+            report.add(
+                CheckResult::fail(
+                    *wants,
+                    CheckCategory::Symlink,
+                    "Missing (service not enabled)",
+                )
+                .with_fix(Fix::wants(wants)),
+            );
         }
     }
 
@@ -461,7 +533,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     }
 
     // Check directories
-    for dir in DIRS {
+    for dir in DIRS.iter().filter(|d| arch.has(d)) {
         if reader.exists(dir) {
             report.add(CheckResult::pass(*dir, CheckCategory::Directory));
         } else {
@@ -469,9 +541,14 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
         }
     }
 
+    // Check /dev/console and /dev/null (or devtmpfs mounted by systemd)
+    for result in devices::check_required(reader) {
+        report.add(result);
+    }
+
     // Check kernel modules (note: some may be built-in)
     // We look in lib/modules/<version>/kernel/... for .ko, .ko.xz, or .ko.gz files
-    for module in MODULES {
+    for module in &spec.install_modules {
         let found = reader.entries().iter().any(|e| {
             let path = &e.path;
             path.contains("lib/modules/")
@@ -487,7 +564,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
                 format!("module: {}", module),
                 CheckCategory::KernelModule,
             ));
-        } else if TYPICALLY_BUILTIN.contains(module) {
+        } else if spec.install_modules_builtin.contains(module) {
             // Module is built-in to LevitateOS kernel - not a failure
             report.add(CheckResult::pass(
                 format!("module: {} (built-in to kernel)", module),
@@ -528,5 +605,15 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
         }
     }
 
+    // Validate udev rule syntax, RUN helpers and GOTO targets
+    for result in udev::check(reader) {
+        report.add(result);
+    }
+
+    // Check for empty files in critical locations (truncated units, binaries)
+    check_empty_files(&mut report, reader, arch);
+
+    report.apply_prerequisites(PREREQUISITES, |path| has_path(reader, path));
+    report.apply_hints(HINTS);
     report
 }
@@ -0,0 +1,174 @@
+//! ELF interpreter (PT_INTERP) resolution
+//!
+//! A dynamically linked binary names the loader that runs it
+//! (`/lib64/ld-linux-x86-64.so.2`). If that path doesn't resolve, exec fails
+//! with a bare "No such file or directory" for a binary that is plainly
+//! there, and existence checklists pass. Every ELF in the archive is read and
+//! its interpreter resolved through the archive's own symlinks, merged-usr
+//! directory links included.
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use crate::reader::ArchiveReader;
+use std::collections::BTreeMap;
+
+/// Requesting binaries named in a failure before the rest are counted
+const SHOWN_USERS: usize = 3;
+
+/// Program header type of the segment naming the interpreter
+const PT_INTERP: u64 = 3;
+
+/// Interpreter path an ELF image requests, if it is a dynamically linked ELF.
+///
+/// Read straight from the program headers, for either class and byte order.
+pub fn interpreter(data: &[u8]) -> Option<String> {
+    if !data.starts_with(b"\x7fELF") {
+        return None;
+    }
+    let wide = match data.get(4)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let big = match data.get(5)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let word = |offset: usize, len: usize| uint(data, offset, len, big);
+    // (e_phoff, e_phentsize, e_phnum) and (p_offset, p_filesz) by class
+    let (phoff, phentsize, phnum) = if wide {
+        (word(0x20, 8)?, word(0x36, 2)?, word(0x38, 2)?)
+    } else {
+        (word(0x1c, 4)?, word(0x2a, 2)?, word(0x2c, 2)?)
+    };
+    let (offset_at, size_at, len) = if wide { (8, 0x20, 8) } else { (4, 0x10, 4) };
+
+    (0..phnum).find_map(|i| {
+        let header = usize::try_from(phoff.checked_add(i.checked_mul(phentsize)?)?).ok()?;
+        if word(header, 4)? != PT_INTERP {
+            return None;
+        }
+        let start = usize::try_from(word(header + offset_at, len)?).ok()?;
+        let size = usize::try_from(word(header + size_at, len)?).ok()?;
+        let raw = data.get(start..start.checked_add(size)?)?;
+        let path = raw.split(|b| *b == 0).next().unwrap_or_default();
+        std::str::from_utf8(path).ok().map(str::to_string)
+    })
+}
+
+/// Unsigned integer of `len` bytes at `offset`.
+fn uint(data: &[u8], offset: usize, len: usize, big: bool) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(len)?)?;
+    let fold = |value: u64, byte: &u8| value << 8 | u64::from(*byte);
+    Some(if big {
+        bytes.iter().fold(0, fold)
+    } else {
+        bytes.iter().rev().fold(0, fold)
+    })
+}
+
+/// Flag interpreters that don't resolve to a regular file in the archive,
+/// one result per interpreter with the binaries that request it.
+///
+/// Only failures are returned - a clean archive adds nothing to the report.
+pub fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    let mut requested: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let scan = reader.visit_files(|path, content| {
+        if let Some(interp) = interpreter(content) {
+            requested.entry(interp).or_default().push(path.to_string());
+        }
+    });
+    if let Err(e) = scan {
+        return vec![CheckResult::fail(
+            "ELF interpreters",
+            CheckCategory::Library,
+            format!("Cannot scan binaries: {}", e),
+        )];
+    }
+
+    requested
+        .into_iter()
+        .filter_map(|(interp, users)| {
+            let problem = match reader.resolve(&interp) {
+                None => "Missing",
+                Some(target) if reader.get(&target).is_some_and(|e| e.is_file()) => {
+                    return None
+                }
+                Some(_) => "Not a regular file",
+            };
+            Some(
+                CheckResult::fail(
+                    &interp,
+                    CheckCategory::Library,
+                    format!("{}, requested by {}", problem, describe(&users)),
+                )
+                .with_hint("The dynamic loader (glibc) or its lib64 -> usr/lib64 symlink wasn't installed; these binaries fail to exec with \"No such file or directory\""),
+            )
+        })
+        .collect()
+}
+
+/// `usr/bin/ls, usr/bin/cat, usr/bin/cp and 12 more`
+pub(super) fn describe(users: &[String]) -> String {
+    let shown = users[..users.len().min(SHOWN_USERS)].join(", ");
+    match users.len().saturating_sub(SHOWN_USERS) {
+        0 => shown,
+        rest => format!("{} and {} more", shown, rest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let users: Vec<String> = (1..=5).map(|i| format!("usr/bin/t{}", i)).collect();
+        assert_eq!(describe(&users[..1]), "usr/bin/t1");
+        assert_eq!(
+            describe(&users),
+            "usr/bin/t1, usr/bin/t2, usr/bin/t3 and 2 more"
+        );
+        assert_eq!(interpreter(b"#!/bin/sh\n"), None);
+    }
+
+    #[test]
+    fn test_interpreter() {
+        const LOADER: &[u8] = b"/lib64/ld-linux-x86-64.so.2\0";
+
+        // ELF64 little endian: one PT_LOAD, then PT_INTERP
+        let mut elf = vec![0u8; 0x100];
+        elf[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        elf[0x20] = 0x40;
+        elf[0x36] = 0x38;
+        elf[0x38] = 2;
+        elf[0x40] = 1;
+        elf[0x78] = 3;
+        elf[0x80] = 0xc0;
+        elf[0x98] = LOADER.len() as u8;
+        elf[0xc0..0xc0 + LOADER.len()].copy_from_slice(LOADER);
+        assert_eq!(
+            interpreter(&elf).as_deref(),
+            Some("/lib64/ld-linux-x86-64.so.2")
+        );
+
+        // Segment past the end of the file
+        elf[0x80] = 0xf0;
+        assert_eq!(interpreter(&elf), None);
+
+        // ELF32 big endian, statically linked then dynamic
+        let mut elf = vec![0u8; 0x80];
+        elf[..6].copy_from_slice(b"\x7fELF\x01\x02");
+        elf[0x1f] = 0x34;
+        elf[0x2b] = 0x20;
+        elf[0x2d] = 1;
+        elf[0x37] = 1;
+        assert_eq!(interpreter(&elf), None);
+        elf[0x37] = 3;
+        elf[0x3b] = 0x60;
+        elf[0x47] = 8;
+        elf[0x60..0x68].copy_from_slice(b"/lib/ld\0");
+        assert_eq!(interpreter(&elf).as_deref(), Some("/lib/ld"));
+    }
+}
@@ -33,14 +33,27 @@
 //! 3. User selects boot option (or default after timeout)
 //! 4. UKI contains kernel + initramfs + cmdline
 //! 5. Kernel extracts initramfs to rootfs
-//! 6. init_tiny mounts ISO by LABEL=<distro label>
+//! 6. init_tiny mounts ISO by `LABEL=<distro label>`
 //! 7. Mounts `/live/filesystem.erofs` as lower layer
 //! 8. Mounts `live/overlayfs.erofs` as middle lowerdir payload
 //! 9. Mounts tmpfs as upper layer (for writes)
 //! 10. switch_root to overlay
 
-use super::{CheckCategory, CheckResult, VerificationReport};
+use super::dtb;
+use super::efi::{self, EfiTrees};
+use super::getty::UkiConsoles;
+use super::grub;
+use super::live_overlay;
+use super::patterns::GlobCheck;
+use super::placement;
+use super::secure_boot;
+use super::{CheckCategory, CheckResult, Hint, Scope, VerificationReport};
+use crate::arch::Arch;
 use crate::iso::IsoReader;
+use crate::magic::{self, FileKind};
+use crate::overlay::{IsoOverlay, OverlayTree};
+use crate::spec::Spec;
+use crate::uki;
 use std::process::Command;
 
 // Import constants from distro-spec
@@ -103,10 +116,6 @@ pub const ROOTFS_FILES: &[&str] = &[
     LIVE_OVERLAYFS_ISO_PATH, // "live/overlayfs.erofs"
 ];
 
-/// Compatibility alias retained in distro-spec. Checklist uses image path.
-#[allow(dead_code)]
-pub const OVERLAY_COMPAT_ALIAS: &str = LIVE_OVERLAY_ISO_PATH;
-
 /// Required number of live UKIs under `/EFI/Linux`.
 pub const LIVE_UKI_MIN_COUNT: usize = 3;
 
@@ -120,8 +129,75 @@ pub const LOADER_CONF: &str = LOADER_CONF_FILENAME; // "loader.conf"
 // VERIFICATION
 // =============================================================================
 
-/// Verify an ISO image against the live ISO checklist.
-pub fn verify(reader: &IsoReader) -> VerificationReport {
+/// Where to fix failures, by category and path prefix.
+pub const HINTS: &[Hint] = &[
+    Hint::new(
+        CheckCategory::Directory,
+        "",
+        "Create it when staging the ISO tree in leviso",
+    ),
+    Hint::new(
+        CheckCategory::Binary,
+        "",
+        "Copy it into the ISO tree in leviso (paths come from distro-spec/src/shared)",
+    ),
+    Hint::new(
+        CheckCategory::Binary,
+        "EFI/Linux",
+        "Build the live/emergency/debug UKIs with ukify in leviso",
+    ),
+    Hint::new(
+        CheckCategory::Other,
+        "",
+        "Check the leviso ISO build step that produces it",
+    ),
+    Hint::new(
+        CheckCategory::EtcFile,
+        "",
+        "Add it to the live overlay staged by leviso",
+    ),
+];
+
+/// Paths referenced by the ISO checklist.
+pub fn scope() -> Scope {
+    let mut scope = Scope::new();
+    scope.extend(DIRS, CheckCategory::Directory);
+    scope.extend(ROOTFS_FILES, CheckCategory::Other);
+    for arch in Arch::ALL {
+        scope.extend(
+            BOOT_FILES.iter().map(|file| arch.path(file)),
+            CheckCategory::Binary,
+        );
+        scope.add(
+            arch.path(&format!("{}/{}", ISO_EFI_DIR, EFI_BOOTLOADER)),
+            CheckCategory::Binary,
+        );
+    }
+    scope.add(EFIBOOT_FILENAME, CheckCategory::Other);
+    scope.add_prefix(dtb::BOOT_DIR, CheckCategory::Other);
+    scope.add_prefix(grub::DIR, CheckCategory::EtcFile);
+    scope.add_prefix(UKI_EFI_DIR, CheckCategory::Binary);
+    scope.add(
+        format!("{}/{}", LOADER_ENTRIES_DIR, LOADER_CONF),
+        CheckCategory::EtcFile,
+    );
+    scope.add_prefix(LIVE_OVERLAY_ISO_PATH, CheckCategory::EtcFile);
+    scope
+}
+
+/// Verify an ISO image, running the live overlay sub-checklist against the
+/// given tree (e.g. an extracted overlayfs.erofs), or against `live/overlay/`
+/// on the ISO when none is given. Kernel and EFI loader names follow `arch`.
+///
+/// The GRUB checks run when the ISO boots legacy BIOS through
+/// `boot/grub/grub.cfg`. EFI binaries report their signers; the profile
+/// decides whether unsigned ones fail (see [`secure_boot`]).
+pub fn verify_with_overlay(
+    reader: &IsoReader,
+    overlay: Option<&dyn OverlayTree>,
+    arch: Arch,
+    spec: &Spec,
+) -> VerificationReport {
     let mut report = VerificationReport::new("Live ISO");
     let partitioned_payload = detect_partitioned_live_payload(reader);
 
@@ -145,9 +221,13 @@ pub fn verify(reader: &IsoReader) -> VerificationReport {
     // 2. Check boot files (kernel, initramfs)
     // =========================================================================
     for file in BOOT_FILES {
-        let path = format!("/{}", file);
+        let path = format!("/{}", arch.path(file));
         if reader.exists(&path) {
-            report.add(CheckResult::pass(&path, CheckCategory::Binary));
+            let expected = match *file {
+                KERNEL_ISO_PATH => vec![FileKind::kernel(arch)],
+                _ => INITRAMFS_KINDS.to_vec(),
+            };
+            report.add(content_check(reader, &path, &expected));
         } else {
             report.add(CheckResult::fail(
                 &path,
@@ -157,6 +237,23 @@ pub fn verify(reader: &IsoReader) -> VerificationReport {
         }
     }
 
+    // Device trees next to an aarch64 kernel, built with it
+    if arch == Arch::Aarch64 {
+        let kernel = format!("/{}", arch.path(KERNEL_ISO_PATH));
+        let version = reader
+            .read_file(&kernel)
+            .ok()
+            .and_then(|image| dtb::kernel_version(&image));
+        let files = reader
+            .entries()
+            .iter()
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| entry.path.trim_start_matches('/'));
+        for result in dtb::check(files, Vec::from_iter(version).as_slice()) {
+            report.add(result);
+        }
+    }
+
     // =========================================================================
     // 3. Check rootfs files
     // =========================================================================
@@ -185,9 +282,9 @@ pub fn verify(reader: &IsoReader) -> VerificationReport {
     // 4. Check EFI boot files
     // =========================================================================
     // systemd-boot in EFI/BOOT/
-    let bootloader_path = format!("/{}/{}", ISO_EFI_DIR, EFI_BOOTLOADER);
+    let bootloader_path = arch.path(&format!("/{}/{}", ISO_EFI_DIR, EFI_BOOTLOADER));
     if reader.exists(&bootloader_path) {
-        report.add(CheckResult::pass(&bootloader_path, CheckCategory::Binary));
+        report.add(content_check(reader, &bootloader_path, &[FileKind::Pe]));
     } else {
         report.add(CheckResult::fail(
             &bootloader_path,
@@ -212,27 +309,26 @@ pub fn verify(reader: &IsoReader) -> VerificationReport {
     // 5. Check live UKIs in EFI/Linux/
     // =========================================================================
     let uki_prefix = format!("/{}/", UKI_EFI_DIR);
-    let live_uki_count = reader
+    let ukis = reader
         .entries()
         .iter()
-        .filter(|entry| {
-            !entry.is_dir && entry.path.starts_with(&uki_prefix) && entry.path.ends_with(".efi")
-        })
-        .count();
-    if live_uki_count >= LIVE_UKI_MIN_COUNT {
-        report.add(CheckResult::pass(
-            format!("{}*.efi (count={})", uki_prefix, live_uki_count),
-            CheckCategory::Binary,
-        ));
-    } else {
-        report.add(CheckResult::fail(
-            format!("{}*.efi (count={})", uki_prefix, live_uki_count),
-            CheckCategory::Binary,
-            format!(
+        .filter(|entry| !entry.is_dir)
+        .map(|entry| entry.path.as_str());
+    report.add(
+        GlobCheck::new(&format!("{}/*.efi", UKI_EFI_DIR), CheckCategory::Binary)
+            .at_least(LIVE_UKI_MIN_COUNT)
+            .labelled(format!("{}*.efi", uki_prefix))
+            .show_count()
+            .failure(format!(
                 "Expected at least {} live UKIs in EFI/Linux",
                 LIVE_UKI_MIN_COUNT
-            ),
-        ));
+            ))
+            .check(ukis),
+    );
+    for entry in reader.entries() {
+        if !entry.is_dir && entry.path.starts_with(&uki_prefix) && entry.path.ends_with(".efi") {
+            report.add(content_check(reader, &entry.path, &[FileKind::Pe]));
+        }
     }
 
     // =========================================================================
@@ -322,9 +418,98 @@ pub fn verify(reader: &IsoReader) -> VerificationReport {
         ));
     }
 
+    // =========================================================================
+    // 8. Check live overlay contents (drop-ins, getty, shadowed binaries)
+    // =========================================================================
+    let iso_overlay = IsoOverlay::new(reader, LIVE_OVERLAY_ISO_PATH);
+    let overlay = match overlay {
+        Some(overlay) => Some(overlay),
+        None if reader.exists(LIVE_OVERLAY_ISO_PATH) => Some(&iso_overlay as &dyn OverlayTree),
+        None => None,
+    };
+    if let Some(overlay) = overlay {
+        let ukis = uki_consoles(reader, &uki_prefix, &mut report);
+        for result in live_overlay::check(overlay, &ukis, spec) {
+            report.add(result);
+        }
+    }
+
+    // =========================================================================
+    // 8b. Check boot file placement (extents, 4 GiB, El Torito load size)
+    // =========================================================================
+    for result in placement::check(reader, arch) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 9. Check GRUB legacy BIOS boot (dual-bootloader spins)
+    // =========================================================================
+    if reader.exists(&format!("/{}", grub::CONFIG)) {
+        for result in grub::check(reader) {
+            report.add(result);
+        }
+    }
+
+    // =========================================================================
+    // 10. Check EFI binaries: PE headers, efiboot.img room, Secure Boot
+    // =========================================================================
+    let efi_trees = EfiTrees::open(reader);
+    for result in efi::check(&efi_trees, arch) {
+        report.add(result);
+    }
+    for result in secure_boot::check(&efi_trees, arch, spec.secure_boot) {
+        report.add(result);
+    }
+
+    report.apply_hints(HINTS);
     report
 }
 
+/// What an initramfs may be: a CPIO, compressed or not.
+const INITRAMFS_KINDS: &[FileKind] =
+    &[FileKind::Cpio, FileKind::Gzip, FileKind::Xz, FileKind::Zstd];
+
+/// Check that a boot binary's content matches its name, so a zero-byte
+/// placeholder or a misnamed file doesn't pass as present.
+fn content_check(reader: &IsoReader, path: &str, expected: &[FileKind]) -> CheckResult {
+    match reader.read_head(path, magic::HEAD_LEN) {
+        Ok(head) => magic::check(path, CheckCategory::Binary, &head, expected),
+        Err(e) => CheckResult::fail(path, CheckCategory::Binary, format!("Cannot read: {}", e)),
+    }
+}
+
+/// Read the `console=` arguments from every UKI's embedded cmdline.
+fn uki_consoles(
+    reader: &IsoReader,
+    uki_prefix: &str,
+    report: &mut VerificationReport,
+) -> Vec<UkiConsoles> {
+    let mut ukis = Vec::new();
+
+    for entry in reader.entries() {
+        if entry.is_dir || !entry.path.starts_with(uki_prefix) || !entry.path.ends_with(".efi") {
+            continue;
+        }
+        let cmdline = reader
+            .read_file(&entry.path)
+            .and_then(|data| uki::cmdline(&data));
+        match cmdline {
+            Ok(Some(cmdline)) => ukis.push(UkiConsoles {
+                uki: entry.path.clone(),
+                consoles: uki::consoles(&cmdline),
+            }),
+            Ok(None) => {}
+            Err(e) => report.add(CheckResult::fail(
+                &entry.path,
+                CheckCategory::Binary,
+                format!("Cannot read UKI cmdline: {}", e),
+            )),
+        }
+    }
+
+    ukis
+}
+
 fn detect_partitioned_live_payload(reader: &IsoReader) -> bool {
     let iso_path = reader.source_path();
     let output = match Command::new("fdisk").arg("-l").arg(iso_path).output() {
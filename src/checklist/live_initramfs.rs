@@ -20,8 +20,13 @@
 //! 8. Sets up overlay for writable layer
 //! 9. switch_root to live system
 
-use super::{CheckCategory, CheckResult, VerificationReport};
+use super::patterns::GlobCheck;
+use super::{
+    check_empty_files, devices, CheckCategory, CheckResult, Hint, Scope, VerificationReport,
+};
 use crate::cpio::CpioReader;
+use crate::fixit::Fix;
+use crate::spec::Spec;
 
 // =============================================================================
 // DIRECTORIES - from recinit/src/tiny.rs INITRAMFS_DIRS
@@ -107,26 +112,73 @@ pub const APPLETS: &[&str] = &[
 /// The init script path and requirements.
 pub const INIT_PATH: &str = "init";
 
-/// Expected init script shebang.
-pub const INIT_SHEBANG: &str = "#!/bin/sh";
-
 /// permissions for init (must be executable).
 pub const INIT_PERMS: u32 = 0o755;
 
 // =============================================================================
-// KERNEL MODULES - from distro-spec (SINGLE SOURCE OF TRUTH)
+// VERIFICATION
 // =============================================================================
 
-// Re-export for consumers that expect these here
-pub use distro_spec::shared::LIVE_MODULES as MODULES;
-pub use distro_spec::shared::LIVE_MODULES_BUILTIN as TYPICALLY_BUILTIN;
+/// Where to fix failures, by category and path prefix.
+pub const HINTS: &[Hint] = &[
+    Hint::new(
+        CheckCategory::Device,
+        "",
+        "Mount devtmpfs on /dev in the init script (tools/recinit/src/tiny.rs)",
+    ),
+    Hint::new(
+        CheckCategory::Directory,
+        "",
+        "Add it to INITRAMFS_DIRS in tools/recinit/src/tiny.rs",
+    ),
+    Hint::new(
+        CheckCategory::Binary,
+        "",
+        "Copy a static busybox in tools/recinit/src/busybox.rs",
+    ),
+    Hint::new(
+        CheckCategory::Binary,
+        "init",
+        "The init script is generated in tools/recinit/src/tiny.rs",
+    ),
+    Hint::new(
+        CheckCategory::Symlink,
+        "",
+        "Add the applet to BUSYBOX_COMMANDS in tools/recinit/src/busybox.rs",
+    ),
+    Hint::new(
+        CheckCategory::KernelModule,
+        "",
+        "Add it to distro-spec/src/shared/modules.rs, or build it into the kernel",
+    ),
+    Hint::new(
+        CheckCategory::EtcFile,
+        "",
+        "Run depmod on the staged modules in tools/recinit/src/tiny.rs",
+    ),
+];
 
-// =============================================================================
-// VERIFICATION
-// =============================================================================
+/// Paths referenced by the live initramfs checklist.
+pub fn scope() -> Scope {
+    let mut scope = Scope::new();
+    scope.add(BUSYBOX_BINARY, CheckCategory::Binary);
+    scope.add(INIT_PATH, CheckCategory::Binary);
+    scope.extend(
+        APPLETS.iter().map(|a| format!("bin/{}", a)),
+        CheckCategory::Symlink,
+    );
+    scope.extend(DIRS, CheckCategory::Directory);
+    scope.add_prefix("lib/modules", CheckCategory::KernelModule);
+    scope.add_prefix("usr/lib/modules", CheckCategory::KernelModule);
+    scope.extend(
+        devices::REQUIRED_NODES.iter().map(|(path, _, _)| *path),
+        CheckCategory::Device,
+    );
+    scope
+}
 
 /// Verify a CPIO archive against the live initramfs checklist.
-pub fn verify(reader: &CpioReader) -> VerificationReport {
+pub fn verify(reader: &CpioReader, spec: &Spec) -> VerificationReport {
     let mut report = VerificationReport::new("Live Initramfs");
 
     // =========================================================================
@@ -152,11 +204,14 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
                     CheckCategory::Binary,
                 ));
             } else {
-                report.add(CheckResult::fail(
-                    BUSYBOX_BINARY,
-                    CheckCategory::Binary,
-                    format!("Not executable (mode {:04o})", perms),
-                ));
+                report.add(
+                    CheckResult::fail(
+                        BUSYBOX_BINARY,
+                        CheckCategory::Binary,
+                        format!("Not executable (mode {:04o})", perms),
+                    )
+                    .with_fix(Fix::chmod(BUSYBOX_BINARY, perms | 0o111)),
+                );
             }
         } else {
             report.add(CheckResult::fail(
@@ -188,11 +243,14 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
                             CheckCategory::Symlink,
                         ));
                     } else {
-                        report.add(CheckResult::fail(
-                            format!("applet: {}", applet),
-                            CheckCategory::Symlink,
-                            format!("Points to '{}' instead of 'busybox'", target),
-                        ));
+                        report.add(
+                            CheckResult::fail(
+                                format!("applet: {}", applet),
+                                CheckCategory::Symlink,
+                                format!("Points to '{}' instead of 'busybox'", target),
+                            )
+                            .with_fix(Fix::symlink(&applet_path, "busybox")),
+                        );
                     }
                 } else {
                     report.add(CheckResult::fail(
@@ -215,11 +273,14 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
                 ));
             }
         } else {
-            report.add(CheckResult::fail(
-                format!("applet: {}", applet),
-                CheckCategory::Symlink,
-                "Missing",
-            ));
+            report.add(
+                CheckResult::fail(
+                    format!("applet: {}", applet),
+                    CheckCategory::Symlink,
+                    "Missing",
+                )
+                .with_fix(Fix::symlink(&applet_path, "busybox")),
+            );
         }
     }
 
@@ -235,14 +296,17 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
                     CheckCategory::Binary,
                 ));
             } else {
-                report.add(CheckResult::fail(
-                    INIT_PATH,
-                    CheckCategory::Binary,
-                    format!(
-                        "Not executable (mode {:04o}, need {:04o})",
-                        perms, INIT_PERMS
-                    ),
-                ));
+                report.add(
+                    CheckResult::fail(
+                        INIT_PATH,
+                        CheckCategory::Binary,
+                        format!(
+                            "Not executable (mode {:04o}, need {:04o})",
+                            perms, INIT_PERMS
+                        ),
+                    )
+                    .with_fix(Fix::chmod(INIT_PATH, INIT_PERMS)),
+                );
             }
         } else if init.is_symlink() {
             if let Some(ref target) = init.link_target {
@@ -269,7 +333,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 5. Check kernel modules
     // =========================================================================
-    for module in MODULES {
+    for module in &spec.live_modules {
         // Search for module in lib/modules/<version>/kernel/...
         let found = reader.entries().iter().any(|e| {
             let path = &e.path;
@@ -285,7 +349,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
                 format!("module: {}", module),
                 CheckCategory::KernelModule,
             ));
-        } else if TYPICALLY_BUILTIN.contains(module) {
+        } else if spec.live_modules_builtin.contains(module) {
             // Don't fail for typically built-in modules
             report.add(CheckResult::pass(
                 format!("module: {} (built-in to kernel)", module),
@@ -303,21 +367,13 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 6. Check for modules.dep (needed by modprobe)
     // =========================================================================
-    let has_modules_dep = reader
-        .entries()
-        .iter()
-        .any(|e| e.path.contains("lib/modules/") && e.path.ends_with("modules.dep"));
-
-    if has_modules_dep {
-        report.add(CheckResult::pass("modules.dep", CheckCategory::EtcFile));
-    } else {
-        // Not critical for insmod (which loads directly), but needed for modprobe
-        report.add(CheckResult::fail(
-            "modules.dep",
-            CheckCategory::EtcFile,
-            "Missing (modprobe won't work, but insmod will)",
-        ));
-    }
+    // Not critical for insmod (which loads directly), but needed for modprobe
+    report.add(
+        GlobCheck::new("**/lib/modules/**/modules.dep", CheckCategory::EtcFile)
+            .labelled("modules.dep")
+            .failure("Missing (modprobe won't work, but insmod will)")
+            .check_cpio(reader),
+    );
 
     // =========================================================================
     // 7. Check that all symlinks resolve
@@ -341,12 +397,27 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
         }
     }
 
+    // =========================================================================
+    // 8. Check /dev/console and /dev/null (or devtmpfs mounted by /init)
+    // =========================================================================
+    for result in devices::check_required(reader) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 9. Check for empty files in critical locations
+    // =========================================================================
+    let arch = crate::arch::detect_cpio(reader).unwrap_or_default();
+    check_empty_files(&mut report, reader, arch);
+
+    report.apply_hints(HINTS);
     report
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use distro_spec::shared::LIVE_MODULES as MODULES;
 
     #[test]
     fn test_dirs_match_recinit() {
@@ -0,0 +1,337 @@
+//! Live overlay checklist
+//!
+//! The ISO checklist only confirms the overlay exists. This sub-checklist
+//! descends into it and verifies:
+//! - The expected live drop-ins are present
+//! - Getty/autologin/console configuration (see [`super::getty`])
+//! - Nothing in the overlay shadows a critical rootfs binary or library
+//!
+//! The overlay is layered *above* the rootfs, so a stray `usr/bin/ls` in it
+//! silently replaces the real one for every live session.
+//!
+//! Given the lower rootfs ([`check_whiteouts`]), whiteouts and opaque
+//! directories must name paths that exist in it: one that doesn't is a
+//! leftover from an older rootfs, or a typo that deletes nothing. So must
+//! redirects and metacopy files, or the renamed directory comes up empty
+//! and the file without data in the booted system.
+
+use super::getty::{self, UkiConsoles};
+use super::{CheckCategory, CheckResult};
+use crate::diff::{EntryKind, EntryMap};
+use crate::overlay::OverlayTree;
+use crate::spec::Spec;
+use std::collections::{HashMap, HashSet};
+
+/// Files the live overlay must provide.
+/// Must match the live overlay staged by leviso.
+pub const EXPECTED_FILES: &[&str] = &[
+    // Autologin on the first VT and the default serial console
+    "etc/systemd/system/getty@tty1.service.d/autologin.conf",
+    "etc/systemd/system/serial-getty@ttyS0.service.d/autologin.conf",
+    // Live user account
+    "etc/passwd",
+    "etc/shadow",
+    "etc/group",
+    // Pre-login banner
+    "etc/issue",
+];
+
+/// Verify the contents of a live overlay tree.
+pub fn check(overlay: &dyn OverlayTree, ukis: &[UkiConsoles], spec: &Spec) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    let paths = overlay.paths();
+
+    // =========================================================================
+    // 1. Expected drop-ins and live config
+    // =========================================================================
+    for file in EXPECTED_FILES {
+        if paths.iter().any(|p| p == file) {
+            results.push(CheckResult::pass(*file, CheckCategory::EtcFile));
+        } else {
+            results.push(
+                CheckResult::fail(*file, CheckCategory::EtcFile, "Missing from live overlay")
+                    .with_hint("Add it to the live overlay staged by leviso"),
+            );
+        }
+    }
+
+    // =========================================================================
+    // 2. Getty/autologin/console configuration
+    // =========================================================================
+    results.extend(
+        getty::check(overlay, ukis)
+            .into_iter()
+            // etc/issue is already covered by EXPECTED_FILES
+            .filter(|r| !EXPECTED_FILES.contains(&r.item.as_str())),
+    );
+
+    // =========================================================================
+    // 3. Overlay must not shadow critical rootfs files
+    // =========================================================================
+    let critical = critical_rootfs_paths(spec);
+    let whiteouts = overlay.whiteouts();
+    for path in &paths {
+        let canonical = merged_usr_path(path);
+        if critical.contains(canonical.as_str()) {
+            let action = if whiteouts.contains(path) {
+                "whites out"
+            } else {
+                "shadows"
+            };
+            results.push(
+                CheckResult::fail(
+                    path.as_str(),
+                    CheckCategory::Forbidden,
+                    format!("Overlay {} rootfs {}", action, canonical),
+                )
+                .with_hint("Remove it from the live overlay; fix the rootfs copy instead"),
+            );
+        }
+    }
+
+    results
+}
+
+/// Check the overlay's whiteouts, opaque directories, redirects and
+/// metacopy files against the lower rootfs they apply to.
+pub fn check_whiteouts(overlay: &dyn OverlayTree, lower: &EntryMap) -> Vec<CheckResult> {
+    let in_lower = |path: &str| {
+        lower
+            .get(path)
+            .or_else(|| lower.get(&merged_usr_path(path)))
+            .map(|e| e.kind)
+    };
+    let mut results = Vec::new();
+    for path in overlay.whiteouts() {
+        let item = format!("{} (whiteout)", path);
+        results.push(match in_lower(&path) {
+            Some(_) => CheckResult::pass(item, CheckCategory::Other),
+            None => CheckResult::fail(item, CheckCategory::Other, "Not in the lower rootfs")
+                .with_hint(
+                    "The whiteout deletes nothing; drop it from the overlay or fix its path",
+                ),
+        });
+    }
+    for path in overlay.opaque_dirs() {
+        let item = format!("{} (opaque)", path);
+        results.push(match in_lower(&path) {
+            Some(EntryKind::Directory) => CheckResult::pass(item, CheckCategory::Other),
+            Some(_) => CheckResult::fail(
+                item,
+                CheckCategory::Other,
+                "Not a directory in the lower rootfs",
+            ),
+            None => CheckResult::fail(item, CheckCategory::Other, "Not in the lower rootfs")
+                .with_hint("The opaque xattr hides nothing; drop it or fix the directory's path"),
+        });
+    }
+
+    let redirects: HashMap<String, String> = overlay.redirects().into_iter().collect();
+    let mut redirected: Vec<&String> = redirects.keys().collect();
+    redirected.sort();
+    for path in redirected {
+        let target = lower_path(path, &redirects);
+        let item = format!("{} (redirect)", path);
+        results.push(match in_lower(&target) {
+            Some(_) => CheckResult::pass(format!("{} -> {}", item, target), CheckCategory::Other),
+            None => CheckResult::fail(
+                item,
+                CheckCategory::Other,
+                format!("Redirects to {}, which is not in the lower rootfs", target),
+            )
+            .with_hint("Its lower contents are lost; fix the redirect xattr or copy it up fully"),
+        });
+    }
+    for path in overlay.metacopies() {
+        let target = lower_path(&path, &redirects);
+        let item = format!("{} (metacopy)", path);
+        results.push(match in_lower(&target) {
+            Some(EntryKind::File) => CheckResult::pass(item, CheckCategory::Other),
+            Some(_) => CheckResult::fail(
+                item,
+                CheckCategory::Other,
+                format!(
+                    "Takes its data from {}, not a regular file in the lower rootfs",
+                    target
+                ),
+            ),
+            None => CheckResult::fail(
+                item,
+                CheckCategory::Other,
+                format!(
+                    "Takes its data from {}, which is not in the lower rootfs",
+                    target
+                ),
+            )
+            .with_hint("The file has no data in the live system; copy it up fully"),
+        });
+    }
+    results
+}
+
+/// Lower path an overlay entry takes its contents from, following its
+/// redirect and its parents'. An absolute redirect is from the lower root;
+/// a relative one names the entry in its parent's lower directory.
+fn lower_path(path: &str, redirects: &HashMap<String, String>) -> String {
+    if let Some(target) = redirects.get(path).and_then(|t| t.strip_prefix('/')) {
+        return target.trim_end_matches('/').to_string();
+    }
+    let (parent, name) = match path.rsplit_once('/') {
+        Some((parent, name)) => (Some(parent), name),
+        None => (None, path),
+    };
+    let name = redirects.get(path).map_or(name, String::as_str);
+    match parent {
+        Some(parent) => format!("{}/{}", lower_path(parent, redirects), name),
+        None => name.to_string(),
+    }
+}
+
+/// Why an overlay entry hiding the rootfs entry at `path` is a problem, or
+/// `None` if the overlay is meant to replace it ([`EXPECTED_FILES`]).
+pub fn unintended_shadow(path: &str, spec: &Spec) -> Option<&'static str> {
+    if EXPECTED_FILES.contains(&path) {
+        None
+    } else if critical_rootfs_paths(spec).contains(merged_usr_path(path).as_str()) {
+        Some("critical rootfs file")
+    } else {
+        Some("not a live overlay file")
+    }
+}
+
+/// Rootfs binaries and libraries the overlay must never replace.
+fn critical_rootfs_paths(spec: &Spec) -> HashSet<String> {
+    let mut paths = HashSet::new();
+    for bin in spec.bin_utils.iter().chain(&spec.auth_bin) {
+        paths.insert(format!("usr/bin/{}", bin));
+    }
+    for bin in spec.sbin_utils.iter().chain(&spec.auth_sbin) {
+        paths.insert(format!("usr/sbin/{}", bin));
+    }
+    for bin in spec
+        .systemd_binaries
+        .iter()
+        .map(String::as_str)
+        .chain(["systemd"])
+    {
+        paths.insert(format!("usr/lib/systemd/{}", bin));
+    }
+    paths.extend(spec.critical_libs.iter().cloned());
+    paths
+}
+
+/// Map pre-merge paths (`bin/ls`, `lib64/libc.so.6`) onto their `usr/` form.
+fn merged_usr_path(path: &str) -> String {
+    for prefix in ["bin/", "sbin/", "lib/", "lib64/"] {
+        if path.starts_with(prefix) {
+            return format!("usr/{}", path);
+        }
+    }
+    path.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merged_usr_path() {
+        assert_eq!(merged_usr_path("bin/ls"), "usr/bin/ls");
+        assert_eq!(merged_usr_path("lib64/libc.so.6"), "usr/lib64/libc.so.6");
+        assert_eq!(merged_usr_path("usr/bin/ls"), "usr/bin/ls");
+        assert_eq!(merged_usr_path("etc/issue"), "etc/issue");
+
+        assert_eq!(unintended_shadow("etc/passwd", Spec::builtin()), None);
+        assert_eq!(
+            unintended_shadow("etc/motd", Spec::builtin()),
+            Some("not a live overlay file")
+        );
+        assert_eq!(
+            unintended_shadow("usr/lib/systemd/systemd", Spec::builtin()),
+            Some("critical rootfs file")
+        );
+    }
+
+    struct Layer;
+
+    impl OverlayTree for Layer {
+        fn paths(&self) -> Vec<String> {
+            ["bin/ping", "etc/motd", "usr/share/doc"]
+                .map(String::from)
+                .to_vec()
+        }
+
+        fn read_to_string(&self, path: &str) -> Result<String, crate::error::FsdbgError> {
+            Err(crate::error::FsdbgError::file_not_found(path))
+        }
+
+        fn whiteouts(&self) -> Vec<String> {
+            vec!["bin/ping".to_string(), "etc/motd".to_string()]
+        }
+
+        fn opaque_dirs(&self) -> Vec<String> {
+            vec!["usr/share/doc".to_string()]
+        }
+
+        fn redirects(&self) -> Vec<(String, String)> {
+            vec![
+                ("usr/share/docs".to_string(), "/usr/share/doc".to_string()),
+                ("usr/share/docs/old".to_string(), "gone".to_string()),
+                ("usr/bin/pong".to_string(), "ping".to_string()),
+            ]
+        }
+
+        fn metacopies(&self) -> Vec<String> {
+            vec![
+                "usr/bin/pong".to_string(),
+                "usr/share/docs/README".to_string(),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_check_whiteouts() {
+        let entry = |kind| crate::diff::DiffEntry {
+            kind,
+            size: 0,
+            mode: None,
+            uid: None,
+            gid: None,
+            link_target: None,
+        };
+        let lower: EntryMap = [
+            ("usr/bin/ping".to_string(), entry(EntryKind::File)),
+            ("usr/share/doc".to_string(), entry(EntryKind::File)),
+        ]
+        .into_iter()
+        .collect();
+
+        let results = check_whiteouts(&Layer, &lower);
+        let failed: Vec<(&str, Option<&str>)> = results
+            .iter()
+            .filter(|r| !r.passed)
+            .map(|r| (r.item.as_str(), r.message.as_deref()))
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                ("etc/motd (whiteout)", Some("Not in the lower rootfs")),
+                (
+                    "usr/share/doc (opaque)",
+                    Some("Not a directory in the lower rootfs")
+                ),
+                (
+                    "usr/share/docs/old (redirect)",
+                    Some("Redirects to usr/share/doc/gone, which is not in the lower rootfs")
+                ),
+                (
+                    "usr/share/docs/README (metacopy)",
+                    Some("Takes its data from usr/share/doc/README, which is not in the lower rootfs")
+                ),
+            ]
+        );
+        assert!(results
+            .iter()
+            .any(|r| r.passed && r.item == "usr/bin/pong (metacopy)"));
+    }
+}
@@ -2,22 +2,85 @@
 //!
 //! Provides expected content definitions for different artifact types.
 
+pub mod alternatives;
 pub mod auth_audit;
+pub mod auto;
+pub mod console;
+pub mod controlled;
+pub mod devices;
+pub mod dns;
+pub mod dtb;
+pub mod efi;
+pub mod empty_files;
+pub mod extensions;
+pub mod filenames;
+pub mod first_boot;
+pub mod fstab;
+pub mod getty;
+pub mod grub;
+pub mod homes;
 pub mod install_initramfs;
+pub mod interpreters;
 pub mod iso;
 pub mod live_initramfs;
+pub mod live_overlay;
+pub mod netboot;
+pub mod patterns;
+pub mod placement;
+pub mod probes;
+#[cfg(feature = "qcow2")]
 pub mod qcow2;
 pub mod rootfs;
+#[cfg(feature = "rpmdb")]
+pub mod rpmdb;
+pub mod secrets;
+pub mod secure_boot;
+pub mod service_configs;
+pub mod shebangs;
+pub mod special_files;
+pub mod sysctl;
+pub mod timestamps;
+pub mod tmpfiles;
+pub mod udev;
 
+use crate::arch::Arch;
+use crate::cpio::CpioReader;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::fixit::Fix;
+use crate::iso::IsoReader;
+use crate::overlay::OverlayTree;
+use crate::report::Fingerprint;
+use crate::spec::Spec;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
+use std::time::{Duration, Instant};
 
 /// A verification check result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CheckResult {
     pub item: String,
     pub passed: bool,
     pub message: Option<String>,
     pub category: CheckCategory,
+    /// How to fix a failure (where the item is defined or built)
+    #[serde(default)]
+    pub hint: Option<String>,
+    /// Failed prerequisite that makes this check meaningless. Skipped
+    /// results count as neither passed nor failed.
+    #[serde(default)]
+    pub skipped_by: Option<String>,
+    /// Microseconds spent producing this result (see [`VerificationReport::add`])
+    #[serde(default)]
+    pub elapsed_us: Option<u64>,
+    /// Part of the system the item belongs to; inferred from the item when
+    /// the check doesn't say (see [`Subsystem::of`])
+    #[serde(default)]
+    pub subsystem: Option<Subsystem>,
+    /// Change to the staged tree that would make a failure pass, where
+    /// there is an obvious one (see `crate::fixit`)
+    #[serde(default)]
+    pub fix: Option<Fix>,
 }
 
 impl CheckResult {
@@ -27,6 +90,11 @@ impl CheckResult {
             passed: true,
             message: None,
             category,
+            hint: None,
+            skipped_by: None,
+            elapsed_us: None,
+            subsystem: None,
+            fix: None,
         }
     }
 
@@ -40,22 +108,142 @@ impl CheckResult {
             passed: false,
             message: Some(message.into()),
             category,
+            hint: None,
+            skipped_by: None,
+            elapsed_us: None,
+            subsystem: None,
+            fix: None,
         }
     }
+
+    /// Attach a remediation hint.
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// Attach the change that would fix a failure.
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+
+    /// Tag the result with the subsystem it belongs to.
+    pub fn with_subsystem(mut self, subsystem: Subsystem) -> Self {
+        self.subsystem = Some(subsystem);
+        self
+    }
+
+    /// The tagged subsystem, or the one inferred from the item (reports
+    /// saved before results were tagged have none).
+    pub fn subsystem(&self) -> Option<Subsystem> {
+        self.subsystem.or_else(|| Subsystem::of(&self.item))
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        self.skipped_by.is_some()
+    }
+
+    /// Failed and not skipped
+    pub fn is_failure(&self) -> bool {
+        !self.passed && !self.is_skipped()
+    }
+
+    /// How badly a failure breaks the artifact, for ranking in summaries.
+    pub fn severity(&self) -> Severity {
+        let message = self.message.as_deref().unwrap_or_default();
+        if self.category == CheckCategory::Forbidden || message.starts_with("REWARD HACK") {
+            Severity::Forbidden
+        } else if message.starts_with("CRITICAL") {
+            Severity::Critical
+        } else {
+            match self.category {
+                CheckCategory::Binary
+                | CheckCategory::Unit
+                | CheckCategory::Symlink
+                | CheckCategory::Library
+                | CheckCategory::Directory => Severity::Major,
+                _ => Severity::Minor,
+            }
+        }
+    }
+}
+
+/// Failure severity, most severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum Severity {
+    /// Something that must not ship is present
+    Forbidden,
+    /// Explicitly flagged by the check as breaking boot or login
+    Critical,
+    /// A missing binary, unit, library, or directory
+    Major,
+    Minor,
+}
+
+/// Remediation hint for a class of failures, pointing at where the failing
+/// items are defined or built. Applied by [`VerificationReport::apply_hints`].
+#[derive(Debug, Clone, Copy)]
+pub struct Hint {
+    pub category: CheckCategory,
+    /// Path prefix the hint applies to ("" for the whole category)
+    pub prefix: &'static str,
+    pub text: &'static str,
+}
+
+impl Hint {
+    pub const fn new(category: CheckCategory, prefix: &'static str, text: &'static str) -> Self {
+        Self {
+            category,
+            prefix,
+            text,
+        }
+    }
+}
+
+/// A path other checks depend on. If it's missing from the archive, failed
+/// dependents are marked skipped so the report leads with the root cause
+/// instead of hundreds of knock-on failures.
+#[derive(Debug, Clone, Copy)]
+pub struct Prerequisite {
+    pub path: &'static str,
+    /// Checks that can't pass without it, by category and item prefix
+    pub dependents: &'static [(CheckCategory, &'static str)],
+}
+
+impl Prerequisite {
+    pub const fn new(
+        path: &'static str,
+        dependents: &'static [(CheckCategory, &'static str)],
+    ) -> Self {
+        Self { path, dependents }
+    }
 }
 
 /// Category of check
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum CheckCategory {
     Binary,
     Unit,
     Symlink,
     EtcFile,
     UdevRule,
+    /// sysctl.d / tmpfiles.d configuration
+    Config,
     Directory,
     Library,
     KernelModule,
     License,
+    /// Installed packages (RPM database)
+    Package,
+    /// Detached artifact signatures
+    Signature,
+    /// File mtimes vs SOURCE_DATE_EPOCH
+    Timestamp,
+    /// Device nodes (/dev/console, /dev/null)
+    Device,
     /// Items that MUST NOT be present (e.g., busybox in live rootfs)
     Forbidden,
     Other,
@@ -69,10 +257,15 @@ impl fmt::Display for CheckCategory {
             CheckCategory::Symlink => write!(f, "Symlinks"),
             CheckCategory::EtcFile => write!(f, "/etc Files"),
             CheckCategory::UdevRule => write!(f, "Udev Rules"),
+            CheckCategory::Config => write!(f, "Config Files"),
             CheckCategory::Directory => write!(f, "Directories"),
             CheckCategory::Library => write!(f, "Libraries"),
             CheckCategory::KernelModule => write!(f, "Kernel Modules"),
             CheckCategory::License => write!(f, "Licenses"),
+            CheckCategory::Package => write!(f, "Packages"),
+            CheckCategory::Signature => write!(f, "Signatures"),
+            CheckCategory::Timestamp => write!(f, "Timestamps"),
+            CheckCategory::Device => write!(f, "Device Nodes"),
             CheckCategory::Forbidden => write!(f, "FORBIDDEN (must NOT exist)"),
             CheckCategory::Other => write!(f, "Other"),
         }
@@ -80,10 +273,26 @@ impl fmt::Display for CheckCategory {
 }
 
 /// Verification report
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct VerificationReport {
     pub results: Vec<CheckResult>,
     pub artifact_type: String,
+    /// Artifact the report was produced from, set by the caller
+    #[serde(default)]
+    pub fingerprint: Option<Fingerprint>,
+    /// distro-spec the checklist verified against (see [`crate::spec::Spec::id`])
+    #[serde(default)]
+    pub spec: Option<String>,
+    /// Architecture the checklist's paths were mapped to, e.g. `aarch64`
+    #[serde(default)]
+    pub arch: Option<String>,
+    /// Why the report covers only part of the artifact (a truncated archive
+    /// verified with `--lenient`)
+    #[serde(default)]
+    pub partial: Option<String>,
+    /// When the previous result was added (or the report created)
+    #[serde(skip)]
+    last_add: Option<Instant>,
 }
 
 impl VerificationReport {
@@ -91,19 +300,150 @@ impl VerificationReport {
         Self {
             results: Vec::new(),
             artifact_type: artifact_type.into(),
+            fingerprint: None,
+            spec: None,
+            arch: None,
+            partial: None,
+            last_add: Some(Instant::now()),
         }
     }
 
-    pub fn add(&mut self, result: CheckResult) {
+    /// Add a result, timing it as the work done since the previous one, and
+    /// tagging it with its inferred subsystem if the check didn't.
+    ///
+    /// Checks that return a batch of results are charged to the first
+    /// result of the batch; the rest take next to no time.
+    pub fn add(&mut self, mut result: CheckResult) {
+        result.subsystem = result.subsystem();
+        let now = Instant::now();
+        if result.elapsed_us.is_none() {
+            let since = self.last_add.map_or(Duration::ZERO, |last| now - last);
+            result.elapsed_us = Some(since.as_micros() as u64);
+        }
+        self.last_add = Some(now);
         self.results.push(result);
     }
 
+    /// The `n` slowest results, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<&CheckResult> {
+        let mut timed: Vec<_> = self
+            .results
+            .iter()
+            .filter(|r| r.elapsed_us.is_some())
+            .collect();
+        timed.sort_by_key(|r| std::cmp::Reverse(r.elapsed_us));
+        timed.truncate(n);
+        timed
+    }
+
+    /// Time spent per category, slowest first.
+    pub fn timings_by_category(&self) -> Vec<(CheckCategory, Duration)> {
+        let mut totals: Vec<(CheckCategory, Duration)> = Vec::new();
+        for result in &self.results {
+            let elapsed = Duration::from_micros(result.elapsed_us.unwrap_or(0));
+            match totals.iter_mut().find(|(c, _)| *c == result.category) {
+                Some((_, total)) => *total += elapsed,
+                None => totals.push((result.category, elapsed)),
+            }
+        }
+        totals.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+        totals
+    }
+
+    /// Attach hints to failures that don't have one. The hint with the
+    /// longest matching prefix in the failure's category wins.
+    pub(crate) fn apply_hints(&mut self, hints: &[Hint]) {
+        for result in self.results.iter_mut() {
+            if !result.is_failure() || result.hint.is_some() {
+                continue;
+            }
+            let item = result.item.trim_start_matches("./").trim_start_matches('/');
+            let best = hints
+                .iter()
+                .filter(|h| h.category == result.category && item.starts_with(h.prefix))
+                .max_by_key(|h| h.prefix.len());
+            if let Some(hint) = best {
+                result.hint = Some(hint.text.to_string());
+            }
+        }
+    }
+
+    /// Skip failures that depend on a missing prerequisite, and make sure
+    /// each missing prerequisite is reported as a failure itself.
+    pub(crate) fn apply_prerequisites(
+        &mut self,
+        prerequisites: &[Prerequisite],
+        exists: impl Fn(&str) -> bool,
+    ) {
+        for prereq in prerequisites {
+            if exists(prereq.path) {
+                continue;
+            }
+
+            let mut skipped = 0;
+            for result in self.results.iter_mut() {
+                let item = result.item.trim_start_matches("./").trim_start_matches('/');
+                let dependent = prereq.dependents.iter().any(|(category, prefix)| {
+                    *category == result.category && item.starts_with(prefix)
+                });
+                if result.is_failure() && item != prereq.path && dependent {
+                    result.skipped_by = Some(prereq.path.to_string());
+                    skipped += 1;
+                }
+            }
+
+            if skipped > 0
+                && !self
+                    .results
+                    .iter()
+                    .any(|r| r.is_failure() && r.item == prereq.path)
+            {
+                self.add(
+                    CheckResult::fail(
+                        prereq.path,
+                        CheckCategory::Directory,
+                        format!("Missing ({} dependent checks skipped)", skipped),
+                    )
+                    .with_hint(
+                        "The whole directory is missing - check the build step that populates it",
+                    ),
+                );
+            }
+        }
+    }
+
     pub fn passed(&self) -> usize {
         self.results.iter().filter(|r| r.passed).count()
     }
 
     pub fn failed(&self) -> usize {
-        self.results.iter().filter(|r| !r.passed).count()
+        self.results.iter().filter(|r| r.is_failure()).count()
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.results.iter().filter(|r| r.is_skipped()).count()
+    }
+
+    /// The `n` most severe failures; ties keep report order.
+    pub fn top_failures(&self, n: usize) -> Vec<&CheckResult> {
+        let mut failures: Vec<_> = self.results.iter().filter(|r| r.is_failure()).collect();
+        failures.sort_by_key(|r| r.severity());
+        failures.truncate(n);
+        failures
+    }
+
+    /// Failures other checks were skipped because of, in report order.
+    pub fn root_causes(&self) -> Vec<&CheckResult> {
+        self.results
+            .iter()
+            .filter(|r| {
+                r.is_failure()
+                    && self
+                        .results
+                        .iter()
+                        .any(|s| s.skipped_by.as_deref() == Some(r.item.as_str()))
+            })
+            .collect()
     }
 
     pub fn total(&self) -> usize {
@@ -121,21 +461,8 @@ impl VerificationReport {
         let mut groups: BTreeMap<u8, (CheckCategory, Vec<&CheckResult>)> = BTreeMap::new();
 
         for result in &self.results {
-            let key = match result.category {
-                CheckCategory::Binary => 0,
-                CheckCategory::Unit => 1,
-                CheckCategory::Symlink => 2,
-                CheckCategory::EtcFile => 3,
-                CheckCategory::UdevRule => 4,
-                CheckCategory::Directory => 5,
-                CheckCategory::Library => 6,
-                CheckCategory::KernelModule => 7,
-                CheckCategory::License => 8,
-                CheckCategory::Forbidden => 9,
-                CheckCategory::Other => 10,
-            };
             groups
-                .entry(key)
+                .entry(result.category.sort_key())
                 .or_insert((result.category, Vec::new()))
                 .1
                 .push(result);
@@ -143,10 +470,622 @@ impl VerificationReport {
 
         groups.into_values().collect()
     }
+
+    /// Group results by subsystem, in [`Subsystem`] order; results outside
+    /// every subsystem come last, under `None`.
+    pub fn by_subsystem(&self) -> Vec<(Option<Subsystem>, Vec<&CheckResult>)> {
+        let mut groups: BTreeMap<(bool, Option<Subsystem>), Vec<&CheckResult>> = BTreeMap::new();
+        for result in &self.results {
+            let subsystem = result.subsystem();
+            groups
+                .entry((subsystem.is_none(), subsystem))
+                .or_default()
+                .push(result);
+        }
+        groups
+            .into_iter()
+            .map(|((_, subsystem), results)| (subsystem, results))
+            .collect()
+    }
+}
+
+impl CheckCategory {
+    /// Machine-readable name, stable across releases (porcelain output)
+    pub fn key(self) -> &'static str {
+        match self {
+            CheckCategory::Binary => "binary",
+            CheckCategory::Unit => "unit",
+            CheckCategory::Symlink => "symlink",
+            CheckCategory::EtcFile => "etc-file",
+            CheckCategory::UdevRule => "udev-rule",
+            CheckCategory::Config => "config",
+            CheckCategory::Directory => "directory",
+            CheckCategory::Library => "library",
+            CheckCategory::KernelModule => "kernel-module",
+            CheckCategory::License => "license",
+            CheckCategory::Package => "package",
+            CheckCategory::Signature => "signature",
+            CheckCategory::Timestamp => "timestamp",
+            CheckCategory::Device => "device",
+            CheckCategory::Forbidden => "forbidden",
+            CheckCategory::Other => "other",
+        }
+    }
+
+    /// Category for a [`key`](Self::key), e.g. from an extension file
+    pub fn from_key(key: &str) -> Option<Self> {
+        const ALL: [CheckCategory; 16] = [
+            CheckCategory::Binary,
+            CheckCategory::Unit,
+            CheckCategory::Symlink,
+            CheckCategory::EtcFile,
+            CheckCategory::UdevRule,
+            CheckCategory::Config,
+            CheckCategory::Directory,
+            CheckCategory::Library,
+            CheckCategory::KernelModule,
+            CheckCategory::License,
+            CheckCategory::Package,
+            CheckCategory::Signature,
+            CheckCategory::Timestamp,
+            CheckCategory::Device,
+            CheckCategory::Forbidden,
+            CheckCategory::Other,
+        ];
+        ALL.into_iter().find(|c| c.key() == key)
+    }
+
+    /// Display order of categories in reports
+    pub fn sort_key(self) -> u8 {
+        match self {
+            CheckCategory::Binary => 0,
+            CheckCategory::Unit => 1,
+            CheckCategory::Symlink => 2,
+            CheckCategory::EtcFile => 3,
+            CheckCategory::UdevRule => 4,
+            CheckCategory::Config => 5,
+            CheckCategory::Directory => 6,
+            CheckCategory::Library => 7,
+            CheckCategory::KernelModule => 8,
+            CheckCategory::License => 9,
+            CheckCategory::Package => 10,
+            CheckCategory::Signature => 11,
+            CheckCategory::Timestamp => 12,
+            CheckCategory::Device => 13,
+            CheckCategory::Forbidden => 14,
+            CheckCategory::Other => 15,
+        }
+    }
+}
+
+/// Part of the system a check is about, across categories: NetworkManager's
+/// binary, unit and config are all `Networking`, so a report grouped by
+/// subsystem shows everything about it failing in one place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Subsystem {
+    Auth,
+    Boot,
+    Networking,
+    Storage,
+    Audio,
+}
+
+/// Path prefixes (lowercase) telling a subsystem, first match wins. Auth
+/// comes first so `etc/pam.d/sshd` is about logging in, not the network.
+///
+/// A prefix ending in `/`, `-` or `_` covers everything below or after it;
+/// any other matches only at a name boundary, so `usr/bin/su` is `su` and
+/// its `su.*`/`su-*` relatives, not `usr/bin/sum`.
+const SUBSYSTEM_PREFIXES: &[(Subsystem, &[&str])] = &[
+    (
+        Subsystem::Auth,
+        &[
+            "etc/pam.d/",
+            "etc/security/",
+            "usr/lib64/security/",
+            "usr/lib/security/",
+            "etc/shadow",
+            "etc/gshadow",
+            "etc/passwd",
+            "etc/group",
+            "etc/sudoers",
+            "etc/login.defs",
+            "etc/nsswitch.conf",
+            "etc/polkit-1/",
+            "usr/share/polkit-1/",
+            "usr/lib/polkit-1/",
+            "usr/bin/sudo",
+            "usr/bin/su",
+            "usr/bin/login",
+            "usr/bin/passwd",
+            "usr/bin/chage",
+            "usr/sbin/unix_chkpwd",
+            "usr/lib64/libcrypt",
+            "usr/lib/libcrypt",
+            "usr/lib64/libpam",
+            "usr/lib/libpam",
+        ],
+    ),
+    (
+        Subsystem::Audio,
+        &[
+            "usr/bin/pipewire",
+            "usr/bin/wireplumber",
+            "usr/bin/pactl",
+            "usr/sbin/alsactl",
+            "etc/pipewire/",
+            "etc/wireplumber/",
+            "usr/share/pipewire/",
+            "usr/share/wireplumber/",
+            "usr/share/alsa/",
+            "usr/lib/systemd/user/pipewire",
+            "usr/lib/systemd/user/wireplumber",
+            "usr/lib64/libpipewire-",
+            "usr/lib/libpipewire-",
+            "usr/lib64/libasound",
+            "usr/lib/libasound",
+        ],
+    ),
+    (
+        Subsystem::Networking,
+        &[
+            "usr/sbin/networkmanager",
+            "usr/bin/nmcli",
+            "usr/bin/nm-",
+            "usr/libexec/nm-",
+            "etc/networkmanager/",
+            "usr/lib/networkmanager/",
+            "usr/lib/systemd/system/networkmanager",
+            "usr/sbin/wpa_supplicant",
+            "usr/lib/systemd/system/wpa_supplicant",
+            "usr/libexec/iwd",
+            "usr/lib/systemd/system/iwd",
+            "etc/resolv.conf",
+            "etc/hosts",
+            "etc/hostname",
+            "usr/bin/hostname",
+            "usr/lib/systemd/systemd-resolved",
+            "usr/lib/systemd/system/systemd-resolved",
+            "usr/lib64/libnss_dns",
+            "usr/lib/libnss_dns",
+            "usr/lib64/libnss_resolve",
+            "usr/lib/libnss_resolve",
+            "usr/sbin/dhclient",
+            "usr/bin/ssh",
+            "usr/sbin/sshd",
+            "etc/ssh/",
+            "usr/libexec/openssh/",
+            "usr/lib/systemd/system/sshd",
+            "usr/sbin/chronyd",
+            "etc/chrony.conf",
+            "usr/lib/systemd/system/chronyd",
+            "usr/sbin/nft",
+            "etc/nftables/",
+            "usr/sbin/iptables",
+            "usr/sbin/firewalld",
+            "etc/firewalld/",
+            "usr/lib/systemd/system/firewalld",
+            "usr/sbin/ip",
+            "usr/bin/ping",
+            "usr/lib/systemd/system/network",
+        ],
+    ),
+    (
+        Subsystem::Storage,
+        &[
+            "etc/fstab",
+            "etc/crypttab",
+            "usr/bin/mount",
+            "usr/bin/umount",
+            "usr/sbin/mount",
+            "usr/bin/lsblk",
+            "usr/sbin/losetup",
+            "usr/libexec/udisks2/",
+            "usr/lib/systemd/system/udisks2",
+            "usr/sbin/lvm",
+            "etc/lvm/",
+            "usr/sbin/mdadm",
+            "usr/sbin/cryptsetup",
+            "usr/lib/systemd/systemd-cryptsetup",
+            "usr/sbin/mkfs",
+            "usr/sbin/fsck",
+            "usr/sbin/blkid",
+            "usr/sbin/fdisk",
+            "usr/sbin/sfdisk",
+            "usr/sbin/parted",
+            "usr/sbin/nvme",
+            "usr/sbin/btrfs",
+            "usr/sbin/xfs_",
+            "usr/sbin/e2fsck",
+            "usr/sbin/mke2fs",
+            "usr/sbin/tune2fs",
+        ],
+    ),
+    (
+        Subsystem::Boot,
+        &[
+            "boot/",
+            "efi/",
+            "isolinux/",
+            "images/pxeboot/",
+            "loader/",
+            "usr/lib/modules/",
+            "lib/modules/",
+            "usr/lib/systemd/boot/",
+            "usr/lib/systemd/system/initrd",
+            "usr/lib/systemd/system/sysroot",
+            "sysroot/",
+            "usr/lib/dracut/",
+            "usr/bin/dracut",
+            "etc/dracut.conf",
+            "etc/kernel/",
+            "etc/default/grub",
+            "usr/lib/grub/",
+            "usr/share/grub/",
+            "usr/bin/grub2-",
+            "usr/sbin/grub2-",
+            "init",
+        ],
+    ),
+];
+
+/// Whether `path` is `prefix` or lies under or after it (see
+/// [`SUBSYSTEM_PREFIXES`]).
+fn under_prefix(path: &str, prefix: &str) -> bool {
+    let Some(rest) = path.strip_prefix(prefix) else {
+        return false;
+    };
+    rest.is_empty() || prefix.ends_with(['/', '-', '_']) || rest.starts_with(['/', '.', '-', '@'])
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 5] = [
+        Subsystem::Auth,
+        Subsystem::Boot,
+        Subsystem::Networking,
+        Subsystem::Storage,
+        Subsystem::Audio,
+    ];
+
+    /// Machine-readable name (`--group-by` keys, extension files, catalogs)
+    pub fn key(self) -> &'static str {
+        match self {
+            Subsystem::Auth => "auth",
+            Subsystem::Boot => "boot",
+            Subsystem::Networking => "networking",
+            Subsystem::Storage => "storage",
+            Subsystem::Audio => "audio",
+        }
+    }
+
+    pub fn parse_name(s: &str) -> Option<Self> {
+        let s = s.to_lowercase();
+        Self::ALL.into_iter().find(|subsystem| subsystem.key() == s)
+    }
+
+    /// Subsystem an item belongs to, from where its path lies
+    /// (`usr/sbin/NetworkManager`, `etc/pam.d/login`). Items under none of
+    /// the known prefixes belong to no subsystem; checks can tag them instead.
+    pub fn of(item: &str) -> Option<Self> {
+        let item = item.to_lowercase();
+        let path = item.trim_start_matches("./").trim_start_matches('/');
+        SUBSYSTEM_PREFIXES
+            .iter()
+            .find(|(_, prefixes)| prefixes.iter().any(|prefix| under_prefix(path, prefix)))
+            .map(|(subsystem, _)| *subsystem)
+    }
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subsystem::Auth => write!(f, "Authentication"),
+            Subsystem::Boot => write!(f, "Boot"),
+            Subsystem::Networking => write!(f, "Networking"),
+            Subsystem::Storage => write!(f, "Storage"),
+            Subsystem::Audio => write!(f, "Audio"),
+        }
+    }
+}
+
+/// How rendered reports group their results (`--group-by`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Grouping {
+    #[default]
+    Category,
+    Subsystem,
+}
+
+impl Grouping {
+    pub fn parse_name(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "category" => Some(Grouping::Category),
+            "subsystem" => Some(Grouping::Subsystem),
+            _ => None,
+        }
+    }
+}
+
+/// Paths a checklist looks at, used to scope diffs to what it cares about.
+///
+/// Entries are exact paths, or prefixes ending in `/` for checks that scan a
+/// whole directory (kernel modules, licenses, udev rules).
+#[derive(Debug, Default)]
+pub struct Scope {
+    entries: Vec<(String, CheckCategory)>,
+}
+
+impl Scope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an exact path.
+    pub fn add(&mut self, path: impl Into<String>, category: CheckCategory) {
+        let path = path.into();
+        self.entries
+            .push((path.trim_matches('/').to_string(), category));
+    }
+
+    /// Add a directory prefix; everything under it is in scope.
+    pub fn add_prefix(&mut self, prefix: &str, category: CheckCategory) {
+        self.entries
+            .push((format!("{}/", prefix.trim_matches('/')), category));
+    }
+
+    /// Add several exact paths of the same category.
+    pub fn extend<S: AsRef<str>>(
+        &mut self,
+        paths: impl IntoIterator<Item = S>,
+        category: CheckCategory,
+    ) {
+        for path in paths {
+            self.add(path.as_ref(), category);
+        }
+    }
+
+    /// First component of every path in scope: the top-level entries a
+    /// listing has to descend into to see them all.
+    pub fn top_level(&self) -> Vec<String> {
+        let mut dirs: Vec<String> = self
+            .entries
+            .iter()
+            .filter_map(|(p, _)| p.split('/').next())
+            .filter(|dir| !dir.is_empty())
+            .map(str::to_string)
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Category of a path if it's in scope. Exact matches win over the
+    /// longest matching prefix.
+    pub fn category_of(&self, path: &str) -> Option<CheckCategory> {
+        let path = path.trim_start_matches("./").trim_matches('/');
+
+        if let Some((_, category)) = self.entries.iter().find(|(p, _)| p == path) {
+            return Some(*category);
+        }
+
+        self.entries
+            .iter()
+            .filter(|(p, _)| p.ends_with('/') && path.starts_with(p.as_str()))
+            .max_by_key(|(p, _)| p.len())
+            .map(|(_, category)| *category)
+    }
+}
+
+/// Paths referenced by a checklist verifying against `spec`, or None for
+/// checklists that don't work on archive paths (qcow2 verifies a mounted
+/// image).
+pub fn scope(checklist: ChecklistType, spec: &Spec) -> Option<Scope> {
+    match checklist {
+        ChecklistType::InstallInitramfs => Some(install_initramfs::scope()),
+        ChecklistType::LiveInitramfs => Some(live_initramfs::scope()),
+        ChecklistType::Rootfs => Some(rootfs::scope(spec)),
+        ChecklistType::Iso => Some(iso::scope()),
+        ChecklistType::AuthAudit => Some(auth_audit::scope(spec)),
+        #[cfg(feature = "rpmdb")]
+        ChecklistType::RpmDb => Some(rpmdb::scope()),
+        #[cfg(not(feature = "rpmdb"))]
+        ChecklistType::RpmDb => None,
+        ChecklistType::Netboot => Some(netboot::scope()),
+        ChecklistType::Qcow2 => None,
+    }
+}
+
+/// Check a prerequisite path is present, either as its own entry or
+/// implied by entries below it (archives don't always list directories).
+pub(crate) fn has_path(reader: &CpioReader, path: &str) -> bool {
+    let prefix = format!("{}/", path);
+    reader.exists(path) || reader.entries().iter().any(|e| e.path.starts_with(&prefix))
+}
+
+/// Flag empty files in the locations every CPIO checklist covers, with the
+/// library directory `arch` uses.
+pub(crate) fn check_empty_files(report: &mut VerificationReport, reader: &CpioReader, arch: Arch) {
+    for result in empty_files::check(reader, &empty_files::default_patterns(arch)) {
+        report.add(result);
+    }
+}
+
+/// Run a checklist against a CPIO archive, for the architecture its
+/// binaries are built for (x86_64 if none could be read), against the
+/// compiled-in spec.
+pub fn verify_cpio(
+    reader: &CpioReader,
+    checklist: ChecklistType,
+) -> Result<VerificationReport, FsdbgError> {
+    let arch = crate::arch::detect_cpio(reader).unwrap_or_default();
+    verify_cpio_for(reader, checklist, arch, Spec::builtin())
+}
+
+/// Run a checklist against a CPIO archive built for `arch`, against `spec`.
+pub fn verify_cpio_for(
+    reader: &CpioReader,
+    checklist: ChecklistType,
+    arch: Arch,
+    spec: &Spec,
+) -> Result<VerificationReport, FsdbgError> {
+    let mut report = match checklist {
+        ChecklistType::InstallInitramfs => install_initramfs::verify_for(reader, arch, spec),
+        ChecklistType::LiveInitramfs => live_initramfs::verify(reader, spec),
+        ChecklistType::Rootfs => rootfs::verify_for(reader, arch, spec),
+        ChecklistType::AuthAudit => auth_audit::verify_for(reader, arch, spec),
+        #[cfg(feature = "rpmdb")]
+        ChecklistType::RpmDb => rpmdb::verify(reader, spec),
+        #[cfg(not(feature = "rpmdb"))]
+        ChecklistType::RpmDb => {
+            return Err(FsdbgError::new(
+                ErrorCode::InvalidArgument,
+                "RPM database checklist requires fsdbg built with the rpmdb feature",
+            ))
+        }
+        ChecklistType::Netboot => netboot::verify(reader),
+        ChecklistType::Iso => {
+            return Err(FsdbgError::new(
+                ErrorCode::InvalidArgument,
+                "ISO checklist requires an ISO file, not CPIO",
+            ))
+        }
+        ChecklistType::Qcow2 => {
+            return Err(FsdbgError::new(
+                ErrorCode::InvalidArgument,
+                "Qcow2 checklist requires a mounted image, not CPIO",
+            ))
+        }
+    };
+    report.spec = Some(spec.id());
+    report.arch = Some(arch.name().to_string());
+    Ok(report)
+}
+
+/// Name lints for a CPIO archive: names that aren't valid UTF-8 and the
+/// [`filenames::Policy::Archive`] rules. They judge how the archive was
+/// written rather than what the checklist expects in it, so checklists
+/// leave them out; callers add them on request (`verify --lint-names`).
+pub fn check_archive_names(reader: &CpioReader) -> Vec<CheckResult> {
+    let names = reader.entries().iter().map(|e| (&e.path, &e.raw_path[..]));
+    let paths = reader.entries().iter().map(|e| &e.path);
+    let mut results = check_names(names);
+    results.extend(filenames::check(paths, filenames::Policy::Archive));
+    results
+}
+
+/// Flag entry names that aren't valid UTF-8. Tools disagree on how to
+/// render them, and config referencing them by name rarely matches.
+pub fn check_names<'a>(names: impl Iterator<Item = (&'a String, &'a [u8])>) -> Vec<CheckResult> {
+    names
+        .filter(|(_, raw)| crate::names::is_non_utf8(raw))
+        .map(|(path, _)| {
+            CheckResult::fail(
+                path.as_str(),
+                CheckCategory::Other,
+                "Name is not valid UTF-8 (shown with \\xNN escapes)",
+            )
+            .with_hint("Rename the file in the build; nothing in LevitateOS ships non-UTF-8 names")
+        })
+        .collect()
+}
+
+/// Flag paths that differ only in case. Copied onto FAT install media or
+/// extracted on a case-insensitive filesystem, only one of them survives.
+///
+/// Only the outermost collision is reported: `EFI/` vs `efi/` is one
+/// failure, not one per file below them.
+pub fn check_case_collisions<'a>(paths: impl Iterator<Item = &'a String>) -> Vec<CheckResult> {
+    let mut groups: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for path in paths {
+        let path = path.trim_matches('/');
+        // Parent directories count even when the archive doesn't list them
+        let ends = path.match_indices('/').map(|(i, _)| i).chain([path.len()]);
+        for name in ends.map(|end| &path[..end]) {
+            let names = groups.entry(name.to_lowercase()).or_default();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    groups.retain(|_, names| names.len() > 1);
+
+    let nested = |folded: &str| {
+        folded
+            .match_indices('/')
+            .any(|(i, _)| groups.contains_key(&folded[..i]))
+    };
+    groups
+        .iter()
+        .filter(|(folded, _)| !nested(folded))
+        .map(|(_, names)| {
+            CheckResult::fail(
+                names[0],
+                CheckCategory::Other,
+                format!("Differs only in case from {}", names[1..].join(", ")),
+            )
+            .with_hint("Case-insensitive filesystems (FAT USB media) keep only one of them; rename one in the build")
+        })
+        .collect()
+}
+
+/// Run a checklist against an ISO image, for the architecture of its EFI
+/// loader (x86_64 if it has none), against the compiled-in spec.
+pub fn verify_iso(
+    reader: &IsoReader,
+    checklist: ChecklistType,
+) -> Result<VerificationReport, FsdbgError> {
+    let arch = crate::arch::detect_iso(reader).unwrap_or_default();
+    verify_iso_for(reader, checklist, None, arch, Spec::builtin())
+}
+
+/// Run a checklist against an ISO image built for `arch`, against `spec`,
+/// optionally with the live overlay read from another tree (see
+/// `iso::verify_with_overlay`).
+pub fn verify_iso_for(
+    reader: &IsoReader,
+    checklist: ChecklistType,
+    overlay: Option<&dyn OverlayTree>,
+    arch: Arch,
+    spec: &Spec,
+) -> Result<VerificationReport, FsdbgError> {
+    match checklist {
+        ChecklistType::Iso => {
+            let mut report = iso::verify_with_overlay(reader, overlay, arch, spec);
+            let names = reader.entries().iter().map(|e| (&e.path, &e.raw_path[..]));
+            for result in check_names(names) {
+                report.add(result);
+            }
+            let paths = reader.entries().iter().map(|e| &e.path);
+            for result in check_case_collisions(paths) {
+                report.add(result);
+            }
+            let paths = reader.entries().iter().map(|e| &e.path);
+            for result in filenames::check(paths, filenames::Policy::Media) {
+                report.add(result);
+            }
+            report.spec = Some(spec.id());
+            report.arch = Some(arch.name().to_string());
+            Ok(report)
+        }
+        ChecklistType::AuthAudit => Err(FsdbgError::new(
+            ErrorCode::InvalidArgument,
+            "Auth audit requires a rootfs archive (CPIO/EROFS), not ISO. Extract the rootfs first.",
+        )),
+        _ => Err(FsdbgError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "Checklist type '{}' not supported for ISO format. Use 'iso'.",
+                checklist.name()
+            ),
+        )),
+    }
 }
 
 /// Checklist type for verification
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ChecklistType {
     /// Install initramfs (systemd-based, for actual installation)
     InstallInitramfs,
@@ -160,6 +1099,10 @@ pub enum ChecklistType {
     AuthAudit,
     /// Qcow2 VM image (mounted filesystem)
     Qcow2,
+    /// RPM database cross-reference (installed packages vs licenses/manifest)
+    RpmDb,
+    /// Netboot tree (iPXE scripts, pxelinux configs and the images they fetch)
+    Netboot,
 }
 
 impl ChecklistType {
@@ -173,10 +1116,26 @@ impl ChecklistType {
             "iso" => Some(ChecklistType::Iso),
             "auth-audit" | "auth_audit" | "auth" => Some(ChecklistType::AuthAudit),
             "qcow2" | "qcow" | "vm" => Some(ChecklistType::Qcow2),
+            "rpmdb" | "rpm-db" | "rpm" => Some(ChecklistType::RpmDb),
+            "netboot" | "pxe" | "ipxe" => Some(ChecklistType::Netboot),
             _ => None,
         }
     }
 
+    /// Name for `--type`, as [`parse_name`](Self::parse_name) takes it
+    pub fn key(&self) -> &'static str {
+        match self {
+            ChecklistType::InstallInitramfs => "install-initramfs",
+            ChecklistType::LiveInitramfs => "live-initramfs",
+            ChecklistType::Rootfs => "rootfs",
+            ChecklistType::Iso => "iso",
+            ChecklistType::AuthAudit => "auth-audit",
+            ChecklistType::Qcow2 => "qcow2",
+            ChecklistType::RpmDb => "rpmdb",
+            ChecklistType::Netboot => "netboot",
+        }
+    }
+
     pub fn name(&self) -> &'static str {
         match self {
             ChecklistType::InstallInitramfs => "Install Initramfs",
@@ -185,6 +1144,8 @@ impl ChecklistType {
             ChecklistType::Iso => "Live ISO",
             ChecklistType::AuthAudit => "Authentication Audit",
             ChecklistType::Qcow2 => "Qcow2 Image",
+            ChecklistType::RpmDb => "RPM Database",
+            ChecklistType::Netboot => "Netboot",
         }
     }
 }
@@ -194,3 +1155,256 @@ impl fmt::Display for ChecklistType {
         write!(f, "{}", self.name())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_category_of() {
+        let mut scope = Scope::new();
+        scope.add("usr/bin/bash", CheckCategory::Binary);
+        scope.add_prefix("usr/lib/systemd/system", CheckCategory::Unit);
+        scope.add_prefix(
+            "usr/lib/systemd/system/getty.target.wants",
+            CheckCategory::Symlink,
+        );
+
+        assert_eq!(
+            scope.category_of("./usr/bin/bash"),
+            Some(CheckCategory::Binary)
+        );
+        assert_eq!(
+            scope.category_of("/usr/lib/systemd/system/sshd.service"),
+            Some(CheckCategory::Unit)
+        );
+        // Longest prefix wins
+        assert_eq!(
+            scope.category_of("usr/lib/systemd/system/getty.target.wants/getty@tty1.service"),
+            Some(CheckCategory::Symlink)
+        );
+        assert_eq!(
+            scope.category_of("usr/share/locale/de/LC_MESSAGES/bash.mo"),
+            None
+        );
+    }
+    #[test]
+    fn test_apply_hints() {
+        let mut report = VerificationReport::new("Test");
+        report.add(CheckResult::fail(
+            "usr/sbin/unix_chkpwd",
+            CheckCategory::Binary,
+            "Missing",
+        ));
+        report.add(CheckResult::fail(
+            "usr/bin/ls",
+            CheckCategory::Binary,
+            "Missing",
+        ));
+        report.add(
+            CheckResult::fail("usr/bin/su", CheckCategory::Binary, "Missing").with_hint("explicit"),
+        );
+        report.add(CheckResult::fail(
+            "etc/passwd",
+            CheckCategory::EtcFile,
+            "Missing",
+        ));
+        report.add(CheckResult::pass("usr/bin/vi", CheckCategory::Binary));
+
+        report.apply_hints(&[
+            Hint::new(CheckCategory::Binary, "", "generic"),
+            Hint::new(CheckCategory::Binary, "usr/sbin/", "sbin"),
+        ]);
+
+        let hints: Vec<_> = report.results.iter().map(|r| r.hint.as_deref()).collect();
+        assert_eq!(
+            hints,
+            vec![Some("sbin"), Some("generic"), Some("explicit"), None, None]
+        );
+    }
+
+    #[test]
+    fn test_apply_prerequisites() {
+        let mut report = VerificationReport::new("test");
+        report.add(CheckResult::fail(
+            "usr/lib/systemd/systemd",
+            CheckCategory::Binary,
+            "Missing",
+        ));
+        report.add(CheckResult::fail(
+            "sshd.service",
+            CheckCategory::Unit,
+            "Missing",
+        ));
+        report.add(CheckResult::fail(
+            "usr/bin/ls",
+            CheckCategory::Binary,
+            "Missing",
+        ));
+        report.add(CheckResult::pass("usr/bin/vi", CheckCategory::Binary));
+
+        const PREREQUISITES: &[Prerequisite] = &[
+            Prerequisite::new(
+                "usr/lib/systemd",
+                &[
+                    (CheckCategory::Binary, "usr/lib/systemd/"),
+                    (CheckCategory::Unit, ""),
+                ],
+            ),
+            Prerequisite::new("usr/bin", &[(CheckCategory::Binary, "usr/bin/")]),
+        ];
+        report.apply_prerequisites(PREREQUISITES, |path| path == "usr/bin");
+
+        assert_eq!(report.skipped(), 2);
+        assert_eq!(report.failed(), 2);
+        assert_eq!(report.passed(), 1);
+        assert!(!report.is_success());
+
+        let causes = report.root_causes();
+        assert_eq!(causes.len(), 1);
+        assert_eq!(causes[0].item, "usr/lib/systemd");
+        assert_eq!(
+            causes[0].message.as_deref(),
+            Some("Missing (2 dependent checks skipped)")
+        );
+    }
+
+    #[test]
+    fn test_timings() {
+        let mut report = VerificationReport::new("test");
+        report.add(CheckResult::pass("usr/bin/ls", CheckCategory::Binary));
+        std::thread::sleep(Duration::from_millis(5));
+        report.add(CheckResult::pass("etc/passwd", CheckCategory::EtcFile));
+        report.add(CheckResult::pass("usr/bin/cp", CheckCategory::Binary));
+
+        assert_eq!(report.slowest(1)[0].item, "etc/passwd");
+        assert!(report.slowest(1)[0].elapsed_us >= Some(5000));
+        let by_category = report.timings_by_category();
+        assert_eq!(by_category[0].0, CheckCategory::EtcFile);
+        assert_eq!(by_category.len(), 2);
+    }
+
+    #[test]
+    fn test_top_failures() {
+        let mut report = VerificationReport::new("test");
+        report.add(CheckResult::fail(
+            "etc/motd",
+            CheckCategory::EtcFile,
+            "Missing",
+        ));
+        report.add(CheckResult::fail(
+            "usr/bin/ls",
+            CheckCategory::Binary,
+            "Missing",
+        ));
+        report.add(CheckResult::fail(
+            "usr/sbin/unix_chkpwd",
+            CheckCategory::Binary,
+            "CRITICAL: password auth WILL FAIL",
+        ));
+        report.add(CheckResult::fail(
+            "usr/bin/busybox",
+            CheckCategory::Forbidden,
+            "Busybox in live rootfs",
+        ));
+        report.add(CheckResult::fail(
+            "usr/bin/cp",
+            CheckCategory::Binary,
+            "Missing",
+        ));
+
+        let top: Vec<_> = report
+            .top_failures(4)
+            .iter()
+            .map(|r| r.item.as_str())
+            .collect();
+        assert_eq!(
+            top,
+            vec![
+                "usr/bin/busybox",
+                "usr/sbin/unix_chkpwd",
+                "usr/bin/ls",
+                "usr/bin/cp"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_case_collisions() {
+        let paths: Vec<String> = [
+            "/EFI/BOOT/BOOTX64.EFI",
+            "/efi/boot/bootx64.efi",
+            "/live/README",
+            "/live/readme",
+            "/boot/vmlinuz",
+        ]
+        .map(String::from)
+        .to_vec();
+        let results = check_case_collisions(paths.iter());
+        let items: Vec<(&str, Option<&str>)> = results
+            .iter()
+            .map(|r| (r.item.as_str(), r.message.as_deref()))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                ("EFI", Some("Differs only in case from efi")),
+                ("live/README", Some("Differs only in case from live/readme")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_by_subsystem() {
+        assert_eq!(
+            Subsystem::of("usr/sbin/NetworkManager"),
+            Some(Subsystem::Networking)
+        );
+        assert_eq!(Subsystem::of("etc/pam.d/sshd"), Some(Subsystem::Auth));
+        assert_eq!(Subsystem::of("usr/bin/ls"), None);
+        assert_eq!(
+            Subsystem::of("/usr/lib/systemd/system/NetworkManager-wait-online.service"),
+            Some(Subsystem::Networking)
+        );
+        assert_eq!(
+            Subsystem::of("usr/lib64/security/pam_unix.so"),
+            Some(Subsystem::Auth)
+        );
+        assert_eq!(Subsystem::of("usr/sbin/mkfs.xfs"), Some(Subsystem::Storage));
+        assert_eq!(Subsystem::of("EFI/BOOT/BOOTX64.EFI"), Some(Subsystem::Boot));
+        // Prefixes are anchored and end at a name boundary
+        assert_eq!(Subsystem::of("usr/bin/sum"), None);
+        assert_eq!(Subsystem::of("usr/share/man/man8/mount.8.gz"), None);
+        assert_eq!(Subsystem::of("usr/share/doc/openssh/README"), None);
+        assert_eq!(Subsystem::of("usr/bin/gpasswd"), None);
+
+        let mut report = VerificationReport::new("test");
+        report.add(CheckResult::pass("usr/bin/ls", CheckCategory::Binary));
+        report.add(CheckResult::fail(
+            "usr/lib/systemd/system/NetworkManager.service",
+            CheckCategory::Unit,
+            "Missing",
+        ));
+        report.add(CheckResult::pass("etc/shadow", CheckCategory::EtcFile));
+        report.add(
+            CheckResult::pass("usr/bin/oem-agent", CheckCategory::Binary)
+                .with_subsystem(Subsystem::Networking),
+        );
+        assert_eq!(report.results[2].subsystem, Some(Subsystem::Auth));
+
+        let groups: Vec<_> = report
+            .by_subsystem()
+            .into_iter()
+            .map(|(subsystem, results)| (subsystem, results.len()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                (Some(Subsystem::Auth), 1),
+                (Some(Subsystem::Networking), 2),
+                (None, 1)
+            ]
+        );
+        assert_eq!(Grouping::parse_name("Subsystem"), Some(Grouping::Subsystem));
+    }
+}
@@ -0,0 +1,890 @@
+//! Netboot tree checklist (PXE/iPXE)
+//!
+//! A netboot tree is what a tftp or http server hands a booting machine:
+//! iPXE scripts or pxelinux configs, and the kernels, initramfs images and
+//! root filesystems they fetch by URL. A typo in a script, or a URL to an
+//! image that was renamed, leaves the machine at a boot prompt; here every
+//! iPXE script is checked for what iPXE would reject, and every image a
+//! script or config names must be in the tree and be what it's loaded as.
+//!
+//! URLs resolve against the tree as the server's document root: relative
+//! ones from the script's directory (pxelinux: from `pxelinux.cfg/`'s
+//! parent), absolute paths and `http://host/...` from the root, or failing
+//! that by the longest path suffix the tree has, for servers that publish
+//! it below a prefix. Variables a script `set`s to a literal are
+//! substituted; URLs built from anything else (`${next-server}`, a `choose`
+//! result) are known only at boot and are skipped.
+//!
+//! Root filesystem URLs are the `scheme://` values on kernel command lines
+//! (`root=live:http://srv/live/filesystem.erofs`); they must resolve too.
+
+use super::{CheckCategory, CheckResult, Scope, Subsystem, VerificationReport};
+use crate::cpio::CpioReader;
+use crate::magic::FileKind;
+use crate::reader::ArchiveReader;
+use std::collections::{BTreeSet, HashMap};
+
+/// First line of every iPXE script
+pub const IPXE_MAGIC: &str = "#!ipxe";
+
+/// Directory pxelinux reads its configs from
+pub const PXELINUX_DIR: &str = "pxelinux.cfg";
+
+/// Scripts are small; larger files aren't read looking for the magic
+const MAX_SCRIPT_SIZE: u64 = 64 * 1024;
+
+/// iPXE's commands, as of v1.21
+const IPXE_COMMANDS: &[&str] = &[
+    "autoboot",
+    "boot",
+    "certfree",
+    "certstat",
+    "certstore",
+    "chain",
+    "choose",
+    "clear",
+    "colour",
+    "console",
+    "cpair",
+    "cpuid",
+    "dhcp",
+    "echo",
+    "exit",
+    "fcels",
+    "fcstat",
+    "form",
+    "gdbstub",
+    "goto",
+    "help",
+    "ibstat",
+    "ifclose",
+    "ifconf",
+    "ifopen",
+    "ifstat",
+    "imgargs",
+    "imgdecrypt",
+    "imgexec",
+    "imgextract",
+    "imgfetch",
+    "imgfree",
+    "imgload",
+    "imgmem",
+    "imgselect",
+    "imgstat",
+    "imgtrust",
+    "imgverify",
+    "inc",
+    "initrd",
+    "ipstat",
+    "iseq",
+    "isset",
+    "item",
+    "iwlist",
+    "iwstat",
+    "kernel",
+    "login",
+    "lotest",
+    "md5sum",
+    "menu",
+    "module",
+    "neighbour",
+    "nslookup",
+    "nstat",
+    "ntp",
+    "param",
+    "params",
+    "pciscan",
+    "ping",
+    "poweroff",
+    "profstat",
+    "prompt",
+    "pxebs",
+    "read",
+    "reboot",
+    "route",
+    "sanboot",
+    "sanhook",
+    "sanunhook",
+    "set",
+    "sha1sum",
+    "shell",
+    "show",
+    "shim",
+    "sleep",
+    "sync",
+    "time",
+    "vcreate",
+    "vdestroy",
+];
+
+/// Options of the image commands that take a value
+const VALUE_OPTIONS: &[&str] = &["--name", "-n", "--timeout", "-t"];
+
+/// What a referenced image is loaded as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Load {
+    Kernel,
+    Initrd,
+    /// Chained scripts and EFI programs, pxelinux modules, device trees
+    Other,
+    /// A root filesystem on the kernel command line
+    Rootfs,
+}
+
+impl Load {
+    /// Content kinds that can be loaded this way; None if any will do.
+    fn kinds(self) -> Option<&'static [FileKind]> {
+        match self {
+            Load::Kernel => Some(&[FileKind::BzImage, FileKind::Arm64Image, FileKind::Pe]),
+            Load::Initrd => Some(&[FileKind::Cpio, FileKind::Gzip, FileKind::Xz, FileKind::Zstd]),
+            Load::Other | Load::Rootfs => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Load::Kernel => "Kernel",
+            Load::Initrd => "Initramfs",
+            Load::Other => "Image",
+            Load::Rootfs => "Root filesystem",
+        }
+    }
+}
+
+/// An image a script names, at `script`:`line`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Reference {
+    script: String,
+    line: usize,
+    url: String,
+    load: Load,
+    /// Directory relative URLs resolve from
+    base: String,
+}
+
+/// Where a reference leads in the tree
+#[derive(Debug, PartialEq, Eq)]
+enum Located {
+    Found(String),
+    /// The tree path it names, which doesn't exist
+    Missing(String),
+    /// Built from a variable known only at boot
+    Dynamic(String),
+}
+
+pub fn scope() -> Scope {
+    // Anything in the tree may be what a script names
+    let mut scope = Scope::new();
+    scope.add_prefix("", CheckCategory::Other);
+    scope
+}
+
+/// Verify a netboot tree (a staged directory, or an unpacked tarball).
+pub fn verify(reader: &CpioReader) -> VerificationReport {
+    let mut report = VerificationReport::new("Netboot");
+    for result in check(reader) {
+        report.add(result.with_subsystem(Subsystem::Boot));
+    }
+    report
+}
+
+fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    let (ipxe, pxelinux) = scripts(reader);
+    if ipxe.is_empty() && pxelinux.is_empty() {
+        return vec![CheckResult::fail(
+            "boot script",
+            CheckCategory::Config,
+            format!(
+                "CRITICAL: No iPXE script ({} or *.ipxe) or {}/ config",
+                IPXE_MAGIC, PXELINUX_DIR
+            ),
+        )
+        .with_hint("Ship the script the DHCP server's boot filename points iPXE at")];
+    }
+
+    let mut results = Vec::new();
+    let mut references = Vec::new();
+    for (path, text) in &ipxe {
+        let problems = parse_ipxe(path, text, &mut references);
+        if problems.is_empty() {
+            results.push(CheckResult::pass(path, CheckCategory::Config));
+        }
+        results.extend(problems.into_iter().map(|(line, message)| {
+            CheckResult::fail(format!("{}:{}", path, line), CheckCategory::Config, message)
+        }));
+    }
+    for (path, text) in &pxelinux {
+        parse_pxelinux(path, text, &mut references);
+        results.push(CheckResult::pass(path, CheckCategory::Config));
+    }
+    if !references.iter().any(|r| r.load == Load::Kernel) {
+        results.push(
+            CheckResult::fail(
+                "kernel",
+                CheckCategory::Binary,
+                "CRITICAL: No script loads a kernel",
+            )
+            .with_hint("Load one with iPXE's kernel command or pxelinux's KERNEL/LINUX"),
+        );
+    }
+
+    let mut kinds: HashMap<String, Option<FileKind>> = HashMap::new();
+    let mut passed = BTreeSet::new();
+    for reference in &references {
+        let at = format!("{}:{}", reference.script, reference.line);
+        match locate(reader, &reference.url, &reference.base) {
+            Located::Found(path) => {
+                let kind = match reference.load.kinds() {
+                    Some(_) => *kinds.entry(path.clone()).or_insert_with(|| {
+                        let content = reader.read_file(&path).ok()?;
+                        Some(FileKind::identify(&content))
+                    }),
+                    None => None,
+                };
+                match (reference.load.kinds(), kind) {
+                    (Some(allowed), Some(kind)) if !allowed.contains(&kind) => {
+                        results.push(CheckResult::fail(
+                            at,
+                            CheckCategory::Binary,
+                            format!(
+                                "{} {} is {}, not a loadable {}",
+                                reference.load.name(),
+                                path,
+                                kind.name(),
+                                reference.load.name().to_lowercase()
+                            ),
+                        ))
+                    }
+                    _ => {
+                        if passed.insert(path.clone()) {
+                            results.push(CheckResult::pass(path, category(reference.load)));
+                        }
+                    }
+                }
+            }
+            Located::Missing(path) => results.push(
+                CheckResult::fail(
+                    at,
+                    category(reference.load),
+                    format!(
+                        "{} {} isn't in the netboot tree (looked for {})",
+                        reference.load.name(),
+                        reference.url,
+                        path
+                    ),
+                )
+                .with_hint("Fix the URL, or copy the image into the tree where the URL points"),
+            ),
+            Located::Dynamic(var) => {
+                let mut result = CheckResult::fail(
+                    at,
+                    category(reference.load),
+                    format!("Not checked: {} depends on ${{{}}}", reference.url, var),
+                );
+                result.skipped_by = Some(format!("${{{}}}", var));
+                results.push(result);
+            }
+        }
+    }
+    results
+}
+
+fn category(load: Load) -> CheckCategory {
+    match load {
+        Load::Kernel | Load::Initrd => CheckCategory::Binary,
+        Load::Other | Load::Rootfs => CheckCategory::Other,
+    }
+}
+
+/// iPXE scripts (`*.ipxe`, or small files starting with the magic) and
+/// pxelinux configs, with their text.
+#[allow(clippy::type_complexity)]
+fn scripts(reader: &CpioReader) -> (Vec<(String, String)>, Vec<(String, String)>) {
+    let candidates: Vec<&str> = reader
+        .entries()
+        .iter()
+        .filter(|e| e.is_file() && e.size <= MAX_SCRIPT_SIZE)
+        .map(|e| e.path.as_str())
+        .collect();
+    let contents = reader.read_files(&candidates).unwrap_or_default();
+    let (mut ipxe, mut pxelinux) = (Vec::new(), Vec::new());
+    for path in candidates {
+        let Some(content) = contents.get(path) else {
+            continue;
+        };
+        let text = String::from_utf8_lossy(content).into_owned();
+        let in_pxelinux = path
+            .rsplit_once('/')
+            .is_some_and(|(dir, _)| dir == PXELINUX_DIR || dir.ends_with("/pxelinux.cfg"));
+        if path.ends_with(".ipxe") || text.starts_with(IPXE_MAGIC) {
+            ipxe.push((path.to_string(), text));
+        } else if in_pxelinux {
+            pxelinux.push((path.to_string(), text));
+        }
+    }
+    (ipxe, pxelinux)
+}
+
+/// Split a line into words, honouring quotes.
+fn words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// Check an iPXE script and collect the images it names. Returns the
+/// problems by line number.
+fn parse_ipxe(path: &str, text: &str, references: &mut Vec<Reference>) -> Vec<(usize, String)> {
+    let base = path.rsplit_once('/').map_or("", |(dir, _)| dir).to_string();
+    let mut problems = Vec::new();
+    if !text.starts_with(IPXE_MAGIC) {
+        problems.push((
+            1,
+            format!(
+                "No {} first line: iPXE won't run it as a script",
+                IPXE_MAGIC
+            ),
+        ));
+    }
+
+    let mut labels = BTreeSet::new();
+    let mut gotos = Vec::new();
+    let mut vars: HashMap<String, String> = HashMap::new();
+    let mut found = Vec::new();
+    let (mut loads_kernel, mut boots) = (None, false);
+    for (number, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(label) = line.strip_prefix(':') {
+            labels.insert(label.split_whitespace().next().unwrap_or("").to_string());
+            continue;
+        }
+        // Substituted as iPXE does, when the line runs
+        let words: Vec<String> = words(line)
+            .iter()
+            .map(|word| substitute(word, &vars))
+            .collect();
+        for command in words.split(|w| w == "||" || w == "&&") {
+            let Some((name, args)) = command.split_first() else {
+                continue;
+            };
+            if name.contains("${") {
+                continue;
+            }
+            if !IPXE_COMMANDS.contains(&name.as_str()) {
+                problems.push((number, format!("Unknown command {}", name)));
+                continue;
+            }
+            let operands: Vec<&String> = {
+                let mut operands = Vec::new();
+                let mut args = args.iter();
+                while let Some(arg) = args.next() {
+                    if VALUE_OPTIONS.contains(&arg.as_str()) {
+                        args.next();
+                    } else if !arg.starts_with('-') {
+                        operands.push(arg);
+                    }
+                }
+                operands
+            };
+            let image = |load| (number, operands.first().map(|u| u.to_string()), load);
+            match name.as_str() {
+                "set" => {
+                    if let Some((var, value)) = args.split_first() {
+                        let value = value.join(" ");
+                        if value.contains("${") {
+                            vars.remove(var);
+                        } else {
+                            vars.insert(var.clone(), value);
+                        }
+                    }
+                }
+                "goto" => match operands.first() {
+                    Some(label) if !label.contains("${") => gotos.push((number, label.to_string())),
+                    Some(_) => {}
+                    None => problems.push((number, "goto without a label".to_string())),
+                },
+                "kernel" | "imgselect" | "imgload" => {
+                    loads_kernel.get_or_insert(number);
+                    found.push(image(if name == "kernel" {
+                        Load::Kernel
+                    } else {
+                        Load::Other
+                    }));
+                }
+                "chain" | "imgexec" => {
+                    boots = true;
+                    found.push(image(Load::Other));
+                }
+                "initrd" => found.push(image(Load::Initrd)),
+                "imgfetch" | "module" => found.push(image(Load::Other)),
+                "boot" => boots = true,
+                _ => {}
+            }
+            // Kernel command line: root filesystems named by URL
+            if matches!(name.as_str(), "kernel" | "chain" | "imgexec" | "imgargs") {
+                for url in operands.iter().skip(1).filter_map(|arg| embedded_url(arg)) {
+                    found.push((number, Some(url.to_string()), Load::Rootfs));
+                }
+            }
+        }
+    }
+
+    for (number, label) in gotos {
+        if !labels.contains(&label) {
+            problems.push((number, format!("goto {}: no :{} label", label, label)));
+        }
+    }
+    if let (Some(number), false) = (loads_kernel, boots) {
+        problems.push((
+            number,
+            "Loads a kernel but never boots it (no boot, chain or imgexec)".to_string(),
+        ));
+    }
+    for (number, url, load) in found {
+        match url {
+            Some(url) => references.push(Reference {
+                script: path.to_string(),
+                line: number,
+                url,
+                load,
+                base: base.clone(),
+            }),
+            None if load != Load::Rootfs => {
+                problems.push((number, "Image command without a URL".to_string()))
+            }
+            None => {}
+        }
+    }
+    problems.sort_by_key(|(number, _)| *number);
+    problems
+}
+
+/// Collect the images a pxelinux config names.
+fn parse_pxelinux(path: &str, text: &str, references: &mut Vec<Reference>) {
+    // Relative to pxelinux.0's directory, the one pxelinux.cfg/ is in
+    let base = path
+        .rsplit_once('/')
+        .map_or("", |(dir, _)| dir)
+        .rsplit_once('/')
+        .map_or("", |(parent, _)| parent)
+        .to_string();
+    let mut add = |line: usize, url: &str, load| {
+        references.push(Reference {
+            script: path.to_string(),
+            line,
+            url: url.to_string(),
+            load,
+            base: base.clone(),
+        })
+    };
+    for (number, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+        let Some((keyword, rest)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let rest = rest.trim();
+        match keyword.to_ascii_uppercase().as_str() {
+            "KERNEL" | "LINUX" => {
+                // Modules (menu.c32) and chained loaders (pxelinux.0)
+                let load = if rest.ends_with(".c32") || rest.ends_with(".0") {
+                    Load::Other
+                } else {
+                    Load::Kernel
+                };
+                add(number, rest, load);
+            }
+            "INITRD" => rest
+                .split(',')
+                .for_each(|url| add(number, url, Load::Initrd)),
+            "APPEND" => {
+                for arg in rest.split_whitespace() {
+                    if let Some(initrds) = arg.strip_prefix("initrd=") {
+                        initrds
+                            .split(',')
+                            .for_each(|url| add(number, url, Load::Initrd));
+                    } else if let Some(url) = embedded_url(arg) {
+                        add(number, url, Load::Rootfs);
+                    }
+                }
+            }
+            "UI" | "FDT" | "INCLUDE" => add(number, rest, Load::Other),
+            _ => {}
+        }
+    }
+}
+
+/// A `scheme://` URL inside a kernel argument (`root=live:http://...`).
+fn embedded_url(arg: &str) -> Option<&str> {
+    let at = arg.find("://")?;
+    let start = arg[..at]
+        .rfind(|c: char| !c.is_ascii_alphanumeric())
+        .map_or(0, |i| i + 1);
+    (start < at).then(|| &arg[start..])
+}
+
+/// Replace the `${var}`s (and `${var:type}`s) `vars` has; the rest stay.
+fn substitute(url: &str, vars: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = url;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        let name = rest[start + 2..end].split(':').next().unwrap_or_default();
+        out.push_str(&rest[..start]);
+        match vars.get(name) {
+            Some(value) => out.push_str(value),
+            None => out.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Where `url` leads in the tree, relative URLs from `base`.
+fn locate(reader: &CpioReader, url: &str, base: &str) -> Located {
+    if let Some(start) = url.find("${") {
+        let name = url[start + 2..]
+            .split(['}', ':'])
+            .next()
+            .unwrap_or_default();
+        return Located::Dynamic(name.to_string());
+    }
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let path = match url.split_once("://") {
+        // Host and all: the path is from the document root
+        Some((_, rest)) => rest
+            .split_once('/')
+            .map_or("", |(_, path)| path)
+            .to_string(),
+        None if url.starts_with('/') => url.to_string(),
+        None => format!("{}/{}", base, url),
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    let is_file = |path: &str| {
+        reader
+            .resolve(path)
+            .filter(|target| reader.get(target).is_some_and(|e| e.is_file()))
+    };
+    (0..parts.len())
+        .find_map(|skip| is_file(&parts[skip..].join("/")))
+        .map_or_else(|| Located::Missing(parts.join("/")), Located::Found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dir::DirReader;
+    use std::fs;
+    use std::path::Path;
+
+    /// Enough of a bzImage header for [`FileKind::identify`]
+    fn bzimage() -> Vec<u8> {
+        let mut kernel = vec![0u8; 0x210];
+        kernel[0x202..0x206].copy_from_slice(b"HdrS");
+        kernel
+    }
+
+    fn write_tree(root: &Path, files: &[(&str, &[u8])]) {
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    /// Failures (item, message) of the tree holding `files`.
+    fn failures(files: &[(&str, &[u8])]) -> Vec<(String, String)> {
+        let dir = tempfile::tempdir().unwrap();
+        write_tree(dir.path(), files);
+        let reader = CpioReader::from_dir(DirReader::open(dir.path()).unwrap());
+        check(&reader)
+            .into_iter()
+            .filter(|r| r.is_failure())
+            .map(|r| (r.item, r.message.unwrap_or_default()))
+            .collect()
+    }
+
+    fn failure(item: &str, message: &str) -> (String, String) {
+        (item.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn test_netboot_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("live")).unwrap();
+        fs::create_dir_all(root.join(PXELINUX_DIR)).unwrap();
+        let mut kernel = vec![0u8; 0x210];
+        kernel[0x202..0x206].copy_from_slice(b"HdrS");
+        fs::write(root.join("live/vmlinuz"), &kernel).unwrap();
+        fs::write(root.join("live/initramfs.img"), b"070701").unwrap();
+        fs::write(root.join("live/filesystem.erofs"), b"erofs").unwrap();
+        fs::write(
+            root.join("boot.ipxe"),
+            "#!ipxe\n\
+             set base http://${next-server}/levitate/live\n\
+             set live http://srv/levitate/live\n\
+             dhcp || goto failed\n\
+             kernel ${live}/vmlinuz root=live:${live}/filesystem.erofs\n\
+             initrd ${live}/initramfs.img\n\
+             initrd --name extra ${base}/extra.img\n\
+             imgfetch live/missing.img\n\
+             boot || goto retry\n\
+             :failed\n\
+             shell\n\
+             sleeep 5\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("pxelinux.cfg/default"),
+            "DEFAULT live\nLABEL live\n  KERNEL live/initramfs.img\n  APPEND initrd=live/initramfs.img\n",
+        )
+        .unwrap();
+
+        let reader = CpioReader::from_dir(DirReader::open(root).unwrap());
+        let results = check(&reader);
+        let failed: Vec<_> = results
+            .iter()
+            .filter(|r| r.is_failure())
+            .map(|r| (r.item.as_str(), r.message.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                ("boot.ipxe:9", "goto retry: no :retry label"),
+                ("boot.ipxe:12", "Unknown command sleeep"),
+                (
+                    "boot.ipxe:8",
+                    "Image live/missing.img isn't in the netboot tree (looked for live/missing.img)"
+                ),
+                (
+                    "pxelinux.cfg/default:3",
+                    "Kernel live/initramfs.img is cpio, not a loadable kernel"
+                ),
+            ]
+        );
+        assert!(results
+            .iter()
+            .any(|r| r.item == "live/filesystem.erofs" && r.passed));
+        assert!(results.iter().any(|r| r.is_skipped()));
+
+        assert_eq!(
+            embedded_url("root=live:http://srv/a.erofs"),
+            Some("http://srv/a.erofs")
+        );
+        assert_eq!(words("echo \"a b\" c"), ["echo", "a b", "c"]);
+    }
+
+    #[test]
+    fn test_pxe_tree() {
+        let kernel = bzimage();
+        let dir = tempfile::tempdir().unwrap();
+        write_tree(
+            dir.path(),
+            &[
+                ("tftp/pxelinux.0", b"pxelinux"),
+                ("tftp/menu.c32", b"module"),
+                ("tftp/vmlinuz", &kernel),
+                ("tftp/initramfs.img", b"070701"),
+                ("tftp/live/filesystem.erofs", b"erofs"),
+                (
+                    "tftp/pxelinux.cfg/default",
+                    b"UI menu.c32\n\
+                      LABEL live\n\
+                      \x20 KERNEL vmlinuz\n\
+                      \x20 INITRD initramfs.img\n\
+                      \x20 APPEND root=live:http://srv/pub/tftp/live/filesystem.erofs quiet\n",
+                ),
+            ],
+        );
+
+        let reader = CpioReader::from_dir(DirReader::open(dir.path()).unwrap());
+        let report = verify(&reader);
+        assert!(report.is_success(), "{:?}", report.results);
+        let passed: Vec<&str> = report.results.iter().map(|r| r.item.as_str()).collect();
+        // Relative to pxelinux.cfg/'s parent; the rootfs URL by path suffix
+        assert_eq!(
+            passed,
+            [
+                "tftp/pxelinux.cfg/default",
+                "tftp/menu.c32",
+                "tftp/vmlinuz",
+                "tftp/initramfs.img",
+                "tftp/live/filesystem.erofs",
+            ]
+        );
+        assert!(report
+            .results
+            .iter()
+            .all(|r| r.subsystem == Some(Subsystem::Boot)));
+    }
+
+    #[test]
+    fn test_ipxe_script() {
+        let kernel = bzimage();
+        let dir = tempfile::tempdir().unwrap();
+        write_tree(
+            dir.path(),
+            &[
+                ("live/vmlinuz", &kernel),
+                ("live/initramfs.img", b"\x1f\x8b\x08\x00"),
+                // No .ipxe suffix: recognised by its first line
+                (
+                    "boot",
+                    b"#!ipxe\n\
+                      set live /live\n\
+                      :retry\n\
+                      dhcp || goto retry\n\
+                      kernel --name vmlinuz ${live}/vmlinuz quiet\n\
+                      initrd ${live:string}/initramfs.img && boot\n",
+                ),
+                ("README", b"not a script"),
+            ],
+        );
+
+        let reader = CpioReader::from_dir(DirReader::open(dir.path()).unwrap());
+        let results = check(&reader);
+        assert!(results.iter().all(|r| r.passed), "{:?}", results);
+        let items: Vec<&str> = results.iter().map(|r| r.item.as_str()).collect();
+        assert_eq!(items, ["boot", "live/vmlinuz", "live/initramfs.img"]);
+    }
+
+    #[test]
+    fn test_tarball() {
+        use crate::sanitize::Destination;
+        use crate::tar::testing::{entry, finish};
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut tar = Vec::new();
+        entry(&mut tar, "netboot/", b'5', b"", "");
+        entry(
+            &mut tar,
+            "netboot/boot.ipxe",
+            b'0',
+            b"#!ipxe\nkernel vmlinuz\ninitrd initrd.img\nboot\n",
+            "",
+        );
+        entry(&mut tar, "netboot/vmlinuz", b'0', &bzimage(), "");
+        entry(&mut tar, "netboot/initrd.img", b'2', b"", "vmlinuz");
+        finish(&mut tar);
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&tar).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("netboot.tar.gz");
+        fs::write(&archive, gz.finish().unwrap()).unwrap();
+        let out = dir.path().join("out");
+        fs::create_dir(&out).unwrap();
+        crate::tar::extract(&archive, &Destination::new(&out)).unwrap();
+
+        // The initrd symlink resolves in the tree, to the kernel
+        let reader = CpioReader::from_dir(DirReader::open(&out.join("netboot")).unwrap());
+        let failed: Vec<_> = check(&reader)
+            .into_iter()
+            .filter(|r| r.is_failure())
+            .map(|r| (r.item, r.message.unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            failed,
+            [failure(
+                "boot.ipxe:3",
+                "Initramfs vmlinuz is bzimage, not a loadable initramfs"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_failing_checks() {
+        let kernel = bzimage();
+
+        let none = failures(&[("vmlinuz", &kernel)]);
+        assert_eq!(none.len(), 1);
+        assert_eq!(none[0].0, "boot script");
+        assert!(none[0].1.starts_with("CRITICAL: No iPXE script"));
+
+        assert_eq!(
+            failures(&[
+                ("vmlinuz", &kernel),
+                ("boot.ipxe", b"kernel vmlinuz\nboot\n"),
+            ]),
+            [failure(
+                "boot.ipxe:1",
+                "No #!ipxe first line: iPXE won't run it as a script"
+            )]
+        );
+        assert_eq!(
+            failures(&[
+                ("vmlinuz", &kernel),
+                ("boot.ipxe", b"#!ipxe\nkernel vmlinuz\ngoto\ninitrd\n"),
+            ]),
+            [
+                failure(
+                    "boot.ipxe:2",
+                    "Loads a kernel but never boots it (no boot, chain or imgexec)"
+                ),
+                failure("boot.ipxe:3", "goto without a label"),
+                failure("boot.ipxe:4", "Image command without a URL"),
+            ]
+        );
+        assert_eq!(
+            failures(&[
+                ("boot.ipxe", b"#!ipxe\nchain next.ipxe\n"),
+                ("next.ipxe", b"#!ipxe\necho no kernel here\n"),
+            ]),
+            [failure("kernel", "CRITICAL: No script loads a kernel")]
+        );
+        assert_eq!(
+            failures(&[
+                ("vmlinuz", &kernel),
+                (
+                    "pxelinux.cfg/default",
+                    b"KERNEL vmlinuz\nAPPEND root=live:http://srv/live/gone.erofs\n",
+                ),
+            ]),
+            [failure(
+                "pxelinux.cfg/default:2",
+                "Root filesystem http://srv/live/gone.erofs isn't in the netboot tree \
+                 (looked for live/gone.erofs)"
+            )]
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! Pattern expectations: globs with count thresholds
+//!
+//! Many expectations aren't one path but "some vmlinuz-*", "at least one
+//! *.rules in rules.d" or "three UKIs in EFI/Linux". A [`GlobCheck`] counts
+//! the paths matching its globs and passes when the count is within bounds,
+//! so checklists don't each write their own scan loop:
+//!
+//! ```ignore
+//! use crate::checklist::patterns::GlobCheck;
+//! use crate::checklist::CheckCategory;
+//!
+//! let rules = GlobCheck::new("usr/lib/udev/rules.d/*.rules", CheckCategory::UdevRule);
+//! let paths = ["usr/lib/udev/rules.d/60-block.rules", "usr/lib/udev/hwdb.bin"];
+//! assert!(rules.check(paths).passed);
+//! ```
+//!
+//! `*` stays within one directory and `**` crosses directories, as in ignore
+//! patterns.
+
+use super::{CheckCategory, CheckResult, Scope};
+use crate::cpio::CpioReader;
+use crate::error::FsdbgError;
+use glob::{MatchOptions, Pattern};
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Whether `path` holds glob metacharacters.
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Expectation on the number of paths matching one or more globs
+#[derive(Debug, Clone)]
+pub struct GlobCheck {
+    patterns: Vec<Pattern>,
+    category: CheckCategory,
+    item: Option<String>,
+    min: usize,
+    max: Option<usize>,
+    failure: Option<String>,
+    show_count: bool,
+}
+
+impl GlobCheck {
+    /// Expect at least one path matching `glob`.
+    ///
+    /// Panics on an invalid pattern; meant for the checklists' own literals.
+    /// Use [`parse`](Self::parse) for patterns from users.
+    pub fn new(glob: &str, category: CheckCategory) -> Self {
+        Self::parse(glob, category).expect("checklist glob is valid")
+    }
+
+    /// Expect at least one path matching `glob`.
+    pub fn parse(glob: &str, category: CheckCategory) -> Result<Self, FsdbgError> {
+        Ok(Self {
+            patterns: vec![compile(glob)?],
+            category,
+            item: None,
+            min: 1,
+            max: None,
+            failure: None,
+            show_count: false,
+        })
+    }
+
+    /// Also count paths matching `glob`.
+    pub fn or(mut self, glob: &str) -> Self {
+        self.patterns
+            .push(compile(glob).expect("checklist glob is valid"));
+        self
+    }
+
+    /// Require at least `n` matches (default 1).
+    pub fn at_least(mut self, n: usize) -> Self {
+        self.min = n;
+        self
+    }
+
+    /// Allow at most `n` matches; `at_most(0)` expects none.
+    pub fn at_most(mut self, n: usize) -> Self {
+        self.max = Some(n);
+        self
+    }
+
+    /// Report as `item` rather than as the first glob.
+    pub fn labelled(mut self, item: impl Into<String>) -> Self {
+        self.item = Some(item.into());
+        self
+    }
+
+    /// Failure message instead of the count.
+    pub fn failure(mut self, message: impl Into<String>) -> Self {
+        self.failure = Some(message.into());
+        self
+    }
+
+    /// Append ` (count=N)` to the reported item.
+    pub fn show_count(mut self) -> Self {
+        self.show_count = true;
+        self
+    }
+
+    /// Whether `path` matches one of the globs.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.trim_start_matches("./").trim_start_matches('/');
+        self.patterns
+            .iter()
+            .any(|p| p.matches_with(path, MATCH_OPTIONS))
+    }
+
+    /// Number of `paths` matching.
+    pub fn count<'a>(&self, paths: impl IntoIterator<Item = &'a str>) -> usize {
+        paths.into_iter().filter(|path| self.matches(path)).count()
+    }
+
+    /// Check the paths of an archive, or of anything else that can list them.
+    pub fn check<'a>(&self, paths: impl IntoIterator<Item = &'a str>) -> CheckResult {
+        let count = self.count(paths);
+        let mut item = self
+            .item
+            .clone()
+            .unwrap_or_else(|| self.patterns[0].as_str().to_string());
+        if self.show_count {
+            item = format!("{} (count={})", item, count);
+        }
+        let problem = if count < self.min {
+            Some(match count {
+                0 => "No match".to_string(),
+                _ => format!("{} match(es), expected at least {}", count, self.min),
+            })
+        } else {
+            match self.max {
+                Some(0) if count > 0 => Some(format!("{} match(es), expected none", count)),
+                Some(max) if count > max => {
+                    Some(format!("{} match(es), expected at most {}", count, max))
+                }
+                _ => None,
+            }
+        };
+        match problem {
+            None => CheckResult::pass(item, self.category),
+            Some(problem) => {
+                CheckResult::fail(item, self.category, self.failure.clone().unwrap_or(problem))
+            }
+        }
+    }
+
+    /// Check the entries of a CPIO archive.
+    pub fn check_cpio(&self, reader: &CpioReader) -> CheckResult {
+        self.check(reader.entries().iter().map(|e| e.path.as_str()))
+    }
+
+    /// Add the directories the globs look in to a checklist scope.
+    pub fn extend_scope(&self, scope: &mut Scope) {
+        for pattern in &self.patterns {
+            let glob = pattern.as_str();
+            let literal = &glob[..glob.find(['*', '?', '[']).unwrap_or(glob.len())];
+            match literal.rfind('/') {
+                Some(end) => scope.add_prefix(&literal[..end], self.category),
+                None => scope.add(glob, self.category),
+            }
+        }
+    }
+}
+
+fn compile(glob: &str) -> Result<Pattern, FsdbgError> {
+    let glob = glob.trim_start_matches("./").trim_start_matches('/');
+    Pattern::new(glob)
+        .map_err(|e| FsdbgError::invalid_format(format!("Invalid glob '{}': {}", glob, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_example() {
+        let rules = GlobCheck::new("usr/lib/udev/rules.d/*.rules", CheckCategory::UdevRule);
+        let paths = ["usr/lib/udev/rules.d/60-block.rules", "usr/lib/udev/hwdb.bin"];
+        assert!(rules.check(paths).passed);
+    }
+
+    #[test]
+    fn test_glob_check() {
+        let paths = [
+            "boot/vmlinuz-6.12.0",
+            "boot/vmlinuz-6.11.9",
+            "EFI/Linux/levitate.efi",
+            "usr/lib/modules/6.12.0/kernel/fs/ext4.ko.xz",
+        ];
+
+        let kernel = GlobCheck::new("/boot/vmlinuz-*", CheckCategory::Binary);
+        assert!(kernel.check(paths).passed);
+        let one = kernel.clone().at_most(1).check(paths);
+        assert_eq!(
+            one.message.as_deref(),
+            Some("2 match(es), expected at most 1")
+        );
+
+        let ukis = GlobCheck::new("EFI/Linux/*.efi", CheckCategory::Binary)
+            .at_least(3)
+            .show_count()
+            .check(paths);
+        assert_eq!(ukis.item, "EFI/Linux/*.efi (count=1)");
+        assert_eq!(
+            ukis.message.as_deref(),
+            Some("1 match(es), expected at least 3")
+        );
+
+        let modules = GlobCheck::new("usr/lib/modules/*/kernel/**", CheckCategory::KernelModule)
+            .labelled("usr/lib/modules/*/kernel/");
+        assert!(modules.check(paths).passed);
+        assert!(
+            !GlobCheck::new("usr/lib/modules/*.ko.xz", CheckCategory::KernelModule)
+                .check(paths)
+                .passed
+        );
+
+        let dep = GlobCheck::new("**/lib/modules/**/modules.dep", CheckCategory::EtcFile);
+        assert!(dep.matches("lib/modules/6.12.0/modules.dep"));
+        assert!(dep.matches("usr/lib/modules/6.12.0/modules.dep"));
+
+        let locale = GlobCheck::new("usr/lib/locale/**", CheckCategory::Other)
+            .or("usr/share/locale/**")
+            .failure("No locale data found")
+            .check(["usr/share/locale/de/LC_MESSAGES/bash.mo"]);
+        assert!(locale.passed);
+        assert!(
+            !GlobCheck::new("usr/bin/busybox", CheckCategory::Forbidden)
+                .at_least(0)
+                .at_most(0)
+                .check(["usr/bin/busybox"])
+                .passed
+        );
+
+        let mut scope = Scope::new();
+        modules.extend_scope(&mut scope);
+        assert_eq!(
+            scope.category_of("usr/lib/modules/6.12.0/modules.dep"),
+            Some(CheckCategory::KernelModule)
+        );
+        assert!(GlobCheck::parse("usr/[lib", CheckCategory::Other).is_err());
+    }
+}
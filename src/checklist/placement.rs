@@ -0,0 +1,155 @@
+//! Placement of the ISO's boot files
+//!
+//! Firmware reads boot files with far less care than Linux reads the ISO.
+//! Some only follow a file's first directory record, so a file split into
+//! several extents (ISO 9660 level 3) comes out truncated; some read with
+//! 32-bit byte offsets, so a file ending past 4 GiB can't be read at all.
+//! The El Torito boot images have a third limit: the catalog's sector count
+//! is 16 bits of 512-byte sectors, and firmware that honours it loads only
+//! the first 32 MiB of a larger efiboot.img.
+//!
+//! These are compatibility heuristics, not spec violations: the ISO boots
+//! on most machines regardless. Problems are reported on passing results
+//! rather than failing the build.
+
+use super::iso::BOOT_FILES;
+use super::{CheckCategory, CheckResult, Subsystem};
+use crate::arch::Arch;
+use crate::iso::{self, IsoEntry, IsoReader, BLOCK_SIZE};
+use distro_spec::shared::{EFIBOOT_FILENAME, ISO_EFI_DIR, UKI_EFI_DIR};
+
+/// Highest byte offset firmware with 32-bit offsets can read
+const FOUR_GIB: u64 = 1 << 32;
+
+/// Most an El Torito sector count can express
+const MAX_CATALOG_LOAD: u64 = 0xffff * 512;
+
+/// What firmware might trip over reading a file made of `extents`.
+fn problems(extents: &[&IsoEntry]) -> Vec<String> {
+    let mut problems = Vec::new();
+    if extents.len() > 1 {
+        problems.push(format!(
+            "split into {} extents; firmware reading only the first gets a truncated file",
+            extents.len()
+        ));
+    }
+    let end = extents
+        .iter()
+        .map(|e| u64::from(e.extent) * BLOCK_SIZE + e.size)
+        .max()
+        .unwrap_or_default();
+    if end > FOUR_GIB {
+        problems.push(format!(
+            "ends {} MiB into the ISO, past the 4 GiB firmware with 32-bit offsets can read",
+            end >> 20
+        ));
+    }
+    problems
+}
+
+/// Check where the boot files sit on the ISO and what the El Torito
+/// catalog has firmware load.
+pub fn check(reader: &IsoReader, arch: Arch) -> Vec<CheckResult> {
+    let mut paths: Vec<String> = BOOT_FILES
+        .iter()
+        .map(|file| format!("/{}", arch.path(file)))
+        .collect();
+    paths.push(format!("/{}/{}", ISO_EFI_DIR, arch.efi_bootloader()));
+    paths.push(format!("/{}", EFIBOOT_FILENAME));
+    let uki_prefix = format!("/{}/", UKI_EFI_DIR);
+    paths.extend(
+        reader
+            .entries()
+            .iter()
+            .filter(|e| !e.is_dir && e.path.starts_with(&uki_prefix) && e.path.ends_with(".efi"))
+            .map(|e| e.path.clone()),
+    );
+
+    let mut results = Vec::new();
+    let mut report = |item: String, found: Vec<String>| {
+        results.push(if found.is_empty() {
+            CheckResult::pass(item, CheckCategory::Other)
+        } else {
+            // Valid, with warnings
+            CheckResult::pass(
+                format!("{} ({})", item, found.join("; ")),
+                CheckCategory::Other,
+            )
+        })
+    };
+    for path in &paths {
+        let extents = reader.extents(path);
+        if !extents.is_empty() {
+            report(format!("{} placement", path), problems(&extents));
+        }
+    }
+
+    // Boot images the catalog points at, which may not be among the above
+    if let Ok(catalog) = iso::boot_catalog(reader.source_path()) {
+        for image in catalog {
+            let Some(file) = reader.file_at(image.extent) else {
+                continue;
+            };
+            let extents = reader.extents(&file.path);
+            let mut found = if paths.contains(&file.path) {
+                Vec::new()
+            } else {
+                problems(&extents)
+            };
+            let size: u64 = extents.iter().map(|e| e.size).sum();
+            let loaded = u64::from(image.sectors) * 512;
+            if image.platform == iso::PLATFORM_EFI && size > MAX_CATALOG_LOAD {
+                found.push(format!(
+                    "{} MiB, more than the catalog's sector count can express; firmware honouring it loads {} KiB",
+                    size >> 20,
+                    loaded.max(BLOCK_SIZE) >> 10
+                ));
+            }
+            report(
+                format!(
+                    "El Torito {} image {} placement",
+                    image.platform_name(),
+                    file.path
+                ),
+                found,
+            );
+        }
+    }
+
+    results
+        .into_iter()
+        .map(|r| r.with_subsystem(Subsystem::Boot))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(extent: u32, size: u64) -> IsoEntry {
+        IsoEntry {
+            path: "/efiboot.img".to_string(),
+            raw_path: b"/efiboot.img".to_vec(),
+            size,
+            is_dir: false,
+            is_symlink: false,
+            link_target: None,
+            extent,
+        }
+    }
+
+    #[test]
+    fn test_problems() {
+        assert!(problems(&[&entry(40, 8 << 20)]).is_empty());
+
+        let high = entry(((FOUR_GIB - BLOCK_SIZE) / BLOCK_SIZE) as u32, 8 << 20);
+        let found = problems(&[&high]);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with("ends 4103 MiB into the ISO"));
+
+        let (first, second) = (entry(40, 4 << 30), entry(40 + (2 << 20), 1 << 20));
+        let found = problems(&[&first, &second]);
+        assert_eq!(found.len(), 2);
+        assert!(found[0].starts_with("split into 2 extents"));
+    }
+}
@@ -0,0 +1,319 @@
+//! Runtime probes on staged directory trees (verify --probe)
+//!
+//! Static checks see that `usr/lib/systemd/system/sshd.service` exists, not
+//! that systemd would reject it, and that a binary's loader exists, not that
+//! the loader finds every library the binary needs. On a staged tree, probes
+//! run the tree's own tools over it in a bubblewrap sandbox - the tree bound
+//! read-only as `/`, no network, nothing of the host visible - and the
+//! problems they report become failures. Nothing is chrooted into and
+//! nothing in the tree is written.
+//!
+//! | Probe | Runs | Fails |
+//! |-------|------|-------|
+//! | libs | the ELF's loader (PT_INTERP) in trace mode, as ldd does, on every dynamically linked ELF | each library the loader can't find |
+//! | units | `systemd-analyze verify` on every unit file | each unit it complains about |
+//!
+//! The tools are the tree's, so the tree must be built for the host's
+//! architecture. bwrap needs root or unprivileged user namespaces; as root,
+//! files only root may read get probed too.
+
+use super::{interpreters, CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::reader::ArchiveReader;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::{Command, Output};
+
+const BWRAP: &str = "bwrap";
+
+/// The units probe runs the tree's own systemd-analyze
+const SYSTEMD_ANALYZE: &str = "usr/bin/systemd-analyze";
+
+/// Unit files directly in these are verified; drop-ins below them are
+/// read along with their unit
+const UNIT_DIRS: &[&str] = &["etc/systemd/system", "usr/lib/systemd/system"];
+
+const UNIT_SUFFIXES: &[&str] = &[
+    ".service",
+    ".socket",
+    ".target",
+    ".timer",
+    ".path",
+    ".mount",
+    ".automount",
+    ".swap",
+    ".slice",
+];
+
+/// A check run inside the tree rather than on its listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    /// Shared libraries the dynamic loader can't find
+    Libraries,
+    /// Unit files systemd-analyze rejects or warns about
+    Units,
+}
+
+impl Probe {
+    pub const ALL: [Probe; 2] = [Probe::Libraries, Probe::Units];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Probe::Libraries => "libs",
+            Probe::Units => "units",
+        }
+    }
+
+    pub fn parse_name(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|probe| probe.key() == s)
+    }
+}
+
+/// Run `probes` on the tree at `root`, which `reader` lists.
+pub fn run(
+    root: &Path,
+    reader: &CpioReader,
+    probes: &[Probe],
+) -> Result<Vec<CheckResult>, FsdbgError> {
+    if probes.is_empty() {
+        return Ok(Vec::new());
+    }
+    if Command::new(BWRAP).arg("--version").output().is_err() {
+        return Err(FsdbgError::tool_missing(
+            BWRAP,
+            "Install bubblewrap, or verify without --probe.",
+        ));
+    }
+    let mut results = Vec::new();
+    for probe in probes {
+        results.extend(match probe {
+            Probe::Libraries => libraries(root, reader)?,
+            Probe::Units => units(root, reader)?,
+        });
+    }
+    Ok(results)
+}
+
+/// Run `program` from the tree with `args` and `env`, the tree as the root.
+fn sandboxed(
+    root: &Path,
+    env: &[(&str, &str)],
+    program: &str,
+    args: &[&str],
+) -> Result<Output, FsdbgError> {
+    let mut command = Command::new(BWRAP);
+    command
+        .arg("--ro-bind")
+        .arg(root)
+        .arg("/")
+        .args(["--dev", "/dev", "--proc", "/proc"])
+        .args(["--tmpfs", "/tmp", "--tmpfs", "/run", "--chdir", "/"])
+        .args(["--unshare-all", "--die-with-parent", "--clearenv"])
+        .args(["--setenv", "PATH", "/usr/bin:/usr/sbin:/bin:/sbin"])
+        .args(["--setenv", "SYSTEMD_LOG_COLOR", "0"]);
+    // After --clearenv, which bwrap applies where it comes
+    for (name, value) in env {
+        command.args(["--setenv", name, value]);
+    }
+    command
+        .arg("--")
+        .arg(format!("/{}", program))
+        .args(args)
+        .output()
+        .map_err(|e| FsdbgError::external_tool_failed(BWRAP, e.to_string()))
+}
+
+fn libraries(root: &Path, reader: &CpioReader) -> Result<Vec<CheckResult>, FsdbgError> {
+    let mut binaries = Vec::new();
+    reader.visit_files(|path, content| {
+        if let Some(interp) = interpreters::interpreter(content) {
+            binaries.push((path.to_string(), interp));
+        }
+    })?;
+
+    let mut results = Vec::new();
+    let mut missing: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (binary, interp) in binaries {
+        // A loader that isn't there is the interpreter check's failure
+        let loader = reader
+            .resolve(&interp)
+            .filter(|target| reader.get(target).is_some_and(|e| e.is_file()));
+        let Some(loader) = loader else {
+            continue;
+        };
+        let output = sandboxed(
+            root,
+            &[("LD_TRACE_LOADED_OBJECTS", "1")],
+            &loader,
+            &[&format!("/{}", binary)],
+        )?;
+        let mut libs = not_found(&String::from_utf8_lossy(&output.stdout));
+        // Listed again for each library that needs it
+        libs.sort();
+        libs.dedup();
+        if libs.is_empty() && !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            results.push(CheckResult::fail(
+                &binary,
+                CheckCategory::Library,
+                format!(
+                    "Loader failed: {}",
+                    stderr.lines().next().unwrap_or("no output")
+                ),
+            ));
+        }
+        for lib in libs {
+            missing.entry(lib).or_default().push(binary.clone());
+        }
+    }
+    results.extend(missing.into_iter().map(|(lib, users)| {
+        CheckResult::fail(
+            &lib,
+            CheckCategory::Library,
+            format!(
+                "Not found by the loader, needed by {}",
+                interpreters::describe(&users)
+            ),
+        )
+        .with_hint("Install the package providing it, or add its directory to etc/ld.so.conf.d and rerun ldconfig on the tree")
+    }));
+    Ok(results)
+}
+
+/// Libraries the loader's trace reports as `libfoo.so.1 => not found`.
+fn not_found(list: &str) -> Vec<String> {
+    list.lines()
+        .filter_map(|line| line.trim().strip_suffix(" => not found"))
+        .map(str::to_string)
+        .collect()
+}
+
+fn units(root: &Path, reader: &CpioReader) -> Result<Vec<CheckResult>, FsdbgError> {
+    if !reader.exists(SYSTEMD_ANALYZE) {
+        return Err(FsdbgError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "The units probe runs the tree's {}, and the tree has none",
+                SYSTEMD_ANALYZE
+            ),
+        ));
+    }
+    // Templates can't be loaded without an instance; masked units are links
+    let units: Vec<&str> = reader
+        .entries()
+        .iter()
+        .filter(|e| e.is_file() && !e.path.contains("@."))
+        .map(|e| e.path.as_str())
+        .filter(|path| {
+            UNIT_DIRS.iter().any(|dir| {
+                path.strip_prefix(dir)
+                    .and_then(|rest| rest.strip_prefix('/'))
+                    .is_some_and(|name| !name.contains('/'))
+            }) && UNIT_SUFFIXES.iter().any(|suffix| path.ends_with(suffix))
+        })
+        .collect();
+    if units.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut args = vec!["verify".to_string(), "--man=no".to_string()];
+    args.extend(units.iter().map(|unit| format!("/{}", unit)));
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = sandboxed(root, &[], SYSTEMD_ANALYZE, &args)?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let (by_unit, other) = unit_messages(&stderr, &units);
+
+    let mut results: Vec<_> = by_unit
+        .into_iter()
+        .map(|(unit, messages)| {
+            CheckResult::fail(unit, CheckCategory::Unit, messages.join("; ")).with_hint(
+                "Reported by systemd-analyze verify in the tree; systemd logs the same at boot",
+            )
+        })
+        .collect();
+    if results.is_empty() && !output.status.success() {
+        results.push(CheckResult::fail(
+            "systemd-analyze verify",
+            CheckCategory::Unit,
+            other.first().copied().unwrap_or("Failed without output"),
+        ));
+    }
+    Ok(results)
+}
+
+/// systemd-analyze messages by the unit file they're about, and the rest.
+/// Messages name the unit by path (`/usr/lib/systemd/system/a.service:12:
+/// ...`) or by name (`a.service: Command ... is not executable`); a name is
+/// the first of `units` with it, as etc/ overrides usr/lib. The unit is
+/// dropped from its messages, the line number kept (`line 12: ...`).
+fn unit_messages<'a, 'u>(
+    stderr: &'a str,
+    units: &[&'u str],
+) -> (BTreeMap<&'u str, Vec<String>>, Vec<&'a str>) {
+    let mut by_unit: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut other = Vec::new();
+    for line in stderr.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let found = line.split_once(": ").and_then(|(head, message)| {
+            let head = head.trim_start_matches('/');
+            let (head, line) = match head.rsplit_once(':') {
+                Some((path, line)) if line.bytes().all(|b| b.is_ascii_digit()) => {
+                    (path, Some(line))
+                }
+                _ => (head, None),
+            };
+            let unit = units
+                .iter()
+                .copied()
+                .find(|unit| *unit == head || unit.rsplit('/').next() == Some(head))?;
+            let message = message.trim_end_matches('.');
+            Some(match line {
+                Some(line) => (unit, format!("line {}: {}", line, message)),
+                None => (unit, message.to_string()),
+            })
+        });
+        match found {
+            Some((unit, message)) => by_unit.entry(unit).or_default().push(message),
+            None => other.push(line),
+        }
+    }
+    (by_unit, other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_output() {
+        assert_eq!(Probe::parse_name("libs"), Some(Probe::Libraries));
+        assert_eq!(Probe::parse_name("ldd"), None);
+
+        let list = "\tlinux-vdso.so.1 (0x00007ffd)\n\
+                    \tlibpam.so.0 => not found\n\
+                    \tlibc.so.6 => /lib64/libc.so.6 (0x00007f)\n";
+        assert_eq!(not_found(list), ["libpam.so.0"]);
+
+        let units = [
+            "etc/systemd/system/sshd.service",
+            "usr/lib/systemd/system/sshd.service",
+            "usr/lib/systemd/system/getty.target",
+        ];
+        let stderr = "/usr/lib/systemd/system/getty.target:7: Unknown key name 'Wnats' in section 'Unit', ignoring.\n\
+                      sshd.service: Command /usr/sbin/sshd is not executable: No such file or directory\n\
+                      Failed to create /init.scope control group: Read-only file system\n";
+        let (by_unit, other) = unit_messages(stderr, &units);
+        assert_eq!(
+            by_unit.keys().copied().collect::<Vec<_>>(),
+            [
+                "etc/systemd/system/sshd.service",
+                "usr/lib/systemd/system/getty.target"
+            ]
+        );
+        assert_eq!(
+            by_unit["usr/lib/systemd/system/getty.target"],
+            ["line 7: Unknown key name 'Wnats' in section 'Unit', ignoring"]
+        );
+        assert_eq!(other.len(), 1);
+    }
+}
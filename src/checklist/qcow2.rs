@@ -6,10 +6,14 @@
 //! and configurations for a bootable, properly configured system.
 //!
 //! Unlike archive-based checklists, this works on a mounted filesystem path.
+//! Given the identifiers of the image's partitions ([`verify_with_partitions`]),
+//! it also checks that the `root=` of every boot entry and every fstab
+//! source name a partition that is actually in the image, and the right one.
 
-use super::{CheckCategory, CheckResult, VerificationReport};
+use super::{CheckCategory, CheckResult, Hint, VerificationReport};
+use crate::qcow2::{DiskPartition, FsKind, ATTR_LEGACY_BIOS_BOOTABLE};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 // =============================================================================
 // BOOT - Critical bootloader and kernel components
@@ -38,9 +42,6 @@ const FSTAB: &str = "etc/fstab";
 /// Hostname file
 const HOSTNAME: &str = "etc/hostname";
 
-/// Machine ID file (should be empty for first-boot regeneration)
-const MACHINE_ID: &str = "etc/machine-id";
-
 /// Shadow file (root should have empty password, not locked)
 const SHADOW: &str = "etc/shadow";
 
@@ -68,26 +69,169 @@ const REQUIRED_ENABLED_SERVICES: &[&str] =
 const MULTI_USER_WANTS: &str = "etc/systemd/system/multi-user.target.wants";
 
 // =============================================================================
-// SECURITY - Files that should NOT exist
+// DISK LAYOUT - ESP, swap and recovery partitions
 // =============================================================================
 
-/// SSH host keys that should NOT exist (regenerated on first boot)
-const SSH_HOST_KEYS: &[&str] = &[
-    "etc/ssh/ssh_host_rsa_key",
-    "etc/ssh/ssh_host_ecdsa_key",
-    "etc/ssh/ssh_host_ed25519_key",
+/// zram-generator configuration (the vendor one, and the admin override)
+const ZRAM_CONFS: &[&str] = &[
+    "usr/lib/systemd/zram-generator.conf",
+    "etc/systemd/zram-generator.conf",
 ];
 
+/// The generator that turns zram-generator.conf into zram swap devices
+const ZRAM_GENERATOR: &str = "usr/lib/systemd/system-generators/zram-generator";
+
+/// Where the ISO keeps its UKIs, and a recovery partition must too
+const RECOVERY_UKI_DIR: &str = distro_spec::shared::UKI_EFI_DIR;
+
+/// Names the ISO gives the UKI a recovery partition carries (`<distro>-emergency.efi`)
+const RECOVERY_UKI_NAMES: &[&str] = &["emergency", "recovery"];
+
 // =============================================================================
 // VERIFICATION
 // =============================================================================
 
-/// Verify a mounted qcow2 filesystem against the checklist.
-///
-/// # Arguments
-///
-/// * `mount_point` - Path where the qcow2 partitions are mounted
-pub fn verify(mount_point: &Path) -> VerificationReport {
+/// Where to fix failures, by category and path prefix.
+pub const HINTS: &[Hint] = &[
+    Hint::new(
+        CheckCategory::Binary,
+        "",
+        "Run bootctl install and copy the kernel/initramfs into the ESP when building the image",
+    ),
+    Hint::new(
+        CheckCategory::Directory,
+        "boot/",
+        "Write a boot loader entry into boot/loader/entries when building the image",
+    ),
+    Hint::new(
+        CheckCategory::EtcFile,
+        "",
+        "Write it when configuring the image",
+    ),
+    Hint::new(
+        CheckCategory::EtcFile,
+        "boot/",
+        "Write loader.conf/entries when running bootctl install",
+    ),
+    Hint::new(
+        CheckCategory::EtcFile,
+        "etc/fstab",
+        "Generate it with recfstab against the mounted partitions",
+    ),
+    Hint::new(
+        CheckCategory::EtcFile,
+        "etc/shadow",
+        "Clear root's password field (root::...) for first login",
+    ),
+    Hint::new(
+        CheckCategory::Directory,
+        "etc/systemd/",
+        "Enable the required services with systemctl --root",
+    ),
+    Hint::new(
+        CheckCategory::Symlink,
+        "",
+        "Enable the service with systemctl --root",
+    ),
+    Hint::new(
+        CheckCategory::Other,
+        "etc/ssh/",
+        "Delete host keys after building; sshd-keygen regenerates them on first boot",
+    ),
+];
+
+/// Identifiers of a partition in the image, as `blkid` reports them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Partition {
+    /// Where the image mounts it (`/`, `/boot`)
+    pub mount: String,
+    pub uuid: Option<String>,
+    pub partuuid: Option<String>,
+    pub label: Option<String>,
+    pub partlabel: Option<String>,
+}
+
+impl Partition {
+    /// Parse `blkid -o export` output for the partition mounted at `mount`.
+    pub fn from_blkid(mount: &str, output: &str) -> Self {
+        let mut partition = Partition {
+            mount: mount.to_string(),
+            ..Default::default()
+        };
+        for line in output.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "UUID" => partition.uuid = value,
+                "PARTUUID" => partition.partuuid = value,
+                "LABEL" => partition.label = value,
+                "PARTLABEL" => partition.partlabel = value,
+                _ => {}
+            }
+        }
+        partition
+    }
+
+    /// Whether this partition carries identifier `kind` with `value`.
+    fn has(&self, kind: IdKind, value: &str) -> bool {
+        let id = match kind {
+            IdKind::Uuid => &self.uuid,
+            IdKind::PartUuid => &self.partuuid,
+            IdKind::Label => &self.label,
+            IdKind::PartLabel => &self.partlabel,
+        };
+        id.as_deref().is_some_and(|id| match kind {
+            IdKind::Uuid | IdKind::PartUuid => id.eq_ignore_ascii_case(value),
+            IdKind::Label | IdKind::PartLabel => id == value,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdKind {
+    Uuid,
+    PartUuid,
+    Label,
+    PartLabel,
+}
+
+/// Split `UUID=...` or `/dev/disk/by-uuid/...` into kind and value. None
+/// for plain device names, which can't be checked against the image.
+fn parse_source(source: &str) -> Option<(IdKind, &str)> {
+    const KINDS: &[(IdKind, &str, &str)] = &[
+        (IdKind::Uuid, "UUID=", "/dev/disk/by-uuid/"),
+        (IdKind::PartUuid, "PARTUUID=", "/dev/disk/by-partuuid/"),
+        (IdKind::Label, "LABEL=", "/dev/disk/by-label/"),
+        (IdKind::PartLabel, "PARTLABEL=", "/dev/disk/by-partlabel/"),
+    ];
+    let source = source.trim_matches('"');
+    KINDS.iter().find_map(|&(kind, tag, link)| {
+        source
+            .strip_prefix(tag)
+            .or_else(|| source.strip_prefix(link))
+            .map(|value| (kind, value.trim_matches('"')))
+    })
+}
+
+/// Why `source` (meant to be mounted at `mount`) doesn't name the right
+/// partition, if it doesn't.
+fn identifier_problem(partitions: &[Partition], source: &str, mount: &str) -> Option<String> {
+    let (kind, value) = parse_source(source)?;
+    match partitions.iter().find(|p| p.has(kind, value)) {
+        None => Some(format!("{} matches no partition in the image", source)),
+        Some(p) if (mount == "/" || mount == "/boot") && p.mount != mount => Some(format!(
+            "{} is the {} partition, not {}",
+            source, p.mount, mount
+        )),
+        Some(_) => None,
+    }
+}
+
+/// Verify a mounted qcow2 filesystem, and check boot entries and fstab
+/// against the identifiers of its partitions (skipped if none are given).
+pub fn verify_with_partitions(mount_point: &Path, partitions: &[Partition]) -> VerificationReport {
     let mut report = VerificationReport::new("Qcow2 Image");
 
     check_boot(&mut report, mount_point);
@@ -96,8 +240,67 @@ pub fn verify(mount_point: &Path) -> VerificationReport {
     check_system_config(&mut report, mount_point);
     check_user_database(&mut report, mount_point);
     check_enabled_services(&mut report, mount_point);
-    check_security(&mut report, mount_point);
+    for result in super::first_boot::check_dir(mount_point) {
+        report.add(result);
+    }
+    for result in super::service_configs::check_dir(mount_point) {
+        report.add(result);
+    }
+    if !partitions.is_empty() {
+        check_identifiers(&mut report, mount_point, partitions);
+    }
+
+    report.apply_hints(HINTS);
+    report
+}
+
+/// What [`verify_with_partitions`] checks, for reporting what a run that
+/// couldn't mount the image left unchecked.
+pub const CHECKS: &[&str] = &[
+    "boot loader (systemd-boot, loader.conf, entries)",
+    "kernel and initramfs",
+    "filesystem layout",
+    "system configuration (fstab, hostname, root password)",
+    "user database",
+    "enabled services",
+    "first-boot state (machine-id, SSH host keys)",
+    "service configuration (sshd, chrony, NetworkManager)",
+    "partition identifiers (blkid vs fstab and root=)",
+    "disk layout (ESP, swap, recovery partition)",
+];
+
+/// Report for an image that wasn't mounted: `reason` is the failure, and
+/// every check in [`CHECKS`] is skipped because of it.
+pub fn unmounted(reason: impl Into<String>) -> VerificationReport {
+    const MOUNT: &str = "qcow2 mount";
+
+    let mut report = VerificationReport::new("Qcow2 Image");
+    report.add(CheckResult::fail(MOUNT, CheckCategory::Other, reason));
+    for check in CHECKS {
+        let mut result = CheckResult::fail(*check, CheckCategory::Other, "Not checked");
+        result.skipped_by = Some(MOUNT.to_string());
+        report.add(result);
+    }
+    report
+}
 
+/// The whole disk, beyond the mounted root and boot partitions
+#[derive(Debug, Clone, Default)]
+pub struct Disk {
+    /// Every partition in the GPT
+    pub partitions: Vec<DiskPartition>,
+    /// Where the recovery partition's files can be read, if it has one
+    pub recovery: Option<PathBuf>,
+}
+
+/// [`verify_with_partitions`], plus the disk layout checks.
+pub fn verify_image(
+    mount_point: &Path,
+    partitions: &[Partition],
+    disk: &Disk,
+) -> VerificationReport {
+    let mut report = verify_with_partitions(mount_point, partitions);
+    check_disk(&mut report, mount_point, disk);
     report
 }
 
@@ -408,6 +611,10 @@ fn check_filesystem(report: &mut VerificationReport, root: &Path) {
                         parts.len() >= 2 && parts[1] == "/boot"
                     });
 
+                    let modules = super::fstab::KernelModules::from_dir(root);
+                    for result in super::fstab::results(&content, &modules) {
+                        report.add(result);
+                    }
                     if has_root && has_boot {
                         report.add(CheckResult::pass(
                             format!("{} ({} entries, has / and /boot)", FSTAB, entries.len()),
@@ -471,49 +678,6 @@ fn check_system_config(report: &mut VerificationReport, root: &Path) {
         ));
     }
 
-    // Machine ID - should be empty for first-boot regeneration
-    let machine_id_path = root.join(MACHINE_ID);
-    if machine_id_path.exists() {
-        match fs::read_to_string(&machine_id_path) {
-            Ok(content) if content.trim().is_empty() => {
-                report.add(CheckResult::pass(
-                    format!("{} (empty for first-boot regeneration)", MACHINE_ID),
-                    CheckCategory::EtcFile,
-                ));
-            }
-            Ok(content) if content.trim() == "uninitialized" => {
-                report.add(CheckResult::pass(
-                    format!("{} (uninitialized for first-boot regeneration)", MACHINE_ID),
-                    CheckCategory::EtcFile,
-                ));
-            }
-            Ok(content) => {
-                // Non-empty machine ID is a warning - image may have been booted
-                report.add(CheckResult::fail(
-                    MACHINE_ID,
-                    CheckCategory::EtcFile,
-                    format!(
-                        "Contains value '{}' - should be empty for unique ID generation",
-                        content.trim()
-                    ),
-                ));
-            }
-            Err(e) => {
-                report.add(CheckResult::fail(
-                    MACHINE_ID,
-                    CheckCategory::EtcFile,
-                    format_permission_error("Cannot read", &e),
-                ));
-            }
-        }
-    } else {
-        // Missing is OK - systemd will create it on first boot
-        report.add(CheckResult::pass(
-            format!("{} (missing, will be created on first boot)", MACHINE_ID),
-            CheckCategory::EtcFile,
-        ));
-    }
-
     // Shadow file - check root password is empty (not locked)
     check_shadow_file(report, root);
 
@@ -789,22 +953,326 @@ fn check_enabled_services(report: &mut VerificationReport, root: &Path) {
     }
 }
 
-/// Check security requirements
-fn check_security(report: &mut VerificationReport, root: &Path) {
-    // SSH host keys should NOT exist (regenerated on first boot)
-    for key in SSH_HOST_KEYS {
-        let key_path = root.join(key);
-        if key_path.exists() {
-            report.add(CheckResult::fail(
-                *key,
+/// Check that boot entries' `root=` and fstab sources name the partitions
+/// the image actually has. A stale UUID here drops the first boot into the
+/// emergency shell.
+fn check_identifiers(report: &mut VerificationReport, root: &Path, partitions: &[Partition]) {
+    const HINT: &str = "Regenerate fstab (recfstab) and the loader entries against the image's partitions, after the last mkfs";
+
+    let mut entries: Vec<_> = fs::read_dir(root.join(LOADER_ENTRIES_DIR))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "conf"))
+        .collect();
+    entries.sort();
+    for path in entries {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue; // check_boot already reported it
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let root_param = content
+            .lines()
+            .filter(|l| l.starts_with("options"))
+            .flat_map(|l| l.split_whitespace())
+            .find_map(|word| word.strip_prefix("root="));
+        let Some(source) = root_param else {
+            continue;
+        };
+        let item = format!("boot entry: {} root={}", name, source);
+        report.add(match identifier_problem(partitions, source, "/") {
+            None => CheckResult::pass(item, CheckCategory::EtcFile),
+            Some(problem) => {
+                CheckResult::fail(item, CheckCategory::EtcFile, problem).with_hint(HINT)
+            }
+        });
+    }
+
+    let Ok(fstab) = fs::read_to_string(root.join(FSTAB)) else {
+        return;
+    };
+    for line in fstab.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (source, mount) = match fields[..] {
+            [source, mount, ..] => (source, mount),
+            _ => continue,
+        };
+        // Missing nofail mounts don't stop the boot
+        if fields
+            .get(3)
+            .is_some_and(|o| o.split(',').any(|o| o == "nofail"))
+        {
+            continue;
+        }
+        if parse_source(source).is_none() {
+            continue;
+        }
+        let item = format!("{}: {} {}", FSTAB, source, mount);
+        report.add(match identifier_problem(partitions, source, mount) {
+            None => CheckResult::pass(item, CheckCategory::EtcFile),
+            Some(problem) => {
+                CheckResult::fail(item, CheckCategory::EtcFile, problem).with_hint(HINT)
+            }
+        });
+    }
+}
+
+/// Check the ESP, swap (partitions, fstab, zram) and recovery partition.
+fn check_disk(report: &mut VerificationReport, root: &Path, disk: &Disk) {
+    // The ESP is partition 1, mounted at /boot with systemd-boot on it
+    let esps: Vec<_> = disk.partitions.iter().filter(|p| p.is_esp()).collect();
+    if esps.is_empty() {
+        report.add(
+            CheckResult::fail(
+                "disk: EFI system partition",
                 CheckCategory::Other,
-                "Should not exist (unique keys should be generated on first boot)",
-            ));
+                "No partition has the ESP type GUID; firmware won't look for a boot loader",
+            )
+            .with_hint("Set partition 1's type to EF00 (sgdisk -t 1:ef00)"),
+        );
+    }
+    for esp in esps {
+        let item = format!("disk: EFI system partition (partition {})", esp.gpt.number);
+        let mut problems = Vec::new();
+        if esp.gpt.number != 1 {
+            problems.push("systemd-boot is installed on partition 1 (/boot), not here".to_string());
+        }
+        match esp.kind() {
+            Some(FsKind::Fat32) => {}
+            Some(kind) => problems.push(format!("{}, firmware only reliably reads FAT32", kind)),
+            None => problems.push("No FAT32 filesystem".to_string()),
+        }
+        if esp.gpt.attributes & ATTR_LEGACY_BIOS_BOOTABLE != 0 {
+            problems.push("Marked legacy BIOS bootable".to_string());
+        }
+        report.add(if problems.is_empty() {
+            CheckResult::pass(item, CheckCategory::Other)
         } else {
-            report.add(CheckResult::pass(
-                format!("{} (absent, will regenerate)", key),
+            CheckResult::fail(item, CheckCategory::Other, problems.join("; "))
+                .with_hint("Format the ESP with mkfs.vfat -F 32 and clear the attributes (sgdisk -A 1:clear:2)")
+        });
+    }
+
+    // Swap partitions, and what fstab activates
+    for swap in disk.partitions.iter().filter(|p| p.is_swap()) {
+        let item = format!("disk: swap partition {}", swap.gpt.number);
+        report.add(match swap.kind() {
+            Some(FsKind::Swap) => CheckResult::pass(item, CheckCategory::Other),
+            _ => CheckResult::fail(
+                item,
                 CheckCategory::Other,
-            ));
+                "Swap partition type, but no swap signature",
+            )
+            .with_hint("Run mkswap on it when building the image"),
+        });
+    }
+    let ids: Vec<_> = disk
+        .partitions
+        .iter()
+        .map(|p| crate::qcow2::partition_ids(&p.gpt, p.filesystem.as_ref(), ""))
+        .collect();
+    let zram = ZRAM_CONFS.iter().any(|conf| root.join(conf).exists());
+    let fstab = fs::read_to_string(root.join(FSTAB)).unwrap_or_default();
+    for line in fstab.lines().map(str::trim) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [source, _, "swap", ..] = fields[..] else {
+            continue;
+        };
+        if line.starts_with('#') {
+            continue;
         }
+        let item = format!("{}: {} swap", FSTAB, source);
+        let problem = if source.starts_with("/dev/zram") {
+            Some(
+                "zram swap is set up by zram-generator (zram-generator.conf), not fstab"
+                    .to_string(),
+            )
+        } else if let Some((kind, value)) = parse_source(source) {
+            match ids.iter().position(|p| p.has(kind, value)) {
+                None => Some(format!("{} matches no partition in the image", source)),
+                Some(i) if disk.partitions[i].kind() != Some(FsKind::Swap) => Some(format!(
+                    "{} is partition {} ({}), not swap",
+                    source,
+                    disk.partitions[i].gpt.number,
+                    disk.partitions[i]
+                        .kind()
+                        .map_or("unformatted".to_string(), |k| k.to_string())
+                )),
+                Some(_) => None,
+            }
+        } else if source.starts_with('/') && !source.starts_with("/dev/") {
+            (!root.join(source.trim_start_matches('/')).exists())
+                .then(|| format!("Swap file {} is missing", source))
+        } else {
+            continue; // Kernel device names are the fstab checks' business
+        };
+        report.add(match problem {
+            None => CheckResult::pass(item, CheckCategory::EtcFile),
+            Some(problem) => CheckResult::fail(item, CheckCategory::EtcFile, problem),
+        });
+    }
+    if zram {
+        report.add(if root.join(ZRAM_GENERATOR).exists() {
+            CheckResult::pass("zram-generator.conf", CheckCategory::Config)
+        } else {
+            CheckResult::fail(
+                "zram-generator.conf",
+                CheckCategory::Config,
+                format!("zram swap is configured, but {} is missing", ZRAM_GENERATOR),
+            )
+            .with_hint("Install zram-generator into the image")
+        });
+    }
+
+    // A recovery partition must carry the recovery UKI the ISO ships
+    for recovery in disk.partitions.iter().filter(|p| p.is_recovery()) {
+        let item = format!("disk: recovery partition {}", recovery.gpt.number);
+        let Some(ref dir) = disk.recovery else {
+            report.add(CheckResult::fail(
+                item,
+                CheckCategory::Other,
+                format!(
+                    "Could not read it ({})",
+                    recovery
+                        .kind()
+                        .map_or("no known filesystem".to_string(), |k| k.to_string())
+                ),
+            ));
+            continue;
+        };
+        let ukis: Vec<_> = fs::read_dir(dir.join(RECOVERY_UKI_DIR))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| {
+                name.to_ascii_lowercase().ends_with(".efi")
+                    && RECOVERY_UKI_NAMES.iter().any(|n| name.contains(n))
+            })
+            .filter(|name| {
+                fs::read(dir.join(RECOVERY_UKI_DIR).join(name)).is_ok_and(|data| {
+                    crate::uki::section(&data, ".linux").is_ok_and(|s| s.is_some())
+                })
+            })
+            .collect();
+        report.add(match ukis.first() {
+            Some(uki) => CheckResult::pass(
+                format!("{} ({}/{})", item, RECOVERY_UKI_DIR, uki),
+                CheckCategory::Other,
+            ),
+            None => CheckResult::fail(
+                item,
+                CheckCategory::Other,
+                format!(
+                    "No recovery UKI ({}/*-emergency.efi with a .linux section, as on the ISO)",
+                    RECOVERY_UKI_DIR
+                ),
+            )
+            .with_hint("Copy the ISO's emergency UKI onto the recovery partition"),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unmounted() {
+        let report = unmounted("needs root");
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.skipped(), CHECKS.len());
+    }
+
+    #[test]
+    fn test_check_disk() {
+        use crate::qcow2::{Filesystem, GptPartition};
+        let partition = |number, type_guid: &str, name: &str, kind: Option<FsKind>| DiskPartition {
+            gpt: GptPartition {
+                number,
+                type_guid: type_guid.to_string(),
+                partuuid: format!("0000000{}-0000-0000-0000-000000000000", number),
+                name: name.to_string(),
+                offset: 0,
+                size: 0,
+                attributes: 0,
+            },
+            filesystem: kind.map(|kind| Filesystem {
+                kind,
+                uuid: format!("uuid-{}", number),
+                label: None,
+            }),
+        };
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("etc")).unwrap();
+        fs::write(
+            root.path().join(FSTAB),
+            "UUID=uuid-2 none swap defaults 0 0\nUUID=uuid-3 none swap defaults 0 0\n/dev/zram0 none swap defaults 0 0\n",
+        )
+        .unwrap();
+        let recovery = tempfile::tempdir().unwrap();
+        let disk = Disk {
+            partitions: vec![
+                partition(1, crate::qcow2::ESP_TYPE, "EFI", Some(FsKind::Fat16)),
+                partition(2, crate::qcow2::SWAP_TYPE, "swap", Some(FsKind::Swap)),
+                partition(
+                    3,
+                    "0fc63daf-8483-4772-8e79-3d69d8477de4",
+                    "root",
+                    Some(FsKind::Ext),
+                ),
+                partition(4, "0fc63daf-8483-4772-8e79-3d69d8477de4", "Recovery", None),
+            ],
+            recovery: Some(recovery.path().to_path_buf()),
+        };
+
+        let mut report = VerificationReport::new("test");
+        check_disk(&mut report, root.path(), &disk);
+        let failed: Vec<_> = report
+            .results
+            .iter()
+            .filter(|r| !r.passed)
+            .map(|r| r.item.as_str())
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                "disk: EFI system partition (partition 1)",
+                "etc/fstab: UUID=uuid-3 swap",
+                "etc/fstab: /dev/zram0 swap",
+                "disk: recovery partition 4",
+            ]
+        );
+        assert_eq!(report.passed(), 2);
+    }
+
+    #[test]
+    fn test_identifier_problem() {
+        let partitions = [
+            Partition::from_blkid(
+                "/boot",
+                "DEVNAME=/dev/nbd0p1\nUUID=ABCD-1234\nPARTUUID=0a0a\n",
+            ),
+            Partition::from_blkid("/", "UUID=5f0c-77\nTYPE=ext4\nPARTLABEL=root\n"),
+        ];
+        assert_eq!(partitions[1].partlabel.as_deref(), Some("root"));
+        assert_eq!(identifier_problem(&partitions, "UUID=5F0C-77", "/"), None);
+        assert_eq!(
+            identifier_problem(&partitions, "/dev/disk/by-partlabel/root", "/"),
+            None
+        );
+        assert_eq!(
+            identifier_problem(&partitions, "UUID=dead-beef", "/").as_deref(),
+            Some("UUID=dead-beef matches no partition in the image")
+        );
+        assert_eq!(
+            identifier_problem(&partitions, "PARTUUID=0a0a", "/").as_deref(),
+            Some("PARTUUID=0a0a is the /boot partition, not /")
+        );
+        assert_eq!(identifier_problem(&partitions, "/dev/vda2", "/"), None);
     }
 }
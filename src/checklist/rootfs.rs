@@ -31,54 +31,23 @@
 //! If busybox appears in the live shell, THE BUILD IS BROKEN. Do not ship it.
 //! Do not "fix" it by adding more busybox. Fix it by ensuring real packages exist.
 
-use super::{CheckCategory, CheckResult, VerificationReport};
-use crate::cpio::CpioReader;
-use std::collections::HashSet;
-
-// Import from SINGLE SOURCE OF TRUTH
-use distro_spec::shared::{
-    all_systemd_units,
-    BIN_UTILS,
-    BLUETOOTH_SBIN,
-    CRITICAL_LIBS,
-
-    ETC_FILES,
-    FHS_DIRS,
-    FHS_SYMLINKS,
-    // LevitateOS installation tools (recstrap, recfstab, recchroot, recipe, levitate-docs)
-    LEVITATE_TOOLS,
-    NM_BIN,
-    NM_SBIN,
-    PIPEWIRE_SBIN,
-    POLKIT_SBIN,
-    SBIN_UTILS,
-    SSH_BIN,
-    SSH_SBIN,
-    SYSTEMD_BINARIES,
-    UDEV_HELPERS,
-    UDISKS_SBIN,
-    UPOWER_SBIN,
-    WPA_SBIN,
-};
-
-// Auth components from dedicated auth subsystem
-use distro_spec::shared::auth::{
-    AUTH_BIN, AUTH_SBIN, PAM_CONFIGS, PAM_MODULES, SECURITY_FILES, SHADOW_SBIN,
-};
-
-// Re-export for backwards compatibility with any external consumers
-pub use distro_spec::shared::{
-    CRITICAL_LIBS as LIBS, FHS_DIRS as DIRS, FHS_SYMLINKS as SYMLINKS, SYSTEM_GROUPS as GROUPS,
-    SYSTEM_USERS as USERS,
+use super::patterns::GlobCheck;
+use super::{
+    alternatives, check_empty_files, console, controlled, devices, dns, dtb, first_boot, fstab,
+    has_path, homes, service_configs, sysctl, tmpfiles, udev, CheckCategory, CheckResult, Hint,
+    Prerequisite, Scope, VerificationReport,
 };
+use crate::arch::Arch;
+use crate::cpio::CpioReader;
+use crate::fixit::Fix;
+use crate::spec::Spec;
+use std::collections::{BTreeMap, HashSet};
 
-// =============================================================================
-// COMBINED LISTS (for verification)
-// =============================================================================
+// Component lists come from the distro-spec snapshot the checklist is given
+// (crate::spec)
 
-/// Systemd binaries that should exist in /usr/lib/systemd/.
-/// Note: "systemd" itself is added during verification.
-pub const SYSTEMD_BINS: &[&str] = SYSTEMD_BINARIES;
+/// Where usr/sbin/init points: systemd, relative to usr/sbin
+const INIT_TARGET: &str = "../lib/systemd/systemd";
 
 // =============================================================================
 // VERIFICATION
@@ -93,8 +62,187 @@ const FORBIDDEN_BUSYBOX_PATHS: &[&str] = &[
     "sbin/busybox",
 ];
 
-/// Verify a CPIO/EROFS archive against the rootfs checklist.
-pub fn verify(reader: &CpioReader) -> VerificationReport {
+/// Where to fix failures, by category and path prefix.
+pub const HINTS: &[Hint] = &[
+    Hint::new(CheckCategory::Forbidden, "", "Remove busybox from the rootfs build and install the real coreutils/procps-ng/util-linux packages"),
+    Hint::new(CheckCategory::Directory, "", "Add it to FHS_DIRS in distro-spec/src/shared/components.rs"),
+    Hint::new(CheckCategory::Symlink, "", "Add it to FHS_SYMLINKS in distro-spec/src/shared/components.rs"),
+    Hint::new(CheckCategory::Binary, "", "Add it to BIN_UTILS/SBIN_UTILS (or the subsystem list) in distro-spec/src/shared/components.rs"),
+    Hint::new(CheckCategory::Binary, "usr/lib/systemd/", "Add it to SYSTEMD_BINARIES in distro-spec/src/shared/components.rs"),
+    Hint::new(CheckCategory::Binary, "usr/lib/udev/", "Add it to UDEV_HELPERS in distro-spec/src/shared/components.rs"),
+    Hint::new(CheckCategory::Unit, "", "Add it to the systemd unit lists in distro-spec/src/shared/components.rs"),
+    Hint::new(CheckCategory::EtcFile, "", "Add it to ETC_FILES in distro-spec/src/shared/components.rs"),
+    Hint::new(CheckCategory::EtcFile, "etc/pam.d/", "Add it to PAM_CONFIGS in distro-spec/src/shared/auth.rs"),
+    Hint::new(CheckCategory::EtcFile, "etc/security/", "Add it to SECURITY_FILES in distro-spec/src/shared/auth.rs"),
+    Hint::new(CheckCategory::Library, "", "Add it to CRITICAL_LIBS in distro-spec/src/shared/components.rs"),
+    Hint::new(CheckCategory::Library, "usr/lib64/security/", "Add it to PAM_MODULES in distro-spec/src/shared/auth.rs"),
+    Hint::new(CheckCategory::Library, "usr/lib/security/", "Add it to PAM_MODULES in distro-spec/src/shared/auth.rs"),
+    Hint::new(CheckCategory::Library, "usr/lib64/libnss_", "Install the package providing this NSS module, or drop the service from nsswitch.conf"),
+    Hint::new(CheckCategory::Library, "usr/lib/libnss_", "Install the package providing this NSS module, or drop the service from nsswitch.conf"),
+    Hint::new(CheckCategory::KernelModule, "", "Install the kernel modules into usr/lib/modules when building the rootfs"),
+    Hint::new(CheckCategory::UdevRule, "", "Install systemd-udev's rules.d when building the rootfs"),
+    Hint::new(CheckCategory::License, "", "Copy the package's usr/share/licenses directory when extracting it"),
+];
+
+/// Directories whose absence explains whole groups of failures.
+pub const PREREQUISITES: &[Prerequisite] = &[
+    Prerequisite::new(
+        "usr/lib/systemd",
+        &[
+            (CheckCategory::Binary, "usr/lib/systemd/"),
+            (CheckCategory::Unit, ""),
+            (CheckCategory::Symlink, "usr/sbin/init"),
+        ],
+    ),
+    Prerequisite::new(
+        "usr/lib/udev",
+        &[
+            (CheckCategory::Binary, "usr/lib/udev/"),
+            (CheckCategory::UdevRule, ""),
+        ],
+    ),
+    Prerequisite::new("usr/bin", &[(CheckCategory::Binary, "usr/bin/")]),
+    Prerequisite::new("usr/sbin", &[(CheckCategory::Binary, "usr/sbin/")]),
+    Prerequisite::new(
+        "usr/lib64/security",
+        &[(CheckCategory::Library, "usr/lib64/security/")],
+    ),
+    Prerequisite::new("etc/pam.d", &[(CheckCategory::EtcFile, "etc/pam.d/")]),
+    Prerequisite::new("usr/share/licenses", &[(CheckCategory::License, "")]),
+];
+
+/// Paths referenced by the rootfs checklist.
+pub fn scope(spec: &Spec) -> Scope {
+    let mut scope = Scope::new();
+    scope.extend(FORBIDDEN_BUSYBOX_PATHS, CheckCategory::Forbidden);
+    scope.extend(
+        excluded_paths(spec).into_iter().map(|(path, _)| path),
+        CheckCategory::Forbidden,
+    );
+    scope.add_prefix("dev", CheckCategory::Forbidden);
+    scope.extend(
+        spec.bin_utils
+            .iter()
+            .chain(&spec.auth_bin)
+            .chain(&spec.ssh_bin)
+            .chain(&spec.nm_bin)
+            .chain(&spec.levitate_tools)
+            .map(String::as_str)
+            .chain(["bash", "openssl"])
+            .map(|b| format!("usr/bin/{}", b)),
+        CheckCategory::Binary,
+    );
+    scope.extend(
+        spec.sbin_utils
+            .iter()
+            .chain(&spec.auth_sbin)
+            .chain(&spec.shadow_sbin)
+            .chain(&spec.nm_sbin)
+            .chain(&spec.wpa_sbin)
+            .chain(&spec.ssh_sbin)
+            .chain(&spec.bluetooth_sbin)
+            .chain(&spec.pipewire_sbin)
+            .chain(&spec.polkit_sbin)
+            .chain(&spec.udisks_sbin)
+            .chain(&spec.upower_sbin)
+            .map(|b| format!("usr/sbin/{}", b)),
+        CheckCategory::Binary,
+    );
+    scope.extend(
+        spec.systemd_binaries
+            .iter()
+            .map(String::as_str)
+            .chain(["systemd"])
+            .map(|b| format!("usr/lib/systemd/{}", b)),
+        CheckCategory::Binary,
+    );
+    scope.extend(
+        spec.udev_helpers
+            .iter()
+            .map(|h| format!("usr/lib/udev/{}", h)),
+        CheckCategory::Binary,
+    );
+    scope.add_prefix("usr/lib/systemd/system", CheckCategory::Unit);
+    scope.add_prefix("usr/lib/systemd/user", CheckCategory::Unit);
+    scope.extend(
+        spec.fhs_symlinks.iter().map(|(link, _)| link.as_str()),
+        CheckCategory::Symlink,
+    );
+    scope.add("usr/sbin/init", CheckCategory::Symlink);
+    scope.extend(
+        spec.etc_files
+            .iter()
+            .chain(&spec.pam_configs)
+            .chain(&spec.security_files),
+        CheckCategory::EtcFile,
+    );
+    scope.add(NSSWITCH_CONF, CheckCategory::EtcFile);
+    scope.add_prefix("usr/lib/udev/rules.d", CheckCategory::UdevRule);
+    scope.add_prefix("usr/lib/sysctl.d", CheckCategory::Config);
+    scope.add_prefix("usr/lib/tmpfiles.d", CheckCategory::Config);
+    scope.add(fstab::FSTAB, CheckCategory::Config);
+    scope.extend(
+        [
+            service_configs::CHRONY_CONF,
+            service_configs::SSHD_CONFIG,
+            service_configs::NM_CONF,
+        ],
+        CheckCategory::Config,
+    );
+    scope.add_prefix(service_configs::SSHD_CONFIG_D, CheckCategory::Config);
+    scope.add(first_boot::MACHINE_ID, CheckCategory::EtcFile);
+    scope.add(homes::PASSWD, CheckCategory::EtcFile);
+    scope.extend(console::KBD_TOOLS.iter().copied(), CheckCategory::Binary);
+    scope.add_prefix(console::KBD_DATA, CheckCategory::Directory);
+    scope.add(console::VCONSOLE_CONF, CheckCategory::Config);
+    scope.add(dns::RESOLV_CONF, CheckCategory::Config);
+    scope.extend(
+        spec.alternatives.iter().map(|(link, _)| link.as_str()),
+        CheckCategory::Symlink,
+    );
+    scope.add_prefix("etc/alternatives", CheckCategory::Symlink);
+    scope.extend(
+        dns::RESOLVED_PATHS.iter().copied(),
+        CheckCategory::Forbidden,
+    );
+    for arch in Arch::ALL {
+        let [dns, resolve] = dns::nss_modules(arch);
+        scope.add(dns, CheckCategory::Library);
+        scope.add(resolve, CheckCategory::Forbidden);
+    }
+    scope.add_prefix(homes::SKEL, CheckCategory::EtcFile);
+    scope.add("root", CheckCategory::Directory);
+    scope.add_prefix("home", CheckCategory::Directory);
+    scope.add(first_boot::RANDOM_SEED, CheckCategory::Other);
+    scope.extend(
+        first_boot::SSH_HOST_KEYS.iter().copied(),
+        CheckCategory::Other,
+    );
+    scope.extend(&spec.fhs_dirs, CheckCategory::Directory);
+    for arch in Arch::ALL {
+        scope.extend(
+            spec.pam_modules
+                .iter()
+                .map(|m| format!("{}/security/{}", arch.lib_dir(), m)),
+            CheckCategory::Library,
+        );
+        scope.extend(
+            spec.critical_libs.iter().map(|lib| arch.path(lib)),
+            CheckCategory::Library,
+        );
+    }
+    scope.add_prefix("usr/lib/modules", CheckCategory::KernelModule);
+    scope.add_prefix(dtb::BOOT_DIR, CheckCategory::Other);
+    scope.add_prefix("usr/share/licenses", CheckCategory::License);
+    controlled::extend_scope(
+        &controlled::builtin(super::ChecklistType::Rootfs, spec),
+        &mut scope,
+    );
+    scope
+}
+
+/// Verify a CPIO/EROFS archive built for `arch` against the rootfs checklist.
+pub fn verify_for(reader: &CpioReader, arch: Arch, spec: &Spec) -> VerificationReport {
     let mut report = VerificationReport::new("Rootfs");
 
     // =========================================================================
@@ -147,50 +295,73 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
         }
     }
 
+    // =========================================================================
+    // 0b. FORBIDDEN CHECK: components the build filters out
+    // =========================================================================
+    for (path, category) in excluded_paths(spec) {
+        if reader.get(&path).is_some() {
+            report.add(
+                CheckResult::fail(
+                    &path,
+                    CheckCategory::Forbidden,
+                    format!("Excluded by the build's filters, but shipped ({})", category),
+                )
+                .with_hint("Deny-listed in the spec (excluded_*); fix the package filter in the rootfs build"),
+            );
+        } else {
+            report.add(CheckResult::pass(
+                format!("{} (correctly absent)", path),
+                CheckCategory::Forbidden,
+            ));
+        }
+    }
+
     // =========================================================================
     // 1. Check directory structure
     // =========================================================================
-    for dir in FHS_DIRS {
+    for dir in spec.fhs_dirs.iter().filter(|d| arch.has(d)) {
         if reader.exists(dir) {
-            report.add(CheckResult::pass(*dir, CheckCategory::Directory));
+            report.add(CheckResult::pass(dir, CheckCategory::Directory));
         } else {
-            report.add(CheckResult::fail(*dir, CheckCategory::Directory, "Missing"));
+            report.add(CheckResult::fail(dir, CheckCategory::Directory, "Missing"));
         }
     }
 
     // =========================================================================
     // 2. Check merged-usr symlinks
     // =========================================================================
-    for (link, target) in FHS_SYMLINKS {
+    for (link, target) in spec.fhs_symlinks.iter().filter(|(l, _)| arch.has(l)) {
         if let Some(entry) = reader.get(link) {
             if entry.is_symlink() {
                 if let Some(ref actual_target) = entry.link_target {
-                    if actual_target == *target {
+                    if actual_target == target {
                         report.add(CheckResult::pass(
                             format!("{} -> {}", link, target),
                             CheckCategory::Symlink,
                         ));
                     } else {
-                        report.add(CheckResult::fail(
-                            format!("{} -> {}", link, target),
-                            CheckCategory::Symlink,
-                            format!("Points to '{}' instead", actual_target),
-                        ));
+                        report.add(
+                            CheckResult::fail(
+                                format!("{} -> {}", link, target),
+                                CheckCategory::Symlink,
+                                format!("Points to '{}' instead", actual_target),
+                            )
+                            .with_fix(Fix::symlink(link, target)),
+                        );
                     }
                 }
             } else {
                 report.add(CheckResult::fail(
-                    *link,
+                    link,
                     CheckCategory::Symlink,
                     "Exists but is not a symlink (merged-usr broken)",
                 ));
             }
         } else {
-            report.add(CheckResult::fail(
-                *link,
-                CheckCategory::Symlink,
-                "Missing (merged-usr broken)",
-            ));
+            report.add(
+                CheckResult::fail(link, CheckCategory::Symlink, "Missing (merged-usr broken)")
+                    .with_fix(Fix::symlink(link, target)),
+            );
         }
     }
 
@@ -198,12 +369,13 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // 3. Check /usr/bin binaries
     // =========================================================================
     // Combine all bin lists
-    let all_bins: Vec<&str> = BIN_UTILS
+    let all_bins: Vec<&str> = spec
+        .bin_utils
         .iter()
-        .chain(AUTH_BIN.iter())
-        .chain(SSH_BIN.iter())
-        .chain(NM_BIN.iter())
-        .copied()
+        .chain(spec.auth_bin.iter())
+        .chain(spec.ssh_bin.iter())
+        .chain(spec.nm_bin.iter())
+        .map(String::as_str)
         .collect();
 
     // Also need bash which is handled separately in leviso
@@ -234,7 +406,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // These are CRITICAL for installing LevitateOS from the live ISO.
     // Without these, users cannot install the system to disk.
-    for tool in LEVITATE_TOOLS {
+    for tool in &spec.levitate_tools {
         let tool_path = format!("usr/bin/{}", tool);
         if reader.exists(&tool_path) {
             report.add(CheckResult::pass(tool_path, CheckCategory::Binary));
@@ -250,19 +422,20 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 4. Check /usr/sbin binaries
     // =========================================================================
-    let all_sbins: Vec<&str> = SBIN_UTILS
+    let all_sbins: Vec<&str> = spec
+        .sbin_utils
         .iter()
-        .chain(AUTH_SBIN.iter())
-        .chain(SHADOW_SBIN.iter())
-        .chain(NM_SBIN.iter())
-        .chain(WPA_SBIN.iter())
-        .chain(SSH_SBIN.iter())
-        .chain(BLUETOOTH_SBIN.iter())
-        .chain(PIPEWIRE_SBIN.iter())
-        .chain(POLKIT_SBIN.iter())
-        .chain(UDISKS_SBIN.iter())
-        .chain(UPOWER_SBIN.iter())
-        .copied()
+        .chain(spec.auth_sbin.iter())
+        .chain(spec.shadow_sbin.iter())
+        .chain(spec.nm_sbin.iter())
+        .chain(spec.wpa_sbin.iter())
+        .chain(spec.ssh_sbin.iter())
+        .chain(spec.bluetooth_sbin.iter())
+        .chain(spec.pipewire_sbin.iter())
+        .chain(spec.polkit_sbin.iter())
+        .chain(spec.udisks_sbin.iter())
+        .chain(spec.upower_sbin.iter())
+        .map(String::as_str)
         .collect();
 
     for sbin in &all_sbins {
@@ -283,9 +456,10 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // systemd itself plus all helpers
     let systemd_bins_to_check: Vec<&str> = std::iter::once("systemd")
-        .chain(SYSTEMD_BINARIES.iter().copied())
-        // Filter out networkd/resolved - LevitateOS uses NetworkManager
-        .filter(|b| *b != "systemd-networkd" && *b != "systemd-resolved")
+        .chain(spec.systemd_binaries.iter().map(String::as_str))
+        // The build filters these out (networkd/resolved - LevitateOS uses
+        // NetworkManager); section 0b asserts they're absent
+        .filter(|b| !spec.excluded_systemd_binaries.iter().any(|e| e == b))
         .collect();
 
     for systemd_bin in &systemd_bins_to_check {
@@ -304,8 +478,14 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 6. Check systemd units (ALL OF THEM)
     // =========================================================================
-    // Composed from constituent unit lists in distro-spec
-    let all_units = all_systemd_units();
+    // Composed from constituent unit lists in distro-spec, minus the ones
+    // the build filters out
+    let all_units: Vec<&str> = spec
+        .systemd_units
+        .iter()
+        .map(String::as_str)
+        .filter(|u| !spec.excluded_units.iter().any(|e| e == u))
+        .collect();
 
     let mut missing_units = Vec::new();
     let mut found_units = Vec::new();
@@ -360,12 +540,12 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 7. Check /etc files
     // =========================================================================
-    for etc_file in ETC_FILES {
+    for etc_file in &spec.etc_files {
         if reader.exists(etc_file) {
-            report.add(CheckResult::pass(*etc_file, CheckCategory::EtcFile));
+            report.add(CheckResult::pass(etc_file, CheckCategory::EtcFile));
         } else {
             report.add(CheckResult::fail(
-                *etc_file,
+                etc_file,
                 CheckCategory::EtcFile,
                 "Missing",
             ));
@@ -375,12 +555,12 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 8. Check PAM configuration
     // =========================================================================
-    for pam_file in PAM_CONFIGS {
+    for pam_file in &spec.pam_configs {
         if reader.exists(pam_file) {
-            report.add(CheckResult::pass(*pam_file, CheckCategory::EtcFile));
+            report.add(CheckResult::pass(pam_file, CheckCategory::EtcFile));
         } else {
             report.add(CheckResult::fail(
-                *pam_file,
+                pam_file,
                 CheckCategory::EtcFile,
                 "Missing (authentication will fail)",
             ));
@@ -390,12 +570,12 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 8.5. Check security configuration files
     // =========================================================================
-    for sec_file in SECURITY_FILES {
+    for sec_file in &spec.security_files {
         if reader.exists(sec_file) {
-            report.add(CheckResult::pass(*sec_file, CheckCategory::EtcFile));
+            report.add(CheckResult::pass(sec_file, CheckCategory::EtcFile));
         } else {
             report.add(CheckResult::fail(
-                *sec_file,
+                sec_file,
                 CheckCategory::EtcFile,
                 "Missing (security policy incomplete)",
             ));
@@ -405,8 +585,8 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 9. Check PAM modules
     // =========================================================================
-    for pam_module in PAM_MODULES {
-        let module_path = format!("usr/lib64/security/{}", pam_module);
+    for pam_module in &spec.pam_modules {
+        let module_path = format!("{}/security/{}", arch.lib_dir(), pam_module);
         if reader.exists(&module_path) {
             report.add(CheckResult::pass(module_path, CheckCategory::Library));
         } else {
@@ -421,7 +601,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 10. Check udev helpers
     // =========================================================================
-    for helper in UDEV_HELPERS {
+    for helper in &spec.udev_helpers {
         let helper_path = format!("usr/lib/udev/{}", helper);
         if reader.exists(&helper_path) {
             report.add(CheckResult::pass(helper_path, CheckCategory::Binary));
@@ -437,12 +617,12 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 11. Check critical libraries
     // =========================================================================
-    for lib in CRITICAL_LIBS {
-        if reader.exists(lib) {
-            report.add(CheckResult::pass(*lib, CheckCategory::Library));
+    for lib in spec.critical_libs.iter().map(|lib| arch.path(lib)) {
+        if reader.exists(&lib) {
+            report.add(CheckResult::pass(lib, CheckCategory::Library));
         } else {
             report.add(CheckResult::fail(
-                *lib,
+                lib,
                 CheckCategory::Library,
                 "Missing (system will not boot)",
             ));
@@ -462,11 +642,14 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
                         CheckCategory::Symlink,
                     ));
                 } else {
-                    report.add(CheckResult::fail(
-                        init_path,
-                        CheckCategory::Symlink,
-                        format!("Points to '{}' instead of systemd", target),
-                    ));
+                    report.add(
+                        CheckResult::fail(
+                            init_path,
+                            CheckCategory::Symlink,
+                            format!("Points to '{}' instead of systemd", target),
+                        )
+                        .with_fix(Fix::symlink(init_path, INIT_TARGET)),
+                    );
                 }
             }
         } else {
@@ -477,11 +660,14 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
             ));
         }
     } else {
-        report.add(CheckResult::fail(
-            init_path,
-            CheckCategory::Symlink,
-            "Missing (kernel can't find init)",
-        ));
+        report.add(
+            CheckResult::fail(
+                init_path,
+                CheckCategory::Symlink,
+                "Missing (kernel can't find init)",
+            )
+            .with_fix(Fix::symlink(init_path, INIT_TARGET)),
+        );
     }
 
     // =========================================================================
@@ -489,7 +675,7 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     for entry in reader.symlinks() {
         // Skip symlinks we already checked
-        if FHS_SYMLINKS.iter().any(|(l, _)| entry.path == *l) {
+        if spec.fhs_symlinks.iter().any(|(l, _)| entry.path == *l) {
             continue;
         }
         if entry.path == init_path {
@@ -498,10 +684,19 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
 
         if !reader.symlink_target_exists(entry) {
             if let Some(ref target) = entry.link_target {
+                // run/ is a tmpfs populated at runtime (etc/resolv.conf ->
+                // ../run/NetworkManager/resolv.conf)
+                let runtime = target
+                    .trim_start_matches('/')
+                    .trim_start_matches("../")
+                    .starts_with("run/");
+                if runtime {
+                    continue;
+                }
                 // Only report broken symlinks in critical paths
                 if entry.path.starts_with("usr/bin/")
                     || entry.path.starts_with("usr/sbin/")
-                    || entry.path.starts_with("usr/lib64/")
+                    || entry.path.starts_with(&format!("{}/", arch.lib_dir()))
                     || entry.path.starts_with("etc/")
                 {
                     report.add(CheckResult::fail(
@@ -517,99 +712,52 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // =========================================================================
     // 14. Check for kernel modules directory
     // =========================================================================
-    let has_modules = reader
-        .entries()
-        .iter()
-        .any(|e| e.path.starts_with("usr/lib/modules/") && e.path.contains("/kernel/"));
-    if has_modules {
-        report.add(CheckResult::pass(
-            "usr/lib/modules/*/kernel/",
-            CheckCategory::KernelModule,
-        ));
-    } else {
-        report.add(CheckResult::fail(
-            "usr/lib/modules/*/kernel/",
-            CheckCategory::KernelModule,
-            "No kernel modules found",
-        ));
-    }
+    report.add(
+        GlobCheck::new("usr/lib/modules/*/kernel/**", CheckCategory::KernelModule)
+            .labelled("usr/lib/modules/*/kernel/")
+            .failure("No kernel modules found")
+            .check_cpio(reader),
+    );
 
     // =========================================================================
     // 15. Check for udev rules
     // =========================================================================
-    let has_udev_rules = reader
-        .entries()
-        .iter()
-        .any(|e| e.path.starts_with("usr/lib/udev/rules.d/") && e.path.ends_with(".rules"));
-    if has_udev_rules {
-        report.add(CheckResult::pass(
-            "usr/lib/udev/rules.d/*.rules",
-            CheckCategory::UdevRule,
-        ));
-    } else {
-        report.add(CheckResult::fail(
-            "usr/lib/udev/rules.d/*.rules",
-            CheckCategory::UdevRule,
-            "No udev rules found (device detection broken)",
-        ));
-    }
+    report.add(
+        GlobCheck::new("usr/lib/udev/rules.d/*.rules", CheckCategory::UdevRule)
+            .failure("No udev rules found (device detection broken)")
+            .check_cpio(reader),
+    );
 
     // =========================================================================
     // 16. Check for terminfo (required for tmux, ncurses apps)
     // =========================================================================
-    let has_terminfo = reader
-        .entries()
-        .iter()
-        .any(|e| e.path.starts_with("usr/share/terminfo/"));
-    if has_terminfo {
-        report.add(CheckResult::pass(
-            "usr/share/terminfo/",
-            CheckCategory::Other,
-        ));
-    } else {
-        report.add(CheckResult::fail(
-            "usr/share/terminfo/",
-            CheckCategory::Other,
-            "No terminfo database (terminal apps broken)",
-        ));
-    }
+    report.add(
+        GlobCheck::new("usr/share/terminfo/**", CheckCategory::Other)
+            .labelled("usr/share/terminfo/")
+            .failure("No terminfo database (terminal apps broken)")
+            .check_cpio(reader),
+    );
 
     // =========================================================================
     // 17. Check for locale data
     // =========================================================================
-    let has_locale = reader
-        .entries()
-        .iter()
-        .any(|e| e.path.starts_with("usr/lib/locale/") || e.path.starts_with("usr/share/locale/"));
-    if has_locale {
-        report.add(CheckResult::pass("locale data", CheckCategory::Other));
-    } else {
-        report.add(CheckResult::fail(
-            "locale data",
-            CheckCategory::Other,
-            "No locale data found",
-        ));
-    }
+    report.add(
+        GlobCheck::new("usr/lib/locale/**", CheckCategory::Other)
+            .or("usr/share/locale/**")
+            .labelled("locale data")
+            .failure("No locale data found")
+            .check_cpio(reader),
+    );
 
     // =========================================================================
     // 18. Check for timezone data
     // =========================================================================
-    let has_zoneinfo = reader
-        .entries()
-        .iter()
-        .any(|e| e.path.starts_with("usr/share/zoneinfo/"));
-    if has_zoneinfo {
-        report.add(CheckResult::pass(
-            "usr/share/zoneinfo/",
-            CheckCategory::Other,
-        ));
-    } else {
-        report.add(CheckResult::fail(
-            "usr/share/zoneinfo/",
-            CheckCategory::Other,
-            "No timezone data (timedatectl broken)",
-        ));
-    }
+    report.add(
+        GlobCheck::new("usr/share/zoneinfo/**", CheckCategory::Other)
+            .labelled("usr/share/zoneinfo/")
+            .failure("No timezone data (timedatectl broken)")
+            .check_cpio(reader),
+    );
 
     // =========================================================================
     // 19. Check for license files (legal compliance)
@@ -618,6 +766,99 @@ pub fn verify(reader: &CpioReader) -> VerificationReport {
     // These are the critical packages that MUST have license directories.
     verify_licenses(reader, &mut report);
 
+    // =========================================================================
+    // 20. Check NSS modules referenced by nsswitch.conf
+    // =========================================================================
+    // A service listed in nsswitch.conf without its libnss_<svc>.so.2 is
+    // silently skipped by glibc - getent works in a chroot (host modules)
+    // but user/host lookups break at boot.
+    verify_nss(reader, arch, &mut report);
+
+    // =========================================================================
+    // 21. Validate udev rule syntax and helpers
+    // =========================================================================
+    for result in udev::check(reader) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 22. Validate sysctl.d, tmpfiles.d and fstab configuration
+    // =========================================================================
+    for result in sysctl::check(reader) {
+        report.add(result);
+    }
+    for result in tmpfiles::check(reader) {
+        report.add(result);
+    }
+    for result in fstab::check(reader) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 22b. chrony, sshd and NetworkManager configs are usable
+    // =========================================================================
+    for result in service_configs::check(reader) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 23. Check for empty files in critical locations
+    // =========================================================================
+    check_empty_files(&mut report, reader, arch);
+
+    // =========================================================================
+    // 24. Check for device nodes leaked from the build host
+    // =========================================================================
+    for result in devices::check_unexpected(reader) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 25. First-boot hygiene (machine-id, random seed, SSH host keys)
+    // =========================================================================
+    for result in first_boot::check(reader) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 26. Home directories (root, login accounts) and etc/skel
+    // =========================================================================
+    for result in homes::check(reader) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 27. Console keymaps and fonts (vconsole.conf)
+    // =========================================================================
+    for result in console::check(reader) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 28. DNS stack: NetworkManager's resolv.conf, libnss_dns, no resolved
+    // =========================================================================
+    for result in dns::check(reader, arch) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 29. Alternative-style links resolve to approved providers
+    // =========================================================================
+    for result in alternatives::check(reader, spec) {
+        report.add(result);
+    }
+
+    // =========================================================================
+    // 30. Device trees for every kernel (aarch64)
+    // =========================================================================
+    if arch == Arch::Aarch64 {
+        for result in dtb::check_cpio(reader) {
+            report.add(result);
+        }
+    }
+
+    report.apply_prerequisites(PREREQUISITES, |path| has_path(reader, path));
+    report.apply_hints(HINTS);
     report
 }
 
@@ -715,10 +956,100 @@ fn verify_licenses(reader: &CpioReader, report: &mut VerificationReport) {
     }
 }
 
+const NSSWITCH_CONF: &str = "etc/nsswitch.conf";
+
+/// Paths of the spec's deny-listed components, with the list they come from.
+fn excluded_paths(spec: &Spec) -> Vec<(String, &'static str)> {
+    let bins = spec
+        .excluded_bin
+        .iter()
+        .map(|b| (format!("usr/bin/{}", b), "tool"));
+    let systemd = spec
+        .excluded_systemd_binaries
+        .iter()
+        .map(|b| (format!("usr/lib/systemd/{}", b), "systemd binary"));
+    let units = spec
+        .excluded_units
+        .iter()
+        .map(|u| (format!("usr/lib/systemd/system/{}", u), "unit"));
+    bins.chain(systemd).chain(units).collect()
+}
+
+/// Verify every service referenced in nsswitch.conf has its NSS module.
+fn verify_nss(reader: &CpioReader, arch: Arch, report: &mut VerificationReport) {
+    if !reader.exists(NSSWITCH_CONF) {
+        report.add(CheckResult::fail(
+            NSSWITCH_CONF,
+            CheckCategory::EtcFile,
+            "Missing (NSS falls back to glibc defaults)",
+        ));
+        return;
+    }
+
+    let content = match reader.read_file_to_string(NSSWITCH_CONF) {
+        Ok(content) => content,
+        Err(e) => {
+            report.add(CheckResult::fail(
+                NSSWITCH_CONF,
+                CheckCategory::EtcFile,
+                format!("Cannot read: {}", e),
+            ));
+            return;
+        }
+    };
+
+    for (service, databases) in nss_services(&content) {
+        let module = format!("{}/libnss_{}.so.2", arch.lib_dir(), service);
+        if reader.exists(&module) {
+            report.add(CheckResult::pass(module, CheckCategory::Library));
+        } else {
+            report.add(CheckResult::fail(
+                module,
+                CheckCategory::Library,
+                format!(
+                    "Missing NSS module for '{}' (used by: {})",
+                    service,
+                    databases.join(", ")
+                ),
+            ));
+        }
+    }
+}
+
+/// Parse nsswitch.conf into service -> databases that reference it.
+///
+/// Action items like `[NOTFOUND=return]` are skipped.
+fn nss_services(content: &str) -> BTreeMap<String, Vec<String>> {
+    let mut services: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some((database, sources)) = line.split_once(':') else {
+            continue;
+        };
+
+        for service in sources.split_whitespace() {
+            if service.starts_with('[') || service.ends_with(']') {
+                continue;
+            }
+            services
+                .entry(service.to_string())
+                .or_default()
+                .push(database.trim().to_string());
+        }
+    }
+
+    services
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use distro_spec::shared::ESSENTIAL_UNITS;
+    use distro_spec::shared::auth::{AUTH_SBIN, PAM_MODULES};
+    use distro_spec::shared::{
+        BIN_UTILS, ESSENTIAL_UNITS, FHS_DIRS, FHS_SYMLINKS, LEVITATE_TOOLS, SBIN_UTILS,
+        SYSTEMD_BINARIES,
+    };
 
     // =========================================================================
     // BUSYBOX FORBIDDEN TESTS
@@ -736,6 +1067,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_busybox_forbidden_reason_documented() {
         // This test exists to document WHY busybox is forbidden.
         //
@@ -918,4 +1250,32 @@ mod tests {
             "LEVITATE_TOOLS has fewer than 5 entries - did someone break it?"
         );
     }
+
+    #[test]
+    fn test_nss_services_parsing() {
+        let conf = "# Generated by authselect\n\
+                    passwd:     files systemd\n\
+                    group:      files [SUCCESS=merge] systemd\n\
+                    hosts:      files myhostname resolve [!UNAVAIL=return] dns\n\
+                    \n";
+        let services = nss_services(conf);
+
+        assert_eq!(
+            services.keys().collect::<Vec<_>>(),
+            vec!["dns", "files", "myhostname", "resolve", "systemd"]
+        );
+        assert_eq!(services["systemd"], vec!["passwd", "group"]);
+        assert_eq!(services["dns"], vec!["hosts"]);
+    }
+
+    #[test]
+    fn test_excluded_paths() {
+        let paths: Vec<String> = excluded_paths(Spec::builtin())
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert!(paths.contains(&"usr/lib/systemd/systemd-resolved".to_string()));
+        assert!(paths.contains(&"usr/lib/systemd/system/systemd-networkd.service".to_string()));
+        assert!(paths.contains(&"usr/bin/resolvectl".to_string()));
+    }
 }
@@ -0,0 +1,274 @@
+//! RPM database checklist
+//!
+//! Opens the sqlite RPM database shipped inside a rootfs archive and
+//! cross-references the installed package set against:
+//! - The license directories in `usr/share/licenses/`
+//! - The package manifest in distro-spec
+//!
+//! The database is read out of the archive into a temporary directory and
+//! queried read-only, so no `rpm` binary or extraction is required.
+//!
+//! ## Database Layout
+//!
+//! rpm >= 4.16 stores its database as `rpmdb.sqlite`. The `Name` index table
+//! maps each package name to its header, which is all we need here - no
+//! header blob parsing.
+
+use super::{CheckCategory, CheckResult, Hint, Scope, VerificationReport};
+use crate::cpio::CpioReader;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::sanitize::Destination;
+use crate::spec::Spec;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::BTreeSet;
+
+// =============================================================================
+// DATABASE LOCATIONS
+// =============================================================================
+
+/// Candidate locations of the sqlite RPM database, in lookup order.
+/// Newer rpm moved it to /usr/lib/sysimage with /var/lib/rpm as a symlink.
+pub const RPMDB_PATHS: &[&str] = &[
+    "var/lib/rpm/rpmdb.sqlite",
+    "usr/lib/sysimage/rpm/rpmdb.sqlite",
+];
+
+/// Location of per-package license directories.
+const LICENSES_DIR: &str = "usr/share/licenses/";
+
+// =============================================================================
+// VERIFICATION
+// =============================================================================
+
+/// Where to fix failures, by category and path prefix.
+pub const HINTS: &[Hint] = &[
+    Hint::new(CheckCategory::Package, "", "Install packages with rpm/dnf into the rootfs so the database is written"),
+    Hint::new(CheckCategory::Package, "package: ", "Add it to the rootfs package set, or drop it from distro-spec's package manifest"),
+    Hint::new(CheckCategory::License, "", "Install the package with rpm instead of copying its files, so its license directory is owned"),
+];
+
+/// Paths referenced by the RPM database checklist.
+pub fn scope() -> Scope {
+    let mut scope = Scope::new();
+    scope.extend(RPMDB_PATHS, CheckCategory::Package);
+    scope.add_prefix(LICENSES_DIR, CheckCategory::License);
+    scope
+}
+
+/// Verify the RPM database in a CPIO/EROFS rootfs archive.
+pub fn verify(reader: &CpioReader, spec: &Spec) -> VerificationReport {
+    let mut report = VerificationReport::new("RPM Database");
+    check_database(reader, spec, &mut report);
+    report.apply_hints(HINTS);
+    report
+}
+
+fn check_database(reader: &CpioReader, spec: &Spec, report: &mut VerificationReport) {
+    // =========================================================================
+    // 1. Locate and read the database
+    // =========================================================================
+    let Some(db_path) = RPMDB_PATHS.iter().find(|p| reader.exists(p)) else {
+        report.add(CheckResult::fail(
+            RPMDB_PATHS[0],
+            CheckCategory::Package,
+            "No sqlite RPM database found",
+        ));
+        return;
+    };
+
+    let installed = match read_installed_packages(reader, db_path) {
+        Ok(installed) => installed,
+        Err(e) => {
+            report.add(CheckResult::fail(
+                *db_path,
+                CheckCategory::Package,
+                format!("Cannot read: {}", e),
+            ));
+            return;
+        }
+    };
+
+    if installed.is_empty() {
+        report.add(CheckResult::fail(
+            *db_path,
+            CheckCategory::Package,
+            "Database contains no packages",
+        ));
+        return;
+    }
+
+    report.add(CheckResult::pass(
+        format!("{} ({} packages installed)", db_path, installed.len()),
+        CheckCategory::Package,
+    ));
+
+    // =========================================================================
+    // 2. Check the manifest's packages are installed
+    // =========================================================================
+    for pkg in &spec.packages {
+        if installed.contains(pkg) {
+            report.add(CheckResult::pass(
+                format!("package: {}", pkg),
+                CheckCategory::Package,
+            ));
+        } else {
+            report.add(CheckResult::fail(
+                format!("package: {}", pkg),
+                CheckCategory::Package,
+                "Not in RPM database",
+            ));
+        }
+    }
+
+    // =========================================================================
+    // 3. Cross-reference installed packages with license directories
+    // =========================================================================
+    let license_dirs = license_directories(reader);
+
+    for pkg in &installed {
+        if has_license_dir(pkg, &license_dirs) {
+            report.add(CheckResult::pass(
+                format!("{}{}/", LICENSES_DIR, pkg),
+                CheckCategory::License,
+            ));
+        } else {
+            report.add(CheckResult::fail(
+                format!("{}{}/", LICENSES_DIR, pkg),
+                CheckCategory::License,
+                "Installed package has no license directory",
+            ));
+        }
+    }
+
+    // License directories with no owning package mean files were copied in
+    // without going through rpm, so the database doesn't describe the image
+    for dir in &license_dirs {
+        if !installed.contains(dir) {
+            report.add(CheckResult::fail(
+                format!("{}{}/", LICENSES_DIR, dir),
+                CheckCategory::License,
+                "No installed package owns this license (copied without rpm?)",
+            ));
+        }
+    }
+}
+
+/// Read the set of installed package names from the RPM database.
+pub fn read_installed_packages(
+    reader: &CpioReader,
+    db_path: &str,
+) -> Result<BTreeSet<String>, FsdbgError> {
+    // Uncheckpointed transactions live in the WAL - without it the
+    // database may be missing recently installed packages. Both come out
+    // of one pass over the archive.
+    let wal_path = format!("{}-wal", db_path);
+    let mut files = reader.read_files(&[db_path, wal_path.as_str()])?;
+    let db = files
+        .remove(db_path)
+        .ok_or_else(|| FsdbgError::file_not_found(db_path))?;
+
+    let temp_dir = tempfile::tempdir()?;
+    let dest = Destination::new(temp_dir.path());
+    let local_db = dest.write_file("rpmdb.sqlite", &db)?;
+    if let Some(wal) = files.remove(&wal_path) {
+        dest.write_file("rpmdb.sqlite-wal", &wal)?;
+    }
+
+    query_package_names(
+        &Connection::open_with_flags(&local_db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(sqlite_error)?,
+    )
+}
+
+fn query_package_names(conn: &Connection) -> Result<BTreeSet<String>, FsdbgError> {
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT key FROM Name")
+        .map_err(sqlite_error)?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(sqlite_error)?
+        .collect::<Result<BTreeSet<_>, _>>()
+        .map_err(sqlite_error)?;
+
+    // gpg-pubkey entries are imported keys, not packages
+    Ok(names
+        .into_iter()
+        .filter(|name| name != "gpg-pubkey")
+        .collect())
+}
+
+fn sqlite_error(e: rusqlite::Error) -> FsdbgError {
+    FsdbgError::new(ErrorCode::ParseError, format!("RPM database: {}", e))
+}
+
+/// Collect package names that have a directory under usr/share/licenses/.
+fn license_directories(reader: &CpioReader) -> BTreeSet<String> {
+    reader
+        .entries()
+        .iter()
+        .filter_map(|e| {
+            let rest = CpioReader::normalize_path(&e.path)
+                .strip_prefix(LICENSES_DIR)?
+                .to_string();
+            let pkg = rest.split('/').next()?;
+            if pkg.is_empty() {
+                None
+            } else {
+                Some(pkg.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Subpackages (foo-libs, foo-common) usually share their parent's license
+/// directory, so accept any license directory that is a dash-prefix of the
+/// package name.
+fn has_license_dir(pkg: &str, license_dirs: &BTreeSet<String>) -> bool {
+    license_dirs.contains(pkg)
+        || license_dirs.iter().any(|dir| {
+            pkg.strip_prefix(dir.as_str())
+                .is_some_and(|rest| rest.starts_with('-'))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subpackage_shares_parent_license() {
+        let dirs: BTreeSet<String> = ["systemd", "pam"].iter().map(|s| s.to_string()).collect();
+        assert!(has_license_dir("systemd", &dirs));
+        assert!(has_license_dir("systemd-libs", &dirs));
+        assert!(has_license_dir("pam-libs", &dirs));
+        assert!(!has_license_dir("pamixer", &dirs));
+        assert!(!has_license_dir("glibc", &dirs));
+    }
+
+    #[test]
+    fn test_query_package_names() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE Name (key TEXT, hnum INTEGER, idx INTEGER);
+             INSERT INTO Name VALUES ('bash', 1, 0), ('glibc', 2, 0),
+                                     ('gpg-pubkey', 3, 0), ('glibc', 4, 0);",
+        )
+        .unwrap();
+
+        let names = query_package_names(&conn).unwrap();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains("bash"));
+        assert!(names.contains("glibc"));
+    }
+
+    #[test]
+    fn test_expected_packages_include_core() {
+        for pkg in ["glibc", "systemd", "pam"] {
+            assert!(
+                Spec::builtin().packages.iter().any(|p| p == pkg),
+                "Missing core package: {}",
+                pkg
+            );
+        }
+    }
+}
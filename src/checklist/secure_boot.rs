@@ -0,0 +1,153 @@
+//! Secure Boot artifacts of the live ISO
+//!
+//! With Secure Boot enabled, firmware loads only EFI binaries signed by a
+//! key in its db. On stock machines that is the Microsoft UEFI CA, so the
+//! fallback loader in `EFI/BOOT` has to be shim, which loads the second
+//! stage (`grubx64.efi`, systemd-boot under that name) if it is signed by
+//! a key shim embeds or the machine owner enrolled with MokManager
+//! (`mmx64.efi`).
+//!
+//! Every EFI binary on the ISO and in efiboot.img is checked for an
+//! Authenticode signature, and passes naming its signers. Unsigned ones,
+//! and a missing shim, second stage or MokManager, fail only when the
+//! profile requires Secure Boot ([`crate::spec::Spec::secure_boot`]).
+
+use super::efi::{EfiTree, EfiTrees};
+use super::{CheckCategory, CheckResult, Subsystem};
+use crate::arch::Arch;
+use crate::authenticode;
+use crate::uki;
+use distro_spec::shared::{EFIBOOT_FILENAME, ISO_EFI_DIR};
+
+/// SBAT component shim's `.sbat` section names
+const SHIM_SBAT: &str = "\nshim,";
+
+/// Check the signatures of the EFI binaries on the ISO and in efiboot.img,
+/// and with `required`, that both boot through shim.
+pub fn check(trees: &EfiTrees, arch: Arch, required: bool) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    for tree in trees.trees() {
+        check_tree(tree, arch, required, &mut results);
+    }
+    if let Some(Err(ref e)) = trees.esp {
+        if required {
+            results.push(CheckResult::fail(
+                format!("/{}", EFIBOOT_FILENAME),
+                CheckCategory::Binary,
+                format!("Cannot read its EFI binaries: {}", e),
+            ));
+        }
+    }
+    results
+        .into_iter()
+        .map(|r| r.with_subsystem(Subsystem::Boot))
+        .collect()
+}
+
+fn check_tree(tree: &EfiTree, arch: Arch, required: bool, results: &mut Vec<CheckResult>) {
+    for path in tree.binaries() {
+        let item = format!("{}/{} signature", tree.label, path);
+        match tree
+            .read(path)
+            .and_then(|data| authenticode::signers(&data))
+        {
+            Ok(signers) if !signers.is_empty() => {
+                let signers: Vec<String> = signers.iter().map(|s| s.to_string()).collect();
+                results.push(CheckResult::pass(
+                    format!("{} (signed by {})", item, signers.join("; ")),
+                    CheckCategory::Binary,
+                ));
+            }
+            Ok(_) if required => results.push(
+                CheckResult::fail(
+                    item,
+                    CheckCategory::Binary,
+                    "Not signed (CRITICAL: Secure Boot firmware won't load it)",
+                )
+                .with_hint("Sign it with sbsign when building the ISO in leviso"),
+            ),
+            Err(e) if required => results.push(CheckResult::fail(
+                item,
+                CheckCategory::Binary,
+                format!("Cannot read signature: {}", e),
+            )),
+            _ => {}
+        }
+    }
+    if !required {
+        return;
+    }
+
+    // The fallback loader must be shim, with its second stage and MokManager beside it
+    let loader = format!("{}/{}", ISO_EFI_DIR, arch.efi_bootloader());
+    let item = format!("{}/{} is shim", tree.label, loader);
+    let shim = tree.find(&loader).map(|(path, _)| {
+        tree.read(path).and_then(|data| {
+            let sbat = uki::section(&data, ".sbat")?.unwrap_or_default();
+            Ok(String::from_utf8_lossy(sbat).contains(SHIM_SBAT))
+        })
+    });
+    results.push(match shim {
+        Some(Ok(true)) => CheckResult::pass(item, CheckCategory::Binary),
+        Some(Ok(false)) => CheckResult::fail(
+            item,
+            CheckCategory::Binary,
+            "No shim entry in its .sbat section (CRITICAL: only firmware trusting our key will boot)",
+        )
+        .with_hint("Install shim as the fallback loader and the boot loader as its second stage"),
+        Some(Err(e)) => CheckResult::fail(item, CheckCategory::Binary, format!("Cannot read: {}", e)),
+        None => CheckResult::fail(item, CheckCategory::Binary, "Missing"),
+    });
+    for (name, what) in [
+        ("grub", "shim's second stage"),
+        ("mm", "MokManager, to enroll the key shim can't verify with"),
+    ] {
+        let path = format!("{}/{}{}.efi", ISO_EFI_DIR, name, arch.efi_suffix());
+        let item = format!("{}/{}", tree.label, path);
+        results.push(match tree.find(&path) {
+            Some(_) => CheckResult::pass(item, CheckCategory::Binary),
+            None => CheckResult::fail(
+                item,
+                CheckCategory::Binary,
+                format!("Missing ({}; CRITICAL with Secure Boot enabled)", what),
+            )
+            .with_hint("Copy it from the shim package next to shim"),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_tree() {
+        let dir = crate::ScratchDir::new("fsdbg-test-").unwrap();
+        let boot = dir.path().join(ISO_EFI_DIR);
+        std::fs::create_dir_all(&boot).unwrap();
+        for name in ["BOOTX64.EFI", "mmx64.efi", "README"] {
+            std::fs::write(boot.join(name), b"MZ not really PE").unwrap();
+        }
+        let tree = EfiTree::dir(format!("{}:", EFIBOOT_FILENAME), dir, None).unwrap();
+
+        let mut results = Vec::new();
+        check_tree(&tree, Arch::X86_64, false, &mut results);
+        assert!(results.is_empty());
+
+        check_tree(&tree, Arch::X86_64, true, &mut results);
+        let failed: Vec<_> = results
+            .iter()
+            .filter(|r| !r.passed)
+            .map(|r| r.item.as_str())
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                format!("efiboot.img:/{}/BOOTX64.EFI signature", ISO_EFI_DIR),
+                format!("efiboot.img:/{}/mmx64.efi signature", ISO_EFI_DIR),
+                format!("efiboot.img:/{}/BOOTX64.EFI is shim", ISO_EFI_DIR),
+                format!("efiboot.img:/{}/grubx64.efi", ISO_EFI_DIR),
+            ]
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! chrony, sshd and NetworkManager configuration sanity
+//!
+//! The checklists confirm chronyd, sshd and NetworkManager are installed and
+//! enabled, but an enabled service with an unusable config still makes a
+//! broken first boot: chronyd with no time source, sshd accepting root
+//! logins while root has no password, NetworkManager refusing a typo'd
+//! config. This module reads the configs themselves.
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use std::path::Path;
+
+pub const CHRONY_CONF: &str = "etc/chrony.conf";
+pub const SSHD_CONFIG: &str = "etc/ssh/sshd_config";
+/// Drop-ins `Include`d by sshd_config
+pub const SSHD_CONFIG_D: &str = "etc/ssh/sshd_config.d/";
+pub const NM_CONF: &str = "etc/NetworkManager/NetworkManager.conf";
+
+/// chrony directives that provide time sources
+const CHRONY_SOURCES: &[&str] = &["pool", "server", "peer", "sourcedir", "refclock"];
+
+/// `PermitRootLogin` values allowed: root has no password on first boot
+const ROOT_LOGIN_ALLOWED: &[&str] = &[
+    "no",
+    "prohibit-password",
+    "without-password",
+    "forced-commands-only",
+];
+
+/// NetworkManager settings plugins
+const NM_PLUGINS: &[&str] = &["keyfile", "ifcfg-rh", "ifupdown"];
+
+/// Check the configs in an archive.
+pub fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    let drop_ins: Vec<String> = reader
+        .files()
+        .map(|e| CpioReader::normalize_path(&e.path))
+        .filter(|p| p.starts_with(SSHD_CONFIG_D) && p.ends_with(".conf"))
+        .collect();
+    let mut paths = vec![CHRONY_CONF, SSHD_CONFIG, NM_CONF];
+    paths.extend(drop_ins.iter().map(String::as_str));
+    let contents = match reader.read_files(&paths) {
+        Ok(contents) => contents,
+        Err(e) => {
+            return vec![CheckResult::fail(
+                SSHD_CONFIG,
+                CheckCategory::Config,
+                format!("Cannot read: {}", e),
+            )]
+        }
+    };
+    results(
+        |path| {
+            contents
+                .get(path)
+                .map(|c| String::from_utf8_lossy(c).to_string())
+        },
+        drop_ins,
+    )
+}
+
+/// Check the configs in a mounted image.
+pub fn check_dir(root: &Path) -> Vec<CheckResult> {
+    let drop_ins: Vec<String> = std::fs::read_dir(root.join(SSHD_CONFIG_D))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| e.file_name().to_str().map(String::from))
+        .filter(|name| name.ends_with(".conf"))
+        .map(|name| format!("{}{}", SSHD_CONFIG_D, name))
+        .collect();
+    results(
+        |path| std::fs::read_to_string(root.join(path)).ok(),
+        drop_ins,
+    )
+}
+
+/// Returns the problem with a config's content, if any
+type Lint<'a> = dyn Fn(&str) -> Option<String> + 'a;
+
+fn results(read: impl Fn(&str) -> Option<String>, mut drop_ins: Vec<String>) -> Vec<CheckResult> {
+    drop_ins.sort();
+    let sshd = |content: &str| {
+        let drop_ins: Vec<(String, String)> = drop_ins
+            .iter()
+            .filter_map(|p| read(p).map(|c| (p.clone(), c)))
+            .collect();
+        sshd_problem(content, &drop_ins)
+    };
+    let checks: [(&str, &Lint, &str); 3] = [
+        (
+            CHRONY_CONF,
+            &chrony_problem,
+            "Add a pool or server line (the distribution's NTP pool) to chrony.conf",
+        ),
+        (
+            SSHD_CONFIG,
+            &sshd,
+            "Set PermitRootLogin prohibit-password (or remove the override); root has no password until first login",
+        ),
+        (
+            NM_CONF,
+            &nm_problem,
+            "Ship NetworkManager.conf from the NetworkManager package, or fix the line",
+        ),
+    ];
+    checks
+        .into_iter()
+        .map(|(path, problem, hint)| match read(path) {
+            None => CheckResult::fail(path, CheckCategory::Config, "Missing").with_hint(hint),
+            Some(content) => match problem(&content) {
+                None => CheckResult::pass(path, CheckCategory::Config),
+                Some(message) => {
+                    CheckResult::fail(path, CheckCategory::Config, message).with_hint(hint)
+                }
+            },
+        })
+        .collect()
+}
+
+/// Directive lines of a `keyword value` config, without comments.
+fn directives(content: &str) -> impl Iterator<Item = (&str, &str)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with('!'))
+        .map(|l| {
+            let (keyword, value) = l.split_once(char::is_whitespace).unwrap_or((l, ""));
+            (keyword, value.trim())
+        })
+}
+
+fn chrony_problem(content: &str) -> Option<String> {
+    let has_source = directives(content)
+        .any(|(keyword, _)| CHRONY_SOURCES.contains(&keyword.to_lowercase().as_str()));
+    (!has_source).then(|| "No pool/server lines: chronyd has no time source".to_string())
+}
+
+/// sshd takes the first value it sees for a keyword, reading `Include`d
+/// drop-ins where the Include appears, and stops at the first `Match` block
+/// (whose settings are conditional).
+fn sshd_problem(content: &str, drop_ins: &[(String, String)]) -> Option<String> {
+    let mut lines: Vec<(String, String, String)> = Vec::new();
+    for (keyword, value) in directives(content) {
+        let keyword = keyword.to_lowercase();
+        if keyword == "include" {
+            for (path, drop_in) in drop_ins {
+                for (k, v) in directives(drop_in) {
+                    lines.push((k.to_lowercase(), v.to_string(), path.clone()));
+                }
+            }
+            continue;
+        }
+        lines.push((keyword, value.to_string(), SSHD_CONFIG.to_string()));
+    }
+    let first = |wanted: &str| {
+        lines
+            .iter()
+            .take_while(|(keyword, _, _)| keyword != "match")
+            .find(|(keyword, _, _)| keyword == wanted)
+    };
+
+    if let Some((_, value, source)) = first("permitrootlogin") {
+        if !ROOT_LOGIN_ALLOWED.contains(&value.to_lowercase().as_str()) {
+            return Some(format!("PermitRootLogin {} (set in {})", value, source));
+        }
+    }
+    match first("permitemptypasswords") {
+        Some((_, value, source)) if value.eq_ignore_ascii_case("yes") => {
+            Some(format!("PermitEmptyPasswords yes (set in {})", source))
+        }
+        _ => None,
+    }
+}
+
+/// NetworkManager refuses to start on a config it can't parse.
+fn nm_problem(content: &str) -> Option<String> {
+    let mut section = String::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            match name.strip_suffix(']') {
+                Some(name) => section = name.trim().to_string(),
+                None => return Some(format!("Line {}: unterminated section header", i + 1)),
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Some(format!("Line {}: not a key=value pair", i + 1));
+        };
+        if section.is_empty() {
+            return Some(format!("Line {}: setting outside any [section]", i + 1));
+        }
+        if section == "main" && key.trim() == "plugins" {
+            let unknown = value
+                .split(',')
+                .map(str::trim)
+                .find(|p| !p.is_empty() && !NM_PLUGINS.contains(p));
+            if let Some(plugin) = unknown {
+                return Some(format!(
+                    "Line {}: unknown settings plugin '{}'",
+                    i + 1,
+                    plugin
+                ));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problems() {
+        assert_eq!(
+            chrony_problem("pool 2.levitateos.pool.ntp.org iburst\n"),
+            None
+        );
+        assert!(
+            chrony_problem("# pool commented out\ndriftfile /var/lib/chrony/drift\n").is_some()
+        );
+
+        let drop_in = [(
+            "etc/ssh/sshd_config.d/50-live.conf".to_string(),
+            "PermitRootLogin yes\n".to_string(),
+        )];
+        let config = "Include /etc/ssh/sshd_config.d/*.conf\nPermitRootLogin prohibit-password\n";
+        assert_eq!(
+            sshd_problem(config, &drop_in).as_deref(),
+            Some("PermitRootLogin yes (set in etc/ssh/sshd_config.d/50-live.conf)")
+        );
+        assert_eq!(sshd_problem(config, &[]), None);
+        assert_eq!(
+            sshd_problem("Match User admin\n  PermitRootLogin yes\n", &[]),
+            None
+        );
+
+        assert_eq!(nm_problem("[main]\nplugins=keyfile\n"), None);
+        assert_eq!(
+            nm_problem("[main]\nplugins=keyfile,ifcfg\n").as_deref(),
+            Some("Line 2: unknown settings plugin 'ifcfg'")
+        );
+        assert!(nm_problem("dns=default\n").is_some());
+    }
+}
@@ -0,0 +1,147 @@
+//! Shebang (`#!`) target validation
+//!
+//! An executable script whose interpreter isn't in the artifact fails the
+//! same way a binary with a missing loader does: "No such file or
+//! directory" for a file that is plainly there. `#!/usr/bin/env prog` lines
+//! are followed through to `prog`, looked up on the default PATH.
+
+use super::interpreters::describe;
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use crate::reader::ArchiveReader;
+use std::collections::BTreeMap;
+
+/// Directories `env` searches, as in systemd's default PATH
+const SEARCH_PATH: &[&str] = &[
+    "usr/local/sbin",
+    "usr/local/bin",
+    "usr/sbin",
+    "usr/bin",
+    "sbin",
+    "bin",
+];
+
+/// What a script's `#!` line asks for
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Shebang {
+    /// Interpreter path as written (`/usr/bin/env` for env lines)
+    pub interpreter: String,
+    /// Program `env` is asked to find, if the interpreter is env
+    pub command: Option<String>,
+}
+
+impl Shebang {
+    /// Parse the `#!` line at the start of a file, if it has one.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let rest = data.strip_prefix(b"#!")?;
+        let line = rest.split(|&b| b == b'\n').next()?;
+        let line = std::str::from_utf8(line).ok()?;
+        let mut words = line.split_whitespace();
+        let interpreter = words.next()?.to_string();
+        let command = if interpreter.rsplit('/').next() == Some("env") {
+            // Skip env's options (-S, -i) and VAR=value assignments
+            words
+                .find(|w| !w.starts_with('-') && !w.contains('='))
+                .map(str::to_string)
+        } else {
+            None
+        };
+        Some(Self {
+            interpreter,
+            command,
+        })
+    }
+
+    /// `/usr/bin/python3`, or `python3 (via /usr/bin/env)`
+    fn name(&self) -> String {
+        match &self.command {
+            Some(command) => format!("{} (via {})", command, self.interpreter),
+            None => self.interpreter.clone(),
+        }
+    }
+}
+
+/// Flag executable scripts whose interpreter (or the program `env` should
+/// run) doesn't resolve to a regular file, one result per interpreter.
+///
+/// Only failures are returned - a clean archive adds nothing to the report.
+pub fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    let mut requested: BTreeMap<Shebang, Vec<String>> = BTreeMap::new();
+    let scan = reader.visit_files(|path, content| {
+        let executable = reader.get(path).is_some_and(|e| e.mode & 0o111 != 0);
+        if let Some(shebang) = Shebang::parse(content).filter(|_| executable) {
+            requested.entry(shebang).or_default().push(path.to_string());
+        }
+    });
+    if let Err(e) = scan {
+        return vec![CheckResult::fail(
+            "Script interpreters",
+            CheckCategory::Binary,
+            format!("Cannot scan scripts: {}", e),
+        )];
+    }
+
+    requested
+        .into_iter()
+        .filter_map(|(shebang, users)| {
+            let problem = problem(reader, &shebang)?;
+            Some(
+                CheckResult::fail(
+                    shebang.name(),
+                    CheckCategory::Binary,
+                    format!("{}, requested by {}", problem, describe(&users)),
+                )
+                .with_hint("Install the interpreter into the artifact, or leave out the scripts that need it"),
+            )
+        })
+        .collect()
+}
+
+/// Why a shebang doesn't resolve, if it doesn't.
+fn problem(reader: &CpioReader, shebang: &Shebang) -> Option<String> {
+    let is_file = |path: &str| {
+        reader
+            .resolve(path)
+            .is_some_and(|p| reader.get(&p).is_some_and(|e| e.is_file()))
+    };
+    if !is_file(&shebang.interpreter) {
+        return Some(match shebang.command {
+            Some(_) => format!("{} is missing", shebang.interpreter),
+            None => "Missing".to_string(),
+        });
+    }
+    match &shebang.command {
+        Some(command) if command.contains('/') => {
+            (!is_file(command)).then(|| format!("{} is missing", command))
+        }
+        Some(command) => {
+            let found = SEARCH_PATH
+                .iter()
+                .any(|dir| is_file(&format!("{}/{}", dir, command)));
+            (!found).then(|| format!("{} is not on PATH", command))
+        }
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let parse = |s: &str| Shebang::parse(s.as_bytes());
+        assert_eq!(
+            parse("#!/bin/sh -e\necho hi\n"),
+            Some(Shebang {
+                interpreter: "/bin/sh".to_string(),
+                command: None,
+            })
+        );
+        let env = parse("#! /usr/bin/env -S LC_ALL=C python3 -u\r\n").unwrap();
+        assert_eq!(env.command.as_deref(), Some("python3"));
+        assert_eq!(env.name(), "python3 (via /usr/bin/env)");
+        assert_eq!(parse("#!\n"), None);
+        assert_eq!(parse("\x7fELF"), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! FIFO, socket and unknown file-type detection
+//!
+//! Release artifacts are built from staged trees, and a FIFO or socket in one
+//! is almost always build-time state that got captured by mistake (a control
+//! socket left in `/run`, a pipe from a packaging script). Entries whose type
+//! bits match no file type at all mean the archive writer is broken.
+//!
+//! Paths that legitimately hold such entries are allowed with glob patterns
+//! (`verify --allow-special`).
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::{CpioReader, FileType};
+use glob::Pattern;
+
+/// Flag FIFOs, sockets and entries of unknown type outside the allowed paths.
+///
+/// Only failures are returned - a clean archive adds nothing to the report.
+pub fn check(reader: &CpioReader, allowed: &[Pattern]) -> Vec<CheckResult> {
+    reader
+        .entries()
+        .iter()
+        .filter_map(|e| {
+            let kind = match e.file_type {
+                FileType::Fifo => "FIFO",
+                FileType::Socket => "socket",
+                FileType::Unknown => "entry of unknown type",
+                _ => return None,
+            };
+            let path = CpioReader::normalize_path(&e.path);
+            if allowed.iter().any(|p| p.matches(&path)) {
+                return None;
+            }
+            Some(
+                CheckResult::fail(
+                    &e.path,
+                    CheckCategory::Forbidden,
+                    format!("Unexpected {} (mode {:o})", kind, e.mode),
+                )
+                .with_hint("Build-time state captured into the artifact; exclude it when staging, or allow it with --allow-special"),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpio::ParseOptions;
+
+    fn archive(paths: &[(&str, u32)]) -> CpioReader {
+        let mut out = Vec::new();
+        for (path, mode) in paths.iter().chain(&[("TRAILER!!!", 0)]) {
+            out.extend_from_slice(b"070701");
+            for field in [0, *mode, 0, 0, 1, 0, 0, 0, 0, 0, 0] {
+                out.extend_from_slice(format!("{:08x}", field).as_bytes());
+            }
+            out.extend_from_slice(format!("{:08x}{:08x}", path.len() + 1, 0).as_bytes());
+            out.extend_from_slice(path.as_bytes());
+            out.push(0);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        CpioReader::from_reader(&out[..], ParseOptions::default()).unwrap()
+    }
+
+    fn flagged(reader: &CpioReader, allowed: &[&str]) -> Vec<(String, String)> {
+        let allowed: Vec<Pattern> = allowed.iter().map(|p| Pattern::new(p).unwrap()).collect();
+        check(reader, &allowed)
+            .into_iter()
+            .inspect(|r| assert!(!r.passed && r.hint.is_some()))
+            .map(|r| (r.item, r.message.unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn test_special_files_flagged() {
+        let reader = archive(&[
+            ("run/initctl", 0o10600),
+            ("run/dbus/system_bus_socket", 0o140666),
+            ("etc/mystery", 0o170644),
+        ]);
+        assert_eq!(
+            flagged(&reader, &[]),
+            [
+                (
+                    "run/initctl".to_string(),
+                    "Unexpected FIFO (mode 10600)".to_string()
+                ),
+                (
+                    "run/dbus/system_bus_socket".to_string(),
+                    "Unexpected socket (mode 140666)".to_string()
+                ),
+                (
+                    "etc/mystery".to_string(),
+                    "Unexpected entry of unknown type (mode 170644)".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_allow_special() {
+        let reader = archive(&[
+            ("run/initctl", 0o10600),
+            ("run/dbus/system_bus_socket", 0o140666),
+        ]);
+        assert_eq!(
+            flagged(&reader, &["run/dbus/*"]),
+            [(
+                "run/initctl".to_string(),
+                "Unexpected FIFO (mode 10600)".to_string()
+            )]
+        );
+        assert!(flagged(&reader, &["run/**"]).is_empty());
+    }
+
+    #[test]
+    fn test_ordinary_types_pass() {
+        let reader = archive(&[
+            ("etc", 0o40755),
+            ("etc/hostname", 0o100644),
+            ("bin", 0o120777),
+            ("dev/null", 0o20666),
+            ("dev/sda", 0o60660),
+        ]);
+        assert!(flagged(&reader, &[]).is_empty());
+    }
+}
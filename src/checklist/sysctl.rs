@@ -0,0 +1,118 @@
+//! sysctl.d configuration validation
+//!
+//! systemd-sysctl logs and skips malformed lines, so a typo in a hardening
+//! setting never fails the boot - it just silently isn't applied. This
+//! module parses every sysctl.d file in the archive and flags lines that
+//! systemd-sysctl would reject.
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+
+/// Directories systemd-sysctl loads configuration from.
+pub const SYSCTL_DIRS: &[&str] = &["usr/lib/sysctl.d/", "etc/sysctl.d/"];
+
+/// Validate all sysctl.d files in the archive.
+pub fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    let conf_files = config_files(reader, SYSCTL_DIRS);
+    if conf_files.is_empty() {
+        return Vec::new();
+    }
+
+    let paths: Vec<&str> = conf_files.iter().map(String::as_str).collect();
+    let contents = match reader.read_files(&paths) {
+        Ok(contents) => contents,
+        Err(e) => {
+            return vec![CheckResult::fail(
+                SYSCTL_DIRS[0],
+                CheckCategory::Config,
+                format!("Cannot read: {}", e),
+            )]
+        }
+    };
+
+    let mut results = Vec::new();
+    for path in &conf_files {
+        let Some(content) = contents.get(path) else {
+            continue;
+        };
+        let issues = lint_sysctl(&String::from_utf8_lossy(content));
+
+        if issues.is_empty() {
+            results.push(CheckResult::pass(path, CheckCategory::Config));
+        }
+        for (line, message) in issues {
+            results.push(CheckResult::fail(
+                format!("{}:{}", path, line),
+                CheckCategory::Config,
+                message,
+            )
+            .with_hint("Fix the line at the source; systemd-sysctl skips it with only a journal warning"));
+        }
+    }
+
+    results
+}
+
+/// List `*.conf` regular files under any of the given directories.
+pub(crate) fn config_files(reader: &CpioReader, dirs: &[&str]) -> Vec<String> {
+    reader
+        .files()
+        .map(|e| CpioReader::normalize_path(&e.path))
+        .filter(|p| {
+            dirs.iter().any(|d| {
+                p.strip_prefix(d)
+                    .is_some_and(|name| !name.contains('/') && name.ends_with(".conf"))
+            })
+        })
+        .collect()
+}
+
+/// Lint one sysctl.d file, returning (line number, message) for each problem.
+fn lint_sysctl(content: &str) -> Vec<(usize, String)> {
+    let mut issues = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        let Some((key, _value)) = line.split_once('=') else {
+            issues.push((i + 1, "Line is not an assignment (missing '=')".to_string()));
+            continue;
+        };
+
+        // A leading '-' means "ignore failure to set", not part of the key
+        let key = key.trim();
+        let key = key.strip_prefix('-').unwrap_or(key);
+        if key.is_empty() {
+            issues.push((i + 1, "Empty sysctl key".to_string()));
+        } else if key.contains(char::is_whitespace) {
+            issues.push((i + 1, format!("Invalid sysctl key '{}'", key)));
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_sysctl() {
+        let conf = "# Hardening\n\
+                    ; also a comment\n\
+                    kernel.kptr_restrict = 2\n\
+                    -net.ipv4.conf.all.rp_filter=1\n\
+                    kernel.yama.ptrace_scope\n\
+                    = 1\n\
+                    net.core bpf_jit_harden = 2\n";
+        let issues = lint_sysctl(conf);
+
+        assert_eq!(
+            issues.iter().map(|(line, _)| *line).collect::<Vec<_>>(),
+            vec![5, 6, 7]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Timestamp policy checks (SOURCE_DATE_EPOCH)
+//!
+//! Reproducible builds clamp every file mtime to `SOURCE_DATE_EPOCH`. These
+//! checks confirm a release artifact actually honours that:
+//! - Every mtime is at or before the epoch (or exactly equal, per policy)
+//! - No mtime is zero unless the epoch itself is zero
+//!
+//! A zero mtime next to a non-zero epoch means some step stripped timestamps
+//! instead of clamping them, so the artifact won't match a rebuild.
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use crate::report::format_timestamp;
+
+/// How file mtimes must relate to SOURCE_DATE_EPOCH
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MtimePolicy {
+    /// mtime <= epoch (files older than the epoch are allowed)
+    #[default]
+    NotAfter,
+    /// mtime == epoch (every file clamped to exactly the epoch)
+    Exact,
+}
+
+impl MtimePolicy {
+    pub fn parse_name(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "not-after" | "not_after" | "le" | "clamp" => Some(MtimePolicy::NotAfter),
+            "exact" | "eq" | "equal" => Some(MtimePolicy::Exact),
+            _ => None,
+        }
+    }
+}
+
+/// Check all entry mtimes against SOURCE_DATE_EPOCH.
+///
+/// Emits one failure per offending entry, or a single pass if every entry
+/// complies.
+pub fn check(reader: &CpioReader, epoch: u64, policy: MtimePolicy) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+
+    for entry in reader.entries() {
+        if let Some(reason) = mtime_violation(u64::from(entry.mtime), epoch, policy) {
+            results.push(CheckResult::fail(
+                &entry.path,
+                CheckCategory::Timestamp,
+                reason,
+            )
+            .with_hint("Clamp mtimes to SOURCE_DATE_EPOCH when packing (cpio/mkfs.erofs), don't strip them"));
+        }
+    }
+
+    if results.is_empty() {
+        let rule = match policy {
+            MtimePolicy::NotAfter => "<=",
+            MtimePolicy::Exact => "==",
+        };
+        results.push(CheckResult::pass(
+            format!(
+                "{} entries with mtime {} SOURCE_DATE_EPOCH ({})",
+                reader.entries().len(),
+                rule,
+                show(epoch)
+            ),
+            CheckCategory::Timestamp,
+        ));
+    }
+
+    results
+}
+
+/// Return why an mtime violates the policy, or None if it complies.
+fn mtime_violation(mtime: u64, epoch: u64, policy: MtimePolicy) -> Option<String> {
+    if mtime == 0 && epoch != 0 {
+        return Some("mtime is zero (timestamp stripped, not clamped)".to_string());
+    }
+
+    match policy {
+        MtimePolicy::NotAfter if mtime > epoch => Some(format!(
+            "mtime {} is after SOURCE_DATE_EPOCH {}",
+            show(mtime),
+            show(epoch)
+        )),
+        MtimePolicy::Exact if mtime != epoch => Some(format!(
+            "mtime {} != SOURCE_DATE_EPOCH {}",
+            show(mtime),
+            show(epoch)
+        )),
+        _ => None,
+    }
+}
+
+/// Raw seconds followed by the ISO 8601 form, e.g. `1700000000 (2023-11-14T22:13:20Z)`.
+fn show(secs: u64) -> String {
+    format!("{} ({})", secs, format_timestamp(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_after_policy() {
+        let epoch = 1_700_000_000;
+        assert!(mtime_violation(epoch, epoch, MtimePolicy::NotAfter).is_none());
+        assert!(mtime_violation(epoch - 100, epoch, MtimePolicy::NotAfter).is_none());
+        assert!(mtime_violation(epoch + 1, epoch, MtimePolicy::NotAfter).is_some());
+    }
+
+    #[test]
+    fn test_exact_policy() {
+        let epoch = 1_700_000_000;
+        assert!(mtime_violation(epoch, epoch, MtimePolicy::Exact).is_none());
+        assert!(mtime_violation(epoch - 100, epoch, MtimePolicy::Exact).is_some());
+    }
+
+    #[test]
+    fn test_zero_mtime() {
+        assert!(mtime_violation(0, 1_700_000_000, MtimePolicy::NotAfter).is_some());
+        // Epoch 0 means everything is expected to be zero
+        assert!(mtime_violation(0, 0, MtimePolicy::Exact).is_none());
+    }
+}
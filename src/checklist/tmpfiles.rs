@@ -0,0 +1,251 @@
+//! tmpfiles.d configuration validation
+//!
+//! systemd-tmpfiles failures at boot are only visible in the journal. This
+//! module parses every tmpfiles.d file in the archive and flags:
+//! - Lines systemd-tmpfiles would reject (unknown type, relative path, bad mode)
+//! - User/group fields naming accounts that won't exist at boot
+//!
+//! Accounts come from etc/passwd and etc/group plus sysusers.d, since
+//! systemd-sysusers runs before systemd-tmpfiles-setup.
+
+use super::sysctl::config_files;
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use std::collections::HashSet;
+
+/// Directories systemd-tmpfiles loads configuration from.
+pub const TMPFILES_DIRS: &[&str] = &["usr/lib/tmpfiles.d/", "etc/tmpfiles.d/"];
+
+/// Directories systemd-sysusers loads configuration from.
+const SYSUSERS_DIRS: &[&str] = &["usr/lib/sysusers.d/", "etc/sysusers.d/"];
+
+const PASSWD: &str = "etc/passwd";
+const GROUP: &str = "etc/group";
+
+/// Line types accepted by systemd-tmpfiles (see tmpfiles.d(5)).
+const LINE_TYPES: &str = "fFwdDevqQpLcbCxXrRzZtThHaA";
+
+/// Modifiers that may follow the line type.
+const TYPE_MODIFIERS: &str = "+!-=~^$?";
+
+/// Accounts that exist when systemd-tmpfiles runs.
+#[derive(Debug, Default)]
+struct Accounts {
+    users: HashSet<String>,
+    groups: HashSet<String>,
+}
+
+/// Validate all tmpfiles.d files in the archive.
+pub fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    let conf_files = config_files(reader, TMPFILES_DIRS);
+    if conf_files.is_empty() {
+        return Vec::new();
+    }
+    let sysusers_files = config_files(reader, SYSUSERS_DIRS);
+
+    let mut paths: Vec<&str> = conf_files.iter().map(String::as_str).collect();
+    paths.extend(sysusers_files.iter().map(String::as_str));
+    paths.extend([PASSWD, GROUP]);
+
+    let contents = match reader.read_files(&paths) {
+        Ok(contents) => contents,
+        Err(e) => {
+            return vec![CheckResult::fail(
+                TMPFILES_DIRS[0],
+                CheckCategory::Config,
+                format!("Cannot read: {}", e),
+            )]
+        }
+    };
+    let text = |path: &str| {
+        contents
+            .get(path)
+            .map(|c| String::from_utf8_lossy(c).into_owned())
+    };
+
+    let mut accounts = Accounts::default();
+    if let Some(passwd) = text(PASSWD) {
+        accounts.users.extend(first_fields(&passwd));
+    }
+    if let Some(group) = text(GROUP) {
+        accounts.groups.extend(first_fields(&group));
+    }
+    for path in &sysusers_files {
+        if let Some(content) = text(path) {
+            accounts.add_sysusers(&content);
+        }
+    }
+
+    let mut results = Vec::new();
+    for path in &conf_files {
+        let Some(content) = text(path) else {
+            continue;
+        };
+        let issues = lint_tmpfiles(&content, &accounts);
+
+        if issues.is_empty() {
+            results.push(CheckResult::pass(path, CheckCategory::Config));
+        }
+        for (line, message) in issues {
+            results.push(CheckResult::fail(
+                format!("{}:{}", path, line),
+                CheckCategory::Config,
+                message,
+            )
+            .with_hint("Fix the line at the source; systemd-tmpfiles skips it with only a journal warning"));
+        }
+    }
+
+    results
+}
+
+/// Names from the first colon-separated field of passwd/group style files.
+fn first_fields(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .lines()
+        .filter_map(|line| line.split(':').next())
+        .filter(|name| !name.is_empty() && !name.starts_with('#'))
+        .map(str::to_string)
+}
+
+impl Accounts {
+    /// Add accounts declared in a sysusers.d file.
+    fn add_sysusers(&mut self, content: &str) {
+        for line in content.lines() {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next()) {
+                // 'u' also creates a group of the same name
+                (Some("u") | Some("u!"), Some(name)) => {
+                    self.users.insert(name.to_string());
+                    self.groups.insert(name.to_string());
+                }
+                (Some("g"), Some(name)) => {
+                    self.groups.insert(name.to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Lint one tmpfiles.d file, returning (line number, message) for each problem.
+fn lint_tmpfiles(content: &str, accounts: &Accounts) -> Vec<(usize, String)> {
+    let mut issues = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().take(5).collect();
+        let line_no = i + 1;
+
+        let (ty, path) = match fields.as_slice() {
+            [ty, path, ..] => (*ty, *path),
+            _ => {
+                issues.push((line_no, "Missing path field".to_string()));
+                continue;
+            }
+        };
+
+        let mut ty_chars = ty.chars();
+        let base = ty_chars.next().unwrap_or(' ');
+        if !LINE_TYPES.contains(base) || !ty_chars.all(|c| TYPE_MODIFIERS.contains(c)) {
+            issues.push((line_no, format!("Unknown line type '{}'", ty)));
+            continue;
+        }
+
+        if !path.starts_with('/') && !path.starts_with('%') {
+            issues.push((line_no, format!("Path '{}' is not absolute", path)));
+        }
+
+        if let Some(mode) = fields.get(2) {
+            if !is_valid_mode(mode) {
+                issues.push((line_no, format!("Invalid mode '{}'", mode)));
+            }
+        }
+
+        if let Some(user) = fields.get(3) {
+            if !is_unset(user) && !accounts.users.contains(*user) {
+                issues.push((line_no, format!("User '{}' not in passwd/sysusers", user)));
+            }
+        }
+
+        if let Some(group) = fields.get(4) {
+            if !is_unset(group) && !accounts.groups.contains(*group) {
+                issues.push((line_no, format!("Group '{}' not in group/sysusers", group)));
+            }
+        }
+    }
+
+    issues
+}
+
+/// `-`, numeric IDs and specifiers don't name an account to look up.
+fn is_unset(field: &str) -> bool {
+    field == "-" || field.contains('%') || field.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Mode is `-` or up to four octal digits, optionally prefixed by `~` or `:`.
+fn is_valid_mode(mode: &str) -> bool {
+    if mode == "-" {
+        return true;
+    }
+    let digits = mode.trim_start_matches(['~', ':']);
+    !digits.is_empty() && digits.len() <= 4 && digits.chars().all(|c| ('0'..='7').contains(&c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts() -> Accounts {
+        let mut accounts = Accounts::default();
+        accounts
+            .users
+            .extend(first_fields("root:x:0:0::/root:/bin/bash\n"));
+        accounts
+            .groups
+            .extend(first_fields("root:x:0:\nwheel:x:10:\n"));
+        accounts.add_sysusers("u systemd-journal-remote - \"Journal Remote\"\ng tty 5\n");
+        accounts
+    }
+
+    #[test]
+    fn test_lint_tmpfiles_valid() {
+        let conf = "# comment\n\
+                    d /run/sshd 0755 root root -\n\
+                    d /var/log/journal/remote 2755 systemd-journal-remote systemd-journal-remote -\n\
+                    L+ /etc/mtab - - - - ../proc/self/mounts\n\
+                    z /dev/pts/ptmx 0666 - tty -\n\
+                    d /run/user/%U 0700 %U %U -\n\
+                    f /run/utmp 0664 0 22 -\n";
+        assert!(lint_tmpfiles(conf, &accounts()).is_empty());
+    }
+
+    #[test]
+    fn test_lint_tmpfiles_errors() {
+        let conf = "d run/relative 0755 root root -\n\
+                    Y /run/bogus\n\
+                    d /run/badmode 0999 root root -\n\
+                    d /run/nouser 0755 chrony root -\n\
+                    d /run/nogroup 0755 root chrony -\n\
+                    d\n";
+        let issues = lint_tmpfiles(conf, &accounts());
+
+        assert_eq!(
+            issues.iter().map(|(line, _)| *line).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn test_is_valid_mode() {
+        assert!(is_valid_mode("-"));
+        assert!(is_valid_mode("0755"));
+        assert!(is_valid_mode("~0644"));
+        assert!(!is_valid_mode("0855"));
+        assert!(!is_valid_mode("12345"));
+    }
+}
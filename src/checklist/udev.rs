@@ -0,0 +1,359 @@
+//! Udev rule syntax validation
+//!
+//! Presence of a rules file says nothing about whether udevd can use it. A
+//! syntax error makes udevd skip the whole line, and a missing RUN helper
+//! only shows up as a journal warning - both leave /dev/disk/by-* links
+//! silently missing. This module parses every rules file in the archive and
+//! flags:
+//! - Syntax errors (unknown keys, bad operators, unquoted values)
+//! - RUN/PROGRAM/IMPORT{program} helpers not present in the archive
+//! - GOTO targets with no matching LABEL in the same file
+
+use super::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use std::collections::HashSet;
+
+/// Directories udevd loads rules from.
+pub const RULES_DIRS: &[&str] = &["usr/lib/udev/rules.d/", "etc/udev/rules.d/"];
+
+/// Relative RUN/PROGRAM paths are looked up here.
+const UDEV_LIB_DIR: &str = "usr/lib/udev/";
+
+/// Keys accepted by udevd (see udev(7)).
+const KNOWN_KEYS: &[&str] = &[
+    "ACTION",
+    "DEVPATH",
+    "KERNEL",
+    "KERNELS",
+    "NAME",
+    "SYMLINK",
+    "SUBSYSTEM",
+    "SUBSYSTEMS",
+    "DRIVER",
+    "DRIVERS",
+    "ATTR",
+    "ATTRS",
+    "SYSCTL",
+    "TAG",
+    "TAGS",
+    "TEST",
+    "PROGRAM",
+    "RESULT",
+    "ENV",
+    "CONST",
+    "OWNER",
+    "GROUP",
+    "MODE",
+    "SECLABEL",
+    "RUN",
+    "LABEL",
+    "GOTO",
+    "IMPORT",
+    "OPTIONS",
+];
+
+const OPERATORS: &[&str] = &["==", "!=", "+=", "-=", ":=", "="];
+
+/// A single `KEY{attr}<op>"value"` pair from a rule line.
+#[derive(Debug, PartialEq, Eq)]
+struct Assignment<'a> {
+    key: &'a str,
+    attr: Option<&'a str>,
+    op: &'a str,
+    value: String,
+}
+
+/// Validate all udev rules files in the archive.
+pub fn check(reader: &CpioReader) -> Vec<CheckResult> {
+    let rule_files: Vec<String> = reader
+        .files()
+        .map(|e| CpioReader::normalize_path(&e.path))
+        .filter(|p| RULES_DIRS.iter().any(|d| p.starts_with(d)) && p.ends_with(".rules"))
+        .collect();
+
+    if rule_files.is_empty() {
+        return Vec::new();
+    }
+
+    let paths: Vec<&str> = rule_files.iter().map(String::as_str).collect();
+    let contents = match reader.read_files(&paths) {
+        Ok(contents) => contents,
+        Err(e) => {
+            return vec![CheckResult::fail(
+                RULES_DIRS[0],
+                CheckCategory::UdevRule,
+                format!("Cannot read rules: {}", e),
+            )]
+        }
+    };
+
+    let mut results = Vec::new();
+    for path in &rule_files {
+        let Some(content) = contents.get(path) else {
+            continue;
+        };
+        let content = String::from_utf8_lossy(content);
+        let issues = lint_rules(&content, |program| program_exists(reader, program));
+
+        if issues.is_empty() {
+            results.push(CheckResult::pass(path, CheckCategory::UdevRule));
+        }
+        for (line, message) in issues {
+            results.push(
+                CheckResult::fail(
+                    format!("{}:{}", path, line),
+                    CheckCategory::UdevRule,
+                    message,
+                )
+                .with_hint("Fix the rule at the source; udevd skips unparseable lines silently"),
+            );
+        }
+    }
+
+    results
+}
+
+/// Check a helper program exists, allowing for merged-usr (`/bin` -> `usr/bin`).
+fn program_exists(reader: &CpioReader, program: &str) -> bool {
+    let path = if program.starts_with('/') {
+        CpioReader::normalize_path(program)
+    } else {
+        format!("{}{}", UDEV_LIB_DIR, program)
+    };
+
+    reader.exists(&path)
+        || ["bin/", "sbin/", "lib/", "lib64/"]
+            .iter()
+            .any(|prefix| path.starts_with(prefix) && reader.exists(&format!("usr/{}", path)))
+}
+
+/// Lint one rules file, returning (line number, message) for each problem.
+fn lint_rules(content: &str, program_exists: impl Fn(&str) -> bool) -> Vec<(usize, String)> {
+    let mut issues = Vec::new();
+    let mut labels = HashSet::new();
+    let mut gotos = Vec::new();
+
+    for (line_no, line) in logical_lines(content) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let assignments = match parse_line(trimmed) {
+            Ok(assignments) => assignments,
+            Err(e) => {
+                issues.push((line_no, e));
+                continue;
+            }
+        };
+
+        for a in assignments {
+            match (a.key, a.attr) {
+                ("LABEL", _) => {
+                    labels.insert(a.value);
+                }
+                ("GOTO", _) => gotos.push((line_no, a.value)),
+                ("RUN", None | Some("program")) | ("PROGRAM", _) | ("IMPORT", Some("program")) => {
+                    if let Some(program) = helper_program(&a.value) {
+                        if !program_exists(program) {
+                            issues.push((
+                                line_no,
+                                format!("{} helper not in archive: {}", a.key, program),
+                            ));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for (line_no, target) in gotos {
+        if !labels.contains(&target) {
+            issues.push((
+                line_no,
+                format!("GOTO target '{}' has no matching LABEL", target),
+            ));
+        }
+    }
+
+    issues.sort_by_key(|(line, _)| *line);
+    issues
+}
+
+/// The program a RUN/PROGRAM value executes, unless it's computed at runtime.
+fn helper_program(value: &str) -> Option<&str> {
+    let program = value.split_whitespace().next()?;
+    if program.contains('$') || program.contains('%') {
+        None
+    } else {
+        Some(program)
+    }
+}
+
+/// Join backslash-continued lines, keeping the number of the first line.
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut pending: Option<(usize, String)> = None;
+
+    for (i, line) in content.lines().enumerate() {
+        let (start, mut joined) = pending.take().unwrap_or((i + 1, String::new()));
+        match line.strip_suffix('\\') {
+            Some(head) => {
+                joined.push_str(head);
+                pending = Some((start, joined));
+            }
+            None => {
+                joined.push_str(line);
+                lines.push((start, joined));
+            }
+        }
+    }
+
+    if let Some(last) = pending {
+        lines.push(last);
+    }
+    lines
+}
+
+/// Parse a rule line into its key/operator/value assignments.
+fn parse_line(line: &str) -> Result<Vec<Assignment<'_>>, String> {
+    let mut assignments = Vec::new();
+    let mut rest = line;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        if rest.is_empty() {
+            break;
+        }
+
+        // Key, with optional {attribute}
+        let key_len = rest
+            .find(|c: char| !(c.is_ascii_uppercase() || c == '_'))
+            .unwrap_or(rest.len());
+        let key = &rest[..key_len];
+        if key.is_empty() {
+            return Err(format!("Expected key at '{}'", truncate(rest)));
+        }
+        if !KNOWN_KEYS.contains(&key) {
+            return Err(format!("Unknown key '{}'", key));
+        }
+        rest = &rest[key_len..];
+
+        let mut attr = None;
+        if let Some(after) = rest.strip_prefix('{') {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("Unterminated {{ after {}", key))?;
+            attr = Some(&after[..end]);
+            rest = &after[end + 1..];
+        }
+
+        // Operator
+        rest = rest.trim_start();
+        let op = OPERATORS
+            .iter()
+            .find(|op| rest.starts_with(**op))
+            .ok_or_else(|| format!("Missing operator after {}", key))?;
+        rest = rest[op.len()..].trim_start();
+
+        // Value: "..." or e"..." (C-style escapes)
+        let quoted = rest.strip_prefix("e\"").or_else(|| rest.strip_prefix('"'));
+        let Some(body) = quoted else {
+            return Err(format!("Value for {} must be double-quoted", key));
+        };
+        let (value, remainder) =
+            read_quoted(body).ok_or_else(|| format!("Unterminated quoted value for {}", key))?;
+        rest = remainder;
+
+        if !rest.is_empty() && !rest.starts_with(',') && !rest.starts_with(char::is_whitespace) {
+            return Err(format!("Expected ',' after value for {}", key));
+        }
+
+        assignments.push(Assignment {
+            key,
+            attr,
+            op,
+            value,
+        });
+    }
+
+    Ok(assignments)
+}
+
+/// Read a quoted string body up to the closing quote, honouring `\"`.
+fn read_quoted(body: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = body.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &body[i + 1..])),
+            '\\' => {
+                if let Some((_, escaped)) = chars.next() {
+                    if escaped != '"' {
+                        value.push('\\');
+                    }
+                    value.push(escaped);
+                }
+            }
+            _ => value.push(c),
+        }
+    }
+
+    None
+}
+
+fn truncate(s: &str) -> &str {
+    s.char_indices().nth(20).map(|(i, _)| &s[..i]).unwrap_or(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let parsed =
+            parse_line(r#"SUBSYSTEM=="block", ENV{ID_FS_UUID}=="?*", SYMLINK+="disk/by-uuid/$env{ID_FS_UUID}""#)
+                .unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[1].key, "ENV");
+        assert_eq!(parsed[1].attr, Some("ID_FS_UUID"));
+        assert_eq!(parsed[2].op, "+=");
+        assert_eq!(parsed[2].value, "disk/by-uuid/$env{ID_FS_UUID}");
+    }
+
+    #[test]
+    fn test_parse_line_errors() {
+        assert!(parse_line(r#"KERNEL=="sd*" MODE="0660""#).is_ok());
+        assert!(parse_line(r#"KERNAL=="sd*""#).is_err());
+        assert!(parse_line(r#"KERNEL sd*"#).is_err());
+        assert!(parse_line(r#"KERNEL==sd*"#).is_err());
+        assert!(parse_line(r#"KERNEL=="sd*"#).is_err());
+    }
+
+    #[test]
+    fn test_lint_rules() {
+        let rules = "# comment\n\
+                     ACTION!=\"add\", GOTO=\"end\"\n\
+                     KERNEL==\"sr*\", RUN+=\"cdrom_id --lock-media $devnode\"\n\
+                     KERNEL==\"sd*\", \\\n  IMPORT{program}=\"/usr/bin/missing-helper\"\n\
+                     GOTO=\"nowhere\"\n\
+                     LABEL=\"end\"\n";
+        let exists = |p: &str| p == "cdrom_id";
+        let issues = lint_rules(rules, exists);
+
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].0, 4);
+        assert!(issues[0].1.contains("missing-helper"));
+        assert_eq!(issues[1].0, 6);
+        assert!(issues[1].1.contains("nowhere"));
+    }
+
+    #[test]
+    fn test_helper_program_skips_substitutions() {
+        assert_eq!(helper_program("ata_id --export $devnode"), Some("ata_id"));
+        assert_eq!(helper_program("$env{HELPER}"), None);
+    }
+}
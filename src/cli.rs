@@ -0,0 +1,4565 @@
+//! The `fsdbg` command line
+//!
+//! Inspect and verify initramfs, rootfs, and ISO images without extraction.
+//! Lives in the library so the modules it drives can stay crate-private;
+//! `src/main.rs` only calls [`main`].
+
+// Doc comments on the clap types are --help text: `<FILE>` is meant literally
+#![allow(rustdoc::invalid_html_tags)]
+
+use std::ffi::OsStr;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitCode, Stdio};
+use std::time::{Instant, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+
+use crate::arch::Arch;
+use crate::boot::{BootTest, Medium};
+use crate::cache::{CachedRun, ReportCache};
+use crate::catalog::Catalog;
+use crate::checklist::auto;
+use crate::checklist::controlled::ControlledDir;
+use crate::checklist::extensions::Extension;
+use crate::checklist::probes::Probe;
+use crate::checklist::qcow2::Disk;
+use crate::checklist::timestamps::MtimePolicy;
+use crate::checklist::{
+    CheckCategory, CheckResult, ChecklistType, Grouping, Scope, VerificationReport,
+};
+use crate::cpio::{CpioReader, DuplicatePolicy, HashAlgorithm, Limits, ParseOptions};
+use crate::diff::{Change, Origin, ThreeWayChange};
+use crate::dir::DirReader;
+use crate::erofs::ErofsReader;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::ignore::IgnoreList;
+use crate::iso::IsoReader;
+use crate::nbd::{NbdConnection, NbdState};
+use crate::oci::Image;
+use crate::overlay::{DirOverlay, OverlayTree};
+use crate::qcow2::Unpacked;
+use crate::report::{Fingerprint, SavedReport};
+use crate::sanitize::Destination;
+use crate::spec::Spec;
+use crate::timeline::Granularity;
+use crate::{ArchiveFormat, ByteRange};
+
+#[derive(Parser)]
+#[command(name = "fsdbg")]
+#[command(about = "Filesystem debugging tool for LevitateOS")]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+    /// Largest entry content read into memory, e.g. 256MiB (default 1 GiB)
+    #[arg(long, global = true, value_name = "SIZE", value_parser = parse_size)]
+    max_entry_size: Option<u64>,
+    /// Most memory one archive reader may hold (default 4 GiB)
+    #[arg(long, global = true, value_name = "SIZE", value_parser = parse_size)]
+    max_memory: Option<u64>,
+}
+
+/// Location of an archive embedded in a larger file
+#[derive(Args, Default)]
+struct RangeArgs {
+    /// Archive starts at this byte offset (decimal or 0x hex)
+    #[arg(long, value_name = "BYTES", value_parser = parse_bytes, default_value = "0")]
+    offset: u64,
+    /// Archive is this many bytes long (default: to end of file)
+    #[arg(long, value_name = "BYTES", value_parser = parse_bytes)]
+    length: Option<u64>,
+}
+
+impl RangeArgs {
+    fn range(&self) -> ByteRange {
+        ByteRange::new(self.offset, self.length)
+    }
+}
+
+fn parse_bytes(s: &str) -> Result<u64, String> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|e| format!("{}: {}", s, e))
+}
+
+fn parse_size(s: &str) -> Result<u64, String> {
+    crate::release::parse_size(s).ok_or_else(|| format!("{}: not a size (e.g. 512MiB)", s))
+}
+
+/// Report output flags shared by verify and show-report
+#[derive(Args)]
+struct OutputArgs {
+    /// Show all checks including passing ones (implies --full)
+    #[arg(short, long)]
+    verbose: bool,
+    /// List every failure instead of the summary's top failures
+    #[arg(long)]
+    full: bool,
+    /// Print only a one-line verdict (the exit code carries the result)
+    #[arg(short, long, conflicts_with_all = ["verbose", "full", "porcelain"])]
+    quiet: bool,
+    /// Print one tab-separated record per failure, for scripts
+    #[arg(long, conflicts_with_all = ["verbose", "full"])]
+    porcelain: bool,
+    /// Message catalog (TOML) translating labels, categories and hints
+    #[arg(long, value_name = "FILE")]
+    catalog: Option<PathBuf>,
+    /// Group the summary and details by: category, subsystem (auth, boot, networking, ...)
+    #[arg(long, value_name = "BY", default_value = "category")]
+    group_by: String,
+}
+
+impl OutputArgs {
+    /// The --catalog reports render with; the built-in English one without it.
+    fn catalog(&self) -> Result<Catalog> {
+        Ok(match self.catalog {
+            Some(ref path) => Catalog::load(path)?,
+            None => Catalog::default(),
+        })
+    }
+
+    /// The report detail (--quiet, --full, ...) and --group-by.
+    fn mode(&self) -> Result<ReportMode> {
+        let detail = if self.quiet {
+            Detail::Quiet
+        } else if self.porcelain {
+            Detail::Porcelain
+        } else if self.verbose {
+            Detail::Verbose
+        } else if self.full {
+            Detail::Full
+        } else {
+            Detail::Summary
+        };
+        let grouping = Grouping::parse_name(&self.group_by).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown grouping: {}. Valid groupings: category, subsystem",
+                self.group_by
+            )
+        })?;
+        Ok(ReportMode { detail, grouping })
+    }
+}
+
+// Parsed once per run; boxing verify's options would only add noise
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+enum Commands {
+    /// Inspect archive contents
+    Inspect {
+        /// Path to archive file ('-' reads stdin)
+        archive: PathBuf,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// List parsing oddities (duplicates, bad padding, ...) and fail if any (CPIO only)
+        #[arg(long)]
+        strict: bool,
+        /// List a truncated archive up to where it ends, and fail (CPIO only)
+        #[arg(long)]
+        lenient: bool,
+        /// Entry a duplicated path resolves to: first, last (as the kernel), error
+        #[arg(long, value_name = "POLICY", default_value = "last")]
+        duplicates: String,
+        /// List the N largest files and directories
+        #[arg(long, value_name = "N")]
+        top: Option<usize>,
+        /// List every file with its content digest (sha256; CPIO only)
+        #[arg(long, value_name = "ALGORITHM")]
+        hash: Option<String>,
+        /// List every file with its content type: elf, script, gzip, ... (CPIO only)
+        #[arg(long)]
+        magic: bool,
+        /// Dump the volume descriptors and El Torito boot catalog (ISO only)
+        #[arg(long)]
+        iso_headers: bool,
+        /// Print --iso-headers as JSON
+        #[arg(long, requires = "iso_headers")]
+        json: bool,
+    },
+    /// Identify a file's format
+    Detect {
+        /// File to probe ('-' reads stdin)
+        file: PathBuf,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// List every plausible format with its confidence
+        #[arg(long)]
+        all: bool,
+    },
+    /// Check archive structure (padding, trailers, descriptors, checksums)
+    Doctor {
+        /// Path to archive file ('-' reads stdin)
+        archive: PathBuf,
+        #[command(flatten)]
+        range: RangeArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// List an initramfs the way dracut's lsinitrd does
+    Lsinitrd {
+        /// Path to initramfs ('-' reads stdin)
+        image: PathBuf,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// Only list the dracut modules
+        #[arg(short, long)]
+        r#mod: bool,
+        /// Prefix each file with its content digest (sha256)
+        #[arg(long, value_name = "ALGORITHM")]
+        hash: Option<String>,
+    },
+    /// Group entries by mtime to spot files packed at unexpected times (CPIO only)
+    Timeline {
+        /// Path to archive file ('-' reads stdin)
+        archive: PathBuf,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// Bucket width: second, minute, hour, day
+        #[arg(long, value_name = "WIDTH", default_value = "day")]
+        by: String,
+        /// Paths listed per group outside the bulk of the image
+        #[arg(long, value_name = "N", default_value = "10")]
+        limit: usize,
+    },
+    /// Copy the embedded initramfs out of a UKI or Android boot image
+    ExtractInitrd {
+        /// Unified kernel image (PE/EFI) or Android boot.img
+        image: PathBuf,
+        /// Where to write the initramfs
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Print the model and compatible strings of device tree blobs
+    InspectDtb {
+        /// Device tree blobs (.dtb)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Verify archive against checklist
+    Verify {
+        /// Path to archive file, UKI, Android boot.img, staged directory tree, container image
+        /// (OCI layout or docker save archive) or netboot tarball
+        /// ('-' reads stdin)
+        archive: PathBuf,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// Checklist type (install-initramfs, live-initramfs, rootfs, iso, auth-audit, qcow2, rpmdb, netboot),
+        /// or auto to pick one from the artifact's contents
+        #[arg(short, long, value_name = "TYPE")]
+        r#type: String,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Save the full report with run metadata as JSON (see show-report)
+        #[arg(long, value_name = "FILE")]
+        save_report: Option<PathBuf>,
+        /// Write a shell script fixing the failures that have a mechanical fix
+        /// (missing .wants symlinks, wrong symlink targets, wrong modes) in the staged tree
+        #[arg(long, value_name = "FILE")]
+        fix_script: Option<PathBuf>,
+        /// Detached signature to verify and include in the report
+        #[arg(long, value_name = "FILE", requires = "keyring")]
+        sig: Option<PathBuf>,
+        /// GPG keyring or ed25519 public key for --sig
+        #[arg(long, value_name = "PATH", requires = "sig")]
+        keyring: Option<PathBuf>,
+        /// Check all file mtimes against this SOURCE_DATE_EPOCH (CPIO only)
+        #[arg(long, value_name = "SECONDS")]
+        source_date_epoch: Option<u64>,
+        /// Timestamp policy for --source-date-epoch (not-after, exact)
+        #[arg(long, value_name = "POLICY", default_value = "not-after")]
+        mtime_policy: String,
+        /// Extra glob where zero-byte files are errors (repeatable, CPIO only)
+        #[arg(long, value_name = "GLOB")]
+        empty_glob: Vec<String>,
+        /// Glob where FIFOs and sockets are expected (repeatable, CPIO only)
+        #[arg(long, value_name = "GLOB")]
+        allow_special: Vec<String>,
+        /// Glob where private keys and credentials are expected, e.g. test fixtures (repeatable, CPIO only)
+        #[arg(long, value_name = "GLOB")]
+        allow_secret: Vec<String>,
+        /// Live overlay directory to verify instead of the ISO's live/overlay (ISO only)
+        #[arg(long, value_name = "DIR")]
+        overlay_dir: Option<PathBuf>,
+        /// Lower rootfs (CPIO/EROFS) the --overlay-dir whiteouts must exist in
+        #[arg(long, value_name = "FILE", requires = "overlay_dir")]
+        lower: Option<PathBuf>,
+        /// List only the root and the top-level directories the checks read, natively,
+        /// instead of the whole image; lints over every file cover only those (ISO only)
+        #[arg(long)]
+        fast_listing: bool,
+        /// Ignore paths matching this glob (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        ignore: Vec<String>,
+        /// Read ignore globs from a file, one per line (repeatable)
+        #[arg(long, value_name = "FILE")]
+        ignore_file: Vec<PathBuf>,
+        /// Fail on parsing oddities (duplicates, bad padding, ...) (CPIO only)
+        #[arg(long)]
+        strict: bool,
+        /// Also fail on entry names that aren't UTF-8, hold control characters
+        /// or trailing spaces, or run over 255 bytes (CPIO only; ISOs always
+        /// check their names)
+        #[arg(long)]
+        lint_names: bool,
+        /// Check a truncated archive up to where it ends; the report is marked partial (CPIO only)
+        #[arg(long)]
+        lenient: bool,
+        /// Entry a duplicated path resolves to: first, last (as the kernel), error
+        #[arg(long, value_name = "POLICY", default_value = "last")]
+        duplicates: String,
+        /// Show the slowest checks and time per category
+        #[arg(long)]
+        timings: bool,
+        /// Replay the cached report when the artifact is unchanged since a previous run
+        #[arg(long)]
+        cache: bool,
+        /// Cache directory (default: $XDG_CACHE_HOME/fsdbg)
+        #[arg(long, value_name = "DIR", requires = "cache")]
+        cache_dir: Option<PathBuf>,
+        /// Also replay when only paths no check looks at changed (CPIO only)
+        #[arg(long, requires = "cache")]
+        incremental: bool,
+        /// Run the tree's own tools over a staged directory tree in a bubblewrap sandbox:
+        /// libs (the loader finds every ELF's libraries), units (systemd-analyze verify) (repeatable)
+        #[arg(long, value_name = "PROBE")]
+        probe: Vec<String>,
+
+        /// Skip checklist extensions (/etc/fsdbg/checks.d, <artifact>.checks.toml)
+        #[arg(long)]
+        no_extensions: bool,
+
+        /// distro-spec snapshot to verify against instead of the compiled-in lists
+        #[arg(long, value_name = "FILE")]
+        spec: Option<PathBuf>,
+
+        /// Check only what the profile keeps of the spec: desktop (default), server, minimal, secure-boot, or one from a profile file
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// Extra profile definitions (TOML), besides /etc/fsdbg/profiles.d
+        #[arg(long, value_name = "FILE")]
+        profile_file: Vec<PathBuf>,
+
+        /// Target architecture: x86_64, aarch64 (default: detected from the artifact)
+        #[arg(long, value_name = "ARCH")]
+        arch: Option<String>,
+
+        /// Mount the image with qemu-nbd and sudo instead of reading it rootless (qcow2 only)
+        #[arg(long)]
+        nbd: bool,
+        /// Print the privileged commands (sudo, qemu-nbd, mount) and run none (qcow2 only)
+        #[arg(long)]
+        dry_run: bool,
+        /// Never escalate with sudo; unless root, report what can't be checked (qcow2 --nbd only)
+        #[arg(long, requires = "nbd")]
+        no_sudo: bool,
+        /// Unlock a LUKS-encrypted root with the passphrase in FILE (qcow2 --nbd only)
+        #[arg(
+            long,
+            value_name = "FILE",
+            requires = "nbd",
+            conflicts_with = "keyfile"
+        )]
+        passphrase_file: Option<PathBuf>,
+        /// Unlock a LUKS-encrypted root with this key file (qcow2 --nbd only)
+        #[arg(long, value_name = "FILE", requires = "nbd")]
+        keyfile: Option<PathBuf>,
+    },
+    /// Print a report saved with verify --save-report
+    ShowReport {
+        /// Saved report (JSON)
+        report: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Show the slowest checks and time per category
+        #[arg(long)]
+        timings: bool,
+        /// Write a shell script fixing the failures that have a mechanical fix
+        #[arg(long, value_name = "FILE")]
+        fix_script: Option<PathBuf>,
+    },
+    /// Boot an ISO or qcow2 image under QEMU and wait for a login prompt
+    BootTest {
+        /// ISO or qcow2 image
+        image: PathBuf,
+        /// Seconds to wait for the login prompt (or --marker)
+        #[arg(long, value_name = "SECONDS", default_value_t = 120)]
+        timeout: u64,
+        /// Console text that means booted, instead of a login prompt (repeatable)
+        #[arg(long, value_name = "TEXT")]
+        marker: Vec<String>,
+        /// Target architecture: x86_64, aarch64 (default: detected from an ISO, else the host's)
+        #[arg(long, value_name = "ARCH")]
+        arch: Option<String>,
+        /// UEFI firmware image (default: OVMF/AAVMF where distributions install it)
+        #[arg(long, value_name = "FILE")]
+        firmware: Option<PathBuf>,
+        /// Guest memory in MiB
+        #[arg(long, value_name = "MIB", default_value_t = 2048)]
+        memory: u32,
+        /// Emulate the CPU (TCG) even where KVM is available
+        #[arg(long)]
+        no_kvm: bool,
+        /// Write the whole serial console output to FILE
+        #[arg(long, value_name = "FILE")]
+        serial_log: Option<PathBuf>,
+        /// Add the result to a report saved with verify --save-report (rewritten in place)
+        #[arg(long, value_name = "FILE", conflicts_with = "save_report")]
+        report: Option<PathBuf>,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Save the report with run metadata as JSON (see show-report)
+        #[arg(long, value_name = "FILE")]
+        save_report: Option<PathBuf>,
+    },
+    /// Classify the failure signatures in a serial console log
+    BootLog {
+        /// Console log, e.g. from boot-test --serial-log
+        log: PathBuf,
+        /// Report saved with verify --save-report, to point at the rules that failed there
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Disconnect qemu-nbd connections left by killed `verify --nbd` runs
+    CleanupNbd {
+        /// Print the commands instead of running them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Tear down a `verify --nbd` connection once its run exits (internal)
+    #[command(hide = true)]
+    NbdSupervise {
+        /// Connection record in the NBD state directory
+        record: PathBuf,
+    },
+    /// Explain an exit code (all of them without CODE)
+    ExplainExit {
+        /// Exit code to explain
+        code: Option<u8>,
+    },
+    /// Show the distro-spec version checklists verify against
+    SpecVersion {
+        /// Write the spec's component lists as a JSON snapshot (for verify --spec)
+        #[arg(long, conflicts_with = "compare")]
+        dump: bool,
+        /// Snapshot to compare against: lists it adds to or removes from this spec
+        #[arg(long, value_name = "FILE")]
+        compare: Option<PathBuf>,
+    },
+    /// List checklist profiles (verify --profile) and what they leave out
+    Profiles {
+        /// Extra profile definitions (TOML), besides /etc/fsdbg/profiles.d
+        #[arg(long, value_name = "FILE")]
+        profile_file: Vec<PathBuf>,
+    },
+    /// Show and check an artifact's provenance manifest (build, commit, distro-spec)
+    Provenance {
+        /// Artifact with an embedded /.fsdbg-manifest.json, or a sidecar <artifact>.fsdbg-manifest.json
+        artifact: PathBuf,
+        /// distro-spec snapshot to check against instead of the compiled-in spec
+        #[arg(long, value_name = "FILE")]
+        spec: Option<PathBuf>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Gate a release: checklists, budgets, signatures and cross-artifact checks, one signed verdict
+    ReleaseGate {
+        /// Live ISO
+        #[arg(long, value_name = "FILE")]
+        iso: Option<PathBuf>,
+        /// Root filesystem image (EROFS)
+        #[arg(long, value_name = "FILE")]
+        rootfs: Option<PathBuf>,
+        /// Initramfs (CPIO, UKI or Android boot.img)
+        #[arg(long, value_name = "FILE")]
+        initramfs: Option<PathBuf>,
+        /// VM image
+        #[arg(long, value_name = "FILE")]
+        qcow2: Option<PathBuf>,
+        /// Release policy (TOML): checklists, budgets, signatures, signing key
+        #[arg(long, value_name = "FILE")]
+        policy: PathBuf,
+        /// Where to write the JSON verdict; its signature goes to <FILE>.sig
+        #[arg(long, short, value_name = "FILE")]
+        output: PathBuf,
+        /// ed25519 secret key to sign the verdict with, instead of the policy's
+        #[arg(long, value_name = "FILE")]
+        signing_key: Option<PathBuf>,
+        /// distro-spec snapshot to verify against instead of the compiled-in spec
+        #[arg(long, value_name = "FILE")]
+        spec: Option<PathBuf>,
+        #[command(flatten)]
+        report: OutputArgs,
+    },
+    /// Verify a detached GPG or ed25519 signature over an artifact
+    VerifySignature {
+        /// Path to signed artifact
+        artifact: PathBuf,
+        /// Detached signature file
+        #[arg(long, value_name = "FILE")]
+        sig: PathBuf,
+        /// GPG keyring (GPG signatures) or public key file (ed25519 signatures)
+        #[arg(long, value_name = "PATH")]
+        keyring: PathBuf,
+    },
+    /// Recompute the media checksum embedded in an ISO (checkisomd5), or implant one
+    VerifyMedia {
+        /// Path to ISO image
+        iso: PathBuf,
+        /// Implant a checksum (implantisomd5) instead of verifying it
+        #[arg(long)]
+        implant: bool,
+        /// Replace a checksum the ISO already has
+        #[arg(long, requires = "implant")]
+        force: bool,
+    },
+    /// Check that all symlinks resolve
+    CheckSymlinks {
+        /// Path to archive file ('-' reads stdin)
+        archive: PathBuf,
+        #[command(flatten)]
+        range: RangeArgs,
+    },
+    /// Export archive statistics as Prometheus metrics
+    Metrics {
+        /// Path to archive file ('-' reads stdin)
+        archive: PathBuf,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// Also run this checklist and export its pass/fail counts
+        #[arg(short, long, value_name = "TYPE")]
+        r#type: Option<String>,
+        /// Write to this file (atomically, for the textfile collector) instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Serve inspect/verify results as JSON over HTTP
+    Serve {
+        /// Address to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+        listen: String,
+        /// Allow opening artifacts under this directory; without it, only uploads
+        #[arg(long, value_name = "DIR")]
+        root: Option<PathBuf>,
+        /// Largest upload body accepted, e.g. 512MiB (default 4 GiB)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_upload: Option<u64>,
+    },
+    /// Serve JSON-RPC verification requests on stdin/stdout
+    #[cfg(feature = "rpc")]
+    Rpc {
+        /// Maximum concurrent verifications
+        #[arg(short, long, default_value_t = 4)]
+        jobs: usize,
+    },
+    /// Compare two archives, or two builds against a common base
+    Diff {
+        /// First archive
+        #[arg(required_unless_present = "base")]
+        archive1: Option<PathBuf>,
+        /// Second archive
+        #[arg(required_unless_present = "base")]
+        archive2: Option<PathBuf>,
+        /// Common base for a three-way diff (requires --ours and --theirs)
+        #[arg(long, requires_all = ["ours", "theirs"], conflicts_with_all = ["archive1", "archive2"])]
+        base: Option<PathBuf>,
+        /// First build to compare against --base
+        #[arg(long, requires = "base")]
+        ours: Option<PathBuf>,
+        /// Second build to compare against --base
+        #[arg(long, requires = "base")]
+        theirs: Option<PathBuf>,
+        /// Only diff paths referenced by this checklist type, grouped by category
+        #[arg(long, value_name = "TYPE")]
+        scope: Option<String>,
+        /// Ignore paths matching this glob (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        ignore: Vec<String>,
+        /// Read ignore globs from a file, one per line (repeatable)
+        #[arg(long, value_name = "FILE")]
+        ignore_file: Vec<PathBuf>,
+        /// Compare live ISOs as composed root filesystems: attribute each
+        /// change to the rootfs or the overlay, and flag overlay files that
+        /// shadow rootfs files
+        #[arg(long, conflicts_with = "base")]
+        layers: bool,
+    },
+}
+
+pub fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli) {
+        Ok(success) => {
+            if success {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(1)
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", error_text(&e));
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+/// Exit code of a failed command: that of the first `FsdbgError` in the
+/// chain, a bare I/O error counting as one (E005, exit 2). The rest are
+/// argument checks (`bail!`), also exit 2.
+fn exit_code(e: &anyhow::Error) -> u8 {
+    e.chain()
+        .find_map(|cause| {
+            cause
+                .downcast_ref::<FsdbgError>()
+                .map(FsdbgError::code)
+                .or_else(|| cause.is::<std::io::Error>().then_some(ErrorCode::IoError))
+        })
+        .map_or(2, ErrorCode::exit_code)
+}
+
+/// An error with the context added to it, down to the first FsdbgError:
+/// its message already says what its source does.
+fn error_text(e: &anyhow::Error) -> String {
+    let mut parts = Vec::new();
+    for cause in e.chain() {
+        parts.push(cause.to_string());
+        if cause.is::<FsdbgError>() {
+            break;
+        }
+    }
+    parts.join(": ")
+}
+
+fn run(cli: Cli) -> Result<bool> {
+    let defaults = Limits::default();
+    let limits = Limits {
+        max_entry: cli.max_entry_size.unwrap_or(defaults.max_entry),
+        max_total: cli.max_memory.unwrap_or(defaults.max_total),
+        ..defaults
+    };
+    match cli.command {
+        Commands::Inspect {
+            archive,
+            range,
+            strict,
+            lenient,
+            duplicates,
+            top,
+            hash,
+            magic,
+            iso_headers,
+            json,
+        } => {
+            let input = Input::new(archive, &range)?;
+            if iso_headers {
+                cmd_iso_headers(input.path(), json)
+            } else {
+                let options = ParseOptions {
+                    hash: hash.as_deref().map(parse_hash_algorithm).transpose()?,
+                    lenient,
+                    limits,
+                };
+                cmd_inspect(
+                    input.path(),
+                    strict,
+                    options,
+                    parse_duplicate_policy(&duplicates)?,
+                    top,
+                    magic,
+                )
+            }
+        }
+        Commands::Verify {
+            archive,
+            range,
+            r#type,
+            output,
+            save_report,
+            fix_script,
+            sig,
+            keyring,
+            source_date_epoch,
+            mtime_policy,
+            empty_glob,
+            allow_special,
+            allow_secret,
+            overlay_dir,
+            lower,
+            fast_listing,
+            ignore,
+            ignore_file,
+            strict,
+            lint_names,
+            lenient,
+            duplicates,
+            timings,
+            cache,
+            cache_dir,
+            incremental,
+            probe,
+            no_extensions,
+            spec,
+            profile,
+            profile_file,
+            arch,
+            nbd,
+            dry_run,
+            no_sudo,
+            passphrase_file,
+            keyfile,
+        } => {
+            let arch = arch
+                .map(|name| {
+                    Arch::parse_name(&name).ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Unknown architecture: {}. Valid architectures: x86_64, aarch64",
+                            name
+                        )
+                    })
+                })
+                .transpose()?;
+            let spec = load_spec(spec.as_deref(), profile.as_deref(), &profile_file)?;
+            // Spool stdin first; `-` has no sidecar to discover
+            let input = Input::new(archive, &range)?;
+            let extensions = if no_extensions {
+                Vec::new()
+            } else {
+                input.extensions()?
+            };
+            let cache = match (cache, cache_dir) {
+                (false, _) => None,
+                (true, Some(dir)) => Some(ReportCache::new(dir)),
+                (true, None) => Some(ReportCache::new(ReportCache::default_dir().context(
+                    "No cache directory: set XDG_CACHE_HOME or HOME, or pass --cache-dir",
+                )?)),
+            };
+            let catalog = output.catalog()?;
+            let signature = sig.as_deref().zip(keyring.as_deref());
+            let timestamps = match source_date_epoch {
+                Some(epoch) => {
+                    let policy = MtimePolicy::parse_name(&mtime_policy).ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Unknown mtime policy: {}. Valid policies: not-after, exact",
+                            mtime_policy
+                        )
+                    })?;
+                    Some((epoch, policy))
+                }
+                None => None,
+            };
+            let probes = probe
+                .iter()
+                .map(|name| parse_probe(name))
+                .collect::<Result<Vec<_>>>()?;
+            let options = VerifyOptions {
+                mode: output.mode()?,
+                catalog,
+                save_report: save_report.as_deref(),
+                fix_script: fix_script.as_deref(),
+                signature,
+                timestamps,
+                empty_globs: &empty_glob,
+                special_globs: &allow_special,
+                secret_globs: &allow_secret,
+                overlay_dir: overlay_dir.as_deref(),
+                lower: lower.as_deref(),
+                fast_listing,
+                ignore: load_ignores(&ignore, &ignore_file)?,
+                strict,
+                lint_names,
+                lenient,
+                duplicates: parse_duplicate_policy(&duplicates)?,
+                timings,
+                cache,
+                incremental,
+                probes: &probes,
+                extensions,
+                spec: Some(&spec),
+                limits,
+                arch,
+                dry_run,
+                no_sudo,
+                nbd,
+                unlock: match (&passphrase_file, &keyfile) {
+                    (Some(file), _) => Some(LuksKey::Passphrase(file)),
+                    (_, Some(file)) => Some(LuksKey::Keyfile(file)),
+                    _ => None,
+                },
+            };
+            cmd_verify(input.path(), &r#type, &options)
+        }
+        Commands::Detect { file, range, all } => cmd_detect(Input::new(file, &range)?.path(), all),
+        Commands::Doctor {
+            archive,
+            range,
+            output,
+        } => cmd_doctor(
+            Input::new(archive, &range)?.path(),
+            output.mode()?,
+            &output.catalog()?,
+        ),
+        Commands::Lsinitrd {
+            image,
+            range,
+            r#mod,
+            hash,
+        } => cmd_lsinitrd(
+            Input::new(image, &range)?.path(),
+            r#mod,
+            ParseOptions {
+                hash: hash.as_deref().map(parse_hash_algorithm).transpose()?,
+                limits,
+                ..Default::default()
+            },
+        ),
+        Commands::Timeline {
+            archive,
+            range,
+            by,
+            limit,
+        } => {
+            let granularity = Granularity::parse_name(&by).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown timeline width: {}. Valid widths: second, minute, hour, day",
+                    by
+                )
+            })?;
+            cmd_timeline(
+                Input::new(archive, &range)?.path(),
+                granularity,
+                limit,
+                limits,
+            )
+        }
+        Commands::ExtractInitrd { image, output } => cmd_extract_initrd(&image, &output),
+        Commands::InspectDtb { files } => cmd_inspect_dtb(&files),
+        Commands::ShowReport {
+            report,
+            output,
+            timings,
+            fix_script,
+        } => cmd_show_report(
+            &report,
+            output.mode()?,
+            &output.catalog()?,
+            timings,
+            fix_script.as_deref(),
+        ),
+        Commands::BootTest {
+            image,
+            timeout,
+            marker,
+            arch,
+            firmware,
+            memory,
+            no_kvm,
+            serial_log,
+            report,
+            output,
+            save_report,
+        } => {
+            let catalog = output.catalog()?;
+            let medium = Medium::detect(&image)?;
+            let arch = match arch {
+                Some(name) => Arch::parse_name(&name).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown architecture: {}. Valid architectures: x86_64, aarch64",
+                        name
+                    )
+                })?,
+                None => (medium == Medium::Iso)
+                    .then(|| IsoReader::open(&image).ok())
+                    .flatten()
+                    .and_then(|reader| crate::arch::detect_iso(&reader))
+                    .or_else(|| Arch::parse_name(std::env::consts::ARCH))
+                    .unwrap_or(Arch::X86_64),
+            };
+            let mut test = BootTest::new(medium, arch);
+            test.timeout = std::time::Duration::from_secs(timeout);
+            if !marker.is_empty() {
+                test.markers = marker;
+            }
+            test.firmware = firmware;
+            test.memory = memory;
+            test.kvm = !no_kvm;
+            cmd_boot_test(
+                &image,
+                &test,
+                serial_log.as_deref(),
+                report.as_deref(),
+                save_report.as_deref(),
+                output.mode()?,
+                &catalog,
+            )
+        }
+        Commands::BootLog {
+            log,
+            report,
+            output,
+        } => cmd_boot_log(&log, report.as_deref(), output.mode()?, &output.catalog()?),
+        Commands::CleanupNbd { dry_run } => cmd_cleanup_nbd(dry_run),
+        Commands::NbdSupervise { record } => cmd_nbd_supervise(&record),
+        Commands::ExplainExit { code } => cmd_explain_exit(code),
+        Commands::SpecVersion { dump, compare } => cmd_spec_version(dump, compare.as_deref()),
+        Commands::Profiles { profile_file } => cmd_profiles(&profile_file),
+        Commands::Provenance {
+            artifact,
+            spec,
+            output,
+        } => {
+            let spec = load_spec(spec.as_deref(), None, &[])?;
+            cmd_provenance(&artifact, &spec, output.mode()?, &output.catalog()?)
+        }
+        Commands::ReleaseGate {
+            iso,
+            rootfs,
+            initramfs,
+            qcow2,
+            policy,
+            output,
+            signing_key,
+            spec,
+            report,
+        } => {
+            use crate::release::{Artifact, Role};
+            let spec = load_spec(spec.as_deref(), None, &[])?;
+            let catalog = report.catalog()?;
+            let artifacts: Vec<Artifact> = [
+                (Role::Iso, iso),
+                (Role::Rootfs, rootfs),
+                (Role::Initramfs, initramfs),
+                (Role::Qcow2, qcow2),
+            ]
+            .into_iter()
+            .filter_map(|(role, path)| Some(Artifact { role, path: path? }))
+            .collect();
+            if artifacts.is_empty() {
+                bail!(
+                    "Nothing to gate: pass at least one of --iso, --rootfs, --initramfs, --qcow2"
+                );
+            }
+            cmd_release_gate(
+                &artifacts,
+                &policy,
+                &output,
+                &spec,
+                signing_key.as_deref(),
+                report.mode()?,
+                &catalog,
+            )
+        }
+        Commands::VerifySignature {
+            artifact,
+            sig,
+            keyring,
+        } => cmd_verify_signature(&artifact, &sig, &keyring),
+        Commands::VerifyMedia {
+            iso,
+            implant,
+            force,
+        } => cmd_verify_media(&iso, implant, force),
+        Commands::CheckSymlinks { archive, range } => {
+            cmd_check_symlinks(Input::new(archive, &range)?.path(), limits)
+        }
+        Commands::Serve {
+            listen,
+            root,
+            max_upload,
+        } => {
+            let mut server = crate::server::Server::new(root.as_deref())?;
+            if let Some(bytes) = max_upload {
+                server = server.with_max_upload(bytes);
+            }
+            eprintln!("Listening on http://{}", listen);
+            server.run(&listen)?;
+            Ok(true)
+        }
+        #[cfg(feature = "rpc")]
+        Commands::Rpc { jobs } => {
+            crate::rpc::serve(std::io::stdin().lock(), std::io::stdout(), jobs)?;
+            Ok(true)
+        }
+        Commands::Metrics {
+            archive,
+            range,
+            r#type,
+            output,
+        } => cmd_metrics(
+            Input::new(archive, &range)?.path(),
+            r#type.as_deref(),
+            output.as_deref(),
+            limits,
+        ),
+        Commands::Diff {
+            archive1,
+            archive2,
+            base,
+            ours,
+            theirs,
+            scope,
+            ignore,
+            ignore_file,
+            layers,
+        } => {
+            let ignore = load_ignores(&ignore, &ignore_file)?;
+            let stdin_args = [&base, &ours, &theirs, &archive1, &archive2]
+                .iter()
+                .filter(|arg| arg.as_deref() == Some(Path::new(STDIN)))
+                .count();
+            if stdin_args > 1 {
+                bail!("Only one archive can be read from stdin");
+            }
+            match (base, ours, theirs, archive1, archive2) {
+                (Some(base), Some(ours), Some(theirs), _, _) => cmd_diff3(
+                    Input::new(base, &RangeArgs::default())?.path(),
+                    Input::new(ours, &RangeArgs::default())?.path(),
+                    Input::new(theirs, &RangeArgs::default())?.path(),
+                    scope.as_deref(),
+                    limits,
+                    &ignore,
+                ),
+                (_, _, _, Some(archive1), Some(archive2)) if layers => cmd_diff_layers(
+                    Input::new(archive1, &RangeArgs::default())?.path(),
+                    Input::new(archive2, &RangeArgs::default())?.path(),
+                    scope.as_deref(),
+                    &ignore,
+                ),
+                (_, _, _, Some(archive1), Some(archive2)) => cmd_diff(
+                    Input::new(archive1, &RangeArgs::default())?.path(),
+                    Input::new(archive2, &RangeArgs::default())?.path(),
+                    scope.as_deref(),
+                    limits,
+                    &ignore,
+                ),
+                _ => bail!("diff needs two archives, or --base with --ours and --theirs"),
+            }
+        }
+    }
+}
+
+/// Archive argument meaning "read from stdin"
+const STDIN: &str = "-";
+
+/// An archive path argument, with `-` spooled from stdin and an embedded
+/// range (--offset/--length) carved out to temp files.
+struct Input {
+    path: PathBuf,
+    /// The path as given; None for stdin
+    named: Option<PathBuf>,
+    /// Keep the temp copies alive until the command finishes
+    _spool: Option<tempfile::NamedTempFile>,
+    _carved: Option<tempfile::NamedTempFile>,
+}
+
+impl Input {
+    fn new(path: PathBuf, range: &RangeArgs) -> Result<Self> {
+        let mut input = Self {
+            named: (path != Path::new(STDIN)).then(|| path.clone()),
+            path,
+            _spool: None,
+            _carved: None,
+        };
+        if input.path == Path::new(STDIN) {
+            let spool = crate::spool(std::io::stdin().lock()).context("Failed to read stdin")?;
+            input.path = spool.path().to_path_buf();
+            input._spool = Some(spool);
+        }
+        let range = range.range();
+        if !range.is_whole() {
+            let carved = crate::carve(&input.path, range)?;
+            input.path = carved.path().to_path_buf();
+            input._carved = Some(carved);
+        }
+        Ok(input)
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Extension files for the input; stdin has no sidecar.
+    fn extensions(&self) -> Result<Vec<Extension>> {
+        let files = crate::checklist::extensions::discover(self.named.as_deref());
+        Ok(files
+            .iter()
+            .map(|file| crate::checklist::extensions::load(file))
+            .collect::<Result<_, _>>()?)
+    }
+}
+
+fn parse_duplicate_policy(name: &str) -> Result<DuplicatePolicy> {
+    DuplicatePolicy::parse_name(name).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unknown duplicate policy: {}. Valid policies: first, last, error",
+            name
+        )
+    })
+}
+
+fn parse_probe(name: &str) -> Result<Probe> {
+    Probe::parse_name(name)
+        .ok_or_else(|| anyhow::anyhow!("Unknown probe: {}. Valid probes: libs, units", name))
+}
+
+fn parse_hash_algorithm(name: &str) -> Result<HashAlgorithm> {
+    HashAlgorithm::parse_name(name)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported hash algorithm: {}. Supported: sha256", name))
+}
+
+fn cmd_inspect(
+    path: &Path,
+    strict: bool,
+    options: ParseOptions,
+    duplicates: DuplicatePolicy,
+    top: Option<usize>,
+    magic: bool,
+) -> Result<bool> {
+    let format = crate::detect_format(path).context("Failed to detect archive format")?;
+    let cpio = matches!(format, ArchiveFormat::Cpio | ArchiveFormat::CpioGzip);
+    if strict && !cpio {
+        bail!("--strict requires a CPIO archive");
+    }
+    if options.lenient && !cpio {
+        bail!("--lenient requires a CPIO archive");
+    }
+    if options.hash.is_some() && !cpio {
+        bail!("--hash requires a CPIO archive");
+    }
+    if magic && !cpio {
+        bail!("--magic requires a CPIO archive");
+    }
+
+    println!("=== Archive: {} ===", path.display());
+    println!("Format: {}", format_name(&format));
+
+    let mut clean = true;
+    let entries = match format {
+        ArchiveFormat::Cpio | ArchiveFormat::CpioGzip => {
+            let reader = CpioReader::open_with(path, ByteRange::default(), options)?
+                .with_duplicate_policy(duplicates)?;
+            let stats = reader.stats();
+
+            println!(
+                "Entries: {} files, {} directories, {} symlinks",
+                stats.files, stats.directories, stats.symlinks
+            );
+            if stats.other > 0 {
+                let char_devices = match stats.whiteouts {
+                    0 => "char devices".to_string(),
+                    n => format!("char devices ({} overlayfs whiteouts)", n),
+                };
+                let breakdown = [
+                    (stats.char_devices, char_devices.as_str()),
+                    (stats.block_devices, "block devices"),
+                    (stats.fifos, "fifos"),
+                    (stats.sockets, "sockets"),
+                    (stats.unknown, "unknown type"),
+                ]
+                .iter()
+                .filter(|(count, _)| *count > 0)
+                .map(|(count, kind)| format!("{} {}", count, kind))
+                .collect::<Vec<_>>();
+                println!("Special: {}", breakdown.join(", "));
+            }
+            println!("Total size: {} bytes (uncompressed)", stats.total_size);
+            if let Some(truncated) = reader.truncated() {
+                println!("Truncated: {}; everything below is partial", truncated);
+                clean = false;
+            }
+
+            let conflicts = reader.duplicates();
+            if !conflicts.is_empty() {
+                let kept = match duplicates {
+                    DuplicatePolicy::First => "first entry used",
+                    DuplicatePolicy::Last => "last entry used",
+                    DuplicatePolicy::Error => "none: duplicates are an error",
+                };
+                println!(
+                    "Duplicates: {} paths stored more than once ({})",
+                    conflicts.len(),
+                    kept
+                );
+                for (path, count) in &conflicts {
+                    println!("  {} ({} entries)", path, count);
+                }
+            }
+            println!();
+
+            // Show top-level entries
+            println!("Top-level structure:");
+            let mut shown = std::collections::HashSet::new();
+            for entry in reader.entries() {
+                let top = entry.path.split('/').next().unwrap_or(&entry.path);
+                if shown.insert(top.to_string()) {
+                    if entry.is_symlink() {
+                        if let Some(ref target) = entry.link_target {
+                            println!("  {} -> {}", entry.path, target);
+                        }
+                    } else if entry.is_dir() {
+                        println!("  {}/", top);
+                    } else {
+                        println!("  {}", top);
+                    }
+                }
+            }
+
+            if options.hash.is_some() || magic {
+                let kinds = match magic {
+                    true => reader.file_kinds()?,
+                    false => Default::default(),
+                };
+                let columns: Vec<&str> = [options.hash.map(|a| a.name()), magic.then_some("type")]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                println!();
+                println!("Files ({}):", columns.join(", "));
+                for entry in reader.files() {
+                    let mut line = String::new();
+                    if let Some(ref digest) = entry.digest {
+                        line.push_str(&format!("{}  ", digest));
+                    }
+                    if let Some(kind) = kinds.get(&CpioReader::normalize_path(&entry.path)) {
+                        line.push_str(&format!("{:<11} ", kind));
+                    }
+                    println!("{}{}", line, entry.path);
+                }
+            }
+
+            if strict {
+                let anomalies = reader.anomalies();
+                println!();
+                println!("Strict: {} anomalies", anomalies.len());
+                for anomaly in anomalies {
+                    println!("  [WARN] {} - {}", anomaly.path, anomaly.message);
+                }
+                clean = anomalies.is_empty();
+            }
+            top.map(|_| crate::diff::cpio_entries(&reader))
+        }
+        ArchiveFormat::Erofs => {
+            let reader = ErofsReader::open(path)?;
+            let stats = reader.stats();
+
+            println!(
+                "Entries: {} files, {} directories, {} symlinks",
+                stats.files, stats.directories, stats.symlinks
+            );
+            println!("Total size: {} bytes", stats.total_size);
+            top.map(|_| crate::diff::erofs_entries(&reader))
+        }
+        ArchiveFormat::Iso => {
+            let reader = IsoReader::open(path)?;
+            let stats = reader.stats();
+
+            if let Some(vol) = reader.volume_id() {
+                println!("Volume ID: {}", vol);
+            }
+            println!(
+                "Entries: {} files, {} directories, {} symlinks",
+                stats.files, stats.directories, stats.symlinks
+            );
+            println!("Total size: {} bytes", stats.total_size);
+            top.map(|_| crate::diff::iso_entries(&reader))
+        }
+    };
+
+    if let (Some(n), Some(entries)) = (top, entries) {
+        print_top(path, &entries, n)?;
+    }
+
+    Ok(clean)
+}
+
+/// Volume descriptors and El Torito boot catalog of an ISO image.
+fn cmd_iso_headers(path: &Path, json: bool) -> Result<bool> {
+    if !matches!(crate::detect_format(path)?, ArchiveFormat::Iso) {
+        bail!("--iso-headers requires an ISO image");
+    }
+    let descriptors = crate::iso::volume_descriptors(path)?;
+    let catalog = crate::iso::boot_catalog(path)?;
+    if json {
+        let headers = serde_json::json!({
+            "descriptors": descriptors,
+            "boot_catalog": catalog,
+        });
+        println!("{}", serde_json::to_string_pretty(&headers)?);
+        return Ok(true);
+    }
+
+    println!("=== ISO headers: {} ===", path.display());
+    for descriptor in &descriptors {
+        println!();
+        print!("{} (block {}", descriptor.kind.name(), descriptor.block);
+        if let Some(level) = descriptor.volume.as_ref().and_then(|v| v.joliet) {
+            print!(", Joliet level {}", level);
+        }
+        println!(")");
+        if let Some(ref volume) = descriptor.volume {
+            let unset = |date: &Option<String>| date.clone().unwrap_or_else(|| "-".to_string());
+            for (label, value) in [
+                ("System ID", volume.system_id.clone()),
+                ("Volume ID", volume.volume_id.clone()),
+                ("Volume set ID", volume.volume_set_id.clone()),
+                ("Publisher ID", volume.publisher_id.clone()),
+                ("Data preparer ID", volume.data_preparer_id.clone()),
+                ("Application ID", volume.application_id.clone()),
+                ("Copyright file", volume.copyright_file.clone()),
+                ("Abstract file", volume.abstract_file.clone()),
+                ("Bibliographic file", volume.bibliographic_file.clone()),
+                (
+                    "Volume size",
+                    format!(
+                        "{} blocks of {} bytes",
+                        volume.volume_blocks, volume.block_size
+                    ),
+                ),
+                (
+                    "Volume set",
+                    format!("{} of {}", volume.volume_sequence, volume.volume_set_size),
+                ),
+                (
+                    "Path table size",
+                    format!("{} bytes", volume.path_table_size),
+                ),
+                ("Created", unset(&volume.created)),
+                ("Modified", unset(&volume.modified)),
+                ("Expires", unset(&volume.expires)),
+                ("Effective", unset(&volume.effective)),
+            ] {
+                println!("  {:<19} {}", format!("{}:", label), value);
+            }
+        }
+        if let Some(ref boot) = descriptor.boot {
+            println!("  {:<19} {}", "Boot system ID:", boot.system_id);
+            println!("  {:<19} {}", "Boot ID:", boot.boot_id);
+            if let Some(catalog) = boot.catalog {
+                println!("  {:<19} block {}", "Boot catalog:", catalog);
+            }
+        }
+    }
+
+    if !catalog.is_empty() {
+        println!();
+        println!("El Torito boot catalog:");
+        for image in &catalog {
+            println!(
+                "  {:<5} {}{}, {} sectors at block {}",
+                image.platform_name(),
+                if image.bootable { "bootable, " } else { "" },
+                image.media_name(),
+                image.sectors,
+                image.extent
+            );
+        }
+    }
+    Ok(true)
+}
+
+/// Largest files and directories, and on-disk vs content size.
+fn print_top(path: &Path, entries: &crate::diff::EntryMap, n: usize) -> Result<()> {
+    println!();
+    println!("Largest files:");
+    for (file, size) in crate::sizes::largest_files(entries, n) {
+        println!("  {:>6}  {}", human_size(size), file);
+    }
+    println!();
+    println!("Largest directories:");
+    for (dir, size) in crate::sizes::largest_directories(entries, n) {
+        println!("  {:>6}  {}/", human_size(size), dir);
+    }
+
+    // Compressed formats only know their total; per-file sizes are content sizes
+    let content: u64 = entries.values().map(|e| e.size).sum();
+    let on_disk = std::fs::metadata(path)?.len();
+    println!();
+    print!(
+        "Size: {} on disk, {} of file content",
+        human_size(on_disk),
+        human_size(content)
+    );
+    if on_disk > 0 && content > on_disk {
+        print!(" ({:.1}x compression)", content as f64 / on_disk as f64);
+    }
+    println!();
+    Ok(())
+}
+
+/// Combine `--ignore` globs and `--ignore-file` contents.
+fn load_ignores(globs: &[String], files: &[PathBuf]) -> Result<IgnoreList> {
+    let mut ignore = IgnoreList::new();
+    for glob in globs {
+        ignore.add(glob)?;
+    }
+    for file in files {
+        ignore.load_file(file)?;
+    }
+    Ok(ignore)
+}
+
+fn parse_checklist(name: &str) -> Result<ChecklistType> {
+    ChecklistType::parse_name(name).ok_or_else(|| anyhow::anyhow!(
+        "Unknown checklist type: {}. Valid types: install-initramfs, live-initramfs, rootfs, iso, auth-audit, qcow2, rpmdb, netboot",
+        name
+    ))
+}
+
+/// Optional extras for `verify` beyond the checklist itself.
+#[derive(Default)]
+struct VerifyOptions<'a> {
+    mode: ReportMode,
+    catalog: Catalog,
+    save_report: Option<&'a Path>,
+    fix_script: Option<&'a Path>,
+    signature: Option<(&'a Path, &'a Path)>,
+    timestamps: Option<(u64, MtimePolicy)>,
+    empty_globs: &'a [String],
+    special_globs: &'a [String],
+    secret_globs: &'a [String],
+    overlay_dir: Option<&'a Path>,
+    lower: Option<&'a Path>,
+    /// List only the ISO directories the checks read
+    fast_listing: bool,
+    ignore: IgnoreList,
+    strict: bool,
+    /// Add the archive name lints (CPIO)
+    lint_names: bool,
+    lenient: bool,
+    duplicates: DuplicatePolicy,
+    timings: bool,
+    cache: Option<ReportCache>,
+    incremental: bool,
+    /// Probes run inside a staged directory tree
+    probes: &'a [Probe],
+    extensions: Vec<Extension>,
+    /// Spec to verify against; the compiled-in one if None
+    spec: Option<&'a Spec>,
+    limits: Limits,
+    /// Overrides the architecture detected from the artifact
+    arch: Option<Arch>,
+    dry_run: bool,
+    no_sudo: bool,
+    nbd: bool,
+    unlock: Option<LuksKey<'a>>,
+}
+
+impl VerifyOptions<'_> {
+    fn spec(&self) -> &Spec {
+        self.spec.unwrap_or(Spec::builtin())
+    }
+
+    /// Everything besides the artifact that decides a report's contents.
+    fn cache_key(&self, checklist_type: &str) -> String {
+        format!(
+            "{} {} spec={} lists={} limits={:?} arch={:?} strict={} lint_names={} lenient={} duplicates={:?} timestamps={:?} empty={:?} special={:?} secret={:?} ignore={:?} extensions={:?} fast_listing={}",
+            env!("CARGO_PKG_VERSION"),
+            checklist_type,
+            self.spec().id(),
+            self.spec().digest(),
+            self.limits,
+            self.arch,
+            self.strict,
+            self.lint_names,
+            self.lenient,
+            self.duplicates,
+            self.timestamps,
+            self.empty_globs,
+            self.special_globs,
+            self.secret_globs,
+            self.ignore,
+            self.extensions,
+            self.fast_listing
+        )
+    }
+
+    /// Extensions merged into this checklist.
+    fn extensions_for(&self, checklist: ChecklistType) -> impl Iterator<Item = &Extension> {
+        self.extensions
+            .iter()
+            .filter(move |e| e.applies_to(checklist))
+    }
+
+    /// Directories the checklist and its extensions control, merged.
+    fn controlled(&self, checklist: ChecklistType) -> Vec<ControlledDir> {
+        crate::checklist::controlled::merge(
+            crate::checklist::controlled::builtin(checklist, self.spec())
+                .into_iter()
+                .chain(
+                    self.extensions_for(checklist)
+                        .flat_map(|e| e.controlled.iter().cloned()),
+                ),
+        )
+    }
+
+    /// The checklist's scope plus the paths its extensions check.
+    fn scope(&self, checklist: ChecklistType) -> Option<Scope> {
+        let mut scope = crate::checklist::scope(checklist, self.spec())?;
+        for extension in self.extensions_for(checklist) {
+            extension.extend_scope(&mut scope);
+        }
+        Some(scope)
+    }
+}
+
+fn cmd_verify(path: &Path, checklist_type: &str, options: &VerifyOptions) -> Result<bool> {
+    let VerifyOptions {
+        mode,
+        ref catalog,
+        save_report,
+        ref ignore,
+        timings,
+        ref cache,
+        incremental,
+        ..
+    } = *options;
+
+    let checklist = if checklist_type == auto::AUTO {
+        let selection = auto::select(path)?;
+        if !mode.is_terse() {
+            eprintln!("Checklist: {}", selection);
+        }
+        selection.checklist
+    } else {
+        parse_checklist(checklist_type)?
+    };
+    // Reports and cache keys name the checklist that ran
+    let checklist_type = checklist.key();
+    if (options.dry_run || options.no_sudo || options.nbd) && checklist != ChecklistType::Qcow2 {
+        bail!("--dry-run, --no-sudo and --nbd only apply to qcow2 verification");
+    }
+    let started = SystemTime::now();
+    let timer = Instant::now();
+
+    // With --cache: fingerprint, key, and the entries/digests to store after the run
+    let mut cached = None;
+    if let Some(cache) = cache {
+        // A tree's fingerprint is its listing; a same-size edit keeps it
+        if path.is_dir() {
+            bail!("--cache can't track a directory tree; its fingerprint doesn't cover contents");
+        }
+        if crate::tar::is_tar(path)? {
+            bail!("--cache can't track a tarball; it's verified as the tree it unpacks to");
+        }
+        if checklist == ChecklistType::Qcow2
+            || options.overlay_dir.is_some()
+            || options.signature.is_some()
+        {
+            bail!("--cache can't track qcow2 images or --overlay-dir/--sig inputs");
+        }
+        let fingerprint = Fingerprint::of(path)?;
+        let key = options.cache_key(checklist_type);
+        if let Some(run) = cache.get(&fingerprint.sha256, &key) {
+            return replay(run.saved, "artifact unchanged", options);
+        }
+
+        let (entries, digests) = checklist_entries(path, options.duplicates, options.limits)?;
+        if let Some(previous) = cache.latest(path, &key).filter(|_| incremental) {
+            let changed = previous.changed_paths(&entries, &digests);
+            let scope = options.scope(checklist).expect("qcow2 rejected above");
+            // Without digests a same-size content change would go unnoticed, and
+            // archive-wide scans (names, types, empty files, timestamps) look at
+            // every entry, so only content changes to unchanged entries qualify.
+            // The interpreter, shebang and secret scans read every file, so
+            // changed binaries, scripts, key files and files the previous report
+            // named don't
+            let comparable = !digests.is_empty()
+                && !previous.digests.is_empty()
+                && options.timestamps.is_none()
+                && crate::diff::compare(&previous.entries, &entries).is_empty();
+            if comparable
+                && changed.iter().all(|p| scope.category_of(p).is_none())
+                && !changed
+                    .iter()
+                    .any(|p| previous.saved.report.results.iter().any(|r| r.item == *p))
+                && !changes_scanned_content(path, options.duplicates, options.limits, &changed)?
+            {
+                let mut saved = previous.saved;
+                saved.report.fingerprint = Some(fingerprint.clone());
+                let reason = format!(
+                    "{} changed path(s), none checked by the {} checklist",
+                    changed.len(),
+                    checklist.name()
+                );
+                let run = CachedRun {
+                    saved,
+                    entries,
+                    digests,
+                };
+                cache.put(path, &fingerprint.sha256, &key, &run)?;
+                return replay(run.saved, &reason, options);
+            }
+        }
+        cached = Some((fingerprint, key, entries, digests));
+    }
+
+    // Handle qcow2 specially - requires mounting
+    let (mut report, ignored) = if checklist == ChecklistType::Qcow2 {
+        if !ignore.is_empty() {
+            bail!("--ignore is not supported for qcow2 verification");
+        }
+        if !options.nbd {
+            if options.dry_run {
+                println!(
+                    "Dry run: {} is read without privileges (qemu-storage-daemon, debugfs, mcopy); nothing runs as root",
+                    path.display()
+                );
+                return Ok(true);
+            }
+            (verify_qcow2_rootless(path, mode)?, 0)
+        } else {
+            let privileged = Privileged {
+                sudo: !options.no_sudo,
+                dry_run: options.dry_run,
+            };
+            match verify_qcow2(path, mode, privileged, options.unlock)? {
+                Some(report) => (report, 0),
+                None => return Ok(true),
+            }
+        }
+    } else {
+        let mut report = run_checklist(path, checklist, options)?;
+        let ignored = match options.scope(checklist) {
+            Some(scope) if !ignore.is_empty() => ignore.filter_report(&mut report, &scope),
+            _ => 0,
+        };
+        (report, ignored)
+    };
+    let duration = timer.elapsed();
+    report.fingerprint = Some(match cached {
+        Some((ref fingerprint, ..)) => fingerprint.clone(),
+        None => Fingerprint::of(path)?,
+    });
+
+    print_report(&report, mode, catalog);
+    if timings {
+        print_timings(&report, catalog);
+    }
+
+    if ignored > 0 && !mode.is_terse() {
+        println!("Ignored: {} failure(s) on paths matching --ignore", ignored);
+    }
+    if !mode.is_terse() {
+        for extension in options.extensions_for(checklist) {
+            println!(
+                "Extension: {} ({} check(s))",
+                extension.source.display(),
+                extension.checks.len()
+            );
+        }
+    }
+
+    // Failures against another spec than the build's say little about the build
+    if checklist != ChecklistType::Qcow2 {
+        if let Ok(Some((manifest, source))) = crate::provenance::find(path) {
+            if let Some(mismatch) = manifest.spec_mismatch(options.spec()) {
+                eprintln!(
+                    "Warning: {} (provenance in {}); verify with --spec and that spec's snapshot",
+                    mismatch, source
+                );
+            }
+        }
+    }
+
+    if let Some(out) = options.fix_script {
+        write_fix_script(&report, out)?;
+    }
+    let success = report.is_success();
+    let saved = SavedReport::new(path, checklist_type, started, duration, report);
+    if let Some(out) = save_report {
+        saved
+            .save(out)
+            .with_context(|| format!("Failed to save report to {}", out.display()))?;
+    }
+    if let (Some(cache), Some((fingerprint, key, entries, digests))) = (cache, cached) {
+        let run = CachedRun {
+            saved,
+            entries,
+            digests,
+        };
+        cache.put(path, &fingerprint.sha256, &key, &run)?;
+    }
+
+    Ok(success)
+}
+
+/// Print a cached report with a "(cached)" marker, as verify would have.
+fn replay(saved: SavedReport, reason: &str, options: &VerifyOptions) -> Result<bool> {
+    let marker = format!(
+        "(cached) {}; report from {}",
+        reason,
+        crate::report::format_timestamp(saved.timestamp)
+    );
+    // Terse output stays parseable; the marker goes to stderr there
+    if options.mode.is_terse() {
+        eprintln!("{}", marker);
+    } else {
+        println!("{}", marker);
+        println!();
+    }
+    print_report(&saved.report, options.mode, &options.catalog);
+    if options.timings {
+        print_timings(&saved.report, &options.catalog);
+    }
+    if let Some(out) = options.fix_script {
+        write_fix_script(&saved.report, out)?;
+    }
+    if let Some(out) = options.save_report {
+        saved
+            .save(out)
+            .with_context(|| format!("Failed to save report to {}", out.display()))?;
+    }
+    Ok(saved.report.is_success())
+}
+
+/// Write the report's fix-it script (--fix-script), if any failure has a fix.
+fn write_fix_script(report: &VerificationReport, out: &Path) -> Result<()> {
+    let Some(script) = crate::fixit::script(report) else {
+        eprintln!(
+            "No failure has a mechanical fix; {} not written",
+            out.display()
+        );
+        return Ok(());
+    };
+    std::fs::write(out, script)
+        .and_then(|()| std::fs::set_permissions(out, std::fs::Permissions::from_mode(0o755)))
+        .with_context(|| format!("Failed to write fix-it script to {}", out.display()))?;
+    eprintln!(
+        "Fix-it script: {} (run it with the staged tree as argument)",
+        out.display()
+    );
+    Ok(())
+}
+
+/// Entries (and CPIO content digests) of what a checklist verifies: a UKI's
+/// or boot image's embedded initrd, otherwise the archive itself.
+fn checklist_entries(
+    path: &Path,
+    duplicates: DuplicatePolicy,
+    limits: Limits,
+) -> Result<(
+    crate::diff::EntryMap,
+    std::collections::BTreeMap<String, String>,
+)> {
+    let range = if let Some(range) = crate::embedded_initrd(path)? {
+        range
+    } else {
+        match crate::detect_format(path)? {
+            ArchiveFormat::Cpio | ArchiveFormat::CpioGzip => ByteRange::default(),
+            format => {
+                return Ok((
+                    diff_entries(path, format, limits, &IgnoreList::new())?,
+                    Default::default(),
+                ))
+            }
+        }
+    };
+    let parse = ParseOptions {
+        limits,
+        ..Default::default()
+    };
+    let reader = CpioReader::open_with(path, range, parse)?.with_duplicate_policy(duplicates)?;
+    Ok((
+        crate::diff::cpio_entries(&reader),
+        reader.content_digests()?,
+    ))
+}
+
+/// Whether any of the changed paths is now an ELF image, a `#!` script or
+/// holds a private key.
+fn changes_scanned_content(
+    path: &Path,
+    duplicates: DuplicatePolicy,
+    limits: Limits,
+    changed: &[String],
+) -> Result<bool> {
+    let range = crate::embedded_initrd(path)?.unwrap_or_default();
+    let parse = ParseOptions {
+        limits,
+        ..Default::default()
+    };
+    let reader = CpioReader::open_with(path, range, parse)?.with_duplicate_policy(duplicates)?;
+    let paths: Vec<&str> = changed.iter().map(String::as_str).collect();
+    Ok(reader.read_files(&paths)?.values().any(|data| {
+        data.starts_with(b"\x7fELF")
+            || data.starts_with(b"#!")
+            || crate::checklist::secrets::private_key_block(data).is_some()
+    }))
+}
+
+fn cmd_boot_test(
+    image: &Path,
+    test: &BootTest,
+    serial_log: Option<&Path>,
+    fold_into: Option<&Path>,
+    save_report: Option<&Path>,
+    mode: ReportMode,
+    catalog: &Catalog,
+) -> Result<bool> {
+    let started = SystemTime::now();
+    let timer = Instant::now();
+
+    // Check the report belongs to this image before spending minutes booting it
+    let fingerprint = Fingerprint::of(image)?;
+    let saved = match fold_into {
+        Some(path) => {
+            let saved = SavedReport::load(path)?;
+            match saved.report.fingerprint {
+                Some(ref f) if f.sha256 != fingerprint.sha256 => bail!(
+                    "{} is a report on {}, not on {}",
+                    path.display(),
+                    saved.archive.display(),
+                    image.display()
+                ),
+                _ => Some(saved),
+            }
+        }
+        None => None,
+    };
+
+    test.firmware()?;
+    if !mode.is_terse() {
+        println!(
+            "Booting {} under QEMU ({}, {}), waiting up to {}s for {}",
+            image.display(),
+            test.arch.name(),
+            if test.uses_kvm() { "KVM" } else { "TCG" },
+            test.timeout.as_secs(),
+            test.markers
+                .iter()
+                .map(|m| format!("{:?}", m))
+                .collect::<Vec<_>>()
+                .join(" or ")
+        );
+        println!();
+    }
+    let outcome = test.run(image)?;
+    if let Some(log) = serial_log {
+        std::fs::write(log, &outcome.console)
+            .with_context(|| format!("Failed to write serial log to {}", log.display()))?;
+    }
+    let mut result = outcome.to_check_result(test.medium);
+    result.elapsed_us = Some(timer.elapsed().as_micros() as u64);
+    let mut results = vec![result];
+    results.extend(
+        crate::bootlog::analyze(&outcome.console)
+            .iter()
+            .map(|finding| finding.to_check_result(saved.as_ref().map(|s| &s.report))),
+    );
+
+    let (saved, out) = match saved {
+        Some(mut saved) => {
+            // A rerun replaces the previous boot results
+            saved
+                .report
+                .results
+                .retain(|r| !r.item.starts_with("boot: ") && !r.item.starts_with("boot log: "));
+            for result in results {
+                saved.report.add(result);
+            }
+            (saved, fold_into)
+        }
+        None => {
+            let mut report = VerificationReport::new("boot-test");
+            report.arch = Some(test.arch.name().to_string());
+            for result in results {
+                report.add(result);
+            }
+            report.fingerprint = Some(fingerprint);
+            let saved = SavedReport::new(image, "boot-test", started, timer.elapsed(), report);
+            (saved, save_report)
+        }
+    };
+    print_report(&saved.report, mode, catalog);
+    if let Some(out) = out {
+        saved
+            .save(out)
+            .with_context(|| format!("Failed to save report to {}", out.display()))?;
+    }
+
+    Ok(saved.report.is_success())
+}
+
+fn cmd_boot_log(
+    log: &Path,
+    report: Option<&Path>,
+    mode: ReportMode,
+    catalog: &Catalog,
+) -> Result<bool> {
+    let console = std::fs::read(log).map_err(|e| FsdbgError::from(e).with_path(log))?;
+    let saved = report.map(SavedReport::load).transpose()?;
+
+    let mut analysis = VerificationReport::new("Boot log");
+    let findings = crate::bootlog::analyze(&console);
+    if findings.is_empty() {
+        analysis.add(CheckResult::pass(
+            "boot log: no known failure signatures",
+            CheckCategory::Other,
+        ));
+    }
+    for finding in &findings {
+        analysis.add(finding.to_check_result(saved.as_ref().map(|s| &s.report)));
+    }
+    print_report(&analysis, mode, catalog);
+    Ok(analysis.is_success())
+}
+
+fn cmd_explain_exit(code: Option<u8>) -> Result<bool> {
+    use crate::error::EXIT_CODES;
+
+    let codes: Vec<_> = match code {
+        None => EXIT_CODES.iter().collect(),
+        Some(code) => match EXIT_CODES.iter().find(|(c, _)| *c == code) {
+            Some(entry) => vec![entry],
+            None => bail!("Exit code {} is not one fsdbg uses (0-5)", code),
+        },
+    };
+    for (exit, meaning) in codes {
+        println!("{}  {}", exit, meaning);
+        let errors: Vec<String> = ErrorCode::ALL
+            .iter()
+            .filter(|e| e.exit_code() == *exit)
+            .map(|e| e.to_string())
+            .collect();
+        if !errors.is_empty() {
+            println!("   errors: {}", errors.join(", "));
+        }
+    }
+    Ok(true)
+}
+
+fn cmd_detect(path: &Path, all: bool) -> Result<bool> {
+    let matches = crate::detect::probe(path)?;
+    let Some(best) = matches.first() else {
+        println!("{}: unknown format", path.display());
+        return Ok(false);
+    };
+
+    if !all {
+        let note = if best.detector.format.is_none() {
+            ", not supported by fsdbg"
+        } else {
+            ""
+        };
+        println!(
+            "{}: {} (confidence: {}{})",
+            path.display(),
+            best.detector.name,
+            best.confidence,
+            note
+        );
+        return Ok(true);
+    }
+
+    println!("{}:", path.display());
+    for m in &matches {
+        let support = if m.detector.format.is_some() {
+            ""
+        } else {
+            "  [not supported]"
+        };
+        println!("  {:<24} {}{}", m.detector.name, m.confidence, support);
+    }
+    Ok(true)
+}
+
+fn cmd_show_report(
+    path: &Path,
+    mode: ReportMode,
+    catalog: &Catalog,
+    timings: bool,
+    fix_script: Option<&Path>,
+) -> Result<bool> {
+    let saved = SavedReport::load(path)?;
+
+    if !mode.is_terse() {
+        println!("Archive:  {}", saved.archive.display());
+        println!(
+            "Checked:  {}",
+            crate::report::format_timestamp(saved.timestamp)
+        );
+        println!(
+            "Duration: {:.1}s (fsdbg {})",
+            saved.duration_ms as f64 / 1000.0,
+            saved.fsdbg_version
+        );
+        println!();
+    }
+    print_report(&saved.report, mode, catalog);
+    if timings {
+        print_timings(&saved.report, catalog);
+    }
+    let current = Spec::builtin().id();
+    match saved.report.spec {
+        Some(ref spec) if *spec != current && !mode.is_terse() => {
+            println!();
+            println!(
+                "Note: verified against distro-spec {}; this fsdbg uses {}",
+                spec, current
+            );
+        }
+        _ => {}
+    }
+    if let Some(out) = fix_script {
+        write_fix_script(&saved.report, out)?;
+    }
+
+    Ok(saved.report.is_success())
+}
+
+fn cmd_spec_version(dump: bool, compare: Option<&Path>) -> Result<bool> {
+    let builtin = Spec::builtin();
+    if dump {
+        println!("{}", serde_json::to_string_pretty(builtin)?);
+        return Ok(true);
+    }
+
+    println!("distro-spec {} (compiled in)", builtin.version);
+    println!("SHA256:   {}", builtin.digest());
+    let Some(path) = compare else {
+        return Ok(true);
+    };
+
+    let snapshot = Spec::load(path)?;
+    println!();
+    println!("Snapshot: {} ({})", snapshot.version, path.display());
+    println!("SHA256:   {}", snapshot.digest());
+    let changes = builtin.compare(&snapshot);
+    if changes.is_empty() {
+        println!("Lists are identical");
+        return Ok(true);
+    }
+    println!();
+    for change in &changes {
+        println!("{}:", change.list);
+        for item in &change.added {
+            println!("  + {}", item);
+        }
+        for item in &change.removed {
+            println!("  - {}", item);
+        }
+    }
+    println!();
+    println!(
+        "{} list(s) differ; verify with --spec {} to use the snapshot",
+        changes.len(),
+        path.display()
+    );
+    Ok(false)
+}
+
+/// Run a (non-qcow2) checklist plus the optional extras, without printing.
+fn run_checklist(
+    path: &Path,
+    checklist: ChecklistType,
+    options: &VerifyOptions,
+) -> Result<VerificationReport> {
+    let VerifyOptions {
+        signature,
+        timestamps,
+        empty_globs,
+        special_globs,
+        secret_globs,
+        overlay_dir,
+        lower,
+        fast_listing,
+        strict,
+        lint_names,
+        lenient,
+        duplicates,
+        limits,
+        arch,
+        ..
+    } = *options;
+
+    // Container images and netboot tarballs verify as the tree they unpack to
+    let unpacked = unpack_tree(path, checklist, arch)?;
+    let (path, owners) = match &unpacked {
+        Some(tree) => (tree.root.as_path(), Some(&tree.owners)),
+        None => (path, None),
+    };
+
+    // A staged tree verifies as the archive it would be packed into
+    if path.is_dir() {
+        if overlay_dir.is_some() || signature.is_some() || lenient || fast_listing {
+            bail!("--overlay-dir, --sig, --lenient and --fast-listing don't apply to a directory tree");
+        }
+        let mut tree = DirReader::open(path)?;
+        if let Some(owners) = owners {
+            tree = tree.with_owners(owners);
+        }
+        let reader = CpioReader::from_dir(tree).with_limits(limits);
+        let mut report = cpio_checklist(&reader, checklist, options)?;
+        for result in crate::checklist::probes::run(path, &reader, options.probes)? {
+            report.add(result);
+        }
+        return Ok(report);
+    }
+    if !options.probes.is_empty() {
+        bail!("--probe only applies to a directory tree");
+    }
+
+    // A UKI's or boot image's embedded initrd is verified in place;
+    // CpioReader handles its compression itself
+    let (format, range) = if let Some(range) = crate::embedded_initrd(path)? {
+        (ArchiveFormat::Cpio, range)
+    } else {
+        (crate::detect_format(path)?, ByteRange::default())
+    };
+
+    let mut report = match format {
+        ArchiveFormat::Cpio | ArchiveFormat::CpioGzip => {
+            if overlay_dir.is_some() {
+                bail!("--overlay-dir requires an ISO image, not CPIO");
+            }
+            if fast_listing {
+                bail!("--fast-listing requires an ISO image, not CPIO");
+            }
+            let parse = ParseOptions {
+                lenient,
+                limits,
+                ..Default::default()
+            };
+            let reader =
+                CpioReader::open_with(path, range, parse)?.with_duplicate_policy(duplicates)?;
+            cpio_checklist(&reader, checklist, options)?
+        }
+        ArchiveFormat::Iso => {
+            if timestamps.is_some() {
+                bail!("--source-date-epoch requires a CPIO archive, not ISO");
+            }
+            if !empty_globs.is_empty() {
+                bail!("--empty-glob requires a CPIO archive, not ISO");
+            }
+            if !special_globs.is_empty() {
+                bail!("--allow-special requires a CPIO archive, not ISO");
+            }
+            if !secret_globs.is_empty() {
+                bail!("--allow-secret requires a CPIO archive, not ISO");
+            }
+            if strict {
+                bail!("--strict requires a CPIO archive, not ISO");
+            }
+            if lint_names {
+                bail!("--lint-names requires a CPIO archive; ISO names are always checked");
+            }
+            if lenient {
+                bail!("--lenient requires a CPIO archive, not ISO");
+            }
+            let reader = if fast_listing {
+                let mut scope = options.scope(checklist).unwrap_or_default();
+                for controlled in options.controlled(checklist) {
+                    scope.add_prefix(&controlled.dir, controlled.category);
+                }
+                let dirs = scope.top_level();
+                IsoReader::open_dirs(path, &dirs.iter().map(String::as_str).collect::<Vec<_>>())?
+            } else {
+                IsoReader::open(path)?
+            };
+            let arch = arch
+                .or_else(|| crate::arch::detect_iso(&reader))
+                .unwrap_or_default();
+            let overlay = overlay_dir.map(DirOverlay::open).transpose()?;
+            let overlay = overlay.as_ref().map(|o| o as &dyn OverlayTree);
+            let mut report = crate::checklist::verify_iso_for(
+                &reader,
+                checklist,
+                overlay,
+                arch,
+                options.spec(),
+            )?;
+            if let (Some(overlay), Some(lower)) = (overlay, lower) {
+                let lower = diff_entries(
+                    lower,
+                    crate::detect_format(lower)?,
+                    limits,
+                    &IgnoreList::new(),
+                )?;
+                for result in crate::checklist::live_overlay::check_whiteouts(overlay, &lower) {
+                    report.add(result);
+                }
+            }
+            let rootfs = distro_spec::shared::ROOTFS_ISO_PATH;
+            if checklist == ChecklistType::Iso && reader.exists(rootfs) {
+                for result in iso_first_boot(&reader)? {
+                    report.add(result);
+                }
+            }
+            for extension in options.extensions_for(checklist) {
+                for result in extension.check_iso(&reader) {
+                    report.add(result);
+                }
+            }
+            let paths = reader.entries().iter().map(|e| e.path.as_str());
+            for result in crate::checklist::controlled::check(paths, &options.controlled(checklist))
+            {
+                report.add(result);
+            }
+            report
+        }
+        _ => bail!("Checklist verification only supports CPIO and ISO archives"),
+    };
+
+    if let Some((sig, keyring)) = signature {
+        let status = crate::signature::verify(path, sig, keyring)?;
+        report.add(status.to_check_result(sig));
+    }
+
+    Ok(report)
+}
+
+/// A tree unpacked from the input to verify in its place
+struct UnpackedTree {
+    /// Holds the tree until the run is over
+    _dir: tempfile::TempDir,
+    root: PathBuf,
+    /// Owners the tar headers record, which unpacking without root loses
+    owners: crate::tar::Owners,
+}
+
+/// Unpack `path` if it's a container image (a directory or tarball), with
+/// its layers flattened, or a netboot tarball.
+fn unpack_tree(
+    path: &Path,
+    checklist: ChecklistType,
+    arch: Option<Arch>,
+) -> Result<Option<UnpackedTree>> {
+    let tarball = !path.is_dir() && crate::tar::is_tar(path)?;
+    let image = if path.is_dir() {
+        crate::oci::is_image(path)
+    } else {
+        tarball && crate::oci::is_image_archive(&crate::tar::list(path)?)
+    };
+    let netboot = tarball && checklist == ChecklistType::Netboot;
+    if !image && !netboot {
+        return Ok(None);
+    }
+
+    let dir = tempfile::tempdir()?;
+    let root = dir.path().join("root");
+    std::fs::create_dir(&root)?;
+    let dest = Destination::new(&root);
+    if !image {
+        crate::tar::extract(path, &dest)?;
+        return Ok(Some(UnpackedTree {
+            root: single_top_dir(&root)?,
+            _dir: dir,
+            owners: Default::default(),
+        }));
+    }
+    let layout = if path.is_dir() {
+        path.to_path_buf()
+    } else {
+        let layout = dir.path().join("image");
+        std::fs::create_dir(&layout)?;
+        crate::tar::extract(path, &Destination::new(&layout))?;
+        layout
+    };
+    let image = Image::open(&layout, arch)?;
+    let owners = image.flatten(&dest).with_context(|| match image.name {
+        Some(ref name) => format!("Failed to flatten {} ({})", name, image.layout.name()),
+        None => format!("Failed to flatten the {}", image.layout.name()),
+    })?;
+    Ok(Some(UnpackedTree {
+        _dir: dir,
+        root,
+        owners,
+    }))
+}
+
+/// `dir`, or the one directory in it: tarballs often hold the tree under
+/// its own name.
+fn single_top_dir(dir: &Path) -> Result<PathBuf> {
+    let entries: Vec<_> = std::fs::read_dir(dir)?.collect::<std::io::Result<_>>()?;
+    match &entries[..] {
+        [only] if only.file_type()?.is_dir() => Ok(only.path()),
+        _ => Ok(dir.to_path_buf()),
+    }
+}
+
+/// The checklist and the CPIO-only checks on top of it, for an archive or
+/// a directory tree read as one.
+fn cpio_checklist(
+    reader: &CpioReader,
+    checklist: ChecklistType,
+    options: &VerifyOptions,
+) -> Result<VerificationReport> {
+    let VerifyOptions {
+        timestamps,
+        empty_globs,
+        special_globs,
+        secret_globs,
+        strict,
+        lint_names,
+        arch,
+        ..
+    } = *options;
+
+    let arch = arch
+        .or_else(|| crate::arch::detect_cpio(reader))
+        .unwrap_or_default();
+    let mut report = crate::checklist::verify_cpio_for(reader, checklist, arch, options.spec())?;
+    if let Some(truncated) = reader.truncated() {
+        report.partial = Some(truncated.to_string());
+        report.add(
+            CheckResult::fail(
+                "archive",
+                CheckCategory::Other,
+                format!("Archive {}; later entries were not checked", truncated),
+            )
+            .with_hint("Rebuild or re-copy the artifact; the report covers only what came before"),
+        );
+    }
+    if strict {
+        for anomaly in reader.anomalies() {
+            report.add(
+                CheckResult::fail(&anomaly.path, CheckCategory::Other, &anomaly.message)
+                    .with_hint("Rejected by --strict; check the archive writer"),
+            );
+        }
+    }
+    if lint_names {
+        for result in crate::checklist::check_archive_names(reader) {
+            report.add(result);
+        }
+    }
+    if let Some((epoch, policy)) = timestamps {
+        for result in crate::checklist::timestamps::check(reader, epoch, policy) {
+            report.add(result);
+        }
+    }
+    if !empty_globs.is_empty() {
+        let patterns = crate::checklist::empty_files::parse_patterns(empty_globs)?;
+        // The checklist already scanned the default locations
+        let extra: Vec<_> = crate::checklist::empty_files::check(reader, &patterns)
+            .into_iter()
+            .filter(|r| !report.results.iter().any(|seen| seen.item == r.item))
+            .collect();
+        for result in extra {
+            report.add(result);
+        }
+    }
+    let allowed = crate::checklist::empty_files::parse_patterns(special_globs)?;
+    for result in crate::checklist::special_files::check(reader, &allowed) {
+        report.add(result);
+    }
+    for result in crate::checklist::interpreters::check(reader) {
+        report.add(result);
+    }
+    for result in crate::checklist::shebangs::check(reader) {
+        report.add(result);
+    }
+    let allowed = crate::checklist::empty_files::parse_patterns(secret_globs)?;
+    for result in crate::checklist::secrets::check(reader, &allowed) {
+        report.add(result);
+    }
+    for extension in options.extensions_for(checklist) {
+        for result in extension.check_cpio(reader) {
+            report.add(result);
+        }
+    }
+    let controlled = options.controlled(checklist);
+    for result in crate::checklist::controlled::check_cpio(reader, &controlled) {
+        report.add(result);
+    }
+    Ok(report)
+}
+
+/// Secret that unlocks a LUKS-encrypted qcow2 root partition
+#[derive(Debug, Clone, Copy)]
+enum LuksKey<'a> {
+    /// Text passphrase; a trailing newline isn't part of it
+    Passphrase(&'a Path),
+    /// Key file, used byte for byte
+    Keyfile(&'a Path),
+}
+
+impl LuksKey<'_> {
+    /// Key file for `cryptsetup --key-file`.
+    fn key_file(&self) -> Result<tempfile::TempPath> {
+        let mut file = tempfile::Builder::new().prefix("fsdbg-luks-").tempfile()?;
+        match *self {
+            LuksKey::Passphrase(path) => {
+                let passphrase = std::fs::read(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let passphrase = passphrase.strip_suffix(b"\n").unwrap_or(&passphrase);
+                std::io::Write::write_all(&mut file, passphrase)?;
+            }
+            LuksKey::Keyfile(path) => {
+                let mut key = std::fs::File::open(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                std::io::copy(&mut key, &mut file)?;
+            }
+        }
+        Ok(file.into_temp_path())
+    }
+}
+
+/// Runs the commands qcow2 verification needs root for.
+#[derive(Debug, Clone, Copy)]
+struct Privileged {
+    /// Prefix commands with sudo (off with --no-sudo)
+    sudo: bool,
+    /// Print commands instead of running them (--dry-run)
+    dry_run: bool,
+}
+
+impl Privileged {
+    fn command(&self, args: &[&dyn AsRef<OsStr>]) -> Command {
+        let (program, args) = args.split_first().expect("command has a program");
+        let mut command = if self.sudo {
+            let mut sudo = Command::new("sudo");
+            sudo.arg(program);
+            sudo
+        } else {
+            Command::new(program)
+        };
+        command.args(args);
+        command
+    }
+
+    /// The command line as a shell would take it.
+    fn describe(&self, args: &[&dyn AsRef<OsStr>]) -> String {
+        let words = args.iter().map(|arg| {
+            let arg = arg.as_ref().to_string_lossy();
+            if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "'\"\\$".contains(c))
+            {
+                arg.into_owned()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        });
+        let sudo = self.sudo.then(|| "sudo".to_string());
+        sudo.into_iter().chain(words).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Run to completion; a dry run prints the command and succeeds. A
+    /// program that isn't installed is a missing tool.
+    fn status(&self, args: &[&dyn AsRef<OsStr>]) -> Result<bool, FsdbgError> {
+        if self.dry_run {
+            println!("  {}", self.describe(args));
+            return Ok(true);
+        }
+        match self.command(args).status() {
+            Ok(status) => Ok(status.success()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let program = match self.sudo {
+                    true => "sudo".to_string(),
+                    false => args[0].as_ref().to_string_lossy().into_owned(),
+                };
+                Err(FsdbgError::tool_missing(
+                    program,
+                    "Install it, or add it to PATH.",
+                ))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Stdout of a successful run (nothing in a dry run, which prints it).
+    fn output(&self, args: &[&dyn AsRef<OsStr>]) -> Option<Vec<u8>> {
+        if self.dry_run {
+            println!("  {}", self.describe(args));
+            return None;
+        }
+        let output = self.command(args).output().ok()?;
+        output.status.success().then_some(output.stdout)
+    }
+
+    fn settle(&self, millis: u64) {
+        if !self.dry_run {
+            std::thread::sleep(std::time::Duration::from_millis(millis));
+        }
+    }
+}
+
+/// Verify a qcow2 image by mounting it via qemu-nbd (`--nbd`).
+///
+/// This requires sudo for mounting (or root with `--no-sudo`). A dry run
+/// prints the privileged commands in order, runs none of them, and returns
+/// no report.
+fn verify_qcow2(
+    path: &Path,
+    mode: ReportMode,
+    privileged: Privileged,
+    unlock: Option<LuksKey>,
+) -> Result<Option<VerificationReport>> {
+    let root = unsafe { libc::getuid() } == 0;
+    if privileged.dry_run {
+        println!("Dry run: privileged commands to verify {}:", path.display());
+        if !privileged.sudo && !root {
+            println!("  (not root: with --no-sudo, none of these would run)");
+        }
+    } else if !privileged.sudo && !root {
+        return Ok(Some(crate::checklist::qcow2::unmounted(
+            "Not root and --no-sudo given: attaching (qemu-nbd) and mounting the image need root",
+        )));
+    } else if !root {
+        eprintln!("Note: qcow2 verification requires sudo for mounting and reading files.");
+    }
+
+    // Check qemu-nbd is available
+    if !privileged.dry_run && Command::new("qemu-nbd").arg("--version").output().is_err() {
+        return Err(FsdbgError::tool_missing("qemu-nbd", "Install qemu-img package.").into());
+    }
+
+    // Connections of killed runs would otherwise hold the devices forever
+    let state = NbdState::new(NbdState::default_dir());
+    nbd_cleanup_stale(privileged, &state);
+
+    // Create temporary mount points
+    let temp_dir = tempfile::tempdir().context("Failed to create temp directory")?;
+    let nbd_device = find_free_nbd_device(privileged)?;
+    let connection = NbdConnection {
+        device: nbd_device.clone(),
+        image: path.to_path_buf(),
+        mount_dir: temp_dir.path().to_path_buf(),
+        sudo: privileged.sudo,
+        owner_pid: std::process::id(),
+        supervisor_pid: None,
+        luks_mapping: None,
+        recovery: false,
+    };
+    let root_mount = connection.root_mount();
+    let boot_mount = connection.boot_mount();
+
+    // Set up cleanup before anything needs cleaning up
+    let mut cleanup = NbdSupervisor::start(privileged, &state, connection)?;
+
+    // Create mount points with sudo so they're accessible
+    let _ = privileged.status(&[&"mkdir", &"-p", &root_mount]);
+    let _ = privileged.status(&[&"mkdir", &"-p", &boot_mount]);
+
+    if !mode.is_terse() && !privileged.dry_run {
+        println!("Mounting {} via qemu-nbd...", path.display());
+    }
+
+    // Connect qcow2 to NBD device (-r = read-only)
+    let connected = privileged
+        .status(&[&"qemu-nbd", &"-c", &nbd_device, &"-r", &path])
+        .context("Failed to run qemu-nbd")?;
+
+    if !connected {
+        return Err(FsdbgError::external_tool_failed(
+            "qemu-nbd",
+            format!("could not connect {}", path.display()),
+        )
+        .into());
+    }
+
+    // Wait for partitions to appear
+    privileged.settle(500);
+
+    // Probe partitions
+    let _ = privileged.status(&[&"partprobe", &nbd_device]);
+
+    privileged.settle(300);
+
+    // Mount root partition (p2) and boot partition (p1)
+    let root_part = format!("{}p2", nbd_device);
+    let boot_part = format!("{}p1", nbd_device);
+
+    // A LUKS root is unlocked (read-only) and the mapping mounted instead
+    let header = privileged.output(&[
+        &"dd",
+        &format!("if={}", root_part),
+        &format!("bs={}", crate::luks::HEADER_LEN),
+        &"count=1",
+        &"status=none",
+    ]);
+    let luks = header
+        .map(|h| crate::luks::parse(&h))
+        .transpose()?
+        .flatten();
+    let root_source = match (&luks, unlock) {
+        (None, Some(_)) if !privileged.dry_run => {
+            bail!(
+                "{} is not LUKS-encrypted; drop --passphrase-file/--keyfile",
+                root_part
+            )
+        }
+        (Some(luks), None) => {
+            return Ok(Some(crate::checklist::qcow2::unmounted(format!(
+                "Root partition is encrypted ({}); unlock it with --passphrase-file or --keyfile",
+                luks
+            ))));
+        }
+        (_, Some(key)) => {
+            let mapping = format!("fsdbg-{}", nbd_device.trim_start_matches("/dev/"));
+            cleanup.update(&state, |c| c.luks_mapping = Some(mapping.clone()))?;
+            let key_file = key.key_file()?;
+            let unlocked = privileged.status(&[
+                &"cryptsetup",
+                &"open",
+                &"--readonly",
+                &"--key-file",
+                &key_file,
+                &root_part,
+                &mapping,
+            ])?;
+            if !unlocked {
+                bail!(
+                    "Failed to unlock {}: wrong passphrase or key file?",
+                    root_part
+                );
+            }
+            format!("/dev/mapper/{}", mapping)
+        }
+        (None, None) => root_part.clone(),
+    };
+
+    // Mount root
+    let mounted = privileged
+        .status(&[&"mount", &"-o", &"ro", &root_source, &root_mount])
+        .context("Failed to mount root partition")?;
+
+    if !mounted {
+        return Err(FsdbgError::external_tool_failed(
+            "mount",
+            format!("could not mount the root partition {}", root_part),
+        )
+        .into());
+    }
+
+    // Mount boot
+    let mounted = privileged
+        .status(&[&"mount", &"-o", &"ro", &boot_part, &boot_mount])
+        .context("Failed to mount boot partition")?;
+
+    if !mounted {
+        // Unmount root before failing
+        let _ = privileged.status(&[&"umount", &root_mount]);
+        return Err(FsdbgError::external_tool_failed(
+            "mount",
+            format!("could not mount the boot partition {}", boot_part),
+        )
+        .into());
+    }
+
+    // Bind-mount boot at root/boot for unified checking
+    let boot_in_root = root_mount.join("boot");
+    // The cleanup guard unmounts it first
+    let bound = privileged.status(&[&"mount", &"--bind", &boot_mount, &boot_in_root]);
+    if !matches!(bound, Ok(true)) {
+        eprintln!("Warning: Could not bind-mount boot, checking separately");
+    }
+
+    // Filesystem UUIDs and GPT GUIDs, for checking fstab and the boot entries
+    let mut partitions: Vec<_> = [("/", &root_part), ("/boot", &boot_part)]
+        .into_iter()
+        .filter_map(|(mount, device)| blkid(privileged, mount, device))
+        .collect();
+    // fstab names the filesystem inside the LUKS container, not the container
+    if root_source != root_part {
+        if let (Some(root), Some(inner)) = (
+            partitions.iter_mut().find(|p| p.mount == "/"),
+            blkid(privileged, "/", &root_source),
+        ) {
+            root.uuid = inner.uuid;
+            root.label = inner.label;
+        }
+    }
+    if partitions.len() < 2 && !privileged.dry_run {
+        eprintln!("Warning: blkid failed, skipping partition identifier checks");
+    }
+
+    // The whole disk: partition types, swap, and a recovery partition
+    let mut disk = None;
+    if privileged.dry_run {
+        println!(
+            "  {} ... (partition table and filesystem headers)",
+            privileged.describe(&[&"dd", &format!("if={}", nbd_device)])
+        );
+    } else {
+        let mut device = DdDisk {
+            privileged,
+            device: &nbd_device,
+            position: 0,
+        };
+        match crate::qcow2::read_layout(&mut device) {
+            Ok(partitions) => {
+                disk = Some(Disk {
+                    partitions,
+                    recovery: None,
+                })
+            }
+            Err(e) => eprintln!("Warning: {}, skipping disk layout checks", e),
+        }
+    }
+    let recovery = disk
+        .as_ref()
+        .and_then(|d| d.partitions.iter().find(|p| p.is_recovery()))
+        .map(|p| format!("{}p{}", nbd_device, p.gpt.number));
+    if let (Some(disk), Some(device)) = (disk.as_mut(), recovery) {
+        cleanup.update(&state, |c| c.recovery = true)?;
+        let recovery_mount = temp_dir.path().join("recovery");
+        let _ = privileged.status(&[&"mkdir", &"-p", &recovery_mount]);
+        if let Ok(true) = privileged.status(&[&"mount", &"-o", &"ro", &device, &recovery_mount]) {
+            disk.recovery = Some(recovery_mount);
+        }
+    }
+
+    let report = if privileged.dry_run {
+        println!("  (qcow2 checklist runs on {})", root_mount.display());
+        None
+    } else {
+        if !mode.is_terse() {
+            println!("Running qcow2 checklist...\n");
+        }
+        // Run verification - use sudo to read files
+        let mut report = verify_qcow2_with_sudo(&root_mount, &partitions, disk.as_ref())?;
+        if let Some(luks) = luks {
+            report.add(CheckResult::pass(
+                format!("root partition encryption: {}", luks),
+                CheckCategory::Other,
+            ));
+        }
+        Some(report)
+    };
+
+    Ok(report)
+}
+
+/// Verify a qcow2 image without privileges, by copying its partitions out
+/// through a qemu-storage-daemon FUSE export (see [`crate::qcow2`]).
+fn verify_qcow2_rootless(path: &Path, mode: ReportMode) -> Result<VerificationReport> {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("fsdbg-qcow2-root-")
+        .tempdir()
+        .context("Failed to create temp directory")?;
+    if !mode.is_terse() {
+        println!("Unpacking {} via qemu-storage-daemon...", path.display());
+    }
+    let (partitions, disk) = match crate::qcow2::unpack(path, temp_dir.path())? {
+        Unpacked::Tree {
+            partitions,
+            layout,
+            recovery,
+        } => (
+            partitions,
+            Disk {
+                partitions: layout,
+                recovery,
+            },
+        ),
+        Unpacked::Encrypted(luks) => {
+            return Ok(crate::checklist::qcow2::unmounted(format!(
+                "Root partition is encrypted ({}); unlock it with --nbd and --passphrase-file or --keyfile",
+                luks
+            )))
+        }
+    };
+    if !mode.is_terse() {
+        println!("Running qcow2 checklist...\n");
+    }
+    Ok(crate::checklist::qcow2::verify_image(
+        &temp_dir.path().join("root"),
+        &partitions,
+        &disk,
+    ))
+}
+
+/// Run qcow2 verification using sudo to read files.
+///
+/// This spawns a subprocess that reads files as root and outputs JSON
+/// that we parse. This avoids permission issues with reading /etc/shadow etc.
+fn verify_qcow2_with_sudo(
+    mount_point: &Path,
+    partitions: &[crate::checklist::qcow2::Partition],
+    disk: Option<&Disk>,
+) -> Result<VerificationReport> {
+    // For now, just call the verify function directly.
+    // Files like /etc/shadow will fail to read without sudo, but we can
+    // detect this via the error messages.
+    //
+    // A more robust solution would serialize the checklist and run it in
+    // a sudo subprocess, but that's overengineering for now.
+    Ok(match disk {
+        Some(disk) => crate::checklist::qcow2::verify_image(mount_point, partitions, disk),
+        None => crate::checklist::qcow2::verify_with_partitions(mount_point, partitions),
+    })
+}
+
+/// A block device read with `dd` (through sudo), for the disk layout checks.
+struct DdDisk<'a> {
+    privileged: Privileged,
+    device: &'a str,
+    position: u64,
+}
+
+impl std::io::Read for DdDisk<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let data = self
+            .privileged
+            .output(&[
+                &"dd",
+                &format!("if={}", self.device),
+                &"iflag=skip_bytes,count_bytes",
+                &format!("skip={}", self.position),
+                &format!("count={}", buf.len()),
+                &"bs=64K",
+                &"status=none",
+            ])
+            .ok_or_else(|| std::io::Error::other(format!("dd could not read {}", self.device)))?;
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl std::io::Seek for DdDisk<'_> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        match pos {
+            std::io::SeekFrom::Start(offset) => self.position = offset,
+            std::io::SeekFrom::Current(delta) => {
+                self.position = self.position.saturating_add_signed(delta)
+            }
+            std::io::SeekFrom::End(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "dd reads don't know the device size",
+                ))
+            }
+        }
+        Ok(self.position)
+    }
+}
+
+/// Identifiers of the partition at `device`, mounted at `mount` in the image.
+fn blkid(
+    privileged: Privileged,
+    mount: &str,
+    device: &str,
+) -> Option<crate::checklist::qcow2::Partition> {
+    let stdout = privileged.output(&[&"blkid", &"-o", &"export", &device])?;
+    Some(crate::checklist::qcow2::Partition::from_blkid(
+        mount,
+        &String::from_utf8_lossy(&stdout),
+    ))
+}
+
+/// Find a free /dev/nbdN device
+fn find_free_nbd_device(privileged: Privileged) -> Result<String> {
+    // Load nbd module if needed
+    let _ = privileged.status(&[&"modprobe", &"nbd", &"max_part=16"]);
+
+    // Find first free nbd device
+    for i in 0..16 {
+        let device = format!("/dev/nbd{}", i);
+        let path = Path::new(&device);
+
+        if !path.exists() {
+            continue;
+        }
+
+        // Check if device is in use by looking at size
+        let size_path = format!("/sys/block/nbd{}/size", i);
+        if let Ok(size) = std::fs::read_to_string(&size_path) {
+            if size.trim() == "0" {
+                return Ok(device);
+            }
+        }
+    }
+
+    if privileged.dry_run {
+        // The module isn't loaded yet, or every device is busy right now
+        return Ok("/dev/nbdN".to_string());
+    }
+    bail!(
+        "No free NBD device found. Disconnect existing qemu-nbd connections (fsdbg cleanup-nbd lists them)."
+    )
+}
+
+/// Unmount and disconnect what an `--nbd` run set up, in reverse order.
+fn nbd_disconnect(privileged: Privileged, connection: &NbdConnection) {
+    let root_mount = connection.root_mount();
+    let boot_mount = connection.boot_mount();
+    if connection.recovery {
+        let _ = privileged.status(&[&"umount", &connection.mount_dir.join("recovery")]);
+    }
+    let _ = privileged.status(&[&"umount", &root_mount.join("boot")]);
+    let _ = privileged.status(&[&"umount", &boot_mount]);
+    let _ = privileged.status(&[&"umount", &root_mount]);
+    if let Some(ref mapping) = connection.luks_mapping {
+        let _ = privileged.status(&[&"cryptsetup", &"close", mapping]);
+    }
+
+    // Disconnect NBD
+    let _ = privileged.status(&[&"qemu-nbd", &"-d", &connection.device]);
+
+    if !privileged.dry_run {
+        // Empty once unmounted; the fsdbg run that made it may not be around
+        let _ = std::fs::remove_dir_all(&connection.mount_dir);
+    }
+}
+
+/// Disconnect the connections of fsdbg runs that were killed mid-way.
+/// Returns how many there were.
+fn nbd_cleanup_stale(privileged: Privileged, state: &NbdState) -> usize {
+    let stale: Vec<_> = state
+        .connections()
+        .into_iter()
+        .filter(NbdConnection::is_stale)
+        .collect();
+    for connection in &stale {
+        eprintln!(
+            "Cleaning up {} ({}), left connected by fsdbg pid {}",
+            connection.device,
+            connection.image.display(),
+            connection.owner_pid
+        );
+        nbd_disconnect(privileged, connection);
+        if !privileged.dry_run {
+            state.remove(&connection.device);
+        }
+    }
+    stale.len()
+}
+
+/// Cleanup for qcow2 mounting that survives the fsdbg run being killed.
+///
+/// A supervisor process (`fsdbg nbd-supervise`) holds the read end of a pipe
+/// from this run. When the run exits - normally, on Ctrl-C or on SIGKILL -
+/// the pipe closes and the supervisor disconnects everything recorded in
+/// the state file. Dropping the guard closes the pipe and waits for it.
+struct NbdSupervisor {
+    privileged: Privileged,
+    connection: NbdConnection,
+    /// None in a dry run, which only prints the cleanup commands
+    supervisor: Option<Child>,
+}
+
+impl NbdSupervisor {
+    fn start(
+        privileged: Privileged,
+        state: &NbdState,
+        mut connection: NbdConnection,
+    ) -> Result<Self> {
+        if privileged.dry_run {
+            return Ok(Self {
+                privileged,
+                connection,
+                supervisor: None,
+            });
+        }
+
+        let record = state.save(&connection)?;
+        let mut command = Command::new(std::env::current_exe()?);
+        command
+            .arg("nbd-supervise")
+            .arg(&record)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null());
+        // Ctrl-C and a closed terminal stop this run, not its cleanup
+        unsafe {
+            command.pre_exec(|| {
+                libc::signal(libc::SIGINT, libc::SIG_IGN);
+                libc::signal(libc::SIGHUP, libc::SIG_IGN);
+                Ok(())
+            });
+        }
+        let supervisor = command
+            .spawn()
+            .context("Failed to start the NBD cleanup supervisor")?;
+        connection.supervisor_pid = Some(supervisor.id());
+        state.save(&connection)?;
+        Ok(Self {
+            privileged,
+            connection,
+            supervisor: Some(supervisor),
+        })
+    }
+}
+
+impl NbdSupervisor {
+    /// Record something teardown has to undo, before doing it.
+    fn update(&mut self, state: &NbdState, change: impl FnOnce(&mut NbdConnection)) -> Result<()> {
+        change(&mut self.connection);
+        if self.supervisor.is_some() {
+            state.save(&self.connection)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for NbdSupervisor {
+    fn drop(&mut self) {
+        match self.supervisor {
+            Some(ref mut supervisor) => {
+                drop(supervisor.stdin.take());
+                let _ = supervisor.wait();
+            }
+            None => nbd_disconnect(self.privileged, &self.connection),
+        }
+    }
+}
+
+/// Wait for the fsdbg run that spawned us to exit, then disconnect its
+/// NBD connection (see [`NbdSupervisor`]).
+fn cmd_nbd_supervise(record: &Path) -> Result<bool> {
+    let _ = std::io::copy(&mut std::io::stdin(), &mut std::io::sink());
+    let connection = NbdState::load(record)?;
+    let privileged = Privileged {
+        sudo: connection.sudo,
+        dry_run: false,
+    };
+    nbd_disconnect(privileged, &connection);
+    let _ = std::fs::remove_file(record);
+    Ok(true)
+}
+
+fn cmd_cleanup_nbd(dry_run: bool) -> Result<bool> {
+    let state = NbdState::new(NbdState::default_dir());
+    let privileged = Privileged {
+        sudo: unsafe { libc::getuid() } != 0,
+        dry_run,
+    };
+    if dry_run {
+        println!("Dry run: commands to clean up stale connections:");
+    }
+    let cleaned = nbd_cleanup_stale(privileged, &state);
+
+    for connection in state.connections().iter().filter(|c| !c.is_stale()) {
+        println!(
+            "{} ({}) in use by fsdbg pid {}",
+            connection.device,
+            connection.image.display(),
+            connection.owner_pid
+        );
+    }
+    // Connected devices fsdbg has no record of aren't ours to disconnect
+    let recorded: Vec<_> = state.connections().into_iter().map(|c| c.device).collect();
+    for i in 0..16 {
+        let device = format!("/dev/nbd{}", i);
+        let connected = Path::new(&format!("/sys/block/nbd{}/pid", i)).exists();
+        if connected && !recorded.contains(&device) && !dry_run {
+            println!(
+                "{} is connected, but not by fsdbg (disconnect: sudo qemu-nbd -d {})",
+                device, device
+            );
+        }
+    }
+    if cleaned == 0 && !dry_run {
+        println!("No stale fsdbg NBD connections");
+    }
+    Ok(true)
+}
+
+fn cmd_doctor(path: &Path, mode: ReportMode, catalog: &Catalog) -> Result<bool> {
+    let mut report = crate::doctor::examine(path)?;
+    report.fingerprint = Some(Fingerprint::of(path)?);
+    print_report(&report, mode, catalog);
+    Ok(report.is_success())
+}
+
+/// Separator line lsinitrd prints between sections
+const LSINITRD_RULE: &str =
+    "========================================================================";
+
+fn cmd_lsinitrd(path: &Path, modules_only: bool, options: ParseOptions) -> Result<bool> {
+    let segments = crate::cpio::segments(path)?;
+    let Some((main, early)) = segments.split_last() else {
+        return Err(FsdbgError::invalid_format("Empty image")
+            .with_path(path)
+            .into());
+    };
+    let hash = options.hash;
+    let reader = CpioReader::open_with(path, main.range, options)?;
+    let listing = |entry: &crate::cpio::CpioEntry| match hash {
+        Some(_) => format!(
+            "{:<64} {}",
+            entry.digest.as_deref().unwrap_or("-"),
+            long_listing(entry)
+        ),
+        None => long_listing(entry),
+    };
+
+    // dracut records its version, arguments and modules; other builders
+    // (recinit included) don't, and those sections are left out
+    let dracut_dir = ["usr/lib/dracut", "lib/dracut"]
+        .into_iter()
+        .find(|dir| reader.exists(&format!("{}/modules.txt", dir)));
+    let modules = match dracut_dir {
+        Some(dir) => reader.read_file_to_string(&format!("{}/modules.txt", dir))?,
+        None => String::new(),
+    };
+
+    if modules_only {
+        if dracut_dir.is_none() {
+            println!("No dracut modules (image was not built by dracut)");
+            return Ok(false);
+        }
+        println!("dracut modules:");
+        modules.lines().for_each(|m| println!("{}", m));
+        return Ok(true);
+    }
+
+    let size = std::fs::metadata(path)?.len();
+    println!("Image: {}: {}", path.display(), human_size(size));
+    println!("{}", LSINITRD_RULE);
+
+    if !early.is_empty() {
+        println!("Early CPIO image");
+        println!("{}", LSINITRD_RULE);
+        for segment in early {
+            for entry in CpioReader::open_with(path, segment.range, options)?.entries() {
+                println!("{}", listing(entry));
+            }
+        }
+        println!("{}", LSINITRD_RULE);
+    }
+
+    if let Some(dir) = dracut_dir {
+        let version = reader
+            .entries()
+            .iter()
+            .find(|e| e.path.starts_with(&format!("{}/dracut-", dir)))
+            .and_then(|e| reader.read_file_to_string(&e.path).ok());
+        if let Some(version) = version {
+            println!("Version: {}", version.trim());
+            println!();
+        }
+        if let Ok(arguments) = reader.read_file_to_string(&format!("{}/build-parameter.txt", dir)) {
+            println!("Arguments: {}", arguments.trim());
+            println!();
+        }
+        println!("dracut modules:");
+        modules.lines().for_each(|m| println!("{}", m));
+        println!("{}", LSINITRD_RULE);
+    }
+
+    for entry in reader.entries() {
+        println!("{}", listing(entry));
+    }
+    println!("{}", LSINITRD_RULE);
+    Ok(true)
+}
+
+/// `cpio -tv` style line, as lsinitrd prints.
+fn long_listing(entry: &crate::cpio::CpioEntry) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let owner = |id: u32| match id {
+        0 => "root".to_string(),
+        id => id.to_string(),
+    };
+    let (year, month, day) = crate::report::civil_date(entry.mtime as u64);
+
+    let mut line = format!(
+        "{} {:>3} {:<8} {:<8} {:>8} {} {:>2} {:>5} {}",
+        entry.mode_string(),
+        entry.nlink,
+        owner(entry.uid),
+        owner(entry.gid),
+        entry.size,
+        MONTHS[month as usize - 1],
+        day,
+        year,
+        entry.path
+    );
+    if let Some(ref target) = entry.link_target {
+        line.push_str(" -> ");
+        line.push_str(target);
+    }
+    if entry.is_whiteout() {
+        line.push_str(" (whiteout)");
+    }
+    line
+}
+
+/// Print entries grouped by mtime, listing the paths outside the bulk group.
+fn cmd_timeline(
+    path: &Path,
+    granularity: Granularity,
+    limit: usize,
+    limits: Limits,
+) -> Result<bool> {
+    let format = crate::detect_format(path).context("Failed to detect archive format")?;
+    if !matches!(format, ArchiveFormat::Cpio | ArchiveFormat::CpioGzip) {
+        bail!("timeline requires a CPIO archive");
+    }
+
+    // Early microcode archives count too: a stale blob shows up as its own group
+    let mut entries = Vec::new();
+    for segment in crate::cpio::segments(path)? {
+        entries.extend(
+            CpioReader::open_with(
+                path,
+                segment.range,
+                ParseOptions {
+                    limits,
+                    ..Default::default()
+                },
+            )?
+            .entries()
+            .to_vec(),
+        );
+    }
+    let groups = crate::timeline::group(&entries, granularity);
+    let bulk = crate::timeline::bulk(&groups);
+
+    println!("=== Timeline: {} ===", path.display());
+    println!("{} entries in {} groups", entries.len(), groups.len());
+    println!();
+    for (i, group) in groups.iter().enumerate() {
+        let count = group.paths.len();
+        let marker = if Some(i) == bulk { "  (bulk)" } else { "" };
+        println!(
+            "{}  {:>6} {}{}",
+            crate::report::format_timestamp(group.start),
+            count,
+            if count == 1 { "entry" } else { "entries" },
+            marker
+        );
+        if Some(i) != bulk {
+            for path in group.paths.iter().take(limit) {
+                println!("    {}", path);
+            }
+            if count > limit {
+                println!("    ... and {} more", count - limit);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// `du -h` style size (e.g. 9.5M, 31M).
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["", "K", "M", "G", "T"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => bytes.to_string(),
+        _ if size < 10.0 => format!("{:.1}{}", size, UNITS[unit]),
+        _ => format!("{:.0}{}", size, UNITS[unit]),
+    }
+}
+
+fn cmd_extract_initrd(image: &Path, output: &Path) -> Result<bool> {
+    let (range, source) = if crate::bootimg::is_boot_img(image)? {
+        // Whatever the compression; an lz4 ramdisk is why this is run
+        (crate::bootimg::BootImage::open(image)?.ramdisk, "ramdisk")
+    } else {
+        (crate::uki::initrd_range(image)?, crate::uki::INITRD_SECTION)
+    };
+    let mut initrd = range.open(image)?;
+    let mut out = std::fs::File::create(output)
+        .with_context(|| format!("Failed to create {}", output.display()))?;
+    let written = std::io::copy(&mut initrd, &mut out)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    println!(
+        "Wrote {} ({} bytes from {} at offset {:#x})",
+        output.display(),
+        written,
+        source,
+        range.offset
+    );
+    Ok(true)
+}
+
+fn cmd_inspect_dtb(files: &[PathBuf]) -> Result<bool> {
+    for file in files {
+        let data =
+            std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        let tree = crate::dtb::DeviceTree::parse(&data).map_err(|e| e.with_path(file))?;
+        println!("{}:", file.display());
+        println!(
+            "  Model:      {}",
+            tree.model.as_deref().unwrap_or("(none)")
+        );
+        println!("  Compatible: {}", tree.compatible.join(", "));
+        println!(
+            "  FDT:        version {}, {} bytes",
+            tree.version, tree.size
+        );
+    }
+    Ok(true)
+}
+
+/// The spec to verify against: `spec` (a snapshot, or the compiled-in
+/// lists) narrowed to `profile`.
+fn load_spec(
+    spec: Option<&Path>,
+    profile: Option<&str>,
+    profile_files: &[PathBuf],
+) -> Result<Spec> {
+    let loaded = spec.map(Spec::load).transpose()?;
+    let base = loaded.as_ref().unwrap_or(Spec::builtin());
+    Ok(match profile {
+        Some(name) => {
+            let profiles = crate::profile::available(profile_files)?;
+            crate::profile::find(&profiles, name)?.apply(base, &profiles)?
+        }
+        None => base.clone(),
+    })
+}
+
+fn cmd_profiles(profile_files: &[PathBuf]) -> Result<bool> {
+    let profiles = crate::profile::available(profile_files)?;
+    let spec = Spec::builtin();
+    for profile in &profiles {
+        let narrowed = profile.apply(spec, &profiles)?;
+        let left_out: usize = spec
+            .compare(&narrowed)
+            .iter()
+            .map(|change| change.removed.len())
+            .sum();
+        match profile.source {
+            Some(ref path) => println!("{} ({})", profile.name, path.display()),
+            None => println!("{} (built-in)", profile.name),
+        }
+        if let Some(ref description) = profile.description {
+            println!("  {}", description);
+        }
+        if let Some(ref base) = profile.base {
+            println!("  Base:    {}", base);
+        }
+        if !profile.lists.is_empty() {
+            println!("  Lists:   {}", profile.lists.join(", "));
+        }
+        if !profile.exclude.is_empty() {
+            println!("  Exclude: {}", profile.exclude.join(", "));
+        }
+        if profile.secure_boot {
+            println!("  Requires Secure Boot artifacts (signed EFI binaries, shim, MokManager)");
+        }
+        println!(
+            "  Leaves out {} item(s) of distro-spec {}",
+            left_out, spec.version
+        );
+    }
+    Ok(true)
+}
+
+fn cmd_provenance(path: &Path, spec: &Spec, mode: ReportMode, catalog: &Catalog) -> Result<bool> {
+    use crate::provenance::{self, MANIFEST_PATH};
+
+    let Some((manifest, source)) = provenance::find(path)? else {
+        println!(
+            "No provenance manifest: no /{} in {}, and no {}",
+            MANIFEST_PATH,
+            path.display(),
+            provenance::sidecar_path(path).display()
+        );
+        return Ok(false);
+    };
+    let fingerprint = Fingerprint::of(path)?;
+
+    if !mode.is_terse() {
+        println!("=== Provenance: {} ===", path.display());
+        println!("Manifest: {}", source);
+        println!("Build:    {}", manifest.build_id);
+        if let Some(ref commit) = manifest.git_commit {
+            println!("Commit:   {}", commit);
+        }
+        match manifest.distro_spec.digest {
+            Some(ref digest) => println!(
+                "Spec:     distro-spec {} ({})",
+                manifest.distro_spec.version, digest
+            ),
+            None => println!("Spec:     distro-spec {}", manifest.distro_spec.version),
+        }
+        if let Some(created) = manifest.created {
+            println!("Built:    {}", crate::report::format_timestamp(created));
+        }
+        println!();
+    }
+
+    let mut report = VerificationReport::new("Provenance");
+    for result in manifest.check(&source, spec, Some(&fingerprint.sha256)) {
+        report.add(result);
+    }
+    report.fingerprint = Some(fingerprint);
+    print_report(&report, mode, catalog);
+    Ok(report.is_success())
+}
+
+fn cmd_release_gate(
+    artifacts: &[crate::release::Artifact],
+    policy: &Path,
+    output: &Path,
+    spec: &Spec,
+    signing_key: Option<&Path>,
+    mode: ReportMode,
+    catalog: &Catalog,
+) -> Result<bool> {
+    use crate::release::{self, ArtifactVerdict, Policy, Verdict};
+
+    let policy = Policy::load(policy)?;
+    let signing_key = signing_key.or(policy.signing_key.as_deref());
+    println!("=== Release Gate: {} ===", policy.source.display());
+    println!();
+
+    let mut verdicts = Vec::new();
+    // One line per artifact for the printed report; the verdict has the details
+    let mut summaries = Vec::new();
+    let mut checks = Vec::new();
+    for artifact in artifacts {
+        let checklist = policy.checklist(artifact.role);
+        println!(
+            "Verifying {} {} ({})...",
+            artifact.role,
+            artifact.path.display(),
+            checklist.name()
+        );
+        let fingerprint = Fingerprint::of(&artifact.path)?;
+        let extensions = crate::checklist::extensions::discover(Some(&artifact.path))
+            .iter()
+            .map(|file| crate::checklist::extensions::load(file))
+            .collect::<Result<_, _>>()?;
+        let options = VerifyOptions {
+            mode: ReportMode::QUIET,
+            catalog: catalog.clone(),
+            save_report: None,
+            fix_script: None,
+            probes: &[],
+            signature: None,
+            timestamps: None,
+            empty_globs: &[],
+            special_globs: &[],
+            secret_globs: &[],
+            overlay_dir: None,
+            lower: None,
+            fast_listing: false,
+            ignore: IgnoreList::default(),
+            strict: false,
+            lint_names: false,
+            lenient: false,
+            duplicates: DuplicatePolicy::default(),
+            timings: false,
+            cache: None,
+            incremental: false,
+            extensions,
+            spec: Some(spec),
+            limits: Limits::default(),
+            arch: None,
+            dry_run: false,
+            no_sudo: false,
+            nbd: false,
+            unlock: None,
+        };
+        let run = match checklist {
+            ChecklistType::Qcow2 => verify_qcow2_rootless(&artifact.path, ReportMode::QUIET),
+            _ => run_checklist(&artifact.path, checklist, &options),
+        };
+        // A checklist that can't run fails the gate rather than aborting it
+        let report = run.unwrap_or_else(|e| {
+            let mut report = VerificationReport::new(checklist.name());
+            report.add(CheckResult::fail(
+                format!("checklist: {}", checklist.key()),
+                CheckCategory::Other,
+                error_text(&e),
+            ));
+            report
+        });
+        summaries.push(if report.is_success() {
+            CheckResult::pass(
+                format!(
+                    "{}: {} ({} passed)",
+                    artifact.role,
+                    checklist.key(),
+                    report.passed()
+                ),
+                CheckCategory::Other,
+            )
+        } else {
+            CheckResult::fail(
+                format!("{}: {}", artifact.role, checklist.key()),
+                CheckCategory::Other,
+                format!(
+                    "{} failed, {} passed; run fsdbg verify -t {} {} for details",
+                    report.failed(),
+                    report.passed(),
+                    checklist.key(),
+                    artifact.path.display()
+                ),
+            )
+        });
+        verdicts.push(ArtifactVerdict::new(
+            artifact,
+            &fingerprint,
+            checklist,
+            &report,
+        ));
+        if let Some(&budget) = policy.budgets.get(&artifact.role) {
+            checks.push(release::check_budget(
+                artifact.role,
+                fingerprint.size,
+                budget,
+            ));
+        }
+    }
+    println!();
+
+    if let Some(ref keyring) = policy.keyring {
+        for role in &policy.signed {
+            match artifacts.iter().find(|a| a.role == *role) {
+                Some(artifact) => checks.push(release::check_signature(artifact, keyring)),
+                None => eprintln!(
+                    "Warning: the policy requires a signed {}, but no --{} was given",
+                    role, role
+                ),
+            }
+        }
+    }
+    checks.extend(release::check_consistency(artifacts, &policy, spec));
+
+    let timestamp = SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let verdict = Verdict::new(timestamp, &policy, spec, verdicts, checks.clone());
+
+    let json = serde_json::to_string_pretty(&verdict)? + "\n";
+    std::fs::write(output, &json)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    let mut report = VerificationReport::new("Release Gate");
+    for check in summaries.into_iter().chain(checks) {
+        report.add(check);
+    }
+    print_report(&report, mode, catalog);
+    println!();
+    println!("Verdict: {}", output.display());
+    if let Some(key) = signing_key {
+        let (signature, public) = crate::signature::sign_ed25519(json.as_bytes(), key)?;
+        let mut sig = output.as_os_str().to_owned();
+        sig.push(".sig");
+        let sig = PathBuf::from(sig);
+        std::fs::write(&sig, signature)
+            .with_context(|| format!("Failed to write {}", sig.display()))?;
+        println!("Signed:  {} (ed25519 key {})", sig.display(), public);
+    } else {
+        eprintln!("Warning: the verdict is unsigned; set [signing] key in the policy or pass --signing-key");
+    }
+    Ok(verdict.passed)
+}
+
+fn cmd_verify_signature(artifact: &Path, sig: &Path, keyring: &Path) -> Result<bool> {
+    println!("=== Signature Verification: {} ===", artifact.display());
+    println!();
+
+    let status = crate::signature::verify(artifact, sig, keyring)?;
+
+    println!("Signature: {} ({})", sig.display(), status.kind);
+    if let Some(ref signer) = status.signer {
+        println!("Signer: {}", signer);
+    }
+    println!("{}", status.message);
+    println!();
+
+    if status.valid {
+        println!("Result: PASS");
+    } else {
+        println!("Result: FAIL");
+    }
+    Ok(status.valid)
+}
+
+fn cmd_verify_media(path: &Path, implant: bool, force: bool) -> Result<bool> {
+    if implant {
+        let embedded = crate::isomd5::implant(path, force)?;
+        println!("Implanted MD5 {} into {}", embedded.md5, path.display());
+        return Ok(true);
+    }
+
+    println!("=== Media Checksum: {} ===", path.display());
+    println!();
+
+    let Some(verification) = crate::isomd5::verify(path)? else {
+        println!("No embedded media checksum (add one with --implant)");
+        println!();
+        println!("Result: FAIL");
+        return Ok(false);
+    };
+    let embedded = &verification.embedded;
+    println!(
+        "Embedded: {} (last {} sectors skipped, {} fragment sums)",
+        embedded.md5, embedded.skip_sectors, embedded.fragment_count
+    );
+    println!("Computed: {}", verification.md5);
+    if let Some(fragment) = verification.bad_fragment {
+        println!(
+            "First corrupted fragment: {} of {}",
+            fragment, embedded.fragment_count
+        );
+    }
+    println!();
+
+    if verification.matches() {
+        println!("Result: PASS");
+    } else {
+        println!("Result: FAIL");
+    }
+    Ok(verification.matches())
+}
+
+fn cmd_check_symlinks(path: &Path, limits: Limits) -> Result<bool> {
+    let format = crate::detect_format(path)?;
+
+    println!("=== Symlink Verification: {} ===", path.display());
+    println!();
+
+    let mut broken = Vec::new();
+    let mut valid = 0;
+
+    match format {
+        ArchiveFormat::Cpio | ArchiveFormat::CpioGzip => {
+            let parse = ParseOptions {
+                limits,
+                ..Default::default()
+            };
+            let reader = CpioReader::open_with(path, ByteRange::default(), parse)?;
+
+            for entry in reader.symlinks() {
+                if reader.symlink_target_exists(entry) {
+                    valid += 1;
+                } else if let Some(ref target) = entry.link_target {
+                    broken.push((entry.path.clone(), target.clone()));
+                }
+            }
+        }
+        ArchiveFormat::Iso => {
+            let reader = IsoReader::open(path)?;
+
+            for entry in reader.symlinks() {
+                if reader.exists(entry.link_target.as_deref().unwrap_or("")) {
+                    valid += 1;
+                } else if let Some(ref target) = entry.link_target {
+                    broken.push((entry.path.clone(), target.clone()));
+                }
+            }
+        }
+        _ => return Err(FsdbgError::unsupported_format("symlink checking of this format").into()),
+    }
+
+    println!("Valid symlinks: {}", valid);
+
+    if broken.is_empty() {
+        println!("Broken symlinks: 0");
+        println!();
+        println!("Result: PASS");
+        Ok(true)
+    } else {
+        println!("Broken symlinks: {}", broken.len());
+        println!();
+        for (link, target) in &broken {
+            println!("  [BROKEN] {} -> {}", link, target);
+        }
+        println!();
+        println!("Result: FAIL");
+        Ok(false)
+    }
+}
+
+fn cmd_metrics(
+    path: &Path,
+    checklist_type: Option<&str>,
+    output: Option<&Path>,
+    limits: Limits,
+) -> Result<bool> {
+    let format = crate::detect_format(path)?;
+    let artifact = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string());
+
+    let mut metrics = crate::metrics::Metrics::new();
+    metrics.gauge(
+        "fsdbg_archive_bytes",
+        "Size of the artifact on disk",
+        &[("artifact", &artifact)],
+        std::fs::metadata(path)?.len(),
+    );
+    metrics.add_entries(
+        &artifact,
+        &diff_entries(path, format, limits, &IgnoreList::new())?,
+    );
+
+    if let Some(name) = checklist_type {
+        let checklist = parse_checklist(name)?;
+        if checklist == ChecklistType::Qcow2 {
+            bail!("Metrics do not support the qcow2 checklist (requires mounting)");
+        }
+        let options = VerifyOptions {
+            limits,
+            ..Default::default()
+        };
+        let report = run_checklist(path, checklist, &options)?;
+        metrics.add_report(&artifact, &report);
+    }
+
+    let text = metrics.render();
+    match output {
+        Some(out) => {
+            // Write then rename so the textfile collector never reads a partial file
+            let tmp = out.with_extension("prom.tmp");
+            std::fs::write(&tmp, text)
+                .with_context(|| format!("Failed to write {}", tmp.display()))?;
+            std::fs::rename(&tmp, out)
+                .with_context(|| format!("Failed to write {}", out.display()))?;
+        }
+        None => print!("{}", text),
+    }
+
+    Ok(true)
+}
+
+/// Resolve a `--scope` checklist name to its path scope.
+fn parse_scope(scope: Option<&str>) -> Result<Option<(ChecklistType, Scope)>> {
+    let Some(name) = scope else {
+        return Ok(None);
+    };
+    let checklist = ChecklistType::parse_name(name)
+        .ok_or_else(|| anyhow::anyhow!("Unknown checklist type: {}", name))?;
+    let scope = crate::checklist::scope(checklist, Spec::builtin())
+        .ok_or_else(|| anyhow::anyhow!("Checklist '{}' has no path scope", checklist.name()))?;
+    Ok(Some((checklist, scope)))
+}
+
+fn cmd_diff(
+    path1: &Path,
+    path2: &Path,
+    scope: Option<&str>,
+    limits: Limits,
+    ignore: &IgnoreList,
+) -> Result<bool> {
+    let scope = parse_scope(scope)?;
+
+    let format1 = crate::detect_format(path1)?;
+    let format2 = crate::detect_format(path2)?;
+
+    println!("=== Diff ===");
+    println!("Archive 1: {} ({})", path1.display(), format_name(&format1));
+    println!("Archive 2: {} ({})", path2.display(), format_name(&format2));
+    println!();
+
+    let [entries1, entries2] =
+        comparable_entries([(path1, format1), (path2, format2)], limits, ignore)?;
+    let changes = crate::diff::compare(&entries1, &entries2);
+
+    if let Some((checklist, scope)) = scope {
+        print_scoped_diff(&changes, checklist, &scope);
+        return Ok(true);
+    }
+
+    let only_in_1: Vec<_> = changes
+        .iter()
+        .filter(|(_, c)| *c == Change::Removed)
+        .map(|(p, _)| p)
+        .collect();
+    let only_in_2: Vec<_> = changes
+        .iter()
+        .filter(|(_, c)| *c == Change::Added)
+        .map(|(p, _)| p)
+        .collect();
+    let changed: Vec<_> = changes
+        .iter()
+        .filter_map(|(p, c)| match c {
+            Change::Modified(fields) => Some((p, fields)),
+            _ => None,
+        })
+        .collect();
+    let in_both = entries1.len() - only_in_1.len();
+
+    println!("Files in both: {}", in_both);
+    println!("Only in archive 1: {}", only_in_1.len());
+    println!("Only in archive 2: {}", only_in_2.len());
+    println!("Changed: {}", changed.len());
+
+    if !only_in_1.is_empty() {
+        println!();
+        println!("Only in {}:", path1.display());
+        for f in only_in_1.iter().take(50) {
+            println!("  - {}", f);
+        }
+        if only_in_1.len() > 50 {
+            println!("  ... and {} more", only_in_1.len() - 50);
+        }
+    }
+
+    if !only_in_2.is_empty() {
+        println!();
+        println!("Only in {}:", path2.display());
+        for f in only_in_2.iter().take(50) {
+            println!("  + {}", f);
+        }
+        if only_in_2.len() > 50 {
+            println!("  ... and {} more", only_in_2.len() - 50);
+        }
+    }
+
+    if !changed.is_empty() {
+        println!();
+        println!("Changed:");
+        for (f, fields) in changed.iter().take(50) {
+            let fields: Vec<String> = fields.iter().map(|c| c.to_string()).collect();
+            println!("  ~ {}: {}", f, fields.join(", "));
+        }
+        if changed.len() > 50 {
+            println!("  ... and {} more", changed.len() - 50);
+        }
+    }
+
+    Ok(true)
+}
+
+fn cmd_diff3(
+    base: &Path,
+    ours: &Path,
+    theirs: &Path,
+    scope: Option<&str>,
+    limits: Limits,
+    ignore: &IgnoreList,
+) -> Result<bool> {
+    let scope = parse_scope(scope)?;
+
+    println!("=== Three-way Diff ===");
+    let mut archives = Vec::new();
+    for (label, path) in [("Base", base), ("Ours", ours), ("Theirs", theirs)] {
+        let format = crate::detect_format(path)?;
+        println!("{}: {} ({})", label, path.display(), format_name(&format));
+        archives.push((path, format));
+    }
+    let archives: [_; 3] = archives.try_into().expect("three archives");
+    let entries = comparable_entries(archives, limits, ignore)?;
+    println!();
+
+    let mut changes = crate::diff::compare3(&entries[0], &entries[1], &entries[2]);
+    if let Some((checklist, scope)) = &scope {
+        changes.retain(|c| scope.category_of(&c.path).is_some());
+        println!("Scope: {} checklist", checklist.name());
+        println!();
+    }
+
+    if changes.is_empty() {
+        println!("No differences from base.");
+        return Ok(true);
+    }
+
+    let mut groups: std::collections::BTreeMap<Origin, Vec<&ThreeWayChange>> =
+        std::collections::BTreeMap::new();
+    for change in &changes {
+        groups.entry(change.origin).or_default().push(change);
+    }
+
+    for (origin, changes) in &groups {
+        println!("{}: {}", origin, changes.len());
+    }
+
+    for (origin, changes) in &groups {
+        println!();
+        println!("{}:", origin);
+        for change in changes.iter().take(50) {
+            match origin {
+                Origin::Ours => println!("  {}: {}", change.path, side(&change.ours)),
+                Origin::Theirs => println!("  {}: {}", change.path, side(&change.theirs)),
+                Origin::Both => println!("  {}: {}", change.path, side(&change.ours)),
+                Origin::Conflict => {
+                    println!("  {}", change.path);
+                    println!("      ours:   {}", side(&change.ours));
+                    println!("      theirs: {}", side(&change.theirs));
+                }
+            }
+        }
+        if changes.len() > 50 {
+            println!("  ... and {} more", changes.len() - 50);
+        }
+    }
+
+    Ok(true)
+}
+
+fn cmd_diff_layers(
+    path1: &Path,
+    path2: &Path,
+    scope: Option<&str>,
+    ignore: &IgnoreList,
+) -> Result<bool> {
+    use crate::checklist::live_overlay::unintended_shadow;
+    use crate::diff::Layer;
+
+    let scope = parse_scope(scope)?;
+
+    println!("=== Layered Diff ===");
+    let mut composed = Vec::new();
+    for (label, path) in [("Archive 1", path1), ("Archive 2", path2)] {
+        if crate::detect_format(path)? != ArchiveFormat::Iso {
+            bail!(
+                "--layers compares live ISOs; {} isn't an ISO",
+                path.display()
+            );
+        }
+        let (mut layered, upper) = layered_entries(&IsoReader::open(path)?)?;
+        layered.retain(|p| !ignore.matches(p));
+        if let Some((_, scope)) = &scope {
+            layered.retain(|p| scope.category_of(p).is_some());
+        }
+        println!(
+            "{}: {} (lower {}, upper {})",
+            label,
+            path.display(),
+            distro_spec::shared::ROOTFS_ISO_PATH,
+            upper
+        );
+        composed.push(layered);
+    }
+    let (old, new) = (&composed[0], &composed[1]);
+    if let Some((checklist, _)) = &scope {
+        println!("Scope: {} checklist", checklist.name());
+    }
+    println!();
+
+    let changes = crate::diff::compare_layered(old, new);
+    let mut groups: std::collections::BTreeMap<&str, Vec<_>> = std::collections::BTreeMap::new();
+    for change in &changes {
+        let group = match (change.old, change.new) {
+            (Some(old), Some(new)) if old != new => "Moved between layers",
+            (Some(Layer::Upper), _) | (_, Some(Layer::Upper)) => "Changed in upper",
+            _ => "Changed in lower",
+        };
+        groups.entry(group).or_default().push(change);
+    }
+    if changes.is_empty() {
+        println!("No differences in the composed root filesystem.");
+    }
+    for (group, changes) in &groups {
+        println!("{}: {}", group, changes.len());
+    }
+    for (group, changes) in &groups {
+        println!();
+        println!("{}:", group);
+        for change in changes.iter().take(50) {
+            let (mark, what) = match &change.change {
+                Some(Change::Added) => ("+", None),
+                Some(Change::Removed) => ("-", None),
+                Some(modified) => ("~", Some(modified.to_string())),
+                None => ("~", None),
+            };
+            match (change.old, change.new, what) {
+                (Some(old), Some(new), what) if old != new => println!(
+                    "  {} {}: {} -> {}{}",
+                    mark,
+                    change.path,
+                    old,
+                    new,
+                    what.map(|w| format!(", {}", w)).unwrap_or_default()
+                ),
+                (_, _, Some(what)) => println!("  {} {}: {}", mark, change.path, what),
+                _ => println!("  {} {}", mark, change.path),
+            }
+        }
+        if changes.len() > 50 {
+            println!("  ... and {} more", changes.len() - 50);
+        }
+    }
+
+    // Overlay entries hiding rootfs ones the overlay isn't meant to replace
+    let unintended = |layered: &crate::diff::Layered| {
+        layered
+            .shadows
+            .iter()
+            .filter_map(|(path, hidden)| {
+                Some((
+                    path.clone(),
+                    (hidden.kind, unintended_shadow(path, Spec::builtin())?),
+                ))
+            })
+            .collect::<std::collections::BTreeMap<_, _>>()
+    };
+    let (before, after) = (unintended(old), unintended(new));
+    let fixed = before.keys().filter(|p| !after.contains_key(*p)).count();
+    println!();
+    println!(
+        "Overlay shadowing rootfs files: {} in archive 2 ({} new, {} gone since archive 1)",
+        after.len(),
+        after.keys().filter(|p| !before.contains_key(*p)).count(),
+        fixed
+    );
+    for (path, (kind, note)) in after.iter().take(50) {
+        let new = if before.contains_key(path) {
+            ""
+        } else {
+            " [new]"
+        };
+        println!("  ! {}: hides rootfs {} ({}){}", path, kind, note, new);
+    }
+    if after.len() > 50 {
+        println!("  ... and {} more", after.len() - 50);
+    }
+
+    Ok(after.is_empty())
+}
+
+/// A live ISO's root filesystem as the overlay stacked on its rootfs
+/// composes it, and the ISO path of the overlay: the `live/overlayfs.erofs`
+/// payload, or the `live/overlay` directory of older ISOs.
+fn layered_entries(iso: &IsoReader) -> Result<(crate::diff::Layered, &'static str)> {
+    use distro_spec::shared::{LIVE_OVERLAYFS_ISO_PATH, LIVE_OVERLAY_ISO_PATH};
+
+    let rootfs = extract_iso_image(iso, distro_spec::shared::ROOTFS_ISO_PATH)?;
+    let lower = crate::diff::erofs_entries(&ErofsReader::open(rootfs.path())?);
+    let (upper, whiteouts, source) = if iso.exists(LIVE_OVERLAYFS_ISO_PATH) {
+        let image = extract_iso_image(iso, LIVE_OVERLAYFS_ISO_PATH)?;
+        let (upper, whiteouts) = crate::diff::erofs_layer(&ErofsReader::open(image.path())?);
+        (upper, whiteouts, LIVE_OVERLAYFS_ISO_PATH)
+    } else if iso.exists(LIVE_OVERLAY_ISO_PATH) {
+        let prefix = format!("{}/", LIVE_OVERLAY_ISO_PATH);
+        let upper = crate::diff::iso_entries(iso)
+            .into_iter()
+            .filter_map(|(path, entry)| Some((path.strip_prefix(&prefix)?.to_string(), entry)))
+            .collect();
+        (upper, Vec::new(), LIVE_OVERLAY_ISO_PATH)
+    } else {
+        return Err(FsdbgError::missing_required(format!(
+            "live overlay ({} or {})",
+            LIVE_OVERLAYFS_ISO_PATH, LIVE_OVERLAY_ISO_PATH
+        ))
+        .into());
+    };
+    let mut layered = crate::diff::compose(&lower, &upper, &whiteouts, &[]);
+    layered.retain(|path| !path.is_empty());
+    Ok((layered, source))
+}
+
+/// Describe one side of a three-way change relative to base.
+fn side(change: &Option<Change>) -> String {
+    change
+        .as_ref()
+        .map_or_else(|| "unchanged".to_string(), |c| c.to_string())
+}
+
+/// Read comparable entries (path, type, mode, ownership, link target),
+/// skipping ignored paths.
+fn diff_entries(
+    path: &Path,
+    format: ArchiveFormat,
+    limits: Limits,
+    ignore: &IgnoreList,
+) -> Result<crate::diff::EntryMap> {
+    let mut entries = match format {
+        ArchiveFormat::Cpio | ArchiveFormat::CpioGzip => {
+            let parse = ParseOptions {
+                limits,
+                ..Default::default()
+            };
+            crate::diff::cpio_entries(&CpioReader::open_with(path, ByteRange::default(), parse)?)
+        }
+        ArchiveFormat::Iso => crate::diff::iso_entries(&IsoReader::open(path)?),
+        ArchiveFormat::Erofs => crate::diff::erofs_entries(&ErofsReader::open(path)?),
+    };
+    entries.retain(|p, _| !ignore.matches(p));
+    Ok(entries)
+}
+
+/// Copy the rootfs an ISO embeds (`live/filesystem.erofs`) to a temporary
+/// file the EROFS tools can read.
+fn extract_iso_rootfs(iso: &IsoReader) -> Result<tempfile::NamedTempFile> {
+    extract_iso_image(iso, distro_spec::shared::ROOTFS_ISO_PATH)
+}
+
+/// Copy an EROFS image on an ISO to a temporary file.
+fn extract_iso_image(iso: &IsoReader, path: &str) -> Result<tempfile::NamedTempFile> {
+    let mut image = tempfile::Builder::new()
+        .prefix("fsdbg-rootfs-")
+        .tempfile()?;
+    std::io::Write::write_all(&mut image, &iso.read_file(path)?)?;
+    Ok(image)
+}
+
+/// First-boot hygiene of the rootfs an ISO embeds, items prefixed with its
+/// path on the ISO.
+fn iso_first_boot(iso: &IsoReader) -> Result<Vec<CheckResult>> {
+    let rootfs = distro_spec::shared::ROOTFS_ISO_PATH;
+    let image = extract_iso_rootfs(iso)?;
+    let reader = ErofsReader::open(image.path())?;
+    if reader.entries().is_empty() {
+        return Ok(vec![CheckResult::fail(
+            rootfs,
+            CheckCategory::Other,
+            "Cannot list the rootfs for first-boot checks (needs dump.erofs)",
+        )]);
+    }
+    let mut results = crate::checklist::first_boot::check_erofs(&reader);
+    for result in &mut results {
+        result.item = format!("{}:{}", rootfs, result.item);
+    }
+    Ok(results)
+}
+
+/// Entries of archives being compared, normalized when their formats differ.
+///
+/// Next to a CPIO or EROFS rootfs, an ISO stands for the rootfs it embeds
+/// (`live/filesystem.erofs`); its own top-level layout has nothing to
+/// compare against.
+fn comparable_entries<const N: usize>(
+    archives: [(&Path, ArchiveFormat); N],
+    limits: Limits,
+    ignore: &IgnoreList,
+) -> Result<[crate::diff::EntryMap; N]> {
+    let family = |format: ArchiveFormat| match format {
+        ArchiveFormat::CpioGzip => ArchiveFormat::Cpio,
+        other => other,
+    };
+    let cross_format = archives
+        .iter()
+        .any(|(_, format)| family(*format) != family(archives[0].1));
+
+    let mut maps = Vec::with_capacity(N);
+    for (path, format) in archives {
+        if cross_format && format == ArchiveFormat::Iso {
+            let image = extract_iso_rootfs(&IsoReader::open(path)?)?;
+            println!(
+                "{}: comparing its {}",
+                path.display(),
+                distro_spec::shared::ROOTFS_ISO_PATH
+            );
+            maps.push(diff_entries(
+                image.path(),
+                ArchiveFormat::Erofs,
+                limits,
+                ignore,
+            )?);
+        } else {
+            maps.push(diff_entries(path, format, limits, ignore)?);
+        }
+    }
+    if cross_format {
+        crate::diff::normalize_cross_format(&mut maps);
+        println!("Formats differ: comparing paths, types, sizes and link targets (modes/owners only if all record them)");
+    }
+    Ok(maps.try_into().expect("one map per archive"))
+}
+
+/// Print only differences in paths a checklist references, grouped by category.
+fn print_scoped_diff(changes: &[(String, Change)], checklist: ChecklistType, scope: &Scope) {
+    use std::collections::BTreeMap;
+
+    let mut groups: BTreeMap<u8, Vec<&(String, Change)>> = BTreeMap::new();
+    let mut categories: BTreeMap<u8, CheckCategory> = BTreeMap::new();
+
+    for change in changes {
+        if let Some(category) = scope.category_of(&change.0) {
+            categories.insert(category.sort_key(), category);
+            groups.entry(category.sort_key()).or_default().push(change);
+        }
+    }
+
+    println!("Scope: {} checklist", checklist.name());
+    println!();
+
+    if groups.is_empty() {
+        println!("No differences in scope.");
+        return;
+    }
+
+    for (key, changes) in groups {
+        println!("{}:", categories[&key]);
+        for (path, change) in changes {
+            match change {
+                Change::Removed => println!("  - {}", path),
+                Change::Added => println!("  + {}", path),
+                Change::Modified(fields) => {
+                    let fields: Vec<String> = fields.iter().map(|c| c.to_string()).collect();
+                    println!("  ~ {}: {}", path, fields.join(", "));
+                }
+            }
+        }
+        println!();
+    }
+}
+
+fn format_name(format: &ArchiveFormat) -> &'static str {
+    match format {
+        ArchiveFormat::Cpio => "CPIO",
+        ArchiveFormat::CpioGzip => "CPIO (gzip compressed)",
+        ArchiveFormat::Erofs => "EROFS",
+        ArchiveFormat::Iso => "ISO 9660",
+    }
+}
+
+/// How much of a verification report to print, and how to group it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct ReportMode {
+    detail: Detail,
+    /// How the summary and details group results (--group-by)
+    grouping: Grouping,
+}
+
+/// How much of a verification report to print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Detail {
+    /// Per-category counts and the most severe failures
+    #[default]
+    Summary,
+    /// Summary, then every failure
+    Full,
+    /// Summary, then every check including passes
+    Verbose,
+    /// Verdict line only
+    Quiet,
+    /// `fail|skip <TAB> category <TAB> item <TAB> message-or-prerequisite`
+    /// per failure; tabs and newlines inside fields become spaces
+    Porcelain,
+}
+
+impl ReportMode {
+    /// The verdict line only, for runs nested inside another report
+    const QUIET: ReportMode = ReportMode {
+        detail: Detail::Quiet,
+        grouping: Grouping::Category,
+    };
+
+    /// Output meant for scripts: no progress or informational lines
+    fn is_terse(self) -> bool {
+        matches!(self.detail, Detail::Quiet | Detail::Porcelain)
+    }
+}
+
+/// Failures listed in the summary before pointing at `--full`.
+const TOP_FAILURES: usize = 10;
+
+/// Results grouped as --group-by asks, under their display names.
+fn report_groups<'a>(
+    report: &'a VerificationReport,
+    grouping: Grouping,
+    catalog: &Catalog,
+) -> Vec<(String, Vec<&'a CheckResult>)> {
+    match grouping {
+        Grouping::Category => report
+            .by_category()
+            .into_iter()
+            .map(|(category, results)| (catalog.category(category), results))
+            .collect(),
+        Grouping::Subsystem => report
+            .by_subsystem()
+            .into_iter()
+            .map(|(subsystem, results)| {
+                let name = match subsystem {
+                    Some(subsystem) => catalog.subsystem(subsystem),
+                    None => catalog.category(CheckCategory::Other),
+                };
+                (name, results)
+            })
+            .collect(),
+    }
+}
+
+fn print_report(report: &VerificationReport, mode: ReportMode, catalog: &Catalog) {
+    match mode.detail {
+        Detail::Quiet => return print_verdict(report, catalog),
+        Detail::Porcelain => return print_porcelain(report),
+        _ => {}
+    }
+
+    println!(
+        "=== {}: {} ===",
+        catalog.label("verification"),
+        report.artifact_type
+    );
+    if let Some(ref fingerprint) = report.fingerprint {
+        print_fingerprint(fingerprint);
+    }
+    if let Some(ref spec) = report.spec {
+        println!("Spec:     distro-spec {}", spec);
+    }
+    if let Some(ref arch) = report.arch {
+        println!("Arch:     {}", arch);
+    }
+    if let Some(ref partial) = report.partial {
+        println!("Partial:  archive {}", partial);
+    }
+    println!();
+
+    print_summary(report, mode.grouping, catalog);
+
+    // Lead with the failures that caused others to be skipped
+    let root_causes = report.root_causes();
+    if !root_causes.is_empty() {
+        println!("{}:", catalog.label("root-causes"));
+        for result in &root_causes {
+            print_failure(result, catalog);
+        }
+        println!();
+    }
+
+    if mode.detail == Detail::Summary {
+        let top: Vec<_> = report
+            .top_failures(TOP_FAILURES + root_causes.len())
+            .into_iter()
+            .filter(|r| !root_causes.iter().any(|cause| std::ptr::eq(*cause, *r)))
+            .take(TOP_FAILURES)
+            .collect();
+        if !top.is_empty() {
+            println!("{}:", catalog.label("top-failures"));
+            for result in &top {
+                print_failure(result, catalog);
+            }
+            let remaining = report.failed() - root_causes.len() - top.len();
+            if remaining > 0 {
+                println!("  ... {} {}", remaining, catalog.label("more-failures"));
+            }
+            println!();
+        }
+    } else {
+        // The details can run to thousands of lines; repeat the verdict
+        print_details(
+            report,
+            mode.detail == Detail::Verbose,
+            mode.grouping,
+            catalog,
+        );
+        print_verdict(report, catalog);
+    }
+}
+
+/// Slowest checks and time per category (--timings).
+fn print_timings(report: &VerificationReport, catalog: &Catalog) {
+    let ms = |us: u64| us as f64 / 1000.0;
+    println!();
+    println!("Slowest checks:");
+    for result in report.slowest(10) {
+        println!(
+            "  {:>9.1}ms  [{}] {}",
+            ms(result.elapsed_us.unwrap_or(0)),
+            result.category.key(),
+            result.item
+        );
+    }
+    println!();
+    println!("Time by category:");
+    for (category, total) in report.timings_by_category() {
+        println!(
+            "  {:>9.1}ms  {}",
+            ms(total.as_micros() as u64),
+            catalog.category(category)
+        );
+    }
+}
+
+fn print_fingerprint(fingerprint: &Fingerprint) {
+    println!(
+        "Artifact: {}, {} bytes",
+        fingerprint.format.as_deref().unwrap_or("unknown format"),
+        fingerprint.size
+    );
+    println!("SHA256:   {}", fingerprint.sha256);
+    match (&fingerprint.volume, &fingerprint.uuid) {
+        (Some(volume), Some(uuid)) => println!("Volume:   {} (UUID {})", volume, uuid),
+        (Some(volume), None) => println!("Volume:   {}", volume),
+        (None, Some(uuid)) => println!("UUID:     {}", uuid),
+        (None, None) => {}
+    }
+}
+
+/// One line per group with any failures, plus the verdict.
+fn print_summary(report: &VerificationReport, grouping: Grouping, catalog: &Catalog) {
+    let groups = report_groups(report, grouping, catalog);
+    let width = groups
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut all_pass_groups = Vec::new();
+    let mut failing = Vec::new();
+    for (name, results) in &groups {
+        let failed = results.iter().filter(|r| r.is_failure()).count();
+        let skipped = results.iter().filter(|r| r.is_skipped()).count();
+        let passed = results.len() - failed - skipped;
+        if failed == 0 && skipped == 0 {
+            all_pass_groups.push(format!("{} ({})", name, results.len()));
+            continue;
+        }
+        let mut counts = format!(
+            "{} {}, {} {}",
+            failed,
+            catalog.label("failed"),
+            passed,
+            catalog.label("passed")
+        );
+        if skipped > 0 {
+            counts.push_str(&format!(", {} {}", skipped, catalog.label("skipped")));
+        }
+        failing.push((name, counts));
+    }
+
+    if !failing.is_empty() {
+        println!("{}:", catalog.label("summary"));
+        for (name, counts) in failing {
+            // Pad by characters; translations aren't ASCII
+            let pad = width - name.chars().count();
+            println!("  {}{:pad$}  {}", name, "", counts, pad = pad);
+        }
+    }
+    if !all_pass_groups.is_empty() {
+        println!(
+            "{}: {}",
+            catalog.label("all-passed"),
+            all_pass_groups.join(", ")
+        );
+    }
+    print_verdict(report, catalog);
+    println!();
+}
+
+fn print_verdict(report: &VerificationReport, catalog: &Catalog) {
+    let status = catalog.label(if report.is_success() { "pass" } else { "fail" });
+    let skipped = match report.skipped() {
+        0 => String::new(),
+        n => format!(", {} {}", n, catalog.label("skipped")),
+    };
+    println!(
+        "{}: {} ({}/{} {}{})",
+        catalog.label("result"),
+        status,
+        report.passed(),
+        report.total(),
+        catalog.label("checks-passed"),
+        skipped
+    );
+}
+
+fn print_porcelain(report: &VerificationReport) {
+    let field = |s: &str| s.replace(['\t', '\n', '\r'], " ");
+    for result in report.results.iter().filter(|r| !r.passed) {
+        let (status, detail) = match result.skipped_by {
+            Some(ref prereq) => ("skip", prereq.as_str()),
+            None => ("fail", result.message.as_deref().unwrap_or_default()),
+        };
+        println!(
+            "{}\t{}\t{}\t{}",
+            status,
+            result.category.key(),
+            field(&result.item),
+            field(detail)
+        );
+    }
+}
+
+fn print_failure(result: &CheckResult, catalog: &Catalog) {
+    let fail = catalog.label("fail");
+    match result.message {
+        Some(ref msg) => println!("  [{}] {} - {}", fail, result.item, msg),
+        None => println!("  [{}] {}", fail, result.item),
+    }
+    if let Some(ref hint) = result.hint {
+        // Continuation lines line up with the item
+        let indent = " ".repeat(fail.chars().count() + 5);
+        println!(
+            "{}{}: {}",
+            indent,
+            catalog.label("hint"),
+            catalog.hint(hint)
+        );
+    }
+}
+
+/// Every failure (and every pass, if verbose), grouped as --group-by asks.
+fn print_details(
+    report: &VerificationReport,
+    verbose: bool,
+    grouping: Grouping,
+    catalog: &Catalog,
+) {
+    for (name, results) in report_groups(report, grouping, catalog) {
+        let failures: Vec<_> = results.iter().filter(|r| r.is_failure()).collect();
+        let skipped: Vec<_> = results.iter().filter(|r| r.is_skipped()).collect();
+        let pass_count = results.len() - failures.len() - skipped.len();
+
+        // Without --verbose, skip groups with no failures
+        if !verbose && failures.is_empty() && skipped.is_empty() {
+            continue;
+        }
+
+        println!("{}:", name);
+
+        // Show passing items only in verbose mode
+        if verbose {
+            let pass = catalog.label("pass");
+            for result in results.iter().filter(|r| r.passed) {
+                match result.message {
+                    Some(ref msg) => println!("  [{}] {} - {}", pass, result.item, msg),
+                    None => println!("  [{}] {}", pass, result.item),
+                }
+            }
+        }
+
+        for result in &failures {
+            print_failure(result, catalog);
+        }
+
+        if verbose {
+            for result in &skipped {
+                if let Some(ref prereq) = result.skipped_by {
+                    println!(
+                        "  [{}] {} ({}: {})",
+                        catalog.label("skip"),
+                        result.item,
+                        catalog.label("prerequisite-failed"),
+                        prereq
+                    );
+                }
+            }
+        } else if !skipped.is_empty() {
+            println!(
+                "  ({} {}: {})",
+                skipped.len(),
+                catalog.label("skipped"),
+                catalog.label("prerequisite-failed")
+            );
+        }
+
+        if !verbose && !failures.is_empty() && pass_count > 0 {
+            println!("  ({} {})", pass_count, catalog.label("passed"));
+        }
+
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(
+            exit_code(&anyhow::anyhow!("--top requires a CPIO archive")),
+            2
+        );
+        let io = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            exit_code(&anyhow::Error::from(io).context("Failed to write report.json")),
+            2
+        );
+        let parse = FsdbgError::invalid_format("Bad tar header checksum at offset 0");
+        assert_eq!(exit_code(&anyhow::Error::from(parse).context("Reading")), 5);
+
+        // A program that isn't installed is a missing tool, not a usage error
+        let privileged = Privileged {
+            sudo: false,
+            dry_run: false,
+        };
+        let missing = privileged
+            .status(&[&"fsdbg-no-such-tool", &"--version"])
+            .context("Failed to run fsdbg-no-such-tool")
+            .unwrap_err();
+        assert_eq!(exit_code(&missing), 3);
+    }
+}
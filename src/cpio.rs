@@ -3,15 +3,21 @@
 //! Reads CPIO archives (newc format) without extraction.
 //! Supports both gzip-compressed and uncompressed archives.
 
+use crate::dir::DirReader;
 use crate::error::FsdbgError;
+use crate::magic::FileKind;
+use crate::names::escape_bytes;
+use crate::reader::ArchiveReader;
+use crate::ByteRange;
 use flate2::read::GzDecoder;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::Path;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
 
 /// File type extracted from mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum FileType {
     Regular,
     Directory,
@@ -24,7 +30,7 @@ pub enum FileType {
 }
 
 impl FileType {
-    fn from_mode(mode: u32) -> Self {
+    pub(crate) fn from_mode(mode: u32) -> Self {
         match mode & 0o170000 {
             0o100000 => FileType::Regular,
             0o040000 => FileType::Directory,
@@ -41,7 +47,10 @@ impl FileType {
 /// Entry in a CPIO archive
 #[derive(Debug, Clone)]
 pub struct CpioEntry {
+    /// Name with non-UTF-8 bytes escaped (see `crate::names`)
     pub path: String,
+    /// Name exactly as stored
+    pub raw_path: Vec<u8>,
     pub size: u64,
     pub mode: u32,
     pub file_type: FileType,
@@ -54,9 +63,22 @@ pub struct CpioEntry {
     pub dev_minor: u32,
     pub rdev_major: u32,
     pub rdev_minor: u32,
+    /// Hex digest of a regular file's content, when the archive was opened
+    /// with a [`HashAlgorithm`]
+    pub digest: Option<String>,
 }
 
 impl CpioEntry {
+    /// Approximate memory the entry holds, for [`Limits::max_total`]
+    fn memory(&self) -> u64 {
+        let strings = self.path.len()
+            + self.raw_path.len()
+            + self.link_target.as_ref().map_or(0, String::len)
+            + self.digest.as_ref().map_or(0, String::len);
+        // The path is stored again as the lookup key
+        (std::mem::size_of::<Self>() + strings + self.path.len()) as u64
+    }
+
     /// Check if this is a directory
     pub fn is_dir(&self) -> bool {
         self.file_type == FileType::Directory
@@ -72,6 +94,12 @@ impl CpioEntry {
         self.file_type == FileType::Symlink
     }
 
+    /// Check if this is an overlayfs whiteout: a `c 0:0` character device
+    /// that deletes the same path in the lower layers
+    pub fn is_whiteout(&self) -> bool {
+        self.file_type == FileType::CharDevice && (self.rdev_major, self.rdev_minor) == (0, 0)
+    }
+
     /// Get permission bits (lower 12 bits of mode)
     pub fn permissions(&self) -> u32 {
         self.mode & 0o7777
@@ -142,151 +170,899 @@ impl CpioEntry {
     }
 }
 
+/// Which entry a path resolves to when the archive holds it more than once
+/// (common with concatenated CPIOs).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum DuplicatePolicy {
+    /// The first entry wins
+    First,
+    /// The last entry wins, as when the kernel unpacks the archive
+    #[default]
+    Last,
+    /// Duplicates are an error
+    Error,
+}
+
+impl DuplicatePolicy {
+    pub fn parse_name(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "first" => Some(DuplicatePolicy::First),
+            "last" | "kernel" => Some(DuplicatePolicy::Last),
+            "error" | "report-conflict" | "conflict" => Some(DuplicatePolicy::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Content digest computed per file while parsing (`inspect --hash`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+}
+
+impl HashAlgorithm {
+    pub fn parse_name(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "sha256" => Some(HashAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// Hex digest of `content`.
+    pub fn digest(self, content: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::digest(content)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        }
+    }
+}
+
+/// Something odd but not fatal found while parsing, reported by `--strict`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anomaly {
+    /// Entry path as stored in the archive
+    pub path: String,
+    pub message: String,
+}
+
+impl Anomaly {
+    fn new(path: &str, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Where a truncated archive ends (see [`ParseOptions::lenient`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncation {
+    /// Start of the first incomplete entry, in the decompressed stream
+    pub offset: u64,
+    /// Its name, if that much was read
+    pub entry: Option<String>,
+}
+
+impl std::fmt::Display for Truncation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "truncated at byte {}", self.offset)?;
+        match self.entry {
+            Some(ref entry) => write!(f, " (in {})", entry),
+            None => write!(f, " (before the trailer)"),
+        }
+    }
+}
+
+/// Caps on what reading an archive may allocate. A crafted header can
+/// declare a multi-GB name or file, and a small gzip stream can inflate to
+/// millions of entries; past a cap, reading fails with
+/// [`ErrorCode::LimitExceeded`](crate::error::ErrorCode::LimitExceeded)
+/// instead of exhausting memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Longest entry name, NUL included
+    pub max_name: usize,
+    /// Most content read into memory for one entry
+    pub max_entry: u64,
+    /// Most memory one reader holds: entry metadata, plus the contents a
+    /// single [`CpioReader::read_files`] returns
+    pub max_total: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            // The kernel's PATH_MAX
+            max_name: 4096,
+            max_entry: 1 << 30,
+            max_total: 4 << 30,
+        }
+    }
+}
+
+/// How to parse an archive (see [`CpioReader::open_with`])
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Record each regular file's digest in [`CpioEntry::digest`]
+    pub hash: Option<HashAlgorithm>,
+    /// Keep the entries before a truncation instead of failing; see
+    /// [`CpioReader::truncated`]
+    pub lenient: bool,
+    /// Allocation caps, see [`Limits`]
+    pub limits: Limits,
+}
+
+/// What a walk over the stream found besides the entries
+#[derive(Default)]
+struct Walk {
+    anomalies: Vec<Anomaly>,
+    truncated: Option<Truncation>,
+    /// Non-zero bytes read after the last trailer that didn't start
+    /// another archive
+    trailing: u64,
+}
+
+/// One step of a walk
+enum Next {
+    Entry(CpioEntry, Vec<u8>),
+    Trailer,
+    /// The stream ended where a header should start
+    End,
+}
+
+/// Where an archive's bytes come from. Entry contents aren't retained
+/// after parsing, so reads stream the source again.
+enum Source {
+    File {
+        path: PathBuf,
+        /// Where the archive sits in `path`
+        range: ByteRange,
+    },
+    /// Read into memory by [`CpioReader::from_reader`]
+    Memory(Vec<u8>),
+    /// A directory tree listed by [`DirReader`]; contents are read from
+    /// its files
+    Dir(PathBuf),
+}
+
+impl Source {
+    /// The archive as a byte stream, transparently decompressing gzip
+    fn stream(&self) -> Result<Box<dyn Read + '_>, FsdbgError> {
+        match self {
+            Source::File { path, range } => decompress(BufReader::new(range.open(path)?)),
+            Source::Memory(bytes) => decompress(&bytes[..]),
+            Source::Dir(root) => Err(FsdbgError::invalid_format(
+                "A directory tree is not a byte stream",
+            )
+            .with_path(root)),
+        }
+    }
+
+    /// Attach the archive's path to `error`, if it has one.
+    fn locate(&self, error: FsdbgError) -> FsdbgError {
+        match self {
+            Source::File { path, .. } | Source::Dir(path) => error.with_path(path),
+            Source::Memory(_) => error,
+        }
+    }
+}
+
 /// CPIO archive reader
 pub struct CpioReader {
+    source: Source,
     entries: Vec<CpioEntry>,
     entry_map: HashMap<String, usize>,
+    anomalies: Vec<Anomaly>,
+    /// Parsed leniently: later reads stop at the truncation too
+    lenient: bool,
+    limits: Limits,
+    truncated: Option<Truncation>,
+    /// Fall back to symlink-aware lookup in `exists`/`get`
+    resolve_symlinks: bool,
 }
 
 impl CpioReader {
     /// Open and parse a CPIO archive
     pub fn open(path: &Path) -> Result<Self, FsdbgError> {
-        let file = File::open(path).map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                FsdbgError::file_not_found(path)
-            } else {
-                FsdbgError::from(e)
-            }
-        })?;
+        Self::open_range(path, ByteRange::default())
+    }
 
-        let mut reader = BufReader::new(file);
+    /// Open and parse a CPIO archive embedded at `range` in a larger file,
+    /// reading it in place.
+    pub fn open_range(path: &Path, range: ByteRange) -> Result<Self, FsdbgError> {
+        Self::open_with(path, range, ParseOptions::default())
+    }
 
-        // Check for gzip magic
-        let mut magic = [0u8; 2];
-        reader.read_exact(&mut magic)?;
+    /// Open and parse a CPIO archive, recording each regular file's digest
+    /// in [`CpioEntry::digest`]. Contents are hashed as they stream past and
+    /// not retained, so this costs one pass like [`open_range`](Self::open_range).
+    pub fn open_hashed(
+        path: &Path,
+        range: ByteRange,
+        algorithm: HashAlgorithm,
+    ) -> Result<Self, FsdbgError> {
+        let options = ParseOptions {
+            hash: Some(algorithm),
+            ..Default::default()
+        };
+        Self::open_with(path, range, options)
+    }
 
-        // Rewind
-        use std::io::Seek;
-        reader.seek(std::io::SeekFrom::Start(0))?;
+    /// Open and parse a CPIO archive at `range` with `options`.
+    pub fn open_with(
+        path: &Path,
+        range: ByteRange,
+        options: ParseOptions,
+    ) -> Result<Self, FsdbgError> {
+        let source = Source::File {
+            path: path.to_path_buf(),
+            range,
+        };
+        Self::parse(source, options, |_, _| Ok(()))
+    }
 
-        if magic[0] == 0x1f && magic[1] == 0x8b {
-            // Gzip compressed
-            let decoder = GzDecoder::new(reader);
-            Self::parse_cpio(decoder)
-        } else {
-            // Uncompressed
-            Self::parse_cpio(reader)
+    /// Open and parse a CPIO archive at `range` with `options`, handing
+    /// every entry with a reader over its content to `visit` as the parse
+    /// reaches it, in archive order. Hashing or scanning contents this way
+    /// takes the one pass, and holds only the current entry's content (at
+    /// most [`Limits::max_entry`]). An error from `visit` stops the parse
+    /// and is returned.
+    pub fn for_each_entry(
+        path: &Path,
+        range: ByteRange,
+        options: ParseOptions,
+        visit: impl FnMut(&CpioEntry, &mut dyn Read) -> Result<(), FsdbgError>,
+    ) -> Result<Self, FsdbgError> {
+        let source = Source::File {
+            path: path.to_path_buf(),
+            range,
+        };
+        Self::parse(source, options, visit)
+    }
+
+    /// Parse a CPIO archive (gzip-compressed or not) from any byte stream,
+    /// such as a pipe or a buffer. The stream is read into memory, at most
+    /// [`Limits::max_total`] bytes of it, so later reads don't need it again.
+    pub fn from_reader(reader: impl Read, options: ParseOptions) -> Result<Self, FsdbgError> {
+        let limits = options.limits;
+        let mut bytes = Vec::new();
+        reader
+            .take(limits.max_total.saturating_add(1))
+            .read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > limits.max_total {
+            return Err(FsdbgError::limit_exceeded(format!(
+                "Archive exceeds the {}-byte memory limit (--max-memory)",
+                limits.max_total
+            )));
+        }
+        Self::parse(Source::Memory(bytes), options, |_, _| Ok(()))
+    }
+
+    /// Present a directory tree as an archive of its contents, so everything
+    /// that takes a `CpioReader` (every checklist) runs on it before it is
+    /// packed. Reads come from the tree's files.
+    pub fn from_dir(reader: DirReader) -> Self {
+        let (root, entries, entry_map) = reader.into_parts();
+        Self {
+            source: Source::Dir(root),
+            entries,
+            entry_map,
+            anomalies: Vec::new(),
+            lenient: false,
+            limits: Limits::default(),
+            truncated: None,
+            resolve_symlinks: false,
         }
     }
 
-    fn parse_cpio<R: Read>(mut reader: R) -> Result<Self, FsdbgError> {
+    fn parse(
+        source: Source,
+        options: ParseOptions,
+        mut visit: impl FnMut(&CpioEntry, &mut dyn Read) -> Result<(), FsdbgError>,
+    ) -> Result<Self, FsdbgError> {
+        let ParseOptions {
+            hash,
+            lenient,
+            limits,
+        } = options;
         let mut entries = Vec::new();
         let mut entry_map = HashMap::new();
+        let mut duplicates = Vec::new();
+        let mut held = 0;
+        let mut failed = None;
+
+        let mut stream = source.stream()?;
+        let walk = Self::walk(&mut stream, limits, lenient, true, |mut entry, content| {
+            if entry.is_file() {
+                entry.digest = hash.map(|algorithm| algorithm.digest(&content));
+            }
+            if let Err(e) = visit(&entry, &mut &content[..]) {
+                failed = Some(e);
+                return false;
+            }
+            held += entry.memory();
+            if held > limits.max_total {
+                return false;
+            }
+            // Normalize the path for the entry_map (for lookups)
+            let normalized_name = Self::normalize_path(&entry.path);
+            if !normalized_name.is_empty()
+                && entry_map.insert(normalized_name, entries.len()).is_some()
+            {
+                duplicates.push(Anomaly::new(
+                    &entry.path,
+                    "Duplicate entry; the kernel keeps the later one",
+                ));
+            }
+            entries.push(entry);
+            true
+        })
+        .map_err(|e| source.locate(e.with_operation("parsing the CPIO stream")))?;
+        if let Some(error) = failed {
+            return Err(error);
+        }
+        if held > limits.max_total {
+            let error = FsdbgError::limit_exceeded(format!(
+                "Entry list exceeds the {}-byte memory limit after {} entries (--max-memory)",
+                limits.max_total,
+                entries.len()
+            ));
+            return Err(source.locate(error));
+        }
+        let mut anomalies = walk.anomalies;
+        anomalies.extend(duplicates);
+
+        // Archives after a trailer were parsed with the first, as the
+        // kernel unpacks them; anything else there is reported
+        let trailing = walk.trailing + count_nonzero(&mut stream);
+        if trailing > 0 {
+            anomalies.push(Anomaly::new(
+                "TRAILER!!!",
+                format!(
+                    "{} bytes of data after the trailer (concatenated archive?)",
+                    trailing
+                ),
+            ));
+        }
+        drop(stream);
+
+        Ok(Self {
+            source,
+            entries,
+            entry_map,
+            anomalies,
+            lenient,
+            limits,
+            truncated: walk.truncated,
+            resolve_symlinks: false,
+        })
+    }
+
+    /// Re-resolve duplicate paths with `policy`. The default after opening
+    /// is [`DuplicatePolicy::Last`].
+    pub fn with_duplicate_policy(mut self, policy: DuplicatePolicy) -> Result<Self, FsdbgError> {
+        if policy == DuplicatePolicy::Error {
+            let duplicates = self.duplicates();
+            if !duplicates.is_empty() {
+                let paths: Vec<_> = duplicates.iter().map(|(path, _)| path.as_str()).collect();
+                let error = FsdbgError::invalid_format(format!(
+                    "Duplicate entries for: {}",
+                    paths.join(", ")
+                ));
+                return Err(self.source.locate(error));
+            }
+        }
+
+        self.entry_map.clear();
+        for (i, entry) in self.entries.iter().enumerate() {
+            let normalized = Self::normalize_path(&entry.path);
+            if normalized.is_empty() {
+                continue;
+            }
+            if policy == DuplicatePolicy::First {
+                self.entry_map.entry(normalized).or_insert(i);
+            } else {
+                self.entry_map.insert(normalized, i);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Let [`exists`](Self::exists) and [`get`](Self::get) find paths through
+    /// symlinked directories (`bin/ls` stored as `usr/bin/ls` behind
+    /// `bin -> usr/bin`) when nothing is stored under the literal path. The
+    /// last component is not followed, as with `lstat`.
+    pub fn with_symlink_resolution(mut self) -> Self {
+        self.resolve_symlinks = true;
+        self
+    }
 
+    /// Read contents with `limits` instead of the defaults. Parsed archives
+    /// take theirs from [`ParseOptions::limits`]; this is for
+    /// [`from_dir`](Self::from_dir) trees, whose files are read on demand.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Paths stored more than once, with their entry count, in archive order.
+    pub fn duplicates(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut order = Vec::new();
+        for entry in &self.entries {
+            let normalized = Self::normalize_path(&entry.path);
+            let count = counts.entry(normalized.clone()).or_default();
+            *count += 1;
+            if *count == 2 {
+                order.push(normalized);
+            }
+        }
+        order
+            .into_iter()
+            .map(|path| {
+                let count = counts[&path];
+                (path, count)
+            })
+            .collect()
+    }
+
+    /// Walk all entries in a CPIO stream, handing each entry and its content
+    /// to `visit`. Stops early when `visit` returns false. Returns the
+    /// non-fatal oddities seen along the way.
+    ///
+    /// With `concatenated`, a trailer followed (after zero padding) by
+    /// another archive's magic doesn't end the walk: the next archive's
+    /// entries follow, as the kernel unpacks them. Otherwise the walk stops
+    /// at the first trailer.
+    ///
+    /// When `lenient`, a stream that ends before the trailer (mid-entry, or
+    /// between entries) isn't an error: the walk stops there and records
+    /// where in [`Walk::truncated`].
+    fn walk<R: Read>(
+        reader: R,
+        limits: Limits,
+        lenient: bool,
+        concatenated: bool,
+        mut visit: impl FnMut(CpioEntry, Vec<u8>) -> bool,
+    ) -> Result<Walk, FsdbgError> {
+        let mut reader = CountingReader::new(reader);
+        let mut walk = Walk::default();
+        // Magic of the next archive, read while looking past a trailer
+        let mut magic = None;
         loop {
-            // Read header (110 bytes for newc format)
-            let mut header = [0u8; 110];
-            match reader.read_exact(&mut header) {
-                Ok(_) => {}
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                Err(e) => return Err(FsdbgError::from(e)),
+            let offset = reader.count - magic.map_or(0, |m: [u8; 6]| m.len() as u64);
+            let mut current = None;
+            let next = Self::next_entry(
+                &mut reader,
+                magic.take(),
+                &limits,
+                &mut walk.anomalies,
+                &mut current,
+            );
+            match next {
+                Ok(Next::Entry(entry, content)) => {
+                    if !visit(entry, content) {
+                        break;
+                    }
+                }
+                Ok(Next::Trailer) if concatenated => {
+                    magic = Self::next_archive(&mut reader, &mut walk.trailing);
+                    if magic.is_none() {
+                        break;
+                    }
+                }
+                Ok(Next::Trailer) => break,
+                Ok(Next::End) => {
+                    if lenient {
+                        walk.truncated = Some(Truncation {
+                            offset,
+                            entry: None,
+                        });
+                    }
+                    break;
+                }
+                Err(_) if lenient && reader.eof => {
+                    walk.truncated = Some(Truncation {
+                        offset,
+                        entry: current,
+                    });
+                    break;
+                }
+                Err(e) => return Err(e.with_offset(offset)),
             }
+        }
+        Ok(walk)
+    }
 
-            // Verify magic
-            let magic = std::str::from_utf8(&header[0..6])
-                .map_err(|_| FsdbgError::invalid_format("Invalid CPIO header: not UTF-8"))?;
+    /// Skip the zero padding after a trailer. Returns the magic of the
+    /// archive that follows, if one does; bytes read that don't start one
+    /// count as `trailing`.
+    fn next_archive(reader: &mut impl Read, trailing: &mut u64) -> Option<[u8; 6]> {
+        let mut byte = [0u8];
+        loop {
+            match reader.read(&mut byte) {
+                Ok(1) if byte[0] == 0 => continue,
+                Ok(1) => break,
+                _ => return None,
+            }
+        }
+        let mut read = vec![byte[0]];
+        reader.by_ref().take(5).read_to_end(&mut read).ok();
+        match <[u8; 6]>::try_from(&read[..]) {
+            Ok(magic) if &magic == b"070701" || &magic == b"070702" => return Some(magic),
+            _ => *trailing += read.iter().filter(|b| **b != 0).count() as u64,
+        }
+        None
+    }
 
-            if magic != "070701" && magic != "070702" {
-                return Err(FsdbgError::invalid_format(format!(
-                    "Invalid CPIO magic: expected 070701/070702, got {}",
-                    magic
-                )));
+    /// Read the next entry, whose first bytes are `magic` if they were
+    /// already read. `current` gets its name as soon as that is read, for
+    /// reporting where a truncated stream ended.
+    fn next_entry<R: Read>(
+        reader: &mut R,
+        magic: Option<[u8; 6]>,
+        limits: &Limits,
+        anomalies: &mut Vec<Anomaly>,
+        current: &mut Option<String>,
+    ) -> Result<Next, FsdbgError> {
+        // Read header (110 bytes for newc format)
+        let mut header = [0u8; 110];
+        let start = match magic {
+            Some(magic) => {
+                header[..6].copy_from_slice(&magic);
+                6
             }
+            None => 0,
+        };
+        match reader.read_exact(&mut header[start..]) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(Next::End),
+            Err(e) => return Err(FsdbgError::from(e)),
+        }
 
-            // Parse header fields (all hex strings)
-            let parse_hex = |slice: &[u8]| -> Result<u32, FsdbgError> {
-                let s = std::str::from_utf8(slice)
-                    .map_err(|_| FsdbgError::invalid_format("Invalid hex field"))?;
-                u32::from_str_radix(s, 16)
-                    .map_err(|_| FsdbgError::invalid_format(format!("Invalid hex: {}", s)))
-            };
+        // Verify magic
+        let magic = std::str::from_utf8(&header[0..6])
+            .map_err(|_| FsdbgError::invalid_format("Invalid CPIO header: not UTF-8"))?;
+
+        if magic != "070701" && magic != "070702" {
+            return Err(FsdbgError::invalid_format(format!(
+                "Invalid CPIO magic: expected 070701/070702, got {}",
+                magic
+            )));
+        }
+
+        // Parse header fields (all hex strings)
+        let parse_hex = |slice: &[u8]| -> Result<u32, FsdbgError> {
+            let s = std::str::from_utf8(slice)
+                .map_err(|_| FsdbgError::invalid_format("Invalid hex field"))?;
+            u32::from_str_radix(s, 16)
+                .map_err(|_| FsdbgError::invalid_format(format!("Invalid hex: {}", s)))
+        };
+
+        let mode = parse_hex(&header[14..22])?;
+        let uid = parse_hex(&header[22..30])?;
+        let gid = parse_hex(&header[30..38])?;
+        let nlink = parse_hex(&header[38..46])?;
+        let mtime = parse_hex(&header[46..54])?;
+        let filesize = parse_hex(&header[54..62])? as u64;
+        let dev_major = parse_hex(&header[62..70])?;
+        let dev_minor = parse_hex(&header[70..78])?;
+        let rdev_major = parse_hex(&header[78..86])?;
+        let rdev_minor = parse_hex(&header[86..94])?;
+        let namesize = parse_hex(&header[94..102])? as usize;
+        if namesize > limits.max_name {
+            return Err(FsdbgError::limit_exceeded(format!(
+                "Entry name of {} bytes exceeds the {}-byte limit",
+                namesize, limits.max_name
+            )));
+        }
+
+        // Read filename (padded to 4-byte boundary including header)
+        let name_buf = read_up_to(reader, namesize as u64)?;
 
-            let mode = parse_hex(&header[14..22])?;
-            let uid = parse_hex(&header[22..30])?;
-            let gid = parse_hex(&header[30..38])?;
-            let nlink = parse_hex(&header[38..46])?;
-            let mtime = parse_hex(&header[46..54])?;
-            let filesize = parse_hex(&header[54..62])? as u64;
-            let dev_major = parse_hex(&header[62..70])?;
-            let dev_minor = parse_hex(&header[70..78])?;
-            let rdev_major = parse_hex(&header[78..86])?;
-            let rdev_minor = parse_hex(&header[86..94])?;
-            let namesize = parse_hex(&header[94..102])? as usize;
-
-            // Read filename (padded to 4-byte boundary including header)
-            let mut name_buf = vec![0u8; namesize];
-            reader.read_exact(&mut name_buf)?;
-
-            // Remove trailing null
-            let name = String::from_utf8_lossy(&name_buf[..namesize.saturating_sub(1)]).to_string();
-
-            // Skip padding after name (header + name aligned to 4 bytes)
-            let header_plus_name = 110 + namesize;
-            let padding = (4 - (header_plus_name % 4)) % 4;
-            if padding > 0 {
-                let mut skip = vec![0u8; padding];
-                reader.read_exact(&mut skip)?;
+        // Remove trailing null
+        let raw_path = name_buf.strip_suffix(&[0]).unwrap_or(&name_buf).to_vec();
+        let name = escape_bytes(&raw_path);
+        *current = Some(name.clone());
+        if name_buf.last() != Some(&0) {
+            anomalies.push(Anomaly::new(&name, "Name is not NUL-terminated"));
+        }
+
+        // Skip padding after name (header + name aligned to 4 bytes)
+        let header_plus_name = 110 + namesize;
+        let padding = (4 - (header_plus_name % 4)) % 4;
+        if padding > 0 {
+            let mut skip = vec![0u8; padding];
+            reader.read_exact(&mut skip)?;
+            if skip.iter().any(|b| *b != 0) {
+                anomalies.push(Anomaly::new(&name, "Non-zero padding after name"));
             }
+        }
 
-            // Check for trailer
-            if name == "TRAILER!!!" {
-                break;
+        // Check for trailer
+        if name == "TRAILER!!!" {
+            return Ok(Next::Trailer);
+        }
+
+        if filesize > limits.max_entry {
+            return Err(FsdbgError::limit_exceeded(format!(
+                "{} declares {} bytes of content, over the {}-byte entry limit (--max-entry-size)",
+                name, filesize, limits.max_entry
+            )));
+        }
+
+        // Read file content
+        let content = read_up_to(reader, filesize)?;
+
+        // Skip padding after content (aligned to 4 bytes)
+        let content_padding = (4 - (filesize as usize % 4)) % 4;
+        if content_padding > 0 {
+            let mut skip = vec![0u8; content_padding];
+            reader.read_exact(&mut skip)?;
+            if skip.iter().any(|b| *b != 0) {
+                anomalies.push(Anomaly::new(&name, "Non-zero padding after content"));
             }
+        }
+        if mode == 0 {
+            anomalies.push(Anomaly::new(&name, "Mode is 0 (no file type)"));
+        }
+
+        // Determine file type and link target
+        let file_type = FileType::from_mode(mode);
+        let link_target = if file_type == FileType::Symlink {
+            Some(escape_bytes(&content))
+        } else {
+            None
+        };
+
+        let entry = CpioEntry {
+            path: name,
+            raw_path,
+            size: filesize,
+            mode,
+            file_type,
+            link_target,
+            uid,
+            gid,
+            nlink,
+            mtime,
+            dev_major,
+            dev_minor,
+            rdev_major,
+            rdev_minor,
+            digest: None,
+        };
+
+        Ok(Next::Entry(entry, content))
+    }
+
+    /// Path to the archive (or [`from_dir`](Self::from_dir) tree) on disk;
+    /// empty for one parsed [`from_reader`](Self::from_reader).
+    pub fn source_path(&self) -> &Path {
+        match self.source {
+            Source::File { ref path, .. } | Source::Dir(ref path) => path,
+            Source::Memory(_) => Path::new(""),
+        }
+    }
+
+    /// Non-fatal oddities found while parsing: duplicate paths, data after
+    /// the trailer, unterminated names, non-zero padding, mode 0 entries.
+    pub fn anomalies(&self) -> &[Anomaly] {
+        &self.anomalies
+    }
+
+    /// Where the archive ends early, if it was opened leniently and does.
+    /// Entries and reads then cover only what comes before.
+    pub fn truncated(&self) -> Option<&Truncation> {
+        self.truncated.as_ref()
+    }
+
+    /// Where the archive sits in [`CpioReader::source_path`].
+    pub fn range(&self) -> ByteRange {
+        match self.source {
+            Source::File { range, .. } => range,
+            Source::Memory(_) | Source::Dir(_) => ByteRange::default(),
+        }
+    }
+
+    /// Read the content of a file from the archive.
+    ///
+    /// Symlinks are followed within the archive. The archive is streamed
+    /// again from its source since entry contents are not retained after parsing.
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, FsdbgError> {
+        let target = self.resolve_entry(path)?;
+
+        // Duplicates resolve to whichever entry entry_map picked
+        let wanted = self.entry_map[&target];
+        let mut found = None;
+        let mut index = 0;
+        self.contents(
+            |i| i == wanted,
+            |_, content| {
+                if index == wanted {
+                    found = Some(content);
+                    return false;
+                }
+                index += 1;
+                true
+            },
+        )?;
 
-            // Read file content
-            let mut content = vec![0u8; filesize as usize];
-            reader.read_exact(&mut content)?;
+        found.ok_or_else(|| FsdbgError::missing_required(path.to_string()))
+    }
 
-            // Skip padding after content (aligned to 4 bytes)
-            let content_padding = (4 - (filesize as usize % 4)) % 4;
-            if content_padding > 0 {
-                let mut skip = vec![0u8; content_padding];
-                reader.read_exact(&mut skip)?;
+    /// Read several files from the archive in a single pass.
+    ///
+    /// Returns contents keyed by the requested path. Paths that don't resolve
+    /// to a regular file are left out rather than failing the whole batch.
+    pub fn read_files(&self, paths: &[&str]) -> Result<HashMap<String, Vec<u8>>, FsdbgError> {
+        // Keyed by entry index, so duplicates follow entry_map
+        let mut wanted: HashMap<usize, Vec<&str>> = HashMap::new();
+        for path in paths {
+            if let Ok(target) = self.resolve_entry(path) {
+                wanted
+                    .entry(self.entry_map[&target])
+                    .or_default()
+                    .push(path);
             }
+        }
+
+        let mut found = HashMap::new();
+        if wanted.is_empty() {
+            return Ok(found);
+        }
+
+        let mut index = 0;
+        let mut held = 0;
+        self.contents(
+            |i| wanted.contains_key(&i),
+            |_, content| {
+                if let Some(requested) = wanted.get(&index) {
+                    held += (content.len() * requested.len()) as u64;
+                    if held > self.limits.max_total {
+                        return false;
+                    }
+                    for path in requested {
+                        found.insert(path.to_string(), content.clone());
+                    }
+                }
+                index += 1;
+                true
+            },
+        )?;
+        if held > self.limits.max_total {
+            let error = FsdbgError::limit_exceeded(format!(
+                "Reading {} files at once exceeds the {}-byte memory limit (--max-memory)",
+                paths.len(),
+                self.limits.max_total
+            ));
+            return Err(self.source.locate(error));
+        }
+
+        Ok(found)
+    }
 
-            // Determine file type and link target
-            let file_type = FileType::from_mode(mode);
-            let link_target = if file_type == FileType::Symlink {
-                Some(String::from_utf8_lossy(&content).to_string())
+    /// SHA256 (hex) of every regular file's content in one pass, keyed by
+    /// normalized path. Duplicated paths hash the entry they resolve to.
+    pub fn content_digests(&self) -> Result<BTreeMap<String, String>, FsdbgError> {
+        let mut digests = BTreeMap::new();
+        self.visit_files(|path, content| {
+            digests.insert(path.to_string(), HashAlgorithm::Sha256.digest(content));
+        })?;
+        Ok(digests)
+    }
+
+    /// Content type of every regular file in one pass, keyed by normalized
+    /// path. Duplicated paths classify the entry they resolve to.
+    pub fn file_kinds(&self) -> Result<BTreeMap<String, FileKind>, FsdbgError> {
+        let mut kinds = BTreeMap::new();
+        self.visit_files(|path, content| {
+            kinds.insert(path.to_string(), FileKind::identify(content));
+        })?;
+        Ok(kinds)
+    }
+
+    /// Stream every regular file's content in one pass, with its normalized
+    /// path. Duplicated paths only visit the entry they resolve to.
+    pub(crate) fn visit_files(&self, mut visit: impl FnMut(&str, &[u8])) -> Result<(), FsdbgError> {
+        let resolved: HashMap<usize, &String> =
+            self.entry_map.iter().map(|(path, &i)| (i, path)).collect();
+
+        let mut index = 0;
+        self.contents(
+            |i| resolved.contains_key(&i),
+            |entry, content| {
+                if let Some(path) = resolved.get(&index).filter(|_| entry.is_file()) {
+                    visit(path, &content);
+                }
+                index += 1;
+                true
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Pass each entry with its content to `visit`, in archive order, until
+    /// it returns false. Archives are streamed again; a directory tree only
+    /// reads the regular files whose index is `wanted`, and passes the rest
+    /// with no content.
+    fn contents(
+        &self,
+        wanted: impl Fn(usize) -> bool,
+        mut visit: impl FnMut(&CpioEntry, Vec<u8>) -> bool,
+    ) -> Result<(), FsdbgError> {
+        let Source::Dir(ref root) = self.source else {
+            Self::walk(
+                self.source.stream()?,
+                self.limits,
+                self.lenient,
+                true,
+                |entry, content| visit(&entry, content),
+            )?;
+            return Ok(());
+        };
+        for (index, entry) in self.entries.iter().enumerate() {
+            let content = if entry.is_file() && wanted(index) {
+                crate::dir::read_entry(root, entry, &self.limits)?
             } else {
-                None
+                Vec::new()
             };
+            if !visit(entry, content) {
+                break;
+            }
+        }
+        Ok(())
+    }
 
-            let entry = CpioEntry {
-                path: name.clone(),
-                size: filesize,
-                mode,
-                file_type,
-                link_target,
-                uid,
-                gid,
-                nlink,
-                mtime,
-                dev_major,
-                dev_minor,
-                rdev_major,
-                rdev_minor,
-            };
+    /// Read a file from the archive as UTF-8 text.
+    pub fn read_file_to_string(&self, path: &str) -> Result<String, FsdbgError> {
+        let content = self.read_file(path)?;
+        String::from_utf8(content).map_err(|e| {
+            FsdbgError::invalid_format(format!("file '{}' is not valid UTF-8: {}", path, e))
+        })
+    }
 
-            // Normalize the path for the entry_map (for lookups)
-            let normalized_name = Self::normalize_path(&name);
-            if !normalized_name.is_empty() {
-                entry_map.insert(normalized_name, entries.len());
+    /// Follow symlinks until a regular file is reached, returning its
+    /// normalized path.
+    fn resolve_entry(&self, path: &str) -> Result<String, FsdbgError> {
+        // Same limit as the kernel's MAXSYMLINKS
+        const MAX_SYMLINK_DEPTH: usize = 40;
+
+        let mut current = Self::normalize_path(path);
+        for _ in 0..MAX_SYMLINK_DEPTH {
+            let entry = self
+                .get(&current)
+                .ok_or_else(|| FsdbgError::missing_required(path.to_string()))?;
+
+            match (entry.is_symlink(), entry.link_target.as_deref()) {
+                (true, Some(target)) => {
+                    current = self.resolve_symlink_target(&entry.path, target);
+                }
+                // A path found through a symlinked directory is stored
+                // under the entry's own name
+                _ if entry.is_file() => return Ok(Self::normalize_path(&entry.path)),
+                _ => {
+                    return Err(FsdbgError::invalid_format(format!(
+                        "{} is not a regular file",
+                        path
+                    )))
+                }
             }
-            entries.push(entry);
         }
 
-        Ok(Self { entries, entry_map })
+        Err(FsdbgError::symlink_broken(
+            path,
+            "too many levels of symbolic links",
+        ))
     }
 
     /// Get all entries
@@ -296,12 +1072,19 @@ impl CpioReader {
 
     /// Check if a path exists
     pub fn exists(&self, path: &str) -> bool {
-        let normalized = Self::normalize_path(path);
-        self.entry_map.contains_key(&normalized)
+        self.get(path).is_some()
     }
 
     /// Get an entry by path
     pub fn get(&self, path: &str) -> Option<&CpioEntry> {
+        self.lookup(path).or_else(|| {
+            let located = self.locate(path).filter(|_| self.resolve_symlinks)?;
+            self.lookup(&located)
+        })
+    }
+
+    /// Entry stored under exactly `path` (after normalization).
+    pub(crate) fn lookup(&self, path: &str) -> Option<&CpioEntry> {
         let normalized = Self::normalize_path(path);
         self.entry_map.get(&normalized).map(|&i| &self.entries[i])
     }
@@ -321,10 +1104,16 @@ impl CpioReader {
         self.entries.iter().filter(|e| e.is_symlink())
     }
 
-    /// Normalize a path (remove leading ./ or /)
+    /// Normalize a path (remove leading ./ or /, in any mix: `/./usr`)
     pub fn normalize_path(path: &str) -> String {
-        let p = path.trim_start_matches("./").trim_start_matches('/');
-        p.to_string()
+        let mut p = path;
+        loop {
+            let next = p.trim_start_matches('/').trim_start_matches("./");
+            if next == p {
+                return p.to_string();
+            }
+            p = next;
+        }
     }
 
     /// Verify that a symlink target exists in the archive
@@ -383,26 +1172,184 @@ impl CpioReader {
                 }
                 FileType::Directory => stats.directories += 1,
                 FileType::Symlink => stats.symlinks += 1,
-                _ => stats.other += 1,
+                FileType::CharDevice => stats.char_devices += 1,
+                FileType::BlockDevice => stats.block_devices += 1,
+                FileType::Fifo => stats.fifos += 1,
+                FileType::Socket => stats.sockets += 1,
+                FileType::Unknown => stats.unknown += 1,
+            }
+            if entry.is_whiteout() {
+                stats.whiteouts += 1;
+            }
+            if !matches!(
+                entry.file_type,
+                FileType::Regular | FileType::Directory | FileType::Symlink
+            ) {
+                stats.other += 1;
             }
         }
         stats
     }
 }
 
+/// One archive in a concatenated initramfs.
+///
+/// The kernel unpacks archives back to back, so an initramfs is often an
+/// uncompressed early CPIO (CPU microcode) followed by the compressed main
+/// image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub range: ByteRange,
+    /// Not a bare newc archive (gzip or another compressor)
+    pub compressed: bool,
+}
+
+/// Split a (possibly concatenated) initramfs into its archives.
+///
+/// Uncompressed archives are walked to their trailer to find where the next
+/// one starts. A compressed archive runs to the end of the file: the stream
+/// end can't be found without decompressing, and builders put it last.
+pub fn segments(path: &Path) -> Result<Vec<Segment>, FsdbgError> {
+    let size = std::fs::metadata(path)
+        .map_err(|_| FsdbgError::file_not_found(path))?
+        .len();
+    let mut segments = Vec::new();
+    let mut offset = 0;
+
+    while offset < size {
+        let mut stream = BufReader::new(ByteRange::new(offset, None).open(path)?);
+        // Archives are padded apart with zeros
+        let zeros = stream.fill_buf()?.iter().take_while(|b| **b == 0).count();
+        if zeros > 0 {
+            offset += zeros as u64;
+            continue;
+        }
+
+        let mut magic = [0u8; 6];
+        stream.read_exact(&mut magic).ok();
+        if &magic != b"070701" && &magic != b"070702" {
+            segments.push(Segment {
+                range: ByteRange::new(offset, Some(size - offset)),
+                compressed: true,
+            });
+            break;
+        }
+
+        let mut counter = CountingReader::new(ByteRange::new(offset, None).open(path)?);
+        CpioReader::walk(&mut counter, Limits::default(), false, false, |_, _| true)?;
+        segments.push(Segment {
+            range: ByteRange::new(offset, Some(counter.count)),
+            compressed: false,
+        });
+        offset += counter.count;
+    }
+
+    Ok(segments)
+}
+
+/// `reader` as a byte stream, transparently decompressing gzip
+pub(crate) fn decompress<'a>(
+    mut reader: impl BufRead + 'a,
+) -> Result<Box<dyn Read + 'a>, FsdbgError> {
+    // Check for gzip magic without consuming it
+    let magic = reader.fill_buf()?;
+    if magic.len() < 2 {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+
+    if magic[0] == 0x1f && magic[1] == 0x8b {
+        // Gzip compressed
+        Ok(Box::new(GzDecoder::new(reader)))
+    } else {
+        // Uncompressed
+        Ok(Box::new(reader))
+    }
+}
+
+/// Read exactly `len` bytes. The buffer grows with the data actually read,
+/// so a header declaring more than the stream holds can't force a large
+/// allocation up front.
+fn read_up_to(reader: &mut impl Read, len: u64) -> std::io::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(len.min(64 * 1024) as usize);
+    reader.take(len).read_to_end(&mut buf)?;
+    if (buf.len() as u64) < len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+/// Non-zero bytes left in a stream, stopping quietly at a read error.
+fn count_nonzero(reader: &mut impl Read) -> u64 {
+    let mut buf = [0u8; 8192];
+    let mut count = 0;
+    while let Ok(n @ 1..) = reader.read(&mut buf) {
+        count += buf[..n].iter().filter(|b| **b != 0).count() as u64;
+    }
+    count
+}
+
+/// Counts bytes read, to find where an archive ends, and notes when the
+/// stream ran dry.
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+    eof: bool,
+}
+
+impl<R> CountingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            count: 0,
+            eof: false,
+        }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.inner.read(buf) {
+            Ok(0) if !buf.is_empty() => {
+                self.eof = true;
+                Ok(0)
+            }
+            Ok(n) => {
+                self.count += n as u64;
+                Ok(n)
+            }
+            // A compressed stream cut short
+            Err(e) => {
+                self.eof |= e.kind() == std::io::ErrorKind::UnexpectedEof;
+                Err(e)
+            }
+        }
+    }
+}
+
 /// Statistics about a CPIO archive
 #[derive(Debug, Default)]
 pub struct CpioStats {
     pub files: usize,
     pub directories: usize,
     pub symlinks: usize,
+    /// Everything that isn't a file, directory or symlink (the sum of the
+    /// breakdown below)
     pub other: usize,
+    pub char_devices: usize,
+    /// Character devices that are overlayfs whiteouts (`c 0:0`)
+    pub whiteouts: usize,
+    pub block_devices: usize,
+    pub fifos: usize,
+    pub sockets: usize,
+    /// File type bits that match no known type
+    pub unknown: usize,
     pub total_size: u64,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ErrorCode;
     use leviso_cheat_guard::cheat_reviewed;
 
     #[cheat_reviewed(
@@ -412,6 +1359,7 @@ mod tests {
     fn test_mode_string() {
         let entry = CpioEntry {
             path: "test".to_string(),
+            raw_path: b"test".to_vec(),
             size: 0,
             mode: 0o100755,
             file_type: FileType::Regular,
@@ -424,6 +1372,7 @@ mod tests {
             dev_minor: 0,
             rdev_major: 0,
             rdev_minor: 0,
+            digest: None,
         };
         assert_eq!(entry.mode_string(), "-rwxr-xr-x");
     }
@@ -436,5 +1385,452 @@ mod tests {
         assert_eq!(CpioReader::normalize_path("./foo/bar"), "foo/bar");
         assert_eq!(CpioReader::normalize_path("/foo/bar"), "foo/bar");
         assert_eq!(CpioReader::normalize_path("foo/bar"), "foo/bar");
+        assert_eq!(CpioReader::normalize_path("/./foo/bar"), "foo/bar");
+    }
+
+    /// Build a newc archive from (path, mode, content) entries.
+    fn newc(entries: &[(&str, u32, &[u8])]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let trailer: &[(&str, u32, &[u8])] = &[("TRAILER!!!", 0, b"")];
+        for (path, mode, content) in entries.iter().chain(trailer) {
+            out.extend_from_slice(b"070701");
+            let fields = [0, *mode, 0, 0, 1, 0, content.len() as u32, 0, 0, 0, 0];
+            for field in fields {
+                out.extend_from_slice(format!("{:08x}", field).as_bytes());
+            }
+            out.extend_from_slice(format!("{:08x}{:08x}", path.len() + 1, 0).as_bytes());
+            out.extend_from_slice(path.as_bytes());
+            out.push(0);
+            out.resize(out.len().next_multiple_of(4), 0);
+            out.extend_from_slice(content);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        out
+    }
+
+    #[cheat_reviewed("Unit test for embedded archive ranges - reads a synthetic archive")]
+    #[test]
+    fn test_open_range() {
+        let archive = newc(&[("etc/hostname", 0o100644, b"levitate\n")]);
+        let mut image = vec![0xffu8; 100];
+        image.extend_from_slice(&archive);
+        image.extend_from_slice(&[0xff; 64]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.bin");
+        std::fs::write(&path, &image).unwrap();
+
+        let range = ByteRange::new(100, Some(archive.len() as u64));
+        let reader = CpioReader::open_range(&path, range).unwrap();
+        assert!(reader.exists("etc/hostname"));
+        assert_eq!(reader.read_file("etc/hostname").unwrap(), b"levitate\n");
+
+        assert!(CpioReader::open(&path).is_err());
+        assert!(CpioReader::open_range(&path, ByteRange::new(100, Some(1 << 20))).is_err());
+    }
+
+    #[cheat_reviewed("Unit test for parsing from memory - synthetic and malformed archives")]
+    #[test]
+    fn test_from_reader() {
+        let archive = newc(&[
+            ("lib64", 0o120777, b"usr/lib64"),
+            ("usr/lib64/ld-2.39.so", 0o100755, b"\x7fELF"),
+        ]);
+        let reader = CpioReader::from_reader(&archive[..], ParseOptions::default()).unwrap();
+        assert_eq!(reader.source_path(), Path::new(""));
+        assert_eq!(
+            reader.read_file("usr/lib64/ld-2.39.so").unwrap(),
+            b"\x7fELF"
+        );
+
+        // Read through the symlinked directory; this used to index the
+        // entry map with the unresolved path
+        let reader = reader.with_symlink_resolution();
+        assert_eq!(reader.read_file("lib64/ld-2.39.so").unwrap(), b"\x7fELF");
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut gzip, &archive).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let reader = CpioReader::from_reader(&gzip[..], ParseOptions::default()).unwrap();
+        assert_eq!(reader.entries().len(), 2);
+
+        // Malformed input is an error, never a panic
+        let mut bad_hex = archive.clone();
+        bad_hex[14..22].copy_from_slice(b"0000z000");
+        for input in [&b""[..], &bad_hex, &archive[..120]] {
+            assert!(CpioReader::from_reader(input, ParseOptions::default()).is_err());
+        }
+        let small = ParseOptions {
+            limits: Limits {
+                max_total: 64,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let error = CpioReader::from_reader(&archive[..], small).err().unwrap();
+        assert_eq!(error.code(), ErrorCode::LimitExceeded);
+    }
+
+    #[cheat_reviewed("Unit test for streaming entry contents - synthetic archive")]
+    #[test]
+    fn test_for_each_entry() {
+        let archive = newc(&[
+            ("bin", 0o040755, b""),
+            ("bin/busybox", 0o100755, b"busybox"),
+            ("bin/sh", 0o120777, b"busybox"),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("initramfs.img");
+        std::fs::write(&path, &archive).unwrap();
+
+        let mut seen = Vec::new();
+        let reader = CpioReader::for_each_entry(
+            &path,
+            ByteRange::default(),
+            ParseOptions::default(),
+            |entry, content| {
+                let mut text = String::new();
+                content.read_to_string(&mut text)?;
+                seen.push((entry.path.clone(), text));
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(reader.entries().len(), 3);
+        assert_eq!(
+            seen,
+            [
+                ("bin".to_string(), String::new()),
+                ("bin/busybox".to_string(), "busybox".to_string()),
+                ("bin/sh".to_string(), "busybox".to_string()),
+            ]
+        );
+
+        let error = CpioReader::for_each_entry(
+            &path,
+            ByteRange::default(),
+            ParseOptions::default(),
+            |entry, _| {
+                if entry.is_symlink() {
+                    return Err(FsdbgError::invalid_format("symlink"));
+                }
+                Ok(())
+            },
+        )
+        .err()
+        .unwrap();
+        assert_eq!(error.code(), ErrorCode::InvalidFormat);
+    }
+
+    #[cheat_reviewed("Unit test for per-file digests - synthetic archive")]
+    #[test]
+    fn test_open_hashed() {
+        let archive = newc(&[
+            ("bin", 0o040755, b""),
+            ("bin/busybox", 0o100755, b"busybox"),
+            ("bin/sh", 0o120777, b"busybox"),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("initramfs.img");
+        std::fs::write(&path, &archive).unwrap();
+
+        let reader =
+            CpioReader::open_hashed(&path, ByteRange::default(), HashAlgorithm::Sha256).unwrap();
+        let digests: Vec<_> = reader
+            .entries()
+            .iter()
+            .map(|e| e.digest.as_deref())
+            .collect();
+        let busybox = HashAlgorithm::Sha256.digest(b"busybox");
+        assert_eq!(digests, vec![None, Some(busybox.as_str()), None]);
+        assert_eq!(reader.content_digests().unwrap()["bin/busybox"], busybox);
+        assert!(CpioReader::open(&path).unwrap().entries()[1]
+            .digest
+            .is_none());
+        assert_eq!(
+            HashAlgorithm::parse_name("SHA256"),
+            Some(HashAlgorithm::Sha256)
+        );
+        assert_eq!(HashAlgorithm::parse_name("md5"), None);
+    }
+
+    #[cheat_reviewed("Unit test for lenient parsing of truncated archives - synthetic archive")]
+    #[test]
+    fn test_truncated() {
+        let archive = newc(&[
+            ("etc/hostname", 0o100644, b"levitate\n"),
+            ("usr/bin/busybox", 0o100755, &[0x7f; 64]),
+        ]);
+        let second = newc(&[("etc/hostname", 0o100644, b"levitate\n")]).len() - 124;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("initramfs.img");
+        std::fs::write(&path, &archive[..second + 150]).unwrap();
+
+        assert!(CpioReader::open(&path).is_err());
+        let lenient = ParseOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let reader = CpioReader::open_with(&path, ByteRange::default(), lenient).unwrap();
+        assert_eq!(reader.entries().len(), 1);
+        assert_eq!(
+            reader.truncated(),
+            Some(&Truncation {
+                offset: second as u64,
+                entry: Some("usr/bin/busybox".to_string()),
+            })
+        );
+        assert_eq!(reader.read_file("etc/hostname").unwrap(), b"levitate\n");
+
+        // Cut between entries: only the trailer is missing
+        std::fs::write(&path, &archive[..second]).unwrap();
+        assert!(CpioReader::open(&path).unwrap().truncated().is_none());
+        let reader = CpioReader::open_with(&path, ByteRange::default(), lenient).unwrap();
+        assert_eq!(
+            reader.truncated().unwrap().to_string(),
+            format!("truncated at byte {} (before the trailer)", second)
+        );
+    }
+
+    #[cheat_reviewed("Unit test for allocation limits - synthetic hostile archives")]
+    #[test]
+    fn test_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("initramfs.img");
+        let with_limits = |limits: Limits| ParseOptions {
+            limits,
+            ..Default::default()
+        };
+
+        // Declares 4 GiB of content but holds 4 bytes
+        let mut archive = newc(&[("big", 0o100644, b"data")]);
+        archive[54..62].copy_from_slice(b"fffffff0");
+        std::fs::write(&path, &archive).unwrap();
+        let error = CpioReader::open(&path).err().unwrap();
+        assert_eq!(error.code(), ErrorCode::LimitExceeded);
+        let roomy = Limits {
+            max_entry: u64::MAX,
+            ..Default::default()
+        };
+        let error = CpioReader::open_with(&path, ByteRange::default(), with_limits(roomy))
+            .err()
+            .unwrap();
+        assert_eq!(error.code(), ErrorCode::IoError);
+
+        let archive = newc(&[
+            ("etc/hostname", 0o100644, b"levitate\n"),
+            ("etc/os-release", 0o100644, b"NAME=LevitateOS\n"),
+        ]);
+        std::fs::write(&path, &archive).unwrap();
+        let short_names = Limits {
+            max_name: 8,
+            ..Default::default()
+        };
+        assert!(
+            CpioReader::open_with(&path, ByteRange::default(), with_limits(short_names)).is_err()
+        );
+
+        let one_entry = Limits {
+            max_total: 300,
+            ..Default::default()
+        };
+        let error = CpioReader::open_with(&path, ByteRange::default(), with_limits(one_entry))
+            .err()
+            .unwrap();
+        assert_eq!(error.code(), ErrorCode::LimitExceeded);
+    }
+
+    #[cheat_reviewed("Unit test for strict-mode anomalies - synthetic archive")]
+    #[test]
+    fn test_anomalies() {
+        let mut archive = newc(&[
+            ("etc/hostname", 0o100644, b"a\n"),
+            ("etc/hostname", 0o100644, b"b\n"),
+            ("dev/weird", 0, b""),
+        ]);
+        archive.extend_from_slice(b"junk");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("initramfs.img");
+        std::fs::write(&path, &archive).unwrap();
+
+        let reader = CpioReader::open(&path).unwrap();
+        let found: Vec<_> = reader
+            .anomalies()
+            .iter()
+            .map(|a| (a.path.as_str(), a.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("dev/weird", "Mode is 0 (no file type)"),
+                (
+                    "etc/hostname",
+                    "Duplicate entry; the kernel keeps the later one"
+                ),
+                (
+                    "TRAILER!!!",
+                    "4 bytes of data after the trailer (concatenated archive?)"
+                ),
+            ]
+        );
+        assert_eq!(reader.read_file("etc/hostname").unwrap(), b"b\n");
+        assert_eq!(reader.duplicates(), vec![("etc/hostname".to_string(), 2)]);
+
+        let reader = reader
+            .with_duplicate_policy(DuplicatePolicy::First)
+            .unwrap();
+        assert_eq!(reader.read_file("etc/hostname").unwrap(), b"a\n");
+        let files = reader.read_files(&["etc/hostname"]).unwrap();
+        assert_eq!(files["etc/hostname"], b"a\n");
+
+        assert!(reader
+            .with_duplicate_policy(DuplicatePolicy::Error)
+            .is_err());
+    }
+
+    #[cheat_reviewed("Unit test for concatenated archives - synthetic archives")]
+    #[test]
+    fn test_concatenated_duplicates() {
+        // An early archive padded to 512 bytes, then an overriding one
+        let mut image = newc(&[
+            ("etc", 0o040755, b""),
+            ("etc/hostname", 0o100644, b"early\n"),
+        ]);
+        image.resize(512, 0);
+        image.extend(newc(&[
+            ("etc/hostname", 0o100644, b"late\n"),
+            ("etc/motd", 0o100644, b"hi\n"),
+        ]));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("initramfs.img");
+        std::fs::write(&path, &image).unwrap();
+
+        let reader = CpioReader::open(&path).unwrap();
+        assert_eq!(reader.entries().len(), 4);
+        assert!(reader.exists("etc/motd"));
+        assert_eq!(reader.duplicates(), vec![("etc/hostname".to_string(), 2)]);
+        assert_eq!(
+            reader
+                .anomalies()
+                .iter()
+                .map(|a| a.message.as_str())
+                .collect::<Vec<_>>(),
+            ["Duplicate entry; the kernel keeps the later one"]
+        );
+
+        // Last is what the kernel unpacks
+        assert_eq!(reader.read_file("etc/hostname").unwrap(), b"late\n");
+        let reader = reader.with_duplicate_policy(DuplicatePolicy::Last).unwrap();
+        assert_eq!(reader.read_file("etc/hostname").unwrap(), b"late\n");
+        let reader = reader
+            .with_duplicate_policy(DuplicatePolicy::First)
+            .unwrap();
+        assert_eq!(reader.read_file("etc/hostname").unwrap(), b"early\n");
+        let error = reader
+            .with_duplicate_policy(DuplicatePolicy::Error)
+            .err()
+            .unwrap();
+        assert_eq!(error.message(), "Duplicate entries for: etc/hostname");
+
+        // Splitting still stops at each trailer
+        assert_eq!(segments(&path).unwrap().len(), 2);
+    }
+
+    #[cheat_reviewed("Unit test for concatenated archive splitting - synthetic archives")]
+    #[test]
+    fn test_segments() {
+        let early = newc(&[("kernel/x86/microcode/GenuineIntel.bin", 0o100644, b"ucode")]);
+        let mut image = early.clone();
+        image.resize(512, 0);
+        image.extend_from_slice(&[0x1f, 0x8b, 0x08, 0x00]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("initramfs.img");
+        std::fs::write(&path, &image).unwrap();
+
+        assert_eq!(
+            segments(&path).unwrap(),
+            vec![
+                Segment {
+                    range: ByteRange::new(0, Some(early.len() as u64)),
+                    compressed: false,
+                },
+                Segment {
+                    range: ByteRange::new(512, Some(4)),
+                    compressed: true,
+                },
+            ]
+        );
+    }
+    #[cheat_reviewed("Unit test for file-type statistics - synthetic archive")]
+    #[test]
+    fn test_stats_breakdown() {
+        let archive = newc(&[
+            ("run/build.sock", 0o140755, b""),
+            ("run/pipe", 0o010644, b""),
+            ("dev/null", 0o020666, b""),
+            ("etc/hostname", 0o100644, b"levitate\n"),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rootfs.cpio");
+        std::fs::write(&path, &archive).unwrap();
+
+        let stats = CpioReader::open(&path).unwrap().stats();
+        assert_eq!((stats.files, stats.other), (1, 3));
+        assert_eq!(
+            (
+                stats.sockets,
+                stats.fifos,
+                stats.char_devices,
+                stats.unknown
+            ),
+            (1, 1, 1, 0)
+        );
+        // newc() writes rdev 0:0, so its char device reads as a whiteout
+        assert_eq!(stats.whiteouts, 1);
+    }
+
+    #[cheat_reviewed("Unit test for symlink-aware path resolution - synthetic archive")]
+    #[test]
+    fn test_resolve() {
+        let archive = newc(&[
+            ("lib64", 0o120777, b"usr/lib64"),
+            ("usr/lib64/ld-linux-x86-64.so.2", 0o120777, b"ld-2.39.so"),
+            ("usr/lib64/ld-2.39.so", 0o100755, b"\x7fELF"),
+            ("usr/lib/loop", 0o120777, b"loop"),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rootfs.cpio");
+        std::fs::write(&path, &archive).unwrap();
+
+        let reader = CpioReader::open(&path).unwrap();
+        assert_eq!(
+            reader.resolve("/lib64/ld-linux-x86-64.so.2").as_deref(),
+            Some("usr/lib64/ld-2.39.so")
+        );
+        assert_eq!(
+            reader.resolve("/usr/lib/../lib64/./ld-2.39.so").as_deref(),
+            Some("usr/lib64/ld-2.39.so")
+        );
+        assert_eq!(reader.resolve("/lib/ld-linux-aarch64.so.1"), None);
+        assert_eq!(reader.resolve("usr/lib/loop"), None);
+        assert_eq!(
+            reader.locate("lib64/ld-linux-x86-64.so.2").as_deref(),
+            Some("usr/lib64/ld-linux-x86-64.so.2")
+        );
+
+        // Literal lookups unless resolution is asked for
+        assert!(!reader.exists("lib64/ld-2.39.so"));
+        let reader = reader.with_symlink_resolution();
+        assert_eq!(
+            reader.get("./lib64/ld-2.39.so").map(|e| e.path.as_str()),
+            Some("usr/lib64/ld-2.39.so")
+        );
+        assert!(reader
+            .get("lib64/ld-linux-x86-64.so.2")
+            .unwrap()
+            .is_symlink());
     }
 }
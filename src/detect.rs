@@ -0,0 +1,305 @@
+//! Format detection registry
+//!
+//! Each known format contributes a [`Detector`]: magic bytes at fixed
+//! offsets, an optional deeper validation of the header, and file
+//! extensions as a last resort. Every detector is probed against one header
+//! read, so `fsdbg detect --all` can list every plausible match with a
+//! confidence, and [`crate::detect_format`] picks the best one.
+//!
+//! The registry covers more formats than fsdbg can open (squashfs, tar,
+//! ext4, qcow2, bare zstd/xz) so that handing it one of those produces
+//! "this is a squashfs image" rather than "could not detect format".
+
+use crate::error::FsdbgError;
+use crate::ArchiveFormat;
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes read from the start of a file for probing. Covers the ISO 9660
+/// primary volume descriptor at 0x8000, the furthest-out magic we check.
+pub const HEADER_LEN: usize = 0x8800;
+
+/// How sure a probe is about a match, weakest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// Only the file extension matched
+    Low,
+    /// Magic bytes matched
+    Medium,
+    /// Magic bytes matched and the header validated
+    High,
+}
+
+impl std::fmt::Display for Confidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Confidence::Low => write!(f, "low (extension only)"),
+            Confidence::Medium => write!(f, "medium (magic)"),
+            Confidence::High => write!(f, "high (magic + header)"),
+        }
+    }
+}
+
+/// Probe for one format.
+#[derive(Debug)]
+pub struct Detector {
+    pub name: &'static str,
+    /// Format fsdbg can open, or None for recognise-only formats
+    pub format: Option<ArchiveFormat>,
+    /// (offset, bytes) pairs; any one matching counts as a magic match
+    pub magic: &'static [(usize, &'static [u8])],
+    /// Deeper check of the header buffer once the magic matched
+    pub validate: Option<fn(&[u8]) -> bool>,
+    pub extensions: &'static [&'static str],
+}
+
+impl Detector {
+    fn probe(&self, header: &[u8], extension: Option<&str>) -> Option<Confidence> {
+        let magic = self
+            .magic
+            .iter()
+            .any(|(offset, bytes)| header.get(*offset..offset + bytes.len()) == Some(bytes));
+        if magic {
+            match self.validate {
+                Some(validate) if validate(header) => Some(Confidence::High),
+                _ => Some(Confidence::Medium),
+            }
+        } else if extension.is_some_and(|ext| self.extensions.contains(&ext)) {
+            Some(Confidence::Low)
+        } else {
+            None
+        }
+    }
+}
+
+/// A plausible format for a file.
+#[derive(Debug, Clone, Copy)]
+pub struct Match {
+    pub detector: &'static Detector,
+    pub confidence: Confidence,
+}
+
+/// All known formats, in priority order (ties in confidence go to the
+/// earlier entry).
+pub const DETECTORS: &[Detector] = &[
+    Detector {
+        name: "CPIO (gzip compressed)",
+        format: Some(ArchiveFormat::CpioGzip),
+        magic: &[(0, &[0x1f, 0x8b])],
+        validate: Some(gzip_contains_cpio),
+        extensions: &["gz"],
+    },
+    Detector {
+        name: "CPIO (uncompressed)",
+        format: Some(ArchiveFormat::Cpio),
+        magic: &[(0, b"070701"), (0, b"070702")],
+        validate: Some(valid_newc_header),
+        extensions: &["img", "cpio"],
+    },
+    Detector {
+        name: "ISO 9660",
+        format: Some(ArchiveFormat::Iso),
+        magic: &[(0x8001, b"CD001")],
+        // Volume descriptor type 1 = primary
+        validate: Some(|h| h[0x8000] == 1),
+        extensions: &["iso"],
+    },
+    Detector {
+        name: "EROFS",
+        format: Some(ArchiveFormat::Erofs),
+        magic: &[(1024, &[0xe2, 0xe1, 0xf5, 0xe0])],
+        // blkszbits: 512 B to 64 KiB blocks
+        validate: Some(|h| (9..=16).contains(&h[1024 + 12])),
+        extensions: &["erofs"],
+    },
+    Detector {
+        name: "squashfs",
+        format: None,
+        magic: &[(0, b"hsqs")],
+        // Major version 4, the only one the kernel still mounts
+        validate: Some(|h| h[28..30] == [4, 0]),
+        extensions: &["squashfs", "sqfs"],
+    },
+    Detector {
+        name: "qcow2",
+        format: None,
+        magic: &[(0, b"QFI\xfb")],
+        validate: Some(|h| matches!(u32::from_be_bytes([h[4], h[5], h[6], h[7]]), 2 | 3)),
+        extensions: &["qcow2"],
+    },
+    Detector {
+        name: "ext2/3/4",
+        format: None,
+        magic: &[(1080, &[0x53, 0xef])],
+        // s_log_block_size: 1 KiB to 64 KiB blocks
+        validate: Some(|h| u32::from_le_bytes([h[1048], h[1049], h[1050], h[1051]]) <= 6),
+        extensions: &["ext4", "ext3", "ext2"],
+    },
+    Detector {
+        name: "LUKS encrypted volume",
+        format: None,
+        magic: &[(0, crate::luks::MAGIC)],
+        validate: Some(|h| matches!(h[6..8], [0, 1] | [0, 2])),
+        extensions: &["luks"],
+    },
+    Detector {
+        name: "tar",
+        format: None,
+        magic: &[(257, b"ustar")],
+        validate: Some(valid_tar_checksum),
+        extensions: &["tar"],
+    },
+    Detector {
+        name: "zstd compressed",
+        format: None,
+        magic: &[(0, &[0x28, 0xb5, 0x2f, 0xfd])],
+        validate: None,
+        extensions: &["zst"],
+    },
+    Detector {
+        name: "xz compressed",
+        format: None,
+        magic: &[(0, &[0xfd, b'7', b'z', b'X', b'Z', 0x00])],
+        validate: None,
+        extensions: &["xz"],
+    },
+    Detector {
+        name: "Device tree blob",
+        format: None,
+        magic: &[(0, &crate::dtb::MAGIC)],
+        validate: None,
+        extensions: &["dtb"],
+    },
+    Detector {
+        name: "Android boot image",
+        format: None,
+        magic: &[(0, crate::bootimg::MAGIC)],
+        validate: None,
+        extensions: &[],
+    },
+    Detector {
+        name: "PE/EFI image (UKI or EFI stub kernel)",
+        format: None,
+        magic: &[(0, b"MZ")],
+        validate: Some(crate::uki::is_pe_header),
+        extensions: &["efi"],
+    },
+];
+
+/// Every plausible format for a file, most confident first.
+pub fn probe(path: &Path) -> Result<Vec<Match>, FsdbgError> {
+    if !path.exists() {
+        return Err(FsdbgError::file_not_found(path));
+    }
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    File::open(path)?
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+
+    let extension = path.extension().and_then(|e| e.to_str());
+    Ok(probe_header(&header, extension))
+}
+
+/// Probe an in-memory header (at least [`HEADER_LEN`] bytes if available).
+pub fn probe_header(header: &[u8], extension: Option<&str>) -> Vec<Match> {
+    // Pad short files so validators can index the header freely
+    let mut padded;
+    let header = if header.len() < HEADER_LEN {
+        padded = header.to_vec();
+        padded.resize(HEADER_LEN, 0);
+        &padded[..]
+    } else {
+        header
+    };
+
+    let mut matches: Vec<Match> = DETECTORS
+        .iter()
+        .filter_map(|detector| {
+            detector.probe(header, extension).map(|confidence| Match {
+                detector,
+                confidence,
+            })
+        })
+        .collect();
+    // Stable sort keeps registry priority within a confidence level
+    matches.sort_by_key(|m| std::cmp::Reverse(m.confidence));
+    matches
+}
+
+/// Decompress the start of a gzip stream and check for a newc header.
+fn gzip_contains_cpio(header: &[u8]) -> bool {
+    let mut magic = [0u8; 6];
+    GzDecoder::new(header).read_exact(&mut magic).is_ok()
+        && (&magic == b"070701" || &magic == b"070702")
+}
+
+/// newc header: magic plus 13 eight-digit hex fields.
+fn valid_newc_header(header: &[u8]) -> bool {
+    header[6..110].iter().all(u8::is_ascii_hexdigit)
+}
+
+/// ustar header checksum: sum of the 512-byte header with the checksum
+/// field (148..156) taken as spaces.
+pub(crate) fn valid_tar_checksum(header: &[u8]) -> bool {
+    let field = String::from_utf8_lossy(&header[148..156]);
+    let Ok(expected) = u32::from_str_radix(field.trim_matches(|c: char| c == '\0' || c == ' '), 8)
+    else {
+        return false;
+    };
+    let sum: u32 = header[..512]
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            if (148..156).contains(&i) {
+                32
+            } else {
+                *b as u32
+            }
+        })
+        .sum();
+    sum == expected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(matches: &[Match]) -> Vec<(&str, Confidence)> {
+        matches
+            .iter()
+            .map(|m| (m.detector.name, m.confidence))
+            .collect()
+    }
+
+    #[test]
+    fn test_probe_header() {
+        let mut newc = b"070701".to_vec();
+        newc.extend_from_slice(&[b'0'; 104]);
+        assert_eq!(
+            names(&probe_header(&newc, Some("img"))),
+            vec![("CPIO (uncompressed)", Confidence::High)]
+        );
+
+        let mut squashfs = vec![0u8; 96];
+        squashfs[..4].copy_from_slice(b"hsqs");
+        squashfs[28] = 4;
+        assert_eq!(
+            names(&probe_header(&squashfs, Some("img"))),
+            vec![
+                ("squashfs", Confidence::High),
+                ("CPIO (uncompressed)", Confidence::Low)
+            ]
+        );
+
+        let mut qcow2 = b"QFI\xfb".to_vec();
+        qcow2.extend_from_slice(&[0, 0, 0, 7]);
+        assert_eq!(
+            names(&probe_header(&qcow2, None)),
+            vec![("qcow2", Confidence::Medium)]
+        );
+
+        assert!(probe_header(b"hello", Some("txt")).is_empty());
+    }
+}
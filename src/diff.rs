@@ -0,0 +1,779 @@
+//! Archive comparison
+//!
+//! Compares two archives entry by entry. Besides added/removed paths, entries
+//! present in both are compared on metadata so that regressions invisible to a
+//! plain path diff show up:
+//! - Permission changes (especially setuid/setgid bits being dropped)
+//! - Ownership flips (uid/gid)
+//! - Symlink retargeting
+//! - File type and size changes
+//!
+//! A three-way mode ([`compare3`]) compares two builds against a common base
+//! and attributes each change to one side, for triaging which branch of the
+//! build pipeline introduced a regression.
+//!
+//! A live ISO's root filesystem is composed: the overlay is stacked on the
+//! EROFS rootfs at boot. [`compose`] builds that merged view, remembering
+//! which layer each entry comes from and which overlay entries hide a rootfs
+//! entry, and [`compare_layered`] attributes each change to its layer.
+//!
+//! ISO entries carry no mode/ownership, so only type, size and symlink target
+//! are compared for them. Archives of different formats (a CPIO against the
+//! EROFS built from the same tree) are first passed through
+//! [`normalize_cross_format`] so only logical content is compared.
+
+use crate::cpio::CpioReader;
+use crate::erofs::ErofsReader;
+use crate::iso::IsoReader;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Kind of archive entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryKind::File => write!(f, "file"),
+            EntryKind::Directory => write!(f, "directory"),
+            EntryKind::Symlink => write!(f, "symlink"),
+            EntryKind::Other => write!(f, "special"),
+        }
+    }
+}
+
+/// Format-independent view of an entry for comparison
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffEntry {
+    pub kind: EntryKind,
+    pub size: u64,
+    /// Permission bits (None if the format doesn't record them)
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub link_target: Option<String>,
+}
+
+/// Entries keyed by normalized path (no leading `./` or `/`)
+pub type EntryMap = BTreeMap<String, DiffEntry>;
+
+/// A single metadata field that differs between two entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+    /// Why this change matters (e.g. "setuid removed"), if notable
+    pub note: Option<&'static str>,
+}
+
+impl fmt::Display for MetadataChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} -> {}", self.field, self.old, self.new)?;
+        if let Some(note) = self.note {
+            write!(f, " ({})", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// Difference for one path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Only in the second archive
+    Added,
+    /// Only in the first archive
+    Removed,
+    /// In both, with differing metadata
+    Modified(Vec<MetadataChange>),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added => write!(f, "added"),
+            Change::Removed => write!(f, "removed"),
+            Change::Modified(fields) => {
+                let fields: Vec<String> = fields.iter().map(|c| c.to_string()).collect();
+                write!(f, "{}", fields.join(", "))
+            }
+        }
+    }
+}
+
+/// Collect comparable entries from a CPIO archive.
+pub fn cpio_entries(reader: &CpioReader) -> EntryMap {
+    reader
+        .entries()
+        .iter()
+        .map(|e| {
+            let kind = if e.is_file() {
+                EntryKind::File
+            } else if e.is_dir() {
+                EntryKind::Directory
+            } else if e.is_symlink() {
+                EntryKind::Symlink
+            } else {
+                EntryKind::Other
+            };
+            (
+                normalize(&e.path),
+                DiffEntry {
+                    kind,
+                    size: if kind == EntryKind::File { e.size } else { 0 },
+                    mode: Some(e.permissions()),
+                    uid: Some(e.uid),
+                    gid: Some(e.gid),
+                    link_target: e.link_target.clone(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Collect comparable entries from an ISO image.
+pub fn iso_entries(reader: &IsoReader) -> EntryMap {
+    reader
+        .entries()
+        .iter()
+        .map(|e| {
+            let kind = if e.is_dir {
+                EntryKind::Directory
+            } else if e.is_symlink {
+                EntryKind::Symlink
+            } else {
+                EntryKind::File
+            };
+            (
+                normalize(&e.path),
+                DiffEntry {
+                    kind,
+                    size: if kind == EntryKind::File { e.size } else { 0 },
+                    mode: None,
+                    uid: None,
+                    gid: None,
+                    link_target: e.link_target.clone(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Collect comparable entries from an EROFS image.
+pub fn erofs_entries(reader: &ErofsReader) -> EntryMap {
+    reader
+        .entries()
+        .iter()
+        .map(|e| {
+            let kind = if e.is_dir {
+                EntryKind::Directory
+            } else if e.is_symlink {
+                EntryKind::Symlink
+            } else if matches!(e.mode.chars().next(), Some('c' | 'b' | 'p' | 's')) {
+                EntryKind::Other
+            } else {
+                EntryKind::File
+            };
+            (
+                normalize(&e.path),
+                DiffEntry {
+                    kind,
+                    size: if kind == EntryKind::File { e.size } else { 0 },
+                    mode: permissions_from_ls(&e.mode),
+                    uid: None,
+                    gid: None,
+                    link_target: e.link_target.clone(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Collect an EROFS overlay layer's entries, and apart from them the paths
+/// its whiteouts delete from the layer below.
+pub fn erofs_layer(reader: &ErofsReader) -> (EntryMap, Vec<String>) {
+    let whiteouts: Vec<String> = reader
+        .entries()
+        .iter()
+        .filter(|e| e.is_whiteout())
+        .map(|e| normalize(&e.path))
+        .collect();
+    let mut entries = erofs_entries(reader);
+    entries.retain(|path, _| !whiteouts.contains(path));
+    (entries, whiteouts)
+}
+
+/// Permission bits from an `ls -l` mode string (`-rwsr-xr-x`).
+fn permissions_from_ls(mode: &str) -> Option<u32> {
+    let bits = mode.as_bytes().get(1..10)?;
+    let mut perms = 0;
+    for (i, &c) in bits.iter().enumerate() {
+        let bit = 1 << (8 - i);
+        // s/t: special bit plus execute; S/T: special bit without execute
+        let special = match i {
+            2 => 0o4000,
+            5 => 0o2000,
+            8 => 0o1000,
+            _ => 0,
+        };
+        match c {
+            b'-' => {}
+            b'r' | b'w' | b'x' => perms |= bit,
+            b's' | b't' if special != 0 => perms |= bit | special,
+            b'S' | b'T' if special != 0 => perms |= special,
+            _ => return None,
+        }
+    }
+    Some(perms)
+}
+
+/// Make entry maps from different formats comparable.
+///
+/// Formats disagree on things that aren't content: whether the root and
+/// intermediate directories get entries of their own, and which metadata is
+/// recorded at all. This drops root entries, adds the directories implied by
+/// each path on every side, and clears metadata not every side records, so a
+/// CPIO, an EROFS image and an ISO built from the same tree compare equal.
+pub fn normalize_cross_format(maps: &mut [EntryMap]) {
+    for entries in maps.iter_mut() {
+        entries.retain(|path, _| !path.is_empty() && path != ".");
+        let implied: Vec<String> = entries
+            .keys()
+            .flat_map(|path| path.match_indices('/').map(|(i, _)| path[..i].to_string()))
+            .collect();
+        for dir in implied {
+            entries.entry(dir).or_insert(DiffEntry {
+                kind: EntryKind::Directory,
+                size: 0,
+                mode: None,
+                uid: None,
+                gid: None,
+                link_target: None,
+            });
+        }
+    }
+
+    let all = |field: fn(&DiffEntry) -> bool| maps.iter().all(|m| m.values().any(field));
+    let modes = all(|e| e.mode.is_some());
+    let owners = all(|e| e.uid.is_some());
+    for entry in maps.iter_mut().flat_map(|m| m.values_mut()) {
+        if !modes {
+            entry.mode = None;
+        }
+        if !owners {
+            entry.uid = None;
+            entry.gid = None;
+        }
+    }
+}
+
+fn normalize(path: &str) -> String {
+    path.trim_start_matches("./")
+        .trim_start_matches('/')
+        .to_string()
+}
+
+/// Compare two entry maps, returning changes sorted by path.
+pub fn compare(old: &EntryMap, new: &EntryMap) -> Vec<(String, Change)> {
+    let paths: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            change_between(old.get(path), new.get(path)).map(|change| (path.clone(), change))
+        })
+        .collect()
+}
+
+/// Difference between two versions of one path, if any.
+pub fn change_between(old: Option<&DiffEntry>, new: Option<&DiffEntry>) -> Option<Change> {
+    match (old, new) {
+        (None, None) => None,
+        (None, Some(_)) => Some(Change::Added),
+        (Some(_), None) => Some(Change::Removed),
+        (Some(old), Some(new)) => {
+            let fields = metadata_changes(old, new);
+            (!fields.is_empty()).then_some(Change::Modified(fields))
+        }
+    }
+}
+
+/// Which side of a three-way diff a change came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Origin {
+    /// Changed from base only in "ours"
+    Ours,
+    /// Changed from base only in "theirs"
+    Theirs,
+    /// Changed identically on both sides
+    Both,
+    /// Changed differently on each side
+    Conflict,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Ours => write!(f, "Changed only in ours"),
+            Origin::Theirs => write!(f, "Changed only in theirs"),
+            Origin::Both => write!(f, "Same change in both"),
+            Origin::Conflict => write!(f, "Conflicting changes"),
+        }
+    }
+}
+
+/// One path in a three-way diff, with each side's change relative to base
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreeWayChange {
+    pub path: String,
+    pub origin: Origin,
+    pub ours: Option<Change>,
+    pub theirs: Option<Change>,
+}
+
+/// Three-way comparison of `ours` and `theirs` against a common `base`.
+///
+/// Paths unchanged on both sides are omitted. Results are sorted by path.
+pub fn compare3(base: &EntryMap, ours: &EntryMap, theirs: &EntryMap) -> Vec<ThreeWayChange> {
+    let paths: BTreeSet<&String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+
+    let mut changes = Vec::new();
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        let ours_change = change_between(b, o);
+        let theirs_change = change_between(b, t);
+
+        let origin = match (&ours_change, &theirs_change) {
+            (None, None) => continue,
+            (Some(_), None) => Origin::Ours,
+            (None, Some(_)) => Origin::Theirs,
+            (Some(_), Some(_)) if change_between(o, t).is_none() => Origin::Both,
+            (Some(_), Some(_)) => Origin::Conflict,
+        };
+        changes.push(ThreeWayChange {
+            path: path.clone(),
+            origin,
+            ours: ours_change,
+            theirs: theirs_change,
+        });
+    }
+    changes
+}
+
+/// Layer of a composed root filesystem an entry comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    /// The read-only rootfs
+    Lower,
+    /// The overlay stacked on it
+    Upper,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layer::Lower => write!(f, "lower"),
+            Layer::Upper => write!(f, "upper"),
+        }
+    }
+}
+
+/// Merged view of an upper layer stacked on a lower one
+#[derive(Debug, Clone, Default)]
+pub struct Layered {
+    /// Entries as overlayfs presents them
+    pub merged: EntryMap,
+    /// Layer each merged entry comes from
+    pub layers: BTreeMap<String, Layer>,
+    /// Upper entries that hide a lower entry, with the entry they hide
+    pub shadows: BTreeMap<String, DiffEntry>,
+}
+
+impl Layered {
+    /// Drop paths from every part of the view.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.merged.retain(|path, _| keep(path));
+        self.layers.retain(|path, _| keep(path));
+        self.shadows.retain(|path, _| keep(path));
+    }
+}
+
+/// Stack `upper` on `lower` as overlayfs does.
+///
+/// An upper entry replaces the lower entry at its path, except that
+/// directories in both merge. A non-directory in the upper layer also hides
+/// everything below its path in the lower one, as do `whiteouts` (which
+/// `upper` must not list) for their path and `opaque` directories for what's
+/// below them.
+pub fn compose(
+    lower: &EntryMap,
+    upper: &EntryMap,
+    whiteouts: &[String],
+    opaque: &[String],
+) -> Layered {
+    let under = |path: &str, dir: &str| {
+        path.strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+    };
+    let hidden = |path: &str| {
+        whiteouts
+            .iter()
+            .any(|w| path == w.as_str() || under(path, w))
+            || opaque.iter().any(|o| under(path, o))
+            || path.match_indices('/').any(|(i, _)| {
+                upper
+                    .get(&path[..i])
+                    .is_some_and(|e| e.kind != EntryKind::Directory)
+            })
+    };
+
+    let mut layered = Layered::default();
+    for (path, entry) in lower {
+        if hidden(path) {
+            continue;
+        }
+        match upper.get(path) {
+            Some(over) => {
+                let merges =
+                    entry.kind == EntryKind::Directory && over.kind == EntryKind::Directory;
+                if !merges {
+                    layered.shadows.insert(path.clone(), entry.clone());
+                }
+            }
+            None => {
+                layered.merged.insert(path.clone(), entry.clone());
+                layered.layers.insert(path.clone(), Layer::Lower);
+            }
+        }
+    }
+    for (path, entry) in upper {
+        layered.merged.insert(path.clone(), entry.clone());
+        layered.layers.insert(path.clone(), Layer::Upper);
+    }
+    layered
+}
+
+/// One path in a layered diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayeredChange {
+    pub path: String,
+    /// Difference in the merged view; `None` if only the layer changed
+    pub change: Option<Change>,
+    /// Layer the path comes from on each side
+    pub old: Option<Layer>,
+    pub new: Option<Layer>,
+}
+
+/// Compare two composed root filesystems, attributing each change to the
+/// layers it comes from. Paths that moved between layers are reported even
+/// when their entry didn't change.
+pub fn compare_layered(old: &Layered, new: &Layered) -> Vec<LayeredChange> {
+    let paths: BTreeSet<&String> = old.merged.keys().chain(new.merged.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let change = change_between(old.merged.get(path), new.merged.get(path));
+            let (old, new) = (old.layers.get(path).copied(), new.layers.get(path).copied());
+            let moved = old.is_some() && new.is_some() && old != new;
+            (change.is_some() || moved).then(|| LayeredChange {
+                path: path.clone(),
+                change,
+                old,
+                new,
+            })
+        })
+        .collect()
+}
+
+/// Compare the metadata of an entry present in both archives.
+pub fn metadata_changes(old: &DiffEntry, new: &DiffEntry) -> Vec<MetadataChange> {
+    let mut changes = Vec::new();
+
+    if old.kind != new.kind {
+        changes.push(MetadataChange {
+            field: "type",
+            old: old.kind.to_string(),
+            new: new.kind.to_string(),
+            note: None,
+        });
+    }
+
+    if let (Some(old_mode), Some(new_mode)) = (old.mode, new.mode) {
+        if old_mode != new_mode {
+            changes.push(MetadataChange {
+                field: "mode",
+                old: format!("{:04o}", old_mode),
+                new: format!("{:04o}", new_mode),
+                note: mode_note(old_mode, new_mode),
+            });
+        }
+    }
+
+    for (field, old_id, new_id) in [("uid", old.uid, new.uid), ("gid", old.gid, new.gid)] {
+        if let (Some(old_id), Some(new_id)) = (old_id, new_id) {
+            if old_id != new_id {
+                changes.push(MetadataChange {
+                    field,
+                    old: old_id.to_string(),
+                    new: new_id.to_string(),
+                    note: None,
+                });
+            }
+        }
+    }
+
+    if old.link_target != new.link_target {
+        changes.push(MetadataChange {
+            field: "target",
+            old: old.link_target.clone().unwrap_or_else(|| "-".to_string()),
+            new: new.link_target.clone().unwrap_or_else(|| "-".to_string()),
+            note: None,
+        });
+    }
+
+    if old.kind == new.kind && old.size != new.size {
+        changes.push(MetadataChange {
+            field: "size",
+            old: old.size.to_string(),
+            new: new.size.to_string(),
+            note: if new.size == 0 {
+                Some("now empty")
+            } else {
+                None
+            },
+        });
+    }
+
+    changes
+}
+
+/// Flag the permission changes that have caused real breakage.
+fn mode_note(old: u32, new: u32) -> Option<&'static str> {
+    const SETUID: u32 = 0o4000;
+    const SETGID: u32 = 0o2000;
+    const EXEC: u32 = 0o111;
+
+    if old & SETUID != 0 && new & SETUID == 0 {
+        Some("setuid removed")
+    } else if old & SETUID == 0 && new & SETUID != 0 {
+        Some("setuid added")
+    } else if old & SETGID != 0 && new & SETGID == 0 {
+        Some("setgid removed")
+    } else if old & EXEC != 0 && new & EXEC == 0 {
+        Some("no longer executable")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(mode: u32) -> DiffEntry {
+        DiffEntry {
+            kind: EntryKind::File,
+            size: 100,
+            mode: Some(mode),
+            uid: Some(0),
+            gid: Some(0),
+            link_target: None,
+        }
+    }
+
+    fn dir() -> DiffEntry {
+        DiffEntry {
+            kind: EntryKind::Directory,
+            size: 0,
+            ..file(0o755)
+        }
+    }
+
+    #[test]
+    fn test_permissions_from_ls() {
+        assert_eq!(permissions_from_ls("-rwsr-xr-x"), Some(0o4755));
+        assert_eq!(permissions_from_ls("drwxrwxrwt"), Some(0o1777));
+        assert_eq!(permissions_from_ls("-rw-r-Sr--"), Some(0o2644));
+        assert_eq!(permissions_from_ls("garbage"), None);
+    }
+
+    #[test]
+    fn test_normalize_cross_format() {
+        // CPIO: root entry, explicit directories, modes and owners
+        let mut cpio = EntryMap::new();
+        cpio.insert(".".into(), file(0o755));
+        cpio.insert("usr".into(), dir());
+        cpio.insert("usr/bin".into(), dir());
+        cpio.insert("usr/bin/ls".into(), file(0o755));
+        // ISO: files only, nothing but sizes
+        let mut iso = EntryMap::new();
+        iso.insert(
+            "usr/bin/ls".into(),
+            DiffEntry {
+                mode: None,
+                uid: None,
+                gid: None,
+                ..file(0)
+            },
+        );
+
+        assert_eq!(compare(&cpio, &iso).len(), 3);
+        let mut maps = [cpio, iso];
+        normalize_cross_format(&mut maps);
+        assert!(compare(&maps[0], &maps[1]).is_empty());
+        assert_eq!(maps[0]["usr/bin/ls"].mode, None);
+    }
+
+    #[test]
+    fn test_setuid_loss_reported() {
+        let changes = metadata_changes(&file(0o4755), &file(0o0755));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].to_string(), "mode 4755 -> 0755 (setuid removed)");
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut old = EntryMap::new();
+        old.insert("usr/bin/su".into(), file(0o4755));
+        old.insert("usr/bin/gone".into(), file(0o755));
+        old.insert(
+            "usr/sbin/init".into(),
+            DiffEntry {
+                kind: EntryKind::Symlink,
+                size: 0,
+                mode: Some(0o777),
+                uid: Some(0),
+                gid: Some(0),
+                link_target: Some("../lib/systemd/systemd".into()),
+            },
+        );
+
+        let mut new = old.clone();
+        new.remove("usr/bin/gone");
+        new.insert("usr/bin/new".into(), file(0o755));
+        new.get_mut("usr/bin/su").unwrap().uid = Some(1000);
+        new.get_mut("usr/sbin/init").unwrap().link_target = Some("/bin/busybox".into());
+
+        let changes = compare(&old, &new);
+        let paths: Vec<_> = changes.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["usr/bin/gone", "usr/bin/new", "usr/bin/su", "usr/sbin/init"]
+        );
+        assert_eq!(changes[0].1, Change::Removed);
+        assert_eq!(changes[1].1, Change::Added);
+        assert!(matches!(&changes[3].1, Change::Modified(f) if f[0].field == "target"));
+    }
+    #[test]
+    fn test_compare3_origins() {
+        let mut base = EntryMap::new();
+        base.insert("usr/bin/su".into(), file(0o4755));
+        base.insert("usr/bin/ls".into(), file(0o755));
+        base.insert("usr/bin/vi".into(), file(0o755));
+        base.insert("etc/motd".into(), file(0o644));
+
+        let mut ours = base.clone();
+        ours.get_mut("usr/bin/su").unwrap().mode = Some(0o755);
+        ours.get_mut("usr/bin/vi").unwrap().size = 200;
+        ours.remove("etc/motd");
+
+        let mut theirs = base.clone();
+        theirs.get_mut("usr/bin/ls").unwrap().uid = Some(1000);
+        theirs.get_mut("usr/bin/vi").unwrap().size = 300;
+        theirs.remove("etc/motd");
+
+        let changes = compare3(&base, &ours, &theirs);
+        let origins: Vec<_> = changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.origin))
+            .collect();
+        assert_eq!(
+            origins,
+            vec![
+                ("etc/motd", Origin::Both),
+                ("usr/bin/ls", Origin::Theirs),
+                ("usr/bin/su", Origin::Ours),
+                ("usr/bin/vi", Origin::Conflict),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compose() {
+        let dir = || DiffEntry {
+            kind: EntryKind::Directory,
+            ..file(0o755)
+        };
+        let mut lower = EntryMap::new();
+        for path in ["etc", "etc/skel", "var/cache", "usr/share/doc"] {
+            lower.insert(path.into(), dir());
+        }
+        for path in ["etc/issue", "etc/skel/.bashrc", "usr/bin/ls", "var/cache/a"] {
+            lower.insert(path.into(), file(0o644));
+        }
+        lower.insert("usr/share/doc/README".into(), file(0o644));
+        let mut upper = EntryMap::new();
+        upper.insert("etc".into(), dir());
+        upper.insert("etc/issue".into(), file(0o600));
+        upper.insert("etc/motd".into(), file(0o644));
+        // A file over a lower directory hides what's below it too
+        upper.insert("usr/share/doc".into(), file(0o644));
+        upper.insert("var/cache".into(), dir());
+
+        let old = compose(&lower, &upper, &["etc/skel".into()], &["var/cache".into()]);
+        assert_eq!(
+            old.shadows.keys().collect::<Vec<_>>(),
+            ["etc/issue", "usr/share/doc"]
+        );
+        assert_eq!(old.layers["etc"], Layer::Upper);
+        assert_eq!(old.layers["usr/bin/ls"], Layer::Lower);
+        for gone in [
+            "etc/skel",
+            "etc/skel/.bashrc",
+            "var/cache/a",
+            "usr/share/doc/README",
+        ] {
+            assert!(!old.merged.contains_key(gone), "{}", gone);
+        }
+
+        // The overlay starts shadowing ls; motd moves down into the rootfs
+        let mut upper = upper.clone();
+        upper.insert("usr/bin/ls".into(), file(0o644));
+        upper.remove("etc/motd");
+        lower.insert("etc/motd".into(), file(0o644));
+        let new = compose(&lower, &upper, &["etc/skel".into()], &["var/cache".into()]);
+        assert!(new.shadows.contains_key("usr/bin/ls"));
+        let changes: Vec<_> = compare_layered(&old, &new)
+            .into_iter()
+            .map(|c| (c.path, c.change.is_some(), c.old, c.new))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (
+                    "etc/motd".into(),
+                    false,
+                    Some(Layer::Upper),
+                    Some(Layer::Lower)
+                ),
+                (
+                    "usr/bin/ls".into(),
+                    false,
+                    Some(Layer::Lower),
+                    Some(Layer::Upper)
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Staged directory trees (verify on a directory)
+//!
+//! Builders stage the rootfs and initramfs as directory trees before packing
+//! them, and mkfs.erofs or a CPIO writer over a broken tree is a slow way to
+//! find out it's broken. [`DirReader`] lists a tree with `lstat` - symlinks
+//! are entries, not followed, and nothing needs root - as the entries an
+//! archive of it would hold, so every checklist runs on the tree through
+//! [`CpioReader::from_dir`](crate::cpio::CpioReader::from_dir).
+//!
+//! Ownership and modes are what the tree has on disk. A tree staged without
+//! root is owned by the builder, so checks on owners (home directories,
+//! `etc/shadow`) report what a packer that doesn't map owners would ship.
+
+use crate::cpio::{CpioEntry, FileType, Limits};
+use crate::diff::EntryKind;
+use crate::error::FsdbgError;
+use crate::reader::ArchiveReader;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{self, File, Metadata};
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// A directory tree read as an archive of its contents
+#[derive(Debug)]
+pub struct DirReader {
+    root: PathBuf,
+    entries: Vec<CpioEntry>,
+    entry_map: HashMap<String, usize>,
+}
+
+impl DirReader {
+    /// List the tree below `root`, parents before their contents and
+    /// siblings in name order.
+    pub fn open(root: &Path) -> Result<Self, FsdbgError> {
+        let metadata = fs::metadata(root).map_err(|e| FsdbgError::from(e).with_path(root))?;
+        if !metadata.is_dir() {
+            return Err(FsdbgError::invalid_format("Not a directory").with_path(root));
+        }
+        let mut reader = Self {
+            root: root.to_path_buf(),
+            entries: Vec::new(),
+            entry_map: HashMap::new(),
+        };
+        reader.collect(&mut Vec::new())?;
+        Ok(reader)
+    }
+
+    /// Add the entries below the directory at `raw` (relative name bytes).
+    fn collect(&mut self, raw: &mut Vec<u8>) -> Result<(), FsdbgError> {
+        let dir = self.root.join(OsStr::from_bytes(raw));
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .and_then(|entries| entries.map(|e| e.map(|e| e.file_name())).collect())
+            .map_err(|e: std::io::Error| FsdbgError::from(e).with_path(&dir))?;
+        names.sort();
+
+        for name in names {
+            let len = raw.len();
+            if !raw.is_empty() {
+                raw.push(b'/');
+            }
+            raw.extend_from_slice(name.as_bytes());
+            let path = self.root.join(OsStr::from_bytes(raw));
+            let metadata =
+                fs::symlink_metadata(&path).map_err(|e| FsdbgError::from(e).with_path(&path))?;
+            let link_target = if metadata.file_type().is_symlink() {
+                let target =
+                    fs::read_link(&path).map_err(|e| FsdbgError::from(e).with_path(&path))?;
+                Some(crate::names::escape_bytes(target.as_os_str().as_bytes()))
+            } else {
+                None
+            };
+            let entry = entry(raw.clone(), &metadata, link_target);
+            self.entry_map
+                .insert(entry.path.clone(), self.entries.len());
+            self.entries.push(entry);
+            if metadata.is_dir() {
+                self.collect(raw)?;
+            }
+            raw.truncate(len);
+        }
+        Ok(())
+    }
+
+    /// Take owners from `owners` (by path) over the ones on disk, for a
+    /// tree unpacked without root from an archive that records them.
+    pub fn with_owners(mut self, owners: &HashMap<String, (u32, u32)>) -> Self {
+        for entry in &mut self.entries {
+            if let Some(&(uid, gid)) = owners.get(&entry.path) {
+                (entry.uid, entry.gid) = (uid, gid);
+            }
+        }
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn entries(&self) -> &[CpioEntry] {
+        &self.entries
+    }
+
+    /// Entry stored under exactly `path`, symlinks not followed.
+    pub fn get(&self, path: &str) -> Option<&CpioEntry> {
+        let path = crate::cpio::CpioReader::normalize_path(path);
+        self.entry_map
+            .get(path.trim_end_matches('/'))
+            .map(|&i| &self.entries[i])
+    }
+
+    /// Read the regular file `entry` (one of [`entries`](Self::entries))
+    /// was listed from.
+    pub fn read(&self, entry: &CpioEntry, limits: &Limits) -> Result<Vec<u8>, FsdbgError> {
+        read_entry(&self.root, entry, limits)
+    }
+
+    /// Split into the root and the entries with their path index.
+    pub(crate) fn into_parts(self) -> (PathBuf, Vec<CpioEntry>, HashMap<String, usize>) {
+        (self.root, self.entries, self.entry_map)
+    }
+}
+
+/// Read the file `entry` was listed from below `root`. A file replaced by a
+/// symlink since is refused rather than followed out of the tree.
+pub(crate) fn read_entry(
+    root: &Path,
+    entry: &CpioEntry,
+    limits: &Limits,
+) -> Result<Vec<u8>, FsdbgError> {
+    let path = root.join(OsStr::from_bytes(&entry.raw_path));
+    let locate = |e: std::io::Error| FsdbgError::from(e).with_path(&path);
+    let file = File::open(&path).map_err(locate)?;
+    // Whatever was opened has to be what lstat sees at the path: a symlink
+    // there now, or a different file, means the tree changed underneath
+    let opened = file.metadata().map_err(locate)?;
+    let listed = fs::symlink_metadata(&path).map_err(locate)?;
+    if listed.file_type().is_symlink()
+        || (listed.dev(), listed.ino()) != (opened.dev(), opened.ino())
+    {
+        return Err(locate(std::io::Error::other(
+            "replaced by a symlink since it was listed",
+        )));
+    }
+    let mut content = Vec::new();
+    file.take(limits.max_entry.saturating_add(1))
+        .read_to_end(&mut content)
+        .map_err(locate)?;
+    if content.len() as u64 > limits.max_entry {
+        return Err(FsdbgError::limit_exceeded(format!(
+            "{} exceeds the {}-byte entry limit (--max-entry-size)",
+            entry.path, limits.max_entry
+        ))
+        .with_path(root));
+    }
+    Ok(content)
+}
+
+/// Major number of a Linux `dev_t`, as glibc's `major()` decodes it.
+fn major(dev: u64) -> u32 {
+    (((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0xfff)) as u32
+}
+
+/// Minor number of a Linux `dev_t`, as glibc's `minor()` decodes it.
+fn minor(dev: u64) -> u32 {
+    (((dev >> 12) & 0xffff_ff00) | (dev & 0xff)) as u32
+}
+
+/// The archive entry for a tree entry named `raw`.
+fn entry(raw: Vec<u8>, metadata: &Metadata, link_target: Option<String>) -> CpioEntry {
+    let file_type = FileType::from_mode(metadata.mode());
+    let size = match (file_type, &link_target) {
+        (FileType::Regular, _) => metadata.size(),
+        // As archivers store them: the target is the content
+        (FileType::Symlink, Some(target)) => target.len() as u64,
+        _ => 0,
+    };
+    let (dev, rdev) = (metadata.dev(), metadata.rdev());
+    CpioEntry {
+        path: crate::names::escape_bytes(&raw),
+        raw_path: raw,
+        size,
+        mode: metadata.mode(),
+        file_type,
+        link_target,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        nlink: metadata.nlink() as u32,
+        // newc stores 32 bits, as archiving the tree would
+        mtime: metadata.mtime().clamp(0, u32::MAX as i64) as u32,
+        dev_major: major(dev),
+        dev_minor: minor(dev),
+        rdev_major: major(rdev),
+        rdev_minor: minor(rdev),
+        digest: None,
+    }
+}
+
+impl ArchiveReader for DirReader {
+    fn entry_kind(&self, path: &str) -> Option<EntryKind> {
+        self.get(path).map(|e| match e.file_type {
+            FileType::Regular => EntryKind::File,
+            FileType::Directory => EntryKind::Directory,
+            FileType::Symlink => EntryKind::Symlink,
+            _ => EntryKind::Other,
+        })
+    }
+
+    fn link_target(&self, path: &str) -> Option<&str> {
+        self.get(path)
+            .filter(|e| e.is_symlink())
+            .and_then(|e| e.link_target.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpio::CpioReader;
+
+    #[test]
+    fn test_dir_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::write(root.join("usr/bin/sh"), b"#!").unwrap();
+        std::os::unix::fs::symlink("usr/bin", root.join("bin")).unwrap();
+        std::os::unix::fs::symlink("/etc/passwd", root.join("passwd")).unwrap();
+
+        let reader = DirReader::open(root).unwrap();
+        let paths: Vec<_> = reader.entries().iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["bin", "passwd", "usr", "usr/bin", "usr/bin/sh"]);
+        assert_eq!(reader.resolve("bin/sh").as_deref(), Some("usr/bin/sh"));
+        assert_eq!(reader.get("bin").unwrap().size, 7);
+
+        // Reads stay in the tree: an absolute link is the tree's, not the host's
+        let cpio = CpioReader::from_dir(reader).with_symlink_resolution();
+        assert_eq!(cpio.read_file("bin/sh").unwrap(), b"#!");
+        assert!(cpio.read_file("passwd").is_err());
+        assert_eq!(cpio.source_path(), root);
+        assert!(DirReader::open(&root.join("usr/bin/sh")).is_err());
+
+        // makedev(8, 3) and makedev(259, 0x12345)
+        assert_eq!((major(0x803), minor(0x803)), (8, 3));
+        assert_eq!((major(0x1231_0345), minor(0x1231_0345)), (259, 0x12345));
+    }
+}
@@ -0,0 +1,411 @@
+//! Archive structural health checks
+//!
+//! The readers are deliberately forgiving: they stop at the first trailer,
+//! ignore padding contents and trust header sizes. The kernel and
+//! bootloaders are not always as forgiving, so a subtly malformed archive
+//! can parse fine here and still hang the boot. `fsdbg doctor` walks the
+//! raw structure and reports every anomaly:
+//!
+//! - CPIO: header alignment, non-zero padding, unterminated names, missing
+//!   trailer, data after the trailer
+//! - gzip: stream integrity (CRC32 and length trailer), trailing data
+//! - ISO 9660: volume descriptor chain, block size, truncation
+//! - EROFS: superblock fields and checksum, truncation
+
+use crate::checklist::{CheckCategory, CheckResult, VerificationReport};
+use crate::cpio::{self, Segment};
+use crate::error::FsdbgError;
+use crate::{ArchiveFormat, ByteRange};
+use flate2::bufread::GzDecoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Run the structural checks for the archive's format.
+pub fn examine(path: &Path) -> Result<VerificationReport, FsdbgError> {
+    let mut report = VerificationReport::new("Archive health");
+    match crate::detect_format(path)? {
+        ArchiveFormat::Cpio | ArchiveFormat::CpioGzip => {
+            for (i, segment) in cpio::segments(path)?.iter().enumerate() {
+                check_segment(path, i, segment, &mut report)?;
+            }
+        }
+        ArchiveFormat::Iso => check_iso(path, &mut report)?,
+        ArchiveFormat::Erofs => check_erofs(path, &mut report)?,
+    }
+    Ok(report)
+}
+
+fn result(item: String, problems: Vec<String>) -> CheckResult {
+    if problems.is_empty() {
+        CheckResult::pass(item, CheckCategory::Other)
+    } else {
+        CheckResult::fail(item, CheckCategory::Other, problems.join("; "))
+    }
+}
+
+// =============================================================================
+// CPIO / gzip
+// =============================================================================
+
+fn check_segment(
+    path: &Path,
+    index: usize,
+    segment: &Segment,
+    report: &mut VerificationReport,
+) -> Result<(), FsdbgError> {
+    let label = format!("archive {} at {:#x}", index + 1, segment.range.offset);
+    let stream = BufReader::new(segment.range.open(path)?);
+
+    if !segment.compressed {
+        report.add(check_newc(stream, &label));
+        return Ok(());
+    }
+
+    let mut magic = [0u8; 2];
+    segment.range.open(path)?.read_exact(&mut magic)?;
+    if magic != [0x1f, 0x8b] {
+        report.add(CheckResult::fail(
+            format!("{}: compression", label),
+            CheckCategory::Other,
+            "Not gzip; only gzip-compressed archives can be checked",
+        ));
+        return Ok(());
+    }
+
+    let mut decoder = GzDecoder::new(stream);
+    let mut decompressed = Vec::new();
+    match decoder.read_to_end(&mut decompressed) {
+        Ok(_) => {
+            // The decoder checked CRC32 and ISIZE; anything left in the
+            // range is outside the gzip member
+            let mut rest = decoder.into_inner();
+            let trailing = count_nonzero(&mut rest)?;
+            let problems = match trailing {
+                0 => vec![],
+                n => vec![format!(
+                    "{} bytes of non-zero data after the gzip stream (the kernel unpacks them as another archive)",
+                    n
+                )],
+            };
+            report.add(result(format!("{}: gzip trailer", label), problems));
+            report.add(check_newc(&decompressed[..], &label));
+        }
+        Err(e) => report.add(CheckResult::fail(
+            format!("{}: gzip trailer", label),
+            CheckCategory::Other,
+            format!("Corrupt gzip stream: {}", e),
+        )),
+    }
+    Ok(())
+}
+
+/// Non-zero bytes left in a stream.
+fn count_nonzero(reader: &mut impl BufRead) -> Result<u64, FsdbgError> {
+    let mut count = 0;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(count);
+        }
+        count += buf.iter().filter(|b| **b != 0).count() as u64;
+        let len = buf.len();
+        reader.consume(len);
+    }
+}
+
+/// Walk newc headers byte by byte, checking what the reader skips over.
+fn check_newc(mut reader: impl Read, label: &str) -> CheckResult {
+    let mut problems = Vec::new();
+    let mut offset: u64 = 0;
+    let mut trailer = false;
+
+    let mut read = |buf: &mut [u8], offset: &mut u64| -> io::Result<()> {
+        reader.read_exact(buf)?;
+        *offset += buf.len() as u64;
+        Ok(())
+    };
+
+    loop {
+        let mut header = [0u8; 110];
+        let start = offset;
+        if read(&mut header, &mut offset).is_err() {
+            break;
+        }
+        if !start.is_multiple_of(4) {
+            problems.push(format!("Header at {:#x} is not 4-byte aligned", start));
+        }
+        if &header[0..6] != b"070701" && &header[0..6] != b"070702" {
+            problems.push(format!("Bad magic at {:#x}", start));
+            break;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            std::str::from_utf8(&header[range])
+                .ok()
+                .and_then(|s| u32::from_str_radix(s, 16).ok())
+        };
+        let (Some(filesize), Some(namesize)) = (field(54..62), field(94..102)) else {
+            problems.push(format!("Non-hex header field at {:#x}", start));
+            break;
+        };
+
+        let mut name = vec![0u8; namesize as usize];
+        if read(&mut name, &mut offset).is_err() {
+            problems.push(format!("Truncated name at {:#x}", start));
+            break;
+        }
+        let display = crate::names::escape_bytes(name.strip_suffix(&[0]).unwrap_or(&name));
+        if name.last() != Some(&0) {
+            problems.push(format!("{}: name is not NUL-terminated", display));
+        }
+
+        let mut padding = vec![0u8; pad4(offset)];
+        if read(&mut padding, &mut offset).is_err() {
+            problems.push(format!("{}: truncated name padding", display));
+            break;
+        }
+        if padding.iter().any(|b| *b != 0) {
+            problems.push(format!("{}: non-zero name padding", display));
+        }
+
+        if display == "TRAILER!!!" {
+            trailer = true;
+            break;
+        }
+
+        let mut content = vec![0u8; filesize as usize];
+        if read(&mut content, &mut offset).is_err() {
+            problems.push(format!("{}: truncated content", display));
+            break;
+        }
+        let mut padding = vec![0u8; pad4(offset)];
+        if read(&mut padding, &mut offset).is_err() {
+            problems.push(format!("{}: truncated content padding", display));
+            break;
+        }
+        if padding.iter().any(|b| *b != 0) {
+            problems.push(format!("{}: non-zero content padding", display));
+        }
+    }
+
+    if !trailer {
+        problems.push("No TRAILER!!! entry (archive truncated?)".to_string());
+    } else {
+        let mut rest = Vec::new();
+        let _ = reader.read_to_end(&mut rest);
+        let garbage = rest.iter().filter(|b| **b != 0).count();
+        if garbage > 0 {
+            problems.push(format!(
+                "{} bytes of non-zero data after the trailer",
+                garbage
+            ));
+        }
+    }
+    result(format!("{}: cpio structure", label), problems)
+}
+
+fn pad4(offset: u64) -> usize {
+    ((4 - offset % 4) % 4) as usize
+}
+
+// =============================================================================
+// ISO 9660
+// =============================================================================
+
+/// Sector where the volume descriptor set starts
+const ISO_FIRST_DESCRIPTOR: u64 = 16;
+/// Sanity bound on the descriptor set length
+const ISO_MAX_DESCRIPTORS: u64 = 64;
+
+fn check_iso(path: &Path, report: &mut VerificationReport) -> Result<(), FsdbgError> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut problems = Vec::new();
+    let mut primary = None;
+    let mut terminated = false;
+
+    for sector in ISO_FIRST_DESCRIPTOR..ISO_FIRST_DESCRIPTOR + ISO_MAX_DESCRIPTORS {
+        let mut descriptor = [0u8; 2048];
+        file.seek(SeekFrom::Start(sector * 2048))?;
+        if file.read_exact(&mut descriptor).is_err() {
+            problems.push(format!("Descriptor set truncated at sector {}", sector));
+            break;
+        }
+        if &descriptor[1..6] != b"CD001" || descriptor[6] != 1 {
+            problems.push(format!("Invalid volume descriptor at sector {}", sector));
+            break;
+        }
+        match descriptor[0] {
+            1 if primary.is_none() => primary = Some(descriptor),
+            1 => problems.push(format!("Second primary descriptor at sector {}", sector)),
+            0 | 2 | 3 => {}
+            255 => {
+                terminated = true;
+                break;
+            }
+            other => problems.push(format!(
+                "Unknown descriptor type {} at sector {}",
+                other, sector
+            )),
+        }
+    }
+    if primary.is_none() {
+        problems.push("No primary volume descriptor".to_string());
+    }
+    if !terminated {
+        problems.push("Descriptor set has no terminator".to_string());
+    }
+    report.add(result("iso: descriptor chain".to_string(), problems));
+
+    if let Some(pvd) = primary {
+        let mut problems = Vec::new();
+        // Both-endian fields: the little-endian half comes first
+        let blocks = u32::from_le_bytes([pvd[80], pvd[81], pvd[82], pvd[83]]) as u64;
+        let block_size = u16::from_le_bytes([pvd[128], pvd[129]]) as u64;
+        if block_size != 2048 {
+            problems.push(format!("Logical block size {} (expected 2048)", block_size));
+        }
+        if blocks * block_size > size {
+            problems.push(format!(
+                "Volume space is {} bytes but the image is {} (truncated)",
+                blocks * block_size,
+                size
+            ));
+        }
+        report.add(result("iso: volume size".to_string(), problems));
+    }
+    Ok(())
+}
+
+// =============================================================================
+// EROFS
+// =============================================================================
+
+/// Superblock offset in the image
+const EROFS_SUPER_OFFSET: u64 = 1024;
+/// feature_compat bit: superblock carries a CRC32C
+const EROFS_FEATURE_COMPAT_SB_CHKSUM: u32 = 0x1;
+
+fn check_erofs(path: &Path, report: &mut VerificationReport) -> Result<(), FsdbgError> {
+    let size = std::fs::metadata(path)?.len();
+    let mut sb = Vec::new();
+    ByteRange::new(EROFS_SUPER_OFFSET, None)
+        .open(path)?
+        .take(64 * 1024)
+        .read_to_end(&mut sb)?;
+    sb.resize(sb.len().max(128), 0);
+
+    let le32 = |at: usize| u32::from_le_bytes([sb[at], sb[at + 1], sb[at + 2], sb[at + 3]]);
+    let blkszbits = sb[12];
+    let mut problems = Vec::new();
+
+    if !(9..=16).contains(&blkszbits) {
+        problems.push(format!("Invalid blkszbits {}", blkszbits));
+        report.add(result("erofs: superblock".to_string(), problems));
+        return Ok(());
+    }
+    let block_size = 1u64 << blkszbits;
+    let blocks = le32(36) as u64;
+    if blocks * block_size > size {
+        problems.push(format!(
+            "Superblock claims {} bytes but the image is {} (truncated)",
+            blocks * block_size,
+            size
+        ));
+    }
+    report.add(result("erofs: superblock".to_string(), problems));
+
+    if le32(8) & EROFS_FEATURE_COMPAT_SB_CHKSUM != 0 {
+        // Checksum covers the rest of the first block, with the field zeroed
+        let len = (block_size.saturating_sub(EROFS_SUPER_OFFSET) as usize).min(sb.len());
+        let expected = le32(4);
+        let mut covered = sb[..len].to_vec();
+        covered[4..8].fill(0);
+        let actual = crc32c(!0, &covered);
+        let problems = match actual == expected {
+            true => vec![],
+            false => vec![format!(
+                "Checksum {:#010x} does not match stored {:#010x}",
+                actual, expected
+            )],
+        };
+        report.add(result("erofs: superblock checksum".to_string(), problems));
+    }
+    Ok(())
+}
+
+/// CRC32C (Castagnoli) without final inversion, matching the kernel's
+/// `crc32c()` as EROFS uses it.
+fn crc32c(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn newc_entry(name: &str, content: &[u8]) -> Vec<u8> {
+        let mut out = format!(
+            "070701{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}",
+            0,
+            0o100644,
+            0,
+            0,
+            1,
+            0,
+            content.len(),
+            0,
+            0,
+            0,
+            0,
+            name.len() + 1,
+            0
+        )
+        .into_bytes();
+        out.extend_from_slice(name.as_bytes());
+        out.push(0);
+        out.resize(out.len().next_multiple_of(4), 0);
+        out.extend_from_slice(content);
+        out.resize(out.len().next_multiple_of(4), 0);
+        out
+    }
+
+    #[test]
+    fn test_check_newc() {
+        let mut archive = newc_entry("init", b"#!/bin/sh\n");
+        archive.extend(newc_entry("TRAILER!!!", b""));
+        assert!(check_newc(&archive[..], "a").passed);
+
+        // Garbage in the padding after "init" (110 + 5 = 115, 1 byte pad)
+        let mut bad = archive.clone();
+        bad[115] = 0xaa;
+        let result = check_newc(&bad[..], "a");
+        assert_eq!(
+            result.message.as_deref(),
+            Some("init: non-zero name padding")
+        );
+
+        let truncated = newc_entry("init", b"x");
+        let result = check_newc(&truncated[..], "a");
+        assert_eq!(
+            result.message.as_deref(),
+            Some("No TRAILER!!! entry (archive truncated?)")
+        );
+    }
+
+    #[test]
+    fn test_crc32c() {
+        // RFC 3720 check value (with the standard final inversion)
+        assert_eq!(!crc32c(!0, b"123456789"), 0xe306_9283);
+    }
+}
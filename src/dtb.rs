@@ -0,0 +1,211 @@
+//! Flattened device tree (DTB) inspection
+//!
+//! aarch64 boards without UEFI+ACPI firmware learn their hardware from a
+//! device tree blob the bootloader loads next to the kernel. The root node's
+//! `model` and `compatible` properties say which board a blob is for; they
+//! are read natively from the FDT structure block - no dtc required.
+
+use crate::error::FsdbgError;
+
+/// `0xd00dfeed`, big-endian
+pub const MAGIC: [u8; 4] = [0xd0, 0x0d, 0xfe, 0xed];
+
+/// Structure block tokens
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// Header fields and root node properties of a device tree blob
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceTree {
+    /// FDT format version (17 for current dtc output)
+    pub version: u32,
+    /// Size of the blob as its header records it
+    pub size: u32,
+    pub model: Option<String>,
+    /// Most specific first, as the root node lists them
+    pub compatible: Vec<String>,
+}
+
+fn truncated() -> FsdbgError {
+    FsdbgError::invalid_format("Device tree is truncated")
+}
+
+fn be32(data: &[u8], offset: usize) -> Result<u32, FsdbgError> {
+    offset
+        .checked_add(4)
+        .and_then(|end| data.get(offset..end))
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().expect("4 bytes")))
+        .ok_or_else(truncated)
+}
+
+/// Check for the FDT magic.
+pub fn is_dtb(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+impl DeviceTree {
+    /// Parse the header and the root node's properties.
+    pub fn parse(data: &[u8]) -> Result<Self, FsdbgError> {
+        if !is_dtb(data) {
+            return Err(FsdbgError::invalid_format(
+                "Not a device tree blob (no d00dfeed magic)",
+            ));
+        }
+        let size = be32(data, 4)?;
+        let data = data.get(..size as usize).ok_or_else(truncated)?;
+        let structs = be32(data, 8)? as usize;
+        let strings = be32(data, 12)? as usize;
+        let version = be32(data, 20)?;
+
+        let mut tree = Self {
+            version,
+            size,
+            model: None,
+            compatible: Vec::new(),
+        };
+        let mut offset = structs;
+        let mut depth = 0;
+        loop {
+            let token = be32(data, offset)?;
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    // Properties come before subnodes: the root's are done
+                    if depth == 1 {
+                        break;
+                    }
+                    depth += 1;
+                    let name = cstr(data, offset)?;
+                    offset += (name.len() + 1).next_multiple_of(4);
+                }
+                FDT_PROP => {
+                    let len = be32(data, offset)? as usize;
+                    let name_offset = be32(data, offset + 4)? as usize;
+                    offset += 8;
+                    let value = offset
+                        .checked_add(len)
+                        .and_then(|end| data.get(offset..end))
+                        .ok_or_else(truncated)?;
+                    offset += len.next_multiple_of(4);
+                    let name = strings
+                        .checked_add(name_offset)
+                        .ok_or_else(truncated)
+                        .and_then(|at| cstr(data, at))?;
+                    match name {
+                        b"model" => tree.model = strings_of(value).next(),
+                        b"compatible" => tree.compatible = strings_of(value).collect(),
+                        _ => {}
+                    }
+                }
+                FDT_NOP => {}
+                FDT_END_NODE | FDT_END => break,
+                token => {
+                    return Err(FsdbgError::invalid_format(format!(
+                        "Unknown device tree token {:#x} at offset {:#x}",
+                        token,
+                        offset - 4
+                    )))
+                }
+            }
+        }
+        Ok(tree)
+    }
+}
+
+/// NUL-terminated bytes at `offset`, without the NUL.
+fn cstr(data: &[u8], offset: usize) -> Result<&[u8], FsdbgError> {
+    let rest = data.get(offset..).ok_or_else(truncated)?;
+    let end = rest.iter().position(|b| *b == 0).ok_or_else(truncated)?;
+    Ok(&rest[..end])
+}
+
+/// The strings of a string-list property (`"a\0b\0"`).
+fn strings_of(value: &[u8]) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(|b| *b == 0)
+        .filter(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A blob with a root node holding `model` and `compatible`, and a
+    /// `cpus` subnode whose properties must not be read as the root's.
+    fn blob(model: &str, compatible: &[&str]) -> Vec<u8> {
+        let mut strings = Vec::new();
+        let mut offsets = Vec::new();
+        for name in ["model", "compatible"] {
+            offsets.push(strings.len() as u32);
+            strings.extend_from_slice(name.as_bytes());
+            strings.push(0);
+        }
+        let mut structs = Vec::new();
+        let word = |out: &mut Vec<u8>, value: u32| out.extend_from_slice(&value.to_be_bytes());
+        let prop = |out: &mut Vec<u8>, name: u32, value: &[u8]| {
+            word(out, FDT_PROP);
+            word(out, value.len() as u32);
+            word(out, name);
+            out.extend_from_slice(value);
+            out.resize(out.len().next_multiple_of(4), 0);
+        };
+        word(&mut structs, FDT_BEGIN_NODE);
+        word(&mut structs, 0);
+        prop(&mut structs, offsets[0], format!("{}\0", model).as_bytes());
+        let list: String = compatible.iter().map(|c| format!("{}\0", c)).collect();
+        prop(&mut structs, offsets[1], list.as_bytes());
+        word(&mut structs, FDT_BEGIN_NODE);
+        structs.extend_from_slice(b"cpus\0\0\0\0");
+        prop(&mut structs, offsets[0], b"not the board\0");
+        word(&mut structs, FDT_END_NODE);
+        word(&mut structs, FDT_END_NODE);
+        word(&mut structs, FDT_END);
+
+        let structs_at = 40;
+        let strings_at = structs_at + structs.len();
+        let size = strings_at + strings.len();
+        let mut out = MAGIC.to_vec();
+        for field in [
+            size,
+            structs_at,
+            strings_at,
+            40,
+            17,
+            16,
+            0,
+            strings.len(),
+            structs.len(),
+        ] {
+            out.extend_from_slice(&(field as u32).to_be_bytes());
+        }
+        out.extend_from_slice(&structs);
+        out.extend_from_slice(&strings);
+        out
+    }
+
+    #[test]
+    fn test_parse() {
+        let data = blob(
+            "Raspberry Pi 4 Model B",
+            &["raspberrypi,4-model-b", "brcm,bcm2711"],
+        );
+        let tree = DeviceTree::parse(&data).unwrap();
+        assert_eq!(tree.model.as_deref(), Some("Raspberry Pi 4 Model B"));
+        assert_eq!(tree.compatible, ["raspberrypi,4-model-b", "brcm,bcm2711"]);
+        assert_eq!((tree.version, tree.size as usize), (17, data.len()));
+
+        // Cut anywhere, it errors rather than panicking
+        for len in [4, 12, 44, data.len() - 1] {
+            let mut cut = data[..len].to_vec();
+            if len > 8 {
+                cut[4..8].copy_from_slice(&(len as u32).to_be_bytes());
+            }
+            assert!(DeviceTree::parse(&cut).is_err(), "{}", len);
+        }
+        assert!(DeviceTree::parse(b"\x7fELF").is_err());
+    }
+}
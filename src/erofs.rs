@@ -16,6 +16,15 @@ pub struct ErofsEntry {
     pub is_symlink: bool,
     pub link_target: Option<String>,
     pub mode: String,
+    /// Device numbers (major, minor) of device nodes
+    pub rdev: Option<(u32, u32)>,
+}
+
+impl ErofsEntry {
+    /// Check if this is an overlayfs whiteout (`c 0:0`)
+    pub fn is_whiteout(&self) -> bool {
+        self.mode.starts_with('c') && self.rdev == Some((0, 0))
+    }
 }
 
 /// EROFS filesystem inspector
@@ -50,9 +59,9 @@ impl ErofsReader {
             if fsck_available {
                 Self::parse_with_fsck_erofs(path)
             } else {
-                Err(FsdbgError::external_tool_failed(
-                    "erofs-utils",
-                    "Neither dump.erofs nor fsck.erofs found. Install erofs-utils.",
+                Err(FsdbgError::tool_missing(
+                    "dump.erofs (or fsck.erofs)",
+                    "Install erofs-utils.",
                 ))
             }
         }
@@ -72,7 +81,8 @@ impl ErofsReader {
             return Err(FsdbgError::external_tool_failed("dump.erofs", stderr));
         }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
+        // Escaped rather than lossy, so non-UTF-8 names survive
+        let stdout = crate::names::escape_bytes(&output.stdout);
         let entries = Self::parse_dump_output(&stdout)?;
 
         Ok(Self { entries })
@@ -126,8 +136,18 @@ impl ErofsReader {
                 let is_dir = mode.starts_with('d');
                 let is_symlink = mode.starts_with('l');
 
+                // Device nodes show "major, minor" where the size would be,
+                // which pushes the rest along by one
+                let rdev = match (parts[4].strip_suffix(','), parts.get(5)) {
+                    (Some(major), Some(minor)) if parts.len() >= 10 => {
+                        major.parse().ok().zip(minor.parse().ok())
+                    }
+                    _ => None,
+                };
+                let skip = if rdev.is_some() { 1 } else { 0 };
+
                 // Find path (everything after the date/time)
-                let path_start = parts[8..].join(" ");
+                let path_start = parts[8 + skip..].join(" ");
                 let (path, link_target) = if is_symlink && path_start.contains(" -> ") {
                     let mut split = path_start.splitn(2, " -> ");
                     (
@@ -138,7 +158,11 @@ impl ErofsReader {
                     (path_start, None)
                 };
 
-                let size = parts[4].parse().unwrap_or(0);
+                let size = if rdev.is_some() {
+                    0
+                } else {
+                    parts[4].parse().unwrap_or(0)
+                };
 
                 entries.push(ErofsEntry {
                     path,
@@ -147,6 +171,7 @@ impl ErofsReader {
                     is_symlink,
                     link_target,
                     mode,
+                    rdev,
                 });
             } else if !parts.is_empty() {
                 // Just a path
@@ -157,6 +182,7 @@ impl ErofsReader {
                     is_symlink: false,
                     link_target: None,
                     mode: String::new(),
+                    rdev: None,
                 });
             }
         }
@@ -208,50 +234,23 @@ pub struct ErofsStats {
     pub total_size: u64,
 }
 
-/// Get EROFS filesystem information using dump.erofs
-pub fn get_erofs_info(path: &Path) -> Result<ErofsInfo, FsdbgError> {
-    if !path.exists() {
-        return Err(FsdbgError::file_not_found(path));
-    }
-
-    let output = Command::new("dump.erofs")
-        .arg(path)
-        .output()
-        .map_err(|e| FsdbgError::external_tool_failed("dump.erofs", e.to_string()))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(FsdbgError::external_tool_failed("dump.erofs", stderr));
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_erofs_info(&stdout)
-}
-
-fn parse_erofs_info(output: &str) -> Result<ErofsInfo, FsdbgError> {
-    let mut info = ErofsInfo::default();
-
-    for line in output.lines() {
-        if line.contains("Filesystem UUID:") {
-            info.uuid = line.split(':').nth(1).map(|s| s.trim().to_string());
-        } else if line.contains("Filesystem total blocks:") {
-            if let Some(blocks) = line.split(':').nth(1) {
-                info.total_blocks = blocks.trim().parse().unwrap_or(0);
-            }
-        } else if line.contains("Filesystem inode count:") {
-            if let Some(count) = line.split(':').nth(1) {
-                info.inode_count = count.trim().parse().unwrap_or(0);
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_device_lines() {
+        let entries = ErofsReader::parse_dump_output(
+            "crw-r--r--   1 root root    0, 0 Jan  1 00:00 etc/motd\n\
+             -rw-r--r--   1 root root      12 Jan  1 00:00 etc/issue\n",
+        )
+        .unwrap();
+        assert_eq!(entries[0].path, "etc/motd");
+        assert!(entries[0].is_whiteout());
+        assert_eq!(
+            (entries[1].path.as_str(), entries[1].size),
+            ("etc/issue", 12)
+        );
+        assert!(!entries[1].is_whiteout());
     }
-
-    Ok(info)
-}
-
-/// EROFS filesystem information
-#[derive(Debug, Default)]
-pub struct ErofsInfo {
-    pub uuid: Option<String>,
-    pub total_blocks: u64,
-    pub inode_count: u64,
 }
@@ -1,10 +1,15 @@
-//! Error types for fsdbg
+//! Error types for fsdbg, and the exit codes they map to
+//!
+//! [`FsdbgError`] has a variant per kind of failure for library users to
+//! match on. Each carries a [`Context`] (what fsdbg was doing, the file and
+//! byte offset involved, the external tool) and the underlying error.
 
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Error codes for structured error reporting
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ErrorCode {
     /// E001: File not found
     FileNotFound = 1,
@@ -26,6 +31,72 @@ pub enum ErrorCode {
     UnsupportedFormat = 9,
     /// E010: Invalid argument
     InvalidArgument = 10,
+    /// E011: External tool not installed
+    ToolMissing = 11,
+    /// E012: Input exceeds a resource limit (entry size, memory)
+    LimitExceeded = 12,
+}
+
+/// Process exit codes and what they mean (`fsdbg explain-exit`)
+pub const EXIT_CODES: &[(u8, &str)] = &[
+    (0, "Success: the artifact passed, or the command completed"),
+    (
+        1,
+        "Verification failed: the checklist verdict; the report lists what is wrong with the artifact",
+    ),
+    (
+        2,
+        "Usage or I/O error: bad arguments, an input that can't be read or an output that can't be written, or a file the command needs is missing",
+    ),
+    (
+        3,
+        "Tool missing: an external tool fsdbg needs (isoinfo, dump.erofs, gpg) isn't installed",
+    ),
+    (
+        4,
+        "Unsupported format: the input isn't a format this command handles",
+    ),
+    (
+        5,
+        "Parse error: the input is malformed or damaged (a bad header, a dangling link) or exceeds a resource limit, or an external tool failed on it",
+    ),
+];
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 12] = [
+        ErrorCode::FileNotFound,
+        ErrorCode::InvalidFormat,
+        ErrorCode::SymlinkBroken,
+        ErrorCode::MissingRequired,
+        ErrorCode::IoError,
+        ErrorCode::ExternalToolFailed,
+        ErrorCode::ParseError,
+        ErrorCode::VerificationFailed,
+        ErrorCode::UnsupportedFormat,
+        ErrorCode::InvalidArgument,
+        ErrorCode::ToolMissing,
+        ErrorCode::LimitExceeded,
+    ];
+
+    /// Exit code a command failing with this error returns (see `EXIT_CODES`).
+    /// Only a verdict exits 1: an error that stops a check from running isn't
+    /// one, even when it names a missing file or a broken link.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorCode::VerificationFailed => 1,
+            ErrorCode::FileNotFound
+            | ErrorCode::IoError
+            | ErrorCode::InvalidArgument
+            | ErrorCode::MissingRequired => 2,
+            ErrorCode::ToolMissing => 3,
+            ErrorCode::UnsupportedFormat => 4,
+            ErrorCode::InvalidFormat
+            | ErrorCode::SymlinkBroken
+            | ErrorCode::ParseError
+            | ErrorCode::ExternalToolFailed
+            | ErrorCode::LimitExceeded => 5,
+        }
+    }
 }
 
 impl fmt::Display for ErrorCode {
@@ -41,29 +112,237 @@ impl fmt::Display for ErrorCode {
             ErrorCode::VerificationFailed => write!(f, "E008"),
             ErrorCode::UnsupportedFormat => write!(f, "E009"),
             ErrorCode::InvalidArgument => write!(f, "E010"),
+            ErrorCode::ToolMissing => write!(f, "E011"),
+            ErrorCode::LimitExceeded => write!(f, "E012"),
         }
     }
 }
 
-/// Main error type for fsdbg
-#[derive(Debug)]
-pub struct FsdbgError {
-    pub code: ErrorCode,
+/// An error's underlying cause
+pub type Source = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// What an error is about, besides its kind
+#[derive(Debug, Default)]
+pub struct Context {
     pub message: String,
+    /// What fsdbg was doing, e.g. "listing the ISO"
+    pub operation: Option<String>,
     pub path: Option<PathBuf>,
+    /// Byte offset in the input where it is malformed (in the decompressed
+    /// stream, for a compressed archive)
+    pub offset: Option<u64>,
+    /// External tool that is missing or failed
+    pub tool: Option<String>,
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref operation) = self.operation {
+            write!(f, "{}: ", operation)?;
+        }
+        write!(f, "{}", self.message)?;
+        match (&self.path, self.offset) {
+            (Some(path), Some(offset)) => write!(f, " ({} at byte {})", path.display(), offset),
+            (Some(path), None) => write!(f, " ({})", path.display()),
+            (None, Some(offset)) => write!(f, " (at byte {})", offset),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Main error type for fsdbg: one variant per [`ErrorCode`], each with its
+/// `Context` and the error that caused it, if any. The message says all
+/// there is to say; the source is there to be matched on (an I/O error's
+/// kind, say), not printed after it.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum FsdbgError {
+    #[error("[{}] {context}", ErrorCode::FileNotFound)]
+    FileNotFound {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::InvalidFormat)]
+    InvalidFormat {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::SymlinkBroken)]
+    SymlinkBroken {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::MissingRequired)]
+    MissingRequired {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::IoError)]
+    Io {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::ExternalToolFailed)]
+    ExternalToolFailed {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::ParseError)]
+    Parse {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::VerificationFailed)]
+    VerificationFailed {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::UnsupportedFormat)]
+    UnsupportedFormat {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::InvalidArgument)]
+    InvalidArgument {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::ToolMissing)]
+    ToolMissing {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("[{}] {context}", ErrorCode::LimitExceeded)]
+    LimitExceeded {
+        context: Box<Context>,
+        #[source]
+        source: Option<Source>,
+    },
 }
 
 impl FsdbgError {
     pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
-        Self {
-            code,
+        let context = Box::new(Context {
             message: message.into(),
-            path: None,
+            ..Default::default()
+        });
+        let source = None;
+        match code {
+            ErrorCode::FileNotFound => Self::FileNotFound { context, source },
+            ErrorCode::InvalidFormat => Self::InvalidFormat { context, source },
+            ErrorCode::SymlinkBroken => Self::SymlinkBroken { context, source },
+            ErrorCode::MissingRequired => Self::MissingRequired { context, source },
+            ErrorCode::IoError => Self::Io { context, source },
+            ErrorCode::ExternalToolFailed => Self::ExternalToolFailed { context, source },
+            ErrorCode::ParseError => Self::Parse { context, source },
+            ErrorCode::VerificationFailed => Self::VerificationFailed { context, source },
+            ErrorCode::UnsupportedFormat => Self::UnsupportedFormat { context, source },
+            ErrorCode::InvalidArgument => Self::InvalidArgument { context, source },
+            ErrorCode::ToolMissing => Self::ToolMissing { context, source },
+            ErrorCode::LimitExceeded => Self::LimitExceeded { context, source },
+        }
+    }
+
+    /// The kind of failure, which decides the exit code.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::FileNotFound { .. } => ErrorCode::FileNotFound,
+            Self::InvalidFormat { .. } => ErrorCode::InvalidFormat,
+            Self::SymlinkBroken { .. } => ErrorCode::SymlinkBroken,
+            Self::MissingRequired { .. } => ErrorCode::MissingRequired,
+            Self::Io { .. } => ErrorCode::IoError,
+            Self::ExternalToolFailed { .. } => ErrorCode::ExternalToolFailed,
+            Self::Parse { .. } => ErrorCode::ParseError,
+            Self::VerificationFailed { .. } => ErrorCode::VerificationFailed,
+            Self::UnsupportedFormat { .. } => ErrorCode::UnsupportedFormat,
+            Self::InvalidArgument { .. } => ErrorCode::InvalidArgument,
+            Self::ToolMissing { .. } => ErrorCode::ToolMissing,
+            Self::LimitExceeded { .. } => ErrorCode::LimitExceeded,
         }
     }
 
+    pub fn context(&self) -> &Context {
+        match self {
+            Self::FileNotFound { context, .. }
+            | Self::InvalidFormat { context, .. }
+            | Self::SymlinkBroken { context, .. }
+            | Self::MissingRequired { context, .. }
+            | Self::Io { context, .. }
+            | Self::ExternalToolFailed { context, .. }
+            | Self::Parse { context, .. }
+            | Self::VerificationFailed { context, .. }
+            | Self::UnsupportedFormat { context, .. }
+            | Self::InvalidArgument { context, .. }
+            | Self::ToolMissing { context, .. }
+            | Self::LimitExceeded { context, .. } => context,
+        }
+    }
+
+    fn parts_mut(&mut self) -> (&mut Context, &mut Option<Source>) {
+        match self {
+            Self::FileNotFound { context, source }
+            | Self::InvalidFormat { context, source }
+            | Self::SymlinkBroken { context, source }
+            | Self::MissingRequired { context, source }
+            | Self::Io { context, source }
+            | Self::ExternalToolFailed { context, source }
+            | Self::Parse { context, source }
+            | Self::VerificationFailed { context, source }
+            | Self::UnsupportedFormat { context, source }
+            | Self::InvalidArgument { context, source }
+            | Self::ToolMissing { context, source }
+            | Self::LimitExceeded { context, source } => (context, source),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.context().message
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.context().path.as_deref()
+    }
+
+    /// Where the input is malformed; see `Context::offset`.
+    pub fn offset(&self) -> Option<u64> {
+        self.context().offset
+    }
+
     pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
-        self.path = Some(path.into());
+        self.parts_mut().0.path = Some(path.into());
+        self
+    }
+
+    /// Say what fsdbg was doing. The innermost operation is kept: it is
+    /// the most specific.
+    pub fn with_operation(mut self, operation: impl Into<String>) -> Self {
+        self.parts_mut()
+            .0
+            .operation
+            .get_or_insert_with(|| operation.into());
+        self
+    }
+
+    /// Record where in the input the error is. The innermost offset is
+    /// kept: an outer one is only where the enclosing record starts.
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.parts_mut().0.offset.get_or_insert(offset);
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<Source>) -> Self {
+        *self.parts_mut().1 = Some(source.into());
         self
     }
 
@@ -95,10 +374,27 @@ impl FsdbgError {
     }
 
     pub fn external_tool_failed(tool: impl Into<String>, message: impl Into<String>) -> Self {
-        Self::new(
+        let tool = tool.into();
+        let mut error = Self::new(
             ErrorCode::ExternalToolFailed,
-            format!("{} failed: {}", tool.into(), message.into()),
-        )
+            format!("{} failed: {}", tool, message.into()),
+        );
+        error.parts_mut().0.tool = Some(tool);
+        error
+    }
+
+    pub fn tool_missing(tool: impl Into<String>, install: impl Into<String>) -> Self {
+        let tool = tool.into();
+        let mut error = Self::new(
+            ErrorCode::ToolMissing,
+            format!("{} not found. {}", tool, install.into()),
+        );
+        error.parts_mut().0.tool = Some(tool);
+        error
+    }
+
+    pub fn limit_exceeded(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::LimitExceeded, message)
     }
 
     pub fn unsupported_format(format: impl Into<String>) -> Self {
@@ -109,20 +405,57 @@ impl FsdbgError {
     }
 }
 
-impl fmt::Display for FsdbgError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[{}] {}", self.code, self.message)?;
-        if let Some(ref path) = self.path {
-            write!(f, " ({})", path.display())?;
-        }
-        Ok(())
+impl From<std::io::Error> for FsdbgError {
+    fn from(err: std::io::Error) -> Self {
+        Self::new(ErrorCode::IoError, err.to_string()).with_source(err)
     }
 }
 
-impl std::error::Error for FsdbgError {}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl From<std::io::Error> for FsdbgError {
-    fn from(err: std::io::Error) -> Self {
-        Self::new(ErrorCode::IoError, err.to_string())
+    #[test]
+    fn test_exit_codes_documented() {
+        for code in ErrorCode::ALL {
+            let exit = code.exit_code();
+            assert!(
+                EXIT_CODES.iter().any(|(c, _)| *c == exit),
+                "{} maps to undocumented exit code {}",
+                code,
+                exit
+            );
+        }
+        // A malformed archive is a parse failure, not a format fsdbg lacks
+        assert_eq!(ErrorCode::InvalidFormat.exit_code(), 5);
+        assert_eq!(ErrorCode::UnsupportedFormat.exit_code(), 4);
+        // Exit code 1 means the checklist ran and said no
+        let verdicts: Vec<_> = ErrorCode::ALL
+            .into_iter()
+            .filter(|code| code.exit_code() == 1)
+            .collect();
+        assert_eq!(verdicts, [ErrorCode::VerificationFailed]);
+    }
+
+    #[test]
+    fn test_context() {
+        let error = FsdbgError::external_tool_failed("isoinfo", "exit status 1")
+            .with_operation("listing the ISO")
+            .with_operation("verifying")
+            .with_path("live.iso");
+        assert!(matches!(error, FsdbgError::ExternalToolFailed { .. }));
+        assert_eq!(error.context().tool.as_deref(), Some("isoinfo"));
+        assert_eq!(
+            error.to_string(),
+            "[E006] listing the ISO: isoinfo failed: exit status 1 (live.iso)"
+        );
+
+        let io = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "early eof");
+        let error = FsdbgError::from(io).with_offset(110).with_offset(0);
+        assert_eq!(error.to_string(), "[E005] early eof (at byte 110)");
+        let source = std::error::Error::source(&error)
+            .and_then(|e| e.downcast_ref::<std::io::Error>())
+            .map(|e| e.kind());
+        assert_eq!(source, Some(std::io::ErrorKind::UnexpectedEof));
     }
 }
@@ -0,0 +1,129 @@
+//! FAT images (the ISO's efiboot.img)
+//!
+//! Files are read through mtools, which takes an image file directly: no
+//! loop device or root needed. Free space is counted natively from the
+//! boot sector and the first FAT.
+
+use crate::error::FsdbgError;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Copy every file of the FAT image at `image` into `dest`.
+pub fn extract(image: &Path, dest: &Path) -> Result<(), FsdbgError> {
+    std::fs::create_dir_all(dest)?;
+    let output = Command::new("mcopy")
+        .args(["-s", "-n", "-i"])
+        .arg(image)
+        .arg("::/*")
+        .arg(dest)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FsdbgError::tool_missing("mcopy", "Install mtools."),
+            _ => FsdbgError::external_tool_failed("mcopy", e.to_string()),
+        })?;
+    // An empty FAT has nothing for the ::/* glob to match
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() && !stderr.contains("not found") {
+        return Err(FsdbgError::external_tool_failed("mcopy", stderr.trim()).with_path(image));
+    }
+    Ok(())
+}
+
+/// Files under `dir`, as paths relative to it, in name order.
+pub fn files(dir: &Path) -> Result<Vec<PathBuf>, FsdbgError> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if let Ok(relative) = path.strip_prefix(dir) {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Bytes in the free clusters of a FAT12/16/32 image.
+pub fn free_bytes(image: &[u8]) -> Result<u64, FsdbgError> {
+    let invalid = || FsdbgError::invalid_format("Not a FAT image (bad BIOS parameter block)");
+    let bytes_per_sector = u64::from(u16_at(image, 0x0b).ok_or_else(invalid)?);
+    let sectors_per_cluster = u64::from(*image.get(0x0d).ok_or_else(invalid)?);
+    let reserved = u64::from(u16_at(image, 0x0e).ok_or_else(invalid)?);
+    let fats = u64::from(*image.get(0x10).ok_or_else(invalid)?);
+    let root_entries = u64::from(u16_at(image, 0x11).ok_or_else(invalid)?);
+    // 16-bit fields are zero when the 32-bit ones are used
+    let total = match u16_at(image, 0x13).ok_or_else(invalid)? {
+        0 => u64::from(u32_at(image, 0x20).ok_or_else(invalid)?),
+        n => u64::from(n),
+    };
+    let fat_sectors = match u16_at(image, 0x16).ok_or_else(invalid)? {
+        0 => u64::from(u32_at(image, 0x24).ok_or_else(invalid)?),
+        n => u64::from(n),
+    };
+    if bytes_per_sector == 0 || sectors_per_cluster == 0 || fats == 0 {
+        return Err(invalid());
+    }
+
+    let root_sectors = (root_entries * 32).div_ceil(bytes_per_sector);
+    let clusters = total
+        .checked_sub(reserved + fats * fat_sectors + root_sectors)
+        .ok_or_else(invalid)?
+        / sectors_per_cluster;
+    let start = (reserved * bytes_per_sector) as usize;
+    let fat = image
+        .get(start..start + (fat_sectors * bytes_per_sector) as usize)
+        .ok_or_else(|| FsdbgError::invalid_format("FAT image is truncated"))?;
+    // The FAT type follows from the cluster count alone
+    let entry = |n: usize| -> Option<u32> {
+        if clusters < 4085 {
+            let pair = u16_at(fat, n + n / 2)?;
+            Some(u32::from(if n % 2 == 1 { pair >> 4 } else { pair & 0xfff }))
+        } else if clusters < 65525 {
+            u16_at(fat, n * 2).map(u32::from)
+        } else {
+            u32_at(fat, n * 4).map(|e| e & 0x0fff_ffff)
+        }
+    };
+    // Data clusters are numbered from 2
+    let free = (2..clusters as usize + 2)
+        .filter(|&n| entry(n) == Some(0))
+        .count() as u64;
+    Ok(free * sectors_per_cluster * bytes_per_sector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_free_bytes() {
+        // FAT12: 1 reserved sector, 2 one-sector FATs, one root directory
+        // sector, 96 one-sector clusters
+        let mut image = vec![0u8; 100 * 512];
+        image[0x0b..0x0d].copy_from_slice(&512u16.to_le_bytes());
+        image[0x0d] = 1;
+        image[0x0e] = 1;
+        image[0x10] = 2;
+        image[0x11] = 16;
+        image[0x13..0x15].copy_from_slice(&100u16.to_le_bytes());
+        image[0x16] = 1;
+        // Media descriptor entries, cluster 2 (end of chain) and 3 (-> 4)
+        image[512..512 + 6].copy_from_slice(&[0xf8, 0xff, 0xff, 0xff, 0x4f, 0x00]);
+        assert_eq!(free_bytes(&image).unwrap(), 94 * 512);
+
+        assert!(free_bytes(&image[..600]).is_err());
+        assert!(free_bytes(&[0u8; 64]).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! Fix-it scripts for failures with a mechanical remedy (--fix-script)
+//!
+//! Some failures say exactly what the tree should contain: a `.wants`
+//! symlink that's missing, a symlink pointing elsewhere, a binary without
+//! its execute bits. Checks attach a [`Fix`] to those, and [`script`] turns
+//! the fixes in a report into a shell script to run against the staged
+//! build tree. It patches the tree, not the build step that produced it -
+//! the point is getting a new artifact to boot without a rebuild per
+//! missing link, not a substitute for fixing the build.
+
+use crate::checklist::VerificationReport;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// What would make a failed check pass, relative to the tree's root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Fix {
+    /// Create `path` as a symlink to `target`, replacing what's there
+    Symlink { path: String, target: String },
+    /// Set the permission bits of `path`
+    Chmod { path: String, mode: u32 },
+}
+
+impl Fix {
+    pub fn symlink(path: impl Into<String>, target: impl Into<String>) -> Self {
+        Fix::Symlink {
+            path: path.into(),
+            target: target.into(),
+        }
+    }
+
+    pub fn chmod(path: impl Into<String>, mode: u32) -> Self {
+        Fix::Chmod {
+            path: path.into(),
+            mode: mode & 0o7777,
+        }
+    }
+
+    /// Enable a unit through the `.wants` symlink at `path`, linked as
+    /// `systemctl enable` would: to the template for instances
+    /// (`serial-getty@ttyS0.service` -> `serial-getty@.service`), relative
+    /// within usr/lib/systemd/system and absolute from etc/systemd/system.
+    pub fn wants(path: &str) -> Self {
+        let path = path.trim_start_matches("./").trim_matches('/');
+        let unit = path.rsplit('/').next().unwrap_or(path);
+        let unit = match (unit.split_once('@'), unit.rsplit_once('.')) {
+            (Some((prefix, _)), Some((_, suffix))) => format!("{}@.{}", prefix, suffix),
+            _ => unit.to_string(),
+        };
+        let target = if path.starts_with("etc/") {
+            format!("/usr/lib/systemd/system/{}", unit)
+        } else {
+            format!("../{}", unit)
+        };
+        Fix::symlink(path, target)
+    }
+
+    /// Directory the fix creates an entry in, which may not exist yet.
+    pub fn parent(&self) -> Option<&str> {
+        match self {
+            Fix::Symlink { path, .. } => path.rsplit_once('/').map(|(parent, _)| parent),
+            Fix::Chmod { .. } => None,
+        }
+    }
+
+    /// Shell command applying the fix, run from the tree's root.
+    pub fn command(&self) -> String {
+        match self {
+            Fix::Symlink { path, target } => {
+                format!("ln -sfn {} {}", quote(target), quote(path))
+            }
+            Fix::Chmod { path, mode } => format!("chmod {:04o} {}", mode, quote(path)),
+        }
+    }
+}
+
+/// `s` single-quoted for sh.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// A shell script applying the fixes of the report's failures, or `None`
+/// if no failure has one. Takes the staged tree as its argument.
+pub fn script(report: &VerificationReport) -> Option<String> {
+    let failures: Vec<_> = report
+        .results
+        .iter()
+        .filter(|r| r.is_failure() && r.fix.is_some())
+        .collect();
+    if failures.is_empty() {
+        return None;
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "#!/bin/sh");
+    let _ = writeln!(
+        out,
+        "# Fixes for {} of {} failure(s) in the {} report (fsdbg {}).",
+        failures.len(),
+        report.failed(),
+        report.artifact_type,
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(
+        out,
+        "# Patches the staged tree only; fix the build step that produced it too."
+    );
+    let _ = writeln!(out, "set -eu");
+    let _ = writeln!(out, "cd \"${{1:?usage: $0 STAGED_TREE}}\"");
+    let mut created = HashSet::new();
+    for result in failures {
+        let Some(ref fix) = result.fix else {
+            continue;
+        };
+        let _ = writeln!(out);
+        let message = result.message.as_deref().unwrap_or("Failed");
+        let _ = writeln!(
+            out,
+            "# {}: {}",
+            result.item.replace('\n', " "),
+            message.replace('\n', " ")
+        );
+        if let Some(parent) = fix.parent().filter(|parent| created.insert(*parent)) {
+            let _ = writeln!(out, "mkdir -p {}", quote(parent));
+        }
+        let _ = writeln!(out, "{}", fix.command());
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::{CheckCategory, CheckResult};
+
+    #[test]
+    fn test_script() {
+        assert_eq!(
+            Fix::wants("usr/lib/systemd/system/sysinit.target.wants/systemd-udevd.service"),
+            Fix::symlink(
+                "usr/lib/systemd/system/sysinit.target.wants/systemd-udevd.service",
+                "../systemd-udevd.service"
+            )
+        );
+        assert_eq!(
+            Fix::wants("etc/systemd/system/getty.target.wants/serial-getty@ttyS0.service"),
+            Fix::symlink(
+                "etc/systemd/system/getty.target.wants/serial-getty@ttyS0.service",
+                "/usr/lib/systemd/system/serial-getty@.service"
+            )
+        );
+        assert_eq!(
+            Fix::symlink("it's", "x").command(),
+            "ln -sfn 'x' 'it'\\''s'"
+        );
+
+        let mut report = VerificationReport::new("Live Initramfs");
+        report.add(
+            CheckResult::fail("bin/busybox", CheckCategory::Binary, "Not executable")
+                .with_fix(Fix::chmod("bin/busybox", 0o100644 | 0o111)),
+        );
+        report.add(CheckResult::fail(
+            "dev",
+            CheckCategory::Directory,
+            "Missing",
+        ));
+        for applet in ["sh", "mount"] {
+            report.add(
+                CheckResult::fail(
+                    format!("applet: {}", applet),
+                    CheckCategory::Symlink,
+                    "Missing",
+                )
+                .with_fix(Fix::symlink(format!("bin/{}", applet), "busybox")),
+            );
+        }
+        let script = script(&report).unwrap();
+        assert!(script.starts_with("#!/bin/sh\n# Fixes for 3 of 4 failure(s)"));
+        assert!(script.contains("# bin/busybox: Not executable\nchmod 0755 'bin/busybox'\n"));
+        // The directory is created once, before the first link into it
+        assert_eq!(script.matches("mkdir -p 'bin'").count(), 1);
+        assert!(script.ends_with("mkdir -p 'bin'\nln -sfn 'busybox' 'bin/sh'\n\n# applet: mount: Missing\nln -sfn 'busybox' 'bin/mount'\n"));
+
+        report.results.retain(|r| r.fix.is_none());
+        assert!(super::script(&report).is_none());
+    }
+}
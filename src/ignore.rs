@@ -0,0 +1,123 @@
+//! Ignore patterns for known-noisy paths
+//!
+//! Some paths change on every build without meaning anything (var/cache,
+//! .build-id links, man page timestamps). An [`IgnoreList`] holds glob
+//! patterns, from `--ignore` or an ignore file, that exclude such paths from:
+//! - `diff` output
+//! - The "unexpected files" side of verification - archive-wide scans like
+//!   empty-file and timestamp checks
+//!
+//! Ignoring never hides a path a checklist explicitly requires: failures on
+//! paths inside the checklist's [`Scope`] are always kept.
+
+use crate::checklist::{Scope, VerificationReport};
+use crate::error::FsdbgError;
+use glob::{MatchOptions, Pattern};
+use std::fs;
+use std::path::Path;
+
+/// Same matching rules as the empty-file scan: `*` stays within one
+/// directory, `**` crosses directories.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Set of glob patterns for paths to ignore.
+///
+/// A pattern matching a directory also ignores everything below it, so
+/// `var/cache` and `var/cache/**` are equivalent.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreList {
+    patterns: Vec<Pattern>,
+}
+
+impl IgnoreList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a single glob pattern.
+    pub fn add(&mut self, glob: &str) -> Result<(), FsdbgError> {
+        let glob = glob.trim_start_matches("./").trim_start_matches('/');
+        let pattern = Pattern::new(glob)
+            .map_err(|e| FsdbgError::invalid_format(format!("Invalid glob '{}': {}", glob, e)))?;
+        self.patterns.push(pattern);
+        Ok(())
+    }
+
+    /// Add patterns from an ignore file: one glob per line, `#` comments and
+    /// blank lines skipped.
+    pub fn load_file(&mut self, path: &Path) -> Result<(), FsdbgError> {
+        if !path.exists() {
+            return Err(FsdbgError::file_not_found(path));
+        }
+        let content = fs::read_to_string(path)?;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.add(line).map_err(|e| e.with_path(path))?;
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Check if a path (or any of its parent directories) is ignored.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.trim_start_matches("./").trim_start_matches('/');
+        let mut candidate = path;
+        loop {
+            if self
+                .patterns
+                .iter()
+                .any(|p| p.matches_with(candidate, MATCH_OPTIONS))
+            {
+                return true;
+            }
+            match candidate.rsplit_once('/') {
+                Some((parent, _)) => candidate = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Drop failures on ignored paths outside the checklist's scope.
+    ///
+    /// Returns the number of failures removed.
+    pub fn filter_report(&self, report: &mut VerificationReport, scope: &Scope) -> usize {
+        let before = report.results.len();
+        report.results.retain(|r| {
+            // Items may carry a parenthesized note after the path
+            let path = r.item.split(" (").next().unwrap_or(&r.item);
+            r.passed || scope.category_of(path).is_some() || !self.matches(path)
+        });
+        before - report.results.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let mut ignore = IgnoreList::new();
+        ignore.add("var/cache").unwrap();
+        ignore.add("usr/lib/.build-id/**").unwrap();
+        ignore.add("/usr/share/man/*/*.gz").unwrap();
+
+        assert!(ignore.matches("var/cache"));
+        assert!(ignore.matches("./var/cache/dnf/packages.db"));
+        assert!(ignore.matches("/usr/lib/.build-id/ab/cdef"));
+        assert!(ignore.matches("usr/share/man/man1/ls.1.gz"));
+        assert!(!ignore.matches("usr/share/man/ls.1.gz"));
+        assert!(!ignore.matches("var/cachefiles"));
+        assert!(!ignore.matches("usr/bin/ls"));
+    }
+}
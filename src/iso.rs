@@ -1,20 +1,34 @@
 //! ISO 9660 image inspection
 //!
 //! Uses isoinfo from cdrtools/genisoimage to inspect ISO images
-//! without mounting.
+//! without mounting. The El Torito boot catalog, which isoinfo doesn't
+//! decode, is read natively ([`boot_catalog`]), as are the volume
+//! descriptors ([`volume_descriptors`]) for the fields `isoinfo -d` omits.
+//! [`IsoReader::open_dirs`] lists only part of the tree, walking the
+//! directory records (with Rock Ridge names and symlinks) natively.
 
 use crate::error::FsdbgError;
+use crate::names::{escape_bytes, unescape_bytes};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 /// Entry in an ISO filesystem
 #[derive(Debug, Clone)]
 pub struct IsoEntry {
+    /// Name with non-UTF-8 bytes escaped (see `crate::names`)
     pub path: String,
+    /// Name exactly as stored
+    pub raw_path: Vec<u8>,
     pub size: u64,
     pub is_dir: bool,
     pub is_symlink: bool,
     pub link_target: Option<String>,
+    /// First logical block of the entry's data
+    pub extent: u32,
 }
 
 /// ISO filesystem inspector
@@ -39,13 +53,14 @@ impl IsoReader {
             .unwrap_or(false);
 
         if !isoinfo_available {
-            return Err(FsdbgError::external_tool_failed(
+            return Err(FsdbgError::tool_missing(
                 "isoinfo",
-                "isoinfo not found. Install cdrtools or genisoimage.",
+                "Install cdrtools or genisoimage.",
             ));
         }
 
-        let entries = Self::list_entries(path)?;
+        let entries = Self::list_entries(path)
+            .map_err(|e| e.with_operation("listing the ISO").with_path(path))?;
         let volume_id = Self::get_volume_id(path).ok();
 
         Ok(Self {
@@ -55,6 +70,54 @@ impl IsoReader {
         })
     }
 
+    /// Open an ISO image listing only its root directory and the trees
+    /// under the root entries named in `dirs` (e.g. `boot`, `EFI`), read
+    /// natively from the directory records. Listing then costs what the
+    /// checks look at, not the size of the image: the packages of an
+    /// install DVD are never read. Files are still read with isoinfo.
+    pub fn open_dirs(path: &Path, dirs: &[&str]) -> Result<Self, FsdbgError> {
+        let file = File::open(path).map_err(|_| FsdbgError::file_not_found(path))?;
+        let (entries, volume_id) = Self::walk_dirs(file, dirs).map_err(|e| e.with_path(path))?;
+
+        Ok(Self {
+            source_path: path.to_path_buf(),
+            entries,
+            volume_id,
+        })
+    }
+
+    /// List an ISO image from any seekable byte source, such as a buffer,
+    /// the way [`IsoReader::open_dirs`] does. Only the listing: reading
+    /// files needs the image on disk.
+    pub fn list_dirs(reader: impl Read + Seek, dirs: &[&str]) -> Result<Vec<IsoEntry>, FsdbgError> {
+        Self::walk_dirs(reader, dirs).map(|(entries, _)| entries)
+    }
+
+    /// The entries under `dirs` and the volume ID of the image in `file`.
+    fn walk_dirs<R: Read + Seek>(
+        mut file: R,
+        dirs: &[&str],
+    ) -> Result<(Vec<IsoEntry>, Option<String>), FsdbgError> {
+        let primary = read_volume_descriptors(&mut file)?
+            .into_iter()
+            .find(|d| d.kind == DescriptorKind::Primary)
+            .ok_or_else(|| FsdbgError::invalid_format("No primary volume descriptor"))?;
+        let mut walker = Walker {
+            file,
+            seen: HashSet::new(),
+            entries: Vec::new(),
+        };
+        // The root directory's record is embedded in the descriptor
+        let root = walker.read(primary.block * BLOCK_SIZE + ROOT_RECORD_OFFSET, 34)?;
+        let extent = u32::from_le_bytes(root[2..6].try_into().expect("4 bytes"));
+        let size = u32::from_le_bytes(root[10..14].try_into().expect("4 bytes"));
+        walker
+            .walk(b"", extent, size, dirs, 0)
+            .map_err(|e| e.with_operation("reading the ISO's directory records"))?;
+
+        Ok((walker.entries, primary.volume.map(|v| v.volume_id)))
+    }
+
     fn list_entries(path: &Path) -> Result<Vec<IsoEntry>, FsdbgError> {
         // Use isoinfo with Rock Ridge extensions
         let output = Command::new("isoinfo")
@@ -70,7 +133,8 @@ impl IsoReader {
             return Err(FsdbgError::external_tool_failed("isoinfo", stderr));
         }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
+        // Escaped rather than lossy, so non-UTF-8 names survive
+        let stdout = escape_bytes(&output.stdout);
         Self::parse_isoinfo_output(&stdout)
     }
 
@@ -131,6 +195,13 @@ impl IsoReader {
             let is_symlink = mode.starts_with('l');
 
             let size: u64 = parts[4].parse().unwrap_or(0);
+            // "[  37 02]": extent, then the file flags
+            let extent = parts[8..]
+                .iter()
+                .flat_map(|part| part.split(['[', ']']))
+                .find(|part| !part.is_empty())
+                .and_then(|part| part.parse().ok())
+                .unwrap_or(0);
 
             // Find filename after the [extent] bracket
             // The bracket starts at parts[8] with '[' and we need to find where ']' ends
@@ -173,11 +244,13 @@ impl IsoReader {
             };
 
             entries.push(IsoEntry {
+                raw_path: unescape_bytes(&full_path),
                 path: full_path,
                 size,
                 is_dir,
                 is_symlink,
                 link_target,
+                extent,
             });
         }
 
@@ -209,11 +282,10 @@ impl IsoReader {
         self.entries.iter().any(|e| e.path == normalized)
     }
 
-    /// Read a file from the ISO image as UTF-8 text.
+    /// Read a file from the ISO image.
     ///
-    /// Uses `isoinfo -x` and returns an error if the file cannot be read
-    /// or is not UTF-8 text.
-    pub fn read_file_to_string(&self, path: &str) -> Result<String, FsdbgError> {
+    /// Uses `isoinfo -x` with Rock Ridge names.
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, FsdbgError> {
         let normalized = if path.starts_with('/') {
             path.to_string()
         } else {
@@ -237,12 +309,60 @@ impl IsoReader {
             ));
         }
 
-        String::from_utf8(output.stdout).map_err(|e| {
+        Ok(output.stdout)
+    }
+
+    /// Read the first `len` bytes of a file from the ISO image, without
+    /// extracting the rest (enough for [`crate::magic`]).
+    pub fn read_head(&self, path: &str, len: usize) -> Result<Vec<u8>, FsdbgError> {
+        let normalized = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path)
+        };
+
+        let mut child = Command::new("isoinfo")
+            .arg("-R")
+            .arg("-i")
+            .arg(&self.source_path)
+            .arg("-x")
+            .arg(&normalized)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| FsdbgError::external_tool_failed("isoinfo", e.to_string()))?;
+
+        let mut head = Vec::with_capacity(len);
+        let read = child
+            .stdout
+            .take()
+            .map(|stdout| stdout.take(len as u64).read_to_end(&mut head));
+        // The rest of the file isn't wanted
+        let _ = child.kill();
+        let _ = child.wait();
+        match read {
+            Some(Ok(_)) => Ok(head),
+            Some(Err(e)) => Err(FsdbgError::external_tool_failed(
+                "isoinfo",
+                format!("failed to read '{}': {}", normalized, e),
+            )),
+            None => Err(FsdbgError::external_tool_failed(
+                "isoinfo",
+                format!("failed to read '{}'", normalized),
+            )),
+        }
+    }
+
+    /// Read a file from the ISO image as UTF-8 text.
+    ///
+    /// Returns an error if the file cannot be read or is not UTF-8 text.
+    pub fn read_file_to_string(&self, path: &str) -> Result<String, FsdbgError> {
+        String::from_utf8(self.read_file(path)?).map_err(|e| {
             FsdbgError::external_tool_failed(
                 "isoinfo",
                 format!(
                     "file '{}' is not valid UTF-8 (treating loader config as text): {}",
-                    normalized, e
+                    path, e
                 ),
             )
         })
@@ -268,6 +388,617 @@ impl IsoReader {
     pub fn symlinks(&self) -> impl Iterator<Item = &IsoEntry> {
         self.entries.iter().filter(|e| e.is_symlink)
     }
+
+    /// Directory records of a file: one per extent, in order, for a file
+    /// split into several (ISO 9660 level 3 multi-extent files).
+    pub fn extents(&self, path: &str) -> Vec<&IsoEntry> {
+        let normalized = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path)
+        };
+        self.entries
+            .iter()
+            .filter(|e| !e.is_dir && e.path == normalized)
+            .collect()
+    }
+
+    /// The file whose data starts at logical block `extent`.
+    pub fn file_at(&self, extent: u32) -> Option<&IsoEntry> {
+        self.entries
+            .iter()
+            .find(|e| !e.is_dir && !e.is_symlink && e.extent == extent)
+    }
+}
+
+/// Logical block size of ISO 9660 volumes
+pub const BLOCK_SIZE: u64 = 2048;
+
+/// Block of the first volume descriptor; an El Torito boot record is the
+/// one after the primary descriptor
+const BOOT_RECORD_BLOCK: u64 = 17;
+
+/// Boot system identifier of an El Torito boot record
+const EL_TORITO_ID: &[u8] = b"EL TORITO SPECIFICATION";
+
+/// El Torito platform IDs
+pub const PLATFORM_BIOS: u8 = 0x00;
+pub const PLATFORM_EFI: u8 = 0xef;
+
+/// A boot image listed in the El Torito boot catalog
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BootImage {
+    /// Platform the image boots ([`PLATFORM_BIOS`], [`PLATFORM_EFI`], ...)
+    pub platform: u8,
+    /// Marked bootable (indicator 0x88)
+    pub bootable: bool,
+    /// Emulation: 0 for none, 1-3 for floppy sizes, 4 for a hard disk
+    pub media: u8,
+    /// Sectors (512 bytes) the firmware loads; 0 means one 2048-byte block
+    pub sectors: u16,
+    /// Logical block the image starts at
+    pub extent: u32,
+}
+
+impl BootImage {
+    pub fn platform_name(&self) -> &'static str {
+        match self.platform {
+            PLATFORM_BIOS => "BIOS",
+            PLATFORM_EFI => "EFI",
+            0x01 => "PowerPC",
+            0x02 => "Mac",
+            _ => "unknown platform",
+        }
+    }
+
+    pub fn media_name(&self) -> &'static str {
+        match self.media {
+            0 => "no emulation",
+            1 => "1.2M floppy",
+            2 => "1.44M floppy",
+            3 => "2.88M floppy",
+            4 => "hard disk",
+            _ => "unknown emulation",
+        }
+    }
+}
+
+/// Read the El Torito boot catalog of an ISO image. An image without a
+/// boot record has no boot images.
+pub fn boot_catalog(path: &Path) -> Result<Vec<BootImage>, FsdbgError> {
+    let mut file = File::open(path).map_err(|_| FsdbgError::file_not_found(path))?;
+    let mut block = vec![0; BLOCK_SIZE as usize];
+    file.seek(SeekFrom::Start(BOOT_RECORD_BLOCK * BLOCK_SIZE))?;
+    if file.read_exact(&mut block).is_err()
+        || block[0] != 0
+        || &block[1..6] != b"CD001"
+        || !block[7..].starts_with(EL_TORITO_ID)
+    {
+        return Ok(Vec::new());
+    }
+    let catalog = u32::from_le_bytes(block[0x47..0x4b].try_into().expect("4 bytes"));
+    file.seek(SeekFrom::Start(u64::from(catalog) * BLOCK_SIZE))?;
+    file.read_exact(&mut block).map_err(|_| {
+        FsdbgError::invalid_format(format!("Boot catalog at block {} is past the end", catalog))
+            .with_path(path)
+    })?;
+    parse_boot_catalog(&block).map_err(|e| e.with_path(path))
+}
+
+/// Parse a boot catalog block: the validation entry, the default entry it
+/// gives the platform of, then sections of entries for further platforms.
+fn parse_boot_catalog(block: &[u8]) -> Result<Vec<BootImage>, FsdbgError> {
+    let validation = block
+        .get(..32)
+        .ok_or_else(|| FsdbgError::invalid_format("Boot catalog is truncated"))?;
+    let checksum = validation.chunks(2).fold(0u16, |sum, word| {
+        sum.wrapping_add(u16::from_le_bytes([word[0], word[1]]))
+    });
+    if validation[0] != 1 || validation[30..32] != [0x55, 0xaa] || checksum != 0 {
+        return Err(FsdbgError::invalid_format(
+            "Boot catalog has no valid validation entry",
+        ));
+    }
+    let image = |entry: &[u8], platform: u8| BootImage {
+        platform,
+        bootable: entry[0] == 0x88,
+        media: entry[1] & 0x0f,
+        sectors: u16::from_le_bytes([entry[6], entry[7]]),
+        extent: u32::from_le_bytes(entry[8..12].try_into().expect("4 bytes")),
+    };
+
+    let mut entries = block[32..].chunks_exact(32);
+    let mut images = Vec::new();
+    if let Some(default) = entries.next() {
+        images.push(image(default, validation[1]));
+    }
+    while let Some(header) = entries.next() {
+        // 0x90: a section header with more to follow, 0x91: the last one
+        if !matches!(header[0], 0x90 | 0x91) {
+            break;
+        }
+        let mut count = u16::from_le_bytes([header[2], header[3]]);
+        while count > 0 {
+            let Some(entry) = entries.next() else {
+                break;
+            };
+            // Extension entries (0x44) continue the entry before
+            if entry[0] != 0x44 {
+                images.push(image(entry, header[1]));
+                count -= 1;
+            }
+        }
+        if header[0] == 0x91 {
+            break;
+        }
+    }
+    Ok(images)
+}
+
+/// Block the volume descriptor set starts at, after the 32 KiB system area
+const DESCRIPTOR_BLOCK: u64 = 16;
+
+/// Most descriptors read when the set has no terminator
+const MAX_DESCRIPTORS: u64 = 64;
+
+/// Type of a volume descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DescriptorKind {
+    BootRecord,
+    Primary,
+    /// Supplementary or enhanced (Joliet's is a supplementary descriptor)
+    Supplementary,
+    Partition,
+    Terminator,
+    Unknown(u8),
+}
+
+impl DescriptorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::BootRecord => "Boot record",
+            Self::Primary => "Primary volume descriptor",
+            Self::Supplementary => "Supplementary volume descriptor",
+            Self::Partition => "Volume partition descriptor",
+            Self::Terminator => "Terminator",
+            Self::Unknown(_) => "Unknown descriptor",
+        }
+    }
+}
+
+/// Fields of a primary or supplementary volume descriptor
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VolumeHeader {
+    pub system_id: String,
+    pub volume_id: String,
+    pub volume_set_id: String,
+    pub publisher_id: String,
+    pub data_preparer_id: String,
+    pub application_id: String,
+    pub copyright_file: String,
+    pub abstract_file: String,
+    pub bibliographic_file: String,
+    /// Size of the volume in logical blocks
+    pub volume_blocks: u32,
+    pub block_size: u16,
+    pub volume_set_size: u16,
+    pub volume_sequence: u16,
+    pub path_table_size: u32,
+    /// Dates as ISO 8601 (with hundredths), None when unset (all zeros)
+    pub created: Option<String>,
+    pub modified: Option<String>,
+    pub expires: Option<String>,
+    pub effective: Option<String>,
+    /// Joliet level (1-3) of a supplementary descriptor that is Joliet's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub joliet: Option<u8>,
+}
+
+/// Fields of a boot record
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BootRecord {
+    pub system_id: String,
+    pub boot_id: String,
+    /// Block of the boot catalog, for an El Torito boot record
+    pub catalog: Option<u32>,
+}
+
+/// A volume descriptor and what of it was decoded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VolumeDescriptor {
+    /// Logical block the descriptor is at
+    pub block: u64,
+    pub kind: DescriptorKind,
+    pub version: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<VolumeHeader>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boot: Option<BootRecord>,
+}
+
+/// Read the volume descriptor set of an ISO image, up to and including
+/// its terminator.
+pub fn volume_descriptors(path: &Path) -> Result<Vec<VolumeDescriptor>, FsdbgError> {
+    let mut file = File::open(path).map_err(|_| FsdbgError::file_not_found(path))?;
+    read_volume_descriptors(&mut file).map_err(|e| e.with_path(path))
+}
+
+fn read_volume_descriptors(
+    file: &mut (impl Read + Seek),
+) -> Result<Vec<VolumeDescriptor>, FsdbgError> {
+    let mut block = vec![0; BLOCK_SIZE as usize];
+    let mut descriptors = Vec::new();
+    for number in DESCRIPTOR_BLOCK..DESCRIPTOR_BLOCK + MAX_DESCRIPTORS {
+        file.seek(SeekFrom::Start(number * BLOCK_SIZE))?;
+        if file.read_exact(&mut block).is_err() {
+            break;
+        }
+        let Some(descriptor) = parse_volume_descriptor(number, &block) else {
+            break;
+        };
+        let kind = descriptor.kind;
+        descriptors.push(descriptor);
+        if kind == DescriptorKind::Terminator {
+            break;
+        }
+    }
+    if descriptors.is_empty() {
+        return Err(FsdbgError::invalid_format(format!(
+            "No ISO 9660 volume descriptor at block {}",
+            DESCRIPTOR_BLOCK
+        )));
+    }
+    Ok(descriptors)
+}
+
+/// Parse the descriptor in `block`, or None if it isn't one.
+fn parse_volume_descriptor(number: u64, block: &[u8]) -> Option<VolumeDescriptor> {
+    if block.len() < BLOCK_SIZE as usize || &block[1..6] != b"CD001" {
+        return None;
+    }
+    let kind = match block[0] {
+        0 => DescriptorKind::BootRecord,
+        1 => DescriptorKind::Primary,
+        2 => DescriptorKind::Supplementary,
+        3 => DescriptorKind::Partition,
+        255 => DescriptorKind::Terminator,
+        other => DescriptorKind::Unknown(other),
+    };
+    let u16_at = |at: usize| u16::from_le_bytes([block[at], block[at + 1]]);
+    let u32_at = |at: usize| u32::from_le_bytes(block[at..at + 4].try_into().expect("4 bytes"));
+
+    let boot = (kind == DescriptorKind::BootRecord).then(|| {
+        let system_id = text(&block[7..39], false);
+        BootRecord {
+            catalog: block[7..].starts_with(EL_TORITO_ID).then(|| u32_at(0x47)),
+            boot_id: text(&block[39..71], false),
+            system_id,
+        }
+    });
+
+    let volume = matches!(
+        kind,
+        DescriptorKind::Primary | DescriptorKind::Supplementary
+    )
+    .then(|| {
+        // Joliet marks its descriptor with a UCS-2 escape sequence
+        let joliet = (kind == DescriptorKind::Supplementary)
+            .then(|| match &block[88..91] {
+                b"%/@" => Some(1),
+                b"%/C" => Some(2),
+                b"%/E" => Some(3),
+                _ => None,
+            })
+            .flatten();
+        let ucs2 = joliet.is_some();
+        VolumeHeader {
+            system_id: text(&block[8..40], ucs2),
+            volume_id: text(&block[40..72], ucs2),
+            volume_set_id: text(&block[190..318], ucs2),
+            publisher_id: text(&block[318..446], ucs2),
+            data_preparer_id: text(&block[446..574], ucs2),
+            application_id: text(&block[574..702], ucs2),
+            copyright_file: text(&block[702..739], ucs2),
+            abstract_file: text(&block[739..776], ucs2),
+            bibliographic_file: text(&block[776..813], ucs2),
+            volume_blocks: u32_at(80),
+            block_size: u16_at(128),
+            volume_set_size: u16_at(120),
+            volume_sequence: u16_at(124),
+            path_table_size: u32_at(132),
+            created: datetime(&block[813..830]),
+            modified: datetime(&block[830..847]),
+            expires: datetime(&block[847..864]),
+            effective: datetime(&block[864..881]),
+            joliet,
+        }
+    });
+
+    Some(VolumeDescriptor {
+        block: number,
+        kind,
+        version: block[6],
+        volume,
+        boot,
+    })
+}
+
+/// A space-padded identifier field; Joliet's are UCS-2 (big-endian).
+fn text(field: &[u8], ucs2: bool) -> String {
+    let text = if ucs2 {
+        let units: Vec<u16> = field
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(field).into_owned()
+    };
+    text.trim_end_matches([' ', '\0']).to_string()
+}
+
+/// A 17-byte descriptor date: "YYYYMMDDHHMMSScc" digits and the offset
+/// from GMT in 15-minute steps. Unset dates are all zeros.
+fn datetime(field: &[u8]) -> Option<String> {
+    let digits = std::str::from_utf8(&field[..16]).ok()?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) || digits.bytes().all(|b| b == b'0') {
+        return None;
+    }
+    let offset = i32::from(field[16] as i8) * 15;
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}.{}{}{:02}:{:02}",
+        &digits[..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14],
+        &digits[14..16],
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60
+    ))
+}
+
+/// Where the root directory record is in the primary volume descriptor
+const ROOT_RECORD_OFFSET: u64 = 156;
+
+/// Directory record flag of directories
+const RECORD_DIRECTORY: u8 = 0x02;
+
+/// Deepest directory the native walk descends into, and most bytes of
+/// directory records it reads for one directory
+const MAX_DEPTH: usize = 64;
+const MAX_DIRECTORY_SIZE: u32 = 16 << 20;
+
+/// Most Rock Ridge continuation areas followed for one record
+const MAX_CONTINUATIONS: usize = 16;
+
+/// Rock Ridge `PX` file type bits of symlinks
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// A directory record, with what Rock Ridge says about it
+struct Record {
+    name: Vec<u8>,
+    extent: u32,
+    size: u32,
+    is_dir: bool,
+    is_symlink: bool,
+    link_target: Option<Vec<u8>>,
+    /// A directory Rock Ridge relocated here from deeper in the tree; it is
+    /// listed where its `CL` record is
+    relocated: bool,
+}
+
+/// Walks directory records natively for [`IsoReader::open_dirs`]
+struct Walker<R> {
+    file: R,
+    /// Directories already walked, against loops in corrupt images
+    seen: HashSet<u32>,
+    entries: Vec<IsoEntry>,
+}
+
+impl<R: Read + Seek> Walker<R> {
+    fn read(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, FsdbgError> {
+        let mut data = vec![0; len];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut data).map_err(|_| {
+            FsdbgError::invalid_format(format!(
+                "Directory data at byte {} is past the end of the image",
+                offset
+            ))
+        })?;
+        Ok(data)
+    }
+
+    /// List the directory at `extent` (`dir` is its raw path), descending
+    /// into every subdirectory below the root, and into `dirs` at it.
+    fn walk(
+        &mut self,
+        dir: &[u8],
+        extent: u32,
+        size: u32,
+        dirs: &[&str],
+        depth: usize,
+    ) -> Result<(), FsdbgError> {
+        if depth > MAX_DEPTH || !self.seen.insert(extent) {
+            return Ok(());
+        }
+        let mut subdirs = Vec::new();
+        for record in self.records(extent, size)? {
+            if record.relocated {
+                continue;
+            }
+            let raw_path = [dir, b"/", &record.name].concat();
+            let wanted = depth > 0 || dirs.iter().any(|d| d.as_bytes() == &raw_path[1..]);
+            if record.is_dir && wanted {
+                subdirs.push((raw_path.clone(), record.extent, record.size));
+            }
+            self.entries.push(IsoEntry {
+                path: escape_bytes(&raw_path),
+                raw_path,
+                size: u64::from(record.size),
+                is_dir: record.is_dir,
+                is_symlink: record.is_symlink,
+                link_target: record.link_target.map(|t| escape_bytes(&t)),
+                extent: record.extent,
+            });
+        }
+        for (path, extent, size) in subdirs {
+            self.walk(&path, extent, size, dirs, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Records of the directory at `extent`, without `.` and `..`. A size
+    /// of 0 (a Rock Ridge `CL` record) is read from the directory's `.`.
+    fn records(&mut self, extent: u32, size: u32) -> Result<Vec<Record>, FsdbgError> {
+        let start = u64::from(extent) * BLOCK_SIZE;
+        let size = match size {
+            0 => {
+                let dot = self.read(start, 14)?;
+                u32::from_le_bytes(dot[10..14].try_into().expect("4 bytes"))
+            }
+            size => size,
+        };
+        let data = self.read(start, size.min(MAX_DIRECTORY_SIZE) as usize)?;
+
+        let mut records = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let len = data[pos] as usize;
+            if len == 0 {
+                // Records don't cross blocks; the rest of this one is padding
+                pos = (pos / BLOCK_SIZE as usize + 1) * BLOCK_SIZE as usize;
+                continue;
+            }
+            let Some(raw) = data.get(pos..pos + len).filter(|raw| raw.len() > 33) else {
+                break;
+            };
+            pos += len;
+            let name_len = raw[32] as usize;
+            let Some(id) = raw.get(33..33 + name_len) else {
+                continue;
+            };
+            // `.` and `..`
+            if id == [0] || id == [1] {
+                continue;
+            }
+            // An even-length name is followed by a padding byte
+            let system_use = raw.get(34 + name_len - name_len % 2..).unwrap_or_default();
+            let mut record = Record {
+                name: iso_name(id),
+                extent: u32::from_le_bytes(raw[2..6].try_into().expect("4 bytes")),
+                size: u32::from_le_bytes(raw[10..14].try_into().expect("4 bytes")),
+                is_dir: raw[25] & RECORD_DIRECTORY != 0,
+                is_symlink: false,
+                link_target: None,
+                relocated: false,
+            };
+            self.rock_ridge(system_use, &mut record)?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Apply a record's Rock Ridge entries: `NM` name, `PX` mode, `SL`
+    /// symlink target, `CL`/`RE` relocation, following `CE` continuations.
+    fn rock_ridge(&mut self, area: &[u8], record: &mut Record) -> Result<(), FsdbgError> {
+        let mut name: Option<Vec<u8>> = None;
+        let mut link: Option<Vec<u8>> = None;
+        // The last symlink component continues in the next one
+        let mut joined = false;
+        let mut area = area.to_vec();
+        for _ in 0..MAX_CONTINUATIONS {
+            let mut continuation = None;
+            let mut pos = 0;
+            while let Some(header) = area.get(pos..pos + 4) {
+                let len = header[2] as usize;
+                let Some(entry) = area.get(pos..pos + len).filter(|_| len >= 4) else {
+                    break;
+                };
+                pos += len;
+                let u32_at = |at: usize| {
+                    entry
+                        .get(at..at + 4)
+                        .map(|b| u32::from_le_bytes(b.try_into().expect("4 bytes")))
+                };
+                match &header[..2] {
+                    // Flags 0x02 and 0x04 name `.` and `..`
+                    b"NM" if len > 4 && entry[4] & 0x06 == 0 => {
+                        name.get_or_insert_with(Vec::new)
+                            .extend_from_slice(&entry[5..]);
+                    }
+                    b"PX" => {
+                        if let Some(mode) = u32_at(4) {
+                            record.is_symlink = mode & S_IFMT == S_IFLNK;
+                        }
+                    }
+                    b"SL" => {
+                        let link = link.get_or_insert_with(Vec::new);
+                        let mut at = 5;
+                        while let Some(&[flags, len]) = entry.get(at..at + 2) {
+                            let content =
+                                entry.get(at + 2..at + 2 + len as usize).unwrap_or_default();
+                            at += 2 + len as usize;
+                            if !joined && !link.is_empty() && !link.ends_with(b"/") {
+                                link.push(b'/');
+                            }
+                            match flags & 0x0e {
+                                0x02 => link.push(b'.'),
+                                0x04 => link.extend_from_slice(b".."),
+                                0x08 => link.push(b'/'),
+                                _ => link.extend_from_slice(content),
+                            }
+                            joined = flags & 0x01 != 0;
+                        }
+                    }
+                    b"CE" => {
+                        if let (Some(block), Some(offset), Some(len)) =
+                            (u32_at(4), u32_at(12), u32_at(20))
+                        {
+                            continuation = Some((block, offset, len));
+                        }
+                    }
+                    b"CL" => {
+                        if let Some(extent) = u32_at(4) {
+                            record.extent = extent;
+                            record.size = 0;
+                            record.is_dir = true;
+                        }
+                    }
+                    b"RE" => record.relocated = true,
+                    b"ST" => break,
+                    _ => {}
+                }
+            }
+            let Some((block, offset, len)) = continuation else {
+                break;
+            };
+            area = self.read(
+                u64::from(block) * BLOCK_SIZE + u64::from(offset),
+                len.min(BLOCK_SIZE as u32) as usize,
+            )?;
+        }
+
+        if let Some(name) = name {
+            record.name = name;
+        }
+        if link.is_some() {
+            record.is_symlink = true;
+            record.link_target = link;
+        }
+        Ok(())
+    }
+}
+
+/// An ISO 9660 file identifier without Rock Ridge: without its `;1`
+/// version, and the `.` of a name without an extension.
+fn iso_name(id: &[u8]) -> Vec<u8> {
+    let name = id.split(|b| *b == b';').next().unwrap_or(id);
+    name.strip_suffix(b".").unwrap_or(name).to_vec()
 }
 
 /// Statistics about an ISO filesystem
@@ -279,73 +1010,280 @@ pub struct IsoStats {
     pub total_size: u64,
 }
 
-/// Get ISO image information
-pub fn get_iso_info(path: &Path) -> Result<IsoInfo, FsdbgError> {
-    if !path.exists() {
-        return Err(FsdbgError::file_not_found(path));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Block of the root directory in images from `image`
+    const ROOT: u32 = 18;
+
+    /// A number in both byte orders, as ISO 9660 stores most
+    fn both(n: u32) -> Vec<u8> {
+        [n.to_le_bytes(), n.to_be_bytes()].concat()
     }
 
-    let output = Command::new("isoinfo")
-        .arg("-d")
-        .arg("-i")
-        .arg(path)
-        .output()
-        .map_err(|e| FsdbgError::external_tool_failed("isoinfo", e.to_string()))?;
+    /// A directory record with Rock Ridge `system_use`.
+    fn record(name: &[u8], extent: u32, size: u32, is_dir: bool, system_use: &[u8]) -> Vec<u8> {
+        let mut record = vec![0u8; 33];
+        record[2..10].copy_from_slice(&both(extent));
+        record[10..18].copy_from_slice(&both(size));
+        record[25] = if is_dir { RECORD_DIRECTORY } else { 0 };
+        record[32] = name.len() as u8;
+        record.extend_from_slice(name);
+        if name.len().is_multiple_of(2) {
+            record.push(0);
+        }
+        record.extend_from_slice(system_use);
+        record.resize(record.len().next_multiple_of(2), 0);
+        record[0] = record.len() as u8;
+        record
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(FsdbgError::external_tool_failed("isoinfo", stderr));
+    /// A System Use entry.
+    fn susp(signature: &[u8; 2], data: &[u8]) -> Vec<u8> {
+        [&signature[..], &[4 + data.len() as u8, 1], data].concat()
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_iso_info(&stdout)
-}
+    fn px(mode: u32) -> Vec<u8> {
+        susp(b"PX", &[both(mode), both(1), both(0), both(0)].concat())
+    }
 
-fn parse_iso_info(output: &str) -> Result<IsoInfo, FsdbgError> {
-    let mut info = IsoInfo::default();
+    /// `SL` with (flags, content) components.
+    fn sl(components: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut data = vec![0];
+        for (flags, content) in components {
+            data.extend_from_slice(&[*flags, content.len() as u8]);
+            data.extend_from_slice(content);
+        }
+        susp(b"SL", &data)
+    }
 
-    for line in output.lines() {
-        let line = line.trim();
-        if line.starts_with("Volume id:") {
-            info.volume_id = Some(
-                line.strip_prefix("Volume id:")
-                    .unwrap_or("")
-                    .trim()
-                    .to_string(),
-            );
-        } else if line.starts_with("Volume size is:") {
-            if let Some(size_str) = line.split(':').nth(1) {
-                let size_str = size_str.split_whitespace().next().unwrap_or("0");
-                info.volume_size = size_str.parse().unwrap_or(0);
-            }
-        } else if line.starts_with("Logical block size is:") {
-            if let Some(bs_str) = line.split(':').nth(1) {
-                info.block_size = bs_str.trim().parse().unwrap_or(2048);
-            }
-        } else if line.starts_with("System id:") {
-            info.system_id = Some(
-                line.strip_prefix("System id:")
-                    .unwrap_or("")
-                    .trim()
-                    .to_string(),
+    fn ce(block: u32, offset: u32, len: u32) -> Vec<u8> {
+        susp(b"CE", &[both(block), both(offset), both(len)].concat())
+    }
+
+    /// An image of `blocks` blocks: a primary volume descriptor, a
+    /// terminator, and an empty root directory at [`ROOT`].
+    fn image(blocks: u32) -> Vec<u8> {
+        let mut image = vec![0u8; blocks as usize * BLOCK_SIZE as usize];
+        let pvd = DESCRIPTOR_BLOCK as usize * BLOCK_SIZE as usize;
+        image[pvd] = 1;
+        image[pvd + 1..pvd + 6].copy_from_slice(b"CD001");
+        image[pvd + 40..pvd + 72].fill(b' ');
+        image[pvd + 40..pvd + 48].copy_from_slice(b"LEVITATE");
+        image[pvd + 80..pvd + 84].copy_from_slice(&blocks.to_le_bytes());
+        image[pvd + 128..pvd + 130].copy_from_slice(&2048u16.to_le_bytes());
+        let root = record(&[0], ROOT, BLOCK_SIZE as u32, true, &[]);
+        let at = pvd + ROOT_RECORD_OFFSET as usize;
+        image[at..at + root.len()].copy_from_slice(&root);
+        image[pvd + 2048] = 255;
+        image[pvd + 2049..pvd + 2054].copy_from_slice(b"CD001");
+        write_dir(&mut image, ROOT, &[]);
+        image
+    }
+
+    /// Write a one-block directory of `records` (after `.` and `..`).
+    fn write_dir(image: &mut [u8], block: u32, records: &[Vec<u8>]) {
+        let dot = record(&[0], block, BLOCK_SIZE as u32, true, &[]);
+        let dotdot = record(&[1], ROOT, BLOCK_SIZE as u32, true, &[]);
+        let data = [&[dot, dotdot][..], records].concat().concat();
+        assert!(data.len() <= BLOCK_SIZE as usize);
+        let at = block as usize * BLOCK_SIZE as usize;
+        image[at..at + data.len()].copy_from_slice(&data);
+    }
+
+    fn open_dirs(image: &[u8], dirs: &[&str]) -> Result<IsoReader, FsdbgError> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), image).unwrap();
+        IsoReader::open_dirs(file.path(), dirs)
+    }
+
+    fn entry<'a>(reader: &'a IsoReader, path: &str) -> &'a IsoEntry {
+        reader
+            .entries()
+            .iter()
+            .find(|e| e.path == path)
+            .unwrap_or_else(|| panic!("no {}", path))
+    }
+
+    #[test]
+    fn test_walk_continuation() {
+        let mut image = image(24);
+        // The name starts in the record and ends in the continuation area,
+        // which also says what the entry is
+        let area = [
+            susp(b"NM", b"\0nuz-6.12"),
+            px(S_IFLNK | 0o777),
+            sl(&[(0x04, b""), (0, b"boot"), (0, b"vmlinuz")]),
+        ]
+        .concat();
+        let at = 20 * BLOCK_SIZE as usize + 100;
+        image[at..at + area.len()].copy_from_slice(&area);
+        // A continuation that continues itself
+        let cycle = [susp(b"NM", b"\x01x"), ce(21, 0, 40)].concat();
+        let at = 21 * BLOCK_SIZE as usize;
+        image[at..at + cycle.len()].copy_from_slice(&cycle);
+        write_dir(
+            &mut image,
+            ROOT,
+            &[
+                record(
+                    b"VMLINUZ.;1",
+                    0,
+                    0,
+                    false,
+                    &[susp(b"NM", b"\x01vmli"), ce(20, 100, area.len() as u32)].concat(),
+                ),
+                record(b"LOOP.;1", 0, 0, false, &ce(21, 0, 40)),
+            ],
+        );
+
+        let reader = open_dirs(&image, &[]).unwrap();
+        assert_eq!(reader.volume_id(), Some("LEVITATE"));
+        let vmlinuz = entry(&reader, "/vmlinuz-6.12");
+        assert!(vmlinuz.is_symlink);
+        assert_eq!(vmlinuz.link_target.as_deref(), Some("../boot/vmlinuz"));
+        // Followed until the limit, the record's own area being the first
+        entry(&reader, &format!("/{}", "x".repeat(MAX_CONTINUATIONS - 1)));
+
+        // A continuation area past the end of the image
+        write_dir(
+            &mut image,
+            ROOT,
+            &[record(b"VMLINUZ.;1", 0, 0, false, &ce(1000, 0, 64))],
+        );
+        let error = open_dirs(&image, &[]).err().unwrap();
+        assert_eq!(
+            error.message(),
+            "Directory data at byte 2048000 is past the end of the image"
+        );
+    }
+
+    #[test]
+    fn test_walk_depth() {
+        const CHAIN: u32 = MAX_DEPTH as u32 + 6;
+        let packages = 20 + CHAIN;
+        let mut image = image(packages + 1);
+        write_dir(
+            &mut image,
+            ROOT,
+            &[
+                record(b"BOOT", 19, BLOCK_SIZE as u32, true, &[]),
+                record(b"PACKAGES", packages, BLOCK_SIZE as u32, true, &[]),
+            ],
+        );
+        write_dir(
+            &mut image,
+            packages,
+            &[record(b"KERNEL.RPM;1", 0, 0, false, &[])],
+        );
+        // boot/d/d/... one directory per block, and a record looping back
+        write_dir(
+            &mut image,
+            19,
+            &[
+                record(b"D", 20, BLOCK_SIZE as u32, true, &[]),
+                record(b"AGAIN", 19, BLOCK_SIZE as u32, true, &[]),
+            ],
+        );
+        for block in 20..20 + CHAIN - 1 {
+            write_dir(
+                &mut image,
+                block,
+                &[record(b"D", block + 1, BLOCK_SIZE as u32, true, &[])],
             );
-        } else if line.contains("Rock Ridge") && line.contains("YES") {
-            info.rock_ridge = true;
-        } else if line.contains("El Torito") {
-            info.el_torito = true;
         }
+
+        let reader = open_dirs(&image, &["BOOT"]).unwrap();
+        assert!(entry(&reader, "/PACKAGES").is_dir);
+        assert!(!reader.exists("/PACKAGES/KERNEL.RPM"));
+        entry(&reader, "/BOOT/AGAIN");
+        assert!(!reader
+            .entries()
+            .iter()
+            .any(|e| e.path.starts_with("/BOOT/AGAIN/")));
+
+        // Each walked directory lists one level further down
+        let deepest = reader
+            .entries()
+            .iter()
+            .map(|e| e.path.matches("/D").count())
+            .max()
+            .unwrap();
+        assert_eq!(deepest, MAX_DEPTH);
+
+        // A buffer lists the same as the file
+        let paths =
+            |entries: &[IsoEntry]| entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>();
+        let listed = IsoReader::list_dirs(std::io::Cursor::new(&image), &["BOOT"]).unwrap();
+        assert_eq!(paths(&listed), paths(reader.entries()));
+        assert!(
+            IsoReader::list_dirs(std::io::Cursor::new(&image[..BLOCK_SIZE as usize]), &[]).is_err()
+        );
     }
 
-    Ok(info)
-}
+    #[test]
+    fn test_walk_symlinks() {
+        let mut image = image(21);
+        write_dir(
+            &mut image,
+            ROOT,
+            &[
+                record(
+                    b"SBIN.;1",
+                    0,
+                    0,
+                    false,
+                    &[
+                        susp(b"NM", b"\0sbin"),
+                        px(S_IFLNK | 0o777),
+                        sl(&[(0, b"usr"), (0, b"sbin")]),
+                    ]
+                    .concat(),
+                ),
+                // The mode alone makes a symlink, even without a target
+                record(
+                    b"LIB64.;1",
+                    0,
+                    0,
+                    false,
+                    &[susp(b"NM", b"\0lib64"), px(S_IFLNK | 0o777)].concat(),
+                ),
+                // A target split across SL entries, mid-component
+                record(
+                    b"LIBC.;1",
+                    0,
+                    0,
+                    false,
+                    &[
+                        susp(b"NM", b"\0libc.so.6"),
+                        px(S_IFLNK | 0o777),
+                        sl(&[(0x08, b""), (0, b"usr"), (0, b"lib"), (0x01, b"libc.so")]),
+                        sl(&[(0, b".6")]),
+                    ]
+                    .concat(),
+                ),
+                record(b"README.TXT;1", 20, 6, false, &px(0o100644)),
+                record(b"ETC", 0, 0, true, &px(0o040755)),
+            ],
+        );
 
-/// ISO image information
-#[derive(Debug, Default)]
-pub struct IsoInfo {
-    pub volume_id: Option<String>,
-    pub system_id: Option<String>,
-    pub volume_size: u64,
-    pub block_size: u32,
-    pub rock_ridge: bool,
-    pub el_torito: bool,
+        let reader = open_dirs(&image, &[]).unwrap();
+        let sbin = entry(&reader, "/sbin");
+        assert!(sbin.is_symlink && !sbin.is_dir);
+        assert_eq!(sbin.link_target.as_deref(), Some("usr/sbin"));
+        let lib64 = entry(&reader, "/lib64");
+        assert!(lib64.is_symlink);
+        assert_eq!(lib64.link_target, None);
+        assert_eq!(
+            entry(&reader, "/libc.so.6").link_target.as_deref(),
+            Some("/usr/lib/libc.so.6")
+        );
+        let readme = entry(&reader, "/README.TXT");
+        assert!(!readme.is_symlink && readme.size == 6 && readme.extent == 20);
+        let etc = entry(&reader, "/ETC");
+        assert!(etc.is_dir && !etc.is_symlink);
+        assert_eq!(reader.symlinks().count(), 3);
+        assert_eq!(reader.stats().symlinks, 3);
+    }
 }
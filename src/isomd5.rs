@@ -0,0 +1,394 @@
+//! Media checksums embedded in ISO images (isomd5sum)
+//!
+//! `implantisomd5` stores an MD5 of the image in the primary volume
+//! descriptor's application use area, which `checkisomd5` (and dracut's
+//! `rd.live.check`, behind the "check media" boot entries) recomputes. The
+//! sum covers the volume up to its last 15 sectors, with the application
+//! use area itself read as spaces. Fragment sums - the first hex digit of
+//! the first bytes of the running MD5 at each twentieth of the volume -
+//! locate the first corrupted stretch.
+//!
+//! The format is Fedora's: the application use area holds
+//! `ISO MD5SUM = <md5>;SKIPSECTORS = 15;RHLISOSTATUS=0;FRAGMENT SUMS =
+//! <sums>;FRAGMENT COUNT = 20;`, padded with spaces.
+
+use crate::error::FsdbgError;
+use crate::iso::{self, DescriptorKind, BLOCK_SIZE};
+use md5::{Digest, Md5};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Where the application use area is in the primary volume descriptor
+const APPDATA_OFFSET: u64 = 883;
+const APPDATA_SIZE: usize = 512;
+
+/// Sectors at the end of the volume the sum leaves out (padding some
+/// writers drop)
+pub const SKIP_SECTORS: u64 = 15;
+
+/// Fragments implanted, and characters of fragment sums between them
+pub const FRAGMENT_COUNT: u64 = 20;
+const FRAGMENT_SUM_LENGTH: u64 = 60;
+
+/// Bytes read at a time; fragment sums depend on it, so it is checkisomd5's
+const BUFFER_SIZE: u64 = 32768;
+
+/// A checksum found in the application use area
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Embedded {
+    /// Hex MD5 of the volume
+    pub md5: String,
+    pub skip_sectors: u64,
+    /// RHLISOSTATUS, which anaconda reads as "supported media"
+    pub supported: bool,
+    pub fragment_sums: String,
+    pub fragment_count: u64,
+}
+
+impl Embedded {
+    /// Parse the application use area; None without an `ISO MD5SUM`.
+    fn parse(appdata: &[u8]) -> Option<Self> {
+        let text = String::from_utf8_lossy(appdata);
+        let field = |key: &str| {
+            text.split(';').find_map(|field| {
+                let (name, value) = field.split_once('=')?;
+                (name.trim() == key).then(|| value.trim().to_string())
+            })
+        };
+        Some(Self {
+            md5: field("ISO MD5SUM")?,
+            skip_sectors: field("SKIPSECTORS")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_default(),
+            supported: field("RHLISOSTATUS").as_deref() == Some("1"),
+            fragment_sums: field("FRAGMENT SUMS").unwrap_or_default(),
+            fragment_count: field("FRAGMENT COUNT")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// The application use area holding the checksum.
+    fn appdata(&self) -> Vec<u8> {
+        let mut appdata = format!(
+            "ISO MD5SUM = {};SKIPSECTORS = {};RHLISOSTATUS={};FRAGMENT SUMS = {};FRAGMENT COUNT = {};",
+            self.md5,
+            self.skip_sectors,
+            u8::from(self.supported),
+            self.fragment_sums,
+            self.fragment_count
+        )
+        .into_bytes();
+        appdata.resize(APPDATA_SIZE, b' ');
+        appdata
+    }
+}
+
+/// Result of recomputing an embedded checksum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub embedded: Embedded,
+    /// Hex MD5 of the volume as it is
+    pub md5: String,
+    /// First fragment (1-based) whose sum differs, if any does
+    pub bad_fragment: Option<u64>,
+}
+
+impl Verification {
+    pub fn matches(&self) -> bool {
+        self.md5.eq_ignore_ascii_case(&self.embedded.md5) && self.bad_fragment.is_none()
+    }
+}
+
+/// Where the primary volume descriptor is, and the volume's size in bytes.
+fn primary_volume(path: &Path) -> Result<(u64, u64), FsdbgError> {
+    iso::volume_descriptors(path)?
+        .into_iter()
+        .find(|d| d.kind == DescriptorKind::Primary)
+        .and_then(|d| {
+            let volume = d.volume?;
+            Some((
+                d.block * BLOCK_SIZE,
+                u64::from(volume.volume_blocks) * u64::from(volume.block_size),
+            ))
+        })
+        .ok_or_else(|| FsdbgError::invalid_format("No primary volume descriptor").with_path(path))
+}
+
+/// Read the checksum embedded in the ISO image at `path`, if it has one.
+pub fn embedded(path: &Path) -> Result<Option<Embedded>, FsdbgError> {
+    let (pvd, _) = primary_volume(path)?;
+    let mut file = File::open(path).map_err(|_| FsdbgError::file_not_found(path))?;
+    let mut appdata = vec![0; APPDATA_SIZE];
+    file.seek(SeekFrom::Start(pvd + APPDATA_OFFSET))?;
+    file.read_exact(&mut appdata)?;
+    Ok(Embedded::parse(&appdata))
+}
+
+/// Hex digits each fragment sum has.
+fn digits(fragment_count: u64) -> usize {
+    FRAGMENT_SUM_LENGTH
+        .checked_div(fragment_count)
+        .unwrap_or_default()
+        .min(16) as usize
+}
+
+/// MD5 and fragment sums of the volume, read as checkisomd5 reads it.
+fn compute(
+    file: &mut File,
+    pvd: u64,
+    volume_size: u64,
+    skip_sectors: u64,
+    fragment_count: u64,
+) -> Result<(String, String), FsdbgError> {
+    let total = volume_size.saturating_sub(skip_sectors * BLOCK_SIZE);
+    let fragment_size = (total / (fragment_count + 1)).max(1);
+    let digits = digits(fragment_count);
+    let appdata = pvd + APPDATA_OFFSET..pvd + APPDATA_OFFSET + APPDATA_SIZE as u64;
+
+    let mut hasher = Md5::new();
+    let mut sums = String::new();
+    let mut previous = 0;
+    let mut buffer = vec![0; BUFFER_SIZE.min(fragment_size) as usize];
+    let mut offset = 0;
+    file.seek(SeekFrom::Start(0))?;
+    while offset < total {
+        let chunk = &mut buffer[..(total - offset).min(BUFFER_SIZE.min(fragment_size)) as usize];
+        file.read_exact(chunk).map_err(|_| {
+            FsdbgError::invalid_format(format!(
+                "Image ends before its volume size ({} bytes)",
+                volume_size
+            ))
+        })?;
+        let end = offset + chunk.len() as u64;
+        // The checksum can't cover the area it is stored in
+        if appdata.start < end && appdata.end > offset {
+            let from = appdata.start.max(offset) - offset;
+            let to = appdata.end.min(end) - offset;
+            chunk[from as usize..to as usize].fill(b' ');
+        }
+        hasher.update(&chunk[..]);
+
+        // Sum up to the end of the read that started the fragment, as checkisomd5 does
+        let current = offset / fragment_size;
+        if current != previous && (1..=fragment_count).contains(&current) {
+            let digest = hasher.clone().finalize();
+            for byte in &digest[..digits] {
+                sums.push_str(&format!("{:x}", byte)[..1]);
+            }
+            previous = current;
+        }
+        offset = end;
+    }
+    let md5 = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Ok((md5, sums))
+}
+
+/// Recompute the checksum embedded in the ISO image at `path`. None if it
+/// has none.
+pub fn verify(path: &Path) -> Result<Option<Verification>, FsdbgError> {
+    let Some(embedded) = embedded(path)? else {
+        return Ok(None);
+    };
+    let (pvd, volume_size) = primary_volume(path)?;
+    let mut file = File::open(path).map_err(|_| FsdbgError::file_not_found(path))?;
+    let (md5, sums) = compute(
+        &mut file,
+        pvd,
+        volume_size,
+        embedded.skip_sectors,
+        embedded.fragment_count,
+    )
+    .map_err(|e| e.with_path(path))?;
+
+    let digits = digits(embedded.fragment_count).max(1);
+    let bad_fragment = sums
+        .as_bytes()
+        .chunks(digits)
+        .zip(embedded.fragment_sums.as_bytes().chunks(digits))
+        .position(|(computed, stored)| !computed.eq_ignore_ascii_case(stored))
+        .map(|i| i as u64 + 1);
+    Ok(Some(Verification {
+        embedded,
+        md5,
+        bad_fragment,
+    }))
+}
+
+/// Implant a checksum into the ISO image at `path`. One already there is
+/// only replaced with `force`.
+pub fn implant(path: &Path, force: bool) -> Result<Embedded, FsdbgError> {
+    if let Some(existing) = embedded(path)? {
+        if !force {
+            return Err(FsdbgError::invalid_format(format!(
+                "Already has a media checksum ({})",
+                existing.md5
+            ))
+            .with_path(path));
+        }
+    }
+    let (pvd, volume_size) = primary_volume(path)?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|e| FsdbgError::from(e).with_path(path))?;
+    let (md5, fragment_sums) = compute(&mut file, pvd, volume_size, SKIP_SECTORS, FRAGMENT_COUNT)
+        .map_err(|e| e.with_path(path))?;
+    let embedded = Embedded {
+        md5,
+        skip_sectors: SKIP_SECTORS,
+        supported: false,
+        fragment_sums,
+        fragment_count: FRAGMENT_COUNT,
+    };
+    file.seek(SeekFrom::Start(pvd + APPDATA_OFFSET))?;
+    file.write_all(&embedded.appdata())?;
+    Ok(embedded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 64 blocks: a primary volume descriptor, a terminator, and data.
+    fn image() -> Vec<u8> {
+        let mut image = vec![0u8; 64 * BLOCK_SIZE as usize];
+        let pvd = 16 * BLOCK_SIZE as usize;
+        image[pvd] = 1;
+        image[pvd + 1..pvd + 6].copy_from_slice(b"CD001");
+        image[pvd + 80..pvd + 84].copy_from_slice(&64u32.to_le_bytes());
+        image[pvd + 128..pvd + 130].copy_from_slice(&2048u16.to_le_bytes());
+        image[pvd + 2048] = 255;
+        image[pvd + 2049..pvd + 2054].copy_from_slice(b"CD001");
+        for (i, byte) in image[40 * 2048..].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        image
+    }
+
+    #[test]
+    fn test_implant_and_verify() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), image()).unwrap();
+
+        assert_eq!(verify(file.path()).unwrap(), None);
+        let implanted = implant(file.path(), false).unwrap();
+        assert_eq!(implanted.fragment_sums.len(), 60);
+        assert!(implant(file.path(), false).is_err());
+        let verification = verify(file.path()).unwrap().unwrap();
+        assert_eq!(verification.embedded, implanted);
+        assert!(verification.matches());
+
+        // Corrupt a byte in the last fragment, then one in the skipped sectors
+        let mut image = std::fs::read(file.path()).unwrap();
+        image[48 * 2048] ^= 0xff;
+        image[60 * 2048] ^= 0xff;
+        std::fs::write(file.path(), &image).unwrap();
+        let verification = verify(file.path()).unwrap().unwrap();
+        assert!(!verification.matches());
+        assert_eq!(verification.bad_fragment, Some(20));
+    }
+
+    #[test]
+    fn test_implant_round_trip() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), image()).unwrap();
+        let implanted = implant(file.path(), false).unwrap();
+
+        // The sum is plain MD5 of the unskipped volume, appdata as spaces
+        let mut expected = image();
+        let appdata = 16 * 2048 + APPDATA_OFFSET as usize;
+        expected[appdata..appdata + APPDATA_SIZE].fill(b' ');
+        let md5: String = Md5::digest(&expected[..(64 - SKIP_SECTORS as usize) * 2048])
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(implanted.md5, md5);
+
+        // Stored in checkisomd5's format, and read back as written
+        let written = std::fs::read(file.path()).unwrap();
+        let area = &written[appdata..appdata + APPDATA_SIZE];
+        let text = format!(
+            "ISO MD5SUM = {};SKIPSECTORS = 15;RHLISOSTATUS=0;FRAGMENT SUMS = {};FRAGMENT COUNT = 20;",
+            md5, implanted.fragment_sums
+        );
+        assert!(area.starts_with(text.as_bytes()));
+        assert!(area[text.len()..].iter().all(|b| *b == b' '));
+        assert_eq!(embedded(file.path()).unwrap(), Some(implanted.clone()));
+        assert_eq!(
+            Embedded::parse(&implanted.appdata()),
+            Some(implanted.clone())
+        );
+
+        // Only the application use area changed
+        let mut unchanged = written.clone();
+        unchanged[appdata..appdata + APPDATA_SIZE]
+            .copy_from_slice(&image()[appdata..appdata + APPDATA_SIZE]);
+        assert_eq!(unchanged, image());
+
+        let verification = verify(file.path()).unwrap().unwrap();
+        assert_eq!(verification.md5, md5);
+        assert_eq!(verification.bad_fragment, None);
+        assert!(verification.matches());
+    }
+
+    #[test]
+    fn test_implant_refuses_existing() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), image()).unwrap();
+        let first = implant(file.path(), false).unwrap();
+
+        // A rebuilt payload behind the old checksum
+        let mut image = std::fs::read(file.path()).unwrap();
+        image[30 * 2048] = 0x55;
+        std::fs::write(file.path(), &image).unwrap();
+        assert!(!verify(file.path()).unwrap().unwrap().matches());
+
+        let refused = implant(file.path(), false).unwrap_err();
+        assert_eq!(
+            refused.message(),
+            format!("Already has a media checksum ({})", first.md5)
+        );
+        assert_eq!(std::fs::read(file.path()).unwrap(), image);
+
+        let replaced = implant(file.path(), true).unwrap();
+        assert_ne!(replaced.md5, first.md5);
+        assert!(verify(file.path()).unwrap().unwrap().matches());
+    }
+
+    #[test]
+    fn test_truncated_image() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut short = image();
+        short.truncate(40 * 2048);
+        std::fs::write(file.path(), &short).unwrap();
+
+        let error = implant(file.path(), false).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "Image ends before its volume size (131072 bytes)"
+        );
+        assert_eq!(std::fs::read(file.path()).unwrap(), short);
+
+        // Implanted whole, then cut short (an interrupted copy)
+        std::fs::write(file.path(), image()).unwrap();
+        implant(file.path(), false).unwrap();
+        let mut cut = std::fs::read(file.path()).unwrap();
+        cut.truncate(45 * 2048);
+        std::fs::write(file.path(), &cut).unwrap();
+        assert!(embedded(file.path()).unwrap().is_some());
+        assert!(verify(file.path()).is_err());
+
+        // Cut before the primary volume descriptor
+        cut.truncate(10 * 2048);
+        std::fs::write(file.path(), &cut).unwrap();
+        assert!(verify(file.path()).is_err());
+        assert!(implant(file.path(), true).is_err());
+    }
+}
@@ -2,19 +2,86 @@
 //!
 //! Provides inspection and verification of CPIO, EROFS, and ISO archives
 //! without requiring extraction or root privileges.
+//!
+//! The default `cli` feature builds the binary and everything it drives.
+//! Without default features the library keeps the readers and checklists;
+//! `qcow2` (disk images through qemu) and `rpmdb` (bundled SQLite) add
+//! checks, `release`, `server` and `rpc` add commands to the binary.
+//!
+//! [`prelude`] is the library surface. The other modules are crate-private,
+//! except the parsers the fuzz targets drive (`authenticode`, `cpio`,
+//! `detect`, `dtb`, `luks`, `magic`, `pe`, `uki`) and the hidden `cli`
+//! module the binary calls.
 
-pub mod checklist;
+// Much of the crate only serves the command line; without it, that code is
+// unreachable rather than dead
+#![cfg_attr(not(feature = "cli"), allow(dead_code, unused_imports))]
+
+pub(crate) mod arch;
+pub mod authenticode;
+pub(crate) mod boot;
+pub(crate) mod bootimg;
+pub(crate) mod bootlog;
+pub(crate) mod cache;
+pub(crate) mod catalog;
+pub(crate) mod checklist;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod cli;
 pub mod cpio;
-pub mod erofs;
-pub mod error;
-pub mod iso;
+pub mod detect;
+pub(crate) mod diff;
+pub(crate) mod dir;
+pub(crate) mod doctor;
+pub mod dtb;
+pub(crate) mod erofs;
+pub(crate) mod error;
+pub(crate) mod fat;
+pub(crate) mod fixit;
+pub(crate) mod ignore;
+pub(crate) mod iso;
+#[cfg(feature = "release")]
+pub(crate) mod isomd5;
+pub mod luks;
+pub mod magic;
+pub(crate) mod metrics;
+pub(crate) mod names;
+#[cfg(feature = "qcow2")]
+pub(crate) mod nbd;
+pub(crate) mod oci;
+pub(crate) mod overlay;
+pub mod pe;
+pub mod prelude;
+pub(crate) mod profile;
+pub(crate) mod provenance;
+#[cfg(feature = "qcow2")]
+pub(crate) mod qcow2;
+pub(crate) mod reader;
+#[cfg(feature = "release")]
+pub(crate) mod release;
+pub(crate) mod report;
+#[cfg(feature = "rpc")]
+pub(crate) mod rpc;
+pub(crate) mod sanitize;
+#[cfg(feature = "server")]
+pub(crate) mod server;
+#[cfg(feature = "release")]
+pub(crate) mod signature;
+pub(crate) mod sizes;
+pub(crate) mod spec;
+pub(crate) mod tar;
+pub(crate) mod timeline;
+pub mod uki;
 
 pub use error::{ErrorCode, FsdbgError};
 
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 
 /// Archive format detection
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ArchiveFormat {
     /// CPIO archive (gzip compressed)
     CpioGzip,
@@ -38,65 +105,157 @@ impl std::fmt::Display for ArchiveFormat {
 }
 
 /// Detect archive format from file
+///
+/// Picks the most confident match from the [`detect`] registry. Formats
+/// fsdbg recognises but can't open (squashfs, qcow2, ...) are reported as
+/// unsupported rather than undetected.
 pub fn detect_format(path: &Path) -> Result<ArchiveFormat, FsdbgError> {
-    use std::fs::File;
-    use std::io::Read;
-
-    if !path.exists() {
-        return Err(FsdbgError::file_not_found(path));
+    match detect::probe(path)?.first() {
+        Some(best) => best.detector.format.ok_or_else(|| {
+            FsdbgError::unsupported_format(format!(
+                "{} (fsdbg cannot open this format)",
+                best.detector.name
+            ))
+            .with_path(path)
+        }),
+        None => Err(FsdbgError::invalid_format(format!(
+            "Could not detect format for: {}",
+            path.display()
+        ))),
     }
+}
 
-    let mut file = File::open(path)?;
-    let mut magic = [0u8; 1024 + 4]; // Need up to offset 1024 for EROFS
-    let bytes_read = file.read(&mut magic)?;
+/// Byte range of an archive embedded in a larger file, such as the initrd
+/// section of a UKI or a CPIO at an offset in a flash dump.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ByteRange {
+    pub offset: u64,
+    /// Length in bytes; None runs to the end of the file
+    pub length: Option<u64>,
+}
 
-    // Check gzip magic (1f 8b)
-    if bytes_read >= 2 && magic[0] == 0x1f && magic[1] == 0x8b {
-        return Ok(ArchiveFormat::CpioGzip);
+impl ByteRange {
+    pub fn new(offset: u64, length: Option<u64>) -> Self {
+        Self { offset, length }
     }
 
-    // Check CPIO newc magic "070701"
-    if bytes_read >= 6 {
-        let cpio_magic = std::str::from_utf8(&magic[0..6]).unwrap_or("");
-        if cpio_magic == "070701" || cpio_magic == "070702" {
-            return Ok(ArchiveFormat::Cpio);
-        }
+    /// True if the range covers the whole file.
+    pub fn is_whole(&self) -> bool {
+        self.offset == 0 && self.length.is_none()
     }
 
-    // Check ISO 9660 magic at offset 0x8001 ("CD001")
-    // But first check simpler extension-based detection for .iso files
-    if let Some(ext) = path.extension() {
-        if ext == "iso" {
-            // Read at offset 0x8001
-            use std::io::Seek;
-            file.seek(std::io::SeekFrom::Start(0x8001))?;
-            let mut iso_magic = [0u8; 5];
-            if file.read(&mut iso_magic)? == 5 && &iso_magic == b"CD001" {
-                return Ok(ArchiveFormat::Iso);
+    /// Open the range of `path` as a stream, checking it lies within the file.
+    pub fn open(&self, path: &Path) -> Result<std::io::Take<File>, FsdbgError> {
+        let mut file = File::open(path).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                FsdbgError::file_not_found(path)
+            } else {
+                FsdbgError::from(e)
             }
+        })?;
+
+        let size = file.metadata()?.len();
+        let end = self.offset.checked_add(self.length.unwrap_or(0));
+        if self.offset > size || end.is_none_or(|end| end > size) {
+            return Err(FsdbgError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Byte range {}+{} is outside the file ({} bytes)",
+                    self.offset,
+                    self.length.map_or("EOF".to_string(), |l| l.to_string()),
+                    size
+                ),
+            )
+            .with_path(path));
         }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        Ok(file.take(self.length.unwrap_or(size - self.offset)))
     }
+}
 
-    // Check EROFS magic at offset 1024 (0xe2f5 in little-endian)
-    if bytes_read >= 1024 + 4 {
-        let erofs_magic = u32::from_le_bytes([magic[1024], magic[1025], magic[1026], magic[1027]]);
-        if erofs_magic == 0xe0f5e1e2 {
-            return Ok(ArchiveFormat::Erofs);
-        }
+/// Where the initramfs sits in a UKI (its `.initrd` section) or an Android
+/// boot image (its ramdisk); None for any other file.
+pub fn embedded_initrd(path: &Path) -> Result<Option<ByteRange>, FsdbgError> {
+    if uki::is_pe(path)? {
+        uki::initrd_range(path).map(Some)
+    } else if bootimg::is_boot_img(path)? {
+        bootimg::ramdisk_range(path).map(Some)
+    } else {
+        Ok(None)
     }
+}
 
-    // Fallback: check file extension
-    if let Some(ext) = path.extension() {
-        match ext.to_str() {
-            Some("img") | Some("cpio") => return Ok(ArchiveFormat::Cpio),
-            Some("erofs") => return Ok(ArchiveFormat::Erofs),
-            Some("iso") => return Ok(ArchiveFormat::Iso),
-            _ => {}
+/// Copy a stream (typically stdin) to a temporary file.
+///
+/// The readers seek, and ISO/EROFS need external tools that take a path, so
+/// piped artifacts are spooled to disk first. Compressed input needs no
+/// special handling: detection sees the same bytes a file would have. The
+/// file is deleted when the returned handle is dropped.
+#[cfg(feature = "cli")]
+pub(crate) fn spool<R: Read>(input: R) -> Result<tempfile::NamedTempFile, FsdbgError> {
+    spool_with_prefix("fsdbg-stdin-", input)
+}
+
+/// Copy an embedded archive to a temporary file.
+///
+/// CPIO archives can be read in place with
+/// [`cpio::CpioReader::open_range`]; ISO and EROFS images go through external
+/// tools that only take whole files, so they are carved out first.
+#[cfg(feature = "cli")]
+pub(crate) fn carve(path: &Path, range: ByteRange) -> Result<tempfile::NamedTempFile, FsdbgError> {
+    spool_with_prefix("fsdbg-carved-", range.open(path)?)
+}
+
+#[cfg(feature = "cli")]
+fn spool_with_prefix<R: Read>(
+    prefix: &str,
+    mut input: R,
+) -> Result<tempfile::NamedTempFile, FsdbgError> {
+    let mut file = tempfile::Builder::new().prefix(prefix).tempfile()?;
+    std::io::copy(&mut input, &mut file)?;
+    Ok(file)
+}
+
+/// A private (0700) directory under the system temp dir, removed with its
+/// contents when dropped.
+///
+/// What the core needs from `tempfile` to hand mtools a place to copy into,
+/// without depending on it outside the cli/server features.
+pub(crate) struct ScratchDir(std::path::PathBuf);
+
+impl ScratchDir {
+    pub(crate) fn new(prefix: &str) -> Result<Self, FsdbgError> {
+        use std::os::unix::fs::DirBuilderExt;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        loop {
+            let path = std::env::temp_dir().join(format!(
+                "{}{}-{}-{:08x}",
+                prefix,
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed),
+                nanos
+            ));
+            match std::fs::DirBuilder::new().mode(0o700).create(&path) {
+                Ok(()) => return Ok(Self(path)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
         }
     }
 
-    Err(FsdbgError::invalid_format(format!(
-        "Could not detect format for: {}",
-        path.display()
-    )))
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
 }
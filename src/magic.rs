@@ -0,0 +1,217 @@
+//! File magic: what an entry's content is
+//!
+//! Presence checks only see names, so a zero-byte `boot/vmlinuz` or an
+//! `EFI/Linux/*.efi` that is really a shell script passes them and fails at
+//! boot. [`FileKind::identify`] classifies content from its first bytes
+//! ([`HEAD_LEN`] are enough), the way `file(1)` does for the handful of
+//! types boot artifacts are made of.
+
+use crate::arch::Arch;
+use crate::checklist::{CheckCategory, CheckResult};
+use std::fmt;
+
+/// Bytes of content [`FileKind::identify`] looks at. Covers the x86 boot
+/// header at 0x202 and the PE header an MZ stub points to.
+pub const HEAD_LEN: usize = 4096;
+
+/// Content type of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileKind {
+    Empty,
+    Elf,
+    /// x86 Linux kernel (boot protocol header `HdrS`)
+    BzImage,
+    /// arm64 Linux kernel (`ARM\x64` at 0x38)
+    Arm64Image,
+    /// PE executable: EFI applications, UKIs
+    Pe,
+    /// `#!` interpreter script
+    Script,
+    Gzip,
+    Xz,
+    Zstd,
+    /// Uncompressed newc CPIO archive
+    Cpio,
+    Text,
+    /// Anything else
+    Data,
+}
+
+impl FileKind {
+    pub const ALL: [FileKind; 12] = [
+        FileKind::Empty,
+        FileKind::Elf,
+        FileKind::BzImage,
+        FileKind::Arm64Image,
+        FileKind::Pe,
+        FileKind::Script,
+        FileKind::Gzip,
+        FileKind::Xz,
+        FileKind::Zstd,
+        FileKind::Cpio,
+        FileKind::Text,
+        FileKind::Data,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FileKind::Empty => "empty",
+            FileKind::Elf => "elf",
+            FileKind::BzImage => "bzimage",
+            FileKind::Arm64Image => "arm64-image",
+            FileKind::Pe => "pe",
+            FileKind::Script => "script",
+            FileKind::Gzip => "gzip",
+            FileKind::Xz => "xz",
+            FileKind::Zstd => "zstd",
+            FileKind::Cpio => "cpio",
+            FileKind::Text => "text",
+            FileKind::Data => "data",
+        }
+    }
+
+    pub fn parse_name(s: &str) -> Option<Self> {
+        let s = s.to_lowercase();
+        Self::ALL.into_iter().find(|kind| kind.name() == s)
+    }
+
+    /// Classify `content`; only its first [`HEAD_LEN`] bytes are looked at.
+    ///
+    /// Kernels are recognised before PE, since an EFI-stub kernel is a PE
+    /// image too.
+    pub fn identify(content: &[u8]) -> Self {
+        let head = &content[..content.len().min(HEAD_LEN)];
+        if head.is_empty() {
+            FileKind::Empty
+        } else if head.get(0x202..0x206) == Some(b"HdrS") {
+            FileKind::BzImage
+        } else if head.get(0x38..0x3c) == Some(b"ARM\x64") {
+            FileKind::Arm64Image
+        } else if crate::uki::is_pe_header(head) {
+            FileKind::Pe
+        } else if head.starts_with(b"\x7fELF") {
+            FileKind::Elf
+        } else if head.starts_with(b"#!") {
+            FileKind::Script
+        } else if head.starts_with(&[0x1f, 0x8b]) {
+            FileKind::Gzip
+        } else if head.starts_with(b"\xfd7zXZ\0") {
+            FileKind::Xz
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            FileKind::Zstd
+        } else if head.starts_with(b"070701") || head.starts_with(b"070702") {
+            FileKind::Cpio
+        } else if is_text(head) {
+            FileKind::Text
+        } else {
+            FileKind::Data
+        }
+    }
+
+    pub fn is_compressed(self) -> bool {
+        matches!(self, FileKind::Gzip | FileKind::Xz | FileKind::Zstd)
+    }
+
+    /// Kernel image type for `arch`.
+    pub fn kernel(arch: Arch) -> Self {
+        match arch {
+            Arch::X86_64 => FileKind::BzImage,
+            Arch::Aarch64 => FileKind::Arm64Image,
+        }
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// UTF-8 without NULs or control characters other than whitespace and
+/// escape. A multi-byte character cut off at the end of `head` is fine.
+fn is_text(head: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    valid
+        && head
+            .iter()
+            .all(|&b| b >= 0x20 || matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+}
+
+/// Check that `content` is one of `expected`, reported as `item`.
+pub fn check(
+    item: &str,
+    category: CheckCategory,
+    content: &[u8],
+    expected: &[FileKind],
+) -> CheckResult {
+    let actual = FileKind::identify(content);
+    if expected.contains(&actual) {
+        return CheckResult::pass(item, category);
+    }
+    let expected: Vec<&str> = expected.iter().map(|kind| kind.name()).collect();
+    let found = match actual {
+        FileKind::Empty => "Empty file".to_string(),
+        kind => format!("Content is {}", kind),
+    };
+    CheckResult::fail(
+        item,
+        category,
+        format!("{}, expected {}", found, expected.join(" or ")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify() {
+        let mut bzimage = vec![0u8; 0x300];
+        bzimage[..2].copy_from_slice(b"MZ");
+        bzimage[0x202..0x206].copy_from_slice(b"HdrS");
+        assert_eq!(FileKind::identify(&bzimage), FileKind::BzImage);
+
+        let mut arm64 = vec![0u8; 0x40];
+        arm64[0x38..0x3c].copy_from_slice(b"ARM\x64");
+        assert_eq!(FileKind::identify(&arm64), FileKind::Arm64Image);
+
+        let mut pe = vec![0u8; 0x100];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c] = 0x80;
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        assert_eq!(FileKind::identify(&pe), FileKind::Pe);
+
+        assert_eq!(FileKind::identify(b""), FileKind::Empty);
+        assert_eq!(FileKind::identify(b"\x7fELF\x02\x01"), FileKind::Elf);
+        assert_eq!(FileKind::identify(b"#!/bin/sh\n"), FileKind::Script);
+        assert_eq!(FileKind::identify(&[0x1f, 0x8b, 0x08]), FileKind::Gzip);
+        assert_eq!(FileKind::identify(b"070701000000"), FileKind::Cpio);
+        assert_eq!(FileKind::identify("Grüße\n".as_bytes()), FileKind::Text);
+        assert_eq!(FileKind::identify(&"ü".as_bytes()[..1]), FileKind::Text);
+        assert_eq!(FileKind::identify(b"MZ\0\0"), FileKind::Data);
+
+        let placeholder = check(
+            "EFI/Linux/levitate.efi",
+            CheckCategory::Binary,
+            b"",
+            &[FileKind::Pe],
+        );
+        assert_eq!(
+            placeholder.message.as_deref(),
+            Some("Empty file, expected pe")
+        );
+        assert!(
+            check(
+                "boot/vmlinuz",
+                CheckCategory::Binary,
+                &bzimage,
+                &[FileKind::kernel(Arch::X86_64)]
+            )
+            .passed
+        );
+        assert_eq!(FileKind::parse_name("PE"), Some(FileKind::Pe));
+    }
+}
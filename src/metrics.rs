@@ -0,0 +1,185 @@
+//! Prometheus metrics export
+//!
+//! Renders archive statistics and checklist results in the Prometheus text
+//! exposition format, suitable for node_exporter's textfile collector:
+//! - `fsdbg_archive_bytes` - size of the artifact on disk
+//! - `fsdbg_entries` - entry counts by type
+//! - `fsdbg_content_bytes` - regular file bytes by top-level directory
+//! - `fsdbg_check_results` - checklist pass/fail counts by category
+//! - `fsdbg_checklist_success` - 1 if every check passed
+//!
+//! Every sample carries an `artifact` label (the file name) so several
+//! artifacts can share one dashboard.
+
+use crate::checklist::VerificationReport;
+use crate::diff::{EntryKind, EntryMap};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// One metric family: HELP/TYPE header plus labelled samples.
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    samples: Vec<(Vec<(&'static str, String)>, u64)>,
+}
+
+/// Collection of gauge metrics, rendered in insertion order.
+#[derive(Default)]
+pub struct Metrics {
+    families: Vec<Family>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a gauge sample, creating its family on first use.
+    pub fn gauge(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&'static str, &str)],
+        value: u64,
+    ) {
+        let labels = labels.iter().map(|(k, v)| (*k, v.to_string())).collect();
+        match self.families.iter_mut().find(|f| f.name == name) {
+            Some(family) => family.samples.push((labels, value)),
+            None => self.families.push(Family {
+                name,
+                help,
+                samples: vec![(labels, value)],
+            }),
+        }
+    }
+
+    /// Render in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for family in &self.families {
+            let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+            let _ = writeln!(out, "# TYPE {} gauge", family.name);
+            for (labels, value) in &family.samples {
+                let labels: Vec<String> = labels
+                    .iter()
+                    .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+                    .collect();
+                let _ = writeln!(out, "{}{{{}}} {}", family.name, labels.join(","), value);
+            }
+        }
+        out
+    }
+
+    /// Record entry counts and content sizes for an archive.
+    pub fn add_entries(&mut self, artifact: &str, entries: &EntryMap) {
+        let mut counts: BTreeMap<&str, u64> = BTreeMap::new();
+        let mut sizes: BTreeMap<String, u64> = BTreeMap::new();
+
+        for (path, entry) in entries {
+            let kind = match entry.kind {
+                EntryKind::File => "file",
+                EntryKind::Directory => "directory",
+                EntryKind::Symlink => "symlink",
+                EntryKind::Other => "other",
+            };
+            *counts.entry(kind).or_default() += 1;
+            if entry.kind == EntryKind::File {
+                *sizes.entry(size_bucket(path)).or_default() += entry.size;
+            }
+        }
+
+        for (kind, count) in counts {
+            self.gauge(
+                "fsdbg_entries",
+                "Number of archive entries by type",
+                &[("artifact", artifact), ("type", kind)],
+                count,
+            );
+        }
+        for (dir, bytes) in sizes {
+            self.gauge(
+                "fsdbg_content_bytes",
+                "Regular file bytes by top-level directory",
+                &[("artifact", artifact), ("dir", &dir)],
+                bytes,
+            );
+        }
+    }
+
+    /// Record checklist pass/fail counts by category.
+    pub fn add_report(&mut self, artifact: &str, report: &VerificationReport) {
+        for (category, results) in report.by_category() {
+            let failed = results.iter().filter(|r| r.is_failure()).count() as u64;
+            let skipped = results.iter().filter(|r| r.is_skipped()).count() as u64;
+            let passed = results.len() as u64 - failed - skipped;
+            let category = category.to_string();
+            for (result, count) in [("pass", passed), ("fail", failed), ("skip", skipped)] {
+                self.gauge(
+                    "fsdbg_check_results",
+                    "Checklist results by category",
+                    &[
+                        ("artifact", artifact),
+                        ("checklist", &report.artifact_type),
+                        ("category", &category),
+                        ("result", result),
+                    ],
+                    count,
+                );
+            }
+        }
+        self.gauge(
+            "fsdbg_checklist_success",
+            "1 if every checklist item passed",
+            &[("artifact", artifact), ("checklist", &report.artifact_type)],
+            report.is_success() as u64,
+        );
+    }
+}
+
+/// Group sizes by first path component, or first two under `usr/`
+/// (`usr/bin`, `usr/lib64`) where the interesting split is.
+fn size_bucket(path: &str) -> String {
+    let mut parts = path.split('/');
+    let first = parts.next().unwrap_or_default();
+    match (first, parts.next()) {
+        // Files directly in the root (e.g. `init`) get their own bucket
+        (_, None) => "/".to_string(),
+        ("usr", Some(second)) if parts.next().is_some() => format!("usr/{}", second),
+        _ => first.to_string(),
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut metrics = Metrics::new();
+        metrics.gauge("fsdbg_x", "Test", &[("artifact", "a\"b.img")], 3);
+        metrics.gauge("fsdbg_x", "Test", &[("artifact", "c.img")], 4);
+        assert_eq!(
+            metrics.render(),
+            "# HELP fsdbg_x Test\n\
+             # TYPE fsdbg_x gauge\n\
+             fsdbg_x{artifact=\"a\\\"b.img\"} 3\n\
+             fsdbg_x{artifact=\"c.img\"} 4\n"
+        );
+    }
+
+    #[test]
+    fn test_size_bucket() {
+        assert_eq!(size_bucket("usr/bin/ls"), "usr/bin");
+        assert_eq!(size_bucket("usr/lib64/security/pam_unix.so"), "usr/lib64");
+        assert_eq!(size_bucket("etc/passwd"), "etc");
+        assert_eq!(size_bucket("usr/README"), "usr");
+        assert_eq!(size_bucket("init"), "/");
+    }
+}
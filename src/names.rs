@@ -0,0 +1,66 @@
+//! Entry names as raw bytes
+//!
+//! Archive formats store names as bytes, not UTF-8. Readers keep the raw
+//! bytes alongside a display string in which each byte that isn't valid
+//! UTF-8 is written as `\xNN`. Unlike a lossy conversion (which turns
+//! every invalid byte into U+FFFD), two names that differ only in invalid
+//! bytes stay distinct, so lookups by the escaped form find the right entry.
+
+/// Display form of a raw name: valid UTF-8 as-is, other bytes as `\xNN`.
+pub fn escape_bytes(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        out.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    out
+}
+
+/// Raw bytes of a name produced by [`escape_bytes`].
+pub fn unescape_bytes(name: &str) -> Vec<u8> {
+    let bytes = name.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 2..i + 4)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], bytes.get(i + 1), hex) {
+            (b'\\', Some(b'x'), Some(byte)) => {
+                out.push(byte);
+                i += 4;
+            }
+            (byte, _, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// True if the raw name isn't valid UTF-8.
+pub fn is_non_utf8(raw: &[u8]) -> bool {
+    std::str::from_utf8(raw).is_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_round_trip() {
+        let raw = b"usr/share/caf\xe9/\xff\xfe.txt";
+        let escaped = escape_bytes(raw);
+        assert_eq!(escaped, "usr/share/caf\\xe9/\\xff\\xfe.txt");
+        assert_eq!(unescape_bytes(&escaped), raw);
+        assert!(is_non_utf8(raw));
+
+        assert_eq!(escape_bytes("usr/bin/ls".as_bytes()), "usr/bin/ls");
+        assert_eq!(escape_bytes("é".as_bytes()), "é");
+        assert!(!is_non_utf8("é".as_bytes()));
+    }
+}
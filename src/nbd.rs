@@ -0,0 +1,157 @@
+//! Bookkeeping for the qemu-nbd connections of `verify --type qcow2 --nbd`
+//!
+//! Each connection is recorded in a state file while it is up, with the pids
+//! of the fsdbg run that made it and of the supervisor process that tears it
+//! down. A record whose processes are both gone belongs to a run that was
+//! killed before its supervisor could clean up; `fsdbg cleanup-nbd` (and the
+//! next `--nbd` run) disconnects it.
+
+use crate::error::FsdbgError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A qemu-nbd connection made by fsdbg
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NbdConnection {
+    /// `/dev/nbdN`
+    pub device: String,
+    pub image: PathBuf,
+    /// Temporary directory holding the `root` and `boot` mount points
+    pub mount_dir: PathBuf,
+    /// Whether the commands go through sudo
+    pub sudo: bool,
+    /// The fsdbg run that connected the device
+    pub owner_pid: u32,
+    /// The process that disconnects it once the owner exits
+    pub supervisor_pid: Option<u32>,
+    /// dm-crypt mapping of an unlocked LUKS root partition
+    #[serde(default)]
+    pub luks_mapping: Option<String>,
+    /// A recovery partition is mounted at `mount_dir/recovery`
+    #[serde(default)]
+    pub recovery: bool,
+}
+
+impl NbdConnection {
+    pub fn root_mount(&self) -> PathBuf {
+        self.mount_dir.join("root")
+    }
+
+    pub fn boot_mount(&self) -> PathBuf {
+        self.mount_dir.join("boot")
+    }
+
+    /// Neither the owner nor its supervisor is left to disconnect it.
+    pub fn is_stale(&self) -> bool {
+        !alive(self.owner_pid) && self.supervisor_pid.is_none_or(|pid| !alive(pid))
+    }
+}
+
+/// Whether process `pid` exists (possibly owned by another user).
+pub fn alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    let found = unsafe { libc::kill(pid, 0) } == 0;
+    found || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Directory of connection records
+pub struct NbdState {
+    dir: PathBuf,
+}
+
+impl NbdState {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `$XDG_RUNTIME_DIR/fsdbg/nbd`, which goes away on reboot like the
+    /// connections do; a per-user directory under the temp dir without it.
+    pub fn default_dir() -> PathBuf {
+        match std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+            Some(dir) => Path::new(&dir).join("fsdbg").join("nbd"),
+            None => {
+                let uid = unsafe { libc::getuid() };
+                std::env::temp_dir().join(format!("fsdbg-nbd-{}", uid))
+            }
+        }
+    }
+
+    /// Record file for `device`.
+    pub fn record_path(&self, device: &str) -> PathBuf {
+        let name = device.rsplit('/').next().unwrap_or(device);
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// Write (or rewrite) the record of a connection.
+    pub fn save(&self, connection: &NbdConnection) -> Result<PathBuf, FsdbgError> {
+        fs::create_dir_all(&self.dir).map_err(|e| FsdbgError::from(e).with_path(&self.dir))?;
+        let path = self.record_path(&connection.device);
+        let json = serde_json::to_vec_pretty(connection)
+            .map_err(|e| FsdbgError::new(crate::ErrorCode::IoError, e.to_string()))?;
+        fs::write(&path, json).map_err(|e| FsdbgError::from(e).with_path(&path))?;
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<NbdConnection, FsdbgError> {
+        let json = fs::read(path).map_err(|e| FsdbgError::from(e).with_path(path))?;
+        serde_json::from_slice(&json).map_err(|e| {
+            FsdbgError::new(crate::ErrorCode::ParseError, e.to_string()).with_path(path)
+        })
+    }
+
+    /// Every recorded connection, by device. Unreadable records are skipped.
+    pub fn connections(&self) -> Vec<NbdConnection> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut connections: Vec<_> = entries
+            .flatten()
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|e| Self::load(&e.path()).ok())
+            .collect();
+        connections.sort_by(|a, b| a.device.cmp(&b.device));
+        connections
+    }
+
+    pub fn remove(&self, device: &str) {
+        let _ = fs::remove_file(self.record_path(device));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_roundtrip_and_staleness() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = NbdState::new(dir.path().join("nbd"));
+        let mut connection = NbdConnection {
+            device: "/dev/nbd3".to_string(),
+            image: PathBuf::from("disk.qcow2"),
+            mount_dir: dir.path().join("mnt"),
+            sudo: true,
+            owner_pid: std::process::id(),
+            supervisor_pid: None,
+            luks_mapping: None,
+            recovery: false,
+        };
+        let path = state.save(&connection).unwrap();
+        assert!(path.ends_with("nbd3.json"));
+        assert_eq!(state.connections(), vec![connection.clone()]);
+        assert!(!connection.is_stale());
+
+        let mut exited = std::process::Command::new("true").spawn().unwrap();
+        exited.wait().unwrap();
+        connection.owner_pid = exited.id();
+        assert!(connection.is_stale());
+        connection.supervisor_pid = Some(std::process::id());
+        assert!(!connection.is_stale());
+
+        state.remove("/dev/nbd3");
+        assert!(state.connections().is_empty());
+    }
+}
@@ -0,0 +1,526 @@
+//! Container images (OCI image layouts, docker save archives)
+//!
+//! The image build also publishes the rootfs as a container image: an OCI
+//! image layout (`oci-layout`, `index.json`, `blobs/sha256/`) or a `docker
+//! save` archive (`manifest.json` and its layer tarballs), as a directory or
+//! a tarball of one. [`Image::flatten`] stacks the layers into a directory
+//! as a container runtime would - OCI whiteouts delete what lower layers
+//! put there - so checklists run on the root filesystem the container sees.
+//!
+//! OCI layers are checked against their manifest digests before they're
+//! unpacked. Layers are read by their magic: plain tar and gzip natively,
+//! zstd through the `zstd` tool.
+
+use crate::arch::Arch;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::sanitize::Destination;
+use crate::tar::Owners;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Marks an OCI image layout
+pub const OCI_LAYOUT: &str = "oci-layout";
+
+/// Entry point of an OCI image layout
+pub const OCI_INDEX: &str = "index.json";
+
+/// Entry point of a docker save archive
+pub const DOCKER_MANIFEST: &str = "manifest.json";
+
+/// Media types of descriptors that list manifests rather than layers
+const INDEX_MEDIA_TYPES: &[&str] = &[
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+];
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Oci,
+    Docker,
+}
+
+impl Layout {
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Oci => "OCI image layout",
+            Layout::Docker => "docker save archive",
+        }
+    }
+}
+
+/// A layer blob, and the digest its manifest records for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layer {
+    pub path: PathBuf,
+    pub digest: Option<String>,
+}
+
+/// A container image's layers, bottom first
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub layout: Layout,
+    /// Tag (`RepoTags`, `org.opencontainers.image.ref.name`), if it has one
+    pub name: Option<String>,
+    pub layers: Vec<Layer>,
+}
+
+/// True if `dir` holds an OCI image layout or a docker save archive.
+pub fn is_image(dir: &Path) -> bool {
+    dir.join(OCI_LAYOUT).is_file() || dir.join(DOCKER_MANIFEST).is_file()
+}
+
+/// True if a tar archive with these entry names is a container image.
+pub fn is_image_archive(names: &[String]) -> bool {
+    names.iter().any(|name| {
+        let name = crate::cpio::CpioReader::normalize_path(name);
+        name == OCI_LAYOUT || name == DOCKER_MANIFEST
+    })
+}
+
+/// Image platforms go by Go's architecture names
+fn goarch(arch: Arch) -> &'static str {
+    match arch {
+        Arch::X86_64 => "amd64",
+        Arch::Aarch64 => "arm64",
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value, FsdbgError> {
+    let text = fs::read(path).map_err(|e| FsdbgError::from(e).with_path(path))?;
+    serde_json::from_slice(&text)
+        .map_err(|e| FsdbgError::invalid_format(format!("Invalid JSON: {}", e)).with_path(path))
+}
+
+impl Image {
+    /// Read the image in `dir`. `arch` picks the manifest of a
+    /// multi-platform image.
+    pub fn open(dir: &Path, arch: Option<Arch>) -> Result<Self, FsdbgError> {
+        if dir.join(OCI_LAYOUT).is_file() {
+            Self::open_oci(dir, arch)
+        } else if dir.join(DOCKER_MANIFEST).is_file() {
+            Self::open_docker(dir)
+        } else {
+            Err(FsdbgError::invalid_format(format!(
+                "Not a container image (no {} or {})",
+                OCI_LAYOUT, DOCKER_MANIFEST
+            ))
+            .with_path(dir))
+        }
+    }
+
+    fn open_oci(dir: &Path, arch: Option<Arch>) -> Result<Self, FsdbgError> {
+        let mut index = read_json(&dir.join(OCI_INDEX))?;
+        let mut name = None;
+        // An index may point at a further index (a multi-platform image)
+        for _ in 0..4 {
+            let descriptor = pick_manifest(&index, arch)?;
+            name = name.or_else(|| {
+                descriptor["annotations"]["org.opencontainers.image.ref.name"]
+                    .as_str()
+                    .map(str::to_string)
+            });
+            let blob = blob_path(dir, &descriptor)?;
+            let manifest = read_json(&blob)?;
+            let media_type = descriptor["mediaType"]
+                .as_str()
+                .or_else(|| manifest["mediaType"].as_str());
+            if media_type.is_some_and(|t| INDEX_MEDIA_TYPES.contains(&t))
+                || manifest["manifests"].is_array()
+            {
+                index = manifest;
+                continue;
+            }
+            let layers = manifest["layers"]
+                .as_array()
+                .ok_or_else(|| {
+                    FsdbgError::invalid_format("Image manifest lists no layers").with_path(&blob)
+                })?
+                .iter()
+                .map(|layer| {
+                    Ok(Layer {
+                        path: blob_path(dir, layer)?,
+                        digest: layer["digest"].as_str().map(str::to_string),
+                    })
+                })
+                .collect::<Result<_, FsdbgError>>()?;
+            return Ok(Self {
+                layout: Layout::Oci,
+                name,
+                layers,
+            });
+        }
+        Err(FsdbgError::invalid_format("Image indexes nest too deeply").with_path(dir))
+    }
+
+    fn open_docker(dir: &Path) -> Result<Self, FsdbgError> {
+        let path = dir.join(DOCKER_MANIFEST);
+        let manifest = read_json(&path)?;
+        let images = manifest
+            .as_array()
+            .filter(|images| !images.is_empty())
+            .ok_or_else(|| {
+                FsdbgError::invalid_format("No images in manifest.json").with_path(&path)
+            })?;
+        if images.len() > 1 {
+            return Err(FsdbgError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "The archive holds {} images; save one image to verify it",
+                    images.len()
+                ),
+            )
+            .with_path(&path));
+        }
+        let image = &images[0];
+        let layers = image["Layers"]
+            .as_array()
+            .ok_or_else(|| {
+                FsdbgError::invalid_format("manifest.json lists no layers").with_path(&path)
+            })?
+            .iter()
+            .map(|layer| {
+                let layer = layer.as_str().unwrap_or_default();
+                Ok(Layer {
+                    // Names come from the archive: keep them inside it
+                    path: dir.join(crate::sanitize::entry_path(layer)?),
+                    digest: None,
+                })
+            })
+            .collect::<Result<_, FsdbgError>>()?;
+        Ok(Self {
+            layout: Layout::Docker,
+            name: image["RepoTags"][0].as_str().map(str::to_string),
+            layers,
+        })
+    }
+
+    /// Unpack the layers into `dest`, bottom first. Returns the owners the
+    /// tar headers record, which unpacking without root doesn't keep.
+    pub fn flatten(&self, dest: &Destination) -> Result<Owners, FsdbgError> {
+        let mut owners = Owners::new();
+        for layer in &self.layers {
+            if let Some(digest) = &layer.digest {
+                verify_digest(&layer.path, digest)?;
+            }
+            let file =
+                File::open(&layer.path).map_err(|_| FsdbgError::file_not_found(&layer.path))?;
+            let mut stream = BufReader::new(file);
+            let magic = stream.fill_buf()?;
+            let locate = |e: FsdbgError| e.with_path(&layer.path);
+            if magic.starts_with(&ZSTD_MAGIC) {
+                let mut child = Command::new("zstd")
+                    .args(["-dcq"])
+                    .stdin(File::open(&layer.path)?)
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .map_err(|_| {
+                        FsdbgError::tool_missing(
+                            "zstd",
+                            "Install zstd to read zstd-compressed layers.",
+                        )
+                    })?;
+                let output = child.stdout.take().expect("piped stdout");
+                crate::tar::extract_layer(output, dest, &mut owners).map_err(locate)?;
+                let status = child.wait()?;
+                if !status.success() {
+                    return Err(locate(FsdbgError::external_tool_failed(
+                        "zstd",
+                        format!("exited with {}", status),
+                    )));
+                }
+            } else {
+                let stream = crate::cpio::decompress(stream).map_err(locate)?;
+                crate::tar::extract_layer(stream, dest, &mut owners).map_err(locate)?;
+            }
+        }
+        Ok(owners)
+    }
+}
+
+/// The manifest of an index to use: the only one, or the one for `arch`.
+fn pick_manifest(index: &Value, arch: Option<Arch>) -> Result<Value, FsdbgError> {
+    let manifests = index["manifests"].as_array().cloned().unwrap_or_default();
+    // Attestations and signatures ride along as unknown/unknown manifests
+    let images: Vec<&Value> = manifests
+        .iter()
+        .filter(|m| m["platform"]["os"].as_str() != Some("unknown"))
+        .collect();
+    let platforms = || {
+        images
+            .iter()
+            .filter_map(|m| m["platform"]["architecture"].as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match (&images[..], arch) {
+        ([], _) => Err(FsdbgError::invalid_format("Image index lists no manifests")),
+        ([only], _) => Ok((*only).clone()),
+        (_, Some(arch)) => images
+            .iter()
+            .find(|m| m["platform"]["architecture"].as_str() == Some(goarch(arch)))
+            .map(|m| (*m).clone())
+            .ok_or_else(|| {
+                FsdbgError::missing_required(format!(
+                    "{} image (the index has {})",
+                    goarch(arch),
+                    platforms()
+                ))
+            }),
+        (_, None) => Err(FsdbgError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "The image has manifests for {}; pick one with --arch",
+                platforms()
+            ),
+        )),
+    }
+}
+
+/// Where the blob a descriptor names lives: `blobs/<algorithm>/<hex>`.
+fn blob_path(dir: &Path, descriptor: &Value) -> Result<PathBuf, FsdbgError> {
+    let digest = descriptor["digest"].as_str().unwrap_or_default();
+    // The digest becomes a path: only algorithm:hex will do
+    match digest.split_once(':') {
+        Some((algorithm, hex))
+            if !algorithm.is_empty()
+                && algorithm.bytes().all(|b| b.is_ascii_alphanumeric())
+                && !hex.is_empty()
+                && hex.bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            Ok(dir.join("blobs").join(algorithm).join(hex))
+        }
+        _ => Err(FsdbgError::invalid_format(format!("Bad digest {:?}", digest)).with_path(dir)),
+    }
+}
+
+/// Check a blob against its `sha256:<hex>` digest.
+fn verify_digest(path: &Path, digest: &str) -> Result<(), FsdbgError> {
+    let Some(expected) = digest.strip_prefix("sha256:") else {
+        return Err(
+            FsdbgError::unsupported_format(format!("layer digest {}", digest)).with_path(path),
+        );
+    };
+    let actual = crate::report::sha256_file(path).map_err(|e| e.with_path(path))?;
+    if actual != expected.to_ascii_lowercase() {
+        return Err(FsdbgError::invalid_format(format!(
+            "Layer doesn't match its digest (sha256:{}, expected {})",
+            actual, digest
+        ))
+        .with_path(path));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tar::testing::{entry, finish};
+    use serde_json::json;
+
+    #[test]
+    fn test_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("blobs/sha256")).unwrap();
+        fs::write(root.join(OCI_LAYOUT), r#"{"imageLayoutVersion":"1.0.0"}"#).unwrap();
+        let blob = |content: &[u8]| {
+            fs::write(root.join("tmp"), content).unwrap();
+            let hex = crate::report::sha256_file(&root.join("tmp")).unwrap();
+            fs::rename(root.join("tmp"), root.join("blobs/sha256").join(&hex)).unwrap();
+            format!("sha256:{}", hex)
+        };
+        let layer = blob(b"layer");
+        let manifest = blob(
+            json!({"layers": [{"digest": layer}]})
+                .to_string()
+                .as_bytes(),
+        );
+        let descriptor = |arch: &str| json!({"digest": manifest, "platform": {"os": "linux", "architecture": arch}});
+        fs::write(
+            root.join(OCI_INDEX),
+            json!({"manifests": [descriptor("amd64"), descriptor("arm64")]}).to_string(),
+        )
+        .unwrap();
+
+        assert!(is_image(root));
+        assert!(Image::open(root, None).is_err());
+        let image = Image::open(root, Some(Arch::Aarch64)).unwrap();
+        assert_eq!(image.layers.len(), 1);
+        assert_eq!(image.layers[0].digest.as_deref(), Some(layer.as_str()));
+        verify_digest(&image.layers[0].path, &layer).unwrap();
+        fs::write(&image.layers[0].path, b"tampered").unwrap();
+        assert!(verify_digest(&image.layers[0].path, &layer).is_err());
+
+        // Digests become paths
+        assert!(blob_path(root, &json!({"digest": "sha256:../../etc/passwd"})).is_err());
+        assert!(is_image_archive(&["./manifest.json".to_string()]));
+    }
+
+    /// Write a docker save archive of `layers` (tar bytes) to `root`.
+    fn docker_save(root: &Path, layers: &[Vec<u8>]) -> Image {
+        let mut names = Vec::new();
+        for (i, layer) in layers.iter().enumerate() {
+            let name = format!("{:064x}/layer.tar", i);
+            fs::create_dir_all(root.join(&name).parent().unwrap()).unwrap();
+            fs::write(root.join(&name), layer).unwrap();
+            names.push(name);
+        }
+        fs::write(
+            root.join(DOCKER_MANIFEST),
+            json!([{"Config": "config.json", "RepoTags": ["levitate:latest"], "Layers": names}])
+                .to_string(),
+        )
+        .unwrap();
+        Image::open(root, None).unwrap()
+    }
+
+    #[test]
+    fn test_docker_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let image = docker_save(root, &[Vec::new(), Vec::new()]);
+        assert_eq!(image.layout, Layout::Docker);
+        assert_eq!(image.name.as_deref(), Some("levitate:latest"));
+        assert_eq!(
+            image.layers,
+            [
+                Layer {
+                    path: root.join(format!("{:064x}/layer.tar", 0)),
+                    digest: None,
+                },
+                Layer {
+                    path: root.join(format!("{:064x}/layer.tar", 1)),
+                    digest: None,
+                },
+            ]
+        );
+
+        let open = |manifest: Value| {
+            fs::write(root.join(DOCKER_MANIFEST), manifest.to_string()).unwrap();
+            Image::open(root, None)
+        };
+        let untagged = open(json!([{"Layers": ["a/layer.tar"]}])).unwrap();
+        assert_eq!(untagged.name, None);
+        assert_eq!(
+            open(json!([{"Layers": []}, {"Layers": []}]))
+                .unwrap_err()
+                .code(),
+            ErrorCode::InvalidArgument
+        );
+        assert!(open(json!([])).is_err());
+        assert!(open(json!({"Layers": ["a/layer.tar"]})).is_err());
+        assert!(open(json!([{"Config": "config.json"}])).is_err());
+        fs::write(root.join(DOCKER_MANIFEST), "[{").unwrap();
+        assert!(Image::open(root, None)
+            .unwrap_err()
+            .message()
+            .starts_with("Invalid JSON"));
+
+        // Layer names come from the archive and must stay inside it
+        assert!(open(json!([{"Layers": ["../outside/layer.tar"]}])).is_err());
+        assert!(open(json!([{"Layers": ["/etc/layer.tar"]}])).is_err());
+    }
+
+    #[test]
+    fn test_flatten_whiteouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let image_dir = dir.path().join("image");
+        let out = dir.path().join("rootfs");
+        fs::create_dir_all(&out).unwrap();
+
+        let mut base = Vec::new();
+        entry(&mut base, "etc/", b'5', b"", "");
+        entry(&mut base, "etc/motd", b'0', b"base", "");
+        entry(&mut base, "etc/issue", b'0', b"base", "");
+        entry(&mut base, "usr/share/doc/a/README", b'0', b"a", "");
+        entry(&mut base, "usr/share/doc/b/README", b'0', b"b", "");
+        entry(&mut base, "opt/app/old.conf", b'0', b"", "");
+        finish(&mut base);
+
+        let mut update = Vec::new();
+        entry(&mut update, "etc/.wh.motd", b'0', b"", "");
+        entry(&mut update, "usr/share/doc/.wh..wh..opq", b'0', b"", "");
+        entry(&mut update, "usr/share/doc/c/README", b'0', b"c", "");
+        // What a layer writes itself survives its own whiteouts
+        entry(&mut update, "opt/app/new.conf", b'0', b"new", "");
+        entry(&mut update, "opt/app/.wh..wh..opq", b'0', b"", "");
+        entry(&mut update, "opt/app/.wh.new.conf", b'0', b"", "");
+        finish(&mut update);
+
+        // Gzipped, as registries ship layers
+        let mut top = Vec::new();
+        entry(&mut top, "etc/motd", b'0', b"top", "");
+        entry(&mut top, "etc/.wh.issue", b'0', b"", "");
+        finish(&mut top);
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut gz, &top).unwrap();
+        let top = gz.finish().unwrap();
+
+        let image = docker_save(&image_dir, &[base, update, top]);
+        let owners = image.flatten(&Destination::new(&out)).unwrap();
+        assert_eq!(fs::read(out.join("etc/motd")).unwrap(), b"top");
+        assert!(!out.join("etc/issue").exists());
+        assert!(!out.join("usr/share/doc/a").exists());
+        assert!(!out.join("usr/share/doc/b").exists());
+        assert_eq!(fs::read(out.join("usr/share/doc/c/README")).unwrap(), b"c");
+        assert!(!out.join("opt/app/old.conf").exists());
+        assert_eq!(fs::read(out.join("opt/app/new.conf")).unwrap(), b"new");
+        assert!(!out.join("etc/.wh.motd").exists());
+        assert_eq!(owners["usr/share/doc/c/README"], (0, 0));
+    }
+
+    #[test]
+    fn test_flatten_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("victim");
+        fs::write(&outside, b"keep").unwrap();
+        let flatten = |layers: &[Vec<u8>]| {
+            let image_dir = tempfile::tempdir_in(dir.path()).unwrap();
+            let out = tempfile::tempdir_in(dir.path()).unwrap();
+            let image = docker_save(image_dir.path(), layers);
+            let result = image.flatten(&Destination::new(out.path()));
+            (result, out)
+        };
+        let layer = |entries: &[(&str, u8, &str)]| {
+            let mut tar = Vec::new();
+            for (name, typeflag, link) in entries {
+                entry(&mut tar, name, *typeflag, b"x", link);
+            }
+            finish(&mut tar);
+            tar
+        };
+
+        let refused = |layers: &[Vec<u8>]| {
+            let (result, _) = flatten(layers);
+            assert!(result
+                .unwrap_err()
+                .message()
+                .starts_with("Unsafe entry name"));
+        };
+        refused(&[layer(&[("../../victim", b'0', "")])]);
+        refused(&[layer(&[("../.wh.victim", b'0', "")])]);
+        refused(&[layer(&[("stolen", b'1', "../victim")])]);
+
+        // A symlink from a lower layer can't carry later writes, whiteouts
+        // or hard links out of the root
+        let link = outside.parent().unwrap().to_str().unwrap();
+        for name in ["escape/victim", "escape/.wh.victim"] {
+            refused(&[layer(&[("escape", b'2', link)]), layer(&[(name, b'0', "")])]);
+        }
+        refused(&[layer(&[
+            ("escape", b'2', link),
+            ("stolen", b'1', "escape/victim"),
+        ])]);
+        assert_eq!(fs::read(&outside).unwrap(), b"keep");
+
+        // Absolute names land below the root
+        let (result, out) = flatten(&[layer(&[("/etc/passwd", b'0', "")])]);
+        result.unwrap();
+        assert_eq!(fs::read(out.path().join("etc/passwd")).unwrap(), b"x");
+    }
+}
@@ -0,0 +1,260 @@
+//! Live overlay tree access
+//!
+//! The live overlay is the set of files layered over the EROFS rootfs at
+//! boot (autologin drop-ins, live user, /etc/issue). It can be inspected from:
+//! - A directory on the ISO (`live/overlay/`), via `isoinfo`
+//! - A local directory (e.g. the build's overlay staging dir), with the
+//!   `cli` feature
+//!
+//! A tree staged as an overlayfs layer can also delete rootfs paths:
+//! whiteouts (`c 0:0` device nodes) hide one lower path, opaque directories
+//! (`trusted.overlay.opaque` / `user.overlay.opaque` = `y`) hide everything
+//! below a lower directory. Redirects (`trusted.overlay.redirect`) make a
+//! renamed directory take its contents from another lower path, and
+//! metacopy files (`trusted.overlay.metacopy`) carry only metadata, their
+//! data still being the lower file's. ISO directories carry neither device
+//! nodes nor xattrs, so only local directories report them.
+
+use crate::error::FsdbgError;
+use crate::iso::IsoReader;
+// Local directories (and their xattrs) are only read by the binary
+#[cfg(feature = "cli")]
+use std::{
+    ffi::CString,
+    fs,
+    os::unix::ffi::OsStrExt,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "cli")]
+/// Xattrs that mark an overlayfs directory opaque (the second with `userxattr`)
+const OPAQUE_XATTRS: &[&str] = &["trusted.overlay.opaque", "user.overlay.opaque"];
+
+#[cfg(feature = "cli")]
+/// Xattrs naming the lower path a directory (or metacopy file) was renamed
+/// from: absolute from the layer root, or a name in the lower parent
+const REDIRECT_XATTRS: &[&str] = &["trusted.overlay.redirect", "user.overlay.redirect"];
+
+#[cfg(feature = "cli")]
+/// Xattrs marking a file whose data is still the lower layer's
+const METACOPY_XATTRS: &[&str] = &["trusted.overlay.metacopy", "user.overlay.metacopy"];
+
+/// Read access to a live overlay tree.
+///
+/// Paths are relative to the overlay root, without a leading slash
+/// (e.g. `etc/issue`).
+pub trait OverlayTree {
+    /// Relative paths of every entry in the tree.
+    fn paths(&self) -> Vec<String>;
+
+    /// Read a file from the tree as UTF-8 text.
+    fn read_to_string(&self, path: &str) -> Result<String, FsdbgError>;
+
+    /// Check if a path exists in the tree.
+    fn exists(&self, path: &str) -> bool {
+        self.paths().iter().any(|p| p == path)
+    }
+
+    /// Paths that are overlayfs whiteouts.
+    fn whiteouts(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Directories marked opaque.
+    fn opaque_dirs(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Entries with a redirect, and the redirect as stored.
+    fn redirects(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Files marked metacopy.
+    fn metacopies(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Overlay stored as a directory inside an ISO image.
+pub struct IsoOverlay<'a> {
+    reader: &'a IsoReader,
+    /// Absolute ISO path with trailing slash, e.g. `/live/overlay/`
+    prefix: String,
+}
+
+impl<'a> IsoOverlay<'a> {
+    /// View the ISO directory `root` (e.g. `live/overlay`) as an overlay tree.
+    pub fn new(reader: &'a IsoReader, root: &str) -> Self {
+        Self {
+            reader,
+            prefix: format!("/{}/", root.trim_matches('/')),
+        }
+    }
+}
+
+impl OverlayTree for IsoOverlay<'_> {
+    fn paths(&self) -> Vec<String> {
+        self.reader
+            .entries()
+            .iter()
+            .filter_map(|e| e.path.strip_prefix(&self.prefix))
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn read_to_string(&self, path: &str) -> Result<String, FsdbgError> {
+        self.reader
+            .read_file_to_string(&format!("{}{}", self.prefix, path))
+    }
+}
+
+#[cfg(feature = "cli")]
+/// Overlay stored as a local directory.
+pub struct DirOverlay {
+    root: PathBuf,
+}
+
+#[cfg(feature = "cli")]
+impl DirOverlay {
+    pub fn open(root: &Path) -> Result<Self, FsdbgError> {
+        if !root.is_dir() {
+            return Err(FsdbgError::file_not_found(root));
+        }
+        Ok(Self {
+            root: root.to_path_buf(),
+        })
+    }
+
+    fn collect(&self, dir: &Path, paths: &mut Vec<String>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if let Ok(rel) = path.strip_prefix(&self.root) {
+                paths.push(rel.to_string_lossy().to_string());
+            }
+            // Don't follow symlinked directories out of the tree
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                self.collect(&path, paths);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+impl OverlayTree for DirOverlay {
+    fn paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        self.collect(&self.root, &mut paths);
+        paths
+    }
+
+    fn read_to_string(&self, path: &str) -> Result<String, FsdbgError> {
+        Ok(fs::read_to_string(self.root.join(path))?)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.root.join(path).symlink_metadata().is_ok()
+    }
+
+    fn whiteouts(&self) -> Vec<String> {
+        self.paths()
+            .into_iter()
+            .filter(|p| {
+                self.root
+                    .join(p)
+                    .symlink_metadata()
+                    .is_ok_and(|m| m.file_type().is_char_device() && m.rdev() == 0)
+            })
+            .collect()
+    }
+
+    fn opaque_dirs(&self) -> Vec<String> {
+        self.paths()
+            .into_iter()
+            .filter(|p| {
+                let path = self.root.join(p);
+                path.symlink_metadata().is_ok_and(|m| m.is_dir())
+                    && OPAQUE_XATTRS
+                        .iter()
+                        .any(|name| xattr(&path, name).as_deref() == Some(b"y".as_slice()))
+            })
+            .collect()
+    }
+
+    fn redirects(&self) -> Vec<(String, String)> {
+        self.paths()
+            .into_iter()
+            .filter_map(|p| {
+                let path = self.root.join(&p);
+                let value = REDIRECT_XATTRS.iter().find_map(|name| xattr(&path, name))?;
+                Some((p, String::from_utf8_lossy(&value).into_owned()))
+            })
+            .collect()
+    }
+
+    fn metacopies(&self) -> Vec<String> {
+        self.paths()
+            .into_iter()
+            .filter(|p| {
+                let path = self.root.join(p);
+                path.symlink_metadata().is_ok_and(|m| m.is_file())
+                    && METACOPY_XATTRS
+                        .iter()
+                        .any(|name| xattr(&path, name).is_some())
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "cli")]
+/// Value of an extended attribute, without following symlinks. None if it
+/// isn't set or can't be read (`trusted.*` needs CAP_SYS_ADMIN).
+fn xattr(path: &Path, name: &str) -> Option<Vec<u8>> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let name = CString::new(name).ok()?;
+    // SAFETY: both strings are NUL-terminated and outlive the call; a null
+    // buffer of length 0 asks for the value's size
+    let size = unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+    let mut value = vec![0u8; usize::try_from(size).ok()?];
+    // SAFETY: as above, and the buffer length passed is the buffer's own
+    let len = unsafe {
+        libc::lgetxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_mut_ptr().cast(),
+            value.len(),
+        )
+    };
+    value.truncate(usize::try_from(len).ok()?);
+    Some(value)
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_overlay() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("etc/systemd/system")).unwrap();
+        fs::write(dir.path().join("etc/issue"), "LevitateOS Live\n").unwrap();
+
+        let overlay = DirOverlay::open(dir.path()).unwrap();
+        let paths = overlay.paths();
+        assert!(paths.contains(&"etc/issue".to_string()));
+        assert!(paths.contains(&"etc/systemd/system".to_string()));
+        assert!(overlay.exists("etc/issue"));
+        assert_eq!(
+            overlay.read_to_string("etc/issue").unwrap(),
+            "LevitateOS Live\n"
+        );
+        assert!(overlay.whiteouts().is_empty());
+        assert!(overlay.opaque_dirs().is_empty());
+        assert!(overlay.redirects().is_empty());
+        assert!(overlay.metacopies().is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+//! PE/COFF headers of EFI binaries
+//!
+//! Read natively from the COFF file header, the optional header and the
+//! section table: enough to tell whether firmware will load an image for
+//! this machine, as an application, and whether the file holds all of it.
+//! [`crate::uki`] finds its sections through the same table.
+
+use crate::arch::Arch;
+use crate::error::FsdbgError;
+
+/// `IMAGE_FILE_MACHINE_*` values EFI binaries use
+pub const MACHINE_I386: u16 = 0x014c;
+pub const MACHINE_AMD64: u16 = 0x8664;
+pub const MACHINE_ARM64: u16 = 0xaa64;
+
+/// `IMAGE_SUBSYSTEM_EFI_*` values
+pub const SUBSYSTEM_EFI_APPLICATION: u16 = 10;
+pub const SUBSYSTEM_EFI_BOOT_SERVICE_DRIVER: u16 = 11;
+pub const SUBSYSTEM_EFI_RUNTIME_DRIVER: u16 = 12;
+
+/// Optional header magic of PE32 and PE32+ images
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+
+/// Size of a section table entry
+const SECTION_HEADER_LEN: usize = 40;
+
+/// A section table entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    /// File offset of the raw data
+    pub raw_offset: u32,
+    pub raw_size: u32,
+}
+
+/// Headers of a PE image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeImage {
+    pub machine: u16,
+    /// PE32+ (64-bit) rather than PE32
+    pub pe32_plus: bool,
+    pub subsystem: u16,
+    pub section_alignment: u32,
+    pub file_alignment: u32,
+    pub size_of_image: u32,
+    pub size_of_headers: u32,
+    /// (address, size) of each data directory
+    pub data_directories: Vec<(u32, u32)>,
+    pub sections: Vec<Section>,
+    /// Size of the file the headers were read from
+    pub file_size: u64,
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Name of a machine type, for messages.
+pub fn machine_name(machine: u16) -> String {
+    match machine {
+        MACHINE_I386 => "i386".to_string(),
+        MACHINE_AMD64 => "x86_64".to_string(),
+        MACHINE_ARM64 => "aarch64".to_string(),
+        other => format!("machine {:#06x}", other),
+    }
+}
+
+/// Machine type of EFI binaries for `arch`.
+pub fn machine_of(arch: Arch) -> u16 {
+    match arch {
+        Arch::X86_64 => MACHINE_AMD64,
+        Arch::Aarch64 => MACHINE_ARM64,
+    }
+}
+
+impl PeImage {
+    /// Parse the headers of a PE image. Only the headers have to be there;
+    /// [`PeImage::problems`] reports what's missing of the rest.
+    pub fn parse(data: &[u8]) -> Result<Self, FsdbgError> {
+        if !crate::uki::is_pe_header(data) {
+            return Err(FsdbgError::invalid_format("Not a PE image"));
+        }
+        let truncated = || FsdbgError::invalid_format("PE headers are truncated");
+        let coff = u32_at(data, 0x3c).ok_or_else(truncated)? as usize + 4;
+        let machine = u16_at(data, coff).ok_or_else(truncated)?;
+        let section_count = u16_at(data, coff + 2).ok_or_else(truncated)? as usize;
+        let optional_len = u16_at(data, coff + 16).ok_or_else(truncated)? as usize;
+        let optional = coff + 20;
+
+        // PE32 and PE32+ differ in where the data directories start
+        let (pe32_plus, directories) = match u16_at(data, optional).ok_or_else(truncated)? {
+            PE32_MAGIC => (false, optional + 96),
+            PE32_PLUS_MAGIC => (true, optional + 112),
+            magic => {
+                return Err(FsdbgError::invalid_format(format!(
+                    "Unknown PE optional header magic {:#x}",
+                    magic
+                )))
+            }
+        };
+        let directory_count = u32_at(data, directories - 4).ok_or_else(truncated)? as usize;
+        let data_directories = (0..directory_count.min(16))
+            .map(|i| {
+                let at = directories + i * 8;
+                Some((u32_at(data, at)?, u32_at(data, at + 4)?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(truncated)?;
+
+        let sections = (0..section_count)
+            .map(|i| {
+                let at = optional + optional_len + i * SECTION_HEADER_LEN;
+                let header = data.get(at..at + SECTION_HEADER_LEN)?;
+                let name = header[..8].split(|b| *b == 0).next().unwrap_or_default();
+                Some(Section {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    virtual_size: u32_at(header, 8)?,
+                    virtual_address: u32_at(header, 12)?,
+                    raw_size: u32_at(header, 16)?,
+                    raw_offset: u32_at(header, 20)?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| FsdbgError::invalid_format("PE section table is truncated"))?;
+
+        Ok(Self {
+            machine,
+            pe32_plus,
+            subsystem: u16_at(data, optional + 68).ok_or_else(truncated)?,
+            section_alignment: u32_at(data, optional + 32).ok_or_else(truncated)?,
+            file_alignment: u32_at(data, optional + 36).ok_or_else(truncated)?,
+            size_of_image: u32_at(data, optional + 56).ok_or_else(truncated)?,
+            size_of_headers: u32_at(data, optional + 60).ok_or_else(truncated)?,
+            data_directories,
+            sections,
+            file_size: data.len() as u64,
+        })
+    }
+
+    /// The data directory at `index`, if the image has it and it isn't empty.
+    pub fn data_directory(&self, index: usize) -> Option<(u32, u32)> {
+        self.data_directories
+            .get(index)
+            .copied()
+            .filter(|(address, size)| *address != 0 && *size != 0)
+    }
+
+    /// What would stop firmware from loading the image as an EFI
+    /// application for `machine`; drivers may be boot service or runtime
+    /// drivers instead.
+    pub fn problems(&self, machine: u16, driver: bool) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.machine != machine {
+            problems.push(format!(
+                "Built for {}, not {}",
+                machine_name(self.machine),
+                machine_name(machine)
+            ));
+        }
+        let subsystems: &[u16] = if driver {
+            &[
+                SUBSYSTEM_EFI_BOOT_SERVICE_DRIVER,
+                SUBSYSTEM_EFI_RUNTIME_DRIVER,
+            ]
+        } else {
+            &[SUBSYSTEM_EFI_APPLICATION]
+        };
+        if !subsystems.contains(&self.subsystem) {
+            problems.push(format!(
+                "Subsystem {}, not an EFI {}",
+                self.subsystem,
+                if driver { "driver" } else { "application" }
+            ));
+        }
+        if !self.file_alignment.is_power_of_two() || !(512..=0x10000).contains(&self.file_alignment)
+        {
+            problems.push(format!(
+                "File alignment {:#x} is invalid",
+                self.file_alignment
+            ));
+        }
+        if !self.section_alignment.is_power_of_two() || self.section_alignment < self.file_alignment
+        {
+            problems.push(format!(
+                "Section alignment {:#x} is invalid (file alignment {:#x})",
+                self.section_alignment, self.file_alignment
+            ));
+        } else if let Some(section) = self
+            .sections
+            .iter()
+            .find(|s| s.virtual_address % self.section_alignment != 0)
+        {
+            problems.push(format!(
+                "Section {} at {:#x} isn't aligned to {:#x}",
+                section.name, section.virtual_address, self.section_alignment
+            ));
+        }
+        let end = self
+            .sections
+            .iter()
+            .map(|s| u64::from(s.raw_offset) + u64::from(s.raw_size))
+            .chain([u64::from(self.size_of_headers)])
+            .max()
+            .unwrap_or_default();
+        if end > self.file_size {
+            problems.push(format!(
+                "Truncated: the sections end at {} bytes, the file has {}",
+                end, self.file_size
+            ));
+        }
+        let mapped = self
+            .sections
+            .iter()
+            .map(|s| u64::from(s.virtual_address) + u64::from(s.virtual_size.max(s.raw_size)))
+            .max()
+            .unwrap_or_default();
+        if mapped > u64::from(self.size_of_image) {
+            problems.push(format!(
+                "Sections map {:#x} bytes, past the image size {:#x}",
+                mapped, self.size_of_image
+            ));
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// PE32+ EFI application with one 4 KiB-aligned `.text` section.
+    fn image() -> Vec<u8> {
+        let mut data = vec![0u8; 0x400];
+        data[..2].copy_from_slice(b"MZ");
+        data[0x3c] = 0x40;
+        data[0x40..0x44].copy_from_slice(b"PE\0\0");
+        let coff = 0x44;
+        data[coff..coff + 2].copy_from_slice(&MACHINE_AMD64.to_le_bytes());
+        data[coff + 2] = 1;
+        data[coff + 16..coff + 18].copy_from_slice(&240u16.to_le_bytes());
+        let optional = coff + 20;
+        let mut put = |at: usize, value: u32| {
+            data[optional + at..optional + at + 4].copy_from_slice(&value.to_le_bytes())
+        };
+        put(0, u32::from(PE32_PLUS_MAGIC));
+        put(32, 0x1000);
+        put(36, 0x200);
+        put(56, 0x2000);
+        put(60, 0x200);
+        put(68, u32::from(SUBSYSTEM_EFI_APPLICATION));
+        put(108, 16);
+        let section = optional + 240;
+        data[section..section + 5].copy_from_slice(b".text");
+        for (at, value) in [(8, 0x100u32), (12, 0x1000), (16, 0x200), (20, 0x200)] {
+            data[section + at..section + at + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_problems() {
+        let data = image();
+        let pe = PeImage::parse(&data).unwrap();
+        assert!(pe.pe32_plus);
+        assert_eq!(pe.sections[0].name, ".text");
+        assert_eq!(pe.data_directories.len(), 16);
+        assert_eq!(pe.data_directory(4), None);
+        assert!(pe.problems(MACHINE_AMD64, false).is_empty());
+
+        let problems = pe.problems(MACHINE_ARM64, true);
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].starts_with("Built for x86_64, not aarch64"));
+
+        let truncated = PeImage::parse(&data[..0x300]).unwrap();
+        assert_eq!(
+            truncated.problems(MACHINE_AMD64, false),
+            ["Truncated: the sections end at 1024 bytes, the file has 768"]
+        );
+        assert!(PeImage::parse(&data[..0x50]).is_err());
+        assert!(PeImage::parse(b"MZ").is_err());
+    }
+}
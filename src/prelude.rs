@@ -0,0 +1,26 @@
+//! The stable library surface
+//!
+//! `use fsdbg::prelude::*;` brings in the archive readers, the report
+//! types and the functions that run a checklist. Library users should
+//! build on these: they change only with the major version, and
+//! `tests/public-api.txt` pins their signatures. The enums are
+//! `#[non_exhaustive]`, so new variants aren't breaking changes.
+
+pub use crate::arch::Arch;
+pub use crate::checklist::{
+    scope, verify_cpio, verify_cpio_for, verify_iso, verify_iso_for, CheckCategory, CheckResult,
+    ChecklistType, Scope, Severity, Subsystem, VerificationReport,
+};
+pub use crate::cpio::{CpioEntry, CpioReader, DuplicatePolicy, FileType, Limits, ParseOptions};
+pub use crate::diff::EntryKind;
+pub use crate::dir::DirReader;
+pub use crate::erofs::{ErofsEntry, ErofsReader, ErofsStats};
+pub use crate::error::{Context as ErrorContext, ErrorCode, FsdbgError};
+pub use crate::fixit::Fix;
+pub use crate::iso::{IsoEntry, IsoReader, IsoStats};
+pub use crate::overlay::OverlayTree;
+pub use crate::reader::ArchiveReader;
+pub use crate::report::Fingerprint;
+pub use crate::sanitize::Destination;
+pub use crate::spec::{ListChange, Spec};
+pub use crate::{detect_format, ArchiveFormat, ByteRange};
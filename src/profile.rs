@@ -0,0 +1,342 @@
+//! Checklist profiles (verify --profile)
+//!
+//! distro-spec describes the desktop build. A minimal server rootfs built
+//! from the same spec leaves out Bluetooth, audio and Wi-Fi on purpose, and
+//! checking for them is noise. A profile names the parts of the spec a build
+//! doesn't ship; verifying with it narrows the spec's lists before any
+//! checklist reads them, so the left-out items aren't checked at all.
+//!
+//! Built-in profiles are `desktop` (everything, the default), `server` and
+//! `minimal`. More are defined in TOML, in `/etc/fsdbg/profiles.d/*.toml` or
+//! a file passed with `--profile-file`; a later definition of a name
+//! replaces an earlier one:
+//!
+//! ```toml
+//! [[profile]]
+//! name = "appliance"
+//! description = "Headless appliance without SSH"
+//! base = "minimal"               # start from another profile's exclusions
+//! lists = ["ssh_bin", "ssh_sbin"] # spec lists left out (as spec-version --dump names them)
+//! exclude = ["sshd*"]            # globs over list items; for link pairs, the link
+//! secure_boot = true             # also require signed EFI binaries, shim and MokManager
+//! ```
+//!
+//! The built-in `secure-boot` profile is `desktop` with `secure_boot` set,
+//! for builds whose ISO boots with Secure Boot enabled.
+
+use crate::error::{ErrorCode, FsdbgError};
+use crate::spec::Spec;
+use glob::{MatchOptions, Pattern};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// System-wide profile directory
+pub const SYSTEM_DIR: &str = "/etc/fsdbg/profiles.d";
+
+/// Same matching rules as ignore patterns
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// A named selection of the spec's lists
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Profile whose exclusions this one adds to
+    #[serde(default)]
+    pub base: Option<String>,
+    /// Spec lists left out entirely
+    #[serde(default)]
+    pub lists: Vec<String>,
+    /// Globs for list items left out
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Require Secure Boot artifacts (see [`crate::checklist::secure_boot`]);
+    /// set if any base sets it
+    #[serde(default)]
+    pub secure_boot: bool,
+    /// File the profile was defined in; None for built-in ones
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    #[serde(default, rename = "profile")]
+    profiles: Vec<Profile>,
+}
+
+fn builtin_profile(
+    name: &str,
+    description: &str,
+    base: Option<&str>,
+    lists: &[&str],
+    exclude: &[&str],
+) -> Profile {
+    Profile {
+        name: name.to_string(),
+        description: Some(description.to_string()),
+        base: base.map(str::to_string),
+        lists: lists.iter().map(|s| s.to_string()).collect(),
+        exclude: exclude.iter().map(|s| s.to_string()).collect(),
+        secure_boot: false,
+        source: None,
+    }
+}
+
+/// Profiles compiled into fsdbg.
+pub fn builtin() -> Vec<Profile> {
+    vec![
+        builtin_profile(
+            "desktop",
+            "Everything distro-spec lists (the default)",
+            None,
+            &[],
+            &[],
+        ),
+        builtin_profile(
+            "server",
+            "No Bluetooth, audio, Wi-Fi, or desktop storage and power daemons",
+            None,
+            &[
+                "wpa_sbin",
+                "bluetooth_sbin",
+                "pipewire_sbin",
+                "udisks_sbin",
+                "upower_sbin",
+            ],
+            &[
+                "bluetooth*",
+                "pipewire*",
+                "wireplumber*",
+                "wpa_supplicant*",
+                "udisks2*",
+                "upower*",
+            ],
+        ),
+        builtin_profile(
+            "minimal",
+            "Server without NetworkManager and polkit",
+            Some("server"),
+            &["nm_bin", "nm_sbin", "polkit_sbin"],
+            &["NetworkManager*", "polkit*"],
+        ),
+        Profile {
+            secure_boot: true,
+            ..builtin_profile(
+                "secure-boot",
+                "Everything, booting with Secure Boot: signed EFI binaries, shim and MokManager",
+                None,
+                &[],
+                &[],
+            )
+        },
+    ]
+}
+
+/// Profile files in [`SYSTEM_DIR`], in name order.
+pub fn discover() -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(SYSTEM_DIR)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Load the profiles defined in a file.
+pub fn load(path: &Path) -> Result<Vec<Profile>, FsdbgError> {
+    let text = fs::read_to_string(path).map_err(|e| FsdbgError::from(e).with_path(path))?;
+    let file: ProfileFile = toml::from_str(&text).map_err(|e| {
+        FsdbgError::new(ErrorCode::ParseError, format!("{}: {}", path.display(), e)).with_path(path)
+    })?;
+    Ok(file
+        .profiles
+        .into_iter()
+        .map(|profile| Profile {
+            source: Some(path.to_path_buf()),
+            ..profile
+        })
+        .collect())
+}
+
+/// Built-in profiles, then those in [`SYSTEM_DIR`], then those in `files`.
+/// A later definition of a name replaces the earlier one.
+pub fn available(files: &[PathBuf]) -> Result<Vec<Profile>, FsdbgError> {
+    let mut profiles = builtin();
+    for file in discover().iter().chain(files) {
+        for profile in load(file)? {
+            profiles.retain(|p| p.name != profile.name);
+            profiles.push(profile);
+        }
+    }
+    Ok(profiles)
+}
+
+/// The profile called `name` among `profiles`.
+pub fn find<'a>(profiles: &'a [Profile], name: &str) -> Result<&'a Profile, FsdbgError> {
+    profiles.iter().find(|p| p.name == name).ok_or_else(|| {
+        let names: Vec<&str> = profiles.iter().map(|p| p.name.as_str()).collect();
+        FsdbgError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "Unknown profile: {}. Available profiles: {}",
+                name,
+                names.join(", ")
+            ),
+        )
+    })
+}
+
+impl Profile {
+    /// Lists and globs left out, including those of its bases, and whether
+    /// any of them requires Secure Boot.
+    fn exclusions<'a>(
+        &'a self,
+        profiles: &'a [Profile],
+    ) -> Result<(Vec<&'a str>, Vec<&'a str>, bool), FsdbgError> {
+        let mut lists = Vec::new();
+        let mut globs = Vec::new();
+        let mut secure_boot = false;
+        let mut seen: Vec<&str> = Vec::new();
+        let mut profile = self;
+        loop {
+            if seen.contains(&profile.name.as_str()) {
+                return Err(FsdbgError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Profile {} is its own base", profile.name),
+                ));
+            }
+            seen.push(&profile.name);
+            lists.extend(profile.lists.iter().map(String::as_str));
+            globs.extend(profile.exclude.iter().map(String::as_str));
+            secure_boot |= profile.secure_boot;
+            match profile.base {
+                Some(ref base) => profile = find(profiles, base)?,
+                None => return Ok((lists, globs, secure_boot)),
+            }
+        }
+    }
+
+    /// `spec` without the lists and items this profile leaves out.
+    pub fn apply(&self, spec: &Spec, profiles: &[Profile]) -> Result<Spec, FsdbgError> {
+        let invalid = |message: String| {
+            let error = FsdbgError::new(
+                ErrorCode::InvalidArgument,
+                format!("Profile {}: {}", self.name, message),
+            );
+            match self.source {
+                Some(ref path) => error.with_path(path),
+                None => error,
+            }
+        };
+        let (lists, globs, secure_boot) = self.exclusions(profiles)?;
+        let names: Vec<&str> = spec.lists().into_iter().map(|(name, _)| name).collect();
+        if let Some(unknown) = lists.iter().find(|list| !names.contains(list)) {
+            return Err(invalid(format!(
+                "unknown list {}; spec-version --dump shows the list names",
+                unknown
+            )));
+        }
+        let patterns = globs
+            .iter()
+            .map(|glob| {
+                Pattern::new(glob).map_err(|e| invalid(format!("invalid glob '{}': {}", glob, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut narrowed = spec.narrowed(&self.name, |list, item| {
+            !lists.contains(&list) && !patterns.iter().any(|p| p.matches_with(item, MATCH_OPTIONS))
+        });
+        narrowed.secure_boot = secure_boot;
+        Ok(narrowed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_profile() {
+        let spec = Spec::builtin();
+        let profiles = builtin();
+        let desktop = find(&profiles, "desktop")
+            .unwrap()
+            .apply(spec, &profiles)
+            .unwrap();
+        assert!(spec.compare(&desktop).is_empty());
+        assert_eq!(desktop.full_digest(), spec.digest());
+
+        let minimal = find(&profiles, "minimal")
+            .unwrap()
+            .apply(spec, &profiles)
+            .unwrap();
+        assert!(minimal.nm_sbin.is_empty());
+        assert!(minimal.wpa_sbin.is_empty());
+        assert_eq!(minimal.ssh_sbin, spec.ssh_sbin);
+        assert_eq!(minimal.full_digest(), spec.digest());
+        assert!(minimal.id().ends_with(", profile minimal"));
+        assert!(!minimal.secure_boot);
+
+        let custom: ProfileFile = toml::from_str(
+            r#"
+            [[profile]]
+            name = "no-ssh"
+            base = "minimal"
+            exclude = ["ssh*"]
+
+            [[profile]]
+            name = "signed-server"
+            base = "server"
+            secure_boot = true
+
+            [[profile]]
+            name = "signed-minimal"
+            base = "signed-server"
+            lists = ["nm_bin"]
+
+            [[profile]]
+            name = "loop"
+            base = "loop"
+
+            [[profile]]
+            name = "typo"
+            lists = ["ssh_binaries"]
+            "#,
+        )
+        .unwrap();
+        let mut profiles = builtin();
+        profiles.extend(custom.profiles);
+        let no_ssh = find(&profiles, "no-ssh")
+            .unwrap()
+            .apply(spec, &profiles)
+            .unwrap();
+        assert!(no_ssh.ssh_bin.is_empty() && no_ssh.ssh_sbin.is_empty());
+        assert!(no_ssh.nm_bin.is_empty());
+        let signed = find(&profiles, "signed-minimal")
+            .unwrap()
+            .apply(spec, &profiles)
+            .unwrap();
+        assert!(signed.secure_boot && signed.nm_bin.is_empty());
+        assert!(find(&profiles, "loop")
+            .unwrap()
+            .apply(spec, &profiles)
+            .is_err());
+        assert!(find(&profiles, "typo")
+            .unwrap()
+            .apply(spec, &profiles)
+            .is_err());
+        assert!(find(&profiles, "workstation").is_err());
+    }
+}
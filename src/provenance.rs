@@ -0,0 +1,266 @@
+//! Artifact provenance manifests
+//!
+//! Build tooling records what an artifact was built from in a small JSON
+//! manifest: `/.fsdbg-manifest.json` inside the artifact (CPIO archives, the
+//! initrd of a UKI, ISOs), or a sidecar `<artifact>.fsdbg-manifest.json` for
+//! artifacts that can't carry one. The manifest names the build, the git
+//! commit and the distro-spec the build used, so verifying against a
+//! different spec can be flagged before its failures are taken at face value.
+//!
+//! ```json
+//! {
+//!   "build_id": "levitate-2026.10.16-3",
+//!   "git_commit": "4a20e2b9c1d0...",
+//!   "distro_spec": { "version": "0.1.0", "digest": "24379f5043c3..." },
+//!   "created": 1760572800
+//! }
+//! ```
+
+use crate::checklist::{CheckCategory, CheckResult};
+use crate::cpio::CpioReader;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::iso::IsoReader;
+use crate::spec::Spec;
+use crate::ArchiveFormat;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Manifest path inside an artifact, relative to its root
+pub const MANIFEST_PATH: &str = ".fsdbg-manifest.json";
+
+/// Appended to the artifact's file name for a sidecar manifest
+pub const SIDECAR_SUFFIX: &str = ".fsdbg-manifest.json";
+
+/// The distro-spec a build used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpecRef {
+    pub version: String,
+    /// SHA256 of the spec's lists, as `fsdbg spec-version` prints it (a
+    /// prefix is enough)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+}
+
+/// What an artifact was built from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub build_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<String>,
+    pub distro_spec: SpecRef,
+    /// Build time, seconds since the Unix epoch (SOURCE_DATE_EPOCH)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+    /// SHA256 of the artifact; sidecars only, since an embedded manifest
+    /// can't hash the artifact holding it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Where a manifest was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Embedded,
+    Sidecar(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Embedded => write!(f, "/{}", MANIFEST_PATH),
+            Source::Sidecar(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn parse(json: &[u8]) -> Result<Self, FsdbgError> {
+        serde_json::from_slice(json).map_err(|e| {
+            FsdbgError::new(
+                ErrorCode::ParseError,
+                format!("Invalid provenance manifest: {}", e),
+            )
+        })
+    }
+
+    /// Why `spec` isn't the spec this artifact was built from, if it isn't.
+    pub fn spec_mismatch(&self, spec: &Spec) -> Option<String> {
+        let built = &self.distro_spec;
+        if built.version != spec.version {
+            return Some(format!(
+                "Built from distro-spec {}, verified against {}",
+                built.version, spec.version
+            ));
+        }
+        let digest = built.digest.as_deref()?.to_ascii_lowercase();
+        let lists = spec.full_digest();
+        (!digest.is_empty() && !lists.starts_with(&digest)).then(|| {
+            format!(
+                "Built from distro-spec {} with lists {}, verified against lists {}",
+                built.version,
+                &digest[..digest.len().min(12)],
+                &lists[..12]
+            )
+        })
+    }
+
+    /// Checks of the manifest itself and of `spec` against it. `sha256`
+    /// is the artifact's, for a sidecar's hash to be compared with.
+    pub fn check(&self, source: &Source, spec: &Spec, sha256: Option<&str>) -> Vec<CheckResult> {
+        let item = |what: &str| format!("provenance: {}", what);
+        let mut results = Vec::new();
+
+        results.push(if self.build_id.trim().is_empty() {
+            CheckResult::fail(item("build id"), CheckCategory::Other, "Empty build_id")
+        } else {
+            CheckResult::pass(
+                format!("{} ({})", item("build id"), self.build_id),
+                CheckCategory::Other,
+            )
+        });
+        if let Some(ref commit) = self.git_commit {
+            let valid =
+                (7..=64).contains(&commit.len()) && commit.bytes().all(|b| b.is_ascii_hexdigit());
+            results.push(if valid {
+                CheckResult::pass(
+                    format!("{} ({})", item("git commit"), commit),
+                    CheckCategory::Other,
+                )
+            } else {
+                CheckResult::fail(
+                    item("git commit"),
+                    CheckCategory::Other,
+                    format!("{:?} is not a git commit hash", commit),
+                )
+            });
+        }
+        if let (Source::Sidecar(path), Some(expected), Some(actual)) =
+            (source, self.sha256.as_deref(), sha256)
+        {
+            results.push(if expected.eq_ignore_ascii_case(actual) {
+                CheckResult::pass(item("sidecar sha256"), CheckCategory::Other)
+            } else {
+                CheckResult::fail(
+                    item("sidecar sha256"),
+                    CheckCategory::Other,
+                    format!(
+                        "{} describes an artifact with SHA256 {}, not this one",
+                        path.display(),
+                        expected
+                    ),
+                )
+                .with_hint("Regenerate the sidecar when the artifact is rebuilt")
+            });
+        }
+        results.push(match self.spec_mismatch(spec) {
+            None => CheckResult::pass(
+                format!("{} ({})", item("distro-spec"), spec.id()),
+                CheckCategory::Other,
+            ),
+            Some(mismatch) => CheckResult::fail(item("distro-spec"), CheckCategory::Other, mismatch)
+                .with_hint(format!(
+                    "Verify with --spec and a snapshot of distro-spec {} (spec-version --dump from that build)",
+                    self.distro_spec.version
+                )),
+        });
+        results
+    }
+}
+
+/// Sidecar manifest path for `artifact`.
+pub fn sidecar_path(artifact: &Path) -> PathBuf {
+    let mut name = artifact.as_os_str().to_owned();
+    name.push(SIDECAR_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// The manifest embedded in `artifact`, if its format can carry one.
+fn embedded(artifact: &Path) -> Result<Option<Vec<u8>>, FsdbgError> {
+    let reader = if let Some(range) = crate::embedded_initrd(artifact)? {
+        CpioReader::open_range(artifact, range)?
+    } else {
+        match crate::detect_format(artifact) {
+            Ok(ArchiveFormat::Cpio | ArchiveFormat::CpioGzip) => CpioReader::open(artifact)?,
+            Ok(ArchiveFormat::Iso) => {
+                let iso = IsoReader::open(artifact)?;
+                return match iso.exists(MANIFEST_PATH) {
+                    true => iso.read_file(MANIFEST_PATH).map(Some),
+                    false => Ok(None),
+                };
+            }
+            // EROFS contents aren't readable, and disk images aren't opened
+            _ => return Ok(None),
+        }
+    };
+    match reader.exists(MANIFEST_PATH) {
+        true => reader.read_file(MANIFEST_PATH).map(Some),
+        false => Ok(None),
+    }
+}
+
+/// Find the manifest of `artifact`: embedded first, then the sidecar.
+pub fn find(artifact: &Path) -> Result<Option<(Manifest, Source)>, FsdbgError> {
+    if let Some(json) = embedded(artifact)? {
+        let manifest = Manifest::parse(&json).map_err(|e| e.with_path(artifact))?;
+        return Ok(Some((manifest, Source::Embedded)));
+    }
+    let sidecar = sidecar_path(artifact);
+    if !sidecar.exists() {
+        return Ok(None);
+    }
+    let json = std::fs::read(&sidecar).map_err(|e| FsdbgError::from(e).with_path(&sidecar))?;
+    let manifest = Manifest::parse(&json).map_err(|e| e.with_path(&sidecar))?;
+    Ok(Some((manifest, Source::Sidecar(sidecar))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_checks() {
+        let spec = Spec::builtin();
+        let mut manifest = Manifest::parse(
+            br#"{"build_id": "b1", "git_commit": "4a20e2b", "distro_spec": {"version": "0.0.9"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.spec_mismatch(spec),
+            Some(format!(
+                "Built from distro-spec 0.0.9, verified against {}",
+                spec.version
+            ))
+        );
+
+        manifest.distro_spec = SpecRef {
+            version: spec.version.clone(),
+            digest: Some(spec.digest()[..12].to_uppercase()),
+        };
+        assert_eq!(manifest.spec_mismatch(spec), None);
+        manifest.distro_spec.digest = Some("0000".to_string());
+        assert!(manifest.spec_mismatch(spec).is_some());
+
+        manifest.sha256 = Some("ab".repeat(32));
+        manifest.git_commit = Some("main".to_string());
+        let sidecar = Source::Sidecar(PathBuf::from("rootfs.img.fsdbg-manifest.json"));
+        let failed: Vec<String> = manifest
+            .check(&sidecar, spec, Some(&"cd".repeat(32)))
+            .into_iter()
+            .filter(|r| !r.passed)
+            .map(|r| r.item)
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                "provenance: git commit",
+                "provenance: sidecar sha256",
+                "provenance: distro-spec"
+            ]
+        );
+        assert_eq!(
+            sidecar_path(Path::new("out/rootfs.img")),
+            Path::new("out/rootfs.img.fsdbg-manifest.json")
+        );
+    }
+}
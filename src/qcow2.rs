@@ -0,0 +1,612 @@
+//! Rootless qcow2 image access
+//!
+//! Exposes the image as a raw disk through qemu-storage-daemon's FUSE block
+//! export, reads the GPT and filesystem identifiers natively, and copies the
+//! partitions out with userspace tools (debugfs for ext2/3/4, mcopy for FAT).
+//! Nothing is mounted and nothing needs root, unlike the qemu-nbd flow - but
+//! a LUKS-encrypted root can only be reported, not unlocked.
+
+use crate::checklist::qcow2::Partition;
+use crate::error::FsdbgError;
+use crate::luks::{self, LuksHeader};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// GPT header signature
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// Sector sizes a GPT may be laid out with
+const SECTOR_SIZES: &[u64] = &[512, 4096];
+
+/// ext2/3/4 superblock magic (at offset 1024 + 0x38)
+const EXT_MAGIC: u16 = 0xEF53;
+
+/// How long qemu-storage-daemon gets to set up the export
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// GPT type of an EFI system partition
+pub const ESP_TYPE: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+
+/// GPT type of a Linux swap partition
+pub const SWAP_TYPE: &str = "0657fd6d-a4ab-43c4-84e5-0933c84b4f4f";
+
+/// GPT attribute: legacy BIOS bootable
+pub const ATTR_LEGACY_BIOS_BOOTABLE: u64 = 1 << 2;
+
+/// Swap signature, in the last bytes of the first page
+const SWAP_MAGIC: &[u8] = b"SWAPSPACE2";
+
+/// A partition from the image's GPT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GptPartition {
+    /// 1-based index in the partition table (the N in `/dev/nbd0pN`)
+    pub number: u32,
+    pub type_guid: String,
+    pub partuuid: String,
+    pub name: String,
+    /// Byte offset in the disk
+    pub offset: u64,
+    pub size: u64,
+    /// Attribute flags ([`ATTR_LEGACY_BIOS_BOOTABLE`], ...)
+    pub attributes: u64,
+}
+
+/// What a partition holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsKind {
+    Ext,
+    Fat16,
+    Fat32,
+    Swap,
+}
+
+impl std::fmt::Display for FsKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FsKind::Ext => write!(f, "ext2/3/4"),
+            FsKind::Fat16 => write!(f, "FAT12/16"),
+            FsKind::Fat32 => write!(f, "FAT32"),
+            FsKind::Swap => write!(f, "swap"),
+        }
+    }
+}
+
+/// Filesystem (or swap area) found at a partition's start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filesystem {
+    pub kind: FsKind,
+    pub uuid: String,
+    pub label: Option<String>,
+}
+
+/// A partition and what is on it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskPartition {
+    pub gpt: GptPartition,
+    pub filesystem: Option<Filesystem>,
+}
+
+impl DiskPartition {
+    pub fn is_esp(&self) -> bool {
+        self.gpt.type_guid == ESP_TYPE
+    }
+
+    pub fn is_swap(&self) -> bool {
+        self.gpt.type_guid == SWAP_TYPE || self.kind() == Some(FsKind::Swap)
+    }
+
+    /// Named "recovery" in the GPT or by its filesystem label.
+    pub fn is_recovery(&self) -> bool {
+        let label = self.filesystem.as_ref().and_then(|f| f.label.as_deref());
+        [Some(self.gpt.name.as_str()), label]
+            .into_iter()
+            .flatten()
+            .any(|name| name.to_ascii_lowercase().contains("recovery"))
+    }
+
+    pub fn kind(&self) -> Option<FsKind> {
+        self.filesystem.as_ref().map(|f| f.kind)
+    }
+}
+
+/// GUID in its textual form; the first three fields are little-endian.
+fn format_guid(bytes: &[u8]) -> String {
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        bytes[3], bytes[2], bytes[1], bytes[0], bytes[5], bytes[4], bytes[7], bytes[6],
+        bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15]
+    )
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().expect("4 bytes"))
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().expect("8 bytes"))
+}
+
+fn read_at<R: Read + Seek>(disk: &mut R, offset: u64, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    disk.seek(SeekFrom::Start(offset))?;
+    disk.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Read the partition table of a raw disk. Unused entries are skipped.
+pub fn read_gpt<R: Read + Seek>(disk: &mut R) -> Result<Vec<GptPartition>, FsdbgError> {
+    for &sector in SECTOR_SIZES {
+        let Ok(header) = read_at(disk, sector, 92) else {
+            continue;
+        };
+        if &header[..8] != GPT_SIGNATURE {
+            continue;
+        }
+        let entries_lba = le_u64(&header[72..]);
+        let count = le_u32(&header[80..]);
+        let entry_size = le_u32(&header[84..]) as usize;
+        if entry_size < 128 || count > 1024 {
+            return Err(FsdbgError::invalid_format(format!(
+                "Implausible GPT header ({} entries of {} bytes)",
+                count, entry_size
+            )));
+        }
+
+        let table = read_at(disk, entries_lba * sector, count as usize * entry_size)?;
+        let partitions = table
+            .chunks(entry_size)
+            .enumerate()
+            .filter(|(_, entry)| entry[..16].iter().any(|&b| b != 0))
+            .map(|(index, entry)| {
+                let first = le_u64(&entry[32..]);
+                let last = le_u64(&entry[40..]);
+                let name: Vec<u16> = entry[56..128]
+                    .chunks(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .take_while(|&c| c != 0)
+                    .collect();
+                GptPartition {
+                    number: index as u32 + 1,
+                    type_guid: format_guid(&entry[..16]),
+                    partuuid: format_guid(&entry[16..32]),
+                    name: String::from_utf16_lossy(&name),
+                    offset: first * sector,
+                    size: (last + 1).saturating_sub(first) * sector,
+                    attributes: le_u64(&entry[48..]),
+                }
+            })
+            .collect();
+        return Ok(partitions);
+    }
+    Err(FsdbgError::invalid_format(
+        "No GPT partition table in the image",
+    ))
+}
+
+/// Space-padded or NUL-padded label, None if blank.
+fn label(bytes: &[u8]) -> Option<String> {
+    let label = String::from_utf8_lossy(bytes);
+    let label = label.trim_end_matches(['\0', ' ']);
+    (!label.is_empty() && label != "NO NAME").then(|| label.to_string())
+}
+
+/// UUID in its textual form, bytes in order (ext and swap superblocks).
+fn format_uuid(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .enumerate()
+        .map(|(i, b)| match i {
+            4 | 6 | 8 | 10 => format!("-{:02x}", b),
+            _ => format!("{:02x}", b),
+        })
+        .collect()
+}
+
+/// Identify the filesystem at `offset`, as blkid would.
+pub fn probe_filesystem<R: Read + Seek>(disk: &mut R, offset: u64) -> Option<Filesystem> {
+    let page = read_at(disk, offset, 4096).ok()?;
+    if page[0x438..0x43A] == EXT_MAGIC.to_le_bytes() {
+        return Some(Filesystem {
+            kind: FsKind::Ext,
+            uuid: format_uuid(&page[0x468..0x478]),
+            label: label(&page[0x478..0x488]),
+        });
+    }
+    if page.ends_with(SWAP_MAGIC) {
+        return Some(Filesystem {
+            kind: FsKind::Swap,
+            uuid: format_uuid(&page[0x40C..0x41C]),
+            label: label(&page[0x41C..0x42C]),
+        });
+    }
+
+    if page[510..512] != [0x55, 0xAA] {
+        return None;
+    }
+    // FAT32 keeps the volume ID and label further in than FAT12/16
+    let (kind, id, name) = if &page[0x52..0x57] == b"FAT32" {
+        (FsKind::Fat32, 0x43, 0x47)
+    } else if &page[0x36..0x39] == b"FAT" {
+        (FsKind::Fat16, 0x27, 0x2B)
+    } else {
+        return None;
+    };
+    let id = le_u32(&page[id..]);
+    Some(Filesystem {
+        kind,
+        uuid: format!("{:04X}-{:04X}", id >> 16, id & 0xffff),
+        label: label(&page[name..name + 11]),
+    })
+}
+
+/// Every partition in the GPT, with the filesystem on it.
+pub fn read_layout<R: Read + Seek>(disk: &mut R) -> Result<Vec<DiskPartition>, FsdbgError> {
+    Ok(read_gpt(disk)?
+        .into_iter()
+        .map(|gpt| DiskPartition {
+            filesystem: probe_filesystem(disk, gpt.offset),
+            gpt,
+        })
+        .collect())
+}
+
+/// Identifiers of a partition mounted at `mount`, for the qcow2 checklist.
+pub fn partition_ids(
+    partition: &GptPartition,
+    filesystem: Option<&Filesystem>,
+    mount: &str,
+) -> Partition {
+    Partition {
+        mount: mount.to_string(),
+        uuid: filesystem.map(|f| f.uuid.clone()),
+        partuuid: Some(partition.partuuid.clone()),
+        label: filesystem.and_then(|f| f.label.clone()),
+        partlabel: (!partition.name.is_empty()).then(|| partition.name.clone()),
+    }
+}
+
+/// A qcow2 image exported as a raw disk file by qemu-storage-daemon.
+/// Dropping it stops the daemon, which removes the FUSE export.
+pub struct FuseExport {
+    daemon: Child,
+    raw: PathBuf,
+    _dir: tempfile::TempDir,
+}
+
+impl FuseExport {
+    /// Export `image` read-only and wait until the raw disk is readable.
+    pub fn open(image: &Path) -> Result<Self, FsdbgError> {
+        if !image.exists() {
+            return Err(FsdbgError::file_not_found(image));
+        }
+        let dir = tempfile::Builder::new().prefix("fsdbg-qcow2-").tempdir()?;
+        let raw = dir.path().join("disk.raw");
+        File::create(&raw)?;
+
+        // Option values escape commas by doubling them
+        let escape = |path: &Path| path.to_string_lossy().replace(',', ",,");
+        let daemon = Command::new("qemu-storage-daemon")
+            .arg("--blockdev")
+            .arg(format!(
+                "driver=file,node-name=file,filename={},read-only=on",
+                escape(image)
+            ))
+            .args([
+                "--blockdev",
+                "driver=qcow2,node-name=disk,file=file,read-only=on",
+            ])
+            .arg("--export")
+            .arg(format!(
+                "type=fuse,id=disk,node-name=disk,mountpoint={},writable=off",
+                escape(&raw)
+            ))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => FsdbgError::tool_missing(
+                    "qemu-storage-daemon",
+                    "Install qemu-tools (or qemu-storage-daemon), or verify with --nbd.",
+                ),
+                _ => FsdbgError::external_tool_failed("qemu-storage-daemon", e.to_string()),
+            })?;
+        let mut export = Self {
+            daemon,
+            raw,
+            _dir: dir,
+        };
+
+        // The placeholder file is empty until the export is mounted over it
+        let started = Instant::now();
+        while fs::metadata(&export.raw)?.len() == 0 {
+            if let Some(status) = export.daemon.try_wait()? {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = export.daemon.stderr.take() {
+                    let _ = pipe.read_to_string(&mut stderr);
+                }
+                return Err(FsdbgError::external_tool_failed(
+                    "qemu-storage-daemon",
+                    format!("exited ({}): {}", status, stderr.trim()),
+                )
+                .with_path(image));
+            }
+            if started.elapsed() > EXPORT_TIMEOUT {
+                return Err(FsdbgError::external_tool_failed(
+                    "qemu-storage-daemon",
+                    "FUSE export did not come up (is /dev/fuse available?)",
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        Ok(export)
+    }
+
+    /// The raw disk, readable like any file while the export lives.
+    pub fn raw(&self) -> &Path {
+        &self.raw
+    }
+}
+
+impl Drop for FuseExport {
+    fn drop(&mut self) {
+        // SIGTERM lets the daemon unmount the export; SIGKILL would leave it
+        unsafe {
+            libc::kill(self.daemon.id() as libc::pid_t, libc::SIGTERM);
+        }
+        let _ = self.daemon.wait();
+    }
+}
+
+/// Copy the files of the filesystem at `partition` into `dest`.
+pub fn extract(
+    raw: &Path,
+    partition: &GptPartition,
+    filesystem: &Filesystem,
+    dest: &Path,
+) -> Result<(), FsdbgError> {
+    fs::create_dir_all(dest)?;
+    let (tool, install, output) = match filesystem.kind {
+        FsKind::Swap => {
+            return Err(FsdbgError::unsupported_format(format!(
+                "swap on partition {} (no files to read)",
+                partition.number
+            )))
+        }
+        FsKind::Ext => (
+            "debugfs",
+            "Install e2fsprogs.",
+            Command::new("debugfs")
+                .arg("-R")
+                .arg(format!("rdump / \"{}\"", dest.display()))
+                .arg(format!("{}?offset={}", raw.display(), partition.offset))
+                .output(),
+        ),
+        FsKind::Fat16 | FsKind::Fat32 => (
+            "mcopy",
+            "Install mtools.",
+            Command::new("mcopy")
+                .args(["-s", "-p", "-n", "-m", "-i"])
+                .arg(format!("{}@@{}", raw.display(), partition.offset))
+                .arg("::/*")
+                .arg(dest)
+                .output(),
+        ),
+    };
+    let output = output.map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => FsdbgError::tool_missing(tool, install),
+        _ => FsdbgError::external_tool_failed(tool, e.to_string()),
+    })?;
+    // An empty FAT has nothing for the ::/* glob to match
+    let empty_fat = matches!(filesystem.kind, FsKind::Fat16 | FsKind::Fat32)
+        && String::from_utf8_lossy(&output.stderr).contains("not found");
+    if !output.status.success() && !empty_fat {
+        return Err(FsdbgError::external_tool_failed(
+            tool,
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    make_readable(dest)
+}
+
+/// Give the owner (us) read access everywhere: the extracted files keep the
+/// image's modes, and a 0000 /etc/shadow would otherwise be unreadable.
+fn make_readable(dir: &Path) -> Result<(), FsdbgError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            continue;
+        }
+        let path = entry.path();
+        let mut permissions = entry.metadata()?.permissions();
+        let owner = if file_type.is_dir() { 0o700 } else { 0o400 };
+        if permissions.mode() & owner != owner {
+            permissions.set_mode(permissions.mode() | owner);
+            fs::set_permissions(&path, permissions)?;
+        }
+        if file_type.is_dir() {
+            make_readable(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// What [`unpack`] found
+#[derive(Debug)]
+pub enum Unpacked {
+    /// Partitions copied out
+    Tree {
+        /// Identifiers of the root and boot partitions
+        partitions: Vec<Partition>,
+        layout: Vec<DiskPartition>,
+        /// Where the recovery partition was copied, if there is one
+        recovery: Option<PathBuf>,
+    },
+    /// The root partition is LUKS-encrypted; unlocking it needs device-mapper
+    Encrypted(LuksHeader),
+}
+
+/// Unpack a qcow2 image into `dest` without privileges: the root partition
+/// (p2) at `dest/root` and the boot partition (p1) at `dest/root/boot`, as
+/// the image would be mounted, and a recovery partition at `dest/recovery`.
+pub fn unpack(image: &Path, dest: &Path) -> Result<Unpacked, FsdbgError> {
+    let export = FuseExport::open(image)?;
+    let mut disk = File::open(export.raw())?;
+    let layout = read_layout(&mut disk)?;
+    let table: Vec<_> = layout.iter().map(|p| p.gpt.clone()).collect();
+
+    let mut partitions = Vec::new();
+    for (number, mount) in [(2, "/"), (1, "/boot")] {
+        let partition = table.iter().find(|p| p.number == number).ok_or_else(|| {
+            FsdbgError::invalid_format(format!("No partition {} (for {})", number, mount))
+                .with_path(image)
+        })?;
+        if read_at(&mut disk, partition.offset, luks::MAGIC.len())
+            .is_ok_and(|magic| luks::is_luks(&magic))
+        {
+            let len = luks::HEADER_LEN.min(partition.size as usize);
+            let header = read_at(&mut disk, partition.offset, len)?;
+            if let Some(luks) = luks::parse(&header)? {
+                return Ok(Unpacked::Encrypted(luks));
+            }
+        }
+        let filesystem = probe_filesystem(&mut disk, partition.offset).ok_or_else(|| {
+            FsdbgError::unsupported_format(format!(
+                "filesystem on partition {} (only ext2/3/4 and FAT can be read without mounting)",
+                number
+            ))
+            .with_path(image)
+        })?;
+        extract(
+            export.raw(),
+            partition,
+            &filesystem,
+            &dest.join("root").join(mount.trim_start_matches('/')),
+        )?;
+        partitions.push(partition_ids(partition, Some(&filesystem), mount));
+    }
+
+    // Unreadable is reported by the checklist, not an error here
+    let recovery = layout
+        .iter()
+        .find(|p| p.is_recovery())
+        .and_then(|p| Some((p, p.filesystem.as_ref()?)))
+        .and_then(|(p, filesystem)| {
+            let dir = dest.join("recovery");
+            extract(export.raw(), &p.gpt, filesystem, &dir).ok()?;
+            Some(dir)
+        });
+    Ok(Unpacked::Tree {
+        partitions,
+        layout,
+        recovery,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// 1 MiB disk: GPT with an ext4 root and a FAT32 boot partition.
+    fn disk() -> Vec<u8> {
+        let mut disk = vec![0u8; 1 << 20];
+        disk[512..520].copy_from_slice(GPT_SIGNATURE);
+        disk[512 + 72..512 + 80].copy_from_slice(&2u64.to_le_bytes());
+        disk[512 + 80..512 + 84].copy_from_slice(&4u32.to_le_bytes());
+        disk[512 + 84..512 + 88].copy_from_slice(&128u32.to_le_bytes());
+
+        let entry = |disk: &mut Vec<u8>, index: usize, first: u64, last: u64, name: &str| {
+            let at = 1024 + index * 128;
+            disk[at..at + 16].copy_from_slice(&[0xAB; 16]);
+            disk[at + 16..at + 32].copy_from_slice(&[
+                0x78,
+                0x56,
+                0x34,
+                0x12,
+                0x34,
+                0x12,
+                0x78,
+                0x56,
+                0,
+                1,
+                2,
+                3,
+                4,
+                5,
+                6,
+                index as u8,
+            ]);
+            disk[at + 32..at + 40].copy_from_slice(&first.to_le_bytes());
+            disk[at + 40..at + 48].copy_from_slice(&last.to_le_bytes());
+            for (i, unit) in name.encode_utf16().enumerate() {
+                disk[at + 56 + i * 2..at + 58 + i * 2].copy_from_slice(&unit.to_le_bytes());
+            }
+        };
+        entry(&mut disk, 0, 64, 511, "boot");
+        entry(&mut disk, 1, 512, 2047, "root");
+
+        let boot = 64 * 512;
+        disk[boot + 0x43..boot + 0x47].copy_from_slice(&0xABCD_1234u32.to_le_bytes());
+        disk[boot + 0x47..boot + 0x52].copy_from_slice(b"EFI        ");
+        disk[boot + 0x52..boot + 0x57].copy_from_slice(b"FAT32");
+        disk[boot + 510..boot + 512].copy_from_slice(&[0x55, 0xAA]);
+
+        let root = 512 * 512 + 1024;
+        disk[root + 0x38..root + 0x3A].copy_from_slice(&EXT_MAGIC.to_le_bytes());
+        disk[root + 0x68..root + 0x78].copy_from_slice(&[0x11; 16]);
+        disk[root + 0x78..root + 0x7C].copy_from_slice(b"root");
+        disk
+    }
+
+    #[test]
+    fn test_read_gpt() {
+        let partitions = read_gpt(&mut Cursor::new(disk())).unwrap();
+        assert_eq!(partitions.len(), 2);
+        assert_eq!(partitions[1].number, 2);
+        assert_eq!(partitions[1].name, "root");
+        assert_eq!(partitions[1].offset, 512 * 512);
+        assert_eq!(partitions[1].size, 1536 * 512);
+        assert_eq!(
+            partitions[1].partuuid,
+            "12345678-1234-5678-0001-020304050601"
+        );
+
+        assert!(read_gpt(&mut Cursor::new(vec![0u8; 8192])).is_err());
+    }
+
+    #[test]
+    fn test_probe_filesystem() {
+        let mut disk = Cursor::new(disk());
+        assert_eq!(
+            probe_filesystem(&mut disk, 64 * 512),
+            Some(Filesystem {
+                kind: FsKind::Fat32,
+                uuid: "ABCD-1234".to_string(),
+                label: Some("EFI".to_string()),
+            })
+        );
+        let root = probe_filesystem(&mut disk, 512 * 512).unwrap();
+        assert_eq!(
+            root,
+            Filesystem {
+                kind: FsKind::Ext,
+                uuid: "11111111-1111-1111-1111-111111111111".to_string(),
+                label: Some("root".to_string()),
+            }
+        );
+
+        let mut swap = vec![0u8; 8192];
+        swap[4086..4096].copy_from_slice(SWAP_MAGIC);
+        swap[0x41C..0x420].copy_from_slice(b"swap");
+        let swap = probe_filesystem(&mut Cursor::new(swap), 0).unwrap();
+        assert_eq!(swap.kind, FsKind::Swap);
+        assert_eq!(swap.label.as_deref(), Some("swap"));
+        assert_eq!(probe_filesystem(&mut disk, 4096 * 100), None);
+    }
+}
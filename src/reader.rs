@@ -0,0 +1,143 @@
+//! Path lookup shared by the archive readers
+//!
+//! Readers store entries under their literal paths, so `bin/ls` isn't found
+//! in a merged-usr archive that stores `usr/bin/ls` behind a `bin -> usr/bin`
+//! symlink. [`ArchiveReader`] resolves paths the way the kernel would once
+//! the archive is unpacked, following symlinks inside the archive.
+
+use crate::cpio::{CpioReader, FileType};
+use crate::diff::EntryKind;
+use crate::erofs::ErofsReader;
+use crate::iso::IsoReader;
+
+/// Same limit as the kernel's MAXSYMLINKS
+const MAX_SYMLINK_DEPTH: usize = 40;
+
+/// Literal entry lookup, plus symlink-aware resolution built on it.
+///
+/// Paths are relative to the archive root without a leading slash
+/// (`usr/bin/ls`), as [`CpioReader::normalize_path`] produces.
+pub trait ArchiveReader {
+    /// Kind of the entry stored under exactly `path`, symlinks not followed.
+    fn entry_kind(&self, path: &str) -> Option<EntryKind>;
+
+    /// Target of the symlink stored under exactly `path`.
+    fn link_target(&self, path: &str) -> Option<&str>;
+
+    /// Resolve `path`, following symlinks in every component including the
+    /// last, to the normalized path of the entry it names (like `stat`).
+    /// None if it doesn't resolve or loops.
+    fn resolve(&self, path: &str) -> Option<String> {
+        walk(self, path, true)
+    }
+
+    /// Resolve the directories leading to `path` but not `path` itself (like
+    /// `lstat`): `bin/sh` finds the `usr/bin/sh` symlink, not what it points to.
+    fn locate(&self, path: &str) -> Option<String> {
+        walk(self, path, false)
+    }
+}
+
+fn walk<R: ArchiveReader + ?Sized>(reader: &R, path: &str, follow_last: bool) -> Option<String> {
+    // Components still to walk, next one last
+    let mut pending: Vec<String> = path.split('/').rev().map(String::from).collect();
+    let mut resolved: Vec<String> = Vec::new();
+    let mut links = 0;
+    while let Some(part) = pending.pop() {
+        match part.as_str() {
+            "" | "." => continue,
+            ".." => {
+                resolved.pop();
+                continue;
+            }
+            _ => resolved.push(part),
+        }
+        let last = pending.iter().all(|p| p.is_empty() || p == ".");
+        if last && !follow_last {
+            break;
+        }
+        // Directories the archive doesn't list are taken to exist; only the
+        // final entry has to
+        let Some(target) = reader.link_target(&resolved.join("/")) else {
+            continue;
+        };
+        links += 1;
+        if links > MAX_SYMLINK_DEPTH {
+            return None;
+        }
+        resolved.pop();
+        if target.starts_with('/') {
+            resolved.clear();
+        }
+        pending.extend(target.split('/').rev().map(String::from));
+    }
+
+    let path = resolved.join("/");
+    reader.entry_kind(&path).map(|_| path)
+}
+
+impl ArchiveReader for CpioReader {
+    fn entry_kind(&self, path: &str) -> Option<EntryKind> {
+        self.lookup(path).map(|e| match e.file_type {
+            FileType::Regular => EntryKind::File,
+            FileType::Directory => EntryKind::Directory,
+            FileType::Symlink => EntryKind::Symlink,
+            _ => EntryKind::Other,
+        })
+    }
+
+    fn link_target(&self, path: &str) -> Option<&str> {
+        self.lookup(path)
+            .filter(|e| e.is_symlink())
+            .and_then(|e| e.link_target.as_deref())
+    }
+}
+
+impl ArchiveReader for IsoReader {
+    fn entry_kind(&self, path: &str) -> Option<EntryKind> {
+        let path = format!("/{}", path);
+        self.entries().iter().find(|e| e.path == path).map(|e| {
+            if e.is_dir {
+                EntryKind::Directory
+            } else if e.is_symlink {
+                EntryKind::Symlink
+            } else {
+                EntryKind::File
+            }
+        })
+    }
+
+    fn link_target(&self, path: &str) -> Option<&str> {
+        let path = format!("/{}", path);
+        self.entries()
+            .iter()
+            .find(|e| e.is_symlink && e.path == path)
+            .and_then(|e| e.link_target.as_deref())
+    }
+}
+
+impl ArchiveReader for ErofsReader {
+    fn entry_kind(&self, path: &str) -> Option<EntryKind> {
+        self.entries()
+            .iter()
+            .find(|e| e.path.trim_start_matches('/') == path)
+            .map(|e| {
+                if e.is_dir {
+                    EntryKind::Directory
+                } else if e.is_symlink {
+                    EntryKind::Symlink
+                } else if matches!(e.mode.chars().next(), Some('c' | 'b' | 'p' | 's')) {
+                    EntryKind::Other
+                } else {
+                    EntryKind::File
+                }
+            })
+    }
+
+    fn link_target(&self, path: &str) -> Option<&str> {
+        self.entries()
+            .iter()
+            .find(|e| e.is_symlink && e.path.trim_start_matches('/') == path)
+            .and_then(|e| e.link_target.as_deref())
+    }
+}
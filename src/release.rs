@@ -0,0 +1,891 @@
+//! Release gate (release-gate)
+//!
+//! The last step of a release pipeline: every artifact of the release runs
+//! its checklist, then the gate adds what no single checklist can see - size
+//! budgets, detached signatures, and consistency across artifacts (the ISO
+//! carries this rootfs, the initramfs and rootfs ship modules for the same
+//! kernel, every provenance manifest names the same build). The outcome is
+//! one JSON verdict, signed with an ed25519 key so later stages can trust it.
+//!
+//! ```toml
+//! # Checklist per artifact, where the default doesn't fit
+//! [checklists]
+//! initramfs = "install-initramfs"
+//!
+//! # Maximum sizes: bytes, or a number with B/KB/MB/GB/KiB/MiB/GiB
+//! [budgets]
+//! iso = "1.5 GiB"
+//! initramfs = "80 MiB"
+//!
+//! # Artifacts that must carry a valid <artifact>.sig
+//! [signatures]
+//! keyring = "keys/release.pub"
+//! artifacts = ["iso", "qcow2"]
+//!
+//! # Key the verdict is signed with (or --signing-key)
+//! [signing]
+//! key = "keys/gate.key"
+//!
+//! [provenance]
+//! required = true
+//! ```
+//!
+//! Relative paths are relative to the policy file.
+
+use crate::checklist::{CheckCategory, CheckResult, ChecklistType, VerificationReport};
+use crate::cpio::CpioReader;
+use crate::erofs::ErofsReader;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::iso::IsoReader;
+use crate::provenance::Manifest;
+use crate::report::Fingerprint;
+use crate::spec::Spec;
+use crate::ArchiveFormat;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// What an artifact is to the release
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Iso,
+    Rootfs,
+    Initramfs,
+    Qcow2,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Iso, Role::Rootfs, Role::Initramfs, Role::Qcow2];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Iso => "iso",
+            Role::Rootfs => "rootfs",
+            Role::Initramfs => "initramfs",
+            Role::Qcow2 => "qcow2",
+        }
+    }
+
+    pub fn parse_name(s: &str) -> Option<Self> {
+        Role::ALL.into_iter().find(|role| role.name() == s)
+    }
+
+    /// Checklist the artifact runs unless the policy says otherwise
+    pub fn default_checklist(self) -> ChecklistType {
+        match self {
+            Role::Iso => ChecklistType::Iso,
+            Role::Rootfs => ChecklistType::Rootfs,
+            Role::Initramfs => ChecklistType::InstallInitramfs,
+            Role::Qcow2 => ChecklistType::Qcow2,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// An artifact of the release
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub role: Role,
+    pub path: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Size {
+    Bytes(u64),
+    Text(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignaturesFile {
+    keyring: PathBuf,
+    #[serde(default)]
+    artifacts: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SigningFile {
+    key: PathBuf,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProvenanceFile {
+    #[serde(default)]
+    required: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    #[serde(default)]
+    checklists: BTreeMap<String, String>,
+    #[serde(default)]
+    budgets: BTreeMap<String, Size>,
+    #[serde(default)]
+    signatures: Option<SignaturesFile>,
+    #[serde(default)]
+    signing: Option<SigningFile>,
+    #[serde(default)]
+    provenance: ProvenanceFile,
+}
+
+/// A loaded release policy
+#[derive(Debug, Clone)]
+pub struct Policy {
+    pub source: PathBuf,
+    /// SHA256 of the policy file, recorded in the verdict
+    pub sha256: String,
+    pub checklists: BTreeMap<Role, ChecklistType>,
+    /// Maximum size in bytes
+    pub budgets: BTreeMap<Role, u64>,
+    pub keyring: Option<PathBuf>,
+    /// Artifacts that must carry a valid signature
+    pub signed: Vec<Role>,
+    pub signing_key: Option<PathBuf>,
+    pub require_provenance: bool,
+}
+
+/// Parse a size: plain bytes, or a number with a unit (`1.5 GiB`, `80MB`).
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" | "k" => 1000,
+        "mb" | "m" => 1000 * 1000,
+        "gb" | "g" => 1000 * 1000 * 1000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> Result<Self, FsdbgError> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| FsdbgError::from(e).with_path(path))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        let mut policy = Self::parse(&text, base).map_err(|e| e.with_path(path))?;
+        policy.source = path.to_path_buf();
+        policy.sha256 = crate::report::sha256_file(path)?;
+        Ok(policy)
+    }
+
+    /// Parse a policy, resolving relative paths against `base`.
+    pub fn parse(text: &str, base: &Path) -> Result<Self, FsdbgError> {
+        let invalid = |message: String| {
+            FsdbgError::new(
+                ErrorCode::ParseError,
+                format!("Invalid release policy: {}", message),
+            )
+        };
+        let file: PolicyFile = toml::from_str(text).map_err(|e| invalid(e.to_string()))?;
+        let role = |name: &str| {
+            Role::parse_name(name).ok_or_else(|| {
+                invalid(format!(
+                    "Unknown artifact: {}. Valid artifacts: iso, rootfs, initramfs, qcow2",
+                    name
+                ))
+            })
+        };
+
+        let mut checklists = BTreeMap::new();
+        for (name, checklist) in &file.checklists {
+            let checklist = ChecklistType::parse_name(checklist)
+                .ok_or_else(|| invalid(format!("Unknown checklist: {}", checklist)))?;
+            checklists.insert(role(name)?, checklist);
+        }
+        let mut budgets = BTreeMap::new();
+        for (name, size) in &file.budgets {
+            let bytes = match size {
+                Size::Bytes(bytes) => *bytes,
+                Size::Text(text) => {
+                    parse_size(text).ok_or_else(|| invalid(format!("Invalid size: {}", text)))?
+                }
+            };
+            budgets.insert(role(name)?, bytes);
+        }
+        let (keyring, signed) = match file.signatures {
+            Some(signatures) => (
+                Some(base.join(signatures.keyring)),
+                signatures
+                    .artifacts
+                    .iter()
+                    .map(|name| role(name))
+                    .collect::<Result<_, _>>()?,
+            ),
+            None => (None, Vec::new()),
+        };
+
+        Ok(Policy {
+            source: PathBuf::new(),
+            sha256: String::new(),
+            checklists,
+            budgets,
+            keyring,
+            signed,
+            signing_key: file.signing.map(|s| base.join(s.key)),
+            require_provenance: file.provenance.required,
+        })
+    }
+
+    pub fn checklist(&self, role: Role) -> ChecklistType {
+        self.checklists
+            .get(&role)
+            .copied()
+            .unwrap_or(role.default_checklist())
+    }
+}
+
+/// Whether `artifact` fits its size budget.
+pub fn check_budget(role: Role, size: u64, budget: u64) -> CheckResult {
+    use humansize::{format_size, BINARY};
+    let item = format!("budget: {}", role);
+    if size <= budget {
+        CheckResult::pass(
+            format!(
+                "{} ({} of {})",
+                item,
+                format_size(size, BINARY),
+                format_size(budget, BINARY)
+            ),
+            CheckCategory::Other,
+        )
+    } else {
+        CheckResult::fail(
+            item,
+            CheckCategory::Other,
+            format!(
+                "{} is over the {} budget by {}",
+                format_size(size, BINARY),
+                format_size(budget, BINARY),
+                format_size(size - budget, BINARY)
+            ),
+        )
+        .with_hint("Trim the artifact, or raise the budget in the release policy")
+    }
+}
+
+/// Whether `artifact` carries a valid `<artifact>.sig` for `keyring`.
+pub fn check_signature(artifact: &Artifact, keyring: &Path) -> CheckResult {
+    let mut sig = artifact.path.as_os_str().to_owned();
+    sig.push(".sig");
+    let sig = PathBuf::from(sig);
+    let item = format!("signature: {}", artifact.role);
+    if !sig.exists() {
+        return CheckResult::fail(
+            item,
+            CheckCategory::Signature,
+            format!("No signature ({} is missing)", sig.display()),
+        )
+        .with_hint("Sign the artifact before the gate runs");
+    }
+    match crate::signature::verify(&artifact.path, &sig, keyring) {
+        Ok(status) => {
+            let mut result = status.to_check_result(&sig);
+            result.item = format!("{} ({})", item, result.item);
+            result
+        }
+        Err(e) => CheckResult::fail(item, CheckCategory::Signature, e.to_string()),
+    }
+}
+
+/// Kernel versions an artifact ships modules for (`usr/lib/modules/<version>`).
+/// None if fsdbg can't list the artifact's files.
+pub fn module_versions(path: &Path) -> Option<BTreeSet<String>> {
+    let paths: Vec<String> = if let Some(range) = crate::embedded_initrd(path).ok()? {
+        let reader = CpioReader::open_range(path, range).ok()?;
+        reader.entries().iter().map(|e| e.path.clone()).collect()
+    } else {
+        match crate::detect_format(path).ok()? {
+            ArchiveFormat::Cpio | ArchiveFormat::CpioGzip => {
+                let reader = CpioReader::open(path).ok()?;
+                reader.entries().iter().map(|e| e.path.clone()).collect()
+            }
+            ArchiveFormat::Erofs => {
+                let reader = ErofsReader::open(path).ok()?;
+                reader.entries().iter().map(|e| e.path.clone()).collect()
+            }
+            ArchiveFormat::Iso => return None,
+        }
+    };
+    let versions = paths
+        .iter()
+        .filter_map(|p| {
+            let p = p.trim_start_matches("./").trim_start_matches('/');
+            ["usr/lib/modules/", "lib/modules/"]
+                .iter()
+                .find_map(|prefix| p.strip_prefix(prefix))
+        })
+        .filter_map(|rest| rest.split('/').next())
+        .filter(|version| !version.is_empty())
+        .map(str::to_string)
+        .collect();
+    Some(versions)
+}
+
+/// Manifest fields that differ between artifacts, each with its values.
+pub fn build_mismatches(manifests: &[(Role, Manifest)]) -> Vec<String> {
+    let field = |manifest: &Manifest, name: &str| match name {
+        "build_id" => Some(manifest.build_id.clone()),
+        "git_commit" => manifest.git_commit.clone(),
+        _ => Some(manifest.distro_spec.version.clone()),
+    };
+    let mut problems = Vec::new();
+    for name in ["build_id", "git_commit", "distro_spec"] {
+        let values: BTreeSet<String> = manifests
+            .iter()
+            .filter_map(|(_, m)| field(m, name))
+            .collect();
+        if values.len() > 1 {
+            let each: Vec<String> = manifests
+                .iter()
+                .filter_map(|(role, m)| Some(format!("{} {}", role, field(m, name)?)))
+                .collect();
+            problems.push(format!("{} differs ({})", name, each.join(", ")));
+        }
+    }
+    problems
+}
+
+/// Checks across the release's artifacts; manifests must name `spec`.
+pub fn check_consistency(artifacts: &[Artifact], policy: &Policy, spec: &Spec) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    let find = |role: Role| artifacts.iter().find(|a| a.role == role);
+
+    // The ISO boots the rootfs it carries, not the one handed to the gate
+    if let (Some(iso), Some(rootfs)) = (find(Role::Iso), find(Role::Rootfs)) {
+        let item = format!(
+            "release: ISO carries the rootfs ({})",
+            distro_spec::shared::ROOTFS_ISO_PATH
+        );
+        let embedded = IsoReader::open(&iso.path).ok().and_then(|reader| {
+            let path = format!("/{}", distro_spec::shared::ROOTFS_ISO_PATH);
+            reader
+                .entries()
+                .iter()
+                .find(|e| e.path == path)
+                .map(|e| e.size)
+        });
+        let size = std::fs::metadata(&rootfs.path).map(|m| m.len()).ok();
+        match (embedded, size) {
+            (Some(embedded), Some(size)) if embedded == size => {
+                results.push(CheckResult::pass(item, CheckCategory::Other))
+            }
+            (Some(embedded), Some(size)) => results.push(
+                CheckResult::fail(
+                    item,
+                    CheckCategory::Other,
+                    format!(
+                        "The ISO's rootfs is {} bytes, {} is {} bytes",
+                        embedded,
+                        rootfs.path.display(),
+                        size
+                    ),
+                )
+                .with_hint(
+                    "Rebuild the ISO from this rootfs, or gate the rootfs it was built from",
+                ),
+            ),
+            // Missing from the ISO is the ISO checklist's failure to report
+            _ => {}
+        }
+    }
+
+    // The initramfs loads modules from the rootfs's kernel
+    if let (Some(initramfs), Some(rootfs)) = (find(Role::Initramfs), find(Role::Rootfs)) {
+        let item = "release: kernel modules (initramfs vs rootfs)";
+        if let (Some(early), Some(late)) = (
+            module_versions(&initramfs.path),
+            module_versions(&rootfs.path),
+        ) {
+            if !early.is_empty() && !late.is_empty() {
+                let join =
+                    |set: &BTreeSet<String>| set.iter().cloned().collect::<Vec<_>>().join(", ");
+                results.push(if early.is_subset(&late) {
+                    CheckResult::pass(format!("{} ({})", item, join(&early)), CheckCategory::Other)
+                } else {
+                    CheckResult::fail(
+                        item,
+                        CheckCategory::Other,
+                        format!(
+                            "The initramfs has modules for {}, the rootfs for {}",
+                            join(&early),
+                            join(&late)
+                        ),
+                    )
+                    .with_hint("Build the initramfs against the rootfs's kernel")
+                });
+            }
+        }
+    }
+
+    // Every manifest names the same build, from the spec the gate verifies against
+    let mut manifests: Vec<(Role, Manifest)> = Vec::new();
+    for artifact in artifacts {
+        let item = format!("provenance: {}", artifact.role);
+        match crate::provenance::find(&artifact.path) {
+            Ok(Some((manifest, _))) => {
+                if let Some(mismatch) = manifest.spec_mismatch(spec) {
+                    results.push(CheckResult::fail(item, CheckCategory::Other, mismatch));
+                }
+                manifests.push((artifact.role, manifest));
+            }
+            Ok(None) if policy.require_provenance => results.push(
+                CheckResult::fail(item, CheckCategory::Other, "No provenance manifest").with_hint(
+                    "Write /.fsdbg-manifest.json (or a sidecar) when building the artifact",
+                ),
+            ),
+            Ok(None) => {}
+            Err(e) => results.push(CheckResult::fail(item, CheckCategory::Other, e.to_string())),
+        }
+    }
+    if manifests.len() > 1 {
+        let problems = build_mismatches(&manifests);
+        let item = "release: one build across artifacts";
+        results.push(if problems.is_empty() {
+            CheckResult::pass(
+                format!("{} ({})", item, manifests[0].1.build_id),
+                CheckCategory::Other,
+            )
+        } else {
+            CheckResult::fail(item, CheckCategory::Other, problems.join("; "))
+                .with_hint("Gate the artifacts of one build together")
+        });
+    }
+
+    results
+}
+
+/// One artifact's outcome in a verdict
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactVerdict {
+    pub role: Role,
+    pub path: PathBuf,
+    pub sha256: String,
+    pub size: u64,
+    /// Checklist type name (e.g. "rootfs")
+    pub checklist: String,
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub failures: Vec<CheckResult>,
+}
+
+impl ArtifactVerdict {
+    pub fn new(
+        artifact: &Artifact,
+        fingerprint: &Fingerprint,
+        checklist: ChecklistType,
+        report: &VerificationReport,
+    ) -> Self {
+        Self {
+            role: artifact.role,
+            path: artifact.path.clone(),
+            sha256: fingerprint.sha256.clone(),
+            size: fingerprint.size,
+            checklist: checklist.key().to_string(),
+            passed: report.passed(),
+            failed: report.failed(),
+            skipped: report.skipped(),
+            failures: report
+                .results
+                .iter()
+                .filter(|r| r.is_failure())
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// The policy a verdict was reached under
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyRef {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+/// Outcome of a release gate run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Verdict {
+    pub fsdbg_version: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// distro-spec the checklists verified against
+    pub spec: String,
+    pub policy: PolicyRef,
+    pub passed: bool,
+    pub artifacts: Vec<ArtifactVerdict>,
+    /// Budget, signature and cross-artifact checks
+    pub checks: Vec<CheckResult>,
+}
+
+impl Verdict {
+    pub fn new(
+        timestamp: u64,
+        policy: &Policy,
+        spec: &Spec,
+        artifacts: Vec<ArtifactVerdict>,
+        checks: Vec<CheckResult>,
+    ) -> Self {
+        let passed =
+            artifacts.iter().all(|a| a.failed == 0) && !checks.iter().any(|c| c.is_failure());
+        Self {
+            fsdbg_version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp,
+            spec: spec.id(),
+            policy: PolicyRef {
+                path: policy.source.clone(),
+                sha256: policy.sha256.clone(),
+            },
+            passed,
+            artifacts,
+            checks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(parse_size("1.5 GiB"), Some(3 << 29));
+        assert_eq!(parse_size("80MB"), Some(80_000_000));
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("lots"), None);
+
+        let policy = Policy::parse(
+            r#"
+            [checklists]
+            initramfs = "live-initramfs"
+            [budgets]
+            iso = "2 GiB"
+            rootfs = 1000
+            [signatures]
+            keyring = "keys/release.pub"
+            artifacts = ["iso"]
+            [signing]
+            key = "/etc/fsdbg/gate.key"
+            "#,
+            Path::new("/srv/release"),
+        )
+        .unwrap();
+        assert_eq!(
+            policy.checklist(Role::Initramfs),
+            ChecklistType::LiveInitramfs
+        );
+        assert_eq!(policy.checklist(Role::Rootfs), ChecklistType::Rootfs);
+        assert_eq!(policy.budgets[&Role::Rootfs], 1000);
+        assert_eq!(
+            policy.keyring.as_deref(),
+            Some(Path::new("/srv/release/keys/release.pub"))
+        );
+        assert_eq!(policy.signed, [Role::Iso]);
+        assert_eq!(
+            policy.signing_key.as_deref(),
+            Some(Path::new("/etc/fsdbg/gate.key"))
+        );
+
+        assert!(Policy::parse("[budgets]\nkernel = 5", Path::new("")).is_err());
+        assert!(Policy::parse("[budget]\niso = 5", Path::new("")).is_err());
+
+        let manifest = |build: &str, commit: &str| {
+            Manifest::parse(
+                format!(
+                    r#"{{"build_id": "{}", "git_commit": "{}", "distro_spec": {{"version": "0.1.0"}}}}"#,
+                    build, commit
+                )
+                .as_bytes(),
+            )
+            .unwrap()
+        };
+        let same = [
+            (Role::Iso, manifest("b7", "4a20e2b")),
+            (Role::Rootfs, manifest("b7", "4a20e2b")),
+        ];
+        assert!(build_mismatches(&same).is_empty());
+        let mixed = [
+            (Role::Iso, manifest("b7", "4a20e2b")),
+            (Role::Initramfs, manifest("b6", "4a20e2b")),
+        ];
+        assert_eq!(
+            build_mismatches(&mixed),
+            ["build_id differs (iso b7, initramfs b6)"]
+        );
+
+        assert!(check_budget(Role::Iso, 10, 10).passed);
+        let over = check_budget(Role::Iso, 3 << 30, 2 << 30);
+        assert_eq!(
+            over.message.as_deref(),
+            Some("3 GiB is over the 2 GiB budget by 1 GiB")
+        );
+    }
+
+    #[test]
+    fn test_budget_overrun() {
+        let within = check_budget(Role::Initramfs, 60 << 20, 80 << 20);
+        assert!(within.passed);
+        assert_eq!(within.item, "budget: initramfs (60 MiB of 80 MiB)");
+
+        let over = check_budget(Role::Initramfs, (80 << 20) + 1, 80 << 20);
+        assert!(over.is_failure());
+        assert_eq!(over.item, "budget: initramfs");
+        assert_eq!(
+            over.hint.as_deref(),
+            Some("Trim the artifact, or raise the budget in the release policy")
+        );
+
+        // One overrun fails the release even when every checklist passes
+        let policy = Policy::parse("[budgets]\ninitramfs = \"80 MiB\"", Path::new("")).unwrap();
+        let verdict = Verdict::new(0, &policy, Spec::builtin(), Vec::new(), vec![over]);
+        assert!(!verdict.passed);
+        let verdict = Verdict::new(0, &policy, Spec::builtin(), Vec::new(), vec![within]);
+        assert!(verdict.passed);
+    }
+
+    #[test]
+    fn test_signature_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let artifact = Artifact {
+            role: Role::Iso,
+            path: dir.path().join("levitate.iso"),
+        };
+        std::fs::write(&artifact.path, b"iso image").unwrap();
+        let sig = dir.path().join("levitate.iso.sig");
+
+        let missing = check_signature(&artifact, dir.path());
+        assert!(missing.is_failure());
+        assert_eq!(
+            missing.message,
+            Some(format!("No signature ({} is missing)", sig.display()))
+        );
+
+        let release_key = dir.path().join("release.key");
+        std::fs::write(&release_key, "07".repeat(32)).unwrap();
+        let other_key = dir.path().join("other.key");
+        std::fs::write(&other_key, "2a".repeat(32)).unwrap();
+        let (signature, public) =
+            crate::signature::sign_ed25519(b"iso image", &release_key).unwrap();
+        let keyring = dir.path().join("release.pub");
+        std::fs::write(&keyring, &public).unwrap();
+
+        std::fs::write(&sig, signature).unwrap();
+        let good = check_signature(&artifact, &keyring);
+        assert!(good.passed, "{:?}", good.message);
+        assert!(good.item.starts_with("signature: iso ("));
+
+        // Signed by a key that isn't in the keyring
+        let (signature, _) = crate::signature::sign_ed25519(b"iso image", &other_key).unwrap();
+        std::fs::write(&sig, signature).unwrap();
+        let foreign = check_signature(&artifact, &keyring);
+        assert!(foreign.is_failure());
+        assert_eq!(
+            foreign.message,
+            Some(format!("Bad signature (key {})", public))
+        );
+
+        // The artifact changed after signing
+        let (signature, _) = crate::signature::sign_ed25519(b"iso image", &release_key).unwrap();
+        std::fs::write(&sig, signature).unwrap();
+        std::fs::write(&artifact.path, b"iso image, rebuilt").unwrap();
+        assert!(check_signature(&artifact, &keyring).is_failure());
+    }
+
+    /// Build a newc archive holding `paths` as empty files.
+    fn newc(paths: &[&str]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (path, mode) in paths
+            .iter()
+            .map(|p| (*p, 0o100644))
+            .chain([("TRAILER!!!", 0)])
+        {
+            out.extend_from_slice(b"070701");
+            let fields = [0, mode, 0, 0, 1, 0, 0, 0, 0, 0, 0, path.len() as u32 + 1, 0];
+            for field in fields {
+                out.extend_from_slice(format!("{:08x}", field).as_bytes());
+            }
+            out.extend_from_slice(path.as_bytes());
+            out.push(0);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        out
+    }
+
+    #[test]
+    fn test_cross_artifact_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let spec = Spec::builtin();
+        let artifact = |role: Role, name: &str, paths: &[&str], build: Option<&str>| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, newc(paths)).unwrap();
+            if let Some(build) = build {
+                let manifest = format!(
+                    r#"{{"build_id": "{}", "git_commit": "4a20e2b", "distro_spec": {{"version": "{}"}}}}"#,
+                    build, spec.version
+                );
+                std::fs::write(crate::provenance::sidecar_path(&path), manifest).unwrap();
+            }
+            Artifact { role, path }
+        };
+        let failures = |artifacts: &[Artifact], policy: &Policy| {
+            check_consistency(artifacts, policy, spec)
+                .into_iter()
+                .filter(|r| r.is_failure())
+                .map(|r| (r.item, r.message.unwrap_or_default()))
+                .collect::<Vec<_>>()
+        };
+        let policy = Policy::parse("", Path::new("")).unwrap();
+
+        let initramfs = artifact(
+            Role::Initramfs,
+            "initramfs.cpio",
+            &["usr/lib/modules/6.12.9/kernel/fs/erofs/erofs.ko"],
+            Some("b7"),
+        );
+        let rootfs = artifact(
+            Role::Rootfs,
+            "rootfs.cpio",
+            &[
+                "usr/lib/modules/6.12.9/modules.dep",
+                "usr/lib/modules/6.13.1/modules.dep",
+            ],
+            Some("b7"),
+        );
+        let matched = [initramfs.clone(), rootfs.clone()];
+        assert!(failures(&matched, &policy).is_empty());
+        let results = check_consistency(&matched, &policy, spec);
+        assert!(results
+            .iter()
+            .any(|r| r.item == "release: kernel modules (initramfs vs rootfs) (6.12.9)"));
+        assert!(results
+            .iter()
+            .any(|r| r.item == "release: one build across artifacts (b7)"));
+
+        // Initramfs for another kernel, from another build
+        let stale = artifact(
+            Role::Initramfs,
+            "stale.cpio",
+            &["lib/modules/6.11.2/kernel/fs/erofs/erofs.ko"],
+            Some("b6"),
+        );
+        assert_eq!(
+            failures(&[stale, rootfs.clone()], &policy),
+            [
+                (
+                    "release: kernel modules (initramfs vs rootfs)".to_string(),
+                    "The initramfs has modules for 6.11.2, the rootfs for 6.12.9, 6.13.1"
+                        .to_string()
+                ),
+                (
+                    "release: one build across artifacts".to_string(),
+                    "build_id differs (initramfs b6, rootfs b7)".to_string()
+                ),
+            ]
+        );
+
+        // Provenance is only demanded when the policy asks for it
+        let unlabelled = artifact(Role::Qcow2, "disk.qcow2", &[], None);
+        let artifacts = [initramfs, rootfs, unlabelled];
+        assert!(failures(&artifacts, &policy).is_empty());
+        let strict = Policy::parse("[provenance]\nrequired = true", Path::new("")).unwrap();
+        assert_eq!(
+            failures(&artifacts, &strict),
+            [(
+                "provenance: qcow2".to_string(),
+                "No provenance manifest".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_signed_verdict_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy_path = dir.path().join("release.toml");
+        std::fs::write(&policy_path, "[budgets]\nrootfs = 4096\n").unwrap();
+        let policy = Policy::load(&policy_path).unwrap();
+
+        let artifact = Artifact {
+            role: Role::Rootfs,
+            path: dir.path().join("rootfs.erofs"),
+        };
+        let fingerprint = Fingerprint {
+            sha256: "ab".repeat(32),
+            size: 8192,
+            format: Some("erofs".to_string()),
+            volume: None,
+            uuid: None,
+        };
+        let mut report = VerificationReport::new("rootfs");
+        report.add(CheckResult::pass("usr/bin/bash", CheckCategory::Binary));
+        report.add(CheckResult::fail(
+            "usr/bin/sudo",
+            CheckCategory::Binary,
+            "Missing",
+        ));
+        let artifacts = vec![ArtifactVerdict::new(
+            &artifact,
+            &fingerprint,
+            policy.checklist(Role::Rootfs),
+            &report,
+        )];
+        let checks = vec![check_budget(Role::Rootfs, fingerprint.size, 4096)];
+        let verdict = Verdict::new(1_700_000_000, &policy, Spec::builtin(), artifacts, checks);
+        assert!(!verdict.passed);
+        assert_eq!(verdict.policy.path, policy_path);
+
+        // Written and signed the way the gate does it
+        let json = serde_json::to_string_pretty(&verdict).unwrap() + "\n";
+        let output = dir.path().join("verdict.json");
+        std::fs::write(&output, &json).unwrap();
+        let key = dir.path().join("gate.key");
+        std::fs::write(&key, "07".repeat(32)).unwrap();
+        let (signature, public) = crate::signature::sign_ed25519(json.as_bytes(), &key).unwrap();
+        let sig = dir.path().join("verdict.json.sig");
+        std::fs::write(&sig, signature).unwrap();
+        let keyring = dir.path().join("gate.pub");
+        std::fs::write(&keyring, &public).unwrap();
+        assert!(
+            crate::signature::verify(&output, &sig, &keyring)
+                .unwrap()
+                .valid
+        );
+
+        let read: Verdict = serde_json::from_slice(&std::fs::read(&output).unwrap()).unwrap();
+        assert!(!read.passed);
+        assert_eq!(read.timestamp, 1_700_000_000);
+        assert_eq!(read.spec, Spec::builtin().id());
+        assert_eq!(read.policy.sha256, policy.sha256);
+        assert_eq!(read.artifacts[0].role, Role::Rootfs);
+        assert_eq!((read.artifacts[0].passed, read.artifacts[0].failed), (1, 1));
+        assert_eq!(read.artifacts[0].failures[0].item, "usr/bin/sudo");
+        assert_eq!(read.checks[0].item, "budget: rootfs");
+
+        // Flipping the outcome breaks the signature
+        let forged = json.replace("\"passed\": false", "\"passed\": true");
+        assert_ne!(forged, json);
+        std::fs::write(&output, forged).unwrap();
+        assert!(
+            !crate::signature::verify(&output, &sig, &keyring)
+                .unwrap()
+                .valid
+        );
+    }
+}
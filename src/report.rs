@@ -0,0 +1,320 @@
+//! Report metadata: artifact fingerprints and saved reports
+//!
+//! A [`Fingerprint`] identifies the exact artifact a report verified (hash,
+//! size, format, volume label/UUID), so a pasted report can be matched to
+//! one of several nightly builds.
+//!
+//! A [`SavedReport`] wraps a [`VerificationReport`] with the metadata of
+//! the run that produced it (fsdbg version, when it ran and how long it
+//! took). Written as JSON by `verify --save-report` and re-rendered by
+//! `fsdbg show-report`, so the details survive after the CI log is gone.
+
+use crate::checklist::VerificationReport;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::ArchiveFormat;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Identity of a verified artifact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// SHA256 of the artifact, lowercase hex
+    pub sha256: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Detected format, if recognised
+    pub format: Option<String>,
+    /// ISO volume id or EROFS volume name
+    pub volume: Option<String>,
+    /// ISO creation-date UUID (as blkid reports it) or EROFS UUID
+    pub uuid: Option<String>,
+}
+
+impl Fingerprint {
+    /// Hash an artifact and read its volume label/UUID from the headers.
+    /// A directory tree is fingerprinted by its listing instead.
+    pub fn of(path: &Path) -> Result<Self, FsdbgError> {
+        if path.is_dir() {
+            return Self::of_dir(path);
+        }
+        let best = crate::detect::probe(path)?.into_iter().next();
+        let (volume, uuid) = match best.and_then(|m| m.detector.format) {
+            Some(ArchiveFormat::Iso) => iso_volume(path)?,
+            Some(ArchiveFormat::Erofs) => erofs_volume(path)?,
+            _ => (None, None),
+        };
+        Ok(Self {
+            sha256: sha256_file(path)?,
+            size: fs::metadata(path)?.len(),
+            format: best.map(|m| m.detector.name.to_string()),
+            volume,
+            uuid,
+        })
+    }
+
+    /// Hash of a tree's listing (names, types, modes, owners, sizes, mtimes
+    /// and link targets) - not of its contents, which would mean reading
+    /// every file before the checks even start. The size is the total of
+    /// its regular files.
+    fn of_dir(path: &Path) -> Result<Self, FsdbgError> {
+        let tree = crate::dir::DirReader::open(path)?;
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        for entry in tree.entries() {
+            hasher.update(&entry.raw_path);
+            hasher.update(
+                format!(
+                    "\0{:o} {}:{} {} {}\0{}\n",
+                    entry.mode,
+                    entry.uid,
+                    entry.gid,
+                    entry.size,
+                    entry.mtime,
+                    entry.link_target.as_deref().unwrap_or_default()
+                )
+                .as_bytes(),
+            );
+            if entry.is_file() {
+                size += entry.size;
+            }
+        }
+        Ok(Self {
+            sha256: hex(&hasher.finalize()),
+            size,
+            format: Some("directory".to_string()),
+            volume: None,
+            uuid: None,
+        })
+    }
+}
+
+/// Primary volume descriptor: volume id at 40..72, creation date at 813..829.
+fn iso_volume(path: &Path) -> Result<(Option<String>, Option<String>), FsdbgError> {
+    let mut pvd = [0u8; 2048];
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(0x8000))?;
+    file.read_exact(&mut pvd)?;
+
+    let volume = header_string(&pvd[40..72]);
+    // blkid formats the creation date as the filesystem UUID
+    let date = &pvd[813..829];
+    let uuid = date.iter().all(u8::is_ascii_digit).then(|| {
+        let d = std::str::from_utf8(date).unwrap_or_default();
+        format!(
+            "{}-{}-{}-{}-{}-{}-{}",
+            &d[0..4],
+            &d[4..6],
+            &d[6..8],
+            &d[8..10],
+            &d[10..12],
+            &d[12..14],
+            &d[14..16]
+        )
+    });
+    Ok((volume, uuid.filter(|u| !u.starts_with("0000"))))
+}
+
+/// Superblock at 1024: UUID at 48..64, volume name at 64..80.
+fn erofs_volume(path: &Path) -> Result<(Option<String>, Option<String>), FsdbgError> {
+    let mut sb = [0u8; 128];
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(1024))?;
+    file.read_exact(&mut sb)?;
+
+    let raw = &sb[48..64];
+    let uuid = raw.iter().any(|b| *b != 0).then(|| {
+        let hex: String = raw.iter().map(|b| format!("{:02x}", b)).collect();
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    });
+    Ok((header_string(&sb[64..80]), uuid))
+}
+
+/// Space- or NUL-padded header field, None if blank.
+fn header_string(bytes: &[u8]) -> Option<String> {
+    let s = String::from_utf8_lossy(bytes);
+    let s = s.trim_end_matches(['\0', ' ']);
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+/// A verification report plus the metadata of the run that produced it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedReport {
+    /// fsdbg version that ran the checks
+    pub fsdbg_version: String,
+    /// Artifact path as given on the command line
+    pub archive: PathBuf,
+    /// Checklist type name (e.g. "rootfs")
+    pub checklist: String,
+    /// Start of the run, seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Wall-clock time of the run, in milliseconds
+    pub duration_ms: u64,
+    pub report: VerificationReport,
+}
+
+impl SavedReport {
+    /// Wrap a report from a run that started at `started`.
+    pub fn new(
+        archive: &Path,
+        checklist: &str,
+        started: SystemTime,
+        duration: Duration,
+        report: VerificationReport,
+    ) -> Self {
+        Self {
+            fsdbg_version: env!("CARGO_PKG_VERSION").to_string(),
+            archive: archive.to_path_buf(),
+            checklist: checklist.to_string(),
+            timestamp: started
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            duration_ms: duration.as_millis() as u64,
+            report,
+        }
+    }
+
+    /// Write the report as pretty-printed JSON.
+    pub fn save(&self, path: &Path) -> Result<(), FsdbgError> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| {
+            FsdbgError::new(
+                ErrorCode::IoError,
+                format!("Cannot serialize report: {}", e),
+            )
+        })?;
+        fs::write(path, json + "\n")?;
+        Ok(())
+    }
+
+    /// Read a report written by [`SavedReport::save`].
+    pub fn load(path: &Path) -> Result<Self, FsdbgError> {
+        if !path.exists() {
+            return Err(FsdbgError::file_not_found(path));
+        }
+        let file = BufReader::new(File::open(path)?);
+        serde_json::from_reader(file).map_err(|e| {
+            FsdbgError::invalid_format(format!("Not a saved fsdbg report: {}", e)).with_path(path)
+        })
+    }
+}
+
+/// SHA256 of a file's contents, lowercase hex.
+pub fn sha256_file(path: &Path) -> Result<String, FsdbgError> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Format Unix seconds as an ISO 8601 UTC timestamp.
+pub fn format_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_date(secs);
+    let rem = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// UTC (year, month, day) of Unix seconds.
+pub fn civil_date(secs: u64) -> (i64, u32, u32) {
+    let days = (secs / 86400) as i64;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::{CheckCategory, CheckResult};
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("initramfs.img");
+        fs::write(&archive, b"abc").unwrap();
+
+        let mut report = VerificationReport::new("Rootfs");
+        report.fingerprint = Some(Fingerprint::of(&archive).unwrap());
+        report.add(
+            CheckResult::fail("usr/bin/ls", CheckCategory::Binary, "Missing").with_hint("fix"),
+        );
+        let saved = SavedReport::new(
+            &archive,
+            "rootfs",
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            Duration::from_millis(42),
+            report,
+        );
+
+        let path = dir.path().join("report.json");
+        saved.save(&path).unwrap();
+        let loaded = SavedReport::load(&path).unwrap();
+        assert_eq!(loaded.timestamp, 1_700_000_000);
+        assert_eq!(loaded.duration_ms, 42);
+        assert_eq!(loaded.report.failed(), 1);
+        assert_eq!(loaded.report.results[0].hint.as_deref(), Some("fix"));
+
+        let fingerprint = loaded.report.fingerprint.unwrap();
+        assert_eq!(
+            fingerprint.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(fingerprint.size, 3);
+        assert_eq!(fingerprint.volume, None);
+    }
+
+    #[test]
+    fn test_iso_volume() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("levitate.iso");
+        let mut image = vec![0u8; 0x8000 + 2048];
+        let pvd = &mut image[0x8000..];
+        pvd[1..6].copy_from_slice(b"CD001");
+        pvd[40..72].copy_from_slice(format!("{:<32}", "LEVITATEOS").as_bytes());
+        pvd[813..830].copy_from_slice(b"2026101612000000\0");
+        fs::write(&path, &image).unwrap();
+
+        let fingerprint = Fingerprint::of(&path).unwrap();
+        assert_eq!(fingerprint.format.as_deref(), Some("ISO 9660"));
+        assert_eq!(fingerprint.volume.as_deref(), Some("LEVITATEOS"));
+        assert_eq!(fingerprint.uuid.as_deref(), Some("2026-10-16-12-00-00-00"));
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+    }
+}
@@ -0,0 +1,451 @@
+//! JSON-RPC verification service (feature `rpc`)
+//!
+//! Line-delimited JSON-RPC 2.0 over a byte stream (stdin/stdout when run as
+//! `fsdbg rpc`), for build orchestrators that keep one fsdbg process alive
+//! and submit artifacts as they are produced. Methods:
+//! - `verify` `{path, type}` - checklist report
+//! - `inspect` `{path}` - format and entry counts
+//! - `upload` `{name, size}` - the `size` bytes after the request line are
+//!   an artifact; returns the `path` to verify it by
+//! - `cancel` `{id}` - cancel an in-flight request
+//! - `shutdown` - stop accepting requests, finish in-flight ones
+//!
+//! `verify`/`inspect` are queued to a pool of `jobs` worker threads, and
+//! responses are written as they complete - match them up by `id`, which
+//! must be non-null and not already in flight. Requests without an `id` are
+//! notifications and are never answered; a `verify`/`inspect` notification
+//! is dropped, its result having nowhere to go.
+//!
+//! `upload` streams an artifact through the same input, so the orchestrator
+//! needn't share a filesystem with fsdbg. The stream can't be resynchronized
+//! after an `upload` whose `size` can't be read, so that ends the session.
+//!
+//! Cancelling answers the request immediately with a "Request cancelled"
+//! error. A queued request is never started; a running one stops at its next
+//! stage (parsing, the checklist, fingerprinting) and its result is
+//! discarded.
+
+use crate::checklist::auto;
+use crate::checklist::ChecklistType;
+use crate::cpio::Limits;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::report::Fingerprint;
+use crate::sanitize::Destination;
+use crate::server::{report_json, Archive};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+/// Same code LSP uses for cancelled requests
+const REQUEST_CANCELLED: i64 = -32800;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct VerifyParams {
+    path: PathBuf,
+    #[serde(rename = "type")]
+    checklist: String,
+}
+
+#[derive(Deserialize)]
+struct InspectParams {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct UploadParams {
+    name: String,
+    size: u64,
+}
+
+#[derive(Deserialize)]
+struct CancelParams {
+    id: Value,
+}
+
+/// A `verify` or `inspect` request waiting for a worker
+struct Job {
+    id: Value,
+    method: String,
+    params: Value,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Shared state between the reader loop and workers.
+struct State<W> {
+    output: Mutex<W>,
+    /// In-flight requests by serialized id, with their cancellation flag
+    pending: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl<W: Write> State<W> {
+    fn send(&self, message: Value) {
+        let mut out = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        // The orchestrator going away ends the session; nothing to report to
+        let _ = writeln!(out, "{}", message);
+        let _ = out.flush();
+    }
+
+    fn reply(&self, id: &Value, result: Result<Value, (i64, String)>) {
+        self.send(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        });
+    }
+
+    /// Track a request as in flight under `id`. False if `id` is null or
+    /// another request in flight has it, since its response and `cancel`
+    /// couldn't tell them apart.
+    fn track(&self, id: &Value, cancelled: &Arc<AtomicBool>) -> bool {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if id.is_null() || pending.contains_key(&id.to_string()) {
+            return false;
+        }
+        pending.insert(id.to_string(), Arc::clone(cancelled));
+        true
+    }
+
+    /// Run a queued job and answer it, unless it was cancelled meanwhile.
+    fn run(&self, job: Job) {
+        let result = dispatch(&job.method, job.params, &job.cancelled).transpose();
+        // Whoever removes the pending entry owns the response
+        let owned = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&job.id.to_string())
+            .is_some();
+        if let (true, Some(result)) = (owned, result) {
+            self.reply(&job.id, result);
+        }
+    }
+}
+
+/// Serve JSON-RPC requests from `input` until EOF or `shutdown`, running up
+/// to `jobs` verifications at once. Returns after in-flight requests finish.
+pub fn serve<R: BufRead, W: Write + Send>(
+    mut input: R,
+    output: W,
+    jobs: usize,
+) -> Result<(), FsdbgError> {
+    let state = State {
+        output: Mutex::new(output),
+        pending: Mutex::new(HashMap::new()),
+    };
+    let uploads = tempfile::tempdir()?;
+    let (queue, queued) = mpsc::channel::<Job>();
+    let queued = Mutex::new(queued);
+
+    std::thread::scope(|scope| -> Result<(), FsdbgError> {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                let job = queued.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                match job {
+                    Ok(job) => state.run(job),
+                    // The reader loop ended and the queue is drained
+                    Err(_) => break,
+                }
+            });
+        }
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value: Value = match serde_json::from_str(&line) {
+                Ok(value) => value,
+                Err(e) => {
+                    state.reply(&Value::Null, Err((PARSE_ERROR, e.to_string())));
+                    continue;
+                }
+            };
+            let notification = value.as_object().is_some_and(|o| !o.contains_key("id"));
+            let request: Request = match serde_json::from_value(value) {
+                Ok(request) => request,
+                Err(e) => {
+                    if !notification {
+                        state.reply(&Value::Null, Err((INVALID_REQUEST, e.to_string())));
+                    }
+                    continue;
+                }
+            };
+            let id = request.id.unwrap_or(Value::Null);
+            let reply = |result| {
+                if !notification {
+                    state.reply(&id, result);
+                }
+            };
+
+            match request.method.as_str() {
+                "shutdown" => {
+                    reply(Ok(Value::Null));
+                    break;
+                }
+                "cancel" => {
+                    let result = parse::<CancelParams>(request.params)
+                        .map(|params| json!({ "cancelled": cancel(&state, &params.id) }));
+                    reply(result);
+                }
+                "upload" => {
+                    let params = match parse::<UploadParams>(request.params) {
+                        Ok(params) => params,
+                        Err(error) => {
+                            reply(Err(error));
+                            break;
+                        }
+                    };
+                    reply(upload(&mut input, &uploads, &params));
+                }
+                "verify" | "inspect" if notification => {}
+                "verify" | "inspect" => {
+                    let cancelled = Arc::new(AtomicBool::new(false));
+                    if !state.track(&id, &cancelled) {
+                        reply(Err((
+                            INVALID_REQUEST,
+                            format!("Request id {} is null or already in flight", id),
+                        )));
+                        continue;
+                    }
+                    let job = Job {
+                        id: id.clone(),
+                        method: request.method,
+                        params: request.params,
+                        cancelled,
+                    };
+                    queue.send(job).expect("workers run until the queue closes");
+                }
+                other => reply(Err((
+                    METHOD_NOT_FOUND,
+                    format!("Unknown method: {}", other),
+                ))),
+            }
+        }
+        // Workers finish what is queued, then see the queue close
+        drop(queue);
+        Ok(())
+    })
+}
+
+/// Store the `size` bytes following an `upload` request line. Only the
+/// name's last component is kept, as for HTTP uploads. Refused uploads are
+/// still read, and dropped, so the next request line is found.
+fn upload(
+    input: &mut impl Read,
+    uploads: &tempfile::TempDir,
+    params: &UploadParams,
+) -> Result<Value, (i64, String)> {
+    let mut content = input.take(params.size);
+    let max = Limits::default().max_total;
+    let file_name = Path::new(&params.name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .filter(|n| !n.is_empty());
+    let refusal = match file_name {
+        _ if params.size > max => Some(format!("Upload exceeds the {}-byte limit", max)),
+        None => Some(format!("Invalid upload name: {}", params.name)),
+        Some(_) => None,
+    };
+    if let Some(refusal) = refusal {
+        io::copy(&mut content, &mut io::sink()).map_err(|e| (SERVER_ERROR, e.to_string()))?;
+        return Err((INVALID_PARAMS, refusal));
+    }
+
+    let file_name = file_name.expect("refused above");
+    let (path, mut file) = Destination::new(uploads.path())
+        .create_file(file_name)
+        .map_err(rpc_error)?;
+    let copied = io::copy(&mut content, &mut file).map_err(|e| (SERVER_ERROR, e.to_string()))?;
+    if copied < params.size {
+        return Err((
+            INVALID_PARAMS,
+            format!("Input ended {} bytes into the upload", copied),
+        ));
+    }
+    Ok(json!({ "path": path, "size": copied }))
+}
+
+/// Cancel an in-flight request, answering it with a cancellation error.
+fn cancel<W: Write>(state: &State<W>, id: &Value) -> bool {
+    let flag = state
+        .pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id.to_string());
+    match flag {
+        Some(flag) => {
+            flag.store(true, Ordering::SeqCst);
+            state.reply(id, Err((REQUEST_CANCELLED, "Request cancelled".into())));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Run a worker method; None if it was cancelled before finishing.
+fn dispatch(
+    method: &str,
+    params: Value,
+    cancelled: &AtomicBool,
+) -> Result<Option<Value>, (i64, String)> {
+    let stopped = || cancelled.load(Ordering::SeqCst);
+    if stopped() {
+        return Ok(None);
+    }
+    match method {
+        "verify" => {
+            let params: VerifyParams = parse(params)?;
+            if params.checklist != auto::AUTO
+                && ChecklistType::parse_name(&params.checklist).is_none()
+            {
+                return Err((
+                    INVALID_PARAMS,
+                    format!("Unknown checklist type: {}", params.checklist),
+                ));
+            }
+            let (_, archive) = Archive::open(&params.path).map_err(rpc_error)?;
+            if stopped() {
+                return Ok(None);
+            }
+            let (checklist, mut report) =
+                archive.verify_named(&params.checklist).map_err(rpc_error)?;
+            if stopped() {
+                return Ok(None);
+            }
+            report.fingerprint = Some(Fingerprint::of(&params.path).map_err(rpc_error)?);
+            Ok(Some(report_json(&params.path, checklist, &report)))
+        }
+        "inspect" => {
+            let params: InspectParams = parse(params)?;
+            let (format, archive) = Archive::open(&params.path).map_err(rpc_error)?;
+            Ok(Some(archive.summary(&params.path, format)))
+        }
+        _ => unreachable!("dispatch only receives worker methods"),
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, (i64, String)> {
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))
+}
+
+fn rpc_error(error: FsdbgError) -> (i64, String) {
+    let code = match error.code() {
+        ErrorCode::InvalidArgument => INVALID_PARAMS,
+        _ => SERVER_ERROR,
+    };
+    (code, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: &str) -> Vec<Value> {
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output, 2).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_errors_and_shutdown() {
+        let responses = run(concat!(
+            "not json\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"frobnicate\"}\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"verify\",\"params\":{\"path\":\"/nonexistent.img\",\"type\":\"rootfs\"}}\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"cancel\",\"params\":{\"id\":99}}\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":4,\"method\":\"shutdown\"}\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":5,\"method\":\"inspect\",\"params\":{\"path\":\"/x\"}}\n",
+        ));
+        let by_id = |id: i64| responses.iter().find(|r| r["id"] == id).unwrap();
+
+        assert_eq!(responses[0]["error"]["code"], PARSE_ERROR);
+        assert_eq!(by_id(1)["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(by_id(2)["error"]["code"], SERVER_ERROR);
+        assert_eq!(by_id(3)["result"]["cancelled"], false);
+        assert!(by_id(4)["result"].is_null());
+        // Nothing is read after shutdown
+        assert!(responses.iter().all(|r| r["id"] != 5));
+    }
+
+    #[test]
+    fn test_notifications_get_no_reply() {
+        let responses = run(concat!(
+            "{\"jsonrpc\":\"2.0\",\"method\":\"frobnicate\"}\n",
+            "{\"jsonrpc\":\"2.0\",\"method\":\"cancel\",\"params\":{\"id\":7}}\n",
+            "{\"jsonrpc\":\"2.0\",\"method\":\"inspect\",\"params\":{\"path\":\"/x\"}}\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":null,\"method\":\"inspect\",\"params\":{\"path\":\"/x\"}}\n",
+            "{\"jsonrpc\":\"2.0\",\"method\":\"shutdown\"}\n",
+        ));
+        // Only the null-id inspect is answered, and refused
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0]["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_duplicate_ids_rejected() {
+        let state = State {
+            output: Mutex::new(Vec::new()),
+            pending: Mutex::new(HashMap::new()),
+        };
+        let flag = Arc::new(AtomicBool::new(false));
+        assert!(state.track(&json!(1), &flag));
+        assert!(!state.track(&json!(1), &flag));
+        assert!(state.track(&json!("1"), &flag));
+        assert!(!state.track(&Value::Null, &flag));
+        assert!(cancel(&state, &json!(1)));
+        assert!(state.track(&json!(1), &flag));
+    }
+
+    #[test]
+    fn test_upload_streams_artifact() {
+        let responses = run(concat!(
+            "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"upload\",\"params\":{\"name\":\"../x.img\",\"size\":6}}\n",
+            "07070\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"cancel\",\"params\":{\"id\":1}}\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"upload\",\"params\":{\"name\":\"y.img\",\"size\":99}}\n",
+            "short",
+        ));
+        let by_id = |id: i64| responses.iter().find(|r| r["id"] == id).unwrap();
+
+        assert_eq!(by_id(1)["result"]["size"], 6);
+        assert!(by_id(1)["result"]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("/x.img"));
+        // The bytes after an upload aren't read as requests
+        assert_eq!(by_id(2)["result"]["cancelled"], false);
+        assert_eq!(by_id(3)["error"]["code"], INVALID_PARAMS);
+        assert_eq!(responses.len(), 3);
+    }
+}
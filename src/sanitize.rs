@@ -0,0 +1,225 @@
+//! Safe destinations for archive contents
+//!
+//! Entry names in an untrusted archive decide where its contents land on
+//! disk. `/etc/shadow`, `../../home/user/.bashrc`, or `lib -> /usr/lib`
+//! followed by `lib/libc.so.6` all write outside the directory being
+//! extracted into. Anything that writes archive contents (or other
+//! client-chosen names) to disk goes through a [`Destination`], which maps
+//! names to paths below its root and refuses the ones that would leave it.
+//!
+//! Symlinks inside the root are fine to create and to write through while
+//! they resolve within it, so merged-usr trees (`bin -> usr/bin`) extract
+//! as they are.
+
+use crate::error::FsdbgError;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+fn unsafe_name(name: &str, why: impl std::fmt::Display) -> FsdbgError {
+    FsdbgError::invalid_format(format!("Unsafe entry name '{}': {}", name, why))
+}
+
+/// `name` as a relative path of normal components. Absolute names, `..`
+/// components and NULs are refused; `.` components and repeated slashes
+/// are dropped, so `./etc//hostname` is `etc/hostname`.
+pub fn entry_path(name: &str) -> Result<PathBuf, FsdbgError> {
+    if name.starts_with('/') {
+        return Err(unsafe_name(name, "absolute path"));
+    }
+    if name.contains('\0') {
+        return Err(unsafe_name(name, "contains a NUL byte"));
+    }
+    let mut path = PathBuf::new();
+    for component in name.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => return Err(unsafe_name(name, "'..' component")),
+            component => path.push(component),
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err(unsafe_name(name, "names no file"));
+    }
+    Ok(path)
+}
+
+/// A directory archive contents are written into
+#[derive(Debug, Clone)]
+pub struct Destination {
+    root: PathBuf,
+}
+
+impl Destination {
+    /// Write below `root`, which must exist.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Where `name` goes below the root. Fails for unsafe names, for names
+    /// reaching through a symlink that leads outside the root, and for
+    /// names that are a symlink already (writing would follow it).
+    pub fn resolve(&self, name: &str) -> Result<PathBuf, FsdbgError> {
+        self.walk(name, false)
+    }
+
+    /// [`resolve`](Self::resolve), with `follow_last` accepting a last
+    /// component that's a symlink staying inside the root.
+    fn walk(&self, name: &str, follow_last: bool) -> Result<PathBuf, FsdbgError> {
+        let relative = entry_path(name)?;
+        let root =
+            fs::canonicalize(&self.root).map_err(|e| FsdbgError::from(e).with_path(&self.root))?;
+        let last = relative.components().count();
+        let mut path = self.root.clone();
+        for (i, component) in relative.components().enumerate() {
+            path.push(component);
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                // Nothing below a missing directory exists either
+                break;
+            };
+            if !metadata.file_type().is_symlink() {
+                continue;
+            }
+            if i + 1 == last && !follow_last {
+                return Err(
+                    unsafe_name(name, "already a symlink in the destination").with_path(&self.root)
+                );
+            }
+            // Dangling links fail to canonicalize: what they'd create is unknown
+            if !fs::canonicalize(&path).is_ok_and(|target| target.starts_with(&root)) {
+                let link = path.strip_prefix(&self.root).unwrap_or(&path);
+                return Err(unsafe_name(
+                    name,
+                    format!("symlink {} leads outside the destination", link.display()),
+                )
+                .with_path(&self.root));
+            }
+        }
+        Ok(self.root.join(relative))
+    }
+
+    /// Create `name` for writing, with its parent directories. An existing
+    /// file is replaced.
+    pub fn create_file(&self, name: &str) -> Result<(PathBuf, File), FsdbgError> {
+        let path = self.resolve(name)?;
+        self.create_parent(&path)?;
+        let locate = |e: std::io::Error| FsdbgError::from(e).with_path(&path);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(locate(e)),
+            _ => {}
+        }
+        // resolve() checked the name isn't a symlink; create_new refuses
+        // one that appeared since rather than following it
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(locate)?;
+        Ok((path, file))
+    }
+
+    /// Write `content` to `name`.
+    pub fn write_file(&self, name: &str, content: &[u8]) -> Result<PathBuf, FsdbgError> {
+        let (path, mut file) = self.create_file(name)?;
+        file.write_all(content)
+            .map_err(|e| FsdbgError::from(e).with_path(&path))?;
+        Ok(path)
+    }
+
+    /// Create directory `name` and its parents.
+    pub fn create_dir(&self, name: &str) -> Result<PathBuf, FsdbgError> {
+        let path = self.resolve(name)?;
+        fs::create_dir_all(&path).map_err(|e| FsdbgError::from(e).with_path(&path))?;
+        Ok(path)
+    }
+
+    /// Create symlink `name` pointing at `target`. The target isn't
+    /// checked: a link may point anywhere, but later names can only be
+    /// written through it while it resolves inside the root.
+    pub fn symlink(&self, name: &str, target: &str) -> Result<PathBuf, FsdbgError> {
+        let path = self.resolve(name)?;
+        self.create_parent(&path)?;
+        std::os::unix::fs::symlink(target, &path)
+            .map_err(|e| FsdbgError::from(e).with_path(&path))?;
+        Ok(path)
+    }
+
+    /// Remove `name` if it exists: a file, a symlink (not what it points
+    /// to), or a directory and everything in it.
+    pub fn remove(&self, name: &str) -> Result<(), FsdbgError> {
+        let relative = entry_path(name)?;
+        let dir = match relative.parent().and_then(Path::to_str) {
+            Some(parent) if !parent.is_empty() => self.walk(parent, true)?,
+            _ => self.root.clone(),
+        };
+        let path = dir.join(relative.file_name().expect("entry_path names a file"));
+        let removed = match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&path),
+            Ok(_) => fs::remove_file(&path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        };
+        removed.map_err(|e| FsdbgError::from(e).with_path(&path))
+    }
+
+    fn create_parent(&self, path: &Path) -> Result<(), FsdbgError> {
+        match path.parent() {
+            Some(parent) => {
+                fs::create_dir_all(parent).map_err(|e| FsdbgError::from(e).with_path(parent))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destination() {
+        assert_eq!(
+            entry_path("./etc//hostname").unwrap(),
+            Path::new("etc/hostname")
+        );
+        for name in ["/etc/shadow", "../x", "usr/../../x", "./", "", "a\0b"] {
+            assert!(entry_path(name).is_err(), "{:?}", name);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let dest = Destination::new(dir.path());
+
+        dest.create_dir("usr/bin").unwrap();
+        dest.symlink("bin", "usr/bin").unwrap();
+        let written = dest.write_file("bin/sh", b"#!").unwrap();
+        assert_eq!(fs::read(dir.path().join("usr/bin/sh")).unwrap(), b"#!");
+        assert_eq!(written, dir.path().join("bin/sh"));
+
+        // A link out of the root can be created but not written through
+        dest.symlink("etc", outside.path().to_str().unwrap())
+            .unwrap();
+        let error = dest
+            .write_file("etc/passwd", b"root::0:0::/:/bin/sh")
+            .unwrap_err();
+        assert!(error.message().contains("symlink etc leads outside"));
+        assert!(!outside.path().join("passwd").exists());
+
+        // Nor is a link itself overwritten, nor a dangling link followed
+        assert!(dest.write_file("etc", b"").is_err());
+        dest.symlink("lib", "missing/dir").unwrap();
+        assert!(dest.create_dir("lib/modules").is_err());
+        assert!(!dir.path().join("missing").exists());
+
+        // Removing a link removes the link, through links that stay inside
+        dest.remove("bin/sh").unwrap();
+        assert!(!dir.path().join("usr/bin/sh").exists());
+        dest.remove("etc").unwrap();
+        assert!(outside.path().exists() && !dir.path().join("etc").exists());
+        assert!(dest.remove("lib/modules").is_err());
+    }
+}
@@ -0,0 +1,466 @@
+//! HTTP API server
+//!
+//! Long-running mode for dashboards that want on-demand inspection and
+//! verification without spawning a CLI per request. Endpoints (all JSON):
+//! - `GET  /health`
+//! - `POST /upload?name=FILE` - store the request body, returns its `path`
+//! - `GET  /inspect?path=PATH` - format and entry counts
+//! - `GET  /verify?path=PATH&type=TYPE` - checklist report
+//!
+//! Only uploads and paths under the served root may be opened; without a
+//! root, only uploads. Upload bodies are capped (see
+//! [`Server::with_max_upload`]).
+//!
+//! Parsed archives are cached between requests, keyed by path and
+//! invalidated when the file's size or mtime changes. The cache holds at
+//! most [`MAX_CACHED`] archives, dropping the least recently used. Requests
+//! are handled one at a time; the cache is the expensive part and isn't
+//! shared.
+
+use crate::checklist::auto::{self, Selection};
+use crate::checklist::{self, ChecklistType, VerificationReport};
+use crate::cpio::{CpioReader, Limits};
+use crate::erofs::ErofsReader;
+use crate::error::{ErrorCode, FsdbgError};
+use crate::iso::IsoReader;
+use crate::report::Fingerprint;
+use crate::sanitize::Destination;
+use crate::ArchiveFormat;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tiny_http::{Header, Method, Request, Response};
+
+/// A parsed archive of any supported format.
+pub enum Archive {
+    Cpio(CpioReader),
+    Iso(IsoReader),
+    Erofs(ErofsReader),
+}
+
+impl Archive {
+    /// Detect the format of `path` and parse it.
+    pub fn open(path: &Path) -> Result<(ArchiveFormat, Self), FsdbgError> {
+        let format = crate::detect_format(path)?;
+        let archive = match format {
+            ArchiveFormat::Cpio | ArchiveFormat::CpioGzip => Archive::Cpio(CpioReader::open(path)?),
+            ArchiveFormat::Iso => Archive::Iso(IsoReader::open(path)?),
+            ArchiveFormat::Erofs => Archive::Erofs(ErofsReader::open(path)?),
+        };
+        Ok((format, archive))
+    }
+
+    /// Format and entry counts, as returned by `inspect`.
+    pub fn summary(&self, path: &Path, format: ArchiveFormat) -> Value {
+        let (files, directories, symlinks, total_size) = match self {
+            Archive::Cpio(reader) => {
+                let s = reader.stats();
+                (s.files, s.directories, s.symlinks, s.total_size)
+            }
+            Archive::Iso(reader) => {
+                let s = reader.stats();
+                (s.files, s.directories, s.symlinks, s.total_size)
+            }
+            Archive::Erofs(reader) => {
+                let s = reader.stats();
+                (s.files, s.directories, s.symlinks, s.total_size)
+            }
+        };
+        let volume_id = match self {
+            Archive::Iso(reader) => reader.volume_id().map(str::to_string),
+            _ => None,
+        };
+
+        json!({
+            "path": path,
+            "format": format.to_string(),
+            "files": files,
+            "directories": directories,
+            "symlinks": symlinks,
+            "total_size": total_size,
+            "volume_id": volume_id,
+        })
+    }
+
+    /// The checklist for this archive's contents (verify type `auto`).
+    pub fn select(&self) -> Result<Selection, FsdbgError> {
+        match self {
+            Archive::Cpio(reader) => auto::select_cpio(reader),
+            Archive::Iso(_) => Ok(Selection::new(ChecklistType::Iso, "ISO 9660 image")),
+            Archive::Erofs(_) => Err(FsdbgError::unsupported_format(
+                "checklist verification only supports CPIO and ISO archives",
+            )),
+        }
+    }
+
+    /// Run the checklist named `checklist_type`, or the one [`select`](Self::select)
+    /// picks for `auto`.
+    pub fn verify_named(
+        &self,
+        checklist_type: &str,
+    ) -> Result<(ChecklistType, VerificationReport), FsdbgError> {
+        let checklist = if checklist_type == auto::AUTO {
+            self.select()?.checklist
+        } else {
+            ChecklistType::parse_name(checklist_type).ok_or_else(|| {
+                FsdbgError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("Unknown checklist type: {}", checklist_type),
+                )
+            })?
+        };
+        Ok((checklist, self.verify(checklist)?))
+    }
+
+    pub fn verify(&self, checklist: ChecklistType) -> Result<VerificationReport, FsdbgError> {
+        match self {
+            Archive::Cpio(reader) => checklist::verify_cpio(reader, checklist),
+            Archive::Iso(reader) => checklist::verify_iso(reader, checklist),
+            Archive::Erofs(_) => Err(FsdbgError::unsupported_format(
+                "checklist verification only supports CPIO and ISO archives",
+            )),
+        }
+    }
+}
+
+/// Report plus summary counts, as returned by `verify`.
+pub fn report_json(path: &Path, checklist: ChecklistType, report: &VerificationReport) -> Value {
+    json!({
+        "path": path,
+        "checklist": checklist.name(),
+        "success": report.is_success(),
+        "passed": report.passed(),
+        "failed": report.failed(),
+        "skipped": report.skipped(),
+        "timings_us": report
+            .timings_by_category()
+            .into_iter()
+            .map(|(category, total)| (category.key(), total.as_micros() as u64))
+            .collect::<std::collections::BTreeMap<_, _>>(),
+        "report": report,
+    })
+}
+
+/// Most parsed archives kept between requests
+pub const MAX_CACHED: usize = 8;
+
+struct CacheEntry {
+    len: u64,
+    mtime: Option<SystemTime>,
+    format: ArchiveFormat,
+    archive: Arc<Archive>,
+    /// Computed on first verify; hashing a large ISO isn't free
+    fingerprint: Option<Fingerprint>,
+    /// Request count when last opened, for eviction
+    used: u64,
+}
+
+/// HTTP API state: archive cache and upload directory.
+pub struct Server {
+    cache: HashMap<PathBuf, CacheEntry>,
+    /// Requests that opened an archive so far
+    opens: u64,
+    uploads: tempfile::TempDir,
+    /// Paths under this directory may be opened besides uploads; without
+    /// it, only uploads
+    root: Option<PathBuf>,
+    /// Largest upload body accepted, in bytes
+    max_upload: u64,
+}
+
+impl Server {
+    /// A server opening uploads and paths under `root`; only uploads if
+    /// `root` is None.
+    pub fn new(root: Option<&Path>) -> Result<Self, FsdbgError> {
+        let root = match root {
+            Some(root) => Some(
+                root.canonicalize()
+                    .map_err(|_| FsdbgError::file_not_found(root))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            cache: HashMap::new(),
+            opens: 0,
+            uploads: tempfile::tempdir()?,
+            root,
+            max_upload: Limits::default().max_total,
+        })
+    }
+
+    /// Reject upload bodies over `bytes` (default [`Limits::max_total`]).
+    pub fn with_max_upload(mut self, bytes: u64) -> Self {
+        self.max_upload = bytes;
+        self
+    }
+
+    /// Listen on `addr` and serve requests until the process is stopped.
+    pub fn run(&mut self, addr: &str) -> Result<(), FsdbgError> {
+        let server = tiny_http::Server::http(addr).map_err(|e| {
+            FsdbgError::new(
+                ErrorCode::IoError,
+                format!("Cannot listen on {}: {}", addr, e),
+            )
+        })?;
+
+        for mut request in server.incoming_requests() {
+            let (status, body) = match self.handle(&mut request) {
+                Ok(body) => (200, body),
+                Err(e) => (status_for(&e), json!({ "error": e.to_string() })),
+            };
+            let response = Response::from_string(body.to_string())
+                .with_status_code(status)
+                .with_header(
+                    Header::from_bytes("Content-Type", "application/json")
+                        .expect("static header is valid"),
+                );
+            // A client hanging up mid-response isn't our problem
+            let _ = request.respond(response);
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, request: &mut Request) -> Result<Value, FsdbgError> {
+        let (route, query) = match request.url().split_once('?') {
+            Some((route, query)) => (route.to_string(), parse_query(query)),
+            None => (request.url().to_string(), HashMap::new()),
+        };
+
+        match (request.method(), route.as_str()) {
+            (Method::Get, "/health") => Ok(json!({ "status": "ok" })),
+            (Method::Post, "/upload") => {
+                let name = required(&query, "name")?;
+                self.upload(name, request.as_reader())
+            }
+            (Method::Get, "/inspect") => self.inspect(Path::new(required(&query, "path")?)),
+            (Method::Get, "/verify") => self.verify(
+                Path::new(required(&query, "path")?),
+                required(&query, "type")?,
+            ),
+            _ => Err(FsdbgError::new(
+                ErrorCode::FileNotFound,
+                format!("No route for {} {}", request.method(), route),
+            )),
+        }
+    }
+
+    /// Store an uploaded artifact; the name's extension is kept because
+    /// format detection falls back to it when no magic matches.
+    fn upload(&mut self, name: &str, body: &mut dyn io::Read) -> Result<Value, FsdbgError> {
+        let file_name = Path::new(name)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| FsdbgError::new(ErrorCode::InvalidArgument, "Invalid upload name"))?;
+        let (path, mut file) = Destination::new(self.uploads.path()).create_file(file_name)?;
+
+        let copied = io::copy(
+            &mut io::Read::take(body, self.max_upload.saturating_add(1)),
+            &mut file,
+        )?;
+        self.cache.remove(&path);
+        if copied > self.max_upload {
+            drop(file);
+            fs::remove_file(&path)?;
+            return Err(FsdbgError::limit_exceeded(format!(
+                "Upload exceeds the {}-byte limit",
+                self.max_upload
+            )));
+        }
+
+        Ok(json!({
+            "path": path,
+            "size": fs::metadata(&path)?.len(),
+        }))
+    }
+
+    fn inspect(&mut self, path: &Path) -> Result<Value, FsdbgError> {
+        let (path, format, archive) = self.open(path)?;
+        Ok(archive.summary(&path, format))
+    }
+
+    fn verify(&mut self, path: &Path, checklist_type: &str) -> Result<Value, FsdbgError> {
+        let (path, _, archive) = self.open(path)?;
+        let (checklist, mut report) = archive.verify_named(checklist_type)?;
+        report.fingerprint = Some(self.fingerprint(&path)?);
+        Ok(report_json(&path, checklist, &report))
+    }
+
+    /// Fingerprint of an opened (cached) archive.
+    fn fingerprint(&mut self, path: &Path) -> Result<Fingerprint, FsdbgError> {
+        let entry = self
+            .cache
+            .get_mut(path)
+            .expect("archive was opened before fingerprinting");
+        if entry.fingerprint.is_none() {
+            entry.fingerprint = Some(Fingerprint::of(path)?);
+        }
+        Ok(entry.fingerprint.clone().expect("fingerprint was just set"))
+    }
+
+    /// Open an archive, reusing the cached parse if the file is unchanged.
+    fn open(&mut self, path: &Path) -> Result<(PathBuf, ArchiveFormat, Arc<Archive>), FsdbgError> {
+        let path = path
+            .canonicalize()
+            .map_err(|_| FsdbgError::file_not_found(path))?;
+        self.check_allowed(&path)?;
+
+        let meta = fs::metadata(&path)?;
+        let (len, mtime) = (meta.len(), meta.modified().ok());
+        self.opens += 1;
+        if let Some(entry) = self.cache.get_mut(&path) {
+            if entry.len == len && entry.mtime == mtime {
+                entry.used = self.opens;
+                return Ok((path, entry.format, Arc::clone(&entry.archive)));
+            }
+        }
+
+        let (format, archive) = Archive::open(&path)?;
+        let archive = Arc::new(archive);
+        self.cache.remove(&path);
+        if self.cache.len() >= MAX_CACHED {
+            let oldest = self
+                .cache
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                self.cache.remove(&oldest);
+            }
+        }
+        self.cache.insert(
+            path.clone(),
+            CacheEntry {
+                len,
+                mtime,
+                format,
+                archive: Arc::clone(&archive),
+                fingerprint: None,
+                used: self.opens,
+            },
+        );
+        Ok((path, format, archive))
+    }
+
+    fn check_allowed(&self, path: &Path) -> Result<(), FsdbgError> {
+        if path.starts_with(self.uploads.path()) {
+            return Ok(());
+        }
+        match self.root {
+            Some(ref root) if path.starts_with(root) => Ok(()),
+            Some(ref root) => Err(FsdbgError::new(
+                ErrorCode::InvalidArgument,
+                format!("Path outside served root {}", root.display()),
+            )
+            .with_path(path)),
+            None => Err(FsdbgError::new(
+                ErrorCode::InvalidArgument,
+                "No --root is served; only uploads may be opened",
+            )
+            .with_path(path)),
+        }
+    }
+}
+
+fn required<'a>(query: &'a HashMap<String, String>, key: &str) -> Result<&'a str, FsdbgError> {
+    query.get(key).map(String::as_str).ok_or_else(|| {
+        FsdbgError::new(
+            ErrorCode::InvalidArgument,
+            format!("Missing query parameter: {}", key),
+        )
+    })
+}
+
+fn status_for(error: &FsdbgError) -> u16 {
+    match error.code() {
+        ErrorCode::FileNotFound => 404,
+        ErrorCode::InvalidArgument | ErrorCode::InvalidFormat | ErrorCode::UnsupportedFormat => 400,
+        ErrorCode::ToolMissing => 503,
+        ErrorCode::LimitExceeded => 413,
+        _ => 500,
+    }
+}
+
+/// Parse an `a=1&b=2` query string, percent-decoding keys and values.
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("path=%2Fsrv%2Flevitate.iso&type=iso&flag");
+        assert_eq!(query["path"], "/srv/levitate.iso");
+        assert_eq!(query["type"], "iso");
+        assert_eq!(query["flag"], "");
+        assert_eq!(percent_decode("a+b%2"), "a b%2");
+    }
+
+    #[test]
+    fn test_uploads_only_without_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let artifact = dir.path().join("initramfs.img");
+        fs::write(&artifact, b"").unwrap();
+
+        let mut server = Server::new(None).unwrap().with_max_upload(4);
+        let error = server.open(&artifact).err().unwrap();
+        assert_eq!(error.code(), ErrorCode::InvalidArgument);
+
+        let upload = server.upload("small.img", &mut &b"abcd"[..]).unwrap();
+        let path = PathBuf::from(upload["path"].as_str().unwrap());
+        assert!(server.check_allowed(&path.canonicalize().unwrap()).is_ok());
+
+        let error = server.upload("big.img", &mut &b"abcde"[..]).err().unwrap();
+        assert_eq!(error.code(), ErrorCode::LimitExceeded);
+        assert!(!server.uploads.path().join("big.img").exists());
+
+        let mut server = Server::new(Some(dir.path())).unwrap();
+        assert!(server
+            .check_allowed(&artifact.canonicalize().unwrap())
+            .is_ok());
+        assert!(server.check_allowed(Path::new("/etc/shadow")).is_err());
+        // Allowed under the root; an empty file just doesn't parse
+        assert_ne!(
+            server.open(&artifact).err().unwrap().code(),
+            ErrorCode::InvalidArgument
+        );
+    }
+}
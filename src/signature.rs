@@ -0,0 +1,366 @@
+//! Artifact signature verification
+//!
+//! Verifies detached signatures over release artifacts:
+//! - GPG detached signatures (binary or ASCII-armored) via `gpg`
+//! - Raw ed25519 signatures (64 bytes) natively, against a raw or hex public key
+//!
+//! and produces raw ed25519 signatures for fsdbg's own output (release verdicts).
+
+use crate::checklist::{CheckCategory, CheckResult};
+use crate::error::FsdbgError;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Size of a raw ed25519 signature in bytes
+const ED25519_SIGNATURE_LEN: usize = 64;
+
+/// Size of a raw ed25519 public key (or secret key seed) in bytes
+const ED25519_KEY_LEN: usize = 32;
+
+/// Kind of detached signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureKind {
+    /// OpenPGP detached signature (checked with gpg)
+    Gpg,
+    /// Raw 64-byte ed25519 signature
+    Ed25519,
+}
+
+impl fmt::Display for SignatureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureKind::Gpg => write!(f, "GPG"),
+            SignatureKind::Ed25519 => write!(f, "ed25519"),
+        }
+    }
+}
+
+/// Result of verifying a signature
+#[derive(Debug, Clone)]
+pub struct SignatureStatus {
+    pub kind: SignatureKind,
+    pub valid: bool,
+    /// Signer identity (GPG user ID or key ID), if known
+    pub signer: Option<String>,
+    /// Human-readable detail (reason for failure, or fingerprint)
+    pub message: String,
+}
+
+impl SignatureStatus {
+    /// Convert into a check result for embedding in a verification report.
+    pub fn to_check_result(&self, sig: &Path) -> CheckResult {
+        let item = format!("{} ({})", sig.display(), self.kind);
+        if self.valid {
+            let detail = match self.signer {
+                Some(ref signer) => format!("{} - signed by {}", item, signer),
+                None => item,
+            };
+            CheckResult::pass(detail, CheckCategory::Signature)
+        } else {
+            CheckResult::fail(item, CheckCategory::Signature, self.message.clone())
+        }
+    }
+}
+
+/// Detect the signature kind from its contents.
+///
+/// Exactly 64 bytes that are not an ASCII-armored block is treated as a raw
+/// ed25519 signature; anything else is handed to gpg.
+pub fn detect_kind(sig: &[u8]) -> SignatureKind {
+    if sig.len() == ED25519_SIGNATURE_LEN && !sig.starts_with(b"-----BEGIN") {
+        SignatureKind::Ed25519
+    } else {
+        SignatureKind::Gpg
+    }
+}
+
+/// Verify a detached signature over an artifact.
+///
+/// `keyring` is a GPG keyring file for GPG signatures, or a public key file
+/// (32 raw bytes or 64 hex characters) for ed25519 signatures.
+pub fn verify(artifact: &Path, sig: &Path, keyring: &Path) -> Result<SignatureStatus, FsdbgError> {
+    for path in [artifact, sig, keyring] {
+        if !path.exists() {
+            return Err(FsdbgError::file_not_found(path));
+        }
+    }
+
+    let sig_bytes = fs::read(sig)?;
+    match detect_kind(&sig_bytes) {
+        SignatureKind::Ed25519 => verify_ed25519(artifact, &sig_bytes, keyring),
+        SignatureKind::Gpg => verify_gpg(artifact, sig, keyring),
+    }
+}
+
+fn verify_ed25519(
+    artifact: &Path,
+    sig_bytes: &[u8],
+    key_path: &Path,
+) -> Result<SignatureStatus, FsdbgError> {
+    let key_bytes = read_ed25519_key(key_path, "public")?;
+    let key = VerifyingKey::from_bytes(&key_bytes).map_err(|e| {
+        FsdbgError::invalid_format(format!("Invalid ed25519 public key: {}", e)).with_path(key_path)
+    })?;
+
+    let sig_array: [u8; ED25519_SIGNATURE_LEN] = sig_bytes
+        .try_into()
+        .map_err(|_| FsdbgError::invalid_format("ed25519 signature must be 64 bytes"))?;
+    let signature = Signature::from_bytes(&sig_array);
+
+    let data = fs::read(artifact)?;
+    let fingerprint = hex_encode(&key_bytes);
+
+    Ok(match key.verify(&data, &signature) {
+        Ok(()) => SignatureStatus {
+            kind: SignatureKind::Ed25519,
+            valid: true,
+            signer: Some(fingerprint.clone()),
+            message: format!("Good signature from key {}", fingerprint),
+        },
+        Err(_) => SignatureStatus {
+            kind: SignatureKind::Ed25519,
+            valid: false,
+            signer: None,
+            message: format!("Bad signature (key {})", fingerprint),
+        },
+    })
+}
+
+/// Sign `data` with an ed25519 secret key (32 raw bytes or 64 hex
+/// characters). Returns the raw signature and the hex public key, which is
+/// what `verify` takes as the keyring.
+pub fn sign_ed25519(
+    data: &[u8],
+    key_path: &Path,
+) -> Result<([u8; ED25519_SIGNATURE_LEN], String), FsdbgError> {
+    let key = SigningKey::from_bytes(&read_ed25519_key(key_path, "secret")?);
+    let signature = key.sign(data).to_bytes();
+    Ok((signature, hex_encode(key.verifying_key().as_bytes())))
+}
+
+/// Read an ed25519 key stored as 32 raw bytes or 64 hex characters.
+fn read_ed25519_key(path: &Path, kind: &str) -> Result<[u8; ED25519_KEY_LEN], FsdbgError> {
+    let raw = fs::read(path)?;
+
+    if raw.len() == ED25519_KEY_LEN {
+        let mut key = [0u8; ED25519_KEY_LEN];
+        key.copy_from_slice(&raw);
+        return Ok(key);
+    }
+
+    let hex = raw.trim_ascii();
+    if hex.len() != ED25519_KEY_LEN * 2 {
+        return Err(FsdbgError::invalid_format(format!(
+            "ed25519 {} key must be 32 raw bytes or 64 hex characters",
+            kind
+        ))
+        .with_path(path));
+    }
+
+    let mut key = [0u8; ED25519_KEY_LEN];
+    // Pairs of bytes, not of chars: the file may hold anything
+    for (byte, pair) in key.iter_mut().zip(hex.chunks(2)) {
+        *byte = Some(pair)
+            .filter(|pair| pair.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|pair| std::str::from_utf8(pair).ok())
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            .ok_or_else(|| {
+                FsdbgError::invalid_format(format!(
+                    "ed25519 {} key contains non-hex characters",
+                    kind
+                ))
+                .with_path(path)
+            })?;
+    }
+    Ok(key)
+}
+
+fn verify_gpg(artifact: &Path, sig: &Path, keyring: &Path) -> Result<SignatureStatus, FsdbgError> {
+    // gpg looks a bare --keyring name up in its home directory, not the
+    // working directory
+    let keyring = keyring
+        .canonicalize()
+        .map_err(|_| FsdbgError::file_not_found(keyring))?;
+    let output = Command::new("gpg")
+        .args(["--batch", "--no-default-keyring", "--status-fd", "1"])
+        .arg("--keyring")
+        .arg(&keyring)
+        .arg("--verify")
+        .arg(sig)
+        .arg(artifact)
+        .output()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                FsdbgError::tool_missing("gpg", "Install gnupg2.")
+            } else {
+                FsdbgError::external_tool_failed("gpg", e.to_string())
+            }
+        })?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let status = parse_gpg_status(&stdout);
+
+    // gpg exits non-zero on bad signatures, which is a verification result,
+    // not a tool failure. Only bail if the status output is unusable.
+    if status.is_none() && !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(FsdbgError::external_tool_failed("gpg", stderr));
+    }
+
+    Ok(status.unwrap_or(SignatureStatus {
+        kind: SignatureKind::Gpg,
+        valid: false,
+        signer: None,
+        message: "gpg produced no signature status".to_string(),
+    }))
+}
+
+/// Parse `gpg --status-fd` output into a signature status.
+fn parse_gpg_status(output: &str) -> Option<SignatureStatus> {
+    let mut good_signer = None;
+    let mut fingerprint = None;
+
+    for line in output.lines() {
+        let Some(rest) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let mut parts = rest.splitn(3, ' ');
+        let keyword = parts.next().unwrap_or("");
+        let key_id = parts.next().unwrap_or("").to_string();
+        let user_id = parts.next().map(|s| s.to_string());
+
+        match keyword {
+            "GOODSIG" => good_signer = Some(user_id.unwrap_or(key_id)),
+            "VALIDSIG" => fingerprint = Some(key_id),
+            "BADSIG" => {
+                return Some(SignatureStatus {
+                    kind: SignatureKind::Gpg,
+                    valid: false,
+                    signer: user_id,
+                    message: format!("Bad signature from key {}", key_id),
+                })
+            }
+            "NO_PUBKEY" => {
+                return Some(SignatureStatus {
+                    kind: SignatureKind::Gpg,
+                    valid: false,
+                    signer: None,
+                    message: format!("Public key {} not in keyring", key_id),
+                })
+            }
+            "EXPKEYSIG" | "REVKEYSIG" => {
+                return Some(SignatureStatus {
+                    kind: SignatureKind::Gpg,
+                    valid: false,
+                    signer: user_id,
+                    message: format!(
+                        "Signing key {} is {}",
+                        key_id,
+                        if keyword == "EXPKEYSIG" {
+                            "expired"
+                        } else {
+                            "revoked"
+                        }
+                    ),
+                })
+            }
+            "ERRSIG" => {
+                return Some(SignatureStatus {
+                    kind: SignatureKind::Gpg,
+                    valid: false,
+                    signer: None,
+                    message: format!("Cannot check signature from key {}", key_id),
+                })
+            }
+            _ => {}
+        }
+    }
+
+    good_signer.map(|signer| SignatureStatus {
+        kind: SignatureKind::Gpg,
+        valid: true,
+        message: match fingerprint {
+            Some(ref fpr) => format!("Good signature (fingerprint {})", fpr),
+            None => "Good signature".to_string(),
+        },
+        signer: Some(signer),
+    })
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_kind() {
+        assert_eq!(detect_kind(&[0u8; 64]), SignatureKind::Ed25519);
+        assert_eq!(detect_kind(&[0x89u8; 287]), SignatureKind::Gpg);
+        assert_eq!(
+            detect_kind(b"-----BEGIN PGP SIGNATURE-----\n"),
+            SignatureKind::Gpg
+        );
+    }
+
+    #[test]
+    fn test_parse_gpg_status() {
+        let good = "[GNUPG:] NEWSIG\n\
+                    [GNUPG:] GOODSIG 0123456789ABCDEF LevitateOS Release <release@levitateos.org>\n\
+                    [GNUPG:] VALIDSIG FFEEDDCCBBAA 2026-01-01 0\n";
+        let status = parse_gpg_status(good).unwrap();
+        assert!(status.valid);
+        assert_eq!(
+            status.signer.as_deref(),
+            Some("LevitateOS Release <release@levitateos.org>")
+        );
+
+        let bad = "[GNUPG:] BADSIG 0123456789ABCDEF Mallory\n";
+        assert!(!parse_gpg_status(bad).unwrap().valid);
+
+        let missing = "[GNUPG:] ERRSIG 0123456789ABCDEF 1 10 00 0 9\n\
+                       [GNUPG:] NO_PUBKEY 0123456789ABCDEF\n";
+        assert!(!parse_gpg_status(missing).unwrap().valid);
+
+        assert!(parse_gpg_status("").is_none());
+    }
+
+    #[test]
+    fn test_sign_ed25519() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("gate.key");
+        fs::write(&secret, "07".repeat(32)).unwrap();
+        let artifact = dir.path().join("verdict.json");
+        fs::write(&artifact, b"{\"passed\": true}").unwrap();
+
+        let (signature, public) = sign_ed25519(&fs::read(&artifact).unwrap(), &secret).unwrap();
+        let sig = dir.path().join("verdict.json.sig");
+        fs::write(&sig, signature).unwrap();
+        let keyring = dir.path().join("gate.pub");
+        fs::write(&keyring, &public).unwrap();
+        assert!(verify(&artifact, &sig, &keyring).unwrap().valid);
+
+        fs::write(&artifact, b"{\"passed\": false}").unwrap();
+        assert!(!verify(&artifact, &sig, &keyring).unwrap().valid);
+
+        // 64 bytes that aren't 64 hex characters
+        for key in [
+            format!("{}é", "07".repeat(31)),
+            format!("+7{}", "07".repeat(31)),
+        ] {
+            fs::write(&secret, &key).unwrap();
+            let error = sign_ed25519(b"", &secret).unwrap_err();
+            assert_eq!(
+                error.message(),
+                "ed25519 secret key contains non-hex characters"
+            );
+        }
+        fs::write(&secret, format!("{}é", "07".repeat(32))).unwrap();
+        assert!(sign_ed25519(b"", &secret).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+//! Size breakdowns (inspect --top)
+//!
+//! Answers the first question of every size regression - what got big -
+//! from an [`EntryMap`] so it works the same for every archive format.
+//! Directory sizes are recursive: a file counts toward each of its ancestors.
+
+use crate::diff::{EntryKind, EntryMap};
+use std::collections::BTreeMap;
+
+/// The `n` largest regular files, largest first (ties by path).
+pub fn largest_files(entries: &EntryMap, n: usize) -> Vec<(&str, u64)> {
+    let files = entries
+        .iter()
+        .filter(|(_, e)| e.kind == EntryKind::File)
+        .map(|(path, e)| (path.as_str(), e.size));
+    top(files, n)
+}
+
+/// Total file bytes under every directory, including nested ones.
+pub fn directory_totals(entries: &EntryMap) -> BTreeMap<&str, u64> {
+    let mut totals: BTreeMap<&str, u64> = BTreeMap::new();
+    for (path, entry) in entries {
+        if entry.kind != EntryKind::File {
+            continue;
+        }
+        let mut dir = path.as_str();
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            *totals.entry(parent).or_default() += entry.size;
+            dir = parent;
+        }
+    }
+    totals
+}
+
+/// The `n` directories holding the most file bytes, largest first.
+pub fn largest_directories(entries: &EntryMap, n: usize) -> Vec<(&str, u64)> {
+    top(directory_totals(entries), n)
+}
+
+fn top<'a>(sizes: impl IntoIterator<Item = (&'a str, u64)>, n: usize) -> Vec<(&'a str, u64)> {
+    let mut sizes: Vec<_> = sizes.into_iter().collect();
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    sizes.truncate(n);
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::DiffEntry;
+
+    fn file(size: u64) -> DiffEntry {
+        DiffEntry {
+            kind: EntryKind::File,
+            size,
+            mode: None,
+            uid: None,
+            gid: None,
+            link_target: None,
+        }
+    }
+
+    #[test]
+    fn test_largest() {
+        let entries: EntryMap = [
+            ("usr/bin/bash", file(1_400_000)),
+            ("usr/lib64/libc.so.6", file(2_300_000)),
+            ("usr/lib64/libm.so.6", file(900_000)),
+            ("etc/hostname", file(9)),
+        ]
+        .into_iter()
+        .map(|(path, entry)| (path.to_string(), entry))
+        .collect();
+
+        assert_eq!(
+            largest_files(&entries, 2),
+            [
+                ("usr/lib64/libc.so.6", 2_300_000),
+                ("usr/bin/bash", 1_400_000)
+            ]
+        );
+        assert_eq!(
+            largest_directories(&entries, 3),
+            [
+                ("usr", 4_600_000),
+                ("usr/lib64", 3_200_000),
+                ("usr/bin", 1_400_000)
+            ]
+        );
+    }
+}
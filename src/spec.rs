@@ -0,0 +1,271 @@
+//! distro-spec snapshots (spec-version, verify --spec)
+//!
+//! The checklists verify against component lists from distro-spec, compiled
+//! into fsdbg. An artifact built from an older spec fails against a newer
+//! one for reasons that have nothing to do with the build, so the lists can
+//! be dumped to a JSON snapshot and loaded back at runtime in place of the
+//! compiled-in ones.
+//!
+//! Only the component lists are part of a snapshot; the ISO layout paths
+//! stay compiled in.
+//!
+//! A profile (see [`crate::profile`]) narrows the lists further, for builds
+//! that ship only part of what the spec describes.
+//!
+//! Checklists take the spec they verify against as an argument;
+//! [`Spec::builtin`] has the compiled-in lists.
+
+use crate::error::{ErrorCode, FsdbgError};
+use distro_spec::shared;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// distro-spec version fsdbg was compiled against (from its Cargo.toml)
+pub const VERSION: &str = env!("DISTRO_SPEC_VERSION");
+
+macro_rules! spec {
+    (
+        lists { $($field:ident = $builtin:expr;)* }
+        pairs { $($pair_field:ident = $pair_builtin:expr;)* }
+    ) => {
+        /// Component lists the checklists verify against
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct Spec {
+            /// distro-spec version the lists come from
+            pub version: String,
+            $(
+                #[serde(default)]
+                pub $field: Vec<String>,
+            )*
+            $(
+                #[serde(default)]
+                pub $pair_field: Vec<(String, String)>,
+            )*
+            /// Profile the lists were narrowed to
+            #[serde(skip)]
+            pub profile: Option<String>,
+            /// The profile requires Secure Boot artifacts
+            #[serde(skip)]
+            pub secure_boot: bool,
+            /// Digest of the lists before the profile narrowed them
+            #[serde(skip)]
+            full_digest: Option<String>,
+        }
+
+        impl Spec {
+            fn compiled() -> Self {
+                Self {
+                    version: VERSION.to_string(),
+                    $($field: $builtin.iter().map(|s| s.to_string()).collect(),)*
+                    $($pair_field: $pair_builtin
+                        .iter()
+                        .map(|(a, b)| (a.to_string(), b.to_string()))
+                        .collect(),)*
+                    profile: None,
+                    secure_boot: false,
+                    full_digest: None,
+                }
+            }
+
+            /// The lists narrowed to the items `keep` accepts, as `profile`.
+            /// `keep` gets the list name and the item (the link, for pairs).
+            pub fn narrowed(&self, profile: &str, keep: impl Fn(&str, &str) -> bool) -> Spec {
+                Spec {
+                    version: self.version.clone(),
+                    $($field: self
+                        .$field
+                        .iter()
+                        .filter(|item| keep(stringify!($field), item))
+                        .cloned()
+                        .collect(),)*
+                    $($pair_field: self
+                        .$pair_field
+                        .iter()
+                        .filter(|(link, _)| keep(stringify!($pair_field), link))
+                        .cloned()
+                        .collect(),)*
+                    profile: Some(profile.to_string()),
+                    secure_boot: self.secure_boot,
+                    full_digest: Some(self.full_digest()),
+                }
+            }
+
+            /// Every list by name, pairs as `link -> target`.
+            pub fn lists(&self) -> Vec<(&'static str, Vec<String>)> {
+                vec![
+                    $((stringify!($field), self.$field.clone()),)*
+                    $((
+                        stringify!($pair_field),
+                        self.$pair_field
+                            .iter()
+                            .map(|(link, target)| format!("{} -> {}", link, target))
+                            .collect(),
+                    ),)*
+                ]
+            }
+        }
+    };
+}
+
+spec! {
+    lists {
+        bin_utils = shared::BIN_UTILS;
+        sbin_utils = shared::SBIN_UTILS;
+        systemd_binaries = shared::SYSTEMD_BINARIES;
+        udev_helpers = shared::UDEV_HELPERS;
+        levitate_tools = shared::LEVITATE_TOOLS;
+        nm_bin = shared::NM_BIN;
+        nm_sbin = shared::NM_SBIN;
+        ssh_bin = shared::SSH_BIN;
+        ssh_sbin = shared::SSH_SBIN;
+        wpa_sbin = shared::WPA_SBIN;
+        bluetooth_sbin = shared::BLUETOOTH_SBIN;
+        pipewire_sbin = shared::PIPEWIRE_SBIN;
+        polkit_sbin = shared::POLKIT_SBIN;
+        udisks_sbin = shared::UDISKS_SBIN;
+        upower_sbin = shared::UPOWER_SBIN;
+        critical_libs = shared::CRITICAL_LIBS;
+        etc_files = shared::ETC_FILES;
+        fhs_dirs = shared::FHS_DIRS;
+        system_users = shared::SYSTEM_USERS;
+        system_groups = shared::SYSTEM_GROUPS;
+        systemd_units = shared::all_systemd_units();
+        install_modules = shared::INSTALL_MODULES;
+        install_modules_builtin = shared::INSTALL_MODULES_BUILTIN;
+        live_modules = shared::LIVE_MODULES;
+        live_modules_builtin = shared::LIVE_MODULES_BUILTIN;
+        auth_bin = shared::auth::AUTH_BIN;
+        auth_sbin = shared::auth::AUTH_SBIN;
+        shadow_sbin = shared::auth::SHADOW_SBIN;
+        pam_configs = shared::auth::PAM_CONFIGS;
+        pam_modules = shared::auth::PAM_MODULES;
+        security_files = shared::auth::SECURITY_FILES;
+        sudo_libs = shared::auth::SUDO_LIBS;
+        packages = shared::PACKAGES;
+        // The build's filters (recinit drops networkd and resolved:
+        // LevitateOS uses NetworkManager)
+        excluded_bin = shared::EXCLUDED_BIN;
+        excluded_systemd_binaries = shared::EXCLUDED_SYSTEMD_BINARIES;
+        excluded_units = shared::EXCLUDED_UNITS;
+    }
+    pairs {
+        fhs_symlinks = shared::FHS_SYMLINKS;
+        alternatives = shared::ALTERNATIVES;
+    }
+}
+
+/// Items added to and removed from one list between two specs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListChange {
+    pub list: &'static str,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Spec {
+    /// The lists compiled into fsdbg.
+    pub fn builtin() -> &'static Spec {
+        static BUILTIN: OnceLock<Spec> = OnceLock::new();
+        BUILTIN.get_or_init(Spec::compiled)
+    }
+
+    /// Load a snapshot written by `fsdbg spec-version --dump`. Lists the
+    /// snapshot doesn't have are empty (they didn't exist in that spec).
+    pub fn load(path: &Path) -> Result<Spec, FsdbgError> {
+        let json = std::fs::read(path)?;
+        serde_json::from_slice(&json).map_err(|e| {
+            FsdbgError::new(
+                ErrorCode::ParseError,
+                format!("Invalid spec snapshot {}: {}", path.display(), e),
+            )
+            .with_path(path)
+        })
+    }
+
+    /// SHA256 of the lists (not the version string), hex.
+    pub fn digest(&self) -> String {
+        let json = serde_json::to_vec(&self.lists()).expect("lists serialize");
+        Sha256::digest(json)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Digest of the spec's lists before a profile narrowed them: the
+    /// digest the build's spec has.
+    pub fn full_digest(&self) -> String {
+        self.full_digest.clone().unwrap_or_else(|| self.digest())
+    }
+
+    /// `0.1.0 (1a2b3c4d5e6f)`: version plus short digest, as shown in
+    /// reports, and the profile if there is one.
+    pub fn id(&self) -> String {
+        let id = format!("{} ({})", self.version, &self.full_digest()[..12]);
+        match self.profile {
+            Some(ref profile) => format!("{}, profile {}", id, profile),
+            None => id,
+        }
+    }
+
+    /// Lists that differ from `other`: what `other` adds and removes.
+    pub fn compare(&self, other: &Spec) -> Vec<ListChange> {
+        let theirs = other.lists();
+        self.lists()
+            .into_iter()
+            .zip(theirs)
+            .filter_map(|((list, ours), (_, theirs))| {
+                let added: Vec<String> = theirs
+                    .iter()
+                    .filter(|item| !ours.contains(item))
+                    .cloned()
+                    .collect();
+                let removed: Vec<String> = ours
+                    .iter()
+                    .filter(|item| !theirs.contains(item))
+                    .cloned()
+                    .collect();
+                (!added.is_empty() || !removed.is_empty()).then_some(ListChange {
+                    list,
+                    added,
+                    removed,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let builtin = Spec::builtin();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spec.json");
+        std::fs::write(&path, serde_json::to_vec(builtin).unwrap()).unwrap();
+
+        let loaded = Spec::load(&path).unwrap();
+        assert_eq!(loaded.id(), builtin.id());
+        assert!(builtin.compare(&loaded).is_empty());
+
+        // An older snapshot: one list changed, one list didn't exist yet
+        std::fs::write(
+            &path,
+            r#"{"version": "0.0.9", "bin_utils": ["ls", "vi"], "fhs_symlinks": [["bin", "usr/bin"]]}"#,
+        )
+        .unwrap();
+        let old = Spec::load(&path).unwrap();
+        assert_eq!(old.bin_utils, ["ls", "vi"]);
+        assert!(old.sbin_utils.is_empty());
+        assert_ne!(old.digest(), builtin.digest());
+
+        let changes = builtin.compare(&old);
+        let bin = changes.iter().find(|c| c.list == "bin_utils").unwrap();
+        assert_eq!(bin.added, ["vi"]);
+        assert!(bin.removed.contains(&"systemctl".to_string()));
+        assert!(!changes.iter().any(|c| c.list == "fhs_symlinks"));
+    }
+}
@@ -0,0 +1,515 @@
+//! Tar archives (netboot tarballs)
+//!
+//! Netboot trees travel as tarballs of what the tftp/http server serves.
+//! fsdbg doesn't list tar in place: [`extract`] unpacks one, gzip-compressed
+//! or not, through a [`Destination`] so no name lands outside the target,
+//! and the checklists read the tree with [`DirReader`](crate::dir::DirReader).
+//!
+//! ustar and GNU headers are read, with GNU long names and pax `path` and
+//! `linkpath` records. Regular files, directories, symlinks and hard links
+//! (as copies) are extracted with their permission bits; owners aren't, so
+//! no root is needed, and devices and FIFOs are skipped.
+
+use crate::error::FsdbgError;
+use crate::sanitize::Destination;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, Permissions};
+use std::io::{self, BufReader, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+const BLOCK: usize = 512;
+
+/// `ustar\0` (POSIX) or `ustar ` (GNU) at 257
+const USTAR_MAGIC: &[u8] = b"ustar";
+
+/// Open `path` as a tar stream, decompressing gzip.
+fn open(path: &Path) -> Result<Box<dyn Read>, FsdbgError> {
+    let file = File::open(path).map_err(|_| FsdbgError::file_not_found(path))?;
+    crate::cpio::decompress(BufReader::new(file))
+}
+
+/// True if the file at `path` is a tar archive, compressed with gzip or not.
+pub fn is_tar(path: &Path) -> Result<bool, FsdbgError> {
+    let mut header = Vec::new();
+    // Too short to decompress is too short to be tar
+    let Ok(stream) = open(path) else {
+        return Ok(false);
+    };
+    if stream.take(BLOCK as u64).read_to_end(&mut header).is_err() {
+        return Ok(false);
+    }
+    Ok(header.len() == BLOCK
+        && header[257..262] == *USTAR_MAGIC
+        && crate::detect::valid_tar_checksum(&header))
+}
+
+/// NUL-terminated (or full-width) header field.
+fn field(bytes: &[u8]) -> &[u8] {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    &bytes[..end]
+}
+
+/// Numeric header field: octal digits, or base-256 with the top bit set
+/// (GNU, for sizes past 8 GiB).
+fn number(bytes: &[u8]) -> Option<u64> {
+    if bytes[0] & 0x80 != 0 {
+        return bytes[1..]
+            .iter()
+            .try_fold((bytes[0] & 0x7f) as u64, |n, b| {
+                n.checked_mul(256).map(|n| n | *b as u64)
+            });
+    }
+    let digits = String::from_utf8_lossy(field(bytes));
+    let digits = digits.trim_matches(' ');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// `path` and `linkpath` from pax extended header records
+/// (`<len> <key>=<value>\n`).
+fn pax_records(data: &[u8]) -> (Option<String>, Option<String>) {
+    let (mut path, mut link) = (None, None);
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|b| *b == b' ') {
+        let Some(len) = std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|len| len.parse::<usize>().ok())
+            .filter(|len| *len > space && *len <= rest.len())
+        else {
+            break;
+        };
+        let record = &rest[space + 1..len];
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Some(eq) = record.iter().position(|b| *b == b'=') {
+            let value = String::from_utf8_lossy(&record[eq + 1..]).into_owned();
+            match &record[..eq] {
+                b"path" => path = Some(value),
+                b"linkpath" => link = Some(value),
+                _ => {}
+            }
+        }
+        rest = &rest[len..];
+    }
+    (path, link)
+}
+
+fn truncated(offset: u64) -> FsdbgError {
+    FsdbgError::invalid_format(format!("Tar archive is truncated at offset {}", offset))
+}
+
+fn padding(size: u64) -> u64 {
+    size.next_multiple_of(BLOCK as u64) - size
+}
+
+/// A file, directory or link in a tar archive, long names applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    /// Typeflag: `0` file, `1` hard link, `2` symlink, `5` directory...
+    pub kind: u8,
+    /// Symlink or hard link target
+    pub link: String,
+    /// Permission bits
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+}
+
+/// Header-by-header reader over a tar stream
+struct Archive<R> {
+    stream: R,
+    /// Offset of the next unread byte
+    offset: u64,
+    /// Content and padding of the current entry not read yet
+    unread: u64,
+}
+
+impl<R: Read> Archive<R> {
+    fn new(stream: R) -> Self {
+        Self {
+            stream,
+            offset: 0,
+            unread: 0,
+        }
+    }
+
+    fn skip(&mut self, len: u64) -> Result<(), FsdbgError> {
+        let skipped = io::copy(&mut (&mut self.stream).take(len), &mut io::sink())?;
+        self.offset += skipped;
+        if skipped < len {
+            return Err(truncated(self.offset));
+        }
+        Ok(())
+    }
+
+    /// Read `size` bytes of a name record and its padding.
+    fn record(&mut self, size: u64) -> Result<Vec<u8>, FsdbgError> {
+        if size > crate::cpio::Limits::default().max_name as u64 * 4 {
+            return Err(FsdbgError::limit_exceeded(format!(
+                "Tar name record of {} bytes at offset {}",
+                size, self.offset
+            )));
+        }
+        let mut data = Vec::new();
+        (&mut self.stream).take(size).read_to_end(&mut data)?;
+        self.offset += data.len() as u64;
+        if (data.len() as u64) < size {
+            return Err(truncated(self.offset));
+        }
+        self.skip(padding(size))?;
+        Ok(data)
+    }
+
+    /// The next entry, skipping what's left of the current one. Long name
+    /// and pax records are folded into the entry they describe.
+    fn next(&mut self) -> Result<Option<Entry>, FsdbgError> {
+        self.skip(self.unread)?;
+        self.unread = 0;
+        let (mut long_name, mut long_link) = (None, None);
+        loop {
+            let mut header = [0u8; BLOCK];
+            let read = io::copy(
+                &mut (&mut self.stream).take(BLOCK as u64),
+                &mut &mut header[..],
+            )?;
+            let at = self.offset;
+            self.offset += read;
+            if read == 0 {
+                return Ok(None);
+            }
+            if read < BLOCK as u64 {
+                return Err(truncated(self.offset));
+            }
+            // Two zero blocks end the archive; one is enough to stop
+            if header.iter().all(|b| *b == 0) {
+                return Ok(None);
+            }
+            if !crate::detect::valid_tar_checksum(&header) {
+                return Err(FsdbgError::invalid_format(format!(
+                    "Bad tar header checksum at offset {}",
+                    at
+                )));
+            }
+            let size = number(&header[124..136]).ok_or_else(|| {
+                FsdbgError::invalid_format(format!("Bad tar entry size at offset {}", at))
+            })?;
+            match header[156] {
+                b'L' => long_name = Some(lossy(field(&self.record(size)?))),
+                b'K' => long_link = Some(lossy(field(&self.record(size)?))),
+                b'x' => {
+                    let (path, link) = pax_records(&self.record(size)?);
+                    long_name = path.or(long_name);
+                    long_link = link.or(long_link);
+                }
+                kind => {
+                    let name = long_name.unwrap_or_else(|| {
+                        let name = lossy(field(&header[..100]));
+                        let prefix = field(&header[345..500]);
+                        // POSIX ustar only; GNU keeps other fields there
+                        if header[257..263] == *b"ustar\0" && !prefix.is_empty() {
+                            format!("{}/{}", lossy(prefix), name)
+                        } else {
+                            name
+                        }
+                    });
+                    self.unread = size + padding(size);
+                    return Ok(Some(Entry {
+                        name,
+                        kind,
+                        link: long_link.unwrap_or_else(|| lossy(field(&header[157..257]))),
+                        mode: number(&header[100..108]).unwrap_or(0o644) as u32 & 0o7777,
+                        uid: number(&header[108..116]).unwrap_or(0) as u32,
+                        gid: number(&header[116..124]).unwrap_or(0) as u32,
+                        size,
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Copy the current entry's content to `out`.
+    fn copy_to(&mut self, out: &mut impl io::Write, size: u64) -> Result<(), FsdbgError> {
+        let copied = io::copy(&mut (&mut self.stream).take(size), out)?;
+        self.offset += copied;
+        self.unread -= copied;
+        if copied < size {
+            return Err(truncated(self.offset));
+        }
+        Ok(())
+    }
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Names of the entries in the tar archive at `path`, contents skipped.
+pub fn list(path: &Path) -> Result<Vec<String>, FsdbgError> {
+    let mut archive = Archive::new(open(path)?);
+    let mut names = Vec::new();
+    while let Some(entry) = archive.next().map_err(|e| e.with_path(path))? {
+        names.push(entry.name);
+    }
+    Ok(names)
+}
+
+/// Unpack the tar archive at `path` into `dest`. Returns the number of
+/// entries extracted.
+pub fn extract(path: &Path, dest: &Destination) -> Result<usize, FsdbgError> {
+    let mut archive = Archive::new(open(path)?);
+    unpack(&mut archive, dest, None).map_err(|e| e.with_path(path))
+}
+
+/// Owners of the entries of unpacked layers, by normalized path
+pub type Owners = HashMap<String, (u32, u32)>;
+
+/// OCI whiteout prefix: `.wh.<name>` deletes `<name>` from lower layers
+pub const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// OCI opaque whiteout: a directory's lower-layer contents are hidden
+pub const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+/// Unpack a container image layer over the layers below it in `dest`:
+/// whiteouts delete what they name, entries replace what's there, and
+/// `owners` records the owners the file system can't keep.
+pub fn extract_layer(
+    stream: impl Read,
+    dest: &Destination,
+    owners: &mut Owners,
+) -> Result<usize, FsdbgError> {
+    unpack(&mut Archive::new(stream), dest, Some(owners))
+}
+
+fn unpack<R: Read>(
+    archive: &mut Archive<R>,
+    dest: &Destination,
+    mut layer: Option<&mut Owners>,
+) -> Result<usize, FsdbgError> {
+    // Set last, so a read-only directory still gets its contents
+    let mut dir_modes: Vec<(PathBuf, u32)> = Vec::new();
+    // What this layer wrote, which its own whiteouts don't apply to
+    let mut written = HashSet::new();
+    let mut count = 0;
+
+    while let Some(entry) = archive.next()? {
+        let name = crate::cpio::CpioReader::normalize_path(&entry.name)
+            .trim_end_matches('/')
+            .to_string();
+        // The archive's root directory (`./`)
+        if name.is_empty() {
+            continue;
+        }
+        if let Some(owners) = layer.as_deref_mut() {
+            let (dir, base) = name.rsplit_once('/').unwrap_or(("", &name));
+            if base == OPAQUE_WHITEOUT {
+                clear_dir(dest, dir, &written)?;
+                continue;
+            }
+            if let Some(hidden) = base.strip_prefix(WHITEOUT_PREFIX) {
+                let hidden = if dir.is_empty() {
+                    hidden.to_string()
+                } else {
+                    format!("{}/{}", dir, hidden)
+                };
+                if !written.contains(&hidden) {
+                    dest.remove(&hidden)?;
+                }
+                continue;
+            }
+            // A directory merges with the one below; anything else replaces
+            if entry.kind != b'5' || !dest.resolve(&name).is_ok_and(|path| path.is_dir()) {
+                dest.remove(&name)?;
+            }
+            owners.insert(name.clone(), (entry.uid, entry.gid));
+            written.insert(name.clone());
+        }
+
+        match entry.kind {
+            // Regular file (contiguous files are regular files too)
+            b'0' | b'\0' | b'7' => {
+                let (file_path, mut file) = dest.create_file(&name)?;
+                archive
+                    .copy_to(&mut file, entry.size)
+                    .map_err(|e| e.with_path(&file_path))?;
+                fs::set_permissions(&file_path, Permissions::from_mode(entry.mode))?;
+            }
+            b'5' => dir_modes.push((dest.create_dir(&name)?, entry.mode)),
+            b'2' => {
+                dest.symlink(&name, &entry.link)?;
+            }
+            b'1' => {
+                let target = dest.resolve(&entry.link)?;
+                let (file_path, _) = dest.create_file(&name)?;
+                fs::copy(&target, &file_path)
+                    .map_err(|e| FsdbgError::from(e).with_path(&target))?;
+            }
+            // Devices, FIFOs, global pax headers, vendor extensions
+            _ => continue,
+        }
+        count += 1;
+    }
+
+    // Deepest first, so a parent's mode can't block setting a child's
+    dir_modes.sort_by(|a, b| b.0.cmp(&a.0));
+    for (dir, mode) in dir_modes {
+        fs::set_permissions(&dir, Permissions::from_mode(mode))
+            .map_err(|e| FsdbgError::from(e).with_path(&dir))?;
+    }
+    Ok(count)
+}
+
+/// Empty directory `dir` of `dest` of everything but what the current
+/// layer wrote (an opaque whiteout).
+fn clear_dir(dest: &Destination, dir: &str, written: &HashSet<String>) -> Result<(), FsdbgError> {
+    let path = if dir.is_empty() {
+        dest.root().to_path_buf()
+    } else {
+        match dest.resolve(dir) {
+            Ok(path) if path.is_dir() => path,
+            _ => return Ok(()),
+        }
+    };
+    for child in fs::read_dir(&path).map_err(|e| FsdbgError::from(e).with_path(&path))? {
+        let child = child?.file_name();
+        let child = child.to_string_lossy();
+        let name = if dir.is_empty() {
+            child.to_string()
+        } else {
+            format!("{}/{}", dir, child)
+        };
+        let prefix = format!("{}/", name);
+        if !written.iter().any(|w| *w == name || w.starts_with(&prefix)) {
+            dest.remove(&name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Tar archives built in memory for tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::BLOCK;
+
+    /// A ustar header for `name` of `typeflag`, `size` bytes.
+    fn header(name: &str, typeflag: u8, size: usize, link: &str) -> Vec<u8> {
+        let mut header = vec![0u8; BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(b"0000755");
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[156] = typeflag;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[148..156].fill(b' ');
+        let sum: u32 = header.iter().map(|b| *b as u32).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+        header
+    }
+
+    /// Append an entry with `content`, padded to the block size.
+    pub(crate) fn entry(out: &mut Vec<u8>, name: &str, typeflag: u8, content: &[u8], link: &str) {
+        out.extend(header(name, typeflag, content.len(), link));
+        out.extend_from_slice(content);
+        out.resize(out.len().next_multiple_of(BLOCK), 0);
+    }
+
+    /// The two zero blocks that end an archive.
+    pub(crate) fn finish(out: &mut Vec<u8>) {
+        out.extend([0u8; BLOCK * 2]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::entry;
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let long = format!("netboot/{}/vmlinuz", "x".repeat(120));
+        let mut tar = Vec::new();
+        entry(&mut tar, "netboot/", b'5', b"", "");
+        entry(&mut tar, "netboot/boot.ipxe", b'0', b"#!ipxe\nboot\n", "");
+        entry(
+            &mut tar,
+            "././@LongLink",
+            b'L',
+            format!("{}\0", long).as_bytes(),
+            "",
+        );
+        entry(&mut tar, "netboot/truncated-name", b'0', b"kernel", "");
+        entry(&mut tar, "netboot/default.ipxe", b'2', b"", "boot.ipxe");
+        entry(
+            &mut tar,
+            "netboot/copy.ipxe",
+            b'1',
+            b"",
+            "netboot/boot.ipxe",
+        );
+        tar.extend([0u8; BLOCK * 2]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("netboot.tar");
+        fs::write(&archive, &tar).unwrap();
+        assert!(is_tar(&archive).unwrap());
+
+        let out = dir.path().join("out");
+        fs::create_dir(&out).unwrap();
+        assert_eq!(extract(&archive, &Destination::new(&out)).unwrap(), 5);
+        assert_eq!(fs::read(out.join(&long)).unwrap(), b"kernel");
+        assert_eq!(
+            fs::read(out.join("netboot/default.ipxe")).unwrap(),
+            b"#!ipxe\nboot\n"
+        );
+        assert_eq!(
+            fs::read(out.join("netboot/copy.ipxe")).unwrap(),
+            b"#!ipxe\nboot\n"
+        );
+        assert!(fs::symlink_metadata(out.join("netboot/default.ipxe"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+
+        // Names can't leave the destination
+        let mut evil = Vec::new();
+        entry(&mut evil, "../escape", b'0', b"x", "");
+        fs::write(&archive, &evil).unwrap();
+        assert!(extract(&archive, &Destination::new(&out)).is_err());
+        assert!(!dir.path().join("escape").exists());
+
+        assert_eq!(pax_records(b"12 path=a/b\n").0.as_deref(), Some("a/b"));
+        assert_eq!(number(b"0000012\0"), Some(10));
+        fs::write(&archive, b"070701").unwrap();
+        assert!(!is_tar(&archive).unwrap());
+
+        // Layers: whiteouts delete from below, opaque dirs hide what's below
+        let root = dir.path().join("layers");
+        fs::create_dir(&root).unwrap();
+        let dest = Destination::new(&root);
+        let mut owners = Owners::new();
+        let mut lower = Vec::new();
+        entry(&mut lower, "./", b'5', b"", "");
+        entry(&mut lower, "etc/motd", b'0', b"hi", "");
+        entry(&mut lower, "etc/issue", b'0', b"hi", "");
+        entry(&mut lower, "var/cache/dnf/x", b'0', b"x", "");
+        entry(&mut lower, "bin", b'0', b"", "");
+        extract_layer(&lower[..], &dest, &mut owners).unwrap();
+        let mut upper = Vec::new();
+        entry(&mut upper, "etc/.wh.motd", b'0', b"", "");
+        entry(&mut upper, "var/cache/.wh..wh..opq", b'0', b"", "");
+        entry(&mut upper, "var/cache/kept", b'0', b"", "");
+        entry(&mut upper, "bin", b'2', b"", "usr/bin");
+        extract_layer(&upper[..], &dest, &mut owners).unwrap();
+        assert!(!root.join("etc/motd").exists() && root.join("etc/issue").exists());
+        assert!(!root.join("var/cache/dnf").exists() && root.join("var/cache/kept").exists());
+        assert_eq!(
+            fs::read_link(root.join("bin")).unwrap(),
+            Path::new("usr/bin")
+        );
+        assert_eq!(owners["etc/issue"], (0, 0));
+    }
+}
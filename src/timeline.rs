@@ -0,0 +1,122 @@
+//! Entry mtime timeline
+//!
+//! Groups CPIO entries by mtime so files packed at an unexpected time stand
+//! out. A reproducible image normally has one large group (every mtime
+//! clamped to SOURCE_DATE_EPOCH); a stray file carrying the build host's
+//! clock lands in a small group of its own.
+
+use crate::cpio::CpioEntry;
+use std::collections::BTreeMap;
+
+/// Width of a timeline bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    Second,
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Granularity {
+    pub fn parse_name(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "second" | "seconds" | "s" => Some(Granularity::Second),
+            "minute" | "minutes" | "m" => Some(Granularity::Minute),
+            "hour" | "hours" | "h" => Some(Granularity::Hour),
+            "day" | "days" | "d" => Some(Granularity::Day),
+            _ => None,
+        }
+    }
+
+    fn seconds(self) -> u64 {
+        match self {
+            Granularity::Second => 1,
+            Granularity::Minute => 60,
+            Granularity::Hour => 3600,
+            Granularity::Day => 86400,
+        }
+    }
+}
+
+/// Entries whose mtimes fall in one bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    /// Start of the bucket (seconds since the epoch)
+    pub start: u64,
+    /// Entry paths, in archive order
+    pub paths: Vec<String>,
+}
+
+/// Group entries into buckets of the given width, oldest first.
+pub fn group<'a>(
+    entries: impl IntoIterator<Item = &'a CpioEntry>,
+    granularity: Granularity,
+) -> Vec<Group> {
+    let width = granularity.seconds();
+    let mut buckets: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+    for entry in entries {
+        let mtime = u64::from(entry.mtime);
+        buckets
+            .entry(mtime - mtime % width)
+            .or_default()
+            .push(entry.path.clone());
+    }
+    buckets
+        .into_iter()
+        .map(|(start, paths)| Group { start, paths })
+        .collect()
+}
+
+/// Index of the largest group (the bulk of the image), if any.
+pub fn bulk(groups: &[Group]) -> Option<usize> {
+    (0..groups.len()).max_by_key(|&i| (groups[i].paths.len(), std::cmp::Reverse(i)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpio::FileType;
+
+    fn entry(path: &str, mtime: u32) -> CpioEntry {
+        CpioEntry {
+            path: path.to_string(),
+            raw_path: path.as_bytes().to_vec(),
+            size: 0,
+            mode: 0o100644,
+            file_type: FileType::Regular,
+            link_target: None,
+            uid: 0,
+            gid: 0,
+            nlink: 1,
+            mtime,
+            dev_major: 0,
+            dev_minor: 0,
+            rdev_major: 0,
+            rdev_minor: 0,
+            digest: None,
+        }
+    }
+
+    #[test]
+    fn test_group() {
+        let epoch = 1_700_000_000;
+        let entries = [
+            entry("usr/bin/ls", epoch),
+            entry("usr/bin/cat", epoch),
+            entry("etc/stray", epoch + 86400 * 30 + 5),
+            entry("usr/lib/libc.so.6", epoch + 10),
+        ];
+
+        let groups = group(&entries, Granularity::Second);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].paths, ["usr/bin/ls", "usr/bin/cat"]);
+        assert_eq!(bulk(&groups), Some(0));
+
+        let groups = group(&entries, Granularity::Day);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].start % 86400, 0);
+        assert_eq!(groups[0].paths.len(), 3);
+        assert_eq!(groups[1].paths, ["etc/stray"]);
+        assert_eq!(bulk(&[]), None);
+    }
+}
@@ -0,0 +1,145 @@
+//! Unified Kernel Image (UKI) inspection
+//!
+//! A UKI is a PE/COFF EFI stub with the kernel, initrd and command line
+//! embedded as named sections (`.linux`, `.initrd`, `.cmdline`, ...).
+//! Sections are read natively - no objcopy or ukify required.
+
+use crate::cpio::CpioReader;
+use crate::error::FsdbgError;
+use crate::pe::PeImage;
+use crate::ByteRange;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Section holding the kernel command line
+pub const CMDLINE_SECTION: &str = ".cmdline";
+
+/// Section holding the initramfs
+pub const INITRD_SECTION: &str = ".initrd";
+
+/// Find a named section in a PE image and return its contents.
+pub fn section<'a>(data: &'a [u8], name: &str) -> Result<Option<&'a [u8]>, FsdbgError> {
+    Ok(section_range(data, name)?.map(|range| {
+        let start = range.offset as usize;
+        &data[start..start + range.length.unwrap_or(0) as usize]
+    }))
+}
+
+/// Find a named section in a PE image and return where its contents sit in
+/// the file.
+pub fn section_range(data: &[u8], name: &str) -> Result<Option<ByteRange>, FsdbgError> {
+    let pe = PeImage::parse(data)?;
+
+    for section in &pe.sections {
+        if section.name != name {
+            continue;
+        }
+        let start = section.raw_offset as usize;
+        // Raw data is padded to the file alignment; virtual size is the real length
+        let len = if section.virtual_size != 0 {
+            section.virtual_size.min(section.raw_size)
+        } else {
+            section.raw_size
+        } as usize;
+
+        if start.checked_add(len).is_none_or(|end| end > data.len()) {
+            return Err(FsdbgError::invalid_format(format!(
+                "Section {} is truncated",
+                name
+            )));
+        }
+        return Ok(Some(ByteRange::new(start as u64, Some(len as u64))));
+    }
+
+    Ok(None)
+}
+
+/// Check for an MZ header pointing at a PE signature.
+pub fn is_pe_header(header: &[u8]) -> bool {
+    let Some(lfanew) = header.get(0x3c..0x40) else {
+        return false;
+    };
+    let lfanew = u32::from_le_bytes([lfanew[0], lfanew[1], lfanew[2], lfanew[3]]) as usize;
+    header.starts_with(b"MZ")
+        && lfanew
+            .checked_add(4)
+            .and_then(|end| header.get(lfanew..end))
+            == Some(b"PE\0\0")
+}
+
+/// True if the file at `path` is a PE image (UKI or EFI stub kernel).
+pub fn is_pe(path: &Path) -> Result<bool, FsdbgError> {
+    let mut header = Vec::new();
+    File::open(path)
+        .map_err(|_| FsdbgError::file_not_found(path))?
+        .take(crate::detect::HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(is_pe_header(&header))
+}
+
+/// Locate the embedded initramfs of a UKI.
+pub fn initrd_range(path: &Path) -> Result<ByteRange, FsdbgError> {
+    let data = std::fs::read(path)?;
+    section_range(&data, INITRD_SECTION)?.ok_or_else(|| {
+        FsdbgError::missing_required(format!("{} section (not a UKI?)", INITRD_SECTION))
+            .with_path(path)
+    })
+}
+
+/// Parse the initramfs embedded in a UKI in place, without extracting it.
+pub fn open_initrd(path: &Path) -> Result<CpioReader, FsdbgError> {
+    CpioReader::open_range(path, initrd_range(path)?)
+}
+
+/// Extract the embedded kernel command line from a UKI.
+pub fn cmdline(data: &[u8]) -> Result<Option<String>, FsdbgError> {
+    Ok(section(data, CMDLINE_SECTION)?.map(|raw| {
+        String::from_utf8_lossy(raw)
+            .trim_end_matches('\0')
+            .trim()
+            .to_string()
+    }))
+}
+
+/// Console device names from `console=` arguments (e.g. `ttyS0,115200n8` -> `ttyS0`).
+pub fn consoles(cmdline: &str) -> Vec<String> {
+    cmdline
+        .split_whitespace()
+        .filter_map(|arg| arg.strip_prefix("console="))
+        .map(|value| value.split(',').next().unwrap_or(value).to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consoles() {
+        assert_eq!(
+            consoles("root=live:LABEL=LEVITATE console=tty0 console=ttyS0,115200n8 quiet"),
+            vec!["tty0", "ttyS0"]
+        );
+        assert!(consoles("quiet splash").is_empty());
+    }
+
+    #[test]
+    fn test_section_rejects_non_pe() {
+        assert!(section(b"not a pe file", CMDLINE_SECTION).is_err());
+        assert!(!is_pe_header(b"not a pe file"));
+    }
+
+    #[test]
+    fn test_is_pe_header() {
+        let mut header = vec![0u8; 0x100];
+        header[..2].copy_from_slice(b"MZ");
+        header[0x3c] = 0x80;
+        header[0x80..0x84].copy_from_slice(b"PE\0\0");
+        assert!(is_pe_header(&header));
+
+        header[0x3c] = 0xfc;
+        assert!(!is_pe_header(&header));
+    }
+}
@@ -0,0 +1,24 @@
+//! The library's public API, pinned
+//!
+//! A change to `tests/public-api.txt` is a change library users see: a
+//! removal or signature change needs a major version. The rustdoc JSON
+//! needs a nightly toolchain, so a plain `cargo test` skips this; a nightly
+//! job runs it with `cargo test --test public_api -- --ignored`. After an
+//! intended change, regenerate the snapshot by running that with
+//! `UPDATE_SNAPSHOTS=yes`.
+
+#[test]
+#[ignore = "needs a nightly toolchain; run with --ignored"]
+fn public_api() {
+    let rustdoc_json = rustdoc_json::Builder::default()
+        .toolchain("nightly")
+        .build()
+        .unwrap();
+    // Blanket and auto trait impls follow the dependencies and the toolchain
+    let public_api = public_api::Builder::from_rustdoc_json(rustdoc_json)
+        .omit_blanket_impls(true)
+        .omit_auto_trait_impls(true)
+        .build()
+        .unwrap();
+    public_api.assert_eq_or_update("./tests/public-api.txt");
+}